@@ -0,0 +1,172 @@
+//! Benchmarks for batch verification under different request arrival
+//! patterns.
+//!
+//! Each benchmark measures the mean latency of a verification request, from
+//! submission to result. Bursts of requests are typical of the initial block
+//! download, and slow, steady requests are typical of verification near the
+//! chain tip.
+
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ed25519_zebra::*;
+use futures::stream::{FuturesUnordered, StreamExt};
+use rand::thread_rng;
+use tokio::{
+    runtime::Runtime,
+    sync::broadcast::{channel, Sender},
+    time::delay_for,
+};
+use tower::{Service, ServiceExt};
+use tower_batch::{Batch, BatchControl};
+
+/// The maximum number of items in each batch.
+const MAX_ITEMS: usize = 64;
+
+/// The maximum latency of each batch item.
+const MAX_LATENCY: Duration = Duration::from_millis(100);
+
+/// The number of requests in each benchmark iteration.
+const REQUESTS: usize = 64;
+
+/// The request arrival patterns, as a name and the interval between requests.
+const ARRIVAL_PATTERNS: &[(&str, Duration)] = &[
+    ("burst", Duration::from_millis(0)),
+    ("fast", Duration::from_micros(100)),
+    ("steady", Duration::from_millis(5)),
+    ("slow", Duration::from_millis(50)),
+];
+
+/// A minimal Ed25519 batch verifier, like the one in the tests.
+struct Ed25519Verifier {
+    batch: batch::Verifier,
+    tx: Sender<Result<(), Error>>,
+}
+
+impl Ed25519Verifier {
+    fn new() -> Self {
+        let (tx, _) = channel(10);
+        Self {
+            batch: batch::Verifier::default(),
+            tx,
+        }
+    }
+}
+
+impl Service<BatchControl<batch::Item>> for Ed25519Verifier {
+    type Response = ();
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: BatchControl<batch::Item>) -> Self::Future {
+        match req {
+            BatchControl::Item(item) => {
+                self.batch.queue(item);
+                let mut rx = self.tx.subscribe();
+                Box::pin(async move { rx.recv().await.unwrap_or(Err(Error::InvalidSignature)) })
+            }
+            BatchControl::Flush => {
+                let batch = mem::take(&mut self.batch);
+                let _ = self.tx.send(batch.verify(thread_rng()));
+                Box::pin(async { Ok(()) })
+            }
+        }
+    }
+}
+
+impl Drop for Ed25519Verifier {
+    fn drop(&mut self) {
+        let batch = mem::take(&mut self.batch);
+        let _ = self.tx.send(batch.verify(thread_rng()));
+    }
+}
+
+/// Returns `count` signed items.
+fn signed_items(count: usize) -> Vec<(VerificationKeyBytes, Signature, &'static [u8; 16])> {
+    (0..count)
+        .map(|_| {
+            let sk = SigningKey::new(thread_rng());
+            let msg = b"BatchVerifyBench";
+            (VerificationKeyBytes::from(&sk), sk.sign(msg), msg)
+        })
+        .collect()
+}
+
+/// Submits `items` to `verifier`, waiting `interval` between each request,
+/// and returns the total latency of all the requests.
+async fn total_latency<V>(
+    mut verifier: V,
+    items: &[(VerificationKeyBytes, Signature, &'static [u8; 16])],
+    interval: Duration,
+) -> Duration
+where
+    V: Service<batch::Item, Response = ()>,
+    V::Error: std::fmt::Debug,
+{
+    let mut results = FuturesUnordered::new();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 && interval > Duration::from_millis(0) {
+            delay_for(interval).await;
+        }
+
+        verifier.ready_and().await.expect("verifier is ready");
+        let start = Instant::now();
+        let result = verifier.call((*item).into());
+        results.push(async move {
+            result.await.expect("signature is valid");
+            start.elapsed()
+        });
+    }
+
+    let mut total = Duration::from_secs(0);
+    while let Some(latency) = results.next().await {
+        total += latency;
+    }
+    total
+}
+
+fn bench_arrival_patterns(c: &mut Criterion) {
+    let mut runtime = Runtime::new().expect("runtime starts");
+    let items = signed_items(REQUESTS);
+
+    let mut group = c.benchmark_group("ed25519 request latency");
+    group.sample_size(10);
+
+    for (pattern, interval) in ARRIVAL_PATTERNS {
+        for &adaptive in &[false, true] {
+            let mode = if adaptive { "adaptive" } else { "fixed" };
+            group.bench_with_input(BenchmarkId::new(mode, pattern), interval, |b, &interval| {
+                b.iter_custom(|iters| {
+                    let mut total = Duration::from_secs(0);
+                    for _ in 0..iters {
+                        total += runtime.block_on(async {
+                            let verifier = if adaptive {
+                                Batch::new_adaptive(Ed25519Verifier::new(), MAX_ITEMS, MAX_LATENCY)
+                            } else {
+                                Batch::new(Ed25519Verifier::new(), MAX_ITEMS, MAX_LATENCY)
+                            };
+                            total_latency(verifier, &items, interval).await
+                        });
+                    }
+                    // Report the mean latency of each request
+                    total / REQUESTS as u32
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_arrival_patterns);
+criterion_main!(benches);
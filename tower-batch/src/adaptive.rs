@@ -0,0 +1,183 @@
+//! Batch limits that adapt to the arrival rate of batch items.
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The weight of each new arrival interval in the mean arrival interval.
+///
+/// Larger weights adapt faster, but are more sensitive to bursts.
+const INTERVAL_WEIGHT: f64 = 0.2;
+
+/// The batch size and latency limits for a batch worker.
+///
+/// Fixed limits always use the configured `max_items` and `max_latency`.
+///
+/// Adaptive limits use them as upper bounds, and estimate how many items will
+/// arrive within `max_latency` from the mean interval between recent items.
+/// When items arrive slowly, like when verifying blocks near the chain tip,
+/// batches are flushed after fewer items, so verification latency stays low.
+/// When items arrive quickly, like during the initial block download, batches
+/// grow up to `max_items`, so throughput stays high.
+#[derive(Debug)]
+pub(crate) struct Limits {
+    max_items: usize,
+    max_latency: Duration,
+    adaptive: Option<ArrivalRate>,
+}
+
+/// The observed arrival rate of batch items.
+#[derive(Debug, Default)]
+struct ArrivalRate {
+    /// The exponentially weighted mean interval between items, in seconds.
+    mean_interval: Option<f64>,
+    /// The time that the last item arrived.
+    last_arrival: Option<Instant>,
+}
+
+impl Limits {
+    /// Returns limits that always use `max_items` and `max_latency`.
+    pub(crate) fn fixed(max_items: usize, max_latency: Duration) -> Self {
+        Limits {
+            max_items,
+            max_latency,
+            adaptive: None,
+        }
+    }
+
+    /// Returns limits that adapt to the arrival rate, up to `max_items` and
+    /// `max_latency`.
+    pub(crate) fn adaptive(max_items: usize, max_latency: Duration) -> Self {
+        Limits {
+            max_items,
+            max_latency,
+            adaptive: Some(ArrivalRate::default()),
+        }
+    }
+
+    /// Records the arrival of a batch item at `now`.
+    pub(crate) fn record_arrival(&mut self, now: Instant) {
+        if let Some(rate) = self.adaptive.as_mut() {
+            if let Some(last_arrival) = rate.last_arrival {
+                let interval = now.saturating_duration_since(last_arrival).as_secs_f64();
+                rate.mean_interval = Some(match rate.mean_interval {
+                    Some(mean) => mean * (1.0 - INTERVAL_WEIGHT) + interval * INTERVAL_WEIGHT,
+                    None => interval,
+                });
+            }
+            rate.last_arrival = Some(now);
+        }
+    }
+
+    /// Returns the number of items that fill the current batch.
+    pub(crate) fn batch_items(&self) -> usize {
+        match self.expected_items() {
+            Some(items) => items,
+            None => self.max_items,
+        }
+    }
+
+    /// Returns the maximum latency for the current batch.
+    ///
+    /// Adaptive limits wait for about as long as it takes the expected
+    /// number of items to arrive.
+    pub(crate) fn batch_latency(&self) -> Duration {
+        match (self.expected_items(), self.mean_interval()) {
+            (Some(items), Some(mean_interval)) => {
+                let latency = Duration::from_secs_f64(mean_interval * items as f64);
+                latency.min(self.max_latency)
+            }
+            _ => self.max_latency,
+        }
+    }
+
+    /// Returns the mean interval between items, if the limits are adaptive,
+    /// and at least two items have arrived.
+    fn mean_interval(&self) -> Option<f64> {
+        self.adaptive.as_ref()?.mean_interval
+    }
+
+    /// Returns the number of items that are expected to arrive within
+    /// `max_latency`, between 1 and `max_items`.
+    fn expected_items(&self) -> Option<usize> {
+        let mean_interval = self.mean_interval()?;
+        if mean_interval <= 0.0 {
+            return Some(self.max_items);
+        }
+
+        let items = self.max_latency.as_secs_f64() / mean_interval;
+        if items >= self.max_items as f64 {
+            Some(self.max_items)
+        } else {
+            Some((items as usize).max(1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_ITEMS: usize = 64;
+    const MAX_LATENCY: Duration = Duration::from_millis(100);
+
+    /// Returns adaptive limits after items arrive every `interval`.
+    fn limits_after(interval: Duration) -> Limits {
+        let mut limits = Limits::adaptive(MAX_ITEMS, MAX_LATENCY);
+        let mut now = Instant::now();
+        for _ in 0..50 {
+            limits.record_arrival(now);
+            now += interval;
+        }
+        limits
+    }
+
+    #[test]
+    fn fixed_limits_ignore_arrivals() {
+        let limits = {
+            let mut limits = Limits::fixed(MAX_ITEMS, MAX_LATENCY);
+            limits.record_arrival(Instant::now());
+            limits.record_arrival(Instant::now() + Duration::from_secs(10));
+            limits
+        };
+
+        assert_eq!(limits.batch_items(), MAX_ITEMS);
+        assert_eq!(limits.batch_latency(), MAX_LATENCY);
+    }
+
+    #[test]
+    fn adaptive_limits_start_at_maximum() {
+        let limits = Limits::adaptive(MAX_ITEMS, MAX_LATENCY);
+
+        assert_eq!(limits.batch_items(), MAX_ITEMS);
+        assert_eq!(limits.batch_latency(), MAX_LATENCY);
+    }
+
+    #[test]
+    fn fast_arrivals_use_maximum_limits() {
+        let limits = limits_after(Duration::from_micros(10));
+
+        assert_eq!(limits.batch_items(), MAX_ITEMS);
+        assert!(limits.batch_latency() <= MAX_LATENCY);
+    }
+
+    #[test]
+    fn slow_arrivals_flush_every_item() {
+        let limits = limits_after(Duration::from_secs(1));
+
+        assert_eq!(limits.batch_items(), 1);
+        assert_eq!(limits.batch_latency(), MAX_LATENCY);
+    }
+
+    #[test]
+    fn moderate_arrivals_use_smaller_batches() {
+        let limits = limits_after(Duration::from_millis(10));
+
+        let items = limits.batch_items();
+        assert!(
+            items > 1 && items < MAX_ITEMS,
+            "unexpected batch size {}",
+            items
+        );
+        assert!(limits.batch_latency() <= MAX_LATENCY);
+    }
+}
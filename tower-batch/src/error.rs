@@ -14,6 +14,30 @@ pub struct Closed {
     _p: (),
 }
 
+/// An error produced when the wrapped service panics in the batch worker.
+///
+/// This error is returned as the source of a [`ServiceError`].
+#[derive(Debug)]
+pub struct Panicked {
+    message: String,
+}
+
+/// An error returned by a `Batch` service.
+///
+/// Errors returned by the wrapped service for a particular request keep their
+/// concrete type, so callers can distinguish an invalid request from a failure
+/// of the batch worker without downcasting.
+#[derive(Debug)]
+pub enum BatchError<E> {
+    /// The wrapped service returned an error for this request.
+    Service(E),
+    /// The wrapped service failed, so the batch worker stopped processing
+    /// requests.
+    Failed(ServiceError),
+    /// The batch worker closed unexpectedly.
+    Closed(Closed),
+}
+
 // ===== impl ServiceError =====
 
 impl ServiceError {
@@ -63,3 +87,69 @@ impl fmt::Display for Closed {
 }
 
 impl std::error::Error for Closed {}
+
+// ===== impl Panicked =====
+
+impl Panicked {
+    pub(crate) fn new(message: String) -> Self {
+        Panicked { message }
+    }
+
+    /// Returns the message from the original panic.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "batch worker panicked: {}", self.message)
+    }
+}
+
+impl std::error::Error for Panicked {}
+
+// ===== impl BatchError =====
+
+impl<E> BatchError<E> {
+    /// Returns the error returned by the wrapped service for this request, if
+    /// there is one.
+    pub fn service_error(&self) -> Option<&E> {
+        match self {
+            BatchError::Service(e) => Some(e),
+            BatchError::Failed(_) | BatchError::Closed(_) => None,
+        }
+    }
+
+    /// Consumes this error, returning the error returned by the wrapped
+    /// service for this request, if there is one.
+    pub fn into_service_error(self) -> Option<E> {
+        match self {
+            BatchError::Service(e) => Some(e),
+            BatchError::Failed(_) | BatchError::Closed(_) => None,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for BatchError<E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchError::Service(e) => e.fmt(fmt),
+            BatchError::Failed(e) => e.fmt(fmt),
+            BatchError::Closed(e) => e.fmt(fmt),
+        }
+    }
+}
+
+impl<E> std::error::Error for BatchError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BatchError::Service(e) => e.source(),
+            BatchError::Failed(e) => Some(e),
+            BatchError::Closed(e) => Some(e),
+        }
+    }
+}
@@ -1,6 +1,9 @@
 //! Future types for the `Batch` middleware.
 
-use super::{error::Closed, message};
+use super::{
+    error::{BatchError, Closed},
+    message,
+};
 use futures_core::ready;
 use pin_project::pin_project;
 use std::{
@@ -12,39 +15,38 @@ use std::{
 /// Future that completes when the batch processing is complete.
 #[pin_project]
 #[derive(Debug)]
-pub struct ResponseFuture<T> {
+pub struct ResponseFuture<T, E> {
     #[pin]
-    state: ResponseState<T>,
+    state: ResponseState<T, E>,
 }
 
 #[pin_project(project = ResponseStateProj)]
 #[derive(Debug)]
-enum ResponseState<T> {
-    Failed(Option<crate::BoxError>),
+enum ResponseState<T, E> {
+    Failed(Option<BatchError<E>>),
     Rx(#[pin] message::Rx<T>),
     Poll(#[pin] T),
 }
 
-impl<T> ResponseFuture<T> {
+impl<T, E> ResponseFuture<T, E> {
     pub(crate) fn new(rx: message::Rx<T>) -> Self {
         ResponseFuture {
             state: ResponseState::Rx(rx),
         }
     }
 
-    pub(crate) fn failed(err: crate::BoxError) -> Self {
+    pub(crate) fn failed(err: BatchError<E>) -> Self {
         ResponseFuture {
             state: ResponseState::Failed(Some(err)),
         }
     }
 }
 
-impl<F, T, E> Future for ResponseFuture<F>
+impl<F, T, E> Future for ResponseFuture<F, E>
 where
     F: Future<Output = Result<T, E>>,
-    E: Into<crate::BoxError>,
 {
-    type Output = Result<T, crate::BoxError>;
+    type Output = Result<T, BatchError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let mut this = self.project();
@@ -56,10 +58,10 @@ where
                 }
                 ResponseStateProj::Rx(rx) => match ready!(rx.poll(cx)) {
                     Ok(Ok(f)) => this.state.set(ResponseState::Poll(f)),
-                    Ok(Err(e)) => return Poll::Ready(Err(e.into())),
-                    Err(_) => return Poll::Ready(Err(Closed::new().into())),
+                    Ok(Err(e)) => return Poll::Ready(Err(BatchError::Failed(e))),
+                    Err(_) => return Poll::Ready(Err(BatchError::Closed(Closed::new()))),
                 },
-                ResponseStateProj::Poll(fut) => return fut.poll(cx).map_err(Into::into),
+                ResponseStateProj::Poll(fut) => return fut.poll(cx).map_err(BatchError::Service),
             }
         }
     }
@@ -12,6 +12,7 @@ use tower::Service;
 pub struct BatchLayer<Request> {
     max_items: usize,
     max_latency: std::time::Duration,
+    adaptive: bool,
     _p: PhantomData<fn(Request)>,
 }
 
@@ -27,6 +28,20 @@ impl<Request> BatchLayer<Request> {
         BatchLayer {
             max_items,
             max_latency,
+            adaptive: false,
+            _p: PhantomData,
+        }
+    }
+
+    /// Creates a new `BatchLayer`, which adapts its batch size and latency to
+    /// the arrival rate of requests, up to `max_items` and `max_latency`.
+    ///
+    /// See [`Batch::new_adaptive`] for details.
+    pub fn new_adaptive(max_items: usize, max_latency: std::time::Duration) -> Self {
+        BatchLayer {
+            max_items,
+            max_latency,
+            adaptive: true,
             _p: PhantomData,
         }
     }
@@ -42,7 +57,11 @@ where
     type Service = Batch<S, Request>;
 
     fn layer(&self, service: S) -> Self::Service {
-        Batch::new(service, self.max_items, self.max_latency)
+        if self.adaptive {
+            Batch::new_adaptive(service, self.max_items, self.max_latency)
+        } else {
+            Batch::new(service, self.max_items, self.max_latency)
+        }
     }
 }
 
@@ -51,6 +70,7 @@ impl<Request> fmt::Debug for BatchLayer<Request> {
         f.debug_struct("BufferLayer")
             .field("max_items", &self.max_items)
             .field("max_latency", &self.max_latency)
+            .field("adaptive", &self.adaptive)
             .finish()
     }
 }
@@ -84,11 +84,18 @@
 //! a `Service<R>`. The wrapped service does not need to implement any batch
 //! control logic, as it will receive explicit [`Flush`](BatchControl::Flush)
 //! requests from the wrapper.
+//!
+//! By default, the wrapper uses a fixed batch size and latency. The
+//! [`Batch::new_adaptive`] constructor adapts them to the arrival rate of
+//! requests, so that latency stays low when requests arrive slowly, and
+//! throughput stays high when they arrive quickly.
 
+mod adaptive;
 pub mod error;
 pub mod future;
 mod layer;
 mod message;
+mod semaphore;
 mod service;
 mod worker;
 
@@ -1,4 +1,4 @@
-use super::error::ServiceError;
+use super::{error::ServiceError, semaphore::Permit};
 use tokio::sync::oneshot;
 
 /// Message sent to the batch worker
@@ -7,6 +7,9 @@ pub(crate) struct Message<Request, Fut> {
     pub(crate) request: Request,
     pub(crate) tx: Tx<Fut>,
     pub(crate) span: tracing::Span,
+    /// The queue capacity used by this message, which is released when the
+    /// worker finishes with the message.
+    pub(crate) _permit: Permit,
 }
 
 /// Response sender
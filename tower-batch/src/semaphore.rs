@@ -0,0 +1,78 @@
+//! Owned semaphore permits, used for `Batch` backpressure.
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// A handle to a semaphore shared by a `Batch` and its clones.
+///
+/// Each handle acquires its own permit, so clones can't use each other's
+/// reserved capacity.
+pub(crate) struct Semaphore {
+    semaphore: Arc<tokio::sync::Semaphore>,
+    acquiring: Option<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>,
+}
+
+/// A permit that is returned to its semaphore when it is dropped.
+#[derive(Debug)]
+pub(crate) struct Permit {
+    semaphore: Arc<tokio::sync::Semaphore>,
+}
+
+impl Semaphore {
+    pub(crate) fn new(permits: usize) -> Self {
+        Semaphore {
+            semaphore: Arc::new(tokio::sync::Semaphore::new(permits)),
+            acquiring: None,
+        }
+    }
+
+    /// Poll for a permit, registering the current task for wakeup if no
+    /// permits are available.
+    pub(crate) fn poll_acquire(&mut self, cx: &mut Context<'_>) -> Poll<Permit> {
+        let semaphore = &self.semaphore;
+        let acquiring = self.acquiring.get_or_insert_with(|| {
+            let semaphore = semaphore.clone();
+            // The permit is released when the `Permit` is dropped
+            Box::pin(async move { semaphore.acquire().await.forget() })
+        });
+
+        match acquiring.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.acquiring = None;
+                Poll::Ready(Permit {
+                    semaphore: self.semaphore.clone(),
+                })
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Clone for Semaphore {
+    fn clone(&self) -> Self {
+        Semaphore {
+            semaphore: self.semaphore.clone(),
+            acquiring: None,
+        }
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("available_permits", &self.semaphore.available_permits())
+            .field("acquiring", &self.acquiring.is_some())
+            .finish()
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(1);
+    }
+}
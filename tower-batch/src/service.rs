@@ -1,25 +1,68 @@
 use super::{
+    adaptive::Limits,
+    error::BatchError,
     future::ResponseFuture,
     message::Message,
+    semaphore::{Permit, Semaphore},
     worker::{Handle, Worker},
     BatchControl,
 };
 
 use futures_core::ready;
-use std::task::{Context, Poll};
-use tokio::sync::{mpsc, oneshot};
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tokio::{
+    runtime,
+    sync::{mpsc, oneshot},
+};
 use tower::Service;
 
 /// Allows batch processing of requests.
 ///
+/// Errors from the wrapped service are returned as
+/// [`BatchError::Service`](crate::error::BatchError::Service), so callers can
+/// distinguish them from failures of the batch worker.
+///
 /// See the module documentation for more details.
 #[derive(Debug)]
 pub struct Batch<T, Request>
 where
     T: Service<BatchControl<Request>>,
 {
-    tx: mpsc::Sender<Message<Request, T::Future>>,
+    tx: mpsc::UnboundedSender<BatchControl<Message<Request, T::Future>>>,
+    semaphore: Semaphore,
+    permit: Option<Permit>,
     handle: Handle,
+    pending_worker: PendingWorker,
+}
+
+/// A batch worker that is spawned by the first call to `poll_ready`.
+#[derive(Clone, Default)]
+struct PendingWorker(Arc<Mutex<Option<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>>>);
+
+impl PendingWorker {
+    fn new(worker: impl Future<Output = ()> + Send + 'static) -> Self {
+        PendingWorker(Arc::new(Mutex::new(Some(Box::pin(worker)))))
+    }
+
+    /// Spawn the worker, if it has not already been spawned.
+    fn spawn(&self) {
+        if let Some(worker) = self.0.lock().unwrap().take() {
+            tokio::spawn(worker);
+        }
+    }
+}
+
+impl fmt::Debug for PendingWorker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let is_pending = self.0.lock().unwrap().is_some();
+        f.debug_tuple("PendingWorker").field(&is_pending).finish()
+    }
 }
 
 impl<T, Request> Batch<T, Request>
@@ -44,14 +87,147 @@ where
         T::Error: Send + Sync,
         Request: Send + 'static,
     {
-        // XXX(hdevalence): is this bound good
-        let (tx, rx) = mpsc::channel(1);
-        let (handle, worker) = Worker::new(service, rx, max_items, max_latency);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (handle, worker) = Worker::new(service, rx, Limits::fixed(max_items, max_latency));
+        tokio::spawn(worker.run());
+        Batch::from_parts(tx, handle, PendingWorker::default(), max_items)
+    }
+
+    /// Creates a new `Batch` wrapping `service`, which adapts its batch size
+    /// and latency to the arrival rate of requests.
+    ///
+    /// `max_items` and `max_latency` are upper bounds. When requests arrive
+    /// slowly, batches are flushed after fewer items, to keep latency low.
+    /// When requests arrive quickly, batches grow up to `max_items`, to keep
+    /// throughput high.
+    ///
+    /// See [`Batch::new`] for details of the other parameters.
+    pub fn new_adaptive(service: T, max_items: usize, max_latency: std::time::Duration) -> Self
+    where
+        T: Send + 'static,
+        T::Future: Send,
+        T::Error: Send + Sync,
+        Request: Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (handle, worker) = Worker::new(service, rx, Limits::adaptive(max_items, max_latency));
         tokio::spawn(worker.run());
-        Batch { tx, handle }
+        Batch::from_parts(tx, handle, PendingWorker::default(), max_items)
+    }
+
+    /// Creates a new `Batch` wrapping `service`, running the batch worker on
+    /// the runtime for `runtime_handle`.
+    ///
+    /// Unlike [`Batch::new`], this method can be called outside a Tokio
+    /// runtime.
+    ///
+    /// See [`Batch::new`] for details of the other parameters.
+    pub fn new_on(
+        runtime_handle: runtime::Handle,
+        service: T,
+        max_items: usize,
+        max_latency: std::time::Duration,
+    ) -> Self
+    where
+        T: Send + 'static,
+        T::Future: Send,
+        T::Error: Send + Sync,
+        Request: Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (handle, worker) = Worker::new(service, rx, Limits::fixed(max_items, max_latency));
+        runtime_handle.spawn(worker.run());
+        Batch::from_parts(tx, handle, PendingWorker::default(), max_items)
     }
 
-    fn get_worker_error(&self) -> crate::BoxError {
+    /// Creates a new `Batch` wrapping `service`, which spawns the batch worker
+    /// on the first call to `poll_ready`.
+    ///
+    /// The worker is spawned on the default Tokio executor for the task that
+    /// calls `poll_ready`, so this method can be called outside a Tokio
+    /// runtime.
+    ///
+    /// See [`Batch::new`] for details of the other parameters.
+    pub fn new_lazy(service: T, max_items: usize, max_latency: std::time::Duration) -> Self
+    where
+        T: Send + 'static,
+        T::Future: Send,
+        T::Error: Send + Sync,
+        Request: Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (handle, worker) = Worker::new(service, rx, Limits::fixed(max_items, max_latency));
+        Batch::from_parts(tx, handle, PendingWorker::new(worker.run()), max_items)
+    }
+
+    /// Creates a new `Batch` wrapping a service created by `make_service`.
+    ///
+    /// If the wrapped service panics, the batch worker replaces it with a new
+    /// service from `make_service`, and keeps processing requests. Requests
+    /// that were being processed by the old service fail with a
+    /// [`Closed`](crate::error::Closed) error.
+    ///
+    /// Panics can only be caught when the binary is built with
+    /// `panic = "unwind"`. The workspace release and dev profiles use
+    /// `panic = "abort"`, so in `zebrad` a panic still aborts the process, and
+    /// the service is never restarted.
+    ///
+    /// See [`Batch::new`] for details of the other parameters.
+    pub fn new_with_restart<F>(
+        mut make_service: F,
+        max_items: usize,
+        max_latency: std::time::Duration,
+    ) -> Self
+    where
+        F: FnMut() -> T + Send + 'static,
+        T: Send + 'static,
+        T::Future: Send,
+        T::Error: Send + Sync,
+        Request: Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (handle, worker) =
+            Worker::new(make_service(), rx, Limits::fixed(max_items, max_latency));
+        tokio::spawn(worker.run_with_restart(make_service));
+        Batch::from_parts(tx, handle, PendingWorker::default(), max_items)
+    }
+
+    /// Creates a `Batch` from its parts.
+    ///
+    /// Up to `bound` requests can wait for the batch worker, across this
+    /// `Batch` and all its clones. Each request holds a permit until the
+    /// worker receives it.
+    fn from_parts(
+        tx: mpsc::UnboundedSender<BatchControl<Message<Request, T::Future>>>,
+        handle: Handle,
+        pending_worker: PendingWorker,
+        bound: usize,
+    ) -> Self {
+        Batch {
+            tx,
+            semaphore: Semaphore::new(bound),
+            permit: None,
+            handle,
+            pending_worker,
+        }
+    }
+
+    /// Asks the batch worker to flush the current batch, without waiting for
+    /// `max_latency`.
+    ///
+    /// Call this method after submitting the last item in a group of related
+    /// items, like the signatures in a block, so that the group isn't delayed
+    /// by a partial batch. The batch includes every item submitted before this
+    /// call, by this handle or its clones.
+    ///
+    /// This method doesn't wait for the flush. If there is no pending batch,
+    /// or the worker has failed, it does nothing.
+    pub fn flush(&self) {
+        // If the worker has failed, item requests return its error
+        let _ = self.tx.send(BatchControl::Flush);
+    }
+
+    fn get_worker_error(&self) -> BatchError<T::Error> {
         self.handle.get_error_on_closed()
     }
 }
@@ -62,22 +238,37 @@ where
     T::Error: Into<crate::BoxError>,
 {
     type Response = T::Response;
-    type Error = crate::BoxError;
-    type Future = ResponseFuture<T::Future>;
+    type Error = BatchError<T::Error>;
+    type Future = ResponseFuture<T::Future, T::Error>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Lazy batches start their worker on the first poll.
+        self.pending_worker.spawn();
+
         // If the inner service has errored, then we error here.
-        if ready!(self.tx.poll_ready(cx)).is_err() {
-            Poll::Ready(Err(self.get_worker_error()))
-        } else {
-            Poll::Ready(Ok(()))
+        if let Some(error) = self.handle.get_error() {
+            return Poll::Ready(Err(error));
         }
+
+        // Each request acquires its own permit, which is released when the
+        // worker receives the request. Clones share the semaphore, so they
+        // can't use capacity reserved by another handle.
+        if self.permit.is_none() {
+            self.permit = Some(ready!(self.semaphore.poll_acquire(cx)));
+        }
+
+        Poll::Ready(Ok(()))
     }
 
     fn call(&mut self, request: Request) -> Self::Future {
+        let permit = self
+            .permit
+            .take()
+            .expect("poll_ready must be called and return Ready before call");
+
         // TODO:
         // ideally we'd poll_ready again here so we don't allocate the oneshot
-        // if the try_send is about to fail, but sadly we can't call poll_ready
+        // if the send is about to fail, but sadly we can't call poll_ready
         // outside of task context.
         let (tx, rx) = oneshot::channel();
 
@@ -86,20 +277,13 @@ where
         // towards that span since the worker would have no way of entering it.
         let span = tracing::Span::current();
         tracing::trace!(parent: &span, "sending request to batch worker");
-        match self.tx.try_send(Message { request, span, tx }) {
-            Err(mpsc::error::TrySendError::Closed(_)) => {
-                ResponseFuture::failed(self.get_worker_error())
-            }
-            Err(mpsc::error::TrySendError::Full(_)) => {
-                // When `mpsc::Sender::poll_ready` returns `Ready`, a slot
-                // in the channel is reserved for the handle. Other `Sender`
-                // handles may not send a message using that slot. This
-                // guarantees capacity for `request`.
-                //
-                // Given this, the only way to hit this code path is if
-                // `poll_ready` has not been called & `Ready` returned.
-                panic!("buffer full; poll_ready must be called first");
-            }
+        match self.tx.send(BatchControl::Item(Message {
+            request,
+            span,
+            tx,
+            _permit: permit,
+        })) {
+            Err(_) => ResponseFuture::failed(self.get_worker_error()),
             Ok(_) => ResponseFuture::new(rx),
         }
     }
@@ -112,7 +296,11 @@ where
     fn clone(&self) -> Self {
         Self {
             tx: self.tx.clone(),
+            // The clone must acquire its own permit
+            semaphore: self.semaphore.clone(),
+            permit: None,
             handle: self.handle.clone(),
+            pending_worker: self.pending_worker.clone(),
         }
     }
 }
@@ -1,15 +1,20 @@
 use super::{
-    error::{Closed, ServiceError},
+    adaptive::Limits,
+    error::{BatchError, Closed, Panicked, ServiceError},
     message::{self, Message},
     BatchControl,
 };
-use futures::future::TryFutureExt;
+use futures::future::{FutureExt, TryFutureExt};
 use pin_project::pin_project;
-use std::sync::{Arc, Mutex};
+use std::{
+    any::Any,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+};
 use tokio::{
     stream::StreamExt,
     sync::mpsc,
-    time::{delay_for, Delay},
+    time::{delay_for, Delay, Instant},
 };
 use tower::{Service, ServiceExt};
 use tracing_futures::Instrument;
@@ -28,12 +33,11 @@ where
     T: Service<BatchControl<Request>>,
     T::Error: Into<crate::BoxError>,
 {
-    rx: mpsc::Receiver<Message<Request, T::Future>>,
+    rx: mpsc::UnboundedReceiver<BatchControl<Message<Request, T::Future>>>,
     service: T,
     failed: Option<ServiceError>,
     handle: Handle,
-    max_items: usize,
-    max_latency: std::time::Duration,
+    limits: Limits,
 }
 
 /// Get the error out
@@ -49,9 +53,8 @@ where
 {
     pub(crate) fn new(
         service: T,
-        rx: mpsc::Receiver<Message<Request, T::Future>>,
-        max_items: usize,
-        max_latency: std::time::Duration,
+        rx: mpsc::UnboundedReceiver<BatchControl<Message<Request, T::Future>>>,
+        limits: Limits,
     ) -> (Handle, Worker<T, Request>) {
         let handle = Handle {
             inner: Arc::new(Mutex::new(None)),
@@ -62,8 +65,7 @@ where
             service,
             handle: handle.clone(),
             failed: None,
-            max_items,
-            max_latency,
+            limits,
         };
 
         (handle, worker)
@@ -102,34 +104,97 @@ where
         }
     }
 
+    /// Process batches until the `Batch` handles are dropped, or the inner
+    /// service fails.
+    ///
+    /// If the inner service panics, the panic is recorded as the worker
+    /// error, and returned to all pending and future requests.
     pub async fn run(mut self) {
+        if let Err(panic) = AssertUnwindSafe(self.process_batches())
+            .catch_unwind()
+            .await
+        {
+            let message = panic_message(panic);
+            tracing::error!(%message, "batch worker panicked");
+            self.failed(Box::new(Panicked::new(message)));
+            self.drain().await;
+        }
+    }
+
+    /// Process batches until the `Batch` handles are dropped, or the inner
+    /// service fails.
+    ///
+    /// If the inner service panics, it is replaced by a new service from
+    /// `make_service`. Requests that were being processed when the panic
+    /// happened fail with a [`Closed`] error.
+    pub async fn run_with_restart<F>(mut self, mut make_service: F)
+    where
+        F: FnMut() -> T,
+    {
+        while let Err(panic) = AssertUnwindSafe(self.process_batches())
+            .catch_unwind()
+            .await
+        {
+            let message = panic_message(panic);
+            tracing::warn!(%message, "batch worker panicked, restarting with a new service");
+            self.service = make_service();
+        }
+    }
+
+    /// Send the worker error to any requests that are still in the channel.
+    async fn drain(&mut self) {
+        let failed = self
+            .failed
+            .as_ref()
+            .map(ServiceError::clone)
+            .expect("drain is only called after the worker fails");
+        while let Some(msg) = self.rx.next().await {
+            if let BatchControl::Item(msg) = msg {
+                let _ = msg.tx.send(Err(failed.clone()));
+            }
+        }
+    }
+
+    async fn process_batches(&mut self) {
         use futures::future::Either::{Left, Right};
         // The timer is started when the first entry of a new batch is
         // submitted, so that the batch latency of all entries is at most
-        // self.max_latency. However, we don't keep the timer running unless
+        // the batch latency. However, we don't keep the timer running unless
         // there is a pending request to prevent wakeups on idle services.
+        //
+        // Adaptive limits can change after each item, so they are checked
+        // when each item arrives.
         let mut timer: Option<Delay> = None;
         let mut pending_items = 0usize;
         loop {
             match timer {
                 None => match self.rx.next().await {
                     // The first message in a new batch.
-                    Some(msg) => {
+                    Some(BatchControl::Item(msg)) => {
+                        self.limits.record_arrival(Instant::now());
                         let span = msg.span;
                         self.process_req(msg.request, msg.tx)
                             // Apply the provided span to request processing
                             .instrument(span)
                             .await;
-                        timer = Some(delay_for(self.max_latency));
-                        pending_items = 1;
+                        if self.limits.batch_items() <= 1 {
+                            // A single item fills the batch.
+                            self.flush_service().await;
+                        } else {
+                            timer = Some(delay_for(self.limits.batch_latency()));
+                            pending_items = 1;
+                        }
                     }
+                    // There is no batch to flush.
+                    Some(BatchControl::Flush) => {}
                     // No more messages, ever.
                     None => return,
                 },
                 Some(delay) => {
                     // Wait on either a new message or the batch timer.
                     match futures::future::select(self.rx.next(), delay).await {
-                        Left((Some(msg), delay)) => {
+                        Left((Some(BatchControl::Item(msg)), delay)) => {
+                            self.limits.record_arrival(Instant::now());
                             let span = msg.span;
                             self.process_req(msg.request, msg.tx)
                                 // Apply the provided span to request processing.
@@ -137,7 +202,7 @@ where
                                 .await;
                             pending_items += 1;
                             // Check whether we have too many pending items.
-                            if pending_items >= self.max_items {
+                            if pending_items >= self.limits.batch_items() {
                                 // XXX(hdevalence): what span should instrument this?
                                 self.flush_service().await;
                                 // Now we have an empty batch.
@@ -148,6 +213,13 @@ where
                                 timer = Some(delay);
                             }
                         }
+                        // The caller has finished submitting related items,
+                        // so we don't wait for the batch timer.
+                        Left((Some(BatchControl::Flush), _delay)) => {
+                            self.flush_service().await;
+                            timer = None;
+                            pending_items = 0;
+                        }
                         // No more messages, ever.
                         Left((None, _delay)) => {
                             return;
@@ -200,14 +272,30 @@ where
     }
 }
 
+/// Returns the message from a panic `payload`.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "unknown panic payload".to_string()),
+    }
+}
+
 impl Handle {
-    pub(crate) fn get_error_on_closed(&self) -> crate::BoxError {
+    /// Returns the worker error, if the worker has failed.
+    pub(crate) fn get_error<E>(&self) -> Option<BatchError<E>> {
         self.inner
             .lock()
             .unwrap()
             .as_ref()
-            .map(|svc_err| svc_err.clone().into())
-            .unwrap_or_else(|| Closed::new().into())
+            .map(|svc_err| BatchError::Failed(svc_err.clone()))
+    }
+
+    pub(crate) fn get_error_on_closed<E>(&self) -> BatchError<E> {
+        self.get_error()
+            .unwrap_or_else(|| BatchError::Closed(Closed::new()))
     }
 }
 
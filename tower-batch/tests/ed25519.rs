@@ -11,7 +11,7 @@ use futures::stream::{FuturesUnordered, StreamExt};
 use rand::thread_rng;
 use tokio::sync::broadcast::{channel, RecvError, Sender};
 use tower::{Service, ServiceExt};
-use tower_batch::{Batch, BatchControl};
+use tower_batch::{error::BatchError, Batch, BatchControl};
 
 // ============ service impl ============
 
@@ -136,3 +136,112 @@ async fn batch_flushes_on_max_latency() {
             .is_ok()
     );
 }
+
+#[tokio::test]
+async fn adaptive_batch_flushes_slow_items() {
+    use tokio::time::timeout;
+    zebra_test::init();
+
+    // Use a high max_items, and a max_latency that is shorter than the
+    // interval between items. The first item waits for max_latency, but the
+    // adaptive limits should flush the second item immediately.
+    let mut verifier = Batch::new_adaptive(Ed25519Verifier::new(), 100, Duration::from_millis(500));
+    assert!(
+        timeout(Duration::from_secs(1), sign_and_verify(&mut verifier, 1))
+            .await
+            .is_ok()
+    );
+    assert!(timeout(
+        Duration::from_millis(250),
+        sign_and_verify(&mut verifier, 1)
+    )
+    .await
+    .is_ok());
+}
+
+#[tokio::test]
+async fn adaptive_batch_flushes_on_max_items() {
+    use tokio::time::timeout;
+    zebra_test::init();
+
+    // Items that arrive quickly should still be flushed in full batches.
+    let verifier = Batch::new_adaptive(Ed25519Verifier::new(), 10, Duration::from_secs(1000));
+    assert!(
+        timeout(Duration::from_secs(1), sign_and_verify(verifier, 100))
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn batch_returns_service_errors() {
+    zebra_test::init();
+
+    let mut verifier = Batch::new(Ed25519Verifier::new(), 10, Duration::from_millis(100));
+
+    // Sign with one key, and verify with another
+    let sk = SigningKey::new(thread_rng());
+    let vk_bytes = VerificationKeyBytes::from(&SigningKey::new(thread_rng()));
+    let msg = b"BatchVerifyTest";
+    let sig = sk.sign(&msg[..]);
+
+    let result = verifier
+        .ready_and()
+        .await
+        .expect("the batch worker is running")
+        .call((vk_bytes, sig, msg).into())
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(BatchError::Service(Error::InvalidSignature))
+    ));
+}
+
+#[tokio::test]
+async fn batch_clones_share_capacity() {
+    use tokio::time::timeout;
+    zebra_test::init();
+
+    // Use many clones concurrently, so they compete for queue capacity.
+    // (The test verifier only buffers 10 batch results, so we keep the total
+    // number of batches below that.)
+    let verifier = Batch::new(Ed25519Verifier::new(), 10, Duration::from_millis(100));
+    let tasks = (0..10)
+        .map(|_| tokio::spawn(sign_and_verify(verifier.clone(), 5)))
+        .collect::<FuturesUnordered<_>>();
+    drop(verifier);
+
+    let results = timeout(Duration::from_secs(5), tasks.collect::<Vec<_>>())
+        .await
+        .expect("clones should not deadlock");
+    for result in results {
+        assert!(result.expect("task should not panic").is_ok());
+    }
+}
+
+#[test]
+fn batch_runs_on_runtime_handle() {
+    zebra_test::init();
+
+    // Create the batch outside the runtime, and drive the requests using
+    // another executor
+    let runtime = tokio::runtime::Runtime::new().expect("runtime starts");
+    let verifier = Batch::new_on(
+        runtime.handle().clone(),
+        Ed25519Verifier::new(),
+        10,
+        Duration::from_millis(100),
+    );
+    assert!(futures::executor::block_on(sign_and_verify(verifier, 100)).is_ok());
+}
+
+#[test]
+fn batch_spawns_lazily() {
+    zebra_test::init();
+
+    // Create the batch outside the runtime, then use it inside the runtime
+    let verifier = Batch::new_lazy(Ed25519Verifier::new(), 10, Duration::from_millis(100));
+    let mut runtime = tokio::runtime::Runtime::new().expect("runtime starts");
+    assert!(runtime.block_on(sign_and_verify(verifier, 100)).is_ok());
+}
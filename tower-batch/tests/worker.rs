@@ -0,0 +1,140 @@
+use std::{
+    convert::Infallible,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    channel::oneshot,
+    future::{join, ready, BoxFuture, FutureExt, Ready},
+};
+use tower::{Service, ServiceExt};
+use tower_batch::{error::BatchError, Batch, BatchControl};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The item that makes `PanicService` panic.
+const PANIC_ITEM: u32 = 7;
+
+/// A service that returns each item, but panics on `PANIC_ITEM`.
+struct PanicService;
+
+impl Service<BatchControl<u32>> for PanicService {
+    type Response = u32;
+    type Error = Infallible;
+    type Future = Ready<Result<u32, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: BatchControl<u32>) -> Self::Future {
+        match req {
+            BatchControl::Item(PANIC_ITEM) => panic!("panic item"),
+            BatchControl::Item(item) => ready(Ok(item)),
+            BatchControl::Flush => ready(Ok(0)),
+        }
+    }
+}
+
+/// Call `batch` with `item`, returning any error from `poll_ready` or the
+/// response.
+async fn call<S>(batch: &mut S, item: u32) -> Result<u32, S::Error>
+where
+    S: Service<u32, Response = u32>,
+{
+    batch.ready_and().await?.call(item).await
+}
+
+#[tokio::test]
+async fn panic_message_is_returned() {
+    zebra_test::init();
+
+    let mut batch = Batch::new(PanicService, 10, Duration::from_millis(100));
+
+    // The panicking request is dropped by the worker
+    assert!(matches!(
+        call(&mut batch, PANIC_ITEM).await,
+        Err(BatchError::Closed(_))
+    ));
+
+    // Later requests get the panic message
+    match call(&mut batch, 1).await {
+        Err(BatchError::Failed(e)) => assert!(e.to_string().contains("panic item")),
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[tokio::test]
+async fn worker_restarts_after_panic() {
+    zebra_test::init();
+
+    let mut batch = Batch::new_with_restart(|| PanicService, 10, Duration::from_millis(100));
+
+    assert!(matches!(
+        call(&mut batch, PANIC_ITEM).await,
+        Err(BatchError::Closed(_))
+    ));
+
+    // The new service processes later requests
+    assert_eq!(call(&mut batch, 1).await.unwrap(), 1);
+    assert_eq!(call(&mut batch, 2).await.unwrap(), 2);
+}
+
+/// A service that returns each item when its batch is flushed.
+#[derive(Default)]
+struct FlushService {
+    pending: Vec<(u32, oneshot::Sender<u32>)>,
+}
+
+impl Service<BatchControl<u32>> for FlushService {
+    type Response = u32;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<u32, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: BatchControl<u32>) -> Self::Future {
+        match req {
+            BatchControl::Item(item) => {
+                let (tx, rx) = oneshot::channel();
+                self.pending.push((item, tx));
+                async move { Ok(rx.await?) }.boxed()
+            }
+            BatchControl::Flush => {
+                for (item, tx) in self.pending.drain(..) {
+                    let _ = tx.send(item);
+                }
+                async { Ok(0) }.boxed()
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn flush_skips_batch_latency() {
+    zebra_test::init();
+
+    let mut batch = Batch::new(FlushService::default(), 10, Duration::from_secs(60 * 60));
+
+    let first = batch.ready_and().await.unwrap().call(1);
+    let second = batch.ready_and().await.unwrap().call(2);
+    batch.flush();
+
+    let responses = tokio::time::timeout(Duration::from_secs(1), join(first, second))
+        .await
+        .expect("flushed items should not wait for the batch latency");
+    assert_eq!(responses.0.unwrap(), 1);
+    assert_eq!(responses.1.unwrap(), 2);
+
+    // Flushing an empty batch does nothing
+    batch.flush();
+    let third = batch.ready_and().await.unwrap().call(3);
+    batch.flush();
+    let response = tokio::time::timeout(Duration::from_secs(1), third)
+        .await
+        .expect("flushed items should not wait for the batch latency");
+    assert_eq!(response.unwrap(), 3);
+}
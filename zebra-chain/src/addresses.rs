@@ -3,3 +3,4 @@
 pub mod sapling;
 pub mod sprout;
 pub mod transparent;
+pub mod unified;
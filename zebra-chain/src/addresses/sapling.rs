@@ -35,6 +35,23 @@ pub struct SaplingShieldedAddress {
     transmission_key: sapling::TransmissionKey,
 }
 
+impl SaplingShieldedAddress {
+    /// Returns the network for this address.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Returns the diversifier for this address.
+    pub fn diversifier(&self) -> sapling::Diversifier {
+        self.diversifier
+    }
+
+    /// Returns the diversified transmission key for this address.
+    pub fn transmission_key(&self) -> sapling::TransmissionKey {
+        self.transmission_key
+    }
+}
+
 impl fmt::Debug for SaplingShieldedAddress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("SaplingShieldedAddress")
@@ -67,20 +84,34 @@ impl std::str::FromStr for SaplingShieldedAddress {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match bech32::decode(s) {
             Ok((hrp, bytes)) => {
-                let mut decoded_bytes = io::Cursor::new(Vec::<u8>::from_base32(&bytes).unwrap());
+                let network = match hrp.as_str() {
+                    human_readable_parts::MAINNET => Network::Mainnet,
+                    human_readable_parts::TESTNET => Network::Testnet,
+                    _ => return Err(SerializationError::Parse("unknown sapling address prefix")),
+                };
+
+                let bytes = Vec::<u8>::from_base32(&bytes)
+                    .map_err(|_| SerializationError::Parse("bech32 decoding error"))?;
+                if bytes.len() != 11 + 32 {
+                    return Err(SerializationError::Parse("wrong sapling address length"));
+                }
+                let mut decoded_bytes = io::Cursor::new(bytes);
 
                 let mut diversifier_bytes = [0; 11];
                 decoded_bytes.read_exact(&mut diversifier_bytes)?;
 
                 let transmission_key_bytes = decoded_bytes.read_32_bytes()?;
+                let transmission_key = jubjub::AffinePoint::from_bytes(transmission_key_bytes);
+                if bool::from(transmission_key.is_none()) {
+                    return Err(SerializationError::Parse(
+                        "invalid sapling transmission key",
+                    ));
+                }
 
                 Ok(SaplingShieldedAddress {
-                    network: match hrp.as_str() {
-                        human_readable_parts::MAINNET => Network::Mainnet,
-                        _ => Network::Testnet,
-                    },
+                    network,
                     diversifier: sapling::Diversifier::from(diversifier_bytes),
-                    transmission_key: sapling::TransmissionKey::from(transmission_key_bytes),
+                    transmission_key: sapling::TransmissionKey(transmission_key.unwrap()),
                 })
             }
             Err(_) => Err(SerializationError::Parse("bech32 decoding error")),
@@ -129,6 +160,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_str_errors() {
+        // Wrong prefix
+        "zx1qqqqqqqqqqqqqqqqqrjq05nyfku05msvu49mawhg6kr0wwljahypwyk2h88z6975u563j8nfaxd"
+            .parse::<SaplingShieldedAddress>()
+            .expect_err("wrong prefixes should be rejected");
+
+        // Truncated
+        "zs1qqqqqqqqqqqqqqqqqrjq05nyfku05msvu49mawhg6kr0wwljahypwyk2h88z6975u5"
+            .parse::<SaplingShieldedAddress>()
+            .expect_err("truncated addresses should be rejected");
+    }
+
     #[test]
     fn derive_keys_and_addresses() {
         let spending_key = sapling::SpendingKey::new(&mut OsRng);
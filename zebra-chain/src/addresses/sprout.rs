@@ -28,6 +28,23 @@ pub struct SproutShieldedAddress {
     transmission_key: sprout::TransmissionKey,
 }
 
+impl SproutShieldedAddress {
+    /// Returns the network for this address.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Returns the paying key for this address.
+    pub fn paying_key(&self) -> sprout::PayingKey {
+        self.paying_key
+    }
+
+    /// Returns the transmission key for this address.
+    pub fn transmission_key(&self) -> sprout::TransmissionKey {
+        self.transmission_key
+    }
+}
+
 impl fmt::Debug for SproutShieldedAddress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("SproutShieldedAddress")
@@ -73,9 +90,11 @@ impl ZcashDeserialize for SproutShieldedAddress {
         let network = match version_bytes {
             magics::MAINNET => Network::Mainnet,
             magics::TESTNET => Network::Testnet,
-            _ => panic!(SerializationError::Parse(
-                "bad sprout shielded addr version/type",
-            )),
+            _ => {
+                return Err(SerializationError::Parse(
+                    "bad sprout shielded addr version/type",
+                ))
+            }
         };
 
         Ok(SproutShieldedAddress {
@@ -103,7 +122,10 @@ impl std::str::FromStr for SproutShieldedAddress {
         let result = &bs58::decode(s).with_check(None).into_vec();
 
         match result {
-            Ok(bytes) => Self::zcash_deserialize(&bytes[..]),
+            Ok(bytes) if bytes.len() == 2 + 32 + 32 => Self::zcash_deserialize(&bytes[..]),
+            Ok(_) => Err(SerializationError::Parse(
+                "wrong sprout shielded addr length",
+            )),
             Err(_) => Err(SerializationError::Parse("bs58 decoding error")),
         }
     }
@@ -142,6 +164,13 @@ mod tests {
 
         assert_eq!(string, zc_addr.to_string());
     }
+
+    #[test]
+    fn from_string_errors() {
+        "t1bmMa1wJDFdbc2TiURQP5BbBz6jHjUBuHq"
+            .parse::<SproutShieldedAddress>()
+            .expect_err("transparent addresses are not sprout addresses");
+    }
 }
 
 #[cfg(test)]
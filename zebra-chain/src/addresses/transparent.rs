@@ -116,7 +116,8 @@ impl std::str::FromStr for TransparentAddress {
         let result = &bs58::decode(s).with_check(None).into_vec();
 
         match result {
-            Ok(bytes) => Self::zcash_deserialize(&bytes[..]),
+            Ok(bytes) if bytes.len() == 2 + 20 => Self::zcash_deserialize(&bytes[..]),
+            Ok(_) => Err(SerializationError::Parse("wrong t-addr length")),
             Err(_) => Err(SerializationError::Parse("t-addr decoding error")),
         }
     }
@@ -193,7 +194,7 @@ impl TransparentAddress {
     /// The resulting hash in both of these cases is always exactly 20
     /// bytes.
     /// https://en.bitcoin.it/Base58Check_encoding#Encoding_a_Bitcoin_address
-    fn hash_payload(bytes: &[u8]) -> [u8; 20] {
+    pub(crate) fn hash_payload(bytes: &[u8]) -> [u8; 20] {
         let sha_hash = Sha256::digest(bytes);
         let ripe_hash = Ripemd160::digest(&sha_hash);
         let mut payload = [0u8; 20];
@@ -202,6 +203,36 @@ impl TransparentAddress {
     }
 }
 
+impl TransparentAddress {
+    /// Returns the network for this address.
+    pub fn network(&self) -> Network {
+        match self {
+            TransparentAddress::PayToScriptHash { network, .. } => *network,
+            TransparentAddress::PayToPublicKeyHash { network, .. } => *network,
+        }
+    }
+
+    /// Returns the standard output script that pays to this address.
+    pub fn output_script(&self) -> Script {
+        match self {
+            TransparentAddress::PayToScriptHash { script_hash, .. } => {
+                // OP_HASH160 <script_hash> OP_EQUAL
+                let mut bytes = vec![0xa9, 20];
+                bytes.extend_from_slice(&script_hash[..]);
+                bytes.push(0x87);
+                Script(bytes)
+            }
+            TransparentAddress::PayToPublicKeyHash { pub_key_hash, .. } => {
+                // OP_DUP OP_HASH160 <pub_key_hash> OP_EQUALVERIFY OP_CHECKSIG
+                let mut bytes = vec![0x76, 0xa9, 20];
+                bytes.extend_from_slice(&pub_key_hash[..]);
+                bytes.extend_from_slice(&[0x88, 0xac]);
+                Script(bytes)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 impl TransparentAddress {
     fn p2pkh_strategy() -> impl Strategy<Value = Self> {
@@ -280,6 +311,21 @@ mod tests {
         assert_eq!(format!("{}", t_addr), "t3Vz22vK5z2LcKEdg16Yv4FFneEL1zg9ojd");
     }
 
+    #[test]
+    fn output_script() {
+        let t_addr: TransparentAddress = "t3Vz22vK5z2LcKEdg16Yv4FFneEL1zg9ojd".parse().unwrap();
+        let script = t_addr.output_script();
+        assert_eq!(
+            hex::encode(&script.0),
+            "a9147d46a730d31f97b1930d3368a967c309bd4d136a87"
+        );
+
+        let t_addr: TransparentAddress = "t1bmMa1wJDFdbc2TiURQP5BbBz6jHjUBuHq".parse().unwrap();
+        let script = t_addr.output_script();
+        assert_eq!(&script.0[..3], &[0x76, 0xa9, 20]);
+        assert_eq!(&script.0[23..], &[0x88, 0xac]);
+    }
+
     #[test]
     fn debug() {
         let t_addr: TransparentAddress = "t3Vz22vK5z2LcKEdg16Yv4FFneEL1zg9ojd".parse().unwrap();
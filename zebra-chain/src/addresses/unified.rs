@@ -0,0 +1,527 @@
+//! Unified Address types.
+//!
+//! Unified addresses are specified in [ZIP-316].
+//!
+//! [ZIP-316]: https://zips.z.cash/zip-0316
+
+use std::{convert::TryFrom, fmt, io};
+
+use bech32::{u5, FromBase32, ToBase32};
+use blake2b_simd::{Params, OUTBYTES};
+
+use crate::{
+    serialization::{
+        CompactSize, ReadZcashExt, SerializationError, WriteZcashExt, ZcashDeserialize,
+        ZcashSerialize,
+    },
+    Network,
+};
+
+/// Human-Readable Parts for input to bech32m encoding.
+mod human_readable_parts {
+    pub const MAINNET: &str = "u";
+    pub const TESTNET: &str = "utest";
+}
+
+/// Receiver typecodes.
+mod typecodes {
+    pub const P2PKH: u32 = 0x00;
+    pub const P2SH: u32 = 0x01;
+    pub const SAPLING: u32 = 0x02;
+    pub const ORCHARD: u32 = 0x03;
+}
+
+/// The length of the human-readable part padding at the end of the raw
+/// encoding.
+const PADDING_LEN: usize = 16;
+
+/// A receiver in a [`UnifiedAddress`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Receiver {
+    /// A transparent pay-to-public-key-hash address, as a public key hash.
+    P2pkh([u8; 20]),
+    /// A transparent pay-to-script-hash address, as a script hash.
+    P2sh([u8; 20]),
+    /// A raw Sapling shielded payment address: a diversifier and a
+    /// diversified transmission key.
+    Sapling([u8; 43]),
+    /// A raw Orchard shielded payment address.
+    Orchard([u8; 43]),
+    /// A receiver with a typecode that Zebra doesn't know about.
+    Unknown {
+        /// The receiver's typecode.
+        typecode: u32,
+        /// The receiver's encoding.
+        data: Vec<u8>,
+    },
+}
+
+impl Receiver {
+    /// Returns the typecode for this receiver.
+    pub fn typecode(&self) -> u32 {
+        match self {
+            Receiver::P2pkh(_) => typecodes::P2PKH,
+            Receiver::P2sh(_) => typecodes::P2SH,
+            Receiver::Sapling(_) => typecodes::SAPLING,
+            Receiver::Orchard(_) => typecodes::ORCHARD,
+            Receiver::Unknown { typecode, .. } => *typecode,
+        }
+    }
+
+    /// Returns the encoding of this receiver.
+    fn data(&self) -> &[u8] {
+        match self {
+            Receiver::P2pkh(data) | Receiver::P2sh(data) => &data[..],
+            Receiver::Sapling(data) | Receiver::Orchard(data) => &data[..],
+            Receiver::Unknown { data, .. } => data,
+        }
+    }
+
+    /// Returns true if this is a transparent receiver.
+    fn is_transparent(&self) -> bool {
+        matches!(self, Receiver::P2pkh(_) | Receiver::P2sh(_))
+    }
+}
+
+impl ZcashSerialize for Receiver {
+    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
+        CompactSize::from_len(self.typecode() as usize)?.zcash_serialize(&mut writer)?;
+        writer.write_bytes(self.data())
+    }
+}
+
+impl ZcashDeserialize for Receiver {
+    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let typecode = u64::from(CompactSize::zcash_deserialize(&mut reader)?);
+        let typecode = u32::try_from(typecode)
+            .map_err(|_| SerializationError::Parse("unified address typecode is too large"))?;
+        let data = reader.read_bytes()?;
+
+        let wrong_length = |_| SerializationError::Parse("wrong unified address receiver length");
+        Ok(match typecode {
+            typecodes::P2PKH => {
+                Receiver::P2pkh(<[u8; 20]>::try_from(&data[..]).map_err(wrong_length)?)
+            }
+            typecodes::P2SH => {
+                Receiver::P2sh(<[u8; 20]>::try_from(&data[..]).map_err(wrong_length)?)
+            }
+            typecodes::SAPLING => {
+                Receiver::Sapling(<[u8; 43]>::try_from(&data[..]).map_err(wrong_length)?)
+            }
+            typecodes::ORCHARD => {
+                Receiver::Orchard(<[u8; 43]>::try_from(&data[..]).map_err(wrong_length)?)
+            }
+            typecode => Receiver::Unknown { typecode, data },
+        })
+    }
+}
+
+/// A Unified Address, containing receivers for one or more address types.
+///
+/// Receivers are stored in ascending typecode order. Each unified address has
+/// at least one shielded receiver, and at most one transparent receiver.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnifiedAddress {
+    network: Network,
+    receivers: Vec<Receiver>,
+}
+
+impl UnifiedAddress {
+    /// Returns a new unified address with `receivers`, or an error if the
+    /// receivers are out of order, duplicated, or all transparent.
+    pub fn new(network: Network, receivers: Vec<Receiver>) -> Result<Self, SerializationError> {
+        if receivers
+            .windows(2)
+            .any(|pair| pair[0].typecode() >= pair[1].typecode())
+        {
+            return Err(SerializationError::Parse(
+                "unified address receivers are duplicated or out of order",
+            ));
+        }
+        if receivers.iter().filter(|r| r.is_transparent()).count() > 1 {
+            return Err(SerializationError::Parse(
+                "unified address has more than one transparent receiver",
+            ));
+        }
+        if receivers.iter().all(Receiver::is_transparent) {
+            return Err(SerializationError::Parse(
+                "unified address has no shielded receivers",
+            ));
+        }
+
+        Ok(UnifiedAddress { network, receivers })
+    }
+
+    /// Returns the network for this address.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Returns the receivers in this address, in ascending typecode order.
+    pub fn receivers(&self) -> &[Receiver] {
+        &self.receivers
+    }
+
+    fn human_readable_part(network: Network) -> &'static str {
+        match network {
+            Network::Mainnet => human_readable_parts::MAINNET,
+            _ => human_readable_parts::TESTNET,
+        }
+    }
+}
+
+impl fmt::Display for UnifiedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hrp = UnifiedAddress::human_readable_part(self.network);
+
+        let mut bytes = Vec::new();
+        for receiver in &self.receivers {
+            receiver
+                .zcash_serialize(&mut bytes)
+                .expect("receivers have valid lengths");
+        }
+        let mut padding = [0; PADDING_LEN];
+        padding[..hrp.len()].copy_from_slice(hrp.as_bytes());
+        bytes.extend_from_slice(&padding);
+
+        f4jumble::jumble(&mut bytes);
+        f.write_str(&bech32m::encode(hrp, &bytes))
+    }
+}
+
+impl std::str::FromStr for UnifiedAddress {
+    type Err = SerializationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hrp, mut bytes) = bech32m::decode(s)?;
+        let network = match hrp.as_str() {
+            human_readable_parts::MAINNET => Network::Mainnet,
+            human_readable_parts::TESTNET => Network::Testnet,
+            _ => return Err(SerializationError::Parse("unknown unified address prefix")),
+        };
+
+        if !f4jumble::VALID_LENGTH.contains(&bytes.len()) {
+            return Err(SerializationError::Parse("wrong unified address length"));
+        }
+        f4jumble::unjumble(&mut bytes);
+
+        let (items, padding) = bytes.split_at(bytes.len() - PADDING_LEN);
+        let mut expected_padding = [0; PADDING_LEN];
+        expected_padding[..hrp.len()].copy_from_slice(hrp.as_bytes());
+        if padding != expected_padding {
+            return Err(SerializationError::Parse("invalid unified address padding"));
+        }
+
+        let mut items = io::Cursor::new(items);
+        let mut receivers = Vec::new();
+        while (items.position() as usize) < items.get_ref().len() {
+            receivers.push(Receiver::zcash_deserialize(&mut items)?);
+        }
+
+        UnifiedAddress::new(network, receivers)
+    }
+}
+
+/// The F4Jumble permutation from [ZIP-316].
+///
+/// [ZIP-316]: https://zips.z.cash/zip-0316#jumbling
+mod f4jumble {
+    use std::ops::RangeInclusive;
+
+    use super::{Params, OUTBYTES};
+
+    /// The valid message lengths.
+    pub const VALID_LENGTH: RangeInclusive<usize> = 38..=4_194_368;
+
+    /// Applies F4Jumble to `message`, which must have a valid length.
+    pub fn jumble(message: &mut [u8]) {
+        let (left, right) = split(message);
+        g_round(0, left, right);
+        h_round(0, left, right);
+        g_round(1, left, right);
+        h_round(1, left, right);
+    }
+
+    /// Applies the inverse of F4Jumble to `message`, which must have a valid
+    /// length.
+    pub fn unjumble(message: &mut [u8]) {
+        let (left, right) = split(message);
+        h_round(1, left, right);
+        g_round(1, left, right);
+        h_round(0, left, right);
+        g_round(0, left, right);
+    }
+
+    fn split(message: &mut [u8]) -> (&mut [u8], &mut [u8]) {
+        assert!(VALID_LENGTH.contains(&message.len()));
+        let left_len = OUTBYTES.min(message.len() / 2);
+        message.split_at_mut(left_len)
+    }
+
+    fn h_round(i: u8, left: &mut [u8], right: &[u8]) {
+        let mut personal = *b"UA_F4Jumble_H\0\0\0";
+        personal[13] = i;
+        let hash = Params::new()
+            .hash_length(left.len())
+            .personal(&personal)
+            .hash(right);
+        xor(left, hash.as_bytes());
+    }
+
+    fn g_round(i: u8, left: &[u8], right: &mut [u8]) {
+        for (j, chunk) in right.chunks_mut(OUTBYTES).enumerate() {
+            let mut personal = *b"UA_F4Jumble_G\0\0\0";
+            personal[13] = i;
+            personal[14..].copy_from_slice(&(j as u16).to_le_bytes());
+            let hash = Params::new()
+                .hash_length(OUTBYTES)
+                .personal(&personal)
+                .hash(left);
+            xor(chunk, hash.as_bytes());
+        }
+    }
+
+    fn xor(target: &mut [u8], source: &[u8]) {
+        for (target, source) in target.iter_mut().zip(source) {
+            *target ^= source;
+        }
+    }
+}
+
+/// The bech32m encoding from [BIP-350], without the 90 character length
+/// limit, as required by [ZIP-316].
+///
+/// The `bech32` crate only supports the original bech32 checksum.
+///
+/// [BIP-350]: https://github.com/bitcoin/bips/blob/master/bip-0350.mediawiki
+/// [ZIP-316]: https://zips.z.cash/zip-0316#encoding-of-unified-addresses
+mod bech32m {
+    use super::{u5, FromBase32, SerializationError, ToBase32};
+
+    const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const CHECKSUM_CONSTANT: u32 = 0x2bc8_30a3;
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+
+    /// Returns the bech32m encoding of `bytes` with human-readable part
+    /// `hrp`.
+    pub fn encode(hrp: &str, bytes: &[u8]) -> String {
+        let data = bytes.to_base32();
+
+        let mut values = expand(hrp);
+        values.extend(data.iter().map(|d| d.to_u8()));
+        values.extend(&[0; 6]);
+        let checksum = polymod(&values) ^ CHECKSUM_CONSTANT;
+
+        let mut encoded = format!("{}1", hrp);
+        encoded.extend(data.iter().map(|d| CHARSET[usize::from(d.to_u8())] as char));
+        encoded
+            .extend((0..6).map(|i| CHARSET[((checksum >> (5 * (5 - i))) & 31) as usize] as char));
+        encoded
+    }
+
+    /// Decodes the bech32m string `s`, returning its lowercase
+    /// human-readable part and its data bytes.
+    pub fn decode(s: &str) -> Result<(String, Vec<u8>), SerializationError> {
+        if s.to_lowercase() != s && s.to_uppercase() != s {
+            return Err(SerializationError::Parse("bech32m string has mixed case"));
+        }
+        let s = s.to_lowercase();
+
+        let separator = s
+            .rfind('1')
+            .ok_or(SerializationError::Parse("bech32m string has no separator"))?;
+        let (hrp, data) = (&s[..separator], &s[separator + 1..]);
+        if hrp.is_empty() || data.len() < 6 || !hrp.bytes().all(|c| (33..=126).contains(&c)) {
+            return Err(SerializationError::Parse("invalid bech32m string"));
+        }
+
+        let data = data
+            .bytes()
+            .map(|c| CHARSET.iter().position(|&d| d == c).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(SerializationError::Parse("invalid bech32m character"))?;
+
+        let mut values = expand(hrp);
+        values.extend(&data);
+        if polymod(&values) != CHECKSUM_CONSTANT {
+            return Err(SerializationError::Parse("invalid bech32m checksum"));
+        }
+
+        let data = data[..data.len() - 6]
+            .iter()
+            .map(|&d| u5::try_from_u8(d).expect("characters are 5 bit values"))
+            .collect::<Vec<u5>>();
+        let bytes = Vec::<u8>::from_base32(&data)
+            .map_err(|_| SerializationError::Parse("invalid bech32m padding"))?;
+
+        Ok((hrp.to_owned(), bytes))
+    }
+
+    fn expand(hrp: &str) -> Vec<u8> {
+        let mut values = hrp.bytes().map(|c| c >> 5).collect::<Vec<_>>();
+        values.push(0);
+        values.extend(hrp.bytes().map(|c| c & 31));
+        values
+    }
+
+    fn polymod(values: &[u8]) -> u32 {
+        let mut checksum = 1u32;
+        for value in values {
+            let top = checksum >> 25;
+            checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(*value);
+            for (i, generator) in GENERATOR.iter().enumerate() {
+                if (top >> i) & 1 == 1 {
+                    checksum ^= generator;
+                }
+            }
+        }
+        checksum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from https://github.com/zcash-hackworks/zcash-test-vectors/blob/master/unified_address.py
+
+    /// A P2PKH and Sapling unified address.
+    const TRANSPARENT_SAPLING: &str = "u1l8xunezsvhq8fgzfl7404m450nwnd76zshscn6nfys7vyz2ywyh4cc5daaq0c7q2su5lqfh23sp7fkf3kt27ve5948mzpfdvckzaect2jtte308mkwlycj2u0eac077wu70vqcetkxf";
+
+    /// A P2PKH, Sapling, and Orchard unified address.
+    const TRANSPARENT_SAPLING_ORCHARD: &str = "u1pg2aaph7jp8rpf6yhsza25722sg5fcn3vaca6ze27hqjw7jvvhhuxkpcg0ge9xh6drsgdkda8qjq5chpehkcpxf87rnjryjqwymdheptpvnljqqrjqzjwkc2ma6hcq666kgwfytxwac8eyex6ndgr6ezte66706e3vaqrd25dzvzkc69kw0jgywtd0cmq52q5lkw6uh7hyvzjse8ksx";
+
+    /// A Sapling and unknown receiver unified address.
+    const SAPLING_UNKNOWN: &str = "u1sem2gcey0emntrvxyjv8hyhq0w5fr4sxaj3cppgrfqgg6laydh8m78gy2cw2p54zzak3alnnsx4xjuhazpkrfcd90wl0c7ldj6y095hh5j6j2evry9vg5jqp4dyqpwqeryu7pes4sxyyyqwn6egs5daxk4473v9xpgzrwv5n0tvs93nlj4xpphq4vs2w8um9ph7zkte08t7fa509mnrt9apuhr22xq34mp2svjnq6rvfn0hg6lkehxtlj39vgjxjlkjfhx8rw2f02ckq8k5szcxsnhkgr2cqlmf2udl2gqdqr5t6";
+
+    fn bytes<T>(data: &str) -> T
+    where
+        T: for<'a> TryFrom<&'a [u8]>,
+        for<'a> <T as TryFrom<&'a [u8]>>::Error: fmt::Debug,
+    {
+        T::try_from(&hex::decode(data).unwrap()[..]).expect("test vector has the right length")
+    }
+
+    #[test]
+    fn from_str_test_vectors() {
+        zebra_test::init();
+
+        let addr: UnifiedAddress = TRANSPARENT_SAPLING.parse().unwrap();
+        assert_eq!(addr.network(), Network::Mainnet);
+        assert_eq!(
+            addr.receivers(),
+            &[
+                Receiver::P2pkh(bytes("7bb83570b8fae146e03c5331a020b1e0892f631d")),
+                Receiver::Sapling(bytes("d8ef8293d26de832e7193f296ba1922d90f122c6135bc231eebd91efdb03b1a8606771cd4fd6480574d43e")),
+            ]
+        );
+
+        let addr: UnifiedAddress = TRANSPARENT_SAPLING_ORCHARD.parse().unwrap();
+        assert_eq!(
+            addr.receivers(),
+            &[
+                Receiver::P2pkh(bytes("cad268758c5e71493066446b98e71df9d1d6a5ca")),
+                Receiver::Sapling(bytes("9f6e0bf90a18fc0b9b83ae9f23ad4358648638482b5def8975635b66fd8a708335f9235a3186ec0f033f84")),
+                Receiver::Orchard(bytes("cecbe5e689a453a3fe10ccf7617e6c1fb382819d7fc9200a1f42092ac84a30378f8c1fb90dff71a6d5042d")),
+            ]
+        );
+
+        let addr: UnifiedAddress = SAPLING_UNKNOWN.parse().unwrap();
+        assert_eq!(addr.receivers().len(), 2);
+        assert_eq!(addr.receivers()[1].typecode(), 0xfffd);
+        assert_eq!(
+            addr.receivers()[1],
+            Receiver::Unknown {
+                typecode: 0xfffd,
+                data: hex::decode("d17d19f3355bcf73cecb8cb8a5da01307152f13936a270572670dc82d39026c6cb4cd4b0f7f5aa2a4f5a5341ec5dd715406f2fdd2afa733f5f641c8c21862a1bafce2609d9eecfa158cfb5cd79f88008e315dc7d8388e76c1782fd2795d18a763624c25fa959cc97489ce75745824b77868c53239cfbdf73ca").unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn from_str_display() {
+        zebra_test::init();
+
+        for s in &[
+            TRANSPARENT_SAPLING,
+            TRANSPARENT_SAPLING_ORCHARD,
+            SAPLING_UNKNOWN,
+        ] {
+            let addr: UnifiedAddress = s.parse().unwrap();
+            assert_eq!(&addr.to_string(), s);
+        }
+
+        let testnet = UnifiedAddress::new(Network::Testnet, vec![Receiver::Orchard([7; 43])])
+            .unwrap()
+            .to_string();
+        assert!(testnet.starts_with("utest1"));
+        assert_eq!(
+            testnet.parse::<UnifiedAddress>().unwrap().network(),
+            Network::Testnet
+        );
+    }
+
+    #[test]
+    fn from_str_errors() {
+        zebra_test::init();
+
+        // Truncated
+        TRANSPARENT_SAPLING[..TRANSPARENT_SAPLING.len() - 1]
+            .parse::<UnifiedAddress>()
+            .expect_err("truncated addresses should be rejected");
+
+        // Bech32 instead of bech32m
+        let bech32 = bech32::encode("u", vec![0u8; 64].to_base32()).unwrap();
+        bech32
+            .parse::<UnifiedAddress>()
+            .expect_err("bech32 checksums should be rejected");
+
+        // Invalid receiver sets
+        assert!(UnifiedAddress::new(Network::Mainnet, vec![Receiver::P2pkh([0; 20])]).is_err());
+        assert!(UnifiedAddress::new(
+            Network::Mainnet,
+            vec![Receiver::Orchard([0; 43]), Receiver::Sapling([0; 43])]
+        )
+        .is_err());
+        assert!(UnifiedAddress::new(
+            Network::Mainnet,
+            vec![
+                Receiver::P2pkh([0; 20]),
+                Receiver::P2sh([0; 20]),
+                Receiver::Orchard([0; 43])
+            ]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn bech32m_test_vectors() {
+        zebra_test::init();
+
+        // Test vectors from BIP-350
+        for s in &[
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ] {
+            let checksum = bech32m::decode(s);
+            // Some vectors have invalid padding, so only the checksum is checked
+            assert!(
+                !matches!(
+                    checksum,
+                    Err(SerializationError::Parse("invalid bech32m checksum"))
+                ),
+                "{} should have a valid checksum",
+                s
+            );
+        }
+    }
+}
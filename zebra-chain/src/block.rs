@@ -1,6 +1,7 @@
 //! Definitions of block datastructures.
 #![allow(clippy::unit_arg)]
 
+mod commitment;
 mod hash;
 mod header;
 mod serialize;
@@ -17,6 +18,10 @@ use proptest_derive::Arbitrary;
 use crate::transaction::Transaction;
 use crate::types::BlockHeight;
 
+pub use commitment::{
+    AuthDataRoot, ChainHistoryBlockTxAuthCommitmentHash, ChainHistoryMmrRootHash, Commitment,
+    CommitmentError, CHAIN_HISTORY_ACTIVATION_RESERVED,
+};
 pub use hash::BlockHeaderHash;
 pub use header::BlockHeader;
 
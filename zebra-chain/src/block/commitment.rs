@@ -0,0 +1,162 @@
+//! The block header commitment field.
+//!
+//! The meaning of the 32-byte commitment field in the block header changes
+//! in network upgrades:
+//!   - before Sapling, it is a reserved field, called `hashReserved`,
+//!   - in Sapling and Blossom, it is the final Sapling note commitment tree
+//!     root, called `hashFinalSaplingRoot`,
+//!   - in Heartwood and Canopy, it is the chain history tree root, called
+//!     `hashLightClientRoot`, except in the Heartwood activation block, where
+//!     it is reserved,
+//!   - from NU5 onward, it commits to the chain history tree root and the
+//!     authorizing data of the block's transactions, and is called
+//!     `hashBlockCommitments`.
+//!
+//! Network upgrades are consensus parameters, so the upgrade-specific parsing
+//! happens in `zebra_consensus::parameters::block_commitment`.
+
+use std::fmt;
+
+use thiserror::Error;
+
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+
+use crate::note_commitment_tree::SaplingNoteTreeRootHash;
+
+/// The commitment bytes in the Heartwood activation block.
+///
+/// [ZIP-221] says that this block has no chain history tree root, because its
+/// history tree is empty.
+///
+/// [ZIP-221]: https://zips.z.cash/zip-0221#block-header-changes
+pub const CHAIN_HISTORY_ACTIVATION_RESERVED: [u8; 32] = [0; 32];
+
+/// The personalization for block commitment hashes, from [ZIP-244].
+///
+/// [ZIP-244]: https://zips.z.cash/zip-0244#block-header-changes
+const BLOCK_COMMITMENTS_PERSONALIZATION: &[u8; 16] = b"ZcashBlockCommit";
+
+/// The interpreted contents of the block header commitment field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Commitment {
+    /// [Pre-Sapling] The reserved field.
+    ///
+    /// zcashd doesn't check these bytes, so they can have any value.
+    PreSaplingReserved([u8; 32]),
+
+    /// [Sapling and Blossom] The final Sapling note commitment tree root of
+    /// this block.
+    FinalSaplingRoot(SaplingNoteTreeRootHash),
+
+    /// [Heartwood activation block] The reserved field, which is always
+    /// [`CHAIN_HISTORY_ACTIVATION_RESERVED`].
+    ChainHistoryActivationReserved,
+
+    /// [Heartwood and Canopy, except the Heartwood activation block] The
+    /// root of the chain history tree, which commits to the previous blocks.
+    ChainHistoryRoot(ChainHistoryMmrRootHash),
+
+    /// [NU5 onward] A commitment to the chain history tree root and the
+    /// authorizing data of this block's transactions.
+    ChainHistoryBlockTxAuthCommitment(ChainHistoryBlockTxAuthCommitmentHash),
+}
+
+impl Commitment {
+    /// Returns the header bytes for this commitment.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        use Commitment::*;
+
+        match self {
+            PreSaplingReserved(bytes) => *bytes,
+            FinalSaplingRoot(root) => root.0,
+            ChainHistoryActivationReserved => CHAIN_HISTORY_ACTIVATION_RESERVED,
+            ChainHistoryRoot(root) => root.0,
+            ChainHistoryBlockTxAuthCommitment(hash) => hash.0,
+        }
+    }
+}
+
+/// The root hash of the chain history Merkle Mountain Range, as defined in
+/// [ZIP-221].
+///
+/// [ZIP-221]: https://zips.z.cash/zip-0221
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct ChainHistoryMmrRootHash(pub [u8; 32]);
+
+impl fmt::Debug for ChainHistoryMmrRootHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ChainHistoryMmrRootHash")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+/// The root of the Merkle tree of transaction authorizing data in a block,
+/// as defined in [ZIP-244].
+///
+/// [ZIP-244]: https://zips.z.cash/zip-0244#block-header-changes
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct AuthDataRoot(pub [u8; 32]);
+
+impl fmt::Debug for AuthDataRoot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("AuthDataRoot")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+/// The NU5 block commitment, which commits to the chain history tree root
+/// and the authorizing data root.
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct ChainHistoryBlockTxAuthCommitmentHash(pub [u8; 32]);
+
+impl fmt::Debug for ChainHistoryBlockTxAuthCommitmentHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ChainHistoryBlockTxAuthCommitmentHash")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+impl ChainHistoryBlockTxAuthCommitmentHash {
+    /// Computes the block commitment from the chain history tree root and
+    /// the authorizing data root, as defined in [ZIP-244].
+    ///
+    /// The hash also covers 32 zero bytes, which are reserved for future
+    /// commitments.
+    ///
+    /// [ZIP-244]: https://zips.z.cash/zip-0244#block-header-changes
+    pub fn from_commitments(
+        history_tree_root: &ChainHistoryMmrRootHash,
+        auth_data_root: &AuthDataRoot,
+    ) -> Self {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(BLOCK_COMMITMENTS_PERSONALIZATION)
+            .to_state()
+            .update(&history_tree_root.0[..])
+            .update(&auth_data_root.0[..])
+            .update(&[0; 32][..])
+            .finalize();
+
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(hash.as_bytes());
+        ChainHistoryBlockTxAuthCommitmentHash(bytes)
+    }
+}
+
+/// An invalid block header commitment.
+#[derive(Error, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommitmentError {
+    /// The Heartwood activation block has non-zero commitment bytes.
+    #[error("invalid chain history activation reserved block commitment: expected all zeroes, actual: {}", hex::encode(.actual))]
+    InvalidChainHistoryActivationReserved {
+        /// The commitment bytes in the block header.
+        actual: [u8; 32],
+    },
+}
@@ -1,11 +1,14 @@
-use std::{fmt, io};
+use std::{convert::TryFrom, fmt};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    serialization::{ReadZcashExt, SerializationError, ZcashDeserialize, ZcashSerialize},
+    serialization::{
+        SerializationError, TrustedPreallocate, ZcashDeserialize, ZcashSerialize,
+        MAX_PROTOCOL_MESSAGE_LEN,
+    },
     sha256d_writer::Sha256dWriter,
 };
 
@@ -23,7 +26,9 @@ use super::BlockHeader;
 /// the direct bytes of the transactions as well as the header. So
 /// for now I want to call it a `BlockHeaderHash` because that's
 /// more explicit.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, ZcashSerialize, ZcashDeserialize,
+)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct BlockHeaderHash(pub [u8; 32]);
 
@@ -45,27 +50,46 @@ impl<'a> From<&'a BlockHeader> for BlockHeaderHash {
     }
 }
 
-impl ZcashSerialize for BlockHeaderHash {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0)?;
-        Ok(())
+impl TrustedPreallocate for BlockHeaderHash {
+    fn max_allocation() -> u64 {
+        // Each hash is 32 bytes
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / 32
     }
 }
 
-impl ZcashDeserialize for BlockHeaderHash {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        Ok(BlockHeaderHash(reader.read_32_bytes()?))
+impl fmt::Display for BlockHeaderHash {
+    /// Formats the hash as hex, in the big-endian byte order used by zcashd
+    /// and block explorers.
+    ///
+    /// This is the reverse of the internal (serialized) byte order, which is
+    /// used by the `Debug` impl.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut reversed_bytes = self.0;
+        reversed_bytes.reverse();
+        f.write_str(&hex::encode(&reversed_bytes))
     }
 }
 
 impl std::str::FromStr for BlockHeaderHash {
     type Err = SerializationError;
+
+    /// Parses a hex hash in the big-endian byte order used by zcashd and
+    /// block explorers.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut bytes = [0; 32];
         if hex::decode_to_slice(s, &mut bytes[..]).is_err() {
             Err(SerializationError::Parse("hex decoding error"))
         } else {
+            bytes.reverse();
             Ok(BlockHeaderHash(bytes))
         }
     }
 }
+
+impl TryFrom<&str> for BlockHeaderHash {
+    type Error = SerializationError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
@@ -1,10 +1,15 @@
 use super::{BlockHeaderHash, Error};
-use crate::equihash_solution::EquihashSolution;
+use crate::equihash_solution::{self, EquihashSolution, EQUIHASH_SOLUTION_SIZE};
 use crate::merkle_tree::MerkleTreeRootHash;
-use crate::note_commitment_tree::SaplingNoteTreeRootHash;
 use crate::serialization::ZcashSerialize;
 use chrono::{DateTime, Duration, Utc};
 
+/// The Equihash `n` parameter for Zcash block headers.
+const EQUIHASH_N: u32 = 200;
+
+/// The Equihash `k` parameter for Zcash block headers.
+const EQUIHASH_K: u32 = 9;
+
 /// Block header.
 ///
 /// How are blocks chained together? They are chained together via the
@@ -35,10 +40,14 @@ pub struct BlockHeader {
     /// header.
     pub merkle_root_hash: MerkleTreeRootHash,
 
-    /// [Sapling onward] The root LEBS2OSP256(rt) of the Sapling note
-    /// commitment tree corresponding to the final Sapling treestate of
-    /// this block.
-    pub final_sapling_root_hash: SaplingNoteTreeRootHash,
+    /// The commitment field, whose meaning depends on the network upgrade.
+    ///
+    /// Before Sapling, it is reserved. In Sapling and Blossom, it is the root
+    /// LEBS2OSP256(rt) of the Sapling note commitment tree corresponding to
+    /// the final Sapling treestate of this block. From Heartwood onward, it
+    /// commits to the chain history tree. See [`Commitment`](super::Commitment)
+    /// for details.
+    pub commitment_bytes: [u8; 32],
 
     /// The block timestamp is a Unix epoch time (UTC) when the miner
     /// started hashing the header (according to the miner).
@@ -68,20 +77,43 @@ pub struct BlockHeader {
 impl BlockHeader {
     /// Returns true if the header is valid based on its `EquihashSolution`
     pub fn is_equihash_solution_valid(&self) -> Result<(), EquihashError> {
-        let n = 200;
-        let k = 9;
         let nonce = &self.nonce;
         let solution = &self.solution.0;
+        let input = self.equihash_input();
+
+        equihash::is_valid_solution(EQUIHASH_N, EQUIHASH_K, &input, nonce, solution)?;
+
+        Ok(())
+    }
+
+    /// Returns the Equihash solutions for this header's fields and nonce,
+    /// ignoring its current solution.
+    ///
+    /// Uses a slow CPU solver, which is only suitable for tests and networks
+    /// with a low difficulty. Some nonces have no solutions.
+    pub fn equihash_solutions(&self) -> Vec<EquihashSolution> {
+        let input = self.equihash_input();
+
+        equihash_solution::solve(EQUIHASH_N, EQUIHASH_K, &input, &self.nonce)
+            .into_iter()
+            .map(|bytes| {
+                let mut solution = [0; EQUIHASH_SOLUTION_SIZE];
+                solution.copy_from_slice(&bytes);
+                EquihashSolution(solution)
+            })
+            .collect()
+    }
+
+    /// Returns the header fields that are used as the Equihash input, which
+    /// are all the fields before the nonce.
+    fn equihash_input(&self) -> Vec<u8> {
         let mut input = Vec::new();
 
         self.zcash_serialize(&mut input)
             .expect("serialization into a vec can't fail");
+        input.truncate(EquihashSolution::INPUT_LENGTH);
 
-        let input = &input[0..EquihashSolution::INPUT_LENGTH];
-
-        equihash::is_valid_solution(n, k, input, nonce, solution)?;
-
-        Ok(())
+        input
     }
 
     /// Check if `self.time` is less than or equal to
@@ -2,11 +2,13 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use chrono::{TimeZone, Utc};
 use std::io;
 
-use crate::equihash_solution::EquihashSolution;
+use crate::equihash_solution::{EquihashSolution, EQUIHASH_SOLUTION_SIZE};
 use crate::merkle_tree::MerkleTreeRootHash;
-use crate::note_commitment_tree::SaplingNoteTreeRootHash;
 use crate::serialization::ZcashDeserializeInto;
-use crate::serialization::{ReadZcashExt, SerializationError, ZcashDeserialize, ZcashSerialize};
+use crate::serialization::{
+    ReadZcashExt, SerializationError, TrustedPreallocate, ZcashDeserialize, ZcashSerialize,
+    MAX_PROTOCOL_MESSAGE_LEN,
+};
 
 use super::Block;
 use super::BlockHeader;
@@ -18,7 +20,7 @@ impl ZcashSerialize for BlockHeader {
         writer.write_u32::<LittleEndian>(self.version)?;
         self.previous_block_hash.zcash_serialize(&mut writer)?;
         writer.write_all(&self.merkle_root_hash.0[..])?;
-        writer.write_all(&self.final_sapling_root_hash.0[..])?;
+        writer.write_all(&self.commitment_bytes[..])?;
         // this is a truncating cast, rather than a saturating cast
         // but u32 times are valid until 2106, and our block verification time
         // checks should detect any truncation.
@@ -66,7 +68,7 @@ impl ZcashDeserialize for BlockHeader {
             version,
             previous_block_hash: BlockHeaderHash::zcash_deserialize(&mut reader)?,
             merkle_root_hash: MerkleTreeRootHash(reader.read_32_bytes()?),
-            final_sapling_root_hash: SaplingNoteTreeRootHash(reader.read_32_bytes()?),
+            commitment_bytes: reader.read_32_bytes()?,
             // This can't panic, because all u32 values are valid `Utc.timestamp`s
             time: Utc.timestamp(reader.read_u32::<LittleEndian>()? as i64, 0),
             bits: reader.read_u32::<LittleEndian>()?,
@@ -76,6 +78,16 @@ impl ZcashDeserialize for BlockHeader {
     }
 }
 
+/// The serialized size of a block header: the fixed-size fields, and the
+/// equihash solution with its 3-byte length.
+const BLOCK_HEADER_LEN: u64 = 4 + 32 + 32 + 32 + 4 + 4 + 32 + 3 + EQUIHASH_SOLUTION_SIZE as u64;
+
+impl TrustedPreallocate for BlockHeader {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / BLOCK_HEADER_LEN
+    }
+}
+
 impl ZcashSerialize for Block {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         // All block structs are validated when they are parsed.
@@ -2,7 +2,6 @@ use super::*;
 
 use crate::equihash_solution::EquihashSolution;
 use crate::merkle_tree::MerkleTreeRootHash;
-use crate::note_commitment_tree::SaplingNoteTreeRootHash;
 use crate::serialization::{
     SerializationError, ZcashDeserialize, ZcashDeserializeInto, ZcashSerialize,
 };
@@ -14,7 +13,10 @@ use proptest::{
     arbitrary::{any, Arbitrary},
     prelude::*,
 };
-use std::io::{Cursor, ErrorKind, Write};
+use std::{
+    convert::TryFrom,
+    io::{Cursor, ErrorKind, Write},
+};
 
 impl Arbitrary for BlockHeader {
     type Parameters = ();
@@ -25,7 +27,7 @@ impl Arbitrary for BlockHeader {
             (4u32..(i32::MAX as u32)),
             any::<BlockHeaderHash>(),
             any::<MerkleTreeRootHash>(),
-            any::<SaplingNoteTreeRootHash>(),
+            any::<[u8; 32]>(),
             // time is interpreted as u32 in the spec, but rust timestamps are i64
             (0i64..(u32::MAX as i64)),
             any::<u32>(),
@@ -37,7 +39,7 @@ impl Arbitrary for BlockHeader {
                     version,
                     previous_block_hash,
                     merkle_root_hash,
-                    final_sapling_root_hash,
+                    commitment_bytes,
                     timestamp,
                     bits,
                     nonce,
@@ -46,7 +48,7 @@ impl Arbitrary for BlockHeader {
                     version,
                     previous_block_hash,
                     merkle_root_hash,
-                    final_sapling_root_hash,
+                    commitment_bytes,
                     time: Utc.timestamp(timestamp, 0),
                     bits,
                     nonce,
@@ -125,6 +127,33 @@ fn deserialize_block() {
         .expect("block test vector should deserialize");
 }
 
+#[test]
+fn mainnet_block_vectors() {
+    for (&height, block) in zebra_test::vectors::MAINNET_BLOCKS.iter() {
+        let block = block
+            .zcash_deserialize_into::<Block>()
+            .expect("block test vector should deserialize");
+
+        assert_eq!(block.coinbase_height(), Some(BlockHeight(height)));
+
+        let hash_str = zebra_test::vectors::MAINNET_BLOCK_HASHES[&height];
+        assert_eq!(block.hash().to_string(), hash_str);
+        assert_eq!(
+            hash_str
+                .parse::<BlockHeaderHash>()
+                .expect("hash test vector should parse"),
+            block.hash()
+        );
+
+        let mut merkle_root = block.header.merkle_root_hash.0;
+        merkle_root.reverse();
+        assert_eq!(
+            hex::encode(merkle_root),
+            zebra_test::vectors::MAINNET_MERKLE_ROOTS[&height]
+        );
+    }
+}
+
 #[test]
 fn block_limits_multi_tx() {
     // Test multiple small transactions to fill a block max size
@@ -198,11 +227,25 @@ proptest! {
     #[test]
     fn blockheaderhash_roundtrip(hash in any::<BlockHeaderHash>()) {
         let bytes = hash.zcash_serialize_to_vec()?;
-        let other_hash = bytes.zcash_deserialize_into()?;
+        let other_hash: BlockHeaderHash = bytes.zcash_deserialize_into()?;
+
+        prop_assert_eq![hash, other_hash];
+    }
+
+    #[test]
+    fn blockheaderhash_display_roundtrip(hash in any::<BlockHeaderHash>()) {
+        let other_hash = BlockHeaderHash::try_from(hash.to_string().as_str())?;
 
         prop_assert_eq![hash, other_hash];
     }
 
+    #[test]
+    fn blockheight_display_roundtrip(height in any::<BlockHeight>()) {
+        let other_height = BlockHeight::try_from(height.to_string().as_str())?;
+
+        prop_assert_eq![height, other_height];
+    }
+
     #[test]
     fn blockheader_roundtrip(header in any::<BlockHeader>()) {
         let bytes = header.zcash_serialize_to_vec()?;
@@ -371,3 +414,32 @@ fn time_check_fixed() {
         node_time_check(now, block_header_time).expect("the inverse comparison should be valid");
     }
 }
+
+proptest! {
+    #[test]
+    fn block_commitments_depend_on_both_roots(
+        history_tree_root in any::<ChainHistoryMmrRootHash>(),
+        auth_data_root in any::<AuthDataRoot>(),
+        other_root in any::<[u8; 32]>(),
+    ) {
+        let commitment =
+            ChainHistoryBlockTxAuthCommitmentHash::from_commitments(&history_tree_root, &auth_data_root);
+
+        prop_assert_eq!(
+            commitment,
+            ChainHistoryBlockTxAuthCommitmentHash::from_commitments(&history_tree_root, &auth_data_root)
+        );
+        if other_root != history_tree_root.0 {
+            prop_assert_ne!(
+                commitment,
+                ChainHistoryBlockTxAuthCommitmentHash::from_commitments(&ChainHistoryMmrRootHash(other_root), &auth_data_root)
+            );
+        }
+        if other_root != auth_data_root.0 {
+            prop_assert_ne!(
+                commitment,
+                ChainHistoryBlockTxAuthCommitmentHash::from_commitments(&history_tree_root, &AuthDataRoot(other_root))
+            );
+        }
+    }
+}
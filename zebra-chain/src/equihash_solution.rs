@@ -2,12 +2,14 @@
 
 use crate::{
     serde_helpers,
-    serialization::{
-        ReadZcashExt, SerializationError, WriteZcashExt, ZcashDeserialize, ZcashSerialize,
-    },
+    serialization::{CompactSize, SerializationError, ZcashDeserialize, ZcashSerialize},
 };
 use std::{fmt, io};
 
+mod solver;
+
+pub use solver::solve;
+
 /// The size of an Equihash solution in bytes (always 1344).
 pub(crate) const EQUIHASH_SOLUTION_SIZE: usize = 1344;
 
@@ -60,7 +62,7 @@ impl Eq for EquihashSolution {}
 
 impl ZcashSerialize for EquihashSolution {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_compactsize(EQUIHASH_SOLUTION_SIZE as u64)?;
+        CompactSize::from_len(EQUIHASH_SOLUTION_SIZE)?.zcash_serialize(&mut writer)?;
         writer.write_all(&self.0[..])?;
         Ok(())
     }
@@ -68,8 +70,8 @@ impl ZcashSerialize for EquihashSolution {
 
 impl ZcashDeserialize for EquihashSolution {
     fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let solution_size = reader.read_compactsize()?;
-        if solution_size != (EQUIHASH_SOLUTION_SIZE as u64) {
+        let solution_size: usize = CompactSize::zcash_deserialize(&mut reader)?.into();
+        if solution_size != EQUIHASH_SOLUTION_SIZE {
             return Err(SerializationError::Parse(
                 "incorrect equihash solution size",
             ));
@@ -86,6 +88,7 @@ mod tests {
     use crate::block::{Block, BlockHeader};
     use crate::serialization::ZcashDeserializeInto;
     use proptest::{arbitrary::Arbitrary, collection::vec, prelude::*};
+    use std::convert::TryFrom;
 
     impl Arbitrary for EquihashSolution {
         type Parameters = ();
@@ -262,9 +265,18 @@ mod tests {
         zebra_test::init();
 
         for size in EQUIHASH_SIZE_TESTS {
-            let mut data = Vec::new();
-            data.write_compactsize(*size as u64)
-                .expect("Compact size should serialize");
+            // Sizes above the maximum can't be constructed as a CompactSize,
+            // so write their canonical encoding directly
+            let mut data = match CompactSize::try_from(*size) {
+                Ok(size) => size
+                    .zcash_serialize_to_vec()
+                    .expect("Compact size should serialize"),
+                Err(_) => {
+                    let mut data = vec![0xff];
+                    data.extend_from_slice(&size.to_le_bytes());
+                    data
+                }
+            };
             data.resize(data.len() + EQUIHASH_SOLUTION_SIZE, 0);
             let result = EquihashSolution::zcash_deserialize(data.as_slice());
             if *size == (EQUIHASH_SOLUTION_SIZE as u64) {
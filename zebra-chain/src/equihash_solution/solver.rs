@@ -0,0 +1,284 @@
+//! A CPU Equihash solver, using Wagner's generalized birthday algorithm.
+//!
+//! This solver is much slower than optimised mining software, and it uses
+//! hundreds of megabytes of memory for the (200, 9) parameters. It is intended
+//! for tests and private networks with a low difficulty.
+
+use blake2b_simd::Params;
+
+/// The maximum number of rows kept in each round, as a multiple of the
+/// initial number of indices.
+///
+/// Each round produces about as many rows as it consumes, but large collision
+/// groups can produce many more. Dropping the excess rows bounds memory use,
+/// at the cost of a small number of missed solutions.
+const MAX_ROWS_FACTOR: usize = 2;
+
+/// Returns the minimal-encoded Equihash solutions for `input` and `nonce`,
+/// using the parameters `n` and `k`.
+///
+/// Each solution passes `equihash::is_valid_solution`. Most nonces have one or
+/// two solutions, but some have none.
+///
+/// # Panics
+///
+/// If `n` and `k` are not valid Equihash parameters.
+pub fn solve(n: u32, k: u32, input: &[u8], nonce: &[u8]) -> Vec<Vec<u8>> {
+    assert!(
+        n % 8 == 0 && k >= 3 && k < n && n % (k + 1) == 0,
+        "invalid Equihash parameters"
+    );
+
+    let collision_bits = (n / (k + 1)) as usize;
+    let hash_len = (n / 8) as usize;
+    let index_count = 1usize << (collision_bits + 1);
+    let max_rows = index_count * MAX_ROWS_FACTOR;
+
+    let mut hashes = initial_hashes(n, k, input, nonce, index_count);
+    // `links[round]` contains the pair of rows in round `round` that were
+    // combined to make each row in round `round + 1`. Round 0 rows are the
+    // indices themselves.
+    let mut links: Vec<Vec<(u32, u32)>> = Vec::with_capacity(k as usize - 1);
+
+    for round in 0..(k as usize - 1) {
+        let start = round * collision_bits;
+        let mut next_hashes = Vec::with_capacity(hashes.len());
+        let mut next_links = Vec::with_capacity(hashes.len() / hash_len);
+
+        for (a, b) in colliding_pairs(&hashes, hash_len, start, collision_bits) {
+            if next_links.len() >= max_rows {
+                break;
+            }
+
+            // Rows that share a child have repeated indices
+            if let Some(previous) = links.last() {
+                let (a_left, a_right) = previous[a as usize];
+                let (b_left, b_right) = previous[b as usize];
+                if a_left == b_left || a_left == b_right || a_right == b_left || a_right == b_right
+                {
+                    continue;
+                }
+            }
+
+            let a_hash = &hashes[a as usize * hash_len..][..hash_len];
+            let b_hash = &hashes[b as usize * hash_len..][..hash_len];
+            let xor: Vec<u8> = a_hash.iter().zip(b_hash).map(|(a, b)| a ^ b).collect();
+
+            // All-zero rows can only come from repeated indices
+            if xor.iter().all(|byte| *byte == 0) {
+                continue;
+            }
+
+            next_hashes.extend_from_slice(&xor);
+            next_links.push((a, b));
+        }
+
+        hashes = next_hashes;
+        links.push(next_links);
+    }
+
+    // The final round needs a collision on the remaining two chunks
+    let start = (k as usize - 1) * collision_bits;
+    let mut solutions: Vec<Vec<u8>> = colliding_pairs(&hashes, hash_len, start, 2 * collision_bits)
+        .filter_map(|(a, b)| {
+            let indices = join(
+                indices(&links, links.len(), a),
+                indices(&links, links.len(), b),
+            );
+
+            let mut distinct = indices.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            if distinct.len() != indices.len() {
+                return None;
+            }
+
+            Some(minimal_encoding(&indices, collision_bits + 1))
+        })
+        .collect();
+
+    solutions.sort();
+    solutions.dedup();
+    solutions
+}
+
+/// Returns the hashes for the first `index_count` indices, concatenated.
+fn initial_hashes(n: u32, k: u32, input: &[u8], nonce: &[u8], index_count: usize) -> Vec<u8> {
+    let hash_len = (n / 8) as usize;
+    let indices_per_hash = (512 / n) as usize;
+
+    let mut personal = [0; 16];
+    personal[..8].copy_from_slice(b"ZcashPoW");
+    personal[8..12].copy_from_slice(&n.to_le_bytes());
+    personal[12..].copy_from_slice(&k.to_le_bytes());
+
+    let mut base_state = Params::new()
+        .hash_length(indices_per_hash * hash_len)
+        .personal(&personal)
+        .to_state();
+    base_state.update(input);
+    base_state.update(nonce);
+
+    let mut hashes = Vec::with_capacity(index_count * hash_len);
+    let mut group = 0u32;
+    while hashes.len() < index_count * hash_len {
+        let mut state = base_state.clone();
+        state.update(&group.to_le_bytes());
+        let output = state.finalize();
+
+        let remaining = index_count * hash_len - hashes.len();
+        let output_len = remaining.min(indices_per_hash * hash_len);
+        hashes.extend_from_slice(&output.as_bytes()[..output_len]);
+
+        group += 1;
+    }
+
+    hashes
+}
+
+/// Returns the pairs of rows in `hashes` that have the same `len` bits,
+/// starting at bit `start`.
+fn colliding_pairs(
+    hashes: &[u8],
+    hash_len: usize,
+    start: usize,
+    len: usize,
+) -> impl Iterator<Item = (u32, u32)> {
+    let mut keys: Vec<(u64, u32)> = hashes
+        .chunks_exact(hash_len)
+        .enumerate()
+        .map(|(row, hash)| (bits(hash, start, len), row as u32))
+        .collect();
+    keys.sort_unstable();
+
+    let mut pairs = Vec::new();
+    let mut group_start = 0;
+    while group_start < keys.len() {
+        let key = keys[group_start].0;
+        let group_len = keys[group_start..]
+            .iter()
+            .take_while(|(other_key, _)| *other_key == key)
+            .count();
+        let group = &keys[group_start..group_start + group_len];
+
+        for (i, (_, a)) in group.iter().enumerate() {
+            for (_, b) in &group[i + 1..] {
+                pairs.push((*a, *b));
+            }
+        }
+
+        group_start += group_len;
+    }
+
+    pairs.into_iter()
+}
+
+/// Returns `len` bits from `hash`, starting at bit `start`, interpreted as a
+/// big-endian integer.
+///
+/// `len` must be at most 64.
+fn bits(hash: &[u8], start: usize, len: usize) -> u64 {
+    let first = start / 8;
+    let last = (start + len - 1) / 8;
+
+    let window = hash[first..=last]
+        .iter()
+        .fold(0u128, |window, byte| (window << 8) | u128::from(*byte));
+    let trailing_bits = (last + 1) * 8 - (start + len);
+
+    ((window >> trailing_bits) & ((1u128 << len) - 1)) as u64
+}
+
+/// Returns the indices of `row` in `round`, in solution order.
+fn indices(links: &[Vec<(u32, u32)>], round: usize, row: u32) -> Vec<u32> {
+    if round == 0 {
+        return vec![row];
+    }
+
+    let (left, right) = links[round - 1][row as usize];
+    join(
+        indices(links, round - 1, left),
+        indices(links, round - 1, right),
+    )
+}
+
+/// Joins the indices of two subtrees, so that the subtree with the smaller
+/// first index comes first.
+fn join(mut left: Vec<u32>, mut right: Vec<u32>) -> Vec<u32> {
+    if left[0] > right[0] {
+        std::mem::swap(&mut left, &mut right);
+    }
+    left.extend(right);
+    left
+}
+
+/// Returns the minimal encoding of `indices`, which packs each index into
+/// `bit_len` big-endian bits.
+fn minimal_encoding(indices: &[u32], bit_len: usize) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(indices.len() * bit_len / 8);
+    let mut buffer = 0u64;
+    let mut buffer_bits = 0;
+
+    for index in indices {
+        buffer = (buffer << bit_len) | u64::from(*index);
+        buffer_bits += bit_len;
+
+        while buffer_bits >= 8 {
+            buffer_bits -= 8;
+            encoded.push((buffer >> buffer_bits) as u8);
+        }
+        buffer &= (1 << buffer_bits) - 1;
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_extraction() {
+        zebra_test::init();
+
+        let hash = [0b1010_1100, 0b0101_0011, 0xff];
+        assert_eq!(bits(&hash, 0, 4), 0b1010);
+        assert_eq!(bits(&hash, 4, 8), 0b1100_0101);
+        assert_eq!(bits(&hash, 6, 12), 0b0001_0100_1111);
+        assert_eq!(bits(&hash, 0, 24), 0x00ac_53ff);
+    }
+
+    #[test]
+    fn minimal_encoding_packs_bits() {
+        zebra_test::init();
+
+        // Eight 9-bit indices fill nine bytes
+        let indices = [1, 2, 3, 4, 5, 6, 7, 0x1ff];
+        let encoded = minimal_encoding(&indices, 9);
+        assert_eq!(encoded.len(), 9);
+        assert_eq!(encoded[0], 0b0000_0000);
+        assert_eq!(encoded[1], 0b1000_0000);
+        assert_eq!(encoded[8], 0xff);
+    }
+
+    /// Solutions for small parameters pass the reference verifier.
+    #[test]
+    fn small_solutions_are_valid() {
+        zebra_test::init();
+
+        let (n, k) = (48, 5);
+        let input = b"Equihash solver test input";
+
+        let mut found = 0;
+        for nonce in 0u8..16 {
+            let nonce = [nonce; 32];
+            for solution in solve(n, k, input, &nonce) {
+                equihash::is_valid_solution(n, k, input, &nonce, &solution)
+                    .expect("solver solutions should be valid");
+                found += 1;
+            }
+        }
+
+        assert!(found > 0, "most nonces have solutions");
+    }
+}
@@ -3,3 +3,6 @@
 pub mod sapling;
 pub mod sprout;
 pub mod transparent;
+
+#[cfg(test)]
+mod tests;
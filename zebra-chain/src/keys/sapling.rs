@@ -23,6 +23,7 @@ use std::{
 
 use bech32::{self, FromBase32, ToBase32};
 use rand_core::{CryptoRng, RngCore};
+use subtle::{Choice, ConstantTimeEq};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
@@ -52,7 +53,7 @@ pub const RANDOMNESS_BEACON_URS: &[u8; 64] =
 /// PRF^expand(sk, t) := BLAKE2b-512("Zcash_ExpandSeed", sk || t)
 ///
 /// https://zips.z.cash/protocol/protocol.pdf#concreteprfs
-fn prf_expand(sk: [u8; 32], t: &[u8]) -> [u8; 64] {
+pub(crate) fn prf_expand(sk: [u8; 32], t: &[u8]) -> [u8; 64] {
     let hash = blake2b_simd::Params::new()
         .hash_length(64)
         .personal(b"Zcash_ExpandSeed")
@@ -124,7 +125,7 @@ fn jubjub_group_hash(d: [u8; 8], m: &[u8]) -> Option<jubjub::ExtendedPoint> {
 ///
 /// [0]: https://github.com/zcash/librustzcash/blob/master/zcash_primitives/src/jubjub/mod.rs#L409
 /// https://zips.z.cash/protocol/protocol.pdf#concretegrouphashjubjub
-fn find_group_hash(d: [u8; 8], m: &[u8]) -> jubjub::ExtendedPoint {
+pub(crate) fn find_group_hash(d: [u8; 8], m: &[u8]) -> jubjub::ExtendedPoint {
     let mut tag = m.to_vec();
     let i = tag.len();
     tag.push(0u8);
@@ -153,7 +154,7 @@ fn zcash_h() -> jubjub::ExtendedPoint {
 /// Used to derive a diversified base point from a diversifier value.
 ///
 /// https://zips.z.cash/protocol/protocol.pdf#concretediversifyhash
-fn diversify_hash(d: [u8; 11]) -> Option<jubjub::ExtendedPoint> {
+pub(crate) fn diversify_hash(d: [u8; 11]) -> Option<jubjub::ExtendedPoint> {
     jubjub_group_hash(*b"Zcash_gd", &d)
 }
 
@@ -176,13 +177,27 @@ mod sk_hrp {
 /// Sapling key types derive from the SpendingKey value.
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#saplingkeycomponents
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct SpendingKey {
     network: Network,
     bytes: [u8; 32],
 }
 
+impl ConstantTimeEq for SpendingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        Choice::from((self.network == other.network) as u8) & self.bytes.ct_eq(&other.bytes)
+    }
+}
+
+impl PartialEq for SpendingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SpendingKey {}
+
 // TODO: impl a From that accepts a Network?
 
 impl From<[u8; 32]> for SpendingKey {
@@ -250,9 +265,23 @@ impl SpendingKey {
 /// _Spend Description_, proving ownership of notes.
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#saplingkeycomponents
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct SpendAuthorizingKey(pub Scalar);
 
+impl ConstantTimeEq for SpendAuthorizingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for SpendAuthorizingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SpendAuthorizingKey {}
+
 impl fmt::Debug for SpendAuthorizingKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("SpendAuthorizingKey")
@@ -282,7 +311,7 @@ impl From<SpendingKey> for SpendAuthorizingKey {
 
 impl PartialEq<[u8; 32]> for SpendAuthorizingKey {
     fn eq(&self, other: &[u8; 32]) -> bool {
-        <[u8; 32]>::from(*self) == *other
+        <[u8; 32]>::from(*self).ct_eq(other).into()
     }
 }
 
@@ -292,9 +321,23 @@ impl PartialEq<[u8; 32]> for SpendAuthorizingKey {
 /// Used in the _Spend Statement_ to prove nullifier integrity.
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#saplingkeycomponents
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct ProofAuthorizingKey(pub Scalar);
 
+impl ConstantTimeEq for ProofAuthorizingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for ProofAuthorizingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for ProofAuthorizingKey {}
+
 impl fmt::Debug for ProofAuthorizingKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("ProofAuthorizingKey")
@@ -323,7 +366,7 @@ impl From<SpendingKey> for ProofAuthorizingKey {
 
 impl PartialEq<[u8; 32]> for ProofAuthorizingKey {
     fn eq(&self, other: &[u8; 32]) -> bool {
-        <[u8; 32]>::from(*self) == *other
+        <[u8; 32]>::from(*self).ct_eq(other).into()
     }
 }
 
@@ -333,9 +376,23 @@ impl PartialEq<[u8; 32]> for ProofAuthorizingKey {
 /// Used to decrypt outgoing notes without spending them.
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#saplingkeycomponents
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct OutgoingViewingKey(pub [u8; 32]);
 
+impl ConstantTimeEq for OutgoingViewingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for OutgoingViewingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for OutgoingViewingKey {}
+
 impl fmt::Debug for OutgoingViewingKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("OutgoingViewingKey")
@@ -374,7 +431,7 @@ impl From<SpendingKey> for OutgoingViewingKey {
 
 impl PartialEq<[u8; 32]> for OutgoingViewingKey {
     fn eq(&self, other: &[u8; 32]) -> bool {
-        self.0 == *other
+        self.0.ct_eq(other).into()
     }
 }
 
@@ -427,9 +484,21 @@ impl PartialEq<[u8; 32]> for AuthorizingKey {
 /// Used to create a _Nullifier_ per note.
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#saplingkeycomponents
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct NullifierDerivingKey(pub jubjub::AffinePoint);
 
+impl ConstantTimeEq for NullifierDerivingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for NullifierDerivingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
 impl fmt::Debug for NullifierDerivingKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("NullifierDerivingKey")
@@ -476,7 +545,7 @@ impl From<ProofAuthorizingKey> for NullifierDerivingKey {
 
 impl PartialEq<[u8; 32]> for NullifierDerivingKey {
     fn eq(&self, other: &[u8; 32]) -> bool {
-        <[u8; 32]>::from(*self) == *other
+        <[u8; 32]>::from(*self).ct_eq(other).into()
     }
 }
 
@@ -494,12 +563,26 @@ mod ivk_hrp {
 /// Used to decrypt incoming notes without spending them.
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#saplingkeycomponents
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct IncomingViewingKey {
     network: Network,
     scalar: Scalar,
 }
 
+impl ConstantTimeEq for IncomingViewingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        Choice::from((self.network == other.network) as u8) & self.scalar.ct_eq(&other.scalar)
+    }
+}
+
+impl PartialEq for IncomingViewingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for IncomingViewingKey {}
+
 // TODO: impl a From that accepts a Network?
 
 impl fmt::Debug for IncomingViewingKey {
@@ -587,7 +670,7 @@ impl FromStr for IncomingViewingKey {
 
 impl PartialEq<[u8; 32]> for IncomingViewingKey {
     fn eq(&self, other: &[u8; 32]) -> bool {
-        self.scalar.to_bytes() == *other
+        self.scalar.to_bytes().ct_eq(other).into()
     }
 }
 
@@ -758,7 +841,7 @@ mod fvk_hrp {
 /// test network, the Human-Readable Part is “zviewtestsapling”.
 ///
 /// https://zips.z.cash/protocol/protocol.pdf#saplingfullviewingkeyencoding
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct FullViewingKey {
     network: Network,
     authorizing_key: AuthorizingKey,
@@ -766,6 +849,27 @@ pub struct FullViewingKey {
     outgoing_viewing_key: OutgoingViewingKey,
 }
 
+impl ConstantTimeEq for FullViewingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // The authorizing key is public, so it doesn't need a constant time
+        // comparison
+        Choice::from((self.network == other.network) as u8)
+            & Choice::from((self.authorizing_key == other.authorizing_key) as u8)
+            & self
+                .nullifier_deriving_key
+                .ct_eq(&other.nullifier_deriving_key)
+            & self.outgoing_viewing_key.ct_eq(&other.outgoing_viewing_key)
+    }
+}
+
+impl PartialEq for FullViewingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for FullViewingKey {}
+
 // TODO: impl a From that accepts a Network?
 
 impl fmt::Debug for FullViewingKey {
@@ -11,6 +11,7 @@ use std::{fmt, io};
 
 use byteorder::{ByteOrder, LittleEndian};
 use rand_core::{CryptoRng, RngCore};
+use subtle::{Choice, ConstantTimeEq};
 
 #[cfg(test)]
 use proptest::{array, prelude::*};
@@ -56,7 +57,7 @@ fn prf_addr(x: [u8; 32], t: u8) -> [u8; 32] {
 ///
 /// All other Sprout key types derive from the SpendingKey value.
 /// Actually 252 bits.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct SpendingKey {
     /// What would normally be the value inside a tuple struct.
@@ -65,6 +66,20 @@ pub struct SpendingKey {
     pub network: Network,
 }
 
+impl ConstantTimeEq for SpendingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        Choice::from((self.network == other.network) as u8) & self.bytes.ct_eq(&other.bytes)
+    }
+}
+
+impl PartialEq for SpendingKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SpendingKey {}
+
 impl ZcashSerialize for SpendingKey {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         match self.network {
@@ -209,11 +224,22 @@ pub struct IncomingViewingKey {
 
 // Can't derive PartialEq because ReceivingKey aka
 // x25519_dalek::StaticSecret does not impl it.
+impl ConstantTimeEq for IncomingViewingKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // The paying key is public, so it doesn't need a constant time
+        // comparison
+        Choice::from((self.network == other.network) as u8)
+            & Choice::from((self.paying_key == other.paying_key) as u8)
+            & self
+                .receiving_key
+                .to_bytes()
+                .ct_eq(&other.receiving_key.to_bytes())
+    }
+}
+
 impl PartialEq for IncomingViewingKey {
     fn eq(&self, other: &Self) -> bool {
-        self.network == other.network
-            && self.paying_key.0 == other.paying_key.0
-            && self.receiving_key.to_bytes() == other.receiving_key.to_bytes()
+        self.ct_eq(other).into()
     }
 }
 
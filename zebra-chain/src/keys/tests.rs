@@ -0,0 +1,113 @@
+//! Checks that secret types use constant-time equality.
+
+use subtle::ConstantTimeEq;
+
+use super::{sapling, sprout};
+use crate::{notes::NoteCommitmentRandomness, types::MAC};
+
+/// The source files that define key, note, and MAC types, and their names.
+const SOURCES: &[(&str, &str)] = &[
+    ("keys::sapling", include_str!("sapling.rs")),
+    ("keys::sprout", include_str!("sprout.rs")),
+    ("keys::transparent", include_str!("transparent.rs")),
+    ("notes", include_str!("../notes.rs")),
+    ("types", include_str!("../types.rs")),
+];
+
+/// Types that contain secret data, so their equality checks must not leak
+/// timing information.
+const SECRET_TYPES: &[(&str, &str)] = &[
+    ("keys::sapling", "SpendingKey"),
+    ("keys::sapling", "SpendAuthorizingKey"),
+    ("keys::sapling", "ProofAuthorizingKey"),
+    ("keys::sapling", "OutgoingViewingKey"),
+    ("keys::sapling", "NullifierDerivingKey"),
+    ("keys::sapling", "IncomingViewingKey"),
+    ("keys::sapling", "FullViewingKey"),
+    ("keys::sprout", "SpendingKey"),
+    ("keys::sprout", "IncomingViewingKey"),
+    ("notes", "NoteCommitmentRandomness"),
+    ("types", "MAC"),
+];
+
+/// Types that only contain public data, so they can use derived equality.
+const PUBLIC_TYPES: &[(&str, &str)] = &[
+    ("keys::sapling", "AuthorizingKey"),
+    ("keys::sapling", "Diversifier"),
+    ("keys::sapling", "TransmissionKey"),
+    ("keys::sprout", "PayingKey"),
+    ("types", "BlockHeight"),
+    ("types", "Script"),
+    ("types", "Sha256dChecksum"),
+];
+
+/// Check at compile time that each secret type implements `ConstantTimeEq`.
+#[test]
+fn secret_types_are_constant_time_eq() {
+    fn assert_constant_time_eq<T: ConstantTimeEq>() {}
+
+    assert_constant_time_eq::<sapling::SpendingKey>();
+    assert_constant_time_eq::<sapling::SpendAuthorizingKey>();
+    assert_constant_time_eq::<sapling::ProofAuthorizingKey>();
+    assert_constant_time_eq::<sapling::OutgoingViewingKey>();
+    assert_constant_time_eq::<sapling::NullifierDerivingKey>();
+    assert_constant_time_eq::<sapling::IncomingViewingKey>();
+    assert_constant_time_eq::<sapling::FullViewingKey>();
+    assert_constant_time_eq::<sprout::SpendingKey>();
+    assert_constant_time_eq::<sprout::IncomingViewingKey>();
+    assert_constant_time_eq::<NoteCommitmentRandomness>();
+    assert_constant_time_eq::<MAC>();
+}
+
+/// List all the types in the key, note, and MAC modules, so new types have
+/// to make a choice here.
+///
+/// Secret types must implement `ConstantTimeEq`, and use it in `PartialEq`,
+/// rather than deriving `PartialEq`.
+#[test]
+fn all_types_are_classified() {
+    zebra_test::init();
+
+    for (module, source) in SOURCES {
+        let mut lines = source.lines();
+        let mut derives = Vec::new();
+
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.starts_with("#[derive(") {
+                derives.push(line);
+                continue;
+            }
+
+            if !line.starts_with("pub struct ") {
+                if !line.starts_with("#[") && !line.starts_with("///") {
+                    derives.clear();
+                }
+                continue;
+            }
+            let name = line["pub struct ".len()..]
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .expect("split always returns at least one item");
+            let key = (*module, name);
+
+            if SECRET_TYPES.contains(&key) {
+                assert!(
+                    !derives.iter().any(|d| d.contains("PartialEq")),
+                    "secret type {}::{} must not derive PartialEq",
+                    module,
+                    name
+                );
+            } else {
+                assert!(
+                    PUBLIC_TYPES.contains(&key),
+                    "{}::{} must be listed in SECRET_TYPES or PUBLIC_TYPES",
+                    module,
+                    name
+                );
+            }
+
+            derives.clear();
+        }
+    }
+}
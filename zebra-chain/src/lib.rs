@@ -9,7 +9,9 @@
 #[macro_use]
 extern crate serde;
 
-mod merkle_tree;
+// Lets the serialization derives use `::zebra_chain` paths inside this crate
+extern crate self as zebra_chain;
+
 mod serde_helpers;
 mod sha256d_writer;
 
@@ -17,14 +19,17 @@ pub mod addresses;
 pub mod block;
 pub mod equihash_solution;
 pub mod keys;
+pub mod merkle_tree;
 pub mod note_commitment_tree;
 pub mod notes;
 pub mod nullifier;
+pub mod parameters;
 pub mod proofs;
 pub mod serialization;
 pub mod transaction;
 pub mod types;
 pub mod utils;
+pub mod work;
 
 pub use ed25519_zebra;
 pub use redjubjub;
@@ -2,14 +2,14 @@
 //! node values.
 #![allow(clippy::unit_arg)]
 
-use std::{fmt, io};
+use std::{fmt, io, io::Write, iter::FromIterator};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
 use crate::serialization::{SerializationError, ZcashDeserialize, ZcashSerialize};
 use crate::sha256d_writer::Sha256dWriter;
-use crate::transaction::Transaction;
+use crate::transaction::{Transaction, TransactionHash};
 
 /// A binary hash tree of SHA256d (two rounds of SHA256) hashes for
 /// node values.
@@ -46,6 +46,43 @@ impl From<MerkleTree<Transaction>> for MerkleTreeRootHash {
     }
 }
 
+impl FromIterator<TransactionHash> for MerkleTreeRootHash {
+    /// Returns the root of the merkle tree of `hashes`, in block order.
+    ///
+    /// Like Bitcoin, if a level of the tree has an odd number of nodes, the
+    /// last node is paired with itself.
+    fn from_iter<I: IntoIterator<Item = TransactionHash>>(hashes: I) -> Self {
+        let mut level: Vec<[u8; 32]> = hashes.into_iter().map(|hash| hash.0).collect();
+
+        // Blocks always have a coinbase transaction, so this only happens
+        // for invalid blocks
+        if level.is_empty() {
+            return MerkleTreeRootHash([0; 32]);
+        }
+
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| {
+                    let left = &pair[0];
+                    let right = pair.get(1).unwrap_or(left);
+
+                    let mut hash_writer = Sha256dWriter::default();
+                    hash_writer
+                        .write_all(&left[..])
+                        .expect("Sha256dWriter is infallible");
+                    hash_writer
+                        .write_all(&right[..])
+                        .expect("Sha256dWriter is infallible");
+                    hash_writer.finish()
+                })
+                .collect();
+        }
+
+        MerkleTreeRootHash(level[0])
+    }
+}
+
 impl fmt::Debug for MerkleTreeRootHash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("MerkleTreeRootHash")
@@ -53,3 +90,29 @@ impl fmt::Debug for MerkleTreeRootHash {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{block::Block, serialization::ZcashDeserializeInto};
+
+    #[test]
+    fn block_test_vectors() {
+        zebra_test::init();
+
+        for block_bytes in zebra_test::vectors::TEST_BLOCKS.iter() {
+            let block: Block = block_bytes
+                .zcash_deserialize_into()
+                .expect("block is valid");
+
+            let merkle_root: MerkleTreeRootHash = block
+                .transactions
+                .iter()
+                .map(|tx| TransactionHash::from(tx.as_ref()))
+                .collect();
+
+            assert_eq!(merkle_root, block.header.merkle_root_hash);
+        }
+    }
+}
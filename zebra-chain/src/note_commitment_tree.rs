@@ -44,6 +44,24 @@ impl fmt::Debug for SaplingNoteTreeRootHash {
     }
 }
 
+/// Sprout note commitment tree root node hash.
+///
+/// JoinSplit descriptions use a root of the Sprout note commitment tree as
+/// their anchor. The anchor is either the root of the tree at some block in
+/// the past, or the root produced by a previous JoinSplit in the same
+/// transaction.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct SproutNoteTreeRootHash(pub [u8; 32]);
+
+impl fmt::Debug for SproutNoteTreeRootHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SproutNoteTreeRootHash")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
 impl From<SaplingNoteCommitmentTree> for SaplingNoteTreeRootHash {
     fn from(_tree: SaplingNoteCommitmentTree) -> Self {
         // TODO: The Sapling note commitment tree requires a Pedersen
@@ -3,6 +3,20 @@ mod memo;
 pub mod sapling;
 pub mod sprout;
 
+use subtle::{Choice, ConstantTimeEq};
+
 /// The randomness used in the Pedersen Hash for note commitment.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug)]
 pub struct NoteCommitmentRandomness(pub [u8; 32]);
+
+impl ConstantTimeEq for NoteCommitmentRandomness {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for NoteCommitmentRandomness {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
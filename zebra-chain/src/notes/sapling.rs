@@ -1,16 +1,22 @@
 //!
 #![allow(dead_code)]
 
-use std::{fmt, io};
+use std::fmt;
 
 #[cfg(test)]
 use proptest::{arbitrary::Arbitrary, collection::vec, prelude::*};
 
 use crate::serde_helpers;
-use crate::serialization::{SerializationError, ZcashDeserialize, ZcashSerialize};
+use crate::serialization::{ZcashDeserialize, ZcashSerialize};
 
 use super::*;
 
+mod commitment;
+mod decrypt;
+
+pub use commitment::note_commitment;
+pub use decrypt::{RecoveredNote, NOTE_PLAINTEXT_V1, NOTE_PLAINTEXT_V2};
+
 /// A _Diversifier_, an 11 byte value used to randomize the
 /// recipient's final public shielded payment address to create a
 /// _diversified payment address_.
@@ -40,7 +46,7 @@ pub struct NotePlaintext {
 }
 
 /// A ciphertext component for encrypted output notes.
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ZcashSerialize, ZcashDeserialize)]
 pub struct EncryptedCiphertext(#[serde(with = "serde_helpers::BigArray")] pub [u8; 580]);
 
 impl fmt::Debug for EncryptedCiphertext {
@@ -71,21 +77,6 @@ impl PartialEq for EncryptedCiphertext {
 
 impl Eq for EncryptedCiphertext {}
 
-impl ZcashSerialize for EncryptedCiphertext {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0[..])?;
-        Ok(())
-    }
-}
-
-impl ZcashDeserialize for EncryptedCiphertext {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let mut bytes = [0; 580];
-        reader.read_exact(&mut bytes[..])?;
-        Ok(Self(bytes))
-    }
-}
-
 #[cfg(test)]
 impl Arbitrary for EncryptedCiphertext {
     type Parameters = ();
@@ -104,7 +95,7 @@ impl Arbitrary for EncryptedCiphertext {
 }
 
 /// A ciphertext component for encrypted output notes.
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ZcashSerialize, ZcashDeserialize)]
 pub struct OutCiphertext(#[serde(with = "serde_helpers::BigArray")] pub [u8; 80]);
 
 impl fmt::Debug for OutCiphertext {
@@ -135,21 +126,6 @@ impl PartialEq for OutCiphertext {
 
 impl Eq for OutCiphertext {}
 
-impl ZcashSerialize for OutCiphertext {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0[..])?;
-        Ok(())
-    }
-}
-
-impl ZcashDeserialize for OutCiphertext {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let mut bytes = [0; 80];
-        reader.read_exact(&mut bytes[..])?;
-        Ok(Self(bytes))
-    }
-}
-
 #[cfg(test)]
 impl Arbitrary for OutCiphertext {
     type Parameters = ();
@@ -0,0 +1,293 @@
+//! Sapling note commitments.
+//!
+//! Sapling notes are committed to using a windowed Pedersen commitment, as
+//! described in [protocol specification §5.4.7.2][ps]. Zebra only needs the
+//! u-coordinate of the commitment, `cm_u`, which is the `cmu` field of each
+//! Sapling output.
+//!
+//! [ps]: https://zips.z.cash/protocol/protocol.pdf#concretewindowedcommit
+
+use crate::keys::sapling::{diversify_hash, find_group_hash, Diversifier, TransmissionKey};
+
+/// The personalization for Sapling note commitments.
+const NOTE_COMMITMENT_PERSONALIZATION: [u8; 8] = *b"Zcash_PH";
+
+/// The maximum number of 3-bit chunks in each Pedersen hash segment, `c`.
+const PEDERSEN_HASH_CHUNKS_PER_SEGMENT: usize = 63;
+
+/// Returns the bits of `bytes`, with the least significant bit of each byte
+/// first.
+fn le_bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+}
+
+/// _PedersenHashToPoint_, as described in [protocol specification
+/// §5.4.1.7][ps].
+///
+/// `domain` is an 8-byte domain separator, and `bits` is the message.
+///
+/// [ps]: https://zips.z.cash/protocol/protocol.pdf#concretepedersenhash
+fn pedersen_hash_to_point(domain: [u8; 8], bits: &[bool]) -> jubjub::ExtendedPoint {
+    let mut result = jubjub::ExtendedPoint::identity();
+
+    for (i, segment) in bits
+        .chunks(3 * PEDERSEN_HASH_CHUNKS_PER_SEGMENT)
+        .enumerate()
+    {
+        // I_i = FindGroupHash(D, I2LEOSP_32(i - 1)), with one-based segments
+        let generator = find_group_hash(domain, &(i as u32).to_le_bytes());
+        result += generator * segment_scalar(segment);
+    }
+
+    result
+}
+
+/// Returns the scalar `⟨M_i⟩` for a Pedersen hash `segment`.
+///
+/// The last chunk of the last segment is padded with zero bits.
+fn segment_scalar(segment: &[bool]) -> jubjub::Fr {
+    let mut scalar = jubjub::Fr::zero();
+    // 2^(4 * (j - 1)), with one-based chunks
+    let mut shift = jubjub::Fr::one();
+
+    for chunk in segment.chunks(3) {
+        let bit = |i: usize| chunk.get(i).copied().unwrap_or(false) as u64;
+
+        // enc(m_j) = (1 - 2 * s_2) * (1 + s_0 + 2 * s_1)
+        let mut enc = jubjub::Fr::from(1 + bit(0) + 2 * bit(1));
+        if bit(2) == 1 {
+            enc = -enc;
+        }
+
+        scalar += enc * shift;
+        shift *= jubjub::Fr::from(16);
+    }
+
+    scalar
+}
+
+/// Returns `cm_u`, the u-coordinate of the Sapling note commitment for a note
+/// paying `value` to the address with `diversifier` and `transmission_key`,
+/// with note commitment randomness `rcm`.
+///
+/// _NoteCommit^Sapling_rcm(g*_d, pk*_d, v) :=
+/// WindowedPedersenCommit_rcm([1; 6] || I2LEBSP_64(v) || g*_d || pk*_d)_
+///
+/// Returns `None` if `diversifier` is invalid.
+pub fn note_commitment(
+    diversifier: Diversifier,
+    transmission_key: TransmissionKey,
+    value: u64,
+    rcm: jubjub::Fr,
+) -> Option<[u8; 32]> {
+    let g_d = diversify_hash(diversifier.0)?;
+
+    let mut bits = vec![true; 6];
+    bits.extend(le_bits(&value.to_le_bytes()));
+    bits.extend(le_bits(&jubjub::AffinePoint::from(g_d).to_bytes()));
+    bits.extend(le_bits(&<[u8; 32]>::from(transmission_key)));
+
+    // WindowedPedersenCommit_r(s) :=
+    //     PedersenHashToPoint("Zcash_PH", s) + [r] FindGroupHash("Zcash_PH", "r")
+    let commitment = pedersen_hash_to_point(NOTE_COMMITMENT_PERSONALIZATION, &bits)
+        + find_group_hash(NOTE_COMMITMENT_PERSONALIZATION, b"r") * rcm;
+
+    Some(jubjub::AffinePoint::from(commitment).get_u().to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keys::sapling::{IncomingViewingKey, SpendingKey};
+
+    #[test]
+    fn note_commitments_bind_each_field() {
+        zebra_test::init();
+
+        let diversifier = Diversifier::from(SpendingKey::from([1; 32]));
+        let transmission_key =
+            TransmissionKey::from((IncomingViewingKey::from([2; 32]), diversifier));
+        let rcm = jubjub::Fr::from_bytes_wide(&[3; 64]);
+
+        let cmu = note_commitment(diversifier, transmission_key, 1234, rcm)
+            .expect("the default diversifier is valid");
+        assert_eq!(
+            note_commitment(diversifier, transmission_key, 1234, rcm),
+            Some(cmu)
+        );
+
+        assert_ne!(
+            note_commitment(diversifier, transmission_key, 1235, rcm),
+            Some(cmu)
+        );
+        assert_ne!(
+            note_commitment(diversifier, transmission_key, 1234, rcm + jubjub::Fr::one()),
+            Some(cmu)
+        );
+
+        let other_key = TransmissionKey::from((IncomingViewingKey::from([4; 32]), diversifier));
+        assert_ne!(
+            note_commitment(diversifier, other_key, 1234, rcm),
+            Some(cmu)
+        );
+    }
+
+    #[test]
+    fn segment_scalars_use_signed_chunks() {
+        zebra_test::init();
+
+        // Each 3-bit chunk encodes a value in {±1, ±2, ±3, ±4}
+        assert_eq!(segment_scalar(&[]), jubjub::Fr::zero());
+        assert_eq!(segment_scalar(&[false, false, false]), jubjub::Fr::one());
+        assert_eq!(segment_scalar(&[true, true, false]), jubjub::Fr::from(4));
+        assert_eq!(segment_scalar(&[true, false, true]), -jubjub::Fr::from(2));
+
+        // Later chunks are shifted by 4 bits, and short chunks are padded
+        assert_eq!(
+            segment_scalar(&[false, false, false, true]),
+            jubjub::Fr::from(1 + 2 * 16)
+        );
+    }
+
+    /// Returns the big-endian hex encoding of `element`, which is the
+    /// encoding used by the test vectors.
+    fn field_hex(element: jubjub::Fq) -> String {
+        let mut bytes = element.to_bytes();
+        bytes.reverse();
+        hex::encode(bytes)
+    }
+
+    #[test]
+    fn pedersen_hash_test_vectors() {
+        zebra_test::init();
+
+        // From https://github.com/zcash-hackworks/zcash-test-vectors/blob/master/sapling_pedersen.py
+        //
+        // Each input starts with the 6-bit personalization for note
+        // commitments or Merkle tree layers. The hashes are (u, v).
+        for (bits, u, v) in &[
+            (
+                "111111",
+                "06b1187c11ca4fb4383b2e0d0dbbde3ad3617338b5029187ec65a5eaed5e4d0b",
+                "3ce70f536652f0dea496393a1e55c4e08b9d55508e16d11e5db40d4810cbc982",
+            ),
+            (
+                "1111110",
+                "2fc3bc454c337f71d4f04f86304262fcbfc9ecd808716b92fc42cbe6827f7f1a",
+                "46d0d25bf1a654eedc6a9b1e5af398925113959feac31b7a2c036ff9b9ec0638",
+            ),
+            (
+                "1111111",
+                "4f8ce0e0a9e674b3ab9606a7d7aefba386e81583d81918127814cde41d209d97",
+                "312b5ab93b14c9b9af334fe1fe3c50fffb53fbd074fa40ca600febde7c97e346",
+            ),
+            (
+                "111111100",
+                "4f8ce0e0a9e674b3ab9606a7d7aefba386e81583d81918127814cde41d209d97",
+                "312b5ab93b14c9b9af334fe1fe3c50fffb53fbd074fa40ca600febde7c97e346",
+            ),
+            (
+                concat!(
+                    "1111111000001111011100011100001000010011101011010001101000010000",
+                    "0010100010111110111010111001001101001101110000111011001100101001",
+                    "0111111111000010111010011111001010100110100010100101010101000",
+                ),
+                "2da510317620f5dfdce1f31db6019f947eedcf02ff2972cff597a5c3ad21f5dd",
+                "198789969c0c33e6c359b9da4a51771f4d50863f36beef90436944fe568399f2",
+            ),
+            (
+                concat!(
+                    "1111111100110101110101110010011101000000010101011000011011110011",
+                    "1000011011001010110110010010001000111010110010110000111101001010",
+                    "0101000010010110001111001110011101011010011011000110011001011101",
+                    "0110011110000101101101001001101010111011101011000101011101011111",
+                    "0111110001101100100000101000100001000100011011101100101111101100",
+                    "0111111110001010110010001110011010001101010111011001010110001111",
+                    "1010101011110101011111000010110110011000011000000110010010001100",
+                    "1100110001001101001001010001010100011110101010001011100011111010",
+                    "1010010101010111001000111110101010100001011011100101111011011000",
+                    "1000000100010011011110100011001011011001011100010101101111100000",
+                    "0101010110110001001111110101001101000011011101010100000101001100",
+                    "11000100001110101100001111010111110010100001010101110",
+                ),
+                "0666c2bce7f362a2b807d212e9a577f116891a932affd7addec39fbf372c494e",
+                "6758bccfaf2e47c07756b96edea23aa8d10c33b38220bd1c411af612eeec18ab",
+            ),
+            (
+                "000000",
+                "62454a957289b3930d10f3def0d512cfe0ef3de06421321221af3558de9d481d",
+                "0279f0aebfb66e53ff69fba16b6608dbf4319b944432f45c6e69a3dbd1f7b330",
+            ),
+            (
+                concat!(
+                    "1101101101101101101101101101101101101101101101101101101101101101",
+                    "1011011011011011011011011011011011011011011011011011011011011011",
+                    "0110110110110110110110110110110110110110110110110110110110110",
+                ),
+                "414f6ba05f6b92da1f9051950769e1083d05615def32b016ae424309828a11f4",
+                "471d2109656afcb96d0609b371b132b97efcf72c6051064dd19fdc004799bfa9",
+            ),
+        ] {
+            let bits: Vec<bool> = bits.chars().map(|bit| bit == '1').collect();
+            let hash = jubjub::AffinePoint::from(pedersen_hash_to_point(
+                NOTE_COMMITMENT_PERSONALIZATION,
+                &bits,
+            ));
+
+            assert_eq!(&field_hex(hash.get_u()), u);
+            assert_eq!(&field_hex(hash.get_v()), v);
+        }
+    }
+
+    #[test]
+    fn note_commitment_test_vectors() {
+        zebra_test::init();
+
+        // From https://github.com/zcash-hackworks/zcash-test-vectors/blob/master/sapling_note_encryption.py
+        //
+        // (diversifier, transmission key, value, rcm, cmu)
+        for (diversifier, transmission_key, value, rcm, cmu) in &[
+            (
+                "f19d9b797e39f337445839",
+                "db4cd2b0aac4f7eb8ca131f16567c445a9555126d3c29f14e3d776e841ae7415",
+                100_000_000,
+                "39176dac39ace4980ecc8d778e89860255ec3615060000000000000000000000",
+                "635572f572a8a1a0b7acbc0afc6d66f14a02efacde7bdf03443ed4c3e551d470",
+            ),
+            (
+                "aef180f6e34e354b888f81",
+                "a6b13ea336ddb7a67bb09a0e68e9d3cfb39210831ea3a296ba09a922060fd38b",
+                200_000_000,
+                "478ba0ee6e1a75b600036f26f18b7015ab556beddf8b960238869f89dd804e06",
+                "0c87417577480b6977ba92c55425d62b03b1e5f3c3829cac49bfe515ae722945",
+            ),
+            (
+                "7599f0bf9b57cd2dc299b6",
+                "66141739514b28f05def8a18eeee5eed4d44c6225c3c65d88dd9907708012f5a",
+                300_000_000,
+                "147cf2b51b4c7c63cb77b99e8b783e5b5111db0a7ca04d6c014a1d7da83bae0a",
+                "b3b4e7ab080b9b0fe473cfc5a3105e9a062a4ee49edd7095a671637e0057242b",
+            ),
+        ] {
+            let mut d = [0; 11];
+            hex::decode_to_slice(diversifier, &mut d).expect("test vector is valid hex");
+            let mut pk_d = [0; 32];
+            hex::decode_to_slice(transmission_key, &mut pk_d).expect("test vector is valid hex");
+            let mut rcm_bytes = [0; 32];
+            hex::decode_to_slice(rcm, &mut rcm_bytes).expect("test vector is valid hex");
+            let rcm = jubjub::Fr::from_bytes(&rcm_bytes).unwrap();
+
+            let commitment = note_commitment(
+                Diversifier::from(d),
+                TransmissionKey::from(pk_d),
+                *value,
+                rcm,
+            )
+            .expect("test vector diversifier is valid");
+            assert_eq!(&hex::encode(commitment), cmu);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Sapling output recovery, using an outgoing viewing key.
+//!
+//! Zebra doesn't have a wallet, so it only decrypts outputs to check
+//! consensus rules. Coinbase outputs must be recoverable with the all-zero
+//! outgoing viewing key ([ZIP-213]), and the version of their note plaintext
+//! depends on the network upgrade ([ZIP-212]).
+//!
+//! [ZIP-212]: https://zips.z.cash/zip-0212
+//! [ZIP-213]: https://zips.z.cash/zip-0213
+
+use std::convert::TryInto;
+
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, Aead, NewAead},
+    ChaCha20Poly1305,
+};
+use subtle::CtOption;
+
+use super::note_commitment;
+use crate::{
+    keys::sapling::{diversify_hash, prf_expand, Diversifier, OutgoingViewingKey, TransmissionKey},
+    transaction::Output,
+};
+
+/// The note plaintext lead byte for notes before [ZIP-212].
+///
+/// [ZIP-212]: https://zips.z.cash/zip-0212
+pub const NOTE_PLAINTEXT_V1: u8 = 0x01;
+
+/// The note plaintext lead byte for [ZIP-212] notes, which derive the
+/// ephemeral secret key and the note commitment randomness from `rseed`.
+///
+/// [ZIP-212]: https://zips.z.cash/zip-0212
+pub const NOTE_PLAINTEXT_V2: u8 = 0x02;
+
+/// The length of a decrypted `enc_ciphertext`.
+const NOTE_PLAINTEXT_LEN: usize = 564;
+
+/// The length of a decrypted `out_ciphertext`.
+const OUT_PLAINTEXT_LEN: usize = 64;
+
+/// A Sapling note, recovered from an output using an outgoing viewing key.
+///
+/// The memo is not recovered.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RecoveredNote {
+    /// The note plaintext lead byte, which is the note plaintext version.
+    pub lead_byte: u8,
+    /// The diversifier of the recipient's address.
+    pub diversifier: Diversifier,
+    /// The transmission key of the recipient's address.
+    pub transmission_key: TransmissionKey,
+    /// The value of the note, in zatoshis.
+    pub value: u64,
+    /// The note commitment randomness in V1 notes, or the seed that it is
+    /// derived from in V2 notes.
+    pub rseed: [u8; 32],
+}
+
+impl Output {
+    /// Recovers the note in this output, using the outgoing viewing key
+    /// `ovk`, as described in [protocol specification §4.19.3][ps].
+    ///
+    /// Returns `None` if either ciphertext doesn't decrypt, the note
+    /// plaintext version is unknown, or the note doesn't match the ephemeral
+    /// key or the note commitment.
+    ///
+    /// [ps]: https://zips.z.cash/protocol/protocol.pdf#decryptovk
+    pub fn recover_note(&self, ovk: &OutgoingViewingKey) -> Option<RecoveredNote> {
+        let epk_bytes = self.ephemeral_key.to_bytes();
+
+        // ock = PRF^ock(ovk, cv, cmu, epk)
+        let ock = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"Zcash_Derive_ock")
+            .to_state()
+            .update(&ovk.0)
+            .update(&self.cv)
+            .update(&self.cmu)
+            .update(&epk_bytes)
+            .finalize();
+        let out_plaintext = sym_decrypt(ock.as_bytes(), &self.out_ciphertext.0[..])?;
+        if out_plaintext.len() != OUT_PLAINTEXT_LEN {
+            return None;
+        }
+
+        let pk_d = ct_option(jubjub::AffinePoint::from_bytes(
+            out_plaintext[..32].try_into().expect("slice is 32 bytes"),
+        ))?;
+        let esk = ct_option(jubjub::Fr::from_bytes(
+            out_plaintext[32..].try_into().expect("slice is 32 bytes"),
+        ))?;
+
+        // KA^Sapling.Agree(esk, pk_d) = [8 * esk] pk_d
+        let shared_secret = jubjub::AffinePoint::from(pk_d.mul_by_cofactor() * esk);
+        let k_enc = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"Zcash_SaplingKDF")
+            .to_state()
+            .update(&shared_secret.to_bytes())
+            .update(&epk_bytes)
+            .finalize();
+        let plaintext = sym_decrypt(k_enc.as_bytes(), &self.enc_ciphertext.0[..])?;
+        if plaintext.len() != NOTE_PLAINTEXT_LEN {
+            return None;
+        }
+
+        let lead_byte = plaintext[0];
+        let diversifier: [u8; 11] = plaintext[1..12].try_into().expect("slice is 11 bytes");
+        let value = u64::from_le_bytes(plaintext[12..20].try_into().expect("slice is 8 bytes"));
+        let rseed: [u8; 32] = plaintext[20..52].try_into().expect("slice is 32 bytes");
+
+        let rcm = match lead_byte {
+            // rcm must be a canonical scalar
+            NOTE_PLAINTEXT_V1 => ct_option(jubjub::Fr::from_bytes(&rseed))?,
+            // esk and rcm are derived from rseed
+            NOTE_PLAINTEXT_V2 => {
+                if jubjub::Fr::from_bytes_wide(&prf_expand(rseed, &[5])) != esk {
+                    return None;
+                }
+                jubjub::Fr::from_bytes_wide(&prf_expand(rseed, &[4]))
+            }
+            _ => return None,
+        };
+
+        let g_d = diversify_hash(diversifier)?;
+        if jubjub::AffinePoint::from(g_d * esk) != self.ephemeral_key {
+            return None;
+        }
+
+        let cmu = note_commitment(Diversifier(diversifier), TransmissionKey(pk_d), value, rcm)?;
+        if cmu != self.cmu {
+            return None;
+        }
+
+        Some(RecoveredNote {
+            lead_byte,
+            diversifier: Diversifier(diversifier),
+            transmission_key: TransmissionKey(pk_d),
+            value,
+            rseed,
+        })
+    }
+}
+
+/// Decrypts `ciphertext` using `key`, with AEAD_CHACHA20_POLY1305 and an
+/// all-zero nonce.
+///
+/// Each Sapling key is only used once, so the nonce is always zero.
+fn sym_decrypt(key: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(key));
+    cipher
+        .decrypt(GenericArray::from_slice(&[0u8; 12]), ciphertext)
+        .ok()
+}
+
+/// Converts a constant-time option into an `Option`.
+fn ct_option<T>(value: CtOption<T>) -> Option<T> {
+    if value.is_some().into() {
+        Some(value.unwrap())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        block::Block,
+        keys::sapling::{IncomingViewingKey, SpendingKey},
+        notes::sapling::{EncryptedCiphertext, OutCiphertext},
+        proofs::Groth16Proof,
+        serialization::ZcashDeserialize,
+    };
+
+    /// Encrypts `plaintext` using `key`, with AEAD_CHACHA20_POLY1305 and an
+    /// all-zero nonce.
+    fn sym_encrypt(key: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(key));
+        cipher
+            .encrypt(GenericArray::from_slice(&[0u8; 12]), plaintext)
+            .expect("encryption is infallible")
+    }
+
+    /// Returns an output that pays `value` to a test address, with the note
+    /// plaintext version `lead_byte`, encrypted for `ovk`.
+    fn output(lead_byte: u8, value: u64, ovk: &OutgoingViewingKey) -> Output {
+        // The default diversifier is always valid
+        let diversifier = Diversifier::from(SpendingKey::from([1; 32]));
+        let ivk = IncomingViewingKey::from([2; 32]);
+        let pk_d = TransmissionKey::from((ivk, diversifier)).0;
+        let g_d = diversify_hash(diversifier.0).expect("test diversifier is valid");
+
+        let rseed = [3; 32];
+        let (esk, rcm) = match lead_byte {
+            NOTE_PLAINTEXT_V2 => (
+                jubjub::Fr::from_bytes_wide(&prf_expand(rseed, &[5])),
+                jubjub::Fr::from_bytes_wide(&prf_expand(rseed, &[4])),
+            ),
+            _ => (
+                jubjub::Fr::from_bytes_wide(&[5; 64]),
+                jubjub::Fr::from_bytes(&rseed).unwrap(),
+            ),
+        };
+        let epk = jubjub::AffinePoint::from(g_d * esk);
+
+        let mut plaintext = vec![lead_byte];
+        plaintext.extend_from_slice(&diversifier.0);
+        plaintext.extend_from_slice(&value.to_le_bytes());
+        plaintext.extend_from_slice(&rseed);
+        plaintext.resize(NOTE_PLAINTEXT_LEN, 0);
+
+        let shared_secret = jubjub::AffinePoint::from(pk_d.mul_by_cofactor() * esk);
+        let k_enc = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"Zcash_SaplingKDF")
+            .to_state()
+            .update(&shared_secret.to_bytes())
+            .update(&epk.to_bytes())
+            .finalize();
+        let mut enc_ciphertext = [0; 580];
+        enc_ciphertext.copy_from_slice(&sym_encrypt(k_enc.as_bytes(), &plaintext));
+
+        let cv = [6; 32];
+        let cmu = note_commitment(diversifier, TransmissionKey(pk_d), value, rcm)
+            .expect("test diversifier is valid");
+        let ock = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"Zcash_Derive_ock")
+            .to_state()
+            .update(&ovk.0)
+            .update(&cv)
+            .update(&cmu)
+            .update(&epk.to_bytes())
+            .finalize();
+        let mut out_plaintext = pk_d.to_bytes().to_vec();
+        out_plaintext.extend_from_slice(&esk.to_bytes());
+        let mut out_ciphertext = [0; 80];
+        out_ciphertext.copy_from_slice(&sym_encrypt(ock.as_bytes(), &out_plaintext));
+
+        Output {
+            cv,
+            cmu,
+            ephemeral_key: epk,
+            enc_ciphertext: EncryptedCiphertext(enc_ciphertext),
+            out_ciphertext: OutCiphertext(out_ciphertext),
+            zkproof: Groth16Proof([0; 192]),
+        }
+    }
+
+    #[test]
+    fn recover_notes_with_ovk() {
+        zebra_test::init();
+
+        let ovk = OutgoingViewingKey([0; 32]);
+        for &lead_byte in &[NOTE_PLAINTEXT_V1, NOTE_PLAINTEXT_V2] {
+            let note = output(lead_byte, 1234, &ovk)
+                .recover_note(&ovk)
+                .expect("output is encrypted for ovk");
+            assert_eq!(note.lead_byte, lead_byte);
+            assert_eq!(note.value, 1234);
+            assert_eq!(
+                note.diversifier,
+                Diversifier::from(SpendingKey::from([1; 32]))
+            );
+        }
+    }
+
+    #[test]
+    fn recover_notes_rejects_bad_outputs() {
+        zebra_test::init();
+
+        let ovk = OutgoingViewingKey([0; 32]);
+
+        // Wrong outgoing viewing key
+        let bad_output = output(NOTE_PLAINTEXT_V2, 1234, &OutgoingViewingKey([8; 32]));
+        assert_eq!(bad_output.recover_note(&ovk), None);
+
+        // Unknown note plaintext version
+        let bad_output = output(0x03, 1234, &ovk);
+        assert_eq!(bad_output.recover_note(&ovk), None);
+
+        // Changing the ciphertext breaks the authentication tag
+        let mut bad_output = output(NOTE_PLAINTEXT_V1, 1234, &ovk);
+        bad_output.enc_ciphertext.0[0] ^= 1;
+        assert_eq!(bad_output.recover_note(&ovk), None);
+
+        // The note must match the note commitment. Both outputs have the same
+        // ephemeral key, so the other note plaintext decrypts.
+        let good_output = output(NOTE_PLAINTEXT_V2, 1234, &ovk);
+        let other_output = output(NOTE_PLAINTEXT_V2, 1235, &ovk);
+        let bad_output = Output {
+            enc_ciphertext: other_output.enc_ciphertext,
+            ..good_output
+        };
+        assert_eq!(bad_output.recover_note(&ovk), None);
+    }
+
+    #[test]
+    fn recover_real_coinbase_notes() {
+        zebra_test::init();
+
+        let ovk = OutgoingViewingKey([0; 32]);
+        for &(bytes, lead_byte, value) in &[
+            // Heartwood allows shielded coinbase outputs
+            (
+                &zebra_test::vectors::BLOCK_MAINNET_949496_BYTES[..],
+                NOTE_PLAINTEXT_V1,
+                500_018_759,
+            ),
+            // Canopy coinbase outputs use ZIP-212 note plaintexts
+            (
+                &zebra_test::vectors::BLOCK_TESTNET_1101629_BYTES[..],
+                NOTE_PLAINTEXT_V2,
+                500_000_000,
+            ),
+        ] {
+            let block = Block::zcash_deserialize(bytes).expect("test block is valid");
+            let outputs: Vec<_> = block.transactions[0].sapling_outputs().collect();
+            assert_eq!(outputs.len(), 1);
+
+            let note = outputs[0]
+                .recover_note(&ovk)
+                .expect("coinbase outputs are encrypted for the all-zero key");
+            assert_eq!(note.lead_byte, lead_byte);
+            assert_eq!(note.value, value);
+        }
+    }
+}
@@ -2,16 +2,15 @@
 #![allow(dead_code)]
 
 use serde::{Deserialize, Serialize};
-use std::{
-    fmt,
-    io::{self},
-};
+use std::fmt;
 
 #[cfg(test)]
 use proptest::{collection::vec, prelude::*};
 
 use crate::serde_helpers;
-use crate::serialization::{SerializationError, ZcashDeserialize, ZcashSerialize};
+use crate::serialization::{
+    TrustedPreallocate, ZcashDeserialize, ZcashSerialize, MAX_PROTOCOL_MESSAGE_LEN,
+};
 
 use super::{memo::Memo, *};
 
@@ -35,8 +34,37 @@ pub struct NotePlaintext {
     memo: Memo,
 }
 
+/// A Sprout note commitment, `cm`.
+///
+/// JoinSplit descriptions contain the commitments of their output notes,
+/// which are added to the Sprout note commitment tree.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ZcashSerialize, ZcashDeserialize,
+)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct NoteCommitment(pub [u8; 32]);
+
+impl From<[u8; 32]> for NoteCommitment {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<NoteCommitment> for [u8; 32] {
+    fn from(commitment: NoteCommitment) -> Self {
+        commitment.0
+    }
+}
+
+impl TrustedPreallocate for NoteCommitment {
+    fn max_allocation() -> u64 {
+        // Each commitment is 32 bytes
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / 32
+    }
+}
+
 /// A ciphertext component for encrypted output notes.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ZcashSerialize, ZcashDeserialize)]
 pub struct EncryptedCiphertext(#[serde(with = "serde_helpers::BigArray")] pub [u8; 601]);
 
 impl fmt::Debug for EncryptedCiphertext {
@@ -67,21 +95,6 @@ impl PartialEq for EncryptedCiphertext {
 
 impl Eq for EncryptedCiphertext {}
 
-impl ZcashSerialize for EncryptedCiphertext {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0[..])?;
-        Ok(())
-    }
-}
-
-impl ZcashDeserialize for EncryptedCiphertext {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let mut bytes = [0; 601];
-        reader.read_exact(&mut bytes[..])?;
-        Ok(Self(bytes))
-    }
-}
-
 #[cfg(test)]
 impl Arbitrary for EncryptedCiphertext {
     type Parameters = ();
@@ -0,0 +1,13 @@
+//! Chain parameters for each Zcash network.
+//!
+//! Consensus rule parameters live in `zebra_consensus::parameters`. This module
+//! contains the parameters that other Zebra crates need, without depending on
+//! `zebra-consensus`.
+
+pub mod branch_id;
+pub mod genesis;
+pub mod testnet;
+
+pub use branch_id::*;
+pub use genesis::*;
+pub use testnet::*;
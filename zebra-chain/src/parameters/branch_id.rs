@@ -0,0 +1,33 @@
+//! Consensus branch ids, which bind transactions to a network upgrade.
+
+use std::fmt;
+
+/// The Consensus Branch Id, used to bind transactions and blocks to a
+/// particular network upgrade.
+///
+/// Signature hashes commit to the branch id, so transactions that are signed
+/// for one network upgrade are invalid after the next upgrade.
+///
+/// The branch ids for each network upgrade are in
+/// `zebra_consensus::parameters`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ConsensusBranchId(u32);
+
+impl ConsensusBranchId {
+    /// Returns the consensus branch id with the raw value `id`.
+    pub const fn new(id: u32) -> ConsensusBranchId {
+        ConsensusBranchId(id)
+    }
+}
+
+impl From<ConsensusBranchId> for u32 {
+    fn from(branch_id: ConsensusBranchId) -> Self {
+        branch_id.0
+    }
+}
+
+impl fmt::Display for ConsensusBranchId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
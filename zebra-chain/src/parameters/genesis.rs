@@ -0,0 +1,118 @@
+//! Genesis blocks and genesis consensus parameters for each Zcash network.
+
+use std::sync::Arc;
+
+use hex::FromHex;
+use lazy_static::lazy_static;
+
+use super::testnet_parameters;
+use crate::{
+    block::{Block, BlockHeaderHash},
+    serialization::ZcashDeserialize,
+    Network::{self, *},
+};
+
+/// The hex-encoded Mainnet genesis block.
+///
+/// zcash-cli getblock 0 0
+const MAINNET_GENESIS_HEX: &str = include_str!("genesis/main-genesis.txt");
+
+/// The hex-encoded Testnet genesis block.
+///
+/// zcash-cli -testnet getblock 0 0
+const TESTNET_GENESIS_HEX: &str = include_str!("genesis/test-genesis.txt");
+
+lazy_static! {
+    /// The Mainnet genesis block.
+    static ref MAINNET_GENESIS: Arc<Block> = parse_genesis(MAINNET_GENESIS_HEX);
+
+    /// The Testnet genesis block.
+    static ref TESTNET_GENESIS: Arc<Block> = parse_genesis(TESTNET_GENESIS_HEX);
+}
+
+/// Parses a hard-coded hex-encoded genesis block.
+fn parse_genesis(genesis_hex: &str) -> Arc<Block> {
+    let bytes =
+        <Vec<u8>>::from_hex(genesis_hex.trim()).expect("hard-coded genesis block is valid hex");
+    Arc::<Block>::zcash_deserialize(&bytes[..]).expect("hard-coded genesis block deserializes")
+}
+
+/// The previous block hash for the genesis block.
+///
+/// All known networks use the Bitcoin `null` value for the parent of the
+/// genesis block. (In Bitcoin, `null` is `[0; 32]`.)
+pub const GENESIS_PREVIOUS_BLOCK_HASH: BlockHeaderHash = BlockHeaderHash([0; 32]);
+
+/// Returns the hash for the genesis block in `network`.
+///
+/// Uses the configured Testnet genesis hash, if there is one.
+pub fn genesis_hash(network: Network) -> BlockHeaderHash {
+    if let (Testnet, Some(hash)) = (
+        network,
+        testnet_parameters().and_then(|parameters| parameters.genesis_hash),
+    ) {
+        return hash;
+    }
+
+    match network {
+        // zcash-cli getblockhash 0
+        Mainnet => "00040fe8ec8471911baa1db1266ea15dd06b4a8a5c453883c000b031973dce08",
+        // zcash-cli -testnet getblockhash 0
+        Testnet => "05a60a92d99d85997cce3b87616c089f6124d7342af37106edc76126334a2c38",
+    }
+    .parse()
+    .expect("hard-coded hash parses")
+}
+
+/// Returns the embedded genesis block for `network`, if there is one.
+///
+/// Genesis blocks are hard-coded in zcashd, and they are not verified like
+/// other blocks. So Zebra commits the embedded genesis block to an empty
+/// state, rather than downloading it from peers.
+///
+/// Configured test networks with a different genesis hash don't have an
+/// embedded genesis block, so this function returns `None` for them.
+/// Callers should download it instead.
+pub fn genesis_block(network: Network) -> Option<Arc<Block>> {
+    let genesis = match network {
+        Mainnet => MAINNET_GENESIS.clone(),
+        Testnet => TESTNET_GENESIS.clone(),
+    };
+
+    if genesis.hash() == genesis_hash(network) {
+        Some(genesis)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genesis_block_matches_hash() {
+        for network in &[Mainnet, Testnet] {
+            let block = genesis_block(*network).expect("default networks have a genesis block");
+            assert_eq!(block.hash(), genesis_hash(*network));
+            assert_eq!(
+                block.header.previous_block_hash,
+                GENESIS_PREVIOUS_BLOCK_HASH
+            );
+            assert_eq!(block.coinbase_height(), Some(crate::types::BlockHeight(0)));
+        }
+    }
+
+    #[test]
+    fn mainnet_genesis_block_matches_test_vector() {
+        assert_eq!(
+            genesis_block(Mainnet),
+            Some(
+                Arc::<Block>::zcash_deserialize(
+                    &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..]
+                )
+                .unwrap()
+            )
+        );
+    }
+}
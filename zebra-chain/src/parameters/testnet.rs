@@ -0,0 +1,234 @@
+//! Parameters for configured test networks.
+//!
+//! Operators can run private test networks, or rehearse network upgrades, by
+//! overriding the Testnet magic, genesis hash, and activation heights.
+//!
+//! `Network` is a `Copy` type which is used throughout Zebra, so the
+//! configured parameters are process-wide. They must be set once, at startup,
+//! before any other Zebra code uses `Network::Testnet`.
+
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+use crate::{block::BlockHeaderHash, types::BlockHeight};
+
+/// The first Testnet height where blocks can use the minimum difficulty.
+///
+/// This matches `nPowAllowMinDifficultyBlocksAfterHeight` in zcashd, which
+/// allows minimum difficulty blocks after height 299187.
+pub const TESTNET_MINIMUM_DIFFICULTY_START_HEIGHT: BlockHeight = BlockHeight(299_188);
+
+lazy_static! {
+    /// The configured Testnet parameters, if any.
+    static ref TESTNET_PARAMETERS: RwLock<Option<Arc<TestnetParameters>>> = RwLock::new(None);
+}
+
+/// Parameters that override the default Testnet parameters.
+///
+/// Unset parameters use the default Testnet values.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct TestnetParameters {
+    /// The network magic, as 4 hex-encoded bytes.
+    #[serde(with = "hex_magic")]
+    pub magic: Option<[u8; 4]>,
+
+    /// The hash of the genesis block, as big-endian hex.
+    #[serde(with = "hex_hash")]
+    pub genesis_hash: Option<BlockHeaderHash>,
+
+    /// The network upgrade activation heights.
+    ///
+    /// If set, these heights replace all the default Testnet activation
+    /// heights.
+    pub activation_heights: Option<ActivationHeights>,
+}
+
+/// Network upgrade activation heights for a configured test network.
+///
+/// Unset upgrades are not activated. If an upgrade is set, all the earlier
+/// upgrades must also be set, and activate at lower heights.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ActivationHeights {
+    /// The activation height of Overwinter.
+    pub overwinter: Option<BlockHeight>,
+    /// The activation height of Sapling.
+    pub sapling: Option<BlockHeight>,
+    /// The activation height of Blossom.
+    pub blossom: Option<BlockHeight>,
+    /// The activation height of Heartwood.
+    pub heartwood: Option<BlockHeight>,
+    /// The activation height of Canopy.
+    pub canopy: Option<BlockHeight>,
+    /// The activation height of NU5.
+    pub nu5: Option<BlockHeight>,
+    /// The activation height of NU6.
+    pub nu6: Option<BlockHeight>,
+}
+
+impl ActivationHeights {
+    /// Returns the activation heights in upgrade order, starting with
+    /// Overwinter.
+    pub fn in_order(&self) -> [Option<BlockHeight>; 7] {
+        [
+            self.overwinter,
+            self.sapling,
+            self.blossom,
+            self.heartwood,
+            self.canopy,
+            self.nu5,
+            self.nu6,
+        ]
+    }
+
+    /// Check that each set height is greater than the previous height, and
+    /// that there are no unset gaps.
+    fn check(&self) -> Result<(), &'static str> {
+        let mut previous = BlockHeight(0);
+        let mut unset = false;
+
+        for height in self.in_order().iter() {
+            match height {
+                Some(_) if unset => {
+                    return Err("activation heights must be set for all earlier upgrades")
+                }
+                Some(height) if *height <= previous => {
+                    return Err("activation heights must be greater than earlier heights")
+                }
+                Some(height) => previous = *height,
+                None => unset = true,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An error setting the configured Testnet parameters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TestnetParametersError(&'static str);
+
+impl fmt::Display for TestnetParametersError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for TestnetParametersError {}
+
+/// Set the process-wide Testnet parameters to `parameters`.
+///
+/// Returns an error if the parameters are invalid, or if different parameters
+/// have already been set.
+pub fn set_testnet_parameters(parameters: TestnetParameters) -> Result<(), TestnetParametersError> {
+    if let Some(heights) = &parameters.activation_heights {
+        heights.check().map_err(TestnetParametersError)?;
+    }
+
+    let mut current = TESTNET_PARAMETERS
+        .write()
+        .expect("lock should be unpoisoned");
+    match current.as_deref() {
+        Some(existing) if existing != &parameters => Err(TestnetParametersError(
+            "testnet parameters have already been set",
+        )),
+        _ => {
+            *current = Some(Arc::new(parameters));
+            Ok(())
+        }
+    }
+}
+
+/// Returns the configured Testnet parameters, if they have been set.
+pub fn testnet_parameters() -> Option<Arc<TestnetParameters>> {
+    TESTNET_PARAMETERS
+        .read()
+        .expect("lock should be unpoisoned")
+        .clone()
+}
+
+/// Serialize an optional magic as hex.
+mod hex_magic {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(magic: &Option<[u8; 4]>, s: S) -> Result<S::Ok, S::Error> {
+        match magic {
+            Some(magic) => s.serialize_some(&hex::encode(magic)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<[u8; 4]>, D::Error> {
+        let mut magic = [0; 4];
+        match Option::<String>::deserialize(d)? {
+            Some(s) => hex::decode_to_slice(s, &mut magic)
+                .map(|()| Some(magic))
+                .map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Serialize an optional block hash as big-endian hex.
+mod hex_hash {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        hash: &Option<BlockHeaderHash>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        match hash {
+            Some(hash) => s.serialize_some(&hash.to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<BlockHeaderHash>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|s| s.parse().map_err(de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activation_heights_order() {
+        let heights = ActivationHeights {
+            overwinter: Some(BlockHeight(1)),
+            sapling: Some(BlockHeight(2)),
+            ..ActivationHeights::default()
+        };
+        assert_eq!(heights.check(), Ok(()));
+
+        let heights = ActivationHeights {
+            overwinter: Some(BlockHeight(2)),
+            sapling: Some(BlockHeight(2)),
+            ..ActivationHeights::default()
+        };
+        assert!(heights.check().is_err());
+
+        let heights = ActivationHeights {
+            sapling: Some(BlockHeight(2)),
+            ..ActivationHeights::default()
+        };
+        assert!(heights.check().is_err());
+
+        // Genesis always uses the pre-Overwinter rules
+        let heights = ActivationHeights {
+            overwinter: Some(BlockHeight(0)),
+            ..ActivationHeights::default()
+        };
+        assert!(heights.check().is_err());
+    }
+}
@@ -1,12 +1,12 @@
-use std::{fmt, io};
+use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
 use crate::serde_helpers;
-use crate::serialization::{SerializationError, ZcashDeserialize, ZcashSerialize};
+use crate::serialization::{ZcashDeserialize, ZcashSerialize};
 
 /// An encoding of a BCTV14 proof, as used in Zcash.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ZcashSerialize, ZcashDeserialize)]
 pub struct Bctv14Proof(#[serde(with = "serde_helpers::BigArray")] pub [u8; 296]);
 
 impl fmt::Debug for Bctv14Proof {
@@ -37,21 +37,6 @@ impl PartialEq for Bctv14Proof {
 
 impl Eq for Bctv14Proof {}
 
-impl ZcashSerialize for Bctv14Proof {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0[..])?;
-        Ok(())
-    }
-}
-
-impl ZcashDeserialize for Bctv14Proof {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let mut bytes = [0; 296];
-        reader.read_exact(&mut bytes[..])?;
-        Ok(Self(bytes))
-    }
-}
-
 #[cfg(test)]
 use proptest::{arbitrary::Arbitrary, collection::vec, prelude::*};
 
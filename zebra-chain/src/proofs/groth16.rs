@@ -1,11 +1,11 @@
 use serde::{Deserialize, Serialize};
-use std::{fmt, io};
+use std::fmt;
 
 use crate::serde_helpers;
-use crate::serialization::{SerializationError, ZcashDeserialize, ZcashSerialize};
+use crate::serialization::{ZcashDeserialize, ZcashSerialize};
 
 /// An encoding of a Groth16 proof, as used in Zcash.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ZcashSerialize, ZcashDeserialize)]
 pub struct Groth16Proof(#[serde(with = "serde_helpers::BigArray")] pub [u8; 192]);
 
 impl fmt::Debug for Groth16Proof {
@@ -36,21 +36,6 @@ impl PartialEq for Groth16Proof {
 
 impl Eq for Groth16Proof {}
 
-impl ZcashSerialize for Groth16Proof {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0[..])?;
-        Ok(())
-    }
-}
-
-impl ZcashDeserialize for Groth16Proof {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let mut bytes = [0; 192];
-        reader.read_exact(&mut bytes[..])?;
-        Ok(Self(bytes))
-    }
-}
-
 #[cfg(test)]
 use proptest::{arbitrary::Arbitrary, collection::vec, prelude::*};
 
@@ -4,7 +4,18 @@
 //! analogs of the Serde `Serialize` and `Deserialize` traits but intended for
 //! consensus-critical Zcash serialization formats, and `WriteZcashExt` and
 //! `ReadZcashExt`, extension traits for `io::Read` and `io::Write` with utility functions
-//! for reading and writing data (e.g., the Bitcoin variable-integer format).
+//! for reading and writing data (e.g., IP addresses and strings).
+//!
+//! Lengths and counts use the `CompactSize` type, which only accepts
+//! canonical encodings.
+//!
+//! Structs that are a sequence of serializable fields can use
+//! `#[derive(ZcashSerialize, ZcashDeserialize)]`, see `zebra_derive` for
+//! details. Length-prefixed lists can only be deserialized for types that implement
+//! `TrustedPreallocate`, which bounds the number of items in a list.
+
+mod at_least_one;
+mod compact_size;
 
 use std::io;
 use std::net::{IpAddr, SocketAddr};
@@ -12,6 +23,10 @@ use std::net::{IpAddr, SocketAddr};
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
 use thiserror::Error;
 
+pub use at_least_one::AtLeastOne;
+pub use compact_size::{CompactSize, MAX_COMPACT_SIZE};
+pub use zebra_derive::{ZcashDeserialize, ZcashSerialize};
+
 /// A serialization error.
 // XXX refine error types -- better to use boxed errors?
 #[derive(Error, Debug)]
@@ -32,6 +47,12 @@ pub enum SerializationError {
     },
 }
 
+/// The maximum size of a Zcash protocol message body, in bytes.
+///
+/// Blocks, transactions, and network messages are all smaller than this
+/// limit, so it also bounds the number of items in any deserialized list.
+pub const MAX_PROTOCOL_MESSAGE_LEN: usize = 2 * 1024 * 1024;
+
 /// Consensus-critical serialization for Zcash.
 ///
 /// This trait provides a generic serialization for consensus-critical
@@ -76,7 +97,7 @@ pub trait ZcashDeserialize: Sized {
 
 impl<T: ZcashSerialize> ZcashSerialize for Vec<T> {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_compactsize(self.len() as u64)?;
+        CompactSize::from_len(self.len())?.zcash_serialize(&mut writer)?;
         for x in self {
             x.zcash_serialize(&mut writer)?;
         }
@@ -84,15 +105,62 @@ impl<T: ZcashSerialize> ZcashSerialize for Vec<T> {
     }
 }
 
-impl<T: ZcashDeserialize> ZcashDeserialize for Vec<T> {
+/// Implements `ZcashSerialize` and `ZcashDeserialize` for integers, which
+/// are little-endian in Zcash.
+macro_rules! impl_integer_serialization {
+    ($($int:ty => $write:ident, $read:ident;)*) => {$(
+        impl ZcashSerialize for $int {
+            fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
+                writer.$write::<LittleEndian>(*self)
+            }
+        }
+
+        impl ZcashDeserialize for $int {
+            fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
+                Ok(reader.$read::<LittleEndian>()?)
+            }
+        }
+    )*};
+}
+
+impl_integer_serialization! {
+    u16 => write_u16, read_u16;
+    u32 => write_u32, read_u32;
+    u64 => write_u64, read_u64;
+    i32 => write_i32, read_i32;
+    i64 => write_i64, read_i64;
+}
+
+/// A type with a maximum number of items in a deserialized list.
+///
+/// List lengths are read from untrusted data, so a list with more than
+/// `max_allocation` items is rejected before any items are read. Lengths
+/// within the limit are safe to preallocate.
+///
+/// The limit is usually `MAX_PROTOCOL_MESSAGE_LEN` divided by the minimum
+/// serialized size of the type, because a longer list can't fit in a valid
+/// message.
+pub trait TrustedPreallocate {
+    /// Returns the maximum number of items of this type in a list.
+    fn max_allocation() -> u64;
+}
+
+/// Returns an error if `len` is more than the maximum list length for `T`.
+pub(crate) fn check_allocation<T: TrustedPreallocate>(len: u64) -> Result<(), SerializationError> {
+    if len > T::max_allocation() {
+        Err(SerializationError::Parse(
+            "list length is larger than the maximum for its item type",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+impl<T: ZcashDeserialize + TrustedPreallocate> ZcashDeserialize for Vec<T> {
     fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let len = reader.read_compactsize()?;
-        // We're given len, so we could preallocate. But blindly preallocating
-        // without a size bound can allow DOS attacks, and there's no way to
-        // pass a size bound in a ZcashDeserialize impl, so instead we allocate
-        // as we read from the reader. (The maximum block and transaction sizes
-        // limit the eventual size of these allocations.)
-        let mut vec = Vec::new();
+        let len = CompactSize::zcash_deserialize(&mut reader)?.into();
+        check_allocation::<T>(len)?;
+        let mut vec = Vec::with_capacity(len as usize);
         for _ in 0..len {
             vec.push(T::zcash_deserialize(&mut reader)?);
         }
@@ -104,52 +172,6 @@ impl<T: ZcashDeserialize> ZcashDeserialize for Vec<T> {
 ///
 /// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 pub trait WriteZcashExt: io::Write {
-    /// Writes a `u64` using the Bitcoin `CompactSize` encoding.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use zebra_chain::serialization::WriteZcashExt;
-    ///
-    /// let mut buf = Vec::new();
-    /// buf.write_compactsize(0x12).unwrap();
-    /// assert_eq!(buf, b"\x12");
-    ///
-    /// let mut buf = Vec::new();
-    /// buf.write_compactsize(0xfd).unwrap();
-    /// assert_eq!(buf, b"\xfd\xfd\x00");
-    ///
-    /// let mut buf = Vec::new();
-    /// buf.write_compactsize(0xaafd).unwrap();
-    /// assert_eq!(buf, b"\xfd\xfd\xaa");
-    ///
-    /// let mut buf = Vec::new();
-    /// buf.write_compactsize(0xbbaafd).unwrap();
-    /// assert_eq!(buf, b"\xfe\xfd\xaa\xbb\x00");
-    ///
-    /// let mut buf = Vec::new();
-    /// buf.write_compactsize(0x22ccbbaafd).unwrap();
-    /// assert_eq!(buf, b"\xff\xfd\xaa\xbb\xcc\x22\x00\x00\x00");
-    /// ```
-    #[inline]
-    fn write_compactsize(&mut self, n: u64) -> io::Result<()> {
-        match n {
-            0x0000_0000..=0x0000_00fc => self.write_u8(n as u8),
-            0x0000_00fd..=0x0000_ffff => {
-                self.write_u8(0xfd)?;
-                self.write_u16::<LittleEndian>(n as u16)
-            }
-            0x0001_0000..=0xffff_ffff => {
-                self.write_u8(0xfe)?;
-                self.write_u32::<LittleEndian>(n as u32)
-            }
-            _ => {
-                self.write_u8(0xff)?;
-                self.write_u64::<LittleEndian>(n)
-            }
-        }
-    }
-
     /// Write an `IpAddr` in Bitcoin format.
     #[inline]
     fn write_ip_addr(&mut self, addr: IpAddr) -> io::Result<()> {
@@ -168,11 +190,17 @@ pub trait WriteZcashExt: io::Write {
         self.write_u16::<BigEndian>(addr.port())
     }
 
+    /// Write a byte vector with a `CompactSize` length.
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        CompactSize::from_len(bytes.len())?.zcash_serialize(&mut *self)?;
+        self.write_all(bytes)
+    }
+
     /// Write a string in Bitcoin format.
     #[inline]
     fn write_string(&mut self, string: &str) -> io::Result<()> {
-        self.write_compactsize(string.len() as u64)?;
-        self.write_all(string.as_bytes())
+        self.write_bytes(string.as_bytes())
     }
 }
 
@@ -183,61 +211,6 @@ impl<W: io::Write + ?Sized> WriteZcashExt for W {}
 ///
 /// [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
 pub trait ReadZcashExt: io::Read {
-    /// Reads a `u64` using the Bitcoin `CompactSize` encoding.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use zebra_chain::serialization::ReadZcashExt;
-    ///
-    /// use std::io::Cursor;
-    /// assert_eq!(
-    ///     0x12,
-    ///     Cursor::new(b"\x12")
-    ///         .read_compactsize().unwrap()
-    /// );
-    /// assert_eq!(
-    ///     0xfd,
-    ///     Cursor::new(b"\xfd\xfd\x00")
-    ///         .read_compactsize().unwrap()
-    /// );
-    /// assert_eq!(
-    ///     0xaafd,
-    ///     Cursor::new(b"\xfd\xfd\xaa")
-    ///         .read_compactsize().unwrap()
-    /// );
-    /// assert_eq!(
-    ///     0xbbaafd,
-    ///     Cursor::new(b"\xfe\xfd\xaa\xbb\x00")
-    ///         .read_compactsize().unwrap()
-    /// );
-    /// assert_eq!(
-    ///     0x22ccbbaafd,
-    ///     Cursor::new(b"\xff\xfd\xaa\xbb\xcc\x22\x00\x00\x00")
-    ///         .read_compactsize().unwrap()
-    /// );
-    /// ```
-    #[inline]
-    fn read_compactsize(&mut self) -> Result<u64, SerializationError> {
-        use SerializationError::Parse;
-        let flag_byte = self.read_u8()?;
-        match flag_byte {
-            n @ 0x00..=0xfc => Ok(n as u64),
-            0xfd => match self.read_u16::<LittleEndian>()? {
-                n @ 0x0000_00fd..=0x0000_ffff => Ok(n as u64),
-                _ => Err(Parse("non-canonical compactsize")),
-            },
-            0xfe => match self.read_u32::<LittleEndian>()? {
-                n @ 0x0001_0000..=0xffff_ffff => Ok(n as u64),
-                _ => Err(Parse("non-canonical compactsize")),
-            },
-            0xff => match self.read_u64::<LittleEndian>()? {
-                n @ 0x1_0000_0000..=0xffff_ffff_ffff_ffff => Ok(n),
-                _ => Err(Parse("non-canonical compactsize")),
-            },
-        }
-    }
-
     /// Read an IP address in Bitcoin format.
     #[inline]
     fn read_ip_addr(&mut self) -> io::Result<IpAddr> {
@@ -261,12 +234,32 @@ pub trait ReadZcashExt: io::Read {
         Ok(SocketAddr::new(ip_addr, port))
     }
 
+    /// Read a byte vector with a `CompactSize` length.
+    ///
+    /// Byte vectors longer than `MAX_PROTOCOL_MESSAGE_LEN` are rejected.
+    #[inline]
+    fn read_bytes(&mut self) -> Result<Vec<u8>, SerializationError> {
+        let len: u64 = CompactSize::zcash_deserialize(&mut *self)?.into();
+        if len > MAX_PROTOCOL_MESSAGE_LEN as u64 {
+            return Err(SerializationError::Parse(
+                "byte vector length is larger than the maximum message size",
+            ));
+        }
+        // Only allocate the bytes that are actually read
+        let mut buf = Vec::new();
+        io::Read::read_to_end(&mut io::Read::take(&mut *self, len), &mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(buf)
+    }
+
     /// Read a Bitcoin-encoded UTF-8 string.
+    ///
+    /// Strings longer than `MAX_PROTOCOL_MESSAGE_LEN` are rejected.
     #[inline]
     fn read_string(&mut self) -> Result<String, SerializationError> {
-        let len = self.read_compactsize()?;
-        let mut buf = vec![0; len as usize];
-        self.read_exact(&mut buf)?;
+        let buf = self.read_bytes()?;
         String::from_utf8(buf).map_err(|_| SerializationError::Parse("invalid utf-8"))
     }
 
@@ -324,40 +317,107 @@ impl<R: io::Read> ZcashDeserializeInto for R {
 }
 
 #[cfg(test)]
-#[allow(clippy::unnecessary_operation)]
 mod tests {
     use super::*;
-    use proptest::prelude::*;
 
-    use std::io::Cursor;
+    use std::{convert::TryFrom, io::Cursor};
 
-    proptest! {
-        // The tests below are cheap so we can run them a lot.
-        #![proptest_config(ProptestConfig::with_cases(100_000))]
+    /// A test type with a small list limit.
+    #[derive(Debug, PartialEq)]
+    struct Byte(u8);
 
-        #[test]
-        fn compactsize_write_then_read_round_trip(s in 0u64..0x2_0000u64) {
-            // Maximum encoding size of a compactsize is 9 bytes.
-            let mut buf = [0u8; 8+1];
-            Cursor::new(&mut buf[..]).write_compactsize(s).unwrap();
-            let expect_s = Cursor::new(&buf[..]).read_compactsize().unwrap();
-            prop_assert_eq!(s, expect_s);
+    impl ZcashDeserialize for Byte {
+        fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
+            Ok(Byte(reader.read_u8()?))
         }
+    }
 
-        #[test]
-        fn compactsize_read_then_write_round_trip(bytes in prop::array::uniform9(0u8..)) {
-            // Only do the test if the bytes were valid.
-            if let Ok(s) = Cursor::new(&bytes[..]).read_compactsize() {
-                // The compactsize encoding is variable-length, so we may not even
-                // read all of the input bytes, and therefore we can't expect that
-                // the encoding will reproduce bytes that were never read. Instead,
-                // copy the input bytes, and overwrite them with the encoding of s,
-                // so that if the encoding is different, we'll catch it on the part
-                // that's written.
-                let mut expect_bytes = bytes;
-                Cursor::new(&mut expect_bytes[..]).write_compactsize(s).unwrap();
-                prop_assert_eq!(bytes, expect_bytes);
-            }
+    impl TrustedPreallocate for Byte {
+        fn max_allocation() -> u64 {
+            3
         }
     }
+
+    #[test]
+    fn oversized_list_lengths_are_rejected() {
+        zebra_test::init();
+
+        let list = Vec::<Byte>::zcash_deserialize(&[3, 1, 2, 3][..]).expect("list is in bounds");
+        assert_eq!(list, vec![Byte(1), Byte(2), Byte(3)]);
+
+        let result = Vec::<Byte>::zcash_deserialize(&[4, 1, 2, 3, 4][..]);
+        assert!(matches!(result, Err(SerializationError::Parse(_))));
+
+        // A huge length is rejected without reading any items
+        let bytes = CompactSize::try_from(MAX_COMPACT_SIZE)
+            .unwrap()
+            .zcash_serialize_to_vec()
+            .unwrap();
+        let result = Vec::<Byte>::zcash_deserialize(&bytes[..]);
+        assert!(matches!(result, Err(SerializationError::Parse(_))));
+    }
+
+    #[test]
+    fn oversized_string_lengths_are_rejected() {
+        zebra_test::init();
+
+        let bytes = CompactSize::try_from(MAX_COMPACT_SIZE)
+            .unwrap()
+            .zcash_serialize_to_vec()
+            .unwrap();
+        let result = Cursor::new(&bytes[..]).read_string();
+        assert!(matches!(result, Err(SerializationError::Parse(_))));
+
+        // A length within the limit fails when the data runs out
+        let mut bytes = CompactSize::from_len(MAX_PROTOCOL_MESSAGE_LEN)
+            .unwrap()
+            .zcash_serialize_to_vec()
+            .unwrap();
+        bytes.extend_from_slice(b"short");
+        let result = Cursor::new(&bytes[..]).read_string();
+        assert!(matches!(result, Err(SerializationError::Io(_))));
+    }
+
+    /// A test type that uses each derived field encoding.
+    #[derive(Debug, PartialEq, ZcashSerialize, ZcashDeserialize)]
+    struct Derived {
+        hash: [u8; 4],
+        index: u32,
+        #[zcash(bytes)]
+        script: Vec<u8>,
+        #[zcash(optional)]
+        size: Option<CompactSize>,
+    }
+
+    #[test]
+    fn derived_impls_round_trip() {
+        zebra_test::init();
+
+        let derived = Derived {
+            hash: [1, 2, 3, 4],
+            index: 5,
+            script: vec![6, 7],
+            size: Some(CompactSize::from_len(8).unwrap()),
+        };
+        let bytes = derived.zcash_serialize_to_vec().unwrap();
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 0, 0, 0, 2, 6, 7, 1, 8]);
+        assert_eq!(Derived::zcash_deserialize(&bytes[..]).unwrap(), derived);
+
+        let derived = Derived {
+            size: None,
+            ..derived
+        };
+        let bytes = derived.zcash_serialize_to_vec().unwrap();
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 0, 0, 0, 2, 6, 7, 0]);
+        assert_eq!(Derived::zcash_deserialize(&bytes[..]).unwrap(), derived);
+    }
+
+    #[test]
+    fn derived_optional_fields_reject_invalid_flags() {
+        zebra_test::init();
+
+        let bytes = [1, 2, 3, 4, 5, 0, 0, 0, 2, 6, 7, 2, 8];
+        let result = Derived::zcash_deserialize(&bytes[..]);
+        assert!(matches!(result, Err(SerializationError::Parse(_))));
+    }
 }
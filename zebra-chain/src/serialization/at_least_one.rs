@@ -0,0 +1,201 @@
+//! A vector type that must contain at least one element.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io,
+    ops::Deref,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    check_allocation, CompactSize, SerializationError, TrustedPreallocate, ZcashDeserialize,
+    ZcashSerialize,
+};
+
+/// A vector that must contain at least one element.
+///
+/// The Zcash specification forbids some empty lists, like the JoinSplits in
+/// [`JoinSplitData`](crate::transaction::JoinSplitData). Using this type for
+/// those lists makes it impossible to construct an empty list, and
+/// deserialization fails if the list is empty.
+///
+/// `AtLeastOne` dereferences to a slice, so it can be used like a `Vec` that
+/// can only be appended to. Its consensus-critical serialization is the same as
+/// `Vec<T>`: a `CompactSize` length, followed by the elements.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "Vec<T>", into = "Vec<T>")]
+#[serde(bound(
+    serialize = "T: Clone + Serialize",
+    deserialize = "T: Deserialize<'de>"
+))]
+pub struct AtLeastOne<T> {
+    /// The inner vector, which is never empty.
+    inner: Vec<T>,
+}
+
+impl<T> AtLeastOne<T> {
+    /// Returns a list containing only `first`.
+    pub fn from_one(first: T) -> Self {
+        AtLeastOne { inner: vec![first] }
+    }
+
+    /// Returns the first element.
+    ///
+    /// Unlike `slice::first`, this method can't fail.
+    pub fn first(&self) -> &T {
+        &self.inner[0]
+    }
+
+    /// Returns the elements after the first one.
+    pub fn rest(&self) -> &[T] {
+        &self.inner[1..]
+    }
+
+    /// Appends `element` to the end of the list.
+    pub fn push(&mut self, element: T) {
+        self.inner.push(element);
+    }
+
+    /// Returns the elements as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        self.inner.as_slice()
+    }
+
+    /// Returns the inner vector, which has at least one element.
+    pub fn into_vec(self) -> Vec<T> {
+        self.inner
+    }
+}
+
+impl<T> TryFrom<Vec<T>> for AtLeastOne<T> {
+    type Error = SerializationError;
+
+    fn try_from(vec: Vec<T>) -> Result<Self, Self::Error> {
+        if vec.is_empty() {
+            Err(SerializationError::Parse("expected at least one element"))
+        } else {
+            Ok(AtLeastOne { inner: vec })
+        }
+    }
+}
+
+impl<T> From<AtLeastOne<T>> for Vec<T> {
+    fn from(list: AtLeastOne<T>) -> Self {
+        list.inner
+    }
+}
+
+impl<T> Deref for AtLeastOne<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.inner.as_slice()
+    }
+}
+
+impl<T> IntoIterator for AtLeastOne<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AtLeastOne<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.iter()
+    }
+}
+
+impl<T: ZcashSerialize> ZcashSerialize for AtLeastOne<T> {
+    fn zcash_serialize<W: io::Write>(&self, writer: W) -> Result<(), io::Error> {
+        self.inner.zcash_serialize(writer)
+    }
+}
+
+impl<T: ZcashDeserialize + TrustedPreallocate> ZcashDeserialize for AtLeastOne<T> {
+    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let len = CompactSize::zcash_deserialize(&mut reader)?.into();
+        if len == 0 {
+            return Err(SerializationError::Parse("expected at least one element"));
+        }
+        AtLeastOne::read_elements(reader, len)
+    }
+}
+
+impl<T: ZcashDeserialize + TrustedPreallocate> AtLeastOne<T> {
+    /// Reads `len` elements from `reader`, after their length has already
+    /// been read.
+    ///
+    /// This is useful for lists where a zero length means that a containing
+    /// structure is absent, like the JoinSplits in a transaction.
+    pub(crate) fn read_elements<R: io::Read>(
+        mut reader: R,
+        len: u64,
+    ) -> Result<Self, SerializationError> {
+        check_allocation::<T>(len)?;
+        let mut vec = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            vec.push(T::zcash_deserialize(&mut reader)?);
+        }
+        vec.try_into()
+    }
+}
+
+#[cfg(test)]
+use proptest::{arbitrary::Arbitrary, collection::vec, prelude::*};
+
+#[cfg(test)]
+impl<T: Arbitrary + std::fmt::Debug + 'static> Arbitrary for AtLeastOne<T> {
+    type Parameters = ();
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        vec(any::<T>(), 1..10)
+            .prop_map(|vec| vec.try_into().expect("vec is not empty"))
+            .boxed()
+    }
+
+    type Strategy = BoxedStrategy<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::notes::sprout::NoteCommitment;
+
+    #[test]
+    fn empty_lists_are_rejected() {
+        zebra_test::init();
+
+        assert!(AtLeastOne::<NoteCommitment>::try_from(Vec::new()).is_err());
+
+        let empty = Vec::<NoteCommitment>::new()
+            .zcash_serialize_to_vec()
+            .unwrap();
+        assert!(AtLeastOne::<NoteCommitment>::zcash_deserialize(Cursor::new(&empty)).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(list in any::<AtLeastOne<NoteCommitment>>()) {
+            let bytes = list.zcash_serialize_to_vec().expect("vec serialization is infallible");
+            let vec_bytes = list.clone().into_vec().zcash_serialize_to_vec().expect("vec serialization is infallible");
+            prop_assert_eq![&bytes, &vec_bytes];
+
+            let other = AtLeastOne::<NoteCommitment>::zcash_deserialize(Cursor::new(&bytes)).expect("list is not empty");
+            prop_assert_eq![&list, &other];
+
+            prop_assert_eq![list.first(), &list[0]];
+            prop_assert_eq![list.rest().len() + 1, list.len()];
+            prop_assert_eq![list.iter().count(), list.len()];
+        }
+    }
+}
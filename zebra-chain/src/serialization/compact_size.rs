@@ -0,0 +1,237 @@
+//! The Bitcoin `CompactSize` encoding for lengths and counts.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use super::{SerializationError, ZcashDeserialize, ZcashSerialize};
+
+/// The maximum value of a [`CompactSize`].
+///
+/// Matches `MAX_SIZE` in `zcashd`, which rejects larger sizes when it reads
+/// a `CompactSize`.
+pub const MAX_COMPACT_SIZE: u64 = 0x0200_0000;
+
+/// A length or count in the Bitcoin `CompactSize` encoding.
+///
+/// Values below `0xfd` are encoded as a single byte. Larger values are
+/// encoded as a marker byte, followed by a little-endian `u16`, `u32`, or
+/// `u64`.
+///
+/// Deserialization rejects non-canonical encodings, which use a longer
+/// encoding than necessary, and values above [`MAX_COMPACT_SIZE`]. `zcashd`
+/// rejects both, so accepting them would split consensus. Values above the
+/// maximum can't be constructed, so each `CompactSize` has exactly one
+/// encoding.
+///
+/// # Examples
+///
+/// ```rust
+/// use zebra_chain::serialization::{CompactSize, ZcashDeserialize, ZcashSerialize};
+///
+/// let size = CompactSize::from_len(0xaafd).unwrap();
+/// assert_eq!(size.zcash_serialize_to_vec().unwrap(), b"\xfd\xfd\xaa");
+///
+/// let size = CompactSize::zcash_deserialize(&b"\xfe\xfd\xaa\x01\x00"[..]).unwrap();
+/// assert_eq!(u64::from(size), 0x01aafd);
+///
+/// // Non-canonical encodings are rejected
+/// assert!(CompactSize::zcash_deserialize(&b"\xfd\x12\x00"[..]).is_err());
+///
+/// // So are sizes above the maximum
+/// assert!(CompactSize::zcash_deserialize(&b"\xfe\x00\x00\x00\x04"[..]).is_err());
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CompactSize(u32);
+
+impl CompactSize {
+    /// Returns the `CompactSize` for a list of `len` items, for
+    /// serialization.
+    ///
+    /// Returns an error if `len` is above [`MAX_COMPACT_SIZE`], because the
+    /// list can't be serialized.
+    pub fn from_len(len: usize) -> Result<Self, io::Error> {
+        (len as u64).try_into().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "length is larger than the maximum compactsize",
+            )
+        })
+    }
+}
+
+impl TryFrom<u64> for CompactSize {
+    type Error = SerializationError;
+
+    fn try_from(size: u64) -> Result<Self, Self::Error> {
+        if size <= MAX_COMPACT_SIZE {
+            Ok(CompactSize(size as u32))
+        } else {
+            Err(SerializationError::Parse(
+                "compactsize is larger than the maximum",
+            ))
+        }
+    }
+}
+
+impl From<CompactSize> for u64 {
+    fn from(size: CompactSize) -> Self {
+        size.0.into()
+    }
+}
+
+impl From<CompactSize> for usize {
+    fn from(size: CompactSize) -> Self {
+        size.0 as usize
+    }
+}
+
+impl ZcashSerialize for CompactSize {
+    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
+        match self.0 {
+            n @ 0x0000_0000..=0x0000_00fc => writer.write_u8(n as u8),
+            n @ 0x0000_00fd..=0x0000_ffff => {
+                writer.write_u8(0xfd)?;
+                writer.write_u16::<LittleEndian>(n as u16)
+            }
+            n => {
+                writer.write_u8(0xfe)?;
+                writer.write_u32::<LittleEndian>(n)
+            }
+        }
+    }
+}
+
+impl ZcashDeserialize for CompactSize {
+    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
+        use SerializationError::Parse;
+
+        let size = match reader.read_u8()? {
+            n @ 0x00..=0xfc => n.into(),
+            0xfd => match reader.read_u16::<LittleEndian>()? {
+                n @ 0x0000_00fd..=0x0000_ffff => n.into(),
+                _ => return Err(Parse("non-canonical compactsize")),
+            },
+            0xfe => match reader.read_u32::<LittleEndian>()? {
+                n @ 0x0001_0000..=0xffff_ffff => n.into(),
+                _ => return Err(Parse("non-canonical compactsize")),
+            },
+            0xff => match reader.read_u64::<LittleEndian>()? {
+                n @ 0x1_0000_0000..=0xffff_ffff_ffff_ffff => n,
+                _ => return Err(Parse("non-canonical compactsize")),
+            },
+        };
+
+        size.try_into()
+    }
+}
+
+#[cfg(test)]
+use proptest::{arbitrary::Arbitrary, prelude::*};
+
+#[cfg(test)]
+impl Arbitrary for CompactSize {
+    type Parameters = ();
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            // Each encoding length, and the boundaries between them
+            0u32..0xfd,
+            0xfdu32..=0xffff,
+            0x1_0000u32..=(MAX_COMPACT_SIZE as u32),
+            Just(0xfc),
+            Just(0xfd),
+            Just(0x1_0000),
+            Just(MAX_COMPACT_SIZE as u32),
+        ]
+        .prop_map(CompactSize)
+        .boxed()
+    }
+
+    type Strategy = BoxedStrategy<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the encoding of `size` with each marker byte that is longer
+    /// than its canonical encoding.
+    fn non_canonical_encodings(size: u32) -> Vec<Vec<u8>> {
+        let mut encodings = Vec::new();
+        if size <= 0xfc {
+            let mut bytes = vec![0xfd];
+            bytes.write_u16::<LittleEndian>(size as u16).unwrap();
+            encodings.push(bytes);
+        }
+        if size <= 0xffff {
+            let mut bytes = vec![0xfe];
+            bytes.write_u32::<LittleEndian>(size).unwrap();
+            encodings.push(bytes);
+        }
+        let mut bytes = vec![0xff];
+        bytes.write_u64::<LittleEndian>(size.into()).unwrap();
+        encodings.push(bytes);
+        encodings
+    }
+
+    #[test]
+    fn sizes_above_maximum_are_rejected() {
+        zebra_test::init();
+
+        assert!(CompactSize::try_from(MAX_COMPACT_SIZE).is_ok());
+        assert!(CompactSize::try_from(MAX_COMPACT_SIZE + 1).is_err());
+        assert!(CompactSize::from_len(MAX_COMPACT_SIZE as usize + 1).is_err());
+
+        let mut bytes = vec![0xfe];
+        bytes
+            .write_u32::<LittleEndian>(MAX_COMPACT_SIZE as u32 + 1)
+            .unwrap();
+        assert!(CompactSize::zcash_deserialize(&bytes[..]).is_err());
+
+        // Canonical u64 encodings are always above the maximum
+        let mut bytes = vec![0xff];
+        bytes.write_u64::<LittleEndian>(u64::MAX).unwrap();
+        assert!(CompactSize::zcash_deserialize(&bytes[..]).is_err());
+    }
+
+    proptest! {
+        // The tests below are cheap so we can run them a lot.
+        #![proptest_config(ProptestConfig::with_cases(100_000))]
+
+        #[test]
+        fn write_then_read_round_trip(size in any::<CompactSize>()) {
+            let bytes = size.zcash_serialize_to_vec().unwrap();
+            let expected_len = match size.0 {
+                0x0000_0000..=0x0000_00fc => 1,
+                0x0000_00fd..=0x0000_ffff => 3,
+                _ => 5,
+            };
+            prop_assert_eq!(bytes.len(), expected_len);
+
+            let other = CompactSize::zcash_deserialize(&bytes[..]).unwrap();
+            prop_assert_eq!(size, other);
+        }
+
+        #[test]
+        fn read_then_write_round_trip(bytes in prop::array::uniform9(0u8..)) {
+            // Only do the test if the bytes were valid.
+            if let Ok(size) = CompactSize::zcash_deserialize(&bytes[..]) {
+                // The encoding is variable-length, so only the bytes that
+                // were read must match.
+                let expect_bytes = size.zcash_serialize_to_vec().unwrap();
+                prop_assert_eq!(&bytes[..expect_bytes.len()], &expect_bytes[..]);
+            }
+        }
+
+        #[test]
+        fn non_canonical_encodings_are_rejected(size in any::<CompactSize>()) {
+            for bytes in non_canonical_encodings(size.0) {
+                prop_assert!(CompactSize::zcash_deserialize(&bytes[..]).is_err());
+            }
+        }
+    }
+}
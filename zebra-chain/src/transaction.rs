@@ -1,23 +1,37 @@
 //! Transaction types.
 
+use std::convert::TryFrom;
+
 use serde::{Deserialize, Serialize};
 
+mod fee;
 mod hash;
 mod joinsplit;
 mod serialize;
 mod shielded_data;
+mod sighash;
 mod transparent;
 
+#[cfg(any(test, feature = "builder"))]
+pub mod builder;
 #[cfg(test)]
 mod tests;
 
-pub use hash::TransactionHash;
+pub use fee::{GRACE_ACTIONS, MARGINAL_FEE, WEIGHT_RATIO_CAP};
+pub use hash::{AuthDigest, TransactionHash, WtxId};
 pub use joinsplit::{JoinSplit, JoinSplitData};
-pub use shielded_data::{Output, ShieldedData, Spend};
-pub use transparent::{CoinbaseData, OutPoint, TransparentInput, TransparentOutput};
+pub use shielded_data::{AnchorVariant, Output, PerSpendAnchor, SharedAnchor, ShieldedData, Spend};
+pub use sighash::HashType;
+pub use transparent::{
+    CoinbaseData, OutPoint, TransparentInput, TransparentOutput, MAX_COINBASE_DATA_LEN,
+};
 
+use crate::parameters::ConsensusBranchId;
 use crate::proofs::{Bctv14Proof, Groth16Proof};
-use crate::types::{amount::Amount, BlockHeight, LockTime};
+use crate::types::{
+    amount::{self, Amount, NonNegative},
+    BlockHeight, LockTime,
+};
 
 /// A Zcash transaction.
 ///
@@ -57,6 +71,9 @@ pub enum Transaction {
         joinsplit_data: Option<JoinSplitData<Bctv14Proof>>,
     },
     /// An Overwinter transaction (`version = 3`).
+    ///
+    /// The version group ID is always `OVERWINTER_VERSION_GROUP_ID`, so it is
+    /// implied by the variant, see [`Transaction::version_group_id`].
     V3 {
         /// The transparent inputs to the transaction.
         inputs: Vec<TransparentInput>,
@@ -71,6 +88,11 @@ pub enum Transaction {
         joinsplit_data: Option<JoinSplitData<Bctv14Proof>>,
     },
     /// A Sapling transaction (`version = 4`).
+    ///
+    /// The version group ID is always `SAPLING_VERSION_GROUP_ID`, so it is
+    /// implied by the variant. The binding signature is in `shielded_data`,
+    /// and the JoinSplit public key and signature are in `joinsplit_data`,
+    /// because they are only present if there are shielded descriptions.
     V4 {
         /// The transparent inputs to the transaction.
         inputs: Vec<TransparentInput>,
@@ -91,6 +113,41 @@ pub enum Transaction {
 }
 
 impl Transaction {
+    /// Get this transaction's version number.
+    pub fn version(&self) -> u32 {
+        match self {
+            Transaction::V1 { .. } => 1,
+            Transaction::V2 { .. } => 2,
+            Transaction::V3 { .. } => 3,
+            Transaction::V4 { .. } => 4,
+        }
+    }
+
+    /// Get this transaction's version group ID, if any.
+    pub fn version_group_id(&self) -> Option<u32> {
+        match self {
+            Transaction::V1 { .. } => None,
+            Transaction::V2 { .. } => None,
+            Transaction::V3 { .. } => Some(serialize::OVERWINTER_VERSION_GROUP_ID),
+            Transaction::V4 { .. } => Some(serialize::SAPLING_VERSION_GROUP_ID),
+        }
+    }
+
+    /// Get the consensus branch ID embedded in this transaction, if any.
+    ///
+    /// V5 transactions include the branch ID of the network upgrade they are
+    /// valid in, and are invalid in any other upgrade. Earlier versions only
+    /// commit to the branch ID in their signature hashes, so they return
+    /// `None`. Zebra doesn't parse V5 transactions yet.
+    pub fn consensus_branch_id(&self) -> Option<ConsensusBranchId> {
+        match self {
+            Transaction::V1 { .. }
+            | Transaction::V2 { .. }
+            | Transaction::V3 { .. }
+            | Transaction::V4 { .. } => None,
+        }
+    }
+
     /// Iterate over the transparent inputs of this transaction, if any.
     pub fn inputs(&self) -> impl Iterator<Item = &TransparentInput> {
         match self {
@@ -136,4 +193,158 @@ impl Transaction {
         self.inputs()
             .any(|input| matches!(input, TransparentInput::Coinbase { .. }))
     }
+
+    /// Returns `true` if this is a coinbase transaction, which has a single
+    /// coinbase input.
+    ///
+    /// Transactions that contain coinbase inputs along with other inputs are
+    /// invalid, so they are not coinbase transactions.
+    pub fn is_coinbase(&self) -> bool {
+        let mut inputs = self.inputs();
+
+        matches!(
+            (inputs.next(), inputs.next()),
+            (Some(TransparentInput::Coinbase { .. }), None)
+        )
+    }
+
+    /// Iterate over the outpoints spent by the transparent inputs of this
+    /// transaction.
+    ///
+    /// Coinbase inputs don't spend any outpoints.
+    pub fn spent_outpoints(&self) -> impl Iterator<Item = &OutPoint> {
+        self.inputs().filter_map(|input| match input {
+            TransparentInput::PrevOut { outpoint, .. } => Some(outpoint),
+            TransparentInput::Coinbase { .. } => None,
+        })
+    }
+
+    /// Get this transaction's JoinSplits with BCTV14 proofs, if any.
+    ///
+    /// V2 and V3 transactions use BCTV14 proofs.
+    pub fn bctv14_joinsplit_data(&self) -> Option<&JoinSplitData<Bctv14Proof>> {
+        match self {
+            Transaction::V2 { joinsplit_data, .. } => joinsplit_data.as_ref(),
+            Transaction::V3 { joinsplit_data, .. } => joinsplit_data.as_ref(),
+            Transaction::V1 { .. } | Transaction::V4 { .. } => None,
+        }
+    }
+
+    /// Get this transaction's JoinSplits with Groth16 proofs, if any.
+    ///
+    /// V4 transactions use Groth16 proofs.
+    pub fn groth16_joinsplit_data(&self) -> Option<&JoinSplitData<Groth16Proof>> {
+        match self {
+            Transaction::V4 { joinsplit_data, .. } => joinsplit_data.as_ref(),
+            Transaction::V1 { .. } | Transaction::V2 { .. } | Transaction::V3 { .. } => None,
+        }
+    }
+
+    /// Iterate over the JoinSplits with BCTV14 proofs in this transaction.
+    pub fn bctv14_joinsplits(&self) -> impl Iterator<Item = &JoinSplit<Bctv14Proof>> {
+        self.bctv14_joinsplit_data()
+            .into_iter()
+            .flat_map(JoinSplitData::joinsplits)
+    }
+
+    /// Iterate over the JoinSplits with Groth16 proofs in this transaction.
+    pub fn groth16_joinsplits(&self) -> impl Iterator<Item = &JoinSplit<Groth16Proof>> {
+        self.groth16_joinsplit_data()
+            .into_iter()
+            .flat_map(JoinSplitData::joinsplits)
+    }
+
+    /// Returns the number of JoinSplits in this transaction, regardless of
+    /// their proof type.
+    pub fn joinsplit_count(&self) -> usize {
+        self.bctv14_joinsplits().count() + self.groth16_joinsplits().count()
+    }
+
+    /// Get this transaction's Sapling shielded data, if any.
+    pub fn shielded_data(&self) -> Option<&ShieldedData> {
+        match self {
+            Transaction::V4 { shielded_data, .. } => shielded_data.as_ref(),
+            Transaction::V1 { .. } | Transaction::V2 { .. } | Transaction::V3 { .. } => None,
+        }
+    }
+
+    /// Iterate over the Sapling spends in this transaction.
+    pub fn sapling_spends(&self) -> impl Iterator<Item = &Spend> {
+        self.shielded_data()
+            .into_iter()
+            .flat_map(ShieldedData::spends)
+    }
+
+    /// Iterate over the Sapling outputs in this transaction.
+    pub fn sapling_outputs(&self) -> impl Iterator<Item = &Output> {
+        self.shielded_data()
+            .into_iter()
+            .flat_map(ShieldedData::outputs)
+    }
+
+    /// Get this transaction's Sapling value balance, if it has one.
+    ///
+    /// Only V4 transactions have a Sapling value balance.
+    pub fn sapling_value_balance(&self) -> Option<Amount> {
+        match self {
+            Transaction::V4 { value_balance, .. } => Some(*value_balance),
+            Transaction::V1 { .. } | Transaction::V2 { .. } | Transaction::V3 { .. } => None,
+        }
+    }
+
+    /// Returns the total value of the transparent outputs of this
+    /// transaction.
+    ///
+    /// The value of the transparent inputs depends on the outputs they spend,
+    /// so it can't be calculated from the transaction alone.
+    pub fn transparent_value_out(&self) -> Result<Amount<NonNegative>, amount::Error> {
+        self.outputs()
+            .map(|output| output.value)
+            .fold(Amount::try_from(0), |total, value| total + value)
+    }
+
+    /// Returns the total value that this transaction transfers from the
+    /// shielded pools into the transparent pool.
+    ///
+    /// This is the sum of the JoinSplit `vpub_new` values, and the Sapling
+    /// value balance, if it is positive.
+    pub fn shielded_value_in(&self) -> Result<Amount<NonNegative>, amount::Error> {
+        let sapling = self
+            .sapling_value_balance()
+            .and_then(|balance| balance.constrain().ok());
+
+        self.sprout_value_transfers()
+            .map(|(_vpub_old, vpub_new)| vpub_new)
+            .chain(sapling)
+            .fold(Amount::try_from(0), |total, value| total + value)
+    }
+
+    /// Returns the total value that this transaction transfers from the
+    /// transparent pool into the shielded pools.
+    ///
+    /// This is the sum of the JoinSplit `vpub_old` values, and the negated
+    /// Sapling value balance, if it is negative.
+    pub fn shielded_value_out(&self) -> Result<Amount<NonNegative>, amount::Error> {
+        let sapling = self
+            .sapling_value_balance()
+            .and_then(|balance| Amount::try_from(-i64::from(balance)).ok());
+
+        self.sprout_value_transfers()
+            .map(|(vpub_old, _vpub_new)| vpub_old)
+            .chain(sapling)
+            .fold(Amount::try_from(0), |total, value| total + value)
+    }
+
+    /// Iterate over the `(vpub_old, vpub_new)` values of the JoinSplits in
+    /// this transaction.
+    pub fn sprout_value_transfers(
+        &self,
+    ) -> impl Iterator<Item = (Amount<NonNegative>, Amount<NonNegative>)> + '_ {
+        self.bctv14_joinsplits()
+            .map(|joinsplit| (joinsplit.vpub_old, joinsplit.vpub_new))
+            .chain(
+                self.groth16_joinsplits()
+                    .map(|joinsplit| (joinsplit.vpub_old, joinsplit.vpub_new)),
+            )
+    }
 }
@@ -0,0 +1,498 @@
+//! A builder for test transactions.
+//!
+//! Consensus tests need transactions that parse, serialize, and pass the
+//! transparent checks, without depending on `librustzcash` fixtures. The
+//! builder signs transparent P2PKH inputs, and adds dummy Sapling spends and
+//! outputs. Dummy shielded descriptions have the correct structure, but their
+//! value commitments, proofs, and signatures are all zero, so they don't pass
+//! shielded verification.
+//!
+//! Zebra doesn't support V5 transactions yet, so the builder only creates V4
+//! transactions.
+
+use std::convert::TryInto;
+
+use futures::future::Either;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use thiserror::Error;
+
+use crate::{
+    addresses::transparent::TransparentAddress,
+    note_commitment_tree::SaplingNoteTreeRootHash,
+    notes::sapling,
+    parameters::ConsensusBranchId,
+    proofs::Groth16Proof,
+    types::{
+        amount::{self, Amount, NonNegative},
+        BlockHeight, LockTime, Script,
+    },
+};
+
+use super::{
+    CoinbaseData, HashType, OutPoint, Output, ShieldedData, Spend, Transaction, TransparentInput,
+    TransparentOutput, MAX_COINBASE_DATA_LEN,
+};
+
+/// An error building a transaction.
+#[derive(Error, Debug)]
+pub enum BuilderError {
+    /// The coinbase data is longer than [`MAX_COINBASE_DATA_LEN`].
+    #[error(
+        "coinbase data is {0} bytes, but the limit is {}",
+        MAX_COINBASE_DATA_LEN
+    )]
+    CoinbaseDataTooLong(usize),
+    /// A coinbase transaction has other inputs.
+    #[error("coinbase transactions must have exactly one input")]
+    CoinbaseWithOtherInputs,
+    /// The transaction spends more than its inputs.
+    #[error("transaction outputs are greater than its inputs")]
+    NegativeFee,
+    /// The transaction values are out of range.
+    #[error("transaction value is out of range")]
+    Amount(#[from] amount::Error),
+}
+
+/// A transparent input that the builder signs.
+#[derive(Clone, Debug)]
+struct SignedInput {
+    outpoint: OutPoint,
+    value: Amount<NonNegative>,
+    secret_key: SecretKey,
+}
+
+/// Builds V4 transactions, with transparent inputs and outputs, and dummy
+/// Sapling spends and outputs.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    coinbase: Option<(BlockHeight, Vec<u8>)>,
+    inputs: Vec<SignedInput>,
+    outputs: Vec<TransparentOutput>,
+    lock_time: LockTime,
+    expiry_height: BlockHeight,
+    sapling_spends: usize,
+    sapling_outputs: usize,
+    value_balance: Amount,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            coinbase: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            lock_time: LockTime::Height(BlockHeight(0)),
+            expiry_height: BlockHeight(0),
+            sapling_spends: 0,
+            sapling_outputs: 0,
+            value_balance: 0i64.try_into().expect("zero is a valid amount"),
+        }
+    }
+}
+
+impl Builder {
+    /// Returns a builder for a transaction with no inputs or outputs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a coinbase input for the block at `height`, with miner `data`.
+    pub fn coinbase(mut self, height: BlockHeight, data: Vec<u8>) -> Self {
+        self.coinbase = Some((height, data));
+        self
+    }
+
+    /// Add an input that spends the P2PKH output at `outpoint`, which holds
+    /// `value` for the public key of `secret_key`.
+    ///
+    /// The input is signed using `secret_key` when the transaction is built.
+    pub fn p2pkh_input(
+        mut self,
+        outpoint: OutPoint,
+        value: Amount<NonNegative>,
+        secret_key: SecretKey,
+    ) -> Self {
+        self.inputs.push(SignedInput {
+            outpoint,
+            value,
+            secret_key,
+        });
+        self
+    }
+
+    /// Add an output that pays `value` to `pk_script`.
+    pub fn output(mut self, value: Amount<NonNegative>, pk_script: Script) -> Self {
+        self.outputs.push(TransparentOutput { value, pk_script });
+        self
+    }
+
+    /// Add an output that pays `value` to `public_key`.
+    pub fn p2pkh_output(self, value: Amount<NonNegative>, public_key: &PublicKey) -> Self {
+        self.output(value, p2pkh_script(public_key))
+    }
+
+    /// Set the lock time of the transaction.
+    pub fn lock_time(mut self, lock_time: LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Set the expiry height of the transaction.
+    ///
+    /// The default expiry height is zero, which means that the transaction
+    /// doesn't expire.
+    pub fn expiry_height(mut self, expiry_height: BlockHeight) -> Self {
+        self.expiry_height = expiry_height;
+        self
+    }
+
+    /// Add `count` dummy Sapling spends to the transaction.
+    pub fn dummy_sapling_spends(mut self, count: usize) -> Self {
+        self.sapling_spends += count;
+        self
+    }
+
+    /// Add `count` dummy Sapling outputs to the transaction.
+    pub fn dummy_sapling_outputs(mut self, count: usize) -> Self {
+        self.sapling_outputs += count;
+        self
+    }
+
+    /// Set the Sapling value balance, which is the value that the shielded
+    /// spends and outputs add to the transparent value pool.
+    pub fn value_balance(mut self, value_balance: Amount) -> Self {
+        self.value_balance = value_balance;
+        self
+    }
+
+    /// Build the transaction, signing transparent inputs under the consensus
+    /// rules of `consensus_branch_id`.
+    pub fn build(
+        self,
+        consensus_branch_id: ConsensusBranchId,
+    ) -> Result<Transaction, BuilderError> {
+        let inputs = match &self.coinbase {
+            Some(_) if !self.inputs.is_empty() => {
+                return Err(BuilderError::CoinbaseWithOtherInputs)
+            }
+            Some((height, data)) => vec![TransparentInput::Coinbase {
+                height: *height,
+                data: CoinbaseData::new(data.clone())
+                    .ok_or_else(|| BuilderError::CoinbaseDataTooLong(data.len()))?,
+                sequence: u32::MAX,
+            }],
+            None => {
+                self.check_fee()?;
+                self.inputs
+                    .iter()
+                    .map(|input| TransparentInput::PrevOut {
+                        outpoint: input.outpoint,
+                        script: Script(Vec::new()),
+                        sequence: u32::MAX,
+                    })
+                    .collect()
+            }
+        };
+
+        let mut tx = Transaction::V4 {
+            inputs,
+            outputs: self.outputs.clone(),
+            lock_time: self.lock_time,
+            expiry_height: self.expiry_height,
+            value_balance: self.value_balance,
+            shielded_data: self.dummy_shielded_data(),
+            joinsplit_data: None,
+        };
+
+        // Signatures don't cover the other input scripts, so we can sign
+        // each input using the unsigned transaction
+        let script_sigs: Vec<Script> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| sign_p2pkh(&tx, consensus_branch_id, index, input))
+            .collect();
+
+        if let Transaction::V4 { inputs, .. } = &mut tx {
+            for (input, script_sig) in inputs.iter_mut().zip(script_sigs) {
+                if let TransparentInput::PrevOut { script, .. } = input {
+                    *script = script_sig;
+                }
+            }
+        }
+
+        Ok(tx)
+    }
+
+    /// Check that the transparent inputs and value balance pay for the
+    /// transparent outputs.
+    fn check_fee(&self) -> Result<(), BuilderError> {
+        let mut fee: Result<Amount, amount::Error> = Ok(self.value_balance);
+        for input in self.inputs.iter() {
+            fee += input.value.constrain()?;
+        }
+        for output in self.outputs.iter() {
+            fee -= output.value.constrain()?;
+        }
+
+        if i64::from(fee?) < 0 {
+            Err(BuilderError::NegativeFee)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns shielded data with the dummy spends and outputs, or `None` if
+    /// there aren't any.
+    fn dummy_shielded_data(&self) -> Option<ShieldedData> {
+        let mut spends = (0..self.sapling_spends).map(dummy_spend);
+        let mut outputs = (0..self.sapling_outputs).map(|_| dummy_output());
+
+        let first = match spends.next() {
+            Some(spend) => Either::Left(spend),
+            None => Either::Right(outputs.next()?),
+        };
+
+        Some(ShieldedData {
+            first,
+            rest_spends: spends.collect(),
+            rest_outputs: outputs.collect(),
+            shared_anchor: (),
+            binding_sig: [0; 64].into(),
+        })
+    }
+}
+
+/// Returns the P2PKH script that pays to `public_key`.
+pub fn p2pkh_script(public_key: &PublicKey) -> Script {
+    let hash = TransparentAddress::hash_payload(&public_key.serialize()[..]);
+
+    // OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG
+    let mut script = vec![0x76, 0xa9, hash.len() as u8];
+    script.extend_from_slice(&hash[..]);
+    script.extend_from_slice(&[0x88, 0xac]);
+    Script(script)
+}
+
+/// Returns the script that signs `input`, which is the input at `index` in
+/// `tx`.
+fn sign_p2pkh(
+    tx: &Transaction,
+    consensus_branch_id: ConsensusBranchId,
+    index: usize,
+    input: &SignedInput,
+) -> Script {
+    let secp = Secp256k1::signing_only();
+    let public_key = PublicKey::from_secret_key(&secp, &input.secret_key);
+
+    let sighash = tx
+        .sighash(
+            consensus_branch_id,
+            HashType::ALL,
+            Some((index, &p2pkh_script(&public_key), input.value)),
+        )
+        .expect("the builder only creates V4 transactions");
+    let message = Message::from_slice(&sighash[..]).expect("sighashes are 32 bytes");
+    let signature = secp.sign(&message, &input.secret_key).serialize_der();
+    let public_key = public_key.serialize();
+
+    // <signature || hash type> <public key>
+    let mut script = vec![signature.len() as u8 + 1];
+    script.extend_from_slice(&signature);
+    script.push(HashType::ALL.0 as u8);
+    script.push(public_key.len() as u8);
+    script.extend_from_slice(&public_key[..]);
+    Script(script)
+}
+
+/// Returns a dummy Sapling spend, with a nullifier derived from `index`.
+fn dummy_spend(index: usize) -> Spend {
+    let mut nullifier = [0; 32];
+    nullifier[..8].copy_from_slice(&(index as u64).to_le_bytes());
+
+    Spend {
+        cv: [0; 32],
+        anchor: SaplingNoteTreeRootHash([0; 32]),
+        nullifier: nullifier.into(),
+        rk: [0; 32].into(),
+        zkproof: Groth16Proof([0; 192]),
+        spend_auth_sig: [0; 64].into(),
+    }
+}
+
+/// Returns a dummy Sapling output.
+fn dummy_output() -> Output {
+    Output {
+        cv: [0; 32],
+        cmu: [0; 32],
+        ephemeral_key: jubjub::AffinePoint::identity(),
+        enc_ciphertext: sapling::EncryptedCiphertext([0; 580]),
+        out_ciphertext: sapling::OutCiphertext([0; 80]),
+        zkproof: Groth16Proof([0; 192]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secp256k1::Signature;
+
+    use crate::{
+        serialization::{ZcashDeserializeInto, ZcashSerialize},
+        transaction::TransactionHash,
+    };
+
+    /// The Sapling consensus branch ID.
+    const SAPLING_BRANCH_ID: ConsensusBranchId = ConsensusBranchId::new(0x76b8_09bb);
+
+    fn secret_key() -> SecretKey {
+        SecretKey::from_slice(&[7; 32]).expect("valid secret key")
+    }
+
+    fn outpoint() -> OutPoint {
+        OutPoint {
+            hash: TransactionHash([1; 32]),
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn signed_transparent_transaction() -> Result<(), BuilderError> {
+        zebra_test::init();
+
+        let secp = Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key());
+        let value: Amount<NonNegative> = 10_000u64.try_into()?;
+
+        let tx = Builder::new()
+            .p2pkh_input(outpoint(), value, secret_key())
+            .p2pkh_output(9_000u64.try_into()?, &public_key)
+            .expiry_height(BlockHeight(100))
+            .build(SAPLING_BRANCH_ID)?;
+
+        // The transaction round-trips through consensus serialization
+        let bytes = tx
+            .zcash_serialize_to_vec()
+            .expect("vec serialization is infallible");
+        let parsed: Transaction = bytes.zcash_deserialize_into().expect("tx should parse");
+        assert_eq!(parsed, tx);
+
+        // The input script contains a valid signature from the public key
+        let script = match tx.inputs().next() {
+            Some(TransparentInput::PrevOut { script, .. }) => script.0.clone(),
+            _ => panic!("expected a signed input"),
+        };
+        let signature_len = script[0] as usize;
+        let signature = Signature::from_der(&script[1..signature_len])
+            .expect("script contains a DER signature");
+        assert_eq!(script[signature_len], HashType::ALL.0 as u8);
+        assert_eq!(&script[signature_len + 2..], &public_key.serialize()[..]);
+
+        let sighash = tx
+            .sighash(
+                SAPLING_BRANCH_ID,
+                HashType::ALL,
+                Some((0, &p2pkh_script(&public_key), value)),
+            )
+            .expect("V4 transactions have sighashes");
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        assert!(secp.verify(&message, &signature, &public_key).is_ok());
+
+        // Signatures commit to the consensus branch
+        let other_sighash = tx.sighash(
+            ConsensusBranchId::new(u32::from(SAPLING_BRANCH_ID) + 1),
+            HashType::ALL,
+            Some((0, &p2pkh_script(&public_key), value)),
+        );
+        assert_ne!(other_sighash, Some(sighash));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dummy_shielded_transaction() -> Result<(), BuilderError> {
+        zebra_test::init();
+
+        let tx = Builder::new()
+            .p2pkh_input(outpoint(), 1_000u64.try_into()?, secret_key())
+            .dummy_sapling_spends(2)
+            .dummy_sapling_outputs(3)
+            .build(SAPLING_BRANCH_ID)?;
+
+        let shielded_data = match &tx {
+            Transaction::V4 {
+                shielded_data: Some(shielded_data),
+                ..
+            } => shielded_data.clone(),
+            _ => panic!("expected shielded data"),
+        };
+        assert_eq!(shielded_data.spends().count(), 2);
+        assert_eq!(shielded_data.outputs().count(), 3);
+
+        let bytes = tx
+            .zcash_serialize_to_vec()
+            .expect("vec serialization is infallible");
+        let parsed: Transaction = bytes.zcash_deserialize_into().expect("tx should parse");
+        assert_eq!(parsed, tx);
+
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_values() -> Result<(), BuilderError> {
+        zebra_test::init();
+
+        let overspend = Builder::new()
+            .p2pkh_input(outpoint(), 1_000u64.try_into()?, secret_key())
+            .output(1_001u64.try_into()?, Script(vec![]))
+            .build(SAPLING_BRANCH_ID);
+        assert!(matches!(overspend, Err(BuilderError::NegativeFee)));
+
+        // The value balance moves shielded value into the transparent pool
+        Builder::new()
+            .p2pkh_input(outpoint(), 1_000u64.try_into()?, secret_key())
+            .output(1_001u64.try_into()?, Script(vec![]))
+            .dummy_sapling_spends(1)
+            .value_balance(1i64.try_into()?)
+            .build(SAPLING_BRANCH_ID)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn coinbase_transaction() -> Result<(), BuilderError> {
+        zebra_test::init();
+
+        let tx = Builder::new()
+            .coinbase(BlockHeight(1_000), vec![1, 2, 3])
+            .output(12_500u64.try_into()?, Script(vec![]))
+            .build(SAPLING_BRANCH_ID)?;
+        assert!(tx.contains_coinbase_input());
+
+        let bytes = tx
+            .zcash_serialize_to_vec()
+            .expect("vec serialization is infallible");
+        let parsed: Transaction = bytes.zcash_deserialize_into().expect("tx should parse");
+        assert_eq!(parsed, tx);
+
+        let too_long = Builder::new()
+            .coinbase(BlockHeight(1_000), vec![0; MAX_COINBASE_DATA_LEN + 1])
+            .build(SAPLING_BRANCH_ID);
+        assert!(matches!(
+            too_long,
+            Err(BuilderError::CoinbaseDataTooLong(_))
+        ));
+
+        let extra_input = Builder::new()
+            .coinbase(BlockHeight(1_000), vec![])
+            .p2pkh_input(outpoint(), 1_000u64.try_into()?, secret_key())
+            .build(SAPLING_BRANCH_ID);
+        assert!(matches!(
+            extra_input,
+            Err(BuilderError::CoinbaseWithOtherInputs)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Conventional transaction fees, from ZIP-317.
+//!
+//! https://zips.z.cash/zip-0317
+
+use std::convert::TryInto;
+
+use crate::{
+    serialization::ZcashSerialize,
+    types::amount::{Amount, NonNegative},
+};
+
+use super::Transaction;
+
+/// The marginal fee for each logical action, in zatoshis.
+pub const MARGINAL_FEE: u64 = 5_000;
+
+/// The number of logical actions that are paid for by every transaction,
+/// even if it has fewer actions.
+pub const GRACE_ACTIONS: u64 = 2;
+
+/// The standard size of a P2PKH input, in bytes.
+const P2PKH_STANDARD_INPUT_SIZE: u64 = 150;
+
+/// The standard size of a P2PKH output, in bytes.
+const P2PKH_STANDARD_OUTPUT_SIZE: u64 = 34;
+
+/// The maximum fee weight ratio used for block template transaction
+/// selection.
+pub const WEIGHT_RATIO_CAP: f32 = 4.0;
+
+impl Transaction {
+    /// Returns the number of ZIP-317 logical actions in this transaction.
+    ///
+    /// Transparent inputs and outputs are counted using their serialized
+    /// sizes, so large transparent scripts pay for extra actions.
+    pub fn logical_actions(&self) -> u64 {
+        let input_size: u64 = self.inputs().map(serialized_size).sum();
+        let output_size: u64 = self.outputs().map(serialized_size).sum();
+        let transparent_actions = input_size
+            .div_ceil(P2PKH_STANDARD_INPUT_SIZE)
+            .max(output_size.div_ceil(P2PKH_STANDARD_OUTPUT_SIZE));
+
+        let joinsplits = self.joinsplit_count();
+        let sapling_spends = self.sapling_spends().count();
+        let sapling_outputs = self.sapling_outputs().count();
+
+        transparent_actions + 2 * joinsplits as u64 + sapling_spends.max(sapling_outputs) as u64
+    }
+
+    /// Returns the ZIP-317 conventional fee for this transaction.
+    pub fn conventional_fee(&self) -> Amount<NonNegative> {
+        let fee = MARGINAL_FEE * GRACE_ACTIONS.max(self.logical_actions());
+
+        fee.try_into()
+            .expect("conventional fees are much less than the maximum amount")
+    }
+
+    /// Returns the number of logical actions in this transaction that are not
+    /// paid for by `fee`.
+    ///
+    /// Block producers limit the number of unpaid actions in each block, so
+    /// transactions with unpaid actions may not be mined.
+    pub fn unpaid_actions(&self, fee: Amount<NonNegative>) -> u64 {
+        let paid_actions = u64::from(fee) / MARGINAL_FEE;
+
+        GRACE_ACTIONS
+            .max(self.logical_actions())
+            .saturating_sub(paid_actions)
+    }
+
+    /// Returns the ZIP-317 fee weight ratio for this transaction, when it pays
+    /// `fee`.
+    ///
+    /// Transactions with higher ratios are preferred when selecting
+    /// transactions for block templates. The ratio is capped at
+    /// `WEIGHT_RATIO_CAP`, so high fees only buy limited priority.
+    pub fn fee_weight_ratio(&self, fee: Amount<NonNegative>) -> f32 {
+        let ratio = u64::from(fee) as f32 / u64::from(self.conventional_fee()) as f32;
+
+        ratio.min(WEIGHT_RATIO_CAP)
+    }
+}
+
+/// Returns the serialized size of `item`.
+fn serialized_size<T: ZcashSerialize>(item: &T) -> u64 {
+    item.zcash_serialize_to_vec()
+        .expect("serializing into a vec is infallible")
+        .len() as u64
+}
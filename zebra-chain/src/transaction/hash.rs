@@ -1,12 +1,12 @@
 #![allow(clippy::unit_arg)]
-use std::fmt;
+use std::{convert::TryFrom, fmt};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    serialization::{SerializationError, ZcashSerialize},
+    serialization::{SerializationError, ZcashDeserialize, ZcashSerialize},
     sha256d_writer::Sha256dWriter,
 };
 
@@ -16,12 +16,20 @@ use super::Transaction;
 ///
 /// TODO: I'm pretty sure this is also a SHA256d hash but I haven't
 /// confirmed it yet.
-#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, ZcashSerialize, ZcashDeserialize,
+)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct TransactionHash(pub [u8; 32]);
 
 impl From<Transaction> for TransactionHash {
     fn from(transaction: Transaction) -> Self {
+        TransactionHash::from(&transaction)
+    }
+}
+
+impl<'a> From<&'a Transaction> for TransactionHash {
+    fn from(transaction: &'a Transaction) -> Self {
         let mut hash_writer = Sha256dWriter::default();
         transaction
             .zcash_serialize(&mut hash_writer)
@@ -38,19 +46,75 @@ impl fmt::Debug for TransactionHash {
     }
 }
 
+impl fmt::Display for TransactionHash {
+    /// Formats the hash as hex, in the big-endian byte order used by zcashd
+    /// and block explorers.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut reversed_bytes = self.0;
+        reversed_bytes.reverse();
+        f.write_str(&hex::encode(&reversed_bytes))
+    }
+}
+
 impl std::str::FromStr for TransactionHash {
     type Err = SerializationError;
 
+    /// Parses a hex hash in the big-endian byte order used by zcashd and
+    /// block explorers.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut bytes = [0; 32];
         if hex::decode_to_slice(s, &mut bytes[..]).is_err() {
             Err(SerializationError::Parse("hex decoding error"))
         } else {
+            bytes.reverse();
             Ok(TransactionHash(bytes))
         }
     }
 }
 
+impl TryFrom<&str> for TransactionHash {
+    type Error = SerializationError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A commitment to the authorizing data of a transaction, such as its
+/// signatures and proofs.
+///
+/// Defined in [ZIP-244] for v5 transactions. Zebra does not support v5
+/// transactions yet, so it only uses these digests as opaque identifiers.
+///
+/// [ZIP-244]: https://zips.z.cash/zip-0244
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct AuthDigest(pub [u8; 32]);
+
+impl fmt::Debug for AuthDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("AuthDigest")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+/// A wide transaction ID, which identifies a transaction and its authorizing
+/// data.
+///
+/// Defined in [ZIP-239] for relaying v5 transactions, where the transaction
+/// hash does not commit to the authorizing data.
+///
+/// [ZIP-239]: https://zips.z.cash/zip-0239
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct WtxId {
+    /// The non-malleable transaction ID.
+    pub id: TransactionHash,
+    /// The authorizing data digest.
+    pub auth_digest: AuthDigest,
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
@@ -76,7 +140,7 @@ mod tests {
     #[test]
     fn transactionhash_from_str() {
         let hash: TransactionHash =
-            "bf46b4b5030752fedac6f884976162bbfb29a9398f104a280b3e34d51b416631"
+            "3166411bd5343e0b284a108f39a929fbbb62619784f8c6dafe520703b5b446bf"
                 .parse()
                 .unwrap();
         assert_eq!(
@@ -84,4 +148,13 @@ mod tests {
             r#"TransactionHash("bf46b4b5030752fedac6f884976162bbfb29a9398f104a280b3e34d51b416631")"#
         );
     }
+
+    #[test]
+    fn transactionhash_display() {
+        let hash_str = "3166411bd5343e0b284a108f39a929fbbb62619784f8c6dafe520703b5b446bf";
+        let hash = TransactionHash::try_from(hash_str).unwrap();
+        assert_eq!(hash.to_string(), hash_str);
+
+        assert!(TransactionHash::try_from("not hex").is_err());
+    }
 }
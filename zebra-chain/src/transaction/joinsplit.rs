@@ -1,9 +1,17 @@
 use crate::types::amount::{Amount, NonNegative};
-use crate::{ed25519_zebra, notes::sprout, proofs::ZkSnarkProof};
+use crate::{
+    ed25519_zebra, note_commitment_tree::SproutNoteTreeRootHash, notes::sprout,
+    proofs::ZkSnarkProof, serialization::AtLeastOne,
+};
 use serde::{Deserialize, Serialize};
 
 /// A _JoinSplit Description_, as described in [protocol specification §7.2][ps].
 ///
+/// JoinSplits are generic over their proof system: version 2 and 3
+/// transactions use [`Bctv14Proof`](crate::proofs::Bctv14Proof)s, and version
+/// 4 transactions use [`Groth16Proof`](crate::proofs::Groth16Proof)s. The
+/// other fields are the same in both proof systems.
+///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#joinsplitencoding
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct JoinSplit<P: ZkSnarkProof> {
@@ -17,17 +25,11 @@ pub struct JoinSplit<P: ZkSnarkProof> {
     /// A root of the Sprout note commitment tree at some block height in the
     /// past, or the root produced by a previous JoinSplit transfer in this
     /// transaction.
-    ///
-    /// XXX refine type
-    pub anchor: [u8; 32],
-    /// A nullifier for the input notes.
-    ///
-    /// XXX refine type to [T; 2] -- there are two nullifiers
+    pub anchor: SproutNoteTreeRootHash,
+    /// The nullifiers of the two input notes.
     pub nullifiers: [crate::nullifier::sprout::Nullifier; 2],
-    /// A note commitment for this output note.
-    ///
-    /// XXX refine type to [T; 2] -- there are two commitments
-    pub commitments: [[u8; 32]; 2],
+    /// The note commitments of the two output notes.
+    pub commitments: [sprout::NoteCommitment; 2],
     /// An X25519 public key.
     pub ephemeral_key: x25519_dalek::PublicKey,
     /// A 256-bit seed that must be chosen independently at random for each
@@ -66,28 +68,15 @@ impl<P: ZkSnarkProof> Eq for JoinSplit<P> {}
 /// A bundle of JoinSplit descriptions and signature data.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct JoinSplitData<P: ZkSnarkProof> {
-    /// The first JoinSplit description, using proofs of type `P`.
-    ///
-    /// Storing this separately from `rest` ensures that it is impossible
-    /// to construct an invalid `JoinSplitData` with no `JoinSplit`s.
-    ///
-    /// However, it's not necessary to access or process `first` and `rest`
-    /// separately, as the [`JoinSplitData::joinsplits`] method provides an
-    /// iterator over all of the `JoinSplit`s.
-    #[serde(bound(
-        serialize = "JoinSplit<P>: Serialize",
-        deserialize = "JoinSplit<P>: Deserialize<'de>"
-    ))]
-    pub first: JoinSplit<P>,
-    /// The rest of the JoinSplit descriptions, using proofs of type `P`.
+    /// The JoinSplit descriptions, using proofs of type `P`.
     ///
-    /// The [`JoinSplitData::joinsplits`] method provides an iterator over
-    /// all `JoinSplit`s.
+    /// Using [`AtLeastOne`] ensures that it is impossible to construct an
+    /// invalid `JoinSplitData` with no `JoinSplit`s.
     #[serde(bound(
-        serialize = "JoinSplit<P>: Serialize",
+        serialize = "JoinSplit<P>: Clone + Serialize",
         deserialize = "JoinSplit<P>: Deserialize<'de>"
     ))]
-    pub rest: Vec<JoinSplit<P>>,
+    pub joinsplits: AtLeastOne<JoinSplit<P>>,
     /// The public key for the JoinSplit signature.
     pub pub_key: ed25519_zebra::VerificationKeyBytes,
     /// The JoinSplit signature.
@@ -97,6 +86,6 @@ pub struct JoinSplitData<P: ZkSnarkProof> {
 impl<P: ZkSnarkProof> JoinSplitData<P> {
     /// Iterate over the [`JoinSplit`]s in `self`.
     pub fn joinsplits(&self) -> impl Iterator<Item = &JoinSplit<P>> {
-        std::iter::once(&self.first).chain(self.rest.iter())
+        self.joinsplits.iter()
     }
 }
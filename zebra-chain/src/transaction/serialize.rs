@@ -8,17 +8,19 @@ use std::{
     sync::Arc,
 };
 
+use crate::note_commitment_tree::SproutNoteTreeRootHash;
 use crate::notes;
 use crate::proofs::ZkSnarkProof;
 use crate::serialization::{
-    ReadZcashExt, SerializationError, WriteZcashExt, ZcashDeserialize, ZcashSerialize,
+    AtLeastOne, CompactSize, ReadZcashExt, SerializationError, TrustedPreallocate,
+    ZcashDeserialize, ZcashSerialize, MAX_PROTOCOL_MESSAGE_LEN,
 };
 use crate::types::Script;
 
 use super::*;
 
-const OVERWINTER_VERSION_GROUP_ID: u32 = 0x03C4_8270;
-const SAPLING_VERSION_GROUP_ID: u32 = 0x892F_2085;
+pub(super) const OVERWINTER_VERSION_GROUP_ID: u32 = 0x03C4_8270;
+pub(super) const SAPLING_VERSION_GROUP_ID: u32 = 0x892F_2085;
 
 /// The coinbase data for a genesis block.
 ///
@@ -31,20 +33,41 @@ const GENESIS_COINBASE_DATA: [u8; 77] = [
     54, 52, 56, 51, 53, 100, 51, 52,
 ];
 
-impl ZcashSerialize for OutPoint {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.hash.0[..])?;
-        writer.write_u32::<LittleEndian>(self.index)?;
-        Ok(())
-    }
-}
+/// The minimum serialized size of a transparent input: an outpoint, an empty
+/// script, and a sequence number.
+const MIN_TRANSPARENT_INPUT_LEN: u64 = 32 + 4 + 1 + 4;
 
-impl ZcashDeserialize for OutPoint {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        Ok(OutPoint {
-            hash: TransactionHash(reader.read_32_bytes()?),
-            index: reader.read_u32::<LittleEndian>()?,
-        })
+/// The minimum serialized size of a transparent output: a value and an empty
+/// script.
+const MIN_TRANSPARENT_OUTPUT_LEN: u64 = 8 + 1;
+
+/// The minimum serialized size of a JoinSplit, which has a Groth16 proof.
+///
+/// JoinSplits with BCTV14 proofs are larger.
+const MIN_JOINSPLIT_LEN: u64 = 8 + 8 + 32 + 2 * 32 + 2 * 32 + 32 + 32 + 2 * 32 + 192 + 2 * 601;
+
+/// The serialized size of a Sapling spend description.
+const SPEND_LEN: u64 = 32 + 32 + 32 + 32 + 192 + 64;
+
+/// The serialized size of a Sapling output description.
+const OUTPUT_LEN: u64 = 32 + 32 + 32 + 580 + 80 + 192;
+
+/// The minimum serialized size of a transaction: a V1 transaction with no
+/// inputs or outputs.
+const MIN_TRANSACTION_LEN: u64 = 4 + 1 + 1 + 4;
+
+/// Reads an `nExpiryHeight` field.
+///
+/// The spec says that "nExpiryHeight MUST be less than or equal to
+/// 499999999", so larger heights are rejected.
+fn read_expiry_height<R: io::Read>(mut reader: R) -> Result<BlockHeight, SerializationError> {
+    let height = reader.read_u32::<LittleEndian>()?;
+    if height <= BlockHeight::MAX.0 {
+        Ok(BlockHeight(height))
+    } else {
+        Err(SerializationError::Parse(
+            "expiry height exceeds maximum height",
+        ))
     }
 }
 
@@ -125,7 +148,10 @@ fn coinbase_height_len(height: BlockHeight) -> usize {
     }
 }
 
-fn write_coinbase_height<W: io::Write>(height: BlockHeight, mut w: W) -> Result<(), io::Error> {
+pub(super) fn write_coinbase_height<W: io::Write>(
+    height: BlockHeight,
+    mut w: W,
+) -> Result<(), io::Error> {
     // We can't write this as a match statement on stable until exclusive range
     // guards are stabilized.
     if let 0 = height.0 {
@@ -173,7 +199,7 @@ impl ZcashSerialize for TransparentInput {
                 writer.write_u32::<LittleEndian>(0xffff_ffff)?;
                 let height_len = coinbase_height_len(*height);
                 let total_len = height_len + data.as_ref().len();
-                writer.write_compactsize(total_len as u64)?;
+                CompactSize::from_len(total_len)?.zcash_serialize(&mut writer)?;
                 write_coinbase_height(*height, &mut writer)?;
                 writer.write_all(&data.as_ref()[..])?;
                 writer.write_u32::<LittleEndian>(*sequence)?;
@@ -192,7 +218,7 @@ impl ZcashDeserialize for TransparentInput {
             if reader.read_u32::<LittleEndian>()? != 0xffff_ffff {
                 return Err(SerializationError::Parse("wrong index in coinbase"));
             }
-            let len = reader.read_compactsize()?;
+            let len: u64 = CompactSize::zcash_deserialize(&mut reader)?.into();
             if len > 100 {
                 return Err(SerializationError::Parse("coinbase has too much data"));
             }
@@ -218,6 +244,12 @@ impl ZcashDeserialize for TransparentInput {
     }
 }
 
+impl TrustedPreallocate for TransparentInput {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / MIN_TRANSPARENT_INPUT_LEN
+    }
+}
+
 impl ZcashSerialize for TransparentOutput {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         writer.write_u64::<LittleEndian>(self.value.into())?;
@@ -235,15 +267,21 @@ impl ZcashDeserialize for TransparentOutput {
     }
 }
 
+impl TrustedPreallocate for TransparentOutput {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / MIN_TRANSPARENT_OUTPUT_LEN
+    }
+}
+
 impl<P: ZkSnarkProof> ZcashSerialize for JoinSplit<P> {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         writer.write_u64::<LittleEndian>(self.vpub_old.into())?;
         writer.write_u64::<LittleEndian>(self.vpub_new.into())?;
-        writer.write_all(&self.anchor[..])?;
+        writer.write_all(&self.anchor.0[..])?;
         self.nullifiers[0].zcash_serialize(&mut writer)?;
         self.nullifiers[1].zcash_serialize(&mut writer)?;
-        writer.write_all(&self.commitments[0][..])?;
-        writer.write_all(&self.commitments[1][..])?;
+        self.commitments[0].zcash_serialize(&mut writer)?;
+        self.commitments[1].zcash_serialize(&mut writer)?;
         writer.write_all(&self.ephemeral_key.as_bytes()[..])?;
         writer.write_all(&self.random_seed[..])?;
         self.vmacs[0].zcash_serialize(&mut writer)?;
@@ -260,12 +298,15 @@ impl<P: ZkSnarkProof> ZcashDeserialize for JoinSplit<P> {
         Ok(JoinSplit::<P> {
             vpub_old: reader.read_u64::<LittleEndian>()?.try_into()?,
             vpub_new: reader.read_u64::<LittleEndian>()?.try_into()?,
-            anchor: reader.read_32_bytes()?,
+            anchor: SproutNoteTreeRootHash(reader.read_32_bytes()?),
             nullifiers: [
                 crate::nullifier::sprout::Nullifier::zcash_deserialize(&mut reader)?,
                 crate::nullifier::sprout::Nullifier::zcash_deserialize(&mut reader)?,
             ],
-            commitments: [reader.read_32_bytes()?, reader.read_32_bytes()?],
+            commitments: [
+                notes::sprout::NoteCommitment::zcash_deserialize(&mut reader)?,
+                notes::sprout::NoteCommitment::zcash_deserialize(&mut reader)?,
+            ],
             ephemeral_key: x25519_dalek::PublicKey::from(reader.read_32_bytes()?),
             random_seed: reader.read_32_bytes()?,
             vmacs: [
@@ -281,12 +322,15 @@ impl<P: ZkSnarkProof> ZcashDeserialize for JoinSplit<P> {
     }
 }
 
+impl<P: ZkSnarkProof> TrustedPreallocate for JoinSplit<P> {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / MIN_JOINSPLIT_LEN
+    }
+}
+
 impl<P: ZkSnarkProof> ZcashSerialize for JoinSplitData<P> {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_compactsize(self.joinsplits().count() as u64)?;
-        for joinsplit in self.joinsplits() {
-            joinsplit.zcash_serialize(&mut writer)?;
-        }
+        self.joinsplits.zcash_serialize(&mut writer)?;
         writer.write_all(&<[u8; 32]>::from(self.pub_key)[..])?;
         writer.write_all(&<[u8; 64]>::from(self.sig)[..])?;
         Ok(())
@@ -295,20 +339,15 @@ impl<P: ZkSnarkProof> ZcashSerialize for JoinSplitData<P> {
 
 impl<P: ZkSnarkProof> ZcashDeserialize for Option<JoinSplitData<P>> {
     fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let num_joinsplits = reader.read_compactsize()?;
+        let num_joinsplits = CompactSize::zcash_deserialize(&mut reader)?.into();
         match num_joinsplits {
             0 => Ok(None),
             n => {
-                let first = JoinSplit::zcash_deserialize(&mut reader)?;
-                let mut rest = Vec::with_capacity((n - 1) as usize);
-                for _ in 0..(n - 1) {
-                    rest.push(JoinSplit::zcash_deserialize(&mut reader)?);
-                }
+                let joinsplits = AtLeastOne::read_elements(&mut reader, n)?;
                 let pub_key = reader.read_32_bytes()?.into();
                 let sig = reader.read_64_bytes()?.into();
                 Ok(Some(JoinSplitData {
-                    first,
-                    rest,
+                    joinsplits,
                     pub_key,
                     sig,
                 }))
@@ -343,6 +382,12 @@ impl ZcashDeserialize for Spend {
     }
 }
 
+impl TrustedPreallocate for Spend {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / SPEND_LEN
+    }
+}
+
 impl ZcashSerialize for Output {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         writer.write_all(&self.cv[..])?;
@@ -368,6 +413,12 @@ impl ZcashDeserialize for Output {
     }
 }
 
+impl TrustedPreallocate for Output {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / OUTPUT_LEN
+    }
+}
+
 impl ZcashSerialize for Transaction {
     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         // Post-Sapling, transaction size is limited to MAX_BLOCK_BYTES.
@@ -404,7 +455,7 @@ impl ZcashSerialize for Transaction {
                 lock_time.zcash_serialize(&mut writer)?;
                 match joinsplit_data {
                     // Write 0 for nJoinSplits to signal no JoinSplitData.
-                    None => writer.write_compactsize(0)?,
+                    None => CompactSize::default().zcash_serialize(&mut writer)?,
                     Some(jsd) => jsd.zcash_serialize(&mut writer)?,
                 }
             }
@@ -424,7 +475,7 @@ impl ZcashSerialize for Transaction {
                 writer.write_u32::<LittleEndian>(expiry_height.0)?;
                 match joinsplit_data {
                     // Write 0 for nJoinSplits to signal no JoinSplitData.
-                    None => writer.write_compactsize(0)?,
+                    None => CompactSize::default().zcash_serialize(&mut writer)?,
                     Some(jsd) => jsd.zcash_serialize(&mut writer)?,
                 }
             }
@@ -457,15 +508,17 @@ impl ZcashSerialize for Transaction {
                 match shielded_data {
                     None => {
                         // Signal no shielded spends and no shielded outputs.
-                        writer.write_compactsize(0)?;
-                        writer.write_compactsize(0)?;
+                        CompactSize::default().zcash_serialize(&mut writer)?;
+                        CompactSize::default().zcash_serialize(&mut writer)?;
                     }
                     Some(shielded_data) => {
-                        writer.write_compactsize(shielded_data.spends().count() as u64)?;
+                        CompactSize::from_len(shielded_data.spends().count())?
+                            .zcash_serialize(&mut writer)?;
                         for spend in shielded_data.spends() {
                             spend.zcash_serialize(&mut writer)?;
                         }
-                        writer.write_compactsize(shielded_data.outputs().count() as u64)?;
+                        CompactSize::from_len(shielded_data.outputs().count())?
+                            .zcash_serialize(&mut writer)?;
                         for output in shielded_data.outputs() {
                             output.zcash_serialize(&mut writer)?;
                         }
@@ -473,7 +526,7 @@ impl ZcashSerialize for Transaction {
                 }
 
                 match joinsplit_data {
-                    None => writer.write_compactsize(0)?,
+                    None => CompactSize::default().zcash_serialize(&mut writer)?,
                     Some(jsd) => jsd.zcash_serialize(&mut writer)?,
                 }
 
@@ -525,7 +578,7 @@ impl ZcashDeserialize for Transaction {
                     inputs: Vec::zcash_deserialize(&mut reader)?,
                     outputs: Vec::zcash_deserialize(&mut reader)?,
                     lock_time: LockTime::zcash_deserialize(&mut reader)?,
-                    expiry_height: BlockHeight(reader.read_u32::<LittleEndian>()?),
+                    expiry_height: read_expiry_height(&mut reader)?,
                     joinsplit_data: OptV3JSD::zcash_deserialize(&mut reader)?,
                 })
             }
@@ -550,7 +603,7 @@ impl ZcashDeserialize for Transaction {
                 let inputs = Vec::zcash_deserialize(&mut reader)?;
                 let outputs = Vec::zcash_deserialize(&mut reader)?;
                 let lock_time = LockTime::zcash_deserialize(&mut reader)?;
-                let expiry_height = BlockHeight(reader.read_u32::<LittleEndian>()?);
+                let expiry_height = read_expiry_height(&mut reader)?;
                 let value_balance = reader.read_i64::<LittleEndian>()?.try_into()?;
                 let mut shielded_spends = Vec::zcash_deserialize(&mut reader)?;
                 let mut shielded_outputs = Vec::zcash_deserialize(&mut reader)?;
@@ -562,6 +615,7 @@ impl ZcashDeserialize for Transaction {
                         first: Left(shielded_spends.remove(0)),
                         rest_spends: shielded_spends,
                         rest_outputs: shielded_outputs,
+                        shared_anchor: (),
                         binding_sig: reader.read_64_bytes()?.into(),
                     })
                 } else if !shielded_outputs.is_empty() {
@@ -569,6 +623,7 @@ impl ZcashDeserialize for Transaction {
                         first: Right(shielded_outputs.remove(0)),
                         rest_spends: shielded_spends,
                         rest_outputs: shielded_outputs,
+                        shared_anchor: (),
                         binding_sig: reader.read_64_bytes()?.into(),
                     })
                 } else {
@@ -590,6 +645,12 @@ impl ZcashDeserialize for Transaction {
     }
 }
 
+impl TrustedPreallocate for Transaction {
+    fn max_allocation() -> u64 {
+        MAX_PROTOCOL_MESSAGE_LEN as u64 / MIN_TRANSACTION_LEN
+    }
+}
+
 impl<T> ZcashDeserialize for Arc<T>
 where
     T: ZcashDeserialize,
@@ -599,6 +660,15 @@ where
     }
 }
 
+impl<T> TrustedPreallocate for Arc<T>
+where
+    T: TrustedPreallocate,
+{
+    fn max_allocation() -> u64 {
+        T::max_allocation()
+    }
+}
+
 impl<T> ZcashSerialize for Arc<T>
 where
     T: ZcashSerialize,
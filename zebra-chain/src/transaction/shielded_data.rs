@@ -5,18 +5,54 @@ use crate::proofs::Groth16Proof;
 use crate::redjubjub::{self, Binding, SpendAuth};
 use crate::serde_helpers;
 use futures::future::Either;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+
+/// The location of the anchors in a Sapling bundle.
+///
+/// In V4 transactions, each spend has its own anchor. In V5 transactions,
+/// every spend in the bundle shares the same anchor, which is stored once in
+/// the [`ShieldedData`].
+pub trait AnchorVariant {
+    /// The type of the anchor shared by every spend in the bundle.
+    type Shared: Clone + Debug + Eq + Serialize + DeserializeOwned;
+    /// The type of the anchor in each spend.
+    type PerSpend: Clone + Debug + Eq + Serialize + DeserializeOwned;
+}
+
+/// Each spend has its own anchor, like in V4 transactions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PerSpendAnchor;
+
+/// Every spend in the bundle shares the same anchor, like in V5
+/// transactions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SharedAnchor;
+
+impl AnchorVariant for PerSpendAnchor {
+    type Shared = ();
+    type PerSpend = SaplingNoteTreeRootHash;
+}
+
+impl AnchorVariant for SharedAnchor {
+    type Shared = SaplingNoteTreeRootHash;
+    type PerSpend = ();
+}
 
 /// A _Spend Description_, as described in [protocol specification §7.3][ps].
 ///
 /// [ps]: https://zips.z.cash/protocol/protocol.pdf#spendencoding
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Spend {
+#[serde(bound = "")]
+pub struct Spend<AnchorV: AnchorVariant = PerSpendAnchor> {
     /// A value commitment to the value of the input note.
     ///
     /// XXX refine to a specific type.
     pub cv: [u8; 32],
     /// A root of the Sapling note commitment tree at some block height in the past.
-    pub anchor: SaplingNoteTreeRootHash,
+    ///
+    /// Spends that use a [`SharedAnchor`] don't have their own anchor.
+    pub anchor: AnchorV::PerSpend,
     /// The nullifier of the input note.
     pub nullifier: crate::nullifier::sapling::Nullifier,
     /// The randomized public key for `spend_auth_sig`.
@@ -54,8 +90,12 @@ pub struct Output {
 impl Eq for Output {}
 
 /// Sapling-on-Groth16 spend and output descriptions.
+///
+/// Only [`PerSpendAnchor`] bundles can currently be serialized, because
+/// Zebra doesn't parse V5 transactions yet.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ShieldedData {
+#[serde(bound = "")]
+pub struct ShieldedData<AnchorV: AnchorVariant = PerSpendAnchor> {
     /// Either a spend or output description.
     ///
     /// Storing this separately ensures that it is impossible to construct
@@ -66,24 +106,29 @@ pub struct ShieldedData {
     /// methods provide iterators over all of the [`Spend`]s and
     /// [`Output`]s.
     #[serde(with = "serde_helpers::Either")]
-    pub first: Either<Spend, Output>,
+    pub first: Either<Spend<AnchorV>, Output>,
     /// The rest of the [`Spend`]s for this transaction.
     ///
     /// Note that the [`ShieldedData::spends`] method provides an iterator
     /// over all spend descriptions.
-    pub rest_spends: Vec<Spend>,
+    pub rest_spends: Vec<Spend<AnchorV>>,
     /// The rest of the [`Output`]s for this transaction.
     ///
     /// Note that the [`ShieldedData::outputs`] method provides an iterator
     /// over all output descriptions.
     pub rest_outputs: Vec<Output>,
+    /// The anchor shared by every spend, for [`SharedAnchor`] bundles.
+    ///
+    /// Bundles that use a [`PerSpendAnchor`] store their anchors in each
+    /// spend instead.
+    pub shared_anchor: AnchorV::Shared,
     /// A signature on the transaction hash.
     pub binding_sig: redjubjub::Signature<Binding>,
 }
 
-impl ShieldedData {
+impl<AnchorV: AnchorVariant> ShieldedData<AnchorV> {
     /// Iterate over the [`Spend`]s for this transaction.
-    pub fn spends(&self) -> impl Iterator<Item = &Spend> {
+    pub fn spends(&self) -> impl Iterator<Item = &Spend<AnchorV>> {
         match self.first {
             Either::Left(ref spend) => Some(spend),
             Either::Right(_) => None,
@@ -103,12 +148,28 @@ impl ShieldedData {
     }
 }
 
+impl ShieldedData<PerSpendAnchor> {
+    /// Iterate over the anchors of each [`Spend`] in this bundle.
+    pub fn anchors(&self) -> impl Iterator<Item = &SaplingNoteTreeRootHash> {
+        self.spends().map(|spend| &spend.anchor)
+    }
+}
+
+impl ShieldedData<SharedAnchor> {
+    /// Iterate over the anchor of each [`Spend`] in this bundle, which is
+    /// always the shared anchor.
+    pub fn anchors(&self) -> impl Iterator<Item = &SaplingNoteTreeRootHash> {
+        let shared_anchor = &self.shared_anchor;
+        self.spends().map(move |_| shared_anchor)
+    }
+}
+
 // Technically, it's possible to construct two equivalent representations
 // of a ShieldedData with at least one spend and at least one output, depending
 // on which goes in the `first` slot.  This is annoying but a smallish price to
 // pay for structural validity.
 
-impl std::cmp::PartialEq for ShieldedData {
+impl<AnchorV: AnchorVariant + PartialEq> std::cmp::PartialEq for ShieldedData<AnchorV> {
     fn eq(&self, other: &Self) -> bool {
         // First check that the lengths match, so we know it is safe to use zip,
         // which truncates to the shorter of the two iterators.
@@ -119,11 +180,12 @@ impl std::cmp::PartialEq for ShieldedData {
             return false;
         }
 
-        // Now check that the binding_sig, spends, outputs match.
-        self.binding_sig == other.binding_sig
+        // Now check that the shared anchor, binding_sig, spends, outputs match.
+        self.shared_anchor == other.shared_anchor
+            && self.binding_sig == other.binding_sig
             && self.spends().zip(other.spends()).all(|(a, b)| a == b)
             && self.outputs().zip(other.outputs()).all(|(a, b)| a == b)
     }
 }
 
-impl std::cmp::Eq for ShieldedData {}
+impl<AnchorV: AnchorVariant + Eq> std::cmp::Eq for ShieldedData<AnchorV> {}
@@ -0,0 +1,258 @@
+//! Signature hashes for transparent inputs, from [ZIP-243].
+//!
+//! [ZIP-243]: https://zips.z.cash/zip-0243
+
+use std::io::{self, Write};
+
+use blake2b_simd::{Params, State};
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{
+    parameters::ConsensusBranchId,
+    serialization::{CompactSize, ZcashSerialize},
+    types::{
+        amount::{Amount, NonNegative},
+        Script,
+    },
+};
+
+use super::{serialize::SAPLING_VERSION_GROUP_ID, Transaction, TransparentInput};
+
+/// The parts of a transaction that a transparent signature commits to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HashType(pub u32);
+
+impl HashType {
+    /// Sign all the inputs and outputs.
+    pub const ALL: HashType = HashType(1);
+    /// Sign all the inputs, and none of the outputs.
+    pub const NONE: HashType = HashType(2);
+    /// Sign all the inputs, and the output with the same index as the signed
+    /// input.
+    pub const SINGLE: HashType = HashType(3);
+
+    /// The flag that restricts a signature to the signed input.
+    const ANYONECANPAY_FLAG: u32 = 0x80;
+
+    /// Returns this hash type, modified so that it only signs the signed
+    /// input.
+    pub fn anyone_can_pay(self) -> HashType {
+        HashType(self.0 | Self::ANYONECANPAY_FLAG)
+    }
+
+    fn is_anyone_can_pay(self) -> bool {
+        self.0 & Self::ANYONECANPAY_FLAG != 0
+    }
+
+    fn is_none(self) -> bool {
+        self.0 & 0x1f == Self::NONE.0
+    }
+
+    fn is_single(self) -> bool {
+        self.0 & 0x1f == Self::SINGLE.0
+    }
+}
+
+impl Transaction {
+    /// Returns the signature hash for this transaction, under the consensus
+    /// rules of `consensus_branch_id`.
+    ///
+    /// If `input` is `Some((index, script_code, value))`, returns the hash for
+    /// signing the transparent input at `index`, which spends an output with
+    /// `value` and `script_code`. Otherwise, returns the hash for signing the
+    /// shielded parts of the transaction.
+    ///
+    /// Returns `None` for pre-Sapling transactions, because their signature
+    /// hashes are not implemented yet.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not the index of a transparent input.
+    pub fn sighash(
+        &self,
+        consensus_branch_id: ConsensusBranchId,
+        hash_type: HashType,
+        input: Option<(usize, &Script, Amount<NonNegative>)>,
+    ) -> Option<[u8; 32]> {
+        match self {
+            Transaction::V4 { .. } => Some(
+                sighash_v4(self, consensus_branch_id, hash_type, input)
+                    .expect("writing to a hash state is infallible"),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the [ZIP-243] signature hash for a V4 transaction.
+///
+/// [ZIP-243]: https://zips.z.cash/zip-0243
+fn sighash_v4(
+    tx: &Transaction,
+    consensus_branch_id: ConsensusBranchId,
+    hash_type: HashType,
+    input: Option<(usize, &Script, Amount<NonNegative>)>,
+) -> Result<[u8; 32], io::Error> {
+    let (inputs, outputs, lock_time, expiry_height, value_balance, shielded_data, joinsplit_data) =
+        match tx {
+            Transaction::V4 {
+                inputs,
+                outputs,
+                lock_time,
+                expiry_height,
+                value_balance,
+                shielded_data,
+                joinsplit_data,
+            } => (
+                inputs,
+                outputs,
+                lock_time,
+                expiry_height,
+                value_balance,
+                shielded_data,
+                joinsplit_data,
+            ),
+            _ => unreachable!("only called for V4 transactions"),
+        };
+
+    let mut personal = [0; 16];
+    personal[..12].copy_from_slice(b"ZcashSigHash");
+    personal[12..].copy_from_slice(&u32::from(consensus_branch_id).to_le_bytes());
+    let mut hash = Params::new().hash_length(32).personal(&personal).to_state();
+
+    hash.write_u32::<LittleEndian>(4 | (1 << 31))?;
+    hash.write_u32::<LittleEndian>(SAPLING_VERSION_GROUP_ID)?;
+
+    // hashPrevouts
+    if hash_type.is_anyone_can_pay() {
+        hash.write_all(&[0; 32])?;
+    } else {
+        let mut prevouts = personalized(b"ZcashPrevoutHash");
+        for input in inputs.iter() {
+            write_prevout(input, &mut prevouts)?;
+        }
+        hash.write_all(prevouts.finalize().as_bytes())?;
+    }
+
+    // hashSequence
+    if hash_type.is_anyone_can_pay() || hash_type.is_single() || hash_type.is_none() {
+        hash.write_all(&[0; 32])?;
+    } else {
+        let mut sequences = personalized(b"ZcashSequencHash");
+        for input in inputs.iter() {
+            sequences.write_u32::<LittleEndian>(sequence(input))?;
+        }
+        hash.write_all(sequences.finalize().as_bytes())?;
+    }
+
+    // hashOutputs
+    let single_output = input
+        .filter(|_| hash_type.is_single())
+        .and_then(|(index, _, _)| outputs.get(index));
+    if !hash_type.is_single() && !hash_type.is_none() {
+        let mut all_outputs = personalized(b"ZcashOutputsHash");
+        for output in outputs.iter() {
+            output.zcash_serialize(&mut all_outputs)?;
+        }
+        hash.write_all(all_outputs.finalize().as_bytes())?;
+    } else if let Some(output) = single_output {
+        let mut hash_outputs = personalized(b"ZcashOutputsHash");
+        output.zcash_serialize(&mut hash_outputs)?;
+        hash.write_all(hash_outputs.finalize().as_bytes())?;
+    } else {
+        hash.write_all(&[0; 32])?;
+    }
+
+    // hashJoinSplits
+    match joinsplit_data {
+        Some(joinsplit_data) => {
+            let mut joinsplits = personalized(b"ZcashJSplitsHash");
+            for joinsplit in joinsplit_data.joinsplits() {
+                joinsplit.zcash_serialize(&mut joinsplits)?;
+            }
+            joinsplits.write_all(&<[u8; 32]>::from(joinsplit_data.pub_key)[..])?;
+            hash.write_all(joinsplits.finalize().as_bytes())?;
+        }
+        None => hash.write_all(&[0; 32])?,
+    }
+
+    // hashShieldedSpends
+    match shielded_data
+        .as_ref()
+        .filter(|data| data.spends().count() > 0)
+    {
+        Some(shielded_data) => {
+            let mut spends = personalized(b"ZcashSSpendsHash");
+            for spend in shielded_data.spends() {
+                // Spend descriptions without their spend authorization
+                // signatures
+                spends.write_all(&spend.cv[..])?;
+                spends.write_all(&spend.anchor.0[..])?;
+                spend.nullifier.zcash_serialize(&mut spends)?;
+                spends.write_all(&<[u8; 32]>::from(spend.rk)[..])?;
+                spend.zkproof.zcash_serialize(&mut spends)?;
+            }
+            hash.write_all(spends.finalize().as_bytes())?;
+        }
+        None => hash.write_all(&[0; 32])?,
+    }
+
+    // hashShieldedOutputs
+    match shielded_data
+        .as_ref()
+        .filter(|data| data.outputs().count() > 0)
+    {
+        Some(shielded_data) => {
+            let mut shielded_outputs = personalized(b"ZcashSOutputHash");
+            for output in shielded_data.outputs() {
+                output.zcash_serialize(&mut shielded_outputs)?;
+            }
+            hash.write_all(shielded_outputs.finalize().as_bytes())?;
+        }
+        None => hash.write_all(&[0; 32])?,
+    }
+
+    lock_time.zcash_serialize(&mut hash)?;
+    hash.write_u32::<LittleEndian>(expiry_height.0)?;
+    hash.write_i64::<LittleEndian>((*value_balance).into())?;
+    hash.write_u32::<LittleEndian>(hash_type.0)?;
+
+    if let Some((index, script_code, value)) = input {
+        let input = &inputs[index];
+        write_prevout(input, &mut hash)?;
+        CompactSize::from_len(script_code.0.len())?.zcash_serialize(&mut hash)?;
+        hash.write_all(&script_code.0[..])?;
+        hash.write_i64::<LittleEndian>(value.into())?;
+        hash.write_u32::<LittleEndian>(sequence(input))?;
+    }
+
+    let mut sighash = [0; 32];
+    sighash.copy_from_slice(hash.finalize().as_bytes());
+    Ok(sighash)
+}
+
+/// Returns a new BLAKE2b-256 hash state with `personal`ization.
+fn personalized(personal: &[u8; 16]) -> State {
+    Params::new().hash_length(32).personal(personal).to_state()
+}
+
+/// Write the outpoint spent by `input` to `writer`.
+///
+/// Coinbase inputs spend the null outpoint.
+fn write_prevout<W: io::Write>(input: &TransparentInput, mut writer: W) -> Result<(), io::Error> {
+    match input {
+        TransparentInput::PrevOut { outpoint, .. } => outpoint.zcash_serialize(&mut writer),
+        TransparentInput::Coinbase { .. } => {
+            writer.write_all(&[0; 32])?;
+            writer.write_u32::<LittleEndian>(0xffff_ffff)
+        }
+    }
+}
+
+/// Returns the sequence number of `input`.
+fn sequence(input: &TransparentInput) -> u32 {
+    match input {
+        TransparentInput::PrevOut { sequence, .. } => *sequence,
+        TransparentInput::Coinbase { sequence, .. } => *sequence,
+    }
+}
@@ -1,8 +1,14 @@
 use proptest::{arbitrary::any, collection::vec, option, prelude::*};
 
+use std::convert::TryInto;
+
+use futures::future::Either;
+
 use crate::{
+    note_commitment_tree::SaplingNoteTreeRootHash,
+    parameters::ConsensusBranchId,
     serialization::{ZcashDeserialize, ZcashDeserializeInto, ZcashSerialize},
-    types::LockTime,
+    types::{amount::MAX_MONEY, LockTime, Script},
 };
 
 use super::*;
@@ -115,6 +121,191 @@ proptest! {
 
         prop_assert_eq![tx, tx2];
     }
+
+    #[test]
+    fn overwintered_fields_are_serialized_in_place(
+        tx in prop_oneof![Transaction::v3_strategy(), Transaction::v4_strategy()]
+    ) {
+        let data = tx.zcash_serialize_to_vec().expect("tx should serialize");
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().expect("slice is 4 bytes"))
+        };
+
+        // The header has the fOverwintered bit and version, then the version group ID
+        prop_assert_eq!(read_u32(0), tx.version() | (1 << 31));
+        prop_assert_eq!(Some(read_u32(4)), tx.version_group_id());
+
+        // nExpiryHeight and valueBalance follow the transparent fields
+        let transparent_len = tx.inputs().cloned().collect::<Vec<_>>().zcash_serialize_to_vec()
+            .expect("inputs should serialize")
+            .len()
+            + tx.outputs().cloned().collect::<Vec<_>>().zcash_serialize_to_vec()
+                .expect("outputs should serialize")
+                .len()
+            + 4;
+        let expiry_offset = 8 + transparent_len;
+        prop_assert_eq!(Some(BlockHeight(read_u32(expiry_offset))), tx.expiry_height());
+        if let Some(value_balance) = tx.sapling_value_balance() {
+            let value_offset = expiry_offset + 4;
+            let bytes = data[value_offset..value_offset + 8].try_into().expect("slice is 8 bytes");
+            prop_assert_eq!(i64::from_le_bytes(bytes), i64::from(value_balance));
+        }
+
+        // bindingSig is last, then joinSplitPubKey and joinSplitSig
+        let mut end = data.len();
+        if let Some(shielded_data) = tx.shielded_data() {
+            prop_assert_eq!(&data[end - 64..], &<[u8; 64]>::from(shielded_data.binding_sig)[..]);
+            end -= 64;
+        }
+        let joinsplit_keys = tx
+            .bctv14_joinsplit_data()
+            .map(|jsd| (jsd.pub_key, jsd.sig))
+            .or_else(|| tx.groth16_joinsplit_data().map(|jsd| (jsd.pub_key, jsd.sig)));
+        if let Some((pub_key, sig)) = joinsplit_keys {
+            prop_assert_eq!(&data[end - 96..end - 64], &<[u8; 32]>::from(pub_key)[..]);
+            prop_assert_eq!(&data[end - 64..end], &<[u8; 64]>::from(sig)[..]);
+        }
+    }
+
+    #[test]
+    fn joinsplit_sizes_depend_on_proof_system(
+        bctv14 in any::<JoinSplit<Bctv14Proof>>(),
+        groth16 in any::<JoinSplit<Groth16Proof>>(),
+    ) {
+        // The JoinSplit description sizes from the spec
+        let data = bctv14.zcash_serialize_to_vec().expect("joinsplit should serialize");
+        prop_assert_eq!(data.len(), 1802);
+        prop_assert_eq!(data.zcash_deserialize_into::<JoinSplit<Bctv14Proof>>().expect("joinsplit should deserialize"), bctv14);
+
+        let data = groth16.zcash_serialize_to_vec().expect("joinsplit should serialize");
+        prop_assert_eq!(data.len(), 1698);
+        prop_assert_eq!(data.zcash_deserialize_into::<JoinSplit<Groth16Proof>>().expect("joinsplit should deserialize"), groth16);
+    }
+
+    #[test]
+    fn conventional_fee_pays_for_all_actions(tx in any::<Transaction>()) {
+        let fee = tx.conventional_fee();
+
+        prop_assert!(u64::from(fee) >= MARGINAL_FEE * GRACE_ACTIONS);
+        prop_assert_eq!(tx.unpaid_actions(fee), 0);
+        prop_assert!(tx.fee_weight_ratio(fee) >= 1.0);
+    }
+
+    #[test]
+    fn shielded_values_net_to_value_balances(tx in any::<Transaction>()) {
+        let sprout_balance: i64 = tx
+            .bctv14_joinsplits()
+            .map(|js| i64::from(js.vpub_new) - i64::from(js.vpub_old))
+            .chain(
+                tx.groth16_joinsplits()
+                    .map(|js| i64::from(js.vpub_new) - i64::from(js.vpub_old)),
+            )
+            .sum();
+        let sapling_balance = tx.sapling_value_balance().map(i64::from).unwrap_or(0);
+
+        // Random transactions can have more than MAX_MONEY in each pool
+        if let (Ok(value_in), Ok(value_out)) = (tx.shielded_value_in(), tx.shielded_value_out()) {
+            prop_assert_eq!(
+                i64::from(value_in) - i64::from(value_out),
+                sprout_balance + sapling_balance
+            );
+        }
+    }
+}
+
+#[test]
+fn conventional_fee() {
+    let mut pk_script = vec![0x76, 0xa9, 20];
+    pk_script.extend_from_slice(&[0; 20]);
+    pk_script.extend_from_slice(&[0x88, 0xac]);
+
+    let tx = Transaction::V1 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint: OutPoint {
+                hash: TransactionHash([0; 32]),
+                index: 0,
+            },
+            script: Script(vec![]),
+            sequence: u32::MAX,
+        }],
+        outputs: vec![TransparentOutput {
+            value: 1u64.try_into().unwrap(),
+            pk_script: Script(pk_script),
+        }],
+        lock_time: LockTime::Height(BlockHeight(0)),
+    };
+
+    // One input and one standard output is one logical action, which is less
+    // than the grace actions
+    assert_eq!(tx.logical_actions(), 1);
+    assert_eq!(u64::from(tx.conventional_fee()), 2 * MARGINAL_FEE);
+    assert!(!tx.is_coinbase());
+    assert_eq!(tx.spent_outpoints().count(), 1);
+    assert_eq!(u64::from(tx.transparent_value_out().unwrap()), 1);
+
+    assert_eq!(tx.unpaid_actions(MARGINAL_FEE.try_into().unwrap()), 1);
+    assert_eq!(tx.unpaid_actions(0u64.try_into().unwrap()), 2);
+    assert_eq!(tx.fee_weight_ratio(MARGINAL_FEE.try_into().unwrap()), 0.5);
+    assert_eq!(
+        tx.fee_weight_ratio((MAX_MONEY as u64).try_into().unwrap()),
+        WEIGHT_RATIO_CAP
+    );
+}
+
+#[test]
+fn coinbase_script_sig() {
+    let block =
+        crate::block::Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])
+            .expect("block test vector should deserialize");
+    let input = block.transactions[0]
+        .inputs()
+        .next()
+        .expect("coinbase transactions have an input");
+
+    // Height 1 is encoded as OP_1, and the miner data is a single zero byte
+    assert_eq!(input.script_sig(), Script(vec![0x51, 0x00]));
+    assert_eq!(input.sequence(), u32::MAX);
+    assert!(block.transactions[0].is_coinbase());
+    assert_eq!(block.transactions[0].spent_outpoints().count(), 0);
+    assert_eq!(block.transactions[0].version(), 1);
+    assert_eq!(block.transactions[0].version_group_id(), None);
+}
+
+#[test]
+fn expiry_heights_above_maximum_are_rejected() {
+    let mut data = zebra_test::vectors::ZIP143_1.clone();
+    let tx: Transaction = data
+        .zcash_deserialize_into()
+        .expect("transaction test vector from ZIP143 should deserialize");
+
+    // The ZIP 143 vectors have no JoinSplits, so nExpiryHeight is followed
+    // by a single byte
+    let expiry_offset = data.len() - 5;
+    assert_eq!(
+        Some(BlockHeight(u32::from_le_bytes(
+            data[expiry_offset..expiry_offset + 4].try_into().unwrap()
+        ))),
+        tx.expiry_height()
+    );
+
+    data[expiry_offset..expiry_offset + 4].copy_from_slice(&500_000_000u32.to_le_bytes());
+    assert!(data.zcash_deserialize_into::<Transaction>().is_err());
+}
+
+#[test]
+fn oversized_input_counts_are_rejected() {
+    use crate::serialization::{CompactSize, SerializationError, MAX_COMPACT_SIZE};
+    use std::convert::TryFrom;
+
+    // A V1 transaction header, followed by a huge input count
+    let mut data = 1u32.to_le_bytes().to_vec();
+    CompactSize::try_from(MAX_COMPACT_SIZE)
+        .unwrap()
+        .zcash_serialize(&mut data)
+        .unwrap();
+
+    let result = data.zcash_deserialize_into::<Transaction>();
+    assert!(matches!(result, Err(SerializationError::Parse(_))));
 }
 
 #[test]
@@ -167,3 +358,89 @@ fn zip243_deserialize_and_round_trip() {
 
     assert_eq!(&zebra_test::vectors::ZIP243_3[..], &data3[..]);
 }
+
+#[test]
+fn zip243_sighash() {
+    for (i, test) in zebra_test::zip0243::TEST_VECTORS.iter().enumerate() {
+        let tx = Transaction::zcash_deserialize(&test.tx[..])
+            .expect("transaction test vector from ZIP243 should deserialize");
+        let script_code = Script(test.script_code.clone());
+        let input = test.transparent_input.map(|index| {
+            (
+                index as usize,
+                &script_code,
+                test.amount.try_into().expect("amount is valid"),
+            )
+        });
+
+        let sighash = tx
+            .sighash(
+                ConsensusBranchId::new(test.consensus_branch_id),
+                HashType(test.hash_type),
+                input,
+            )
+            .expect("V4 transactions have sighashes");
+
+        assert_eq!(sighash, test.sighash, "test vector {} sighash", i);
+    }
+}
+
+#[test]
+fn zip243_example_sighash() {
+    // Example 3 from ZIP-243, which signs a mainnet transaction
+    let tx = Transaction::zcash_deserialize(&zebra_test::vectors::ZIP243_3[..])
+        .expect("transaction test vector from ZIP243 should deserialize");
+    let script_code = Script(
+        hex::decode("76a914507173527b4c3318a2aecd793bf1cfed705950cf88ac")
+            .expect("script code is valid hex"),
+    );
+    let value = 50_000_000.try_into().expect("value is valid");
+
+    let sighash = tx
+        .sighash(
+            // The Sapling consensus branch ID
+            ConsensusBranchId::new(0x76b8_09bb),
+            HashType::ALL,
+            Some((0, &script_code, value)),
+        )
+        .expect("V4 transactions have sighashes");
+
+    assert_eq!(
+        hex::encode(sighash),
+        "f3148f80dfab5e573d5edfe7a850f5fd39234f80b5429d3a57edcc11e34c585b"
+    );
+}
+
+proptest! {
+    #[test]
+    fn sapling_anchor_variants(per_spend in any::<ShieldedData>(), shared_anchor in any::<SaplingNoteTreeRootHash>()) {
+        let anchors: Vec<_> = per_spend.spends().map(|spend| spend.anchor).collect();
+        let output_count = per_spend.outputs().count();
+        prop_assert_eq!(per_spend.anchors().cloned().collect::<Vec<_>>(), anchors.clone());
+
+        // Moving the anchors out of the spends doesn't change the descriptions
+        let strip_anchor = |spend: Spend| Spend::<SharedAnchor> {
+            cv: spend.cv,
+            anchor: (),
+            nullifier: spend.nullifier,
+            rk: spend.rk,
+            zkproof: spend.zkproof,
+            spend_auth_sig: spend.spend_auth_sig,
+        };
+        let first = match per_spend.first.clone() {
+            Either::Left(spend) => Either::Left(strip_anchor(spend)),
+            Either::Right(output) => Either::Right(output),
+        };
+        let shared = ShieldedData::<SharedAnchor> {
+            first,
+            rest_spends: per_spend.rest_spends.into_iter().map(strip_anchor).collect(),
+            rest_outputs: per_spend.rest_outputs,
+            shared_anchor,
+            binding_sig: per_spend.binding_sig,
+        };
+
+        prop_assert_eq!(shared.spends().count(), anchors.len());
+        prop_assert_eq!(shared.outputs().count(), output_count);
+        prop_assert!(shared.anchors().all(|anchor| *anchor == shared_anchor));
+    }
+}
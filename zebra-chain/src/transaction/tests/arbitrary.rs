@@ -1,7 +1,8 @@
 use crate::{
-    note_commitment_tree::SaplingNoteTreeRootHash,
+    note_commitment_tree::{SaplingNoteTreeRootHash, SproutNoteTreeRootHash},
     notes::{sapling, sprout},
     proofs::{Groth16Proof, ZkSnarkProof},
+    serialization::AtLeastOne,
     transaction::{
         CoinbaseData, JoinSplit, JoinSplitData, OutPoint, Output, ShieldedData, Spend, Transaction,
         TransparentInput,
@@ -21,9 +22,9 @@ impl<P: ZkSnarkProof + Arbitrary + 'static> Arbitrary for JoinSplit<P> {
         (
             any::<Amount<NonNegative>>(),
             any::<Amount<NonNegative>>(),
-            array::uniform32(any::<u8>()),
+            any::<SproutNoteTreeRootHash>(),
             array::uniform2(any::<crate::nullifier::sprout::Nullifier>()),
-            array::uniform2(array::uniform32(any::<u8>())),
+            array::uniform2(any::<sprout::NoteCommitment>()),
             array::uniform32(any::<u8>()),
             array::uniform32(any::<u8>()),
             array::uniform2(any::<crate::types::MAC>()),
@@ -68,14 +69,12 @@ impl<P: ZkSnarkProof + Arbitrary + 'static> Arbitrary for JoinSplitData<P> {
 
     fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
         (
-            any::<JoinSplit<P>>(),
-            vec(any::<JoinSplit<P>>(), 0..10),
+            any::<AtLeastOne<JoinSplit<P>>>(),
             array::uniform32(any::<u8>()),
             vec(any::<u8>(), 64),
         )
-            .prop_map(|(first, rest, pub_key_bytes, sig_bytes)| Self {
-                first,
-                rest,
+            .prop_map(|(joinsplits, pub_key_bytes, sig_bytes)| Self {
+                joinsplits,
                 pub_key: ed25519_zebra::VerificationKeyBytes::from(pub_key_bytes),
                 sig: ed25519_zebra::Signature::from({
                     let mut b = [0u8; 64];
@@ -136,6 +135,7 @@ impl Arbitrary for ShieldedData {
                 first,
                 rest_spends,
                 rest_outputs,
+                shared_anchor: (),
                 binding_sig: redjubjub::Signature::from({
                     let mut b = [0u8; 64];
                     b.copy_from_slice(sig_bytes.as_slice());
@@ -4,25 +4,43 @@
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
+use crate::serialization::{ZcashDeserialize, ZcashSerialize};
 use crate::types::{
     amount::{Amount, NonNegative},
     BlockHeight, Script,
 };
 
-use super::TransactionHash;
+use super::{serialize::write_coinbase_height, TransactionHash};
+
+/// The maximum length of the miner data in a coinbase transaction.
+///
+/// Coinbase scripts are limited to 100 bytes, and the coinbase height takes
+/// up to 5 bytes.
+pub const MAX_COINBASE_DATA_LEN: usize = 95;
 
 /// Arbitrary data inserted by miners into a coinbase transaction.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CoinbaseData(
     /// Invariant: this vec, together with the coinbase height, must be less than
     /// 100 bytes. We enforce this by only constructing CoinbaseData fields by
-    /// parsing blocks with 100-byte data fields. When we implement block
-    /// creation, we should provide a constructor for the coinbase data field
-    /// that restricts it to 95 = 100 -1 -4 bytes (safe for any block height up
-    /// to 500_000_000).
+    /// parsing blocks with 100-byte data fields, or by using
+    /// `CoinbaseData::new`, which restricts it to `MAX_COINBASE_DATA_LEN`
+    /// bytes (safe for any block height up to 500_000_000).
     pub(super) Vec<u8>,
 );
 
+impl CoinbaseData {
+    /// Returns coinbase data containing `data`, or `None` if `data` is longer
+    /// than `MAX_COINBASE_DATA_LEN`.
+    pub fn new(data: Vec<u8>) -> Option<CoinbaseData> {
+        if data.len() <= MAX_COINBASE_DATA_LEN {
+            Some(CoinbaseData(data))
+        } else {
+            None
+        }
+    }
+}
+
 impl AsRef<[u8]> for CoinbaseData {
     fn as_ref(&self) -> &[u8] {
         self.0.as_ref()
@@ -32,7 +50,18 @@ impl AsRef<[u8]> for CoinbaseData {
 /// OutPoint
 ///
 /// A particular transaction output reference.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Eq,
+    Hash,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    ZcashSerialize,
+    ZcashDeserialize,
+)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct OutPoint {
     /// References the transaction that contains the UTXO being spent.
@@ -66,6 +95,32 @@ pub enum TransparentInput {
     },
 }
 
+impl TransparentInput {
+    /// Returns the script of this input, as it is serialized.
+    ///
+    /// Coinbase scripts start with the block height, followed by the miner
+    /// data.
+    pub fn script_sig(&self) -> Script {
+        match self {
+            TransparentInput::PrevOut { script, .. } => script.clone(),
+            TransparentInput::Coinbase { height, data, .. } => {
+                let mut bytes = Vec::new();
+                write_coinbase_height(*height, &mut bytes).expect("writing to a vec can't fail");
+                bytes.extend_from_slice(data.as_ref());
+                Script(bytes)
+            }
+        }
+    }
+
+    /// Returns the sequence number of this input.
+    pub fn sequence(&self) -> u32 {
+        match self {
+            TransparentInput::PrevOut { sequence, .. } => *sequence,
+            TransparentInput::Coinbase { sequence, .. } => *sequence,
+        }
+    }
+}
+
 /// A transparent output from a transaction.
 ///
 /// The most fundamental building block of a transaction is a
@@ -1,16 +1,19 @@
 //! Newtype wrappers for primitive data types with semantic meaning.
 #![allow(clippy::unit_arg)]
-use crate::serialization::{
-    ReadZcashExt, SerializationError, WriteZcashExt, ZcashDeserialize, ZcashSerialize,
-};
+use crate::serialization::{SerializationError, ZcashDeserialize, ZcashSerialize};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use chrono::{DateTime, TimeZone, Utc};
 use std::{
-    fmt,
-    io::{self, Read},
+    convert::TryFrom,
+    fmt, io,
+    ops::{Add, Sub},
 };
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::{parameters::TESTNET_MINIMUM_DIFFICULTY_START_HEIGHT, Network};
 
 pub mod amount;
+pub mod script;
 
 /// A u32 which represents a block height value.
 ///
@@ -20,6 +23,12 @@ pub mod amount;
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct BlockHeight(pub u32);
 
+impl fmt::Display for BlockHeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl std::str::FromStr for BlockHeight {
     type Err = SerializationError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -35,6 +44,14 @@ impl std::str::FromStr for BlockHeight {
     }
 }
 
+impl TryFrom<&str> for BlockHeight {
+    type Error = SerializationError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
 #[cfg(test)]
 impl Arbitrary for BlockHeight {
     type Parameters = ();
@@ -87,6 +104,71 @@ impl BlockHeight {
     /// `BlockHeight::MAX.0` can't be used in match range patterns, use this
     /// alias instead.
     pub const MAX_AS_U32: u32 = Self::MAX.0;
+
+    /// Returns the next height, or `None` if this is `BlockHeight::MAX`.
+    pub fn next(self) -> Option<BlockHeight> {
+        self + 1
+    }
+
+    /// Returns the previous height, or `None` if this is the genesis height.
+    pub fn previous(self) -> Option<BlockHeight> {
+        self - 1
+    }
+
+    /// Returns true if blocks at this height on `network` can use the
+    /// minimum difficulty, when they are far enough apart.
+    ///
+    /// Minimum difficulty blocks are only allowed on Testnet, after
+    /// `TESTNET_MINIMUM_DIFFICULTY_START_HEIGHT`.
+    pub fn is_min_difficulty_eligible(self, network: Network) -> bool {
+        match network {
+            Network::Mainnet => false,
+            Network::Testnet => self >= TESTNET_MINIMUM_DIFFICULTY_START_HEIGHT,
+        }
+    }
+}
+
+/// A difference between two block heights.
+///
+/// Height differences can be negative, so they are signed. A `HeightDiff`
+/// can hold the difference between any two valid heights.
+pub type HeightDiff = i64;
+
+impl Add<HeightDiff> for BlockHeight {
+    type Output = Option<BlockHeight>;
+
+    /// Returns the height `rhs` blocks after this height, or `None` if it is
+    /// less than `BlockHeight::MIN` or greater than `BlockHeight::MAX`.
+    fn add(self, rhs: HeightDiff) -> Option<BlockHeight> {
+        let height = HeightDiff::from(self.0).checked_add(rhs)?;
+        let height = BlockHeight(u32::try_from(height).ok()?);
+
+        if height <= BlockHeight::MAX {
+            Some(height)
+        } else {
+            None
+        }
+    }
+}
+
+impl Sub<HeightDiff> for BlockHeight {
+    type Output = Option<BlockHeight>;
+
+    /// Returns the height `rhs` blocks before this height, or `None` if it is
+    /// less than `BlockHeight::MIN` or greater than `BlockHeight::MAX`.
+    fn sub(self, rhs: HeightDiff) -> Option<BlockHeight> {
+        self + rhs.checked_neg()?
+    }
+}
+
+impl Sub<BlockHeight> for BlockHeight {
+    type Output = HeightDiff;
+
+    /// Returns the number of blocks from `rhs` to this height, which is
+    /// negative if `rhs` is higher.
+    fn sub(self, rhs: BlockHeight) -> HeightDiff {
+        HeightDiff::from(self.0) - HeightDiff::from(rhs.0)
+    }
 }
 
 impl LockTime {
@@ -168,27 +250,32 @@ impl Arbitrary for LockTime {
 ///
 /// binding h_sig to each a_sk of the JoinSplit description, computed as
 /// described in § 4.10 ‘Non-malleability (Sprout)’ on p. 37
-#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ZcashSerialize, ZcashDeserialize)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct MAC([u8; 32]);
 
-impl ZcashDeserialize for MAC {
-    fn zcash_deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
-        let bytes = reader.read_32_bytes()?;
+impl ConstantTimeEq for MAC {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
 
-        Ok(Self(bytes))
+impl PartialEq for MAC {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
     }
 }
 
-impl ZcashSerialize for MAC {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_all(&self.0[..])
+impl From<MAC> for [u8; 32] {
+    fn from(mac: MAC) -> Self {
+        mac.0
     }
 }
+
 /// An encoding of a Bitcoin script.
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, ZcashSerialize, ZcashDeserialize)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
-pub struct Script(pub Vec<u8>);
+pub struct Script(#[zcash(bytes)] pub Vec<u8>);
 
 impl fmt::Debug for Script {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -198,24 +285,6 @@ impl fmt::Debug for Script {
     }
 }
 
-impl ZcashSerialize for Script {
-    fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
-        writer.write_compactsize(self.0.len() as u64)?;
-        writer.write_all(&self.0[..])?;
-        Ok(())
-    }
-}
-
-impl ZcashDeserialize for Script {
-    fn zcash_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        // XXX what is the max length of a script?
-        let len = reader.read_compactsize()?;
-        let mut bytes = Vec::new();
-        reader.take(len).read_to_end(&mut bytes)?;
-        Ok(Script(bytes))
-    }
-}
-
 /// A 4-byte checksum using truncated double-SHA256 (two rounds of SHA256).
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct Sha256dChecksum(pub [u8; 4]);
@@ -246,6 +315,35 @@ use proptest::prelude::*;
 mod tests {
     use super::*;
 
+    #[test]
+    fn height_arithmetic() {
+        assert_eq!(BlockHeight(5) + 3, Some(BlockHeight(8)));
+        assert_eq!(BlockHeight(5) - 3, Some(BlockHeight(2)));
+        assert_eq!(BlockHeight(5) + -5, Some(BlockHeight::MIN));
+        assert_eq!(BlockHeight(5) - 6, None);
+        assert_eq!(BlockHeight::MAX + 1, None);
+        assert_eq!(BlockHeight::MIN - HeightDiff::MIN, None);
+        assert_eq!(BlockHeight::MIN.previous(), None);
+        assert_eq!(BlockHeight::MAX.next(), None);
+        assert_eq!(BlockHeight(8) - BlockHeight(5), 3);
+        assert_eq!(BlockHeight(5) - BlockHeight(8), -3);
+        assert_eq!(
+            BlockHeight::MAX - BlockHeight::MIN,
+            HeightDiff::from(BlockHeight::MAX.0)
+        );
+    }
+
+    #[test]
+    fn min_difficulty_eligible() {
+        let start = TESTNET_MINIMUM_DIFFICULTY_START_HEIGHT;
+        let before = start.previous().unwrap();
+
+        assert!(start.is_min_difficulty_eligible(Network::Testnet));
+        assert!(!before.is_min_difficulty_eligible(Network::Testnet));
+        assert!(!start.is_min_difficulty_eligible(Network::Mainnet));
+        assert!(!BlockHeight::MAX.is_min_difficulty_eligible(Network::Mainnet));
+    }
+
     #[test]
     fn sha256d_checksum() {
         // https://en.bitcoin.it/wiki/Protocol_documentation#Hashes
@@ -271,11 +369,18 @@ mod proptests {
 
     use proptest::prelude::*;
 
-    use super::{LockTime, Script};
+    use super::{BlockHeight, HeightDiff, LockTime, Script};
     use crate::serialization::{ZcashDeserialize, ZcashSerialize};
 
     proptest! {
 
+        #[test]
+        fn height_diff_roundtrip(height in any::<BlockHeight>(), other in any::<BlockHeight>()) {
+            let diff: HeightDiff = other - height;
+            prop_assert_eq![height + diff, Some(other)];
+            prop_assert_eq![other - diff, Some(height)];
+        }
+
         #[test]
         fn locktime_roundtrip(locktime in any::<LockTime>()) {
             let mut bytes = Cursor::new(Vec::new());
@@ -1,8 +1,10 @@
 //! Module of types for working with validated zatoshi Amounts
 use std::{
     convert::{TryFrom, TryInto},
+    fmt,
     marker::PhantomData,
     ops::RangeInclusive,
+    str::FromStr,
 };
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -12,6 +14,11 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 pub struct Amount<C = NegativeAllowed>(i64, PhantomData<C>);
 
 impl<C> Amount<C> {
+    /// Returns a zero amount, which is valid for every constraint.
+    pub fn zero() -> Self {
+        Amount(0, PhantomData)
+    }
+
     /// Convert this amount to a different Amount type if it satisfies the new constraint
     pub fn constrain<C2>(self) -> Result<Amount<C2>>
     where
@@ -19,6 +26,77 @@ impl<C> Amount<C> {
     {
         self.0.try_into()
     }
+
+    /// Returns this amount in ZEC, for JSON APIs that use floating-point ZEC
+    /// values.
+    ///
+    /// Every valid amount is less than 2^53 zatoshis, so the result is the
+    /// closest `f64` to the exact ZEC value.
+    pub fn zec(self) -> f64 {
+        self.0 as f64 / COIN as f64
+    }
+}
+
+/// Amounts are displayed in ZEC, with all 8 decimal places.
+impl<C> fmt::Display for Amount<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+
+        write!(
+            f,
+            "{}{}.{:08}",
+            sign,
+            (self.0 / COIN).abs(),
+            (self.0 % COIN).abs()
+        )
+    }
+}
+
+/// Parses a decimal ZEC amount, like `1.5` or `-0.00000001`.
+///
+/// Amounts must have at most 8 decimal places. Exponents, whitespace, and
+/// leading `+` signs are rejected.
+impl<C> FromStr for Amount<C>
+where
+    C: AmountConstraint,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_error = || Error::Parse {
+            input: s.to_string(),
+        };
+
+        let (negative, digits) = if s.starts_with('-') {
+            (true, &s[1..])
+        } else {
+            (false, s)
+        };
+        let (whole, fraction) = match digits.find('.') {
+            Some(point) => (&digits[..point], &digits[point + 1..]),
+            None => (digits, ""),
+        };
+        let is_decimal = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty()
+            || fraction.len() > 8
+            || (digits.contains('.') && fraction.is_empty())
+            || !is_decimal(whole)
+            || !is_decimal(fraction)
+        {
+            return Err(parse_error());
+        }
+
+        let whole: i64 = whole.parse().map_err(|_| parse_error())?;
+        let fraction: i64 = format!("{:0<8}", fraction)
+            .parse()
+            .map_err(|_| parse_error())?;
+        let zatoshis = whole
+            .checked_mul(COIN)
+            .and_then(|zatoshis| zatoshis.checked_add(fraction))
+            .ok_or_else(parse_error)?;
+
+        if negative { -zatoshis } else { zatoshis }.try_into()
+    }
 }
 
 impl<C> std::ops::Add<Amount<C>> for Amount<C>
@@ -176,6 +254,8 @@ pub enum Error {
         value: u64,
         source: std::num::TryFromIntError,
     },
+    /// {input:?} is not a valid ZEC amount
+    Parse { input: String },
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -198,8 +278,11 @@ impl AmountConstraint for NonNegative {
     }
 }
 
+/// The number of zatoshis in one ZEC.
+pub const COIN: i64 = 100_000_000;
+
 /// The max amount of money that can be obtained in zatoshis
-pub const MAX_MONEY: i64 = 21_000_000 * 100_000_000;
+pub const MAX_MONEY: i64 = 21_000_000 * COIN;
 
 /// A trait for defining constraints on `Amount`
 pub trait AmountConstraint {
@@ -384,4 +467,79 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn display_zec() -> Result<()> {
+        let cases: &[(i64, &str)] = &[
+            (0, "0.00000000"),
+            (1, "0.00000001"),
+            (-1, "-0.00000001"),
+            (150_000_000, "1.50000000"),
+            (-COIN, "-1.00000000"),
+            (MAX_MONEY, "21000000.00000000"),
+        ];
+
+        for (zatoshis, zec) in cases {
+            let amount = Amount::<NegativeAllowed>::try_from(*zatoshis)?;
+            assert_eq!(amount.to_string(), *zec);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_zec() -> Result<()> {
+        let cases: &[(&str, i64)] = &[
+            ("0", 0),
+            ("1", COIN),
+            ("1.5", 150_000_000),
+            ("0.00000001", 1),
+            ("-0.00000001", -1),
+            ("21000000.00000000", MAX_MONEY),
+        ];
+
+        for (zec, zatoshis) in cases {
+            let amount: Amount = zec.parse()?;
+            assert_eq!(i64::from(amount), *zatoshis, "{}", zec);
+        }
+
+        for invalid in &[
+            "",
+            "-",
+            ".5",
+            "1.",
+            "+1",
+            " 1",
+            "1e8",
+            "0.000000001",
+            "1.-5",
+            "--1",
+            "99999999999999999999",
+        ] {
+            assert!(
+                matches!(invalid.parse::<Amount>(), Err(Error::Parse { .. })),
+                "{:?}",
+                invalid
+            );
+        }
+
+        assert!(matches!(
+            "21000000.00000001".parse::<Amount>(),
+            Err(Error::Contains { .. })
+        ));
+        assert!(matches!(
+            "-1".parse::<Amount<NonNegative>>(),
+            Err(Error::Contains { .. })
+        ));
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn display_parse_round_trip(amount in any::<Amount>()) {
+            let parsed: Amount = amount.to_string().parse().expect("display output parses");
+            prop_assert_eq!(parsed, amount);
+        }
+    }
 }
@@ -0,0 +1,330 @@
+//! Classifying transparent output scripts into standard templates.
+//!
+//! The templates match the `Solver` in zcashd's `script/standard.cpp`. Bare
+//! multisig scripts are classified as non-standard.
+
+use super::Script;
+use crate::{addresses::transparent::TransparentAddress, Network};
+
+/// Script opcodes used by the standard templates and signature operation
+/// counts.
+mod opcodes {
+    pub const OP_PUSHDATA1: u8 = 0x4c;
+    pub const OP_PUSHDATA2: u8 = 0x4d;
+    pub const OP_PUSHDATA4: u8 = 0x4e;
+    pub const OP_16: u8 = 0x60;
+    pub const OP_RETURN: u8 = 0x6a;
+    pub const OP_DUP: u8 = 0x76;
+    pub const OP_EQUAL: u8 = 0x87;
+    pub const OP_EQUALVERIFY: u8 = 0x88;
+    pub const OP_HASH160: u8 = 0xa9;
+    pub const OP_CHECKSIG: u8 = 0xac;
+    pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+    pub const OP_CHECKMULTISIG: u8 = 0xae;
+    pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+}
+
+/// The number of signature operations counted for each multisig opcode.
+const MAX_PUBKEYS_PER_MULTISIG: u32 = 20;
+
+use opcodes::*;
+
+/// The standard template matched by a transparent output script.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScriptKind {
+    /// `OP_DUP OP_HASH160 <pub_key_hash> OP_EQUALVERIFY OP_CHECKSIG`
+    PayToPublicKeyHash {
+        /// A RIPEMD-160 hash of a SHA-256 hash of a public key.
+        pub_key_hash: [u8; 20],
+    },
+    /// `OP_HASH160 <script_hash> OP_EQUAL`
+    PayToScriptHash {
+        /// A RIPEMD-160 hash of a SHA-256 hash of the redeem script.
+        script_hash: [u8; 20],
+    },
+    /// `<pub_key> OP_CHECKSIG`
+    PayToPublicKey {
+        /// A 33-byte compressed or 65-byte uncompressed public key encoding.
+        ///
+        /// The encoding is not checked to be a valid curve point.
+        pub_key: Vec<u8>,
+    },
+    /// `OP_RETURN` followed by data pushes, which makes the output
+    /// unspendable.
+    ///
+    /// The size of the data is not checked here. zcashd's standardness
+    /// policy limits it separately.
+    NullData,
+    /// Any other script.
+    NonStandard,
+}
+
+impl Script {
+    /// Returns the standard template matched by this script.
+    pub fn kind(&self) -> ScriptKind {
+        let script = &self.0[..];
+
+        match script {
+            [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG]
+                if hash.len() == 20 =>
+            {
+                let mut pub_key_hash = [0; 20];
+                pub_key_hash.copy_from_slice(hash);
+                ScriptKind::PayToPublicKeyHash { pub_key_hash }
+            }
+            [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20 => {
+                let mut script_hash = [0; 20];
+                script_hash.copy_from_slice(hash);
+                ScriptKind::PayToScriptHash { script_hash }
+            }
+            [len, pub_key @ .., OP_CHECKSIG]
+                if *len as usize == pub_key.len() && is_valid_pub_key_size(pub_key) =>
+            {
+                ScriptKind::PayToPublicKey {
+                    pub_key: pub_key.to_vec(),
+                }
+            }
+            [OP_RETURN, data @ ..] if is_push_only(data) => ScriptKind::NullData,
+            _ => ScriptKind::NonStandard,
+        }
+    }
+
+    /// Returns the transparent address paid by this script on `network`, if
+    /// it has one.
+    ///
+    /// Like zcashd, pay-to-public-key scripts return the pay-to-public-key-hash
+    /// address for their public key.
+    pub fn address(&self, network: Network) -> Option<TransparentAddress> {
+        match self.kind() {
+            ScriptKind::PayToPublicKeyHash { pub_key_hash } => {
+                Some(TransparentAddress::PayToPublicKeyHash {
+                    network,
+                    pub_key_hash,
+                })
+            }
+            ScriptKind::PayToScriptHash { script_hash } => {
+                Some(TransparentAddress::PayToScriptHash {
+                    network,
+                    script_hash,
+                })
+            }
+            ScriptKind::PayToPublicKey { pub_key } => {
+                Some(TransparentAddress::PayToPublicKeyHash {
+                    network,
+                    pub_key_hash: TransparentAddress::hash_payload(&pub_key),
+                })
+            }
+            ScriptKind::NullData | ScriptKind::NonStandard => None,
+        }
+    }
+
+    /// Returns true if this script is the standard template for an
+    /// unspendable data output.
+    pub fn is_null_data(&self) -> bool {
+        self.kind() == ScriptKind::NullData
+    }
+
+    /// Returns true if this script only contains data pushes.
+    ///
+    /// Standard input scripts must be push-only.
+    pub fn is_push_only(&self) -> bool {
+        is_push_only(&self.0)
+    }
+
+    /// Returns the number of signature operations in this script, counted
+    /// like zcashd's legacy `GetSigOpCount`.
+    ///
+    /// Each multisig opcode counts as the maximum number of public keys.
+    /// Counting stops at the first truncated data push.
+    pub fn legacy_sigop_count(&self) -> u32 {
+        let mut script = &self.0[..];
+        let mut count = 0;
+
+        while let Some((opcode, rest)) = split_opcode(script) {
+            count += match opcode {
+                OP_CHECKSIG | OP_CHECKSIGVERIFY => 1,
+                OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => MAX_PUBKEYS_PER_MULTISIG,
+                _ => 0,
+            };
+            script = rest;
+        }
+
+        count
+    }
+}
+
+/// Returns true if `pub_key` has the size implied by its header byte.
+fn is_valid_pub_key_size(pub_key: &[u8]) -> bool {
+    match pub_key.first() {
+        Some(0x02) | Some(0x03) => pub_key.len() == 33,
+        Some(0x04) | Some(0x06) | Some(0x07) => pub_key.len() == 65,
+        _ => false,
+    }
+}
+
+/// Returns true if `script` only contains complete data push opcodes.
+fn is_push_only(mut script: &[u8]) -> bool {
+    while !script.is_empty() {
+        match split_opcode(script) {
+            // Data pushes, OP_1NEGATE, OP_RESERVED, and OP_1 to OP_16
+            Some((opcode, rest)) if opcode <= OP_16 => script = rest,
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/// Splits the first opcode from `script`, and skips over its push data.
+///
+/// Returns `None` if `script` is empty, or if its first data push is
+/// truncated.
+fn split_opcode(script: &[u8]) -> Option<(u8, &[u8])> {
+    let (&opcode, rest) = script.split_first()?;
+    let (len_bytes, len) = match opcode {
+        0..=0x4b => (0, opcode as usize),
+        OP_PUSHDATA1 => (1, *rest.first()? as usize),
+        OP_PUSHDATA2 if rest.len() >= 2 => (2, u16::from_le_bytes([rest[0], rest[1]]) as usize),
+        OP_PUSHDATA4 if rest.len() >= 4 => (
+            4,
+            u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize,
+        ),
+        OP_PUSHDATA2 | OP_PUSHDATA4 => return None,
+        _ => (0, 0),
+    };
+
+    rest.get(len_bytes + len..).map(|rest| (opcode, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A compressed public key, from the transparent address tests.
+    const PUB_KEY: [u8; 33] = [
+        3, 23, 183, 225, 206, 31, 159, 148, 195, 42, 67, 115, 146, 41, 248, 140, 11, 3, 51, 41,
+        111, 180, 110, 143, 114, 134, 88, 73, 198, 174, 52, 184, 78,
+    ];
+
+    #[test]
+    fn p2pkh() {
+        let mut bytes = vec![OP_DUP, OP_HASH160, 20];
+        bytes.extend_from_slice(&[7; 20]);
+        bytes.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+        let script = Script(bytes);
+
+        assert_eq!(
+            script.kind(),
+            ScriptKind::PayToPublicKeyHash {
+                pub_key_hash: [7; 20]
+            }
+        );
+        assert_eq!(
+            script.address(Network::Testnet),
+            Some(TransparentAddress::PayToPublicKeyHash {
+                network: Network::Testnet,
+                pub_key_hash: [7; 20]
+            })
+        );
+    }
+
+    #[test]
+    fn p2sh() {
+        let mut bytes = vec![OP_HASH160, 20];
+        bytes.extend_from_slice(&[0; 20]);
+        bytes.push(OP_EQUAL);
+        let script = Script(bytes);
+
+        assert_eq!(
+            script.kind(),
+            ScriptKind::PayToScriptHash {
+                script_hash: [0; 20]
+            }
+        );
+        assert_eq!(
+            script.address(Network::Mainnet),
+            Some(TransparentAddress::PayToScriptHash {
+                network: Network::Mainnet,
+                script_hash: [0; 20]
+            })
+        );
+    }
+
+    #[test]
+    fn p2pk() {
+        let mut bytes = vec![33];
+        bytes.extend_from_slice(&PUB_KEY);
+        bytes.push(OP_CHECKSIG);
+        let script = Script(bytes);
+
+        assert_eq!(
+            script.kind(),
+            ScriptKind::PayToPublicKey {
+                pub_key: PUB_KEY.to_vec()
+            }
+        );
+        // The same address as the public key's P2PKH address
+        assert_eq!(
+            script.address(Network::Mainnet).unwrap().to_string(),
+            "t1bmMa1wJDFdbc2TiURQP5BbBz6jHjUBuHq"
+        );
+
+        // The size must match the header byte
+        let mut bytes = vec![33, 0x04];
+        bytes.extend_from_slice(&PUB_KEY[1..]);
+        bytes.push(OP_CHECKSIG);
+        assert_eq!(Script(bytes).kind(), ScriptKind::NonStandard);
+    }
+
+    #[test]
+    fn null_data() {
+        assert!(Script(vec![OP_RETURN]).is_null_data());
+        assert!(Script(vec![OP_RETURN, 3, 1, 2, 3, OP_16]).is_null_data());
+        assert!(Script(vec![OP_RETURN, OP_PUSHDATA1, 2, 1, 2]).is_null_data());
+        assert!(Script(vec![OP_RETURN, OP_PUSHDATA2, 1, 0, 9]).is_null_data());
+
+        // Truncated pushes and non-push opcodes
+        assert!(!Script(vec![OP_RETURN, 3, 1, 2]).is_null_data());
+        assert!(!Script(vec![OP_RETURN, OP_PUSHDATA2, 1]).is_null_data());
+        assert!(!Script(vec![OP_RETURN, OP_DUP]).is_null_data());
+
+        assert_eq!(Script(vec![OP_RETURN]).address(Network::Mainnet), None);
+    }
+
+    #[test]
+    fn non_standard() {
+        assert_eq!(Script(vec![]).kind(), ScriptKind::NonStandard);
+        assert_eq!(Script(vec![OP_CHECKSIG]).kind(), ScriptKind::NonStandard);
+
+        // A P2PKH script with an extra byte
+        let mut bytes = vec![OP_DUP, OP_HASH160, 20];
+        bytes.extend_from_slice(&[7; 21]);
+        bytes.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+        assert_eq!(Script(bytes).kind(), ScriptKind::NonStandard);
+    }
+
+    #[test]
+    fn sigop_count() {
+        let mut p2pkh = vec![OP_DUP, OP_HASH160, 20];
+        p2pkh.extend_from_slice(&[7; 20]);
+        p2pkh.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+        assert_eq!(Script(p2pkh).legacy_sigop_count(), 1);
+
+        assert_eq!(
+            Script(vec![OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CHECKMULTISIG]).legacy_sigop_count(),
+            22
+        );
+
+        // Opcodes inside data pushes are not counted
+        assert_eq!(
+            Script(vec![2, OP_CHECKSIG, OP_CHECKSIG, OP_CHECKSIG]).legacy_sigop_count(),
+            1
+        );
+
+        // Counting stops at truncated pushes
+        assert_eq!(
+            Script(vec![OP_CHECKSIG, OP_PUSHDATA1, 5, OP_CHECKSIG]).legacy_sigop_count(),
+            1
+        );
+    }
+}
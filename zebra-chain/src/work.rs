@@ -0,0 +1,10 @@
+//! Proof of work difficulty and chain work.
+//!
+//! Zcash chains are compared using their cumulative work, rather than their
+//! height. The state doesn't store side-chains yet, so it doesn't use these
+//! types for fork choice. `zebra_state::chain::Chain` uses them to order
+//! chains of non-finalized blocks.
+
+pub mod difficulty;
+
+pub use difficulty::*;
@@ -0,0 +1,461 @@
+//! Block difficulty thresholds, and the work represented by each block.
+//!
+//! The difficulty threshold of each block is encoded in its header using the
+//! Bitcoin `nBits` format. Expanding the threshold gives the 256-bit target
+//! that the block's hash must be less than or equal to.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+use primitive_types::U256;
+
+use crate::{
+    block::{BlockHeader, BlockHeaderHash},
+    Network,
+};
+
+/// A difficulty threshold in the compact `nBits` format used by block
+/// headers.
+///
+/// The top byte is a base-256 exponent, and the lower 23 bits are the
+/// mantissa. Bit 23 is a sign bit, which must not be set in valid
+/// thresholds.
+///
+/// [Bitcoin-nBits](https://bitcoin.org/en/developer-reference#target-nbits)
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CompactDifficulty(pub u32);
+
+impl fmt::Debug for CompactDifficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("CompactDifficulty")
+            .field(&format_args!("{:#010x}", self.0))
+            .finish()
+    }
+}
+
+/// A 256-bit difficulty threshold, expanded from a `CompactDifficulty`.
+///
+/// Block hashes are interpreted as little-endian 256-bit integers, and
+/// compared with this threshold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ExpandedDifficulty(U256);
+
+/// The work represented by a single block.
+///
+/// This is the expected number of hashes needed to find a block hash that
+/// is less than or equal to the block's difficulty threshold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Work(u128);
+
+/// The sum of the work of a range of blocks.
+///
+/// Non-finalized chains share their finalized ancestors, so they only
+/// need to compare the work of their non-finalized blocks.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct PartialCumulativeWork(u128);
+
+impl CompactDifficulty {
+    /// The sign bit of the mantissa.
+    const SIGN_BIT: u32 = 0x0080_0000;
+
+    /// The mask for the unsigned mantissa.
+    const UNSIGNED_MANTISSA_MASK: u32 = 0x007f_ffff;
+
+    /// The exponent of a mantissa that is not shifted.
+    const OFFSET: u32 = 3;
+
+    /// Returns the expanded threshold for this compact threshold.
+    ///
+    /// Returns `None` if the threshold is negative, zero, or larger than 256
+    /// bits. Valid blocks never have these thresholds.
+    pub fn to_expanded(&self) -> Option<ExpandedDifficulty> {
+        let exponent = self.0 >> 24;
+        let mantissa = self.0 & CompactDifficulty::UNSIGNED_MANTISSA_MASK;
+
+        if self.0 & CompactDifficulty::SIGN_BIT != 0 || mantissa == 0 {
+            return None;
+        }
+
+        let mantissa = U256::from(mantissa);
+        let expanded = if exponent < CompactDifficulty::OFFSET {
+            mantissa >> (8 * (CompactDifficulty::OFFSET - exponent) as usize)
+        } else {
+            let shift = 8 * (exponent - CompactDifficulty::OFFSET) as usize;
+            if mantissa.bits() + shift > 256 {
+                return None;
+            }
+            mantissa << shift
+        };
+
+        if expanded.is_zero() {
+            None
+        } else {
+            Some(ExpandedDifficulty(expanded))
+        }
+    }
+
+    /// Returns the work represented by a block with this threshold.
+    ///
+    /// Returns `None` if the threshold is invalid, or if the work does not
+    /// fit in 128 bits. Zcash thresholds are much larger than 128 bits, so
+    /// their work always fits.
+    pub fn to_work(&self) -> Option<Work> {
+        let expanded = self.to_expanded()?.0;
+
+        // The work is 2^256 / (expanded + 1), but 2^256 doesn't fit in a
+        // U256. So we use (2^256 - expanded - 1) / (expanded + 1) + 1, which
+        // is equal.
+        let work = (!expanded / (expanded + 1)) + 1;
+        if work > U256::from(u128::MAX) {
+            return None;
+        }
+
+        Some(Work(work.low_u128()))
+    }
+}
+
+impl ExpandedDifficulty {
+    /// Returns the easiest difficulty threshold that is valid on `network`.
+    ///
+    /// This is `powLimit` in zcashd. It is not a compact threshold: the
+    /// Mainnet limit is `0x0007ffff...ff`, and the Testnet limit is
+    /// `0x07ffff...ff`.
+    pub fn target_difficulty_limit(network: Network) -> ExpandedDifficulty {
+        let limit_bits = match network {
+            Network::Mainnet => 243,
+            Network::Testnet => 251,
+        };
+
+        ExpandedDifficulty((U256::one() << limit_bits) - 1)
+    }
+
+    /// Returns true if `hash` is less than or equal to this threshold.
+    pub fn is_met_by(&self, hash: &BlockHeaderHash) -> bool {
+        U256::from_little_endian(&hash.0) <= self.0
+    }
+
+    /// Returns this threshold as big-endian bytes, which is the byte order
+    /// used to display hashes.
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.0.to_big_endian(&mut bytes);
+        bytes
+    }
+}
+
+/// An error checking a block header's proof of work.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum DifficultyError {
+    /// The compact threshold is negative, zero, or larger than 256 bits.
+    #[error("invalid difficulty threshold {0:?}")]
+    InvalidThreshold(CompactDifficulty),
+    /// The threshold is easier than the limit for the network.
+    #[error("difficulty threshold {0:?} is easier than the {1:?} limit")]
+    AboveLimit(CompactDifficulty, Network),
+    /// The block hash is greater than the threshold.
+    #[error("block hash {hash:?} is greater than its difficulty threshold {threshold:?}")]
+    HashAboveThreshold {
+        /// The hash of the block header.
+        hash: BlockHeaderHash,
+        /// The block header's difficulty threshold.
+        threshold: CompactDifficulty,
+    },
+}
+
+impl BlockHeader {
+    /// Returns the difficulty threshold of this header.
+    pub fn difficulty_threshold(&self) -> CompactDifficulty {
+        CompactDifficulty(self.bits)
+    }
+
+    /// Returns `Ok(())` if this header's hash meets its difficulty threshold,
+    /// and the threshold is valid on `network`.
+    ///
+    /// This is the proof of work check in zcashd's `CheckProofOfWork`. The
+    /// Equihash solution is checked separately.
+    pub fn is_difficulty_threshold_met(&self, network: Network) -> Result<(), DifficultyError> {
+        let threshold = self.difficulty_threshold();
+        let expanded = threshold
+            .to_expanded()
+            .ok_or(DifficultyError::InvalidThreshold(threshold))?;
+
+        if expanded > ExpandedDifficulty::target_difficulty_limit(network) {
+            return Err(DifficultyError::AboveLimit(threshold, network));
+        }
+
+        let hash = BlockHeaderHash::from(self);
+        if !expanded.is_met_by(&hash) {
+            return Err(DifficultyError::HashAboveThreshold { hash, threshold });
+        }
+
+        Ok(())
+    }
+}
+
+impl Work {
+    /// Returns the work as a number of hashes.
+    pub fn as_u128(self) -> u128 {
+        self.0
+    }
+}
+
+impl PartialCumulativeWork {
+    /// Returns the work as a number of hashes.
+    pub fn as_u128(self) -> u128 {
+        self.0
+    }
+}
+
+impl From<Work> for PartialCumulativeWork {
+    fn from(work: Work) -> Self {
+        PartialCumulativeWork(work.0)
+    }
+}
+
+impl Add<Work> for PartialCumulativeWork {
+    type Output = PartialCumulativeWork;
+
+    fn add(self, work: Work) -> PartialCumulativeWork {
+        PartialCumulativeWork(
+            self.0
+                .checked_add(work.0)
+                .expect("cumulative work is much less than 2^128"),
+        )
+    }
+}
+
+impl AddAssign<Work> for PartialCumulativeWork {
+    fn add_assign(&mut self, work: Work) {
+        *self = *self + work;
+    }
+}
+
+impl Sub<Work> for PartialCumulativeWork {
+    type Output = PartialCumulativeWork;
+
+    fn sub(self, work: Work) -> PartialCumulativeWork {
+        PartialCumulativeWork(
+            self.0
+                .checked_sub(work.0)
+                .expect("only work that was added is subtracted"),
+        )
+    }
+}
+
+impl SubAssign<Work> for PartialCumulativeWork {
+    fn sub_assign(&mut self, work: Work) {
+        *self = *self - work;
+    }
+}
+
+impl PartialEq<ExpandedDifficulty> for BlockHeaderHash {
+    fn eq(&self, other: &ExpandedDifficulty) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd<ExpandedDifficulty> for BlockHeaderHash {
+    /// Compares the hash as a little-endian 256-bit integer.
+    fn partial_cmp(&self, other: &ExpandedDifficulty) -> Option<Ordering> {
+        Some(U256::from_little_endian(&self.0).cmp(&other.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    use crate::{block::Block, serialization::ZcashDeserialize};
+
+    /// The `SetCompact` test vectors from zcashd's `arith_uint256_tests.cpp`.
+    ///
+    /// Each vector is a compact threshold, and its expanded value, or `None`
+    /// if it is negative, zero, or overflows.
+    fn zcashd_compact_vectors() -> Vec<(u32, Option<U256>)> {
+        vec![
+            (0x0000_0000, None),
+            (0x0012_3456, None),
+            (0x0100_3456, None),
+            (0x0200_0056, None),
+            (0x0300_0000, None),
+            (0x0400_0000, None),
+            (0x0092_3456, None),
+            (0x0180_3456, None),
+            (0x0280_0056, None),
+            (0x0380_0000, None),
+            (0x0480_0000, None),
+            (0x0112_3456, Some(U256::from(0x12))),
+            (0x01fe_dcba, None),
+            (0x0212_3456, Some(U256::from(0x1234))),
+            (0x0312_3456, Some(U256::from(0x0012_3456))),
+            (0x0412_3456, Some(U256::from(0x1234_5600))),
+            (0x0492_3456, None),
+            (0x0500_9234, Some(U256::from(0x9234_0000u64))),
+            (0x2012_3456, Some(U256::from(0x0012_3456) << (8 * 29))),
+            (0xff12_3456, None),
+        ]
+    }
+
+    #[test]
+    fn zcashd_compact_difficulty_vectors() {
+        for (compact, expected) in zcashd_compact_vectors() {
+            assert_eq!(
+                CompactDifficulty(compact).to_expanded(),
+                expected.map(ExpandedDifficulty),
+                "compact threshold {:#010x}",
+                compact
+            );
+        }
+    }
+
+    #[test]
+    fn target_difficulty_limits() {
+        let mainnet = ExpandedDifficulty::target_difficulty_limit(Network::Mainnet);
+        let testnet = ExpandedDifficulty::target_difficulty_limit(Network::Testnet);
+
+        assert_eq!(mainnet.to_be_bytes()[..3], [0x00, 0x07, 0xff]);
+        assert!(mainnet.to_be_bytes()[3..].iter().all(|byte| *byte == 0xff));
+        assert_eq!(testnet.to_be_bytes()[..2], [0x07, 0xff]);
+        assert!(testnet.to_be_bytes()[2..].iter().all(|byte| *byte == 0xff));
+
+        // The minimum difficulty compact thresholds are just below the limits
+        assert!(CompactDifficulty(0x1f07_ffff).to_expanded().unwrap() <= mainnet);
+        assert!(CompactDifficulty(0x1f08_0000).to_expanded().unwrap() > mainnet);
+        assert!(CompactDifficulty(0x2007_ffff).to_expanded().unwrap() <= testnet);
+        assert!(CompactDifficulty(0x2008_0000).to_expanded().unwrap() > testnet);
+    }
+
+    #[test]
+    fn header_difficulty_checks() {
+        let block =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..]).unwrap();
+        block
+            .header
+            .is_difficulty_threshold_met(Network::Mainnet)
+            .expect("mainnet blocks meet their thresholds");
+
+        let mut header = block.header;
+        header.bits = 0x0480_0001;
+        assert_eq!(
+            header.is_difficulty_threshold_met(Network::Mainnet),
+            Err(DifficultyError::InvalidThreshold(CompactDifficulty(
+                0x0480_0001
+            )))
+        );
+
+        header.bits = 0x2007_ffff;
+        assert_eq!(
+            header.is_difficulty_threshold_met(Network::Mainnet),
+            Err(DifficultyError::AboveLimit(
+                CompactDifficulty(0x2007_ffff),
+                Network::Mainnet
+            ))
+        );
+
+        // A threshold of 1 is only met by the all-zero hash
+        header.bits = 0x0300_0001;
+        assert!(matches!(
+            header.is_difficulty_threshold_met(Network::Mainnet),
+            Err(DifficultyError::HashAboveThreshold { .. })
+        ));
+    }
+
+    proptest! {
+        /// Check that hashes are compared with thresholds in display byte
+        /// order, which is the reverse of the serialized byte order.
+        #[test]
+        fn threshold_comparison_uses_display_order(hash in any::<BlockHeaderHash>(), compact in any::<u32>()) {
+            if let Some(threshold) = CompactDifficulty(compact).to_expanded() {
+                let mut display_hash = hash.0;
+                display_hash.reverse();
+
+                prop_assert_eq!(
+                    threshold.is_met_by(&hash),
+                    display_hash <= threshold.to_be_bytes()
+                );
+                prop_assert_eq!(threshold.is_met_by(&hash), hash <= threshold);
+            }
+        }
+
+        /// Check that valid expanded thresholds are the compact mantissa,
+        /// shifted by the exponent.
+        #[test]
+        fn expanded_threshold_matches_mantissa(compact in any::<u32>()) {
+            if let Some(threshold) = CompactDifficulty(compact).to_expanded() {
+                let exponent = compact >> 24;
+                let mantissa = U256::from(compact & 0x007f_ffff);
+                let shifted = if exponent < 3 {
+                    threshold.0 << (8 * (3 - exponent) as usize)
+                } else {
+                    threshold.0 >> (8 * (exponent - 3) as usize)
+                };
+
+                prop_assert!(!threshold.0.is_zero());
+                prop_assert_eq!(compact & CompactDifficulty::SIGN_BIT, 0);
+                // Small exponents discard the low bytes of the mantissa
+                prop_assert!(shifted <= mantissa);
+            }
+        }
+    }
+
+    #[test]
+    fn expand_compact_difficulty() {
+        // The Bitcoin genesis threshold, 0x00000000ffff0000...
+        let expanded = CompactDifficulty(0x1d00_ffff).to_expanded().unwrap();
+        assert_eq!(expanded.0, U256::from(0xffff) << (8 * 26));
+
+        // Small exponents shift the mantissa right
+        let expanded = CompactDifficulty(0x0200_ffff).to_expanded().unwrap();
+        assert_eq!(expanded.0, U256::from(0xff));
+
+        // Negative, zero, and overflowing thresholds are invalid
+        assert_eq!(CompactDifficulty(0x0480_0001).to_expanded(), None);
+        assert_eq!(CompactDifficulty(0x0400_0000).to_expanded(), None);
+        assert_eq!(CompactDifficulty(0x0100_ffff).to_expanded(), None);
+        assert_eq!(CompactDifficulty(0x2201_0000).to_expanded(), None);
+    }
+
+    #[test]
+    fn work() {
+        // A threshold just below 2^255 needs 2 hashes on average
+        let work = CompactDifficulty(0x207f_ffff).to_work().unwrap();
+        assert_eq!(work, Work(2));
+
+        // Lower thresholds need more work
+        let easy = CompactDifficulty(0x1f07_ffff).to_work().unwrap();
+        let hard = CompactDifficulty(0x1d00_ffff).to_work().unwrap();
+        assert!(hard > easy);
+        assert_eq!(hard, Work(0x1_0001_0001));
+
+        let mut cumulative = PartialCumulativeWork::default();
+        cumulative += easy;
+        cumulative += hard;
+        assert_eq!(cumulative.as_u128(), easy.as_u128() + hard.as_u128());
+        cumulative -= easy;
+        assert_eq!(cumulative, PartialCumulativeWork::from(hard));
+    }
+
+    #[test]
+    fn mainnet_blocks_meet_their_thresholds() {
+        for bytes in &[
+            &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..],
+        ] {
+            let block = Block::zcash_deserialize(*bytes).unwrap();
+            let threshold = block
+                .header
+                .difficulty_threshold()
+                .to_expanded()
+                .expect("mainnet thresholds are valid");
+
+            assert!(threshold.is_met_by(&block.hash()));
+            assert!(block.hash() <= threshold);
+        }
+    }
+}
@@ -12,17 +12,32 @@
 mod tests;
 
 use chrono::Utc;
+use futures::stream::{FuturesUnordered, StreamExt};
 use futures_util::FutureExt;
 use std::{
     error,
     future::Future,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
+    time::Duration,
 };
-use tower::{buffer::Buffer, Service};
+use tower::{buffer::Buffer, Service, ServiceExt};
 
 use zebra_chain::block::{Block, BlockHeaderHash};
+use zebra_chain::types::BlockHeight;
+use zebra_chain::Network;
+
+use crate::{
+    groth16, parameters,
+    transaction::{self, TransactionVerifier},
+};
+
+/// The maximum number of Sprout Groth16 proofs in each batch.
+const JOINSPLIT_MAX_BATCH_ITEMS: usize = 64;
+
+/// The maximum latency for a Sprout Groth16 proof in a batch.
+const JOINSPLIT_MAX_BATCH_LATENCY: Duration = Duration::from_millis(100);
 
 struct BlockVerifier<S>
 where
@@ -36,6 +51,54 @@ where
     // TODO: contextual verification
     #[allow(dead_code)]
     state_service: S,
+
+    /// The verifier for the transactions in each block.
+    transaction_verifier: TransactionVerifier<S>,
+
+    /// The network this verifier checks blocks for.
+    network: Network,
+
+    /// The Groth16 parameters, loaded when the first post-Sapling JoinSplit
+    /// is verified.
+    params_loader: groth16::ParamsLoader,
+
+    /// The Sprout Groth16 proof verifier, created when the first post-Sapling
+    /// JoinSplit is verified.
+    ///
+    /// Creating the verifier loads the Groth16 parameters, which might need
+    /// to download them. So the verifier is created inside the returned
+    /// future, and shared with later calls.
+    joinsplit_verifier: Arc<Mutex<Option<groth16::FallbackVerifier>>>,
+}
+
+/// Returns the Sprout Groth16 proof verifier in `cached`, creating it if
+/// needed.
+///
+/// Creating the verifier loads the Groth16 parameters using `params_loader`,
+/// on a blocking thread.
+async fn joinsplit_verifier(
+    params_loader: groth16::ParamsLoader,
+    cached: Arc<Mutex<Option<groth16::FallbackVerifier>>>,
+) -> Result<groth16::FallbackVerifier, Error> {
+    if let Some(verifier) = cached.lock().expect("no panics while locked").clone() {
+        return Ok(verifier);
+    }
+
+    let params = params_loader.load().await?;
+
+    // If another call created the verifier while we were loading, use that
+    // verifier, so all the proofs share the same batches
+    Ok(cached
+        .lock()
+        .expect("no panics while locked")
+        .get_or_insert_with(|| {
+            groth16::FallbackVerifier::new(
+                params.sprout.joinsplit_prepared_verifying_key.clone(),
+                JOINSPLIT_MAX_BATCH_ITEMS,
+                JOINSPLIT_MAX_BATCH_LATENCY,
+            )
+        })
+        .clone())
 }
 
 /// The error type for the BlockVerifier Service.
@@ -69,15 +132,84 @@ where
     fn call(&mut self, block: Arc<Block>) -> Self::Future {
         // TODO(jlusby): Error = Report, handle errors from state_service.
 
+        // Collect the JoinSplit proofs before the async block, so we only
+        // load the Groth16 parameters if we need them.
+        let joinsplit_items = block.coinbase_height().map(|height| {
+            block
+                .transactions
+                .iter()
+                .map(|tx| groth16::sprout_joinsplit_items(self.network, height, tx))
+                .collect::<Result<Vec<_>, _>>()
+        });
+        let joinsplit_items: Vec<groth16::Item> = match joinsplit_items {
+            Some(Ok(items)) => items.into_iter().flatten().collect(),
+            Some(Err(e)) => return async move { Err(e.into()) }.boxed(),
+            // The coinbase check below rejects this block
+            None => Vec::new(),
+        };
+        let network = self.network;
+        let transaction_verifier = self.transaction_verifier.clone();
+        let params_loader = self.params_loader.clone();
+        let cached_joinsplit_verifier = self.joinsplit_verifier.clone();
+
         async move {
             // Since errors cause an early exit, try to do the
             // quick checks first.
 
+            check_genesis_rules(network, &block)?;
+            check_version(&block)?;
+
             let now = Utc::now();
             block.header.is_time_valid_at(now)?;
             block.header.is_equihash_solution_valid()?;
+            block.header.is_difficulty_threshold_met(network)?;
             block.is_coinbase_first()?;
 
+            // Check each transaction in the context of this block.
+            //
+            // Transactions can spend outputs from earlier transactions in the
+            // same block, so we pass the block's outputs to each transaction.
+            // (We don't check the order of transactions within the block yet.)
+            let height = block
+                .coinbase_height()
+                .ok_or("block has no coinbase height")?;
+            parameters::block_commitment(network, height, block.header.commitment_bytes)?;
+            let known_utxos = Arc::new(zebra_state::Utxo::from_block(&block));
+            let mut transactions = block
+                .transactions
+                .iter()
+                .map(|transaction| {
+                    transaction_verifier
+                        .clone()
+                        .oneshot(transaction::Request::Block {
+                            transaction: transaction.clone(),
+                            known_utxos: known_utxos.clone(),
+                            height,
+                            time: block.header.time,
+                        })
+                })
+                .collect::<FuturesUnordered<_>>();
+            while let Some(result) = transactions.next().await {
+                result?;
+            }
+
+            // Verify the Sprout Groth16 proofs in post-Sapling JoinSplits
+            if !joinsplit_items.is_empty() {
+                let joinsplit_verifier =
+                    joinsplit_verifier(params_loader, cached_joinsplit_verifier).await?;
+                let mut service = joinsplit_verifier.service();
+                let mut proofs = FuturesUnordered::new();
+                for item in joinsplit_items {
+                    let proof = service.ready_and().await?.call(item);
+                    proofs.push(proof);
+                }
+                // Don't wait for more proofs from other blocks
+                joinsplit_verifier.flush();
+                while let Some(result) = proofs.next().await {
+                    result?;
+                }
+            }
+
             // TODO:
             //   - header verification
             //   - contextual verification
@@ -88,7 +220,45 @@ where
     }
 }
 
-/// Return a block verification service, using the provided state service.
+/// Check the special rules for genesis blocks.
+///
+/// Genesis blocks are hard-coded in zcashd, so the only valid genesis block is
+/// the one with the hard-coded hash for `network`. (This also fixes its
+/// difficulty target.) All other blocks must have a previous block.
+fn check_genesis_rules(network: Network, block: &Block) -> Result<(), Error> {
+    let is_genesis_height = block.coinbase_height() == Some(BlockHeight(0));
+    let has_genesis_parent =
+        block.header.previous_block_hash == parameters::GENESIS_PREVIOUS_BLOCK_HASH;
+
+    if (is_genesis_height || has_genesis_parent)
+        && block.hash() != parameters::genesis_hash(network)
+    {
+        Err("invalid genesis block: hash does not match the hard-coded genesis hash")?;
+    }
+
+    Ok(())
+}
+
+/// Check the block header version.
+///
+/// Blocks with future versions are valid, because rejecting them would fork
+/// Zebra off the chain when miners signal support for future rules.
+fn check_version(block: &Block) -> Result<(), Error> {
+    let version = block.header.version;
+
+    if parameters::check_block_version(version)? == parameters::BlockVersion::Future {
+        metrics::counter!("block.version.future", 1);
+        tracing::debug!(version, "block has a future version");
+    }
+
+    Ok(())
+}
+
+/// Return a block verification service for `network`, using the provided
+/// state service.
+///
+/// The Groth16 parameters are loaded by `params_loader`, when the verifier
+/// first needs them.
 ///
 /// The block verifier holds a state service of type `S`, used as context for
 /// block validation. This state is pluggable to allow for testing or
@@ -102,7 +272,9 @@ where
 /// the result be shared, cloning if needed). Constructing multiple services
 /// from the same underlying state might cause synchronisation bugs.
 pub fn init<S>(
+    network: Network,
     state_service: S,
+    params_loader: groth16::ParamsLoader,
 ) -> impl Service<
     Arc<Block>,
     Response = BlockHeaderHash,
@@ -118,5 +290,14 @@ where
         + 'static,
     S::Future: Send + 'static,
 {
-    Buffer::new(BlockVerifier { state_service }, 1)
+    Buffer::new(
+        BlockVerifier {
+            transaction_verifier: TransactionVerifier::new(network, state_service.clone()),
+            state_service,
+            network,
+            params_loader,
+            joinsplit_verifier: Arc::new(Mutex::new(None)),
+        },
+        1,
+    )
 }
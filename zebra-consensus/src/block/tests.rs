@@ -2,6 +2,9 @@
 
 use super::*;
 
+use crate::groth16::ParamsLoader;
+use crate::parameters::genesis_block;
+
 use chrono::{Duration, Utc};
 use color_eyre::eyre::eyre;
 use color_eyre::eyre::Report;
@@ -12,6 +15,7 @@ use zebra_chain::block::Block;
 use zebra_chain::block::BlockHeader;
 use zebra_chain::serialization::ZcashDeserialize;
 use zebra_chain::transaction::Transaction;
+use zebra_chain::Network::*;
 
 #[tokio::test]
 async fn verify_test() -> Result<(), Report> {
@@ -27,7 +31,7 @@ async fn verify() -> Result<(), Report> {
     let hash: BlockHeaderHash = block.as_ref().into();
 
     let state_service = Box::new(zebra_state::in_memory::init());
-    let mut block_verifier = super::init(state_service);
+    let mut block_verifier = super::init(Mainnet, state_service, ParamsLoader::new(None));
 
     /// SPANDOC: Make sure the verifier service is ready
     let ready_verifier_service = block_verifier.ready_and().await.map_err(|e| eyre!(e))?;
@@ -55,7 +59,7 @@ async fn verify_fail_future_time() -> Result<(), Report> {
         <Block>::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..])?;
 
     let state_service = zebra_state::in_memory::init();
-    let mut block_verifier = super::init(state_service.clone());
+    let mut block_verifier = super::init(Mainnet, state_service.clone(), ParamsLoader::new(None));
 
     // Modify the block's time
     // Changing the block header also invalidates the header hashes, but
@@ -92,7 +96,7 @@ async fn header_solution() -> Result<(), Report> {
 
     // Service variables
     let state_service = Box::new(zebra_state::in_memory::init());
-    let mut block_verifier = super::init(state_service.clone());
+    let mut block_verifier = super::init(Mainnet, state_service.clone(), ParamsLoader::new(None));
 
     let ready_verifier_service = block_verifier.ready_and().await.map_err(|e| eyre!(e))?;
 
@@ -127,7 +131,7 @@ async fn coinbase() -> Result<(), Report> {
 
     // Service variables
     let state_service = Box::new(zebra_state::in_memory::init());
-    let mut block_verifier = super::init(state_service.clone());
+    let mut block_verifier = super::init(Mainnet, state_service.clone(), ParamsLoader::new(None));
 
     // Get a header of a block
     let header = BlockHeader::zcash_deserialize(&zebra_test::vectors::DUMMY_HEADER[..]).unwrap();
@@ -184,3 +188,46 @@ async fn coinbase() -> Result<(), Report> {
 
     Ok(())
 }
+
+#[test]
+fn version_rules() -> Result<(), Report> {
+    zebra_test::init();
+
+    let mut block = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..])?;
+    check_version(&block).map_err(|e| eyre!(e))?;
+
+    // Blocks with future versions are valid
+    block.header.version = parameters::CURRENT_BLOCK_VERSION + 1;
+    check_version(&block).map_err(|e| eyre!(e))?;
+
+    block.header.version = parameters::MIN_BLOCK_VERSION - 1;
+    check_version(&block).expect_err("versions below the minimum should fail");
+
+    Ok(())
+}
+
+#[test]
+fn genesis_rules() -> Result<(), Report> {
+    zebra_test::init();
+
+    let genesis = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?;
+    check_genesis_rules(Mainnet, &genesis).map_err(|e| eyre!(e))?;
+
+    // The Mainnet genesis block is not a valid Testnet genesis block
+    check_genesis_rules(Testnet, &genesis).expect_err("wrong network genesis should fail");
+
+    let testnet_genesis = genesis_block(Testnet).expect("Testnet has a genesis block");
+    check_genesis_rules(Testnet, &testnet_genesis).map_err(|e| eyre!(e))?;
+    check_genesis_rules(Mainnet, &testnet_genesis).expect_err("wrong network genesis should fail");
+
+    // Changing the genesis block changes its hash
+    let mut bad_genesis = genesis.clone();
+    bad_genesis.header.version = 0;
+    check_genesis_rules(Mainnet, &bad_genesis).expect_err("modified genesis should fail");
+
+    // Other blocks are not affected by the genesis rules
+    let block1 = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?;
+    check_genesis_rules(Mainnet, &block1).map_err(|e| eyre!(e))?;
+
+    Ok(())
+}
@@ -0,0 +1,437 @@
+//! Block template construction for block producers.
+//!
+//! A block template contains a coinbase transaction, and transactions
+//! selected from the mempool. Mining software fills in the nonce and Equihash
+//! solution to turn a template into a block. This module is the backend for
+//! the `getblocktemplate` RPC.
+//!
+//! Callers supply the candidate transactions and their fees, usually from
+//! [`ZebraMempoolState::transactions`](crate::mempool::ZebraMempoolState::transactions).
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fmt,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+
+use zebra_chain::{
+    block::{Block, BlockHeader, BlockHeaderHash, MAX_BLOCK_BYTES},
+    equihash_solution::EquihashSolution,
+    merkle_tree::MerkleTreeRootHash,
+    serialization::ZcashSerialize,
+    transaction::{
+        CoinbaseData, Transaction, TransactionHash, TransparentInput, TransparentOutput,
+        MAX_COINBASE_DATA_LEN,
+    },
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight, LockTime, Script,
+    },
+    Network,
+};
+
+use crate::parameters::{
+    subsidy::{founders_reward, funding_stream_values, miner_subsidy, FundingStreamReceiver},
+    NetworkUpgrade, CURRENT_BLOCK_VERSION,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The maximum number of legacy signature operations in a block.
+pub const MAX_BLOCK_SIGOPS: u32 = 20_000;
+
+/// The maximum number of ZIP-317 unpaid logical actions in a block template.
+pub const BLOCK_UNPAID_ACTION_LIMIT: u64 = 50;
+
+/// The maximum serialized size of a block header and its transaction count.
+///
+/// Headers with Equihash (200, 9) solutions are 1487 bytes, and the count is
+/// at most 5 bytes.
+const MAX_BLOCK_HEADER_AND_COUNT_BYTES: u64 = 1487 + 5;
+
+/// The zcashd coinbase data for empty miner data, which is `OP_0`.
+///
+/// This makes sure that the coinbase script is at least two bytes long.
+const DEFAULT_COINBASE_DATA: [u8; 1] = [0x00];
+
+/// A verified mempool transaction, which can be included in a block
+/// template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MempoolTransaction {
+    /// The transaction.
+    pub transaction: Arc<Transaction>,
+    /// The fee paid by the transaction.
+    pub fee: Amount<NonNegative>,
+}
+
+/// The scripts that receive the block subsidy and transaction fees.
+///
+/// Zebra doesn't have the founders' reward address list yet, so block
+/// producers must supply the founders' script before Canopy. Funding streams
+/// are paid to the [ZIP-214] addresses, unless they are overridden.
+///
+/// [ZIP-214]: https://zips.z.cash/zip-0214
+#[derive(Clone, Debug)]
+pub struct Recipients {
+    /// The miner's script, which receives the miner subsidy and all the
+    /// transaction fees.
+    pub miner: Script,
+    /// The founders' reward script, which is required before Canopy and the
+    /// first halving.
+    pub founders: Option<Script>,
+    /// The funding stream scripts, which override the ZIP-214 funding
+    /// stream addresses.
+    pub funding_streams: HashMap<FundingStreamReceiver, Script>,
+}
+
+/// The chain state that a block template extends.
+///
+/// The state can't calculate difficulty adjustments or block header
+/// commitments yet, so callers supply them.
+#[derive(Clone, Debug)]
+pub struct ChainContext {
+    /// The network for the template.
+    pub network: Network,
+    /// The height of the template block.
+    pub height: BlockHeight,
+    /// The hash of the current chain tip, which is the parent of the
+    /// template block.
+    pub previous_block_hash: BlockHeaderHash,
+    /// The block header commitment for the template block.
+    ///
+    /// Its meaning depends on the network upgrade, see
+    /// [`block_commitment`](crate::parameters::block_commitment).
+    pub commitment_bytes: [u8; 32],
+    /// The template block time.
+    pub time: DateTime<Utc>,
+    /// The difficulty threshold for the template block, in compact form.
+    pub bits: u32,
+}
+
+/// An error constructing a block template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TemplateError {
+    /// Templates need V4 coinbase transactions, which are only valid after
+    /// Sapling activation.
+    BeforeSapling(BlockHeight),
+    /// The miner data doesn't fit in the coinbase.
+    CoinbaseDataTooLong(usize),
+    /// The founders' reward is required, but there is no founders' script.
+    MissingFoundersRecipient,
+    /// A funding stream is required, but there is no script for it, and no
+    /// transparent ZIP-214 address at the template height.
+    ///
+    /// Templates can't pay Sapling funding stream addresses, because they
+    /// only have transparent outputs.
+    MissingFundingStreamRecipient(FundingStreamReceiver),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::BeforeSapling(height) => write!(
+                f,
+                "block templates are not supported before Sapling, height {:?}",
+                height
+            ),
+            TemplateError::CoinbaseDataTooLong(len) => write!(
+                f,
+                "coinbase data is {} bytes, but the limit is {}",
+                len, MAX_COINBASE_DATA_LEN
+            ),
+            TemplateError::MissingFoundersRecipient => {
+                f.write_str("the founders' reward requires a founders' script")
+            }
+            TemplateError::MissingFundingStreamRecipient(receiver) => {
+                write!(f, "the {:?} funding stream requires a script", receiver)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A block template, which becomes a block when it has a valid nonce and
+/// Equihash solution.
+#[derive(Clone, Debug)]
+pub struct BlockTemplate {
+    /// The chain state that this template extends.
+    pub context: ChainContext,
+    /// The coinbase transaction.
+    pub coinbase: Arc<Transaction>,
+    /// The transactions selected from the mempool, in block order.
+    pub transactions: Vec<Arc<Transaction>>,
+    /// The sum of the fees paid by `transactions`.
+    pub total_fees: Amount<NonNegative>,
+    /// The merkle root of the coinbase and `transactions`.
+    pub merkle_root: MerkleTreeRootHash,
+}
+
+impl BlockTemplate {
+    /// Returns a new block template that extends `context`, paying the block
+    /// subsidy and fees to `recipients`.
+    ///
+    /// `coinbase_data` is included in the coinbase input after the block
+    /// height. Transactions are selected from `candidates`, using
+    /// `select_transactions`.
+    pub fn new(
+        context: ChainContext,
+        recipients: &Recipients,
+        coinbase_data: Vec<u8>,
+        candidates: Vec<MempoolTransaction>,
+    ) -> Result<BlockTemplate, TemplateError> {
+        let is_sapling_active = NetworkUpgrade::Sapling
+            .activation_height(context.network)
+            .map(|sapling_height| context.height >= sapling_height)
+            .unwrap_or(false);
+        if !is_sapling_active {
+            return Err(TemplateError::BeforeSapling(context.height));
+        }
+
+        // The coinbase size doesn't depend on the fees, so we can build a
+        // coinbase with zero fees to find out how much space is left
+        let zero = 0u64.try_into().expect("zero is a valid amount");
+        let coinbase = coinbase_transaction(&context, recipients, &coinbase_data, zero)?;
+        let max_bytes =
+            MAX_BLOCK_BYTES - MAX_BLOCK_HEADER_AND_COUNT_BYTES - serialized_size(&coinbase) as u64;
+        let max_sigops = MAX_BLOCK_SIGOPS - legacy_sigop_count(&coinbase);
+
+        let selected = select_transactions(candidates, max_bytes, max_sigops);
+        let total_fees: u64 = selected.iter().map(|tx| u64::from(tx.fee)).sum();
+        let total_fees = total_fees
+            .try_into()
+            .expect("the fees in a block are less than the maximum amount");
+
+        let coinbase = Arc::new(coinbase_transaction(
+            &context,
+            recipients,
+            &coinbase_data,
+            total_fees,
+        )?);
+        let transactions: Vec<Arc<Transaction>> =
+            selected.into_iter().map(|tx| tx.transaction).collect();
+        let merkle_root = std::iter::once(&coinbase)
+            .chain(transactions.iter())
+            .map(|tx| TransactionHash::from(tx.as_ref()))
+            .collect();
+
+        Ok(BlockTemplate {
+            context,
+            coinbase,
+            transactions,
+            total_fees,
+            merkle_root,
+        })
+    }
+
+    /// Returns the template block header, with `nonce` and `solution`.
+    pub fn header(&self, nonce: [u8; 32], solution: EquihashSolution) -> BlockHeader {
+        BlockHeader {
+            version: CURRENT_BLOCK_VERSION,
+            previous_block_hash: self.context.previous_block_hash,
+            merkle_root_hash: self.merkle_root,
+            commitment_bytes: self.context.commitment_bytes,
+            time: self.context.time,
+            bits: self.context.bits,
+            nonce,
+            solution,
+        }
+    }
+
+    /// Returns the template block, with `nonce` and `solution`.
+    pub fn block(&self, nonce: [u8; 32], solution: EquihashSolution) -> Block {
+        Block {
+            header: self.header(nonce, solution),
+            transactions: std::iter::once(self.coinbase.clone())
+                .chain(self.transactions.iter().cloned())
+                .collect(),
+        }
+    }
+}
+
+/// Returns the coinbase transaction for `context`, which pays the subsidy and
+/// `total_fees` to `recipients`.
+fn coinbase_transaction(
+    context: &ChainContext,
+    recipients: &Recipients,
+    coinbase_data: &[u8],
+    total_fees: Amount<NonNegative>,
+) -> Result<Transaction, TemplateError> {
+    let ChainContext {
+        network, height, ..
+    } = *context;
+
+    let miner_value = (miner_subsidy(network, height) + total_fees)
+        .expect("the block subsidy and fees are less than the maximum amount");
+    let mut outputs = vec![TransparentOutput {
+        value: miner_value,
+        pk_script: recipients.miner.clone(),
+    }];
+
+    let founders_reward = founders_reward(network, height);
+    if u64::from(founders_reward) > 0 {
+        let pk_script = recipients
+            .founders
+            .clone()
+            .ok_or(TemplateError::MissingFoundersRecipient)?;
+        outputs.push(TransparentOutput {
+            value: founders_reward,
+            pk_script,
+        });
+    }
+
+    let funding_streams = funding_stream_values(network, height);
+    for receiver in FundingStreamReceiver::ALL.iter() {
+        if let Some(value) = funding_streams.get(receiver) {
+            let pk_script = recipients
+                .funding_streams
+                .get(receiver)
+                .cloned()
+                .or_else(|| {
+                    receiver
+                        .address_for(height, network)
+                        .and_then(|address| address.output_script())
+                })
+                .ok_or(TemplateError::MissingFundingStreamRecipient(*receiver))?;
+            outputs.push(TransparentOutput {
+                value: *value,
+                pk_script,
+            });
+        }
+    }
+
+    let data = if coinbase_data.is_empty() {
+        DEFAULT_COINBASE_DATA.to_vec()
+    } else {
+        coinbase_data.to_vec()
+    };
+    let data_len = data.len();
+    let data = CoinbaseData::new(data).ok_or(TemplateError::CoinbaseDataTooLong(data_len))?;
+
+    Ok(Transaction::V4 {
+        inputs: vec![TransparentInput::Coinbase {
+            height,
+            data,
+            sequence: u32::MAX,
+        }],
+        outputs,
+        lock_time: LockTime::Height(BlockHeight(0)),
+        expiry_height: height,
+        value_balance: 0i64.try_into().expect("zero is a valid amount"),
+        shielded_data: None,
+        joinsplit_data: None,
+    })
+}
+
+/// Select transactions from `candidates` for a block template, using at most
+/// `max_bytes` and `max_sigops`.
+///
+/// Transactions are selected in order of their ZIP-317 fee weight ratio,
+/// which is the fee paid per logical action, up to a cap. Transactions with
+/// unpaid actions are only selected while the template has fewer than
+/// `BLOCK_UNPAID_ACTION_LIMIT` unpaid actions. Transactions that spend the
+/// outputs of other candidates are only selected after their parents.
+///
+/// Unlike the randomized selection in ZIP-317, this selection is
+/// deterministic, so templates are reproducible.
+pub fn select_transactions(
+    candidates: Vec<MempoolTransaction>,
+    max_bytes: u64,
+    max_sigops: u32,
+) -> Vec<MempoolTransaction> {
+    let mut candidates: Vec<_> = candidates
+        .into_iter()
+        .map(|tx| {
+            let hash = TransactionHash::from(tx.transaction.as_ref());
+            let ratio = tx.transaction.fee_weight_ratio(tx.fee);
+            (hash, ratio, tx)
+        })
+        .collect();
+    // Highest ratio first, with ties broken by hash
+    candidates.sort_by(|(a_hash, a_ratio, _), (b_hash, b_ratio, _)| {
+        b_ratio
+            .partial_cmp(a_ratio)
+            .expect("fee weight ratios are finite")
+            .then_with(|| a_hash.0.cmp(&b_hash.0))
+    });
+
+    let candidate_hashes: HashSet<TransactionHash> =
+        candidates.iter().map(|(hash, _, _)| *hash).collect();
+    let mut selected_hashes = HashSet::new();
+    let mut selected = Vec::new();
+    let mut remaining_bytes = max_bytes;
+    let mut remaining_sigops = max_sigops;
+    let mut unpaid_actions = 0;
+
+    // Each pass can unblock the children of selected transactions
+    let mut made_progress = true;
+    while made_progress {
+        made_progress = false;
+        let mut skipped = Vec::new();
+
+        for (hash, ratio, tx) in candidates {
+            let size = serialized_size(tx.transaction.as_ref()) as u64;
+            let sigops = legacy_sigop_count(&tx.transaction);
+            let unpaid = tx.transaction.unpaid_actions(tx.fee);
+
+            let has_unselected_parent = tx.transaction.inputs().any(|input| match input {
+                TransparentInput::PrevOut { outpoint, .. } => {
+                    candidate_hashes.contains(&outpoint.hash)
+                        && !selected_hashes.contains(&outpoint.hash)
+                }
+                TransparentInput::Coinbase { .. } => false,
+            });
+
+            if has_unselected_parent {
+                skipped.push((hash, ratio, tx));
+                continue;
+            }
+
+            if size > remaining_bytes
+                || sigops > remaining_sigops
+                || unpaid_actions + unpaid > BLOCK_UNPAID_ACTION_LIMIT
+            {
+                continue;
+            }
+
+            remaining_bytes -= size;
+            remaining_sigops -= sigops;
+            unpaid_actions += unpaid;
+            selected_hashes.insert(hash);
+            selected.push(tx);
+            made_progress = true;
+        }
+
+        candidates = skipped;
+    }
+
+    selected
+}
+
+/// Returns the number of legacy signature operations in `transaction`.
+fn legacy_sigop_count(transaction: &Transaction) -> u32 {
+    let input_sigops: u32 = transaction
+        .inputs()
+        .map(|input| match input {
+            TransparentInput::PrevOut { script, .. } => script.legacy_sigop_count(),
+            TransparentInput::Coinbase { .. } => 0,
+        })
+        .sum();
+    let output_sigops: u32 = transaction
+        .outputs()
+        .map(|output| output.pk_script.legacy_sigop_count())
+        .sum();
+
+    input_sigops + output_sigops
+}
+
+/// Returns the serialized size of `item`.
+fn serialized_size<T: ZcashSerialize>(item: &T) -> usize {
+    item.zcash_serialize_to_vec()
+        .expect("serializing into a vec is infallible")
+        .len()
+}
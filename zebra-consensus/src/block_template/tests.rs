@@ -0,0 +1,275 @@
+//! Tests for block template construction.
+
+use std::convert::TryInto;
+
+use chrono::{TimeZone, Utc};
+
+use zebra_chain::transaction::OutPoint;
+
+use super::*;
+
+/// Returns a P2PKH output script for the key hash `[byte; 20]`.
+fn p2pkh_script(byte: u8) -> Script {
+    let mut bytes = vec![0x76, 0xa9, 20];
+    bytes.extend_from_slice(&[byte; 20]);
+    bytes.extend_from_slice(&[0x88, 0xac]);
+    Script(bytes)
+}
+
+/// Returns a mainnet template context at `height`.
+fn context(height: u32) -> ChainContext {
+    ChainContext {
+        network: Network::Mainnet,
+        height: BlockHeight(height),
+        previous_block_hash: BlockHeaderHash([0; 32]),
+        commitment_bytes: [0; 32],
+        time: Utc.timestamp(1_600_000_000, 0),
+        bits: 0x1f07_ffff,
+    }
+}
+
+/// Returns recipients with distinct scripts for each receiver.
+fn recipients() -> Recipients {
+    Recipients {
+        miner: p2pkh_script(1),
+        founders: Some(p2pkh_script(2)),
+        funding_streams: vec![
+            (FundingStreamReceiver::Ecc, p2pkh_script(3)),
+            (FundingStreamReceiver::ZcashFoundation, p2pkh_script(4)),
+            (FundingStreamReceiver::MajorGrants, p2pkh_script(5)),
+        ]
+        .into_iter()
+        .collect(),
+    }
+}
+
+/// Returns a mempool transaction that spends `outpoint` to a P2PKH output,
+/// and pays `fee`.
+fn candidate(outpoint: OutPoint, fee: u64) -> MempoolTransaction {
+    let transaction = Transaction::V1 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint,
+            script: Script(vec![]),
+            sequence: u32::MAX,
+        }],
+        outputs: vec![TransparentOutput {
+            value: 1_000u64.try_into().unwrap(),
+            pk_script: p2pkh_script(9),
+        }],
+        lock_time: LockTime::Height(BlockHeight(0)),
+    };
+
+    MempoolTransaction {
+        transaction: Arc::new(transaction),
+        fee: fee.try_into().unwrap(),
+    }
+}
+
+/// Returns an outpoint in a transaction that isn't in the mempool.
+fn outpoint(index: u32) -> OutPoint {
+    OutPoint {
+        hash: TransactionHash([0xff; 32]),
+        index,
+    }
+}
+
+/// Returns the values of the outputs of `transaction`.
+fn output_values(transaction: &Transaction) -> Vec<u64> {
+    transaction
+        .outputs()
+        .map(|output| u64::from(output.value))
+        .collect()
+}
+
+#[test]
+fn coinbase_recipients() -> Result<(), TemplateError> {
+    zebra_test::init();
+
+    // Before Canopy, the founders' reward is paid
+    let template = BlockTemplate::new(context(419_200), &recipients(), vec![], vec![])?;
+    assert_eq!(
+        output_values(&template.coinbase),
+        vec![1_000_000_000, 250_000_000]
+    );
+
+    // After Canopy, the funding streams are paid
+    let template = BlockTemplate::new(context(1_046_400), &recipients(), vec![], vec![])?;
+    assert_eq!(
+        output_values(&template.coinbase),
+        vec![250_000_000, 21_875_000, 15_625_000, 25_000_000]
+    );
+    assert_eq!(
+        template.coinbase.outputs().nth(1).unwrap().pk_script,
+        p2pkh_script(3)
+    );
+
+    let block = template.block([0; 32], EquihashSolution([0; 1344]));
+    assert_eq!(block.coinbase_height(), Some(BlockHeight(1_046_400)));
+    assert_eq!(
+        block.header.merkle_root_hash.0,
+        TransactionHash::from(template.coinbase.as_ref()).0
+    );
+
+    Ok(())
+}
+
+#[test]
+fn template_errors() {
+    zebra_test::init();
+
+    let no_founders = Recipients {
+        founders: None,
+        ..recipients()
+    };
+    assert_eq!(
+        BlockTemplate::new(context(419_200), &no_founders, vec![], vec![]).unwrap_err(),
+        TemplateError::MissingFoundersRecipient
+    );
+
+    assert_eq!(
+        BlockTemplate::new(context(1), &recipients(), vec![], vec![]).unwrap_err(),
+        TemplateError::BeforeSapling(BlockHeight(1))
+    );
+
+    assert_eq!(
+        BlockTemplate::new(
+            context(1_046_400),
+            &recipients(),
+            vec![0; MAX_COINBASE_DATA_LEN + 1],
+            vec![]
+        )
+        .unwrap_err(),
+        TemplateError::CoinbaseDataTooLong(MAX_COINBASE_DATA_LEN + 1)
+    );
+}
+
+#[test]
+fn funding_streams_default_to_zip_214_addresses() -> Result<(), TemplateError> {
+    zebra_test::init();
+
+    let no_funding_streams = Recipients {
+        funding_streams: HashMap::new(),
+        ..recipients()
+    };
+    let height = BlockHeight(1_046_400);
+    let template = BlockTemplate::new(context(height.0), &no_funding_streams, vec![], vec![])?;
+
+    let scripts: Vec<Script> = template
+        .coinbase
+        .outputs()
+        .skip(1)
+        .map(|output| output.pk_script.clone())
+        .collect();
+    let expected: Vec<Script> = FundingStreamReceiver::ALL
+        .iter()
+        .map(|receiver| {
+            receiver
+                .address_for(height, Network::Mainnet)
+                .expect("funding streams have addresses at Canopy activation")
+                .output_script()
+                .expect("Canopy funding stream addresses are transparent")
+        })
+        .collect();
+    assert_eq!(scripts, expected);
+
+    Ok(())
+}
+
+#[test]
+fn fees_are_paid_to_miner() -> Result<(), TemplateError> {
+    zebra_test::init();
+
+    let candidates = vec![
+        candidate(outpoint(0), 10_000),
+        candidate(outpoint(1), 20_000),
+    ];
+    let template = BlockTemplate::new(context(1_046_400), &recipients(), vec![], candidates)?;
+
+    assert_eq!(template.transactions.len(), 2);
+    assert_eq!(u64::from(template.total_fees), 30_000);
+    assert_eq!(output_values(&template.coinbase)[0], 250_030_000);
+
+    let block = template.block([0; 32], EquihashSolution([0; 1344]));
+    let merkle_root: MerkleTreeRootHash = block
+        .transactions
+        .iter()
+        .map(|tx| TransactionHash::from(tx.as_ref()))
+        .collect();
+    assert_eq!(block.header.merkle_root_hash, merkle_root);
+
+    Ok(())
+}
+
+#[test]
+fn selection_order_and_limits() {
+    zebra_test::init();
+
+    let paid = candidate(outpoint(0), 10_000);
+    let high_fee = candidate(outpoint(1), 20_000);
+    let unpaid = candidate(outpoint(2), 0);
+    let candidates = vec![paid.clone(), high_fee.clone(), unpaid.clone()];
+
+    // Higher fee weight ratios are selected first
+    assert_eq!(
+        select_transactions(candidates.clone(), MAX_BLOCK_BYTES, MAX_BLOCK_SIGOPS),
+        vec![high_fee.clone(), paid, unpaid]
+    );
+
+    // Size limit
+    let size = serialized_size(high_fee.transaction.as_ref()) as u64;
+    assert_eq!(
+        select_transactions(candidates.clone(), size, MAX_BLOCK_SIGOPS),
+        vec![high_fee.clone()]
+    );
+
+    // Sigop limit, each P2PKH output has one sigop
+    assert_eq!(
+        select_transactions(candidates, MAX_BLOCK_BYTES, 1),
+        vec![high_fee]
+    );
+
+    // Unpaid action limit, each unpaid transaction has two unpaid actions
+    let unpaid_candidates = (0..30).map(|index| candidate(outpoint(index), 0)).collect();
+    let selected = select_transactions(unpaid_candidates, MAX_BLOCK_BYTES, MAX_BLOCK_SIGOPS);
+    assert_eq!(selected.len() as u64, BLOCK_UNPAID_ACTION_LIMIT / 2);
+}
+
+#[test]
+fn parents_are_selected_before_children() {
+    zebra_test::init();
+
+    let parent = candidate(outpoint(0), 0);
+    let parent_hash = TransactionHash::from(parent.transaction.as_ref());
+    let child = candidate(
+        OutPoint {
+            hash: parent_hash,
+            index: 0,
+        },
+        40_000,
+    );
+
+    // The child has a higher fee, but it can't be mined before its parent
+    assert_eq!(
+        select_transactions(
+            vec![child.clone(), parent.clone()],
+            MAX_BLOCK_BYTES,
+            MAX_BLOCK_SIGOPS
+        ),
+        vec![parent.clone(), child]
+    );
+
+    // Children of unselected parents are not selected
+    let size = serialized_size(parent.transaction.as_ref()) as u64;
+    let other = candidate(outpoint(1), 20_000);
+    let child = candidate(
+        OutPoint {
+            hash: parent_hash,
+            index: 0,
+        },
+        40_000,
+    );
+    assert_eq!(
+        select_transactions(vec![child, parent, other.clone()], size, MAX_BLOCK_SIGOPS),
+        vec![other]
+    );
+}
@@ -18,25 +18,31 @@ use crate::checkpoint::CheckpointVerifier;
 
 use futures_util::FutureExt;
 use std::{
-    error,
+    collections::HashSet,
+    error, fmt,
     future::Future,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
+    time::Instant,
 };
 use tower::{buffer::Buffer, Service, ServiceExt};
 use tracing_futures::Instrument;
 
 use zebra_chain::block::{Block, BlockHeaderHash};
-use zebra_chain::types::BlockHeight;
+use zebra_chain::types::{BlockHeight, HeightDiff};
 use zebra_chain::Network;
+use zebra_state::KnownBlock;
 
 /// The maximum expected gap between blocks.
 ///
 /// Used to identify unexpected high blocks.
-const MAX_EXPECTED_BLOCK_GAP: u32 = 100_000;
+const MAX_EXPECTED_BLOCK_GAP: HeightDiff = 100_000;
 
 struct ChainVerifier<BV, S> {
+    /// The network of the verified blocks.
+    network: Network,
+
     /// The underlying `BlockVerifier`, possibly wrapped in other services.
     block_verifier: BV,
 
@@ -53,12 +59,59 @@ struct ChainVerifier<BV, S> {
     ///
     /// Not updated for unexpected high blocks.
     last_block_height: BlockHeight,
+
+    /// The hashes of the blocks that are currently being verified.
+    queued: Arc<Mutex<HashSet<BlockHeaderHash>>>,
 }
 
 /// The error type for the ChainVerifier Service.
 // TODO(jlusby): Error = Report ?
 type Error = Box<dyn error::Error + Send + Sync + 'static>;
 
+/// The error returned by the ChainVerifier for blocks that are already
+/// queued for verification, or already in the state.
+///
+/// Duplicate blocks are rejected before verification, so callers can
+/// downcast the verifier error to this type, and ignore it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DuplicateBlock {
+    /// The hash of the duplicate block.
+    pub hash: BlockHeaderHash,
+    /// Where the block is already known.
+    pub location: KnownBlock,
+}
+
+impl fmt::Display for DuplicateBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            KnownBlock::BestChain => write!(f, "block {:?} is already in the state", self.hash),
+            KnownBlock::Queue => write!(
+                f,
+                "block {:?} is already queued for verification",
+                self.hash
+            ),
+        }
+    }
+}
+
+impl error::Error for DuplicateBlock {}
+
+/// Removes a block hash from the verifier's queued set when it is dropped,
+/// even if the verification future is cancelled.
+struct QueuedBlockGuard {
+    hash: BlockHeaderHash,
+    queued: Arc<Mutex<HashSet<BlockHeaderHash>>>,
+}
+
+impl Drop for QueuedBlockGuard {
+    fn drop(&mut self) {
+        self.queued
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .remove(&self.hash);
+    }
+}
+
 /// The ChainVerifier service implementation.
 ///
 /// After verification, blocks are added to the underlying state service.
@@ -90,21 +143,40 @@ where
         let mut checkpoint_verifier = self.checkpoint_verifier.clone();
         let mut state_service = self.state_service.clone();
         let max_checkpoint_height = self.max_checkpoint_height;
+        let network = self.network;
 
+        let hash = block.hash();
         let span = tracing::debug_span!(
             "block_verify",
             height = ?block.coinbase_height(),
-            hash = ?BlockHeaderHash::from(block.as_ref())
+            ?hash
         );
         let height = block.coinbase_height();
 
+        // Reject duplicate blocks that are already being verified, without
+        // waiting for the state or verifiers
+        if !self
+            .queued
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .insert(hash)
+        {
+            metrics::counter!("chain.duplicate.queued", 1);
+            let error: Error = DuplicateBlock {
+                hash,
+                location: KnownBlock::Queue,
+            }
+            .into();
+            return async move { Err(error) }.boxed();
+        }
+        let queued_guard = QueuedBlockGuard {
+            hash,
+            queued: self.queued.clone(),
+        };
+
         // Log a warning on unexpected high blocks
         let is_unexpected_high_block = match height {
-            Some(BlockHeight(height))
-                if (height > self.last_block_height.0 + MAX_EXPECTED_BLOCK_GAP) =>
-            {
-                true
-            }
+            Some(height) if (height - self.last_block_height > MAX_EXPECTED_BLOCK_GAP) => true,
             Some(height) => {
                 // Update the last height if the block height was expected
                 self.last_block_height = height;
@@ -114,6 +186,25 @@ where
         };
 
         async move {
+            // Hold the guard until verification finishes or fails
+            let _queued_guard = queued_guard;
+
+            // Reject duplicate blocks that are already in the state, before
+            // verifying them
+            let known_block = state_service
+                .ready_and()
+                .await?
+                .call(zebra_state::Request::KnownBlock { hash })
+                .await?;
+            match known_block {
+                zebra_state::Response::KnownBlock(Some(location)) => {
+                    metrics::counter!("chain.duplicate.state", 1);
+                    Err(DuplicateBlock { hash, location })?;
+                }
+                zebra_state::Response::KnownBlock(None) => {}
+                _ => unreachable!("KnownBlock request can only result in Response::KnownBlock"),
+            }
+
             // TODO(teor): for post-sapling checkpoint blocks, allow callers
             //             to use BlockVerifier, CheckpointVerifier, or both.
 
@@ -145,10 +236,17 @@ where
             //   - handle chain reorgs
             //   - adjust state_service "unique block height" conditions
 
+            let deferred_pool_contribution = crate::parameters::deferred_pool_contribution(
+                network,
+                height.ok_or("verified blocks have a coinbase height")?,
+            );
             let add_block = state_service
                 .ready_and()
                 .await?
-                .call(zebra_state::Request::AddBlock { block });
+                .call(zebra_state::Request::AddBlock {
+                    block,
+                    deferred_pool_contribution,
+                });
 
             match add_block.await? {
                 zebra_state::Response::Added { hash } => Ok(hash),
@@ -163,7 +261,8 @@ where
 /// Return a chain verification service, using `network` and `state_service`.
 ///
 /// Gets the initial tip from the state service, and uses it to create a block
-/// verifier and checkpoint verifier.
+/// verifier and checkpoint verifier. The block verifier loads the Groth16
+/// parameters using `params_loader`.
 ///
 /// This function should only be called once for a particular state service. If
 /// you need shared block or checkpoint verfiers, create them yourself, and pass
@@ -175,6 +274,7 @@ where
 pub async fn init<S>(
     network: Network,
     state_service: S,
+    params_loader: crate::groth16::ParamsLoader,
 ) -> impl Service<
     Arc<Block>,
     Response = BlockHeaderHash,
@@ -202,14 +302,35 @@ where
         "initialising ChainVerifier with network and initial tip"
     );
 
-    let block_verifier = crate::block::init(state_service.clone());
+    let block_verifier = crate::block::init(network, state_service.clone(), params_loader);
     let checkpoint_verifier = CheckpointVerifier::new(network, initial_tip);
 
-    init_from_verifiers(block_verifier, checkpoint_verifier, state_service)
+    init_from_verifiers(network, block_verifier, checkpoint_verifier, state_service)
+}
+
+/// Load the verifier parameters that are expensive to initialize, so the
+/// first blocks that need them aren't delayed.
+///
+/// Loads the Groth16 parameters and builds their prepared verifying keys,
+/// downloading the parameters if they aren't in the cache directory.
+///
+/// The parameters are loaded on a blocking thread. On the first run, loading
+/// can take a long time, so callers should spawn this future, rather than
+/// waiting for it.
+pub async fn warm_up(params_loader: crate::groth16::ParamsLoader) -> Result<(), Error> {
+    let start = Instant::now();
+    tracing::info!("loading Groth16 parameters and verifying keys");
+    params_loader.load().await?;
+    tracing::info!(
+        elapsed = ?start.elapsed(),
+        "loaded Groth16 parameters and verifying keys"
+    );
+
+    Ok(())
 }
 
-/// Return a chain verification service, using the provided verifier and state
-/// services.
+/// Return a chain verification service for `network`, using the provided
+/// verifier and state services.
 ///
 /// The chain verifier holds a state service of type `S`, used as context for
 /// block validation and to which newly verified blocks will be committed. This
@@ -224,6 +345,7 @@ where
 /// multiple services from the same underlying state might cause synchronisation
 /// bugs.
 pub fn init_from_verifiers<BV, S>(
+    network: Network,
     block_verifier: BV,
     checkpoint_verifier: CheckpointVerifier,
     state_service: S,
@@ -255,6 +377,7 @@ where
 
     Buffer::new(
         ChainVerifier {
+            network,
             block_verifier,
             checkpoint_verifier,
             max_checkpoint_height,
@@ -263,6 +386,7 @@ where
             // because this field is only used for debugging unexpected high
             // blocks.
             last_block_height: BlockHeight(0),
+            queued: Arc::new(Mutex::new(HashSet::new())),
         },
         1,
     )
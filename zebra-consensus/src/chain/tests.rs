@@ -3,6 +3,7 @@
 use super::*;
 
 use crate::checkpoint::CheckpointList;
+use crate::groth16::ParamsLoader;
 
 use color_eyre::eyre::Report;
 use color_eyre::eyre::{bail, eyre};
@@ -14,6 +15,7 @@ use tower::{util::ServiceExt, Service};
 
 use zebra_chain::block::{Block, BlockHeader};
 use zebra_chain::serialization::ZcashDeserialize;
+use zebra_chain::types::amount::Amount;
 use zebra_chain::Network::{self, *};
 
 /// The timeout we apply to each verify future during testing.
@@ -61,11 +63,16 @@ fn verifiers_from_checkpoint_list(
         + 'static,
 ) {
     let state_service = zebra_state::in_memory::init();
-    let block_verifier = crate::block::init(state_service.clone());
+    let block_verifier =
+        crate::block::init(Mainnet, state_service.clone(), ParamsLoader::new(None));
     let checkpoint_verifier =
         crate::checkpoint::CheckpointVerifier::from_checkpoint_list(checkpoint_list, None);
-    let chain_verifier =
-        super::init_from_verifiers(block_verifier, checkpoint_verifier, state_service.clone());
+    let chain_verifier = super::init_from_verifiers(
+        Mainnet,
+        block_verifier,
+        checkpoint_verifier,
+        state_service.clone(),
+    );
 
     (chain_verifier, state_service)
 }
@@ -124,7 +131,17 @@ async fn verify_block() -> Result<(), Report> {
         checkpoint_data.iter().cloned().collect();
     let checkpoint_list = CheckpointList::from_list(checkpoint_list).map_err(|e| eyre!(e))?;
 
-    let (mut chain_verifier, _) = verifiers_from_checkpoint_list(checkpoint_list);
+    let (mut chain_verifier, state_service) = verifiers_from_checkpoint_list(checkpoint_list);
+
+    /// SPANDOC: Add the genesis block to the state
+    // The state needs the value pools before block 1
+    state_service
+        .oneshot(zebra_state::Request::AddBlock {
+            block: block0,
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await
+        .map_err(|e| eyre!(e))?;
 
     let block1 = Arc::<Block>::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?;
     let hash1: BlockHeaderHash = block1.as_ref().into();
@@ -167,7 +184,12 @@ async fn verify_checkpoint() -> Result<(), Report> {
 
     // Test that the chain::init function works. Most of the other tests use
     // init_from_verifiers.
-    let mut chain_verifier = super::init(Mainnet, zebra_state::in_memory::init()).await;
+    let mut chain_verifier = super::init(
+        Mainnet,
+        zebra_state::in_memory::init(),
+        ParamsLoader::new(None),
+    )
+    .await;
 
     /// SPANDOC: Make sure the verifier service is ready
     let ready_verifier_service = chain_verifier.ready_and().await.map_err(|e| eyre!(e))?;
@@ -343,14 +365,19 @@ async fn verify_fail_add_block_checkpoint() -> Result<(), Report> {
         Duration::from_secs(VERIFY_TIMEOUT_SECONDS),
         ready_verifier_service.call(block.clone()),
     );
-    /// SPANDOC: Verify the block for the first time
-    // TODO(teor): ignore duplicate block verifies?
-    // TODO(teor || jlusby): check error kind
-    let _ = verify_future
+    /// SPANDOC: Verify the block for the second time
+    let verify_error = verify_future
         .map_err(|e| eyre!(e))
         .await
         .expect("timeout should not happen")
         .unwrap_err();
+    assert_eq!(
+        verify_error.downcast_ref::<DuplicateBlock>(),
+        Some(&DuplicateBlock {
+            hash,
+            location: zebra_state::KnownBlock::BestChain,
+        })
+    );
 
     /// SPANDOC: Make sure the state service is ready (2/2)
     let ready_state_service = state_service.ready_and().await.map_err(|e| eyre!(e))?;
@@ -371,3 +398,48 @@ async fn verify_fail_add_block_checkpoint() -> Result<(), Report> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn verify_fail_duplicate_queued_test() -> Result<(), Report> {
+    verify_fail_duplicate_queued().await
+}
+
+/// Test that the chain verifier rejects blocks that are already queued for
+/// verification
+#[spandoc::spandoc]
+async fn verify_fail_duplicate_queued() -> Result<(), Report> {
+    zebra_test::init();
+
+    let block1: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?.into();
+    let hash1: BlockHeaderHash = block1.as_ref().into();
+
+    let (mut chain_verifier, _) = verifiers_from_network(Mainnet);
+
+    /// SPANDOC: Queue the block, which waits for the genesis block
+    let ready_verifier_service = chain_verifier.ready_and().await.map_err(|e| eyre!(e))?;
+    let first_verify_future = ready_verifier_service.call(block1.clone());
+
+    /// SPANDOC: Verify the same block while it is still queued
+    let ready_verifier_service = chain_verifier.ready_and().await.map_err(|e| eyre!(e))?;
+    let verify_error = timeout(
+        Duration::from_secs(VERIFY_TIMEOUT_SECONDS),
+        ready_verifier_service.call(block1.clone()),
+    )
+    .map_err(|e| eyre!(e))
+    .await
+    .expect("timeout should not happen")
+    .unwrap_err();
+
+    assert_eq!(
+        verify_error.downcast_ref::<DuplicateBlock>(),
+        Some(&DuplicateBlock {
+            hash: hash1,
+            location: zebra_state::KnownBlock::Queue,
+        })
+    );
+
+    drop(first_verify_future);
+
+    Ok(())
+}
@@ -10,6 +10,11 @@
 //! Verification starts at the first checkpoint, which is the genesis block for the
 //! configured network.
 //!
+//! Since each checkpoint range starts and ends at a hard-coded checkpoint hash,
+//! later checkpoint ranges can be verified before earlier ranges are complete.
+//! But their results are held until all the earlier blocks have been verified,
+//! so that callers commit blocks to the state in order.
+//!
 //! Verification is provided via a `tower::Service`, to support backpressure and batch
 //! verification.
 
@@ -39,7 +44,7 @@ use tokio::sync::oneshot;
 use tower::Service;
 
 use zebra_chain::block::{Block, BlockHeaderHash};
-use zebra_chain::types::BlockHeight;
+use zebra_chain::types::{BlockHeight, HeightDiff};
 use zebra_chain::Network;
 
 /// The inner error type for CheckpointVerifier.
@@ -81,6 +86,40 @@ pub const MAX_QUEUED_BLOCKS_PER_HEIGHT: usize = 4;
 /// constant amount of memory for the supporting data structures and futures.
 pub const MAX_CHECKPOINT_HEIGHT_GAP: usize = 2_000;
 
+/// The number of checkpoint ranges that the sync service should download in
+/// parallel.
+///
+/// The CheckpointVerifier verifies complete checkpoint ranges as soon as they
+/// are queued, but it holds verified blocks until all the earlier blocks
+/// have been verified. So memory usage increases with this value.
+pub const MAX_PARALLEL_CHECKPOINT_RANGES: usize = 4;
+
+/// Returns the verified tip height that the sync reaches on `network`, if it
+/// stops downloading blocks after `height`.
+///
+/// Blocks below the final checkpoint are committed in checkpoint ranges. So if
+/// `height` is below the final checkpoint, the verified tip stops at the
+/// highest checkpoint at or below `height`.
+pub fn reachable_height(network: Network, height: BlockHeight) -> BlockHeight {
+    let list = CheckpointList::new(network);
+    if height >= list.max_height() {
+        height
+    } else {
+        list.max_height_in_range(..=height)
+            .expect("checkpoint lists must contain the genesis block")
+    }
+}
+
+/// A checkpoint range which has been verified, but whose results are being held
+/// until all the earlier blocks have been verified.
+#[derive(Debug)]
+struct PreVerifiedRange {
+    /// The height of the checkpoint at the end of this range.
+    end: BlockHeight,
+    /// The valid blocks in this range, in height order.
+    blocks: Vec<QueuedBlock>,
+}
+
 /// A checkpointing block verifier.
 ///
 /// Verifies blocks using a supplied list of checkpoints. There must be at
@@ -109,6 +148,13 @@ pub struct CheckpointVerifier {
     /// genesis block.
     queued: BTreeMap<BlockHeight, QueuedBlockList>,
 
+    /// Checkpoint ranges that have been verified out of order, keyed by the
+    /// height of the checkpoint at the start of the range.
+    ///
+    /// These blocks are returned once the verifier's progress reaches the
+    /// start of the range.
+    pre_verified: BTreeMap<BlockHeight, PreVerifiedRange>,
+
     /// The current progress of this verifier.
     verifier_progress: Progress<BlockHeight>,
 }
@@ -192,6 +238,7 @@ impl CheckpointVerifier {
             checkpoint_list,
             initial_tip_hash,
             queued: BTreeMap::new(),
+            pre_verified: BTreeMap::new(),
             verifier_progress,
         }
     }
@@ -263,7 +310,7 @@ impl CheckpointVerifier {
         // it stops after the first gap.
         for (&height, _) in self.queued.range((Excluded(pending_height), Unbounded)) {
             // If the queued blocks are continuous.
-            if height == BlockHeight(pending_height.0 + 1) {
+            if Some(height) == pending_height.next() {
                 pending_height = height;
             } else {
                 break;
@@ -581,16 +628,7 @@ impl CheckpointVerifier {
                 // We kept all the matching blocks down to this height, in
                 // anticipation of the chain verifying. But the chain is
                 // incomplete, so we have to put them back in the queue.
-                //
-                // The order here shouldn't matter, but add the blocks in
-                // height order, for consistency.
-                for vblock in rev_valid_blocks.drain(..).rev() {
-                    let height = vblock
-                        .block
-                        .coinbase_height()
-                        .expect("queued blocks have a block height");
-                    self.queued.entry(height).or_default().push(vblock);
-                }
+                self.requeue_blocks(rev_valid_blocks);
 
                 // Make sure the current progress hasn't changed
                 assert_eq!(
@@ -661,6 +699,192 @@ impl CheckpointVerifier {
             "processing must cover all available checkpoints"
         );
     }
+
+    /// Put `rev_valid_blocks` back in the queue, after a failed checkpoint
+    /// range.
+    ///
+    /// `rev_valid_blocks` is in reverse chain order.
+    fn requeue_blocks(&mut self, rev_valid_blocks: Vec<QueuedBlock>) {
+        // The order here shouldn't matter, but add the blocks in
+        // height order, for consistency.
+        for vblock in rev_valid_blocks.into_iter().rev() {
+            let height = vblock
+                .block
+                .coinbase_height()
+                .expect("queued blocks have a block height");
+            self.queued.entry(height).or_default().push(vblock);
+        }
+    }
+
+    /// Returns true if there are queued blocks at every height in the
+    /// checkpoint range `(start, end]`.
+    fn is_range_queued(&self, start: BlockHeight, end: BlockHeight) -> bool {
+        // In maps, keys are unique, so counting the keys is enough
+        let queued_heights = self.queued.range((Excluded(start), Included(end))).count();
+        queued_heights as HeightDiff == end - start
+    }
+
+    /// Check any complete checkpoint ranges above the current checkpoint
+    /// range, and hold their valid blocks in `pre_verified`.
+    ///
+    /// Does nothing if verification has finished.
+    fn pre_verify_later_checkpoint_ranges(&mut self) {
+        // Later ranges start at or above the highest verified height
+        let verified_height = match self.previous_checkpoint_height() {
+            BeforeGenesis => BlockHeight(0),
+            InitialTip(height) | PreviousCheckpoint(height) => height,
+            FinalCheckpoint => return,
+        };
+        let max_queued_height = match self.queued.keys().next_back() {
+            Some(height) => *height,
+            None => return,
+        };
+
+        // Each later range starts at a checkpoint, and ends at the next
+        // checkpoint
+        let mut range_start = match self.checkpoint_list.min_height_in_range(verified_height..) {
+            Some(height) if height < max_queued_height => height,
+            _ => return,
+        };
+        while let Some(range_end) = self
+            .checkpoint_list
+            .min_height_in_range((Excluded(range_start), Included(max_queued_height)))
+        {
+            if !self.pre_verified.contains_key(&range_start)
+                && self.is_range_queued(range_start, range_end)
+            {
+                self.pre_verify_range(range_start, range_end);
+            }
+            range_start = range_end;
+        }
+
+        metrics::gauge!(
+            "checkpoint.pre_verified_ranges",
+            self.pre_verified.len() as i64
+        );
+    }
+
+    /// Check the queued blocks in the checkpoint range `(start, end]`, and
+    /// move the valid blocks to `pre_verified`.
+    ///
+    /// `start` and `end` must be consecutive checkpoints, and all the heights
+    /// in the range must have queued blocks.
+    fn pre_verify_range(&mut self, start: BlockHeight, end: BlockHeight) {
+        let start_hash = self
+            .checkpoint_list
+            .hash(start)
+            .expect("every checkpoint height must have a hash");
+        let mut expected_hash = self
+            .checkpoint_list
+            .hash(end)
+            .expect("every checkpoint height must have a hash");
+
+        let range_heights: Vec<BlockHeight> = self
+            .queued
+            .range((Excluded(start), Included(end)))
+            .rev()
+            .map(|(key, _)| *key)
+            .collect();
+        // A list of pending valid blocks, in reverse chain order
+        let mut rev_valid_blocks = Vec::new();
+
+        for current_height in range_heights {
+            let valid_qblock = self.process_height(current_height, expected_hash);
+            if let Some(qblock) = valid_qblock {
+                expected_hash = qblock.block.header.previous_block_hash;
+                rev_valid_blocks.push(qblock);
+            } else {
+                tracing::warn!(
+                    ?current_height,
+                    ?start,
+                    ?end,
+                    "No valid blocks at height in later checkpoint range"
+                );
+                self.requeue_blocks(rev_valid_blocks);
+                return;
+            }
+        }
+
+        // The checkpoint and the parent hash must match.
+        assert_eq!(
+            expected_hash, start_hash,
+            "the start checkpoint should match: bad checkpoint list, zebra bug, or bad chain"
+        );
+
+        tracing::info!(?start, ?end, "Pre-verified later checkpoint range");
+
+        let blocks = rev_valid_blocks.into_iter().rev().collect();
+        self.pre_verified
+            .insert(start, PreVerifiedRange { end, blocks });
+    }
+
+    /// Return the results for any pre-verified checkpoint ranges that start
+    /// at the verifier's current progress, in chain order.
+    fn release_pre_verified_ranges(&mut self) {
+        loop {
+            let verified_height = match self.previous_checkpoint_height() {
+                InitialTip(height) | PreviousCheckpoint(height) => height,
+                // Later ranges always start at or after the genesis block
+                BeforeGenesis => return,
+                FinalCheckpoint => {
+                    self.reject_pre_verified_ranges(..);
+                    return;
+                }
+            };
+
+            // Ranges below the current progress were verified in order, using
+            // duplicate blocks
+            self.reject_pre_verified_ranges(..verified_height);
+
+            let range = match self.pre_verified.remove(&verified_height) {
+                Some(range) => range,
+                None => return,
+            };
+
+            tracing::info!(start = ?verified_height, end = ?range.end, "Verified pre-verified checkpoint range");
+
+            for qblock in range.blocks {
+                let height = qblock
+                    .block
+                    .coinbase_height()
+                    .expect("queued blocks have a block height");
+
+                // Reject any blocks that were queued after this range was
+                // pre-verified
+                if let Some(mut qblocks) = self.queued.remove(&height) {
+                    for duplicate in qblocks.drain(..) {
+                        let _ = duplicate.tx.send(Err(
+                            "duplicate valid blocks at this height, only one was chosen".into(),
+                        ));
+                    }
+                }
+
+                // Sending can fail, but there's nothing we can do about it.
+                let _ = qblock.tx.send(Ok(qblock.hash));
+            }
+
+            self.update_progress(range.end);
+        }
+    }
+
+    /// Reject the pre-verified checkpoint ranges that start in `range`.
+    fn reject_pre_verified_ranges<R>(&mut self, range: R)
+    where
+        R: std::ops::RangeBounds<BlockHeight>,
+    {
+        let rejected: Vec<BlockHeight> = self.pre_verified.range(range).map(|(h, _)| *h).collect();
+        for start in rejected {
+            let range = self
+                .pre_verified
+                .remove(&start)
+                .expect("each entry is only removed once");
+            for qblock in range.blocks {
+                let _ = qblock
+                    .tx
+                    .send(Err("block height has already been verified".into()));
+            }
+        }
+    }
 }
 
 /// CheckpointVerifier rejects pending futures on drop.
@@ -683,6 +907,15 @@ impl Drop for CheckpointVerifier {
                     .send(Err("checkpoint verifier was dropped".into()));
             }
         }
+
+        for (_, range) in std::mem::take(&mut self.pre_verified) {
+            for qblock in range.blocks {
+                // Sending can fail, but there's nothing we can do about it.
+                let _ = qblock
+                    .tx
+                    .send(Err("checkpoint verifier was dropped".into()));
+            }
+        }
     }
 }
 
@@ -719,6 +952,11 @@ impl Service<Arc<Block>> for CheckpointVerifier {
         // TODO(teor): retry on failure (low priority, failures should be rare)
         self.process_checkpoint_range();
 
+        // Verify any complete checkpoint ranges above the current range, then
+        // return the results for any ranges that are now next in the chain.
+        self.pre_verify_later_checkpoint_ranges();
+        self.release_pre_verified_ranges();
+
         metrics::gauge!("checkpoint.queued_slots", self.queued.len() as i64);
 
         async move {
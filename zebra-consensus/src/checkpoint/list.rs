@@ -15,6 +15,7 @@ use std::{
 };
 
 use zebra_chain::block::BlockHeaderHash;
+use zebra_chain::parameters::testnet_parameters;
 use zebra_chain::types::BlockHeight;
 use zebra_chain::Network::{self, *};
 
@@ -43,7 +44,8 @@ impl FromStr for CheckpointList {
     /// Parse a string into a CheckpointList.
     ///
     /// Each line has one checkpoint, consisting of a `BlockHeight` and
-    /// `BlockHeaderHash`, separated by a single space.
+    /// `BlockHeaderHash`, separated by a single space. Hashes use the
+    /// big-endian hex byte order from zcashd.
     ///
     /// Assumes that the provided genesis checkpoint is correct.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -64,7 +66,19 @@ impl FromStr for CheckpointList {
 
 impl CheckpointList {
     /// Returns the hard-coded checkpoint list for `network`.
+    ///
+    /// If a Testnet genesis hash is configured, the hard-coded Testnet
+    /// checkpoints belong to a different chain, so the list only contains the
+    /// configured genesis block.
     pub fn new(network: Network) -> Self {
+        if let (Testnet, Some(hash)) = (
+            network,
+            testnet_parameters().and_then(|parameters| parameters.genesis_hash),
+        ) {
+            return CheckpointList::from_list(vec![(BlockHeight(0), hash)])
+                .expect("configured genesis checkpoint is valid");
+        }
+
         // parse calls CheckpointList::from_list
         let checkpoint_list: CheckpointList = match network {
             Mainnet => MAINNET_CHECKPOINTS
@@ -170,6 +184,14 @@ impl CheckpointList {
     {
         self.0.range(range).map(|(height, _)| *height).next_back()
     }
+
+    /// Return the block height of the lowest checkpoint in a sub-range.
+    pub fn min_height_in_range<R>(&self, range: R) -> Option<BlockHeight>
+    where
+        R: RangeBounds<BlockHeight>,
+    {
+        self.0.range(range).map(|(height, _)| *height).next()
+    }
 }
 
 #[cfg(test)]
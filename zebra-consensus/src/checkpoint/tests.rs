@@ -204,6 +204,85 @@ async fn multi_item_checkpoint_list() -> Result<(), Report> {
     Ok(())
 }
 
+#[tokio::test]
+async fn out_of_order_checkpoint_range_test() -> Result<(), Report> {
+    out_of_order_checkpoint_range().await
+}
+
+#[spandoc::spandoc]
+async fn out_of_order_checkpoint_range() -> Result<(), Report> {
+    zebra_test::init();
+
+    let block0 =
+        Arc::<Block>::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?;
+    let hash0: BlockHeaderHash = block0.as_ref().into();
+    let block1 = Arc::<Block>::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?;
+    let hash1: BlockHeaderHash = block1.as_ref().into();
+
+    // Make a checkpoint list containing both blocks, so each block is in a
+    // separate checkpoint range
+    let checkpoint_list: BTreeMap<BlockHeight, BlockHeaderHash> =
+        [(BlockHeight(0), hash0), (BlockHeight(1), hash1)]
+            .iter()
+            .cloned()
+            .collect();
+
+    let mut checkpoint_verifier =
+        CheckpointVerifier::from_list(checkpoint_list, None).map_err(|e| eyre!(e))?;
+
+    /// SPANDOC: Set up the future for block 1, before block 0
+    let verify_future1 = timeout(
+        Duration::from_secs(VERIFY_TIMEOUT_SECONDS),
+        checkpoint_verifier
+            .ready_and()
+            .map_err(|e| eyre!(e))
+            .await?
+            .call(block1.clone()),
+    );
+
+    // The later range is verified, but its result is held
+    assert_eq!(checkpoint_verifier.pre_verified.len(), 1);
+    assert!(checkpoint_verifier.queued.is_empty());
+    assert_eq!(
+        checkpoint_verifier.previous_checkpoint_height(),
+        BeforeGenesis
+    );
+
+    /// SPANDOC: Set up the future for block 0
+    let verify_future0 = timeout(
+        Duration::from_secs(VERIFY_TIMEOUT_SECONDS),
+        checkpoint_verifier
+            .ready_and()
+            .map_err(|e| eyre!(e))
+            .await?
+            .call(block0.clone()),
+    );
+
+    // Both ranges are now verified, in order
+    assert!(checkpoint_verifier.pre_verified.is_empty());
+    assert_eq!(
+        checkpoint_verifier.previous_checkpoint_height(),
+        FinalCheckpoint
+    );
+
+    /// SPANDOC: Wait for the responses for blocks 0 and 1
+    let verify_response0 = verify_future0
+        .map_err(|e| eyre!(e))
+        .await
+        .expect("timeout should not happen")
+        .expect("block 0 should verify");
+    let verify_response1 = verify_future1
+        .map_err(|e| eyre!(e))
+        .await
+        .expect("timeout should not happen")
+        .expect("block 1 should verify");
+
+    assert_eq!(verify_response0, hash0);
+    assert_eq!(verify_response1, hash1);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn continuous_blockchain_test() -> Result<(), Report> {
     continuous_blockchain(None).await?;
@@ -768,3 +847,27 @@ async fn hard_coded_mainnet() -> Result<(), Report> {
 
     Ok(())
 }
+
+#[test]
+fn reachable_height_uses_checkpoints() {
+    zebra_test::init();
+
+    for network in &[Network::Mainnet, Network::Testnet] {
+        let list = CheckpointList::new(*network);
+        let max_height = list.max_height();
+
+        // Checkpoints and heights after the final checkpoint are reachable
+        assert_eq!(reachable_height(*network, BlockHeight(0)), BlockHeight(0));
+        assert_eq!(reachable_height(*network, max_height), max_height);
+        let after_max = (max_height + 1).unwrap();
+        assert_eq!(reachable_height(*network, after_max), after_max);
+
+        // Other heights stop at the previous checkpoint
+        if max_height > BlockHeight(0) {
+            let before_max = (max_height - 1).unwrap();
+            let reachable = reachable_height(*network, before_max);
+            assert!(reachable <= before_max);
+            assert!(list.contains(reachable));
+        }
+    }
+}
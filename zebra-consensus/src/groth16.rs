@@ -0,0 +1,321 @@
+//! Async Groth16 batch verifier service
+
+pub mod params;
+
+#[cfg(test)]
+mod tests;
+
+pub use params::{Groth16Params, ParamsLoader, SaplingParams, SproutParams};
+
+use std::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bellman::{
+    gadgets::multipack,
+    groth16::{verify_proof, PreparedVerifyingKey, Proof},
+    SynthesisError, VerificationError,
+};
+use bls12_381::{Bls12, Scalar};
+use futures::future::{ready, Ready};
+use tokio::sync::broadcast::{channel, RecvError, Sender};
+use tower::Service;
+use tower_batch::{Batch, BatchControl};
+use tower_fallback::Fallback;
+
+use zebra_chain::{
+    ed25519_zebra,
+    proofs::Groth16Proof,
+    transaction::{JoinSplit, Transaction},
+    types::BlockHeight,
+    Network,
+};
+
+use crate::parameters::ConsensusRule;
+
+/// A Groth16 verification item, containing a proof and its public inputs.
+#[derive(Clone)]
+pub struct Item {
+    proof: Proof<Bls12>,
+    inputs: Vec<Scalar>,
+}
+
+impl fmt::Debug for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // bellman proofs don't implement Debug
+        f.debug_struct("Item")
+            .field("inputs", &self.inputs)
+            .finish()
+    }
+}
+
+impl From<(Proof<Bls12>, Vec<Scalar>)> for Item {
+    fn from((proof, inputs): (Proof<Bls12>, Vec<Scalar>)) -> Self {
+        Self { proof, inputs }
+    }
+}
+
+impl Item {
+    /// Create a verification item for a Sprout JoinSplit with a Groth16
+    /// proof.
+    ///
+    /// `pub_key` is the JoinSplit public key for the transaction containing
+    /// `joinsplit`. It is used to calculate `h_sig`.
+    pub fn from_sprout_joinsplit(
+        joinsplit: &JoinSplit<Groth16Proof>,
+        pub_key: &ed25519_zebra::VerificationKeyBytes,
+    ) -> Result<Self, Error> {
+        let proof = Proof::read(&joinsplit.zkproof.0[..]).map_err(|_| Error::Malformed)?;
+
+        let nullifiers: Vec<[u8; 32]> = joinsplit
+            .nullifiers
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .collect();
+        let vmacs: Vec<[u8; 32]> = joinsplit.vmacs.iter().cloned().map(Into::into).collect();
+        let pub_key: [u8; 32] = (*pub_key).into();
+
+        // hSig = BLAKE2b-256("ZcashComputehSig", randomSeed || nf_old || joinSplitPubKey)
+        let h_sig = blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(b"ZcashComputehSig")
+            .to_state()
+            .update(&joinsplit.random_seed[..])
+            .update(&nullifiers[0][..])
+            .update(&nullifiers[1][..])
+            .update(&pub_key[..])
+            .finalize();
+
+        // The primary input encoding from the Sprout circuit in zcashd.
+        let mut public_input = Vec::with_capacity((32 * 8) + (8 * 2));
+        public_input.extend_from_slice(&joinsplit.anchor.0[..]);
+        public_input.extend_from_slice(h_sig.as_bytes());
+        public_input.extend_from_slice(&nullifiers[0][..]);
+        public_input.extend_from_slice(&vmacs[0][..]);
+        public_input.extend_from_slice(&nullifiers[1][..]);
+        public_input.extend_from_slice(&vmacs[1][..]);
+        public_input.extend_from_slice(&joinsplit.commitments[0].0[..]);
+        public_input.extend_from_slice(&joinsplit.commitments[1].0[..]);
+        public_input.extend_from_slice(&u64::from(joinsplit.vpub_old).to_le_bytes());
+        public_input.extend_from_slice(&u64::from(joinsplit.vpub_new).to_le_bytes());
+
+        let inputs = multipack::compute_multipacking(&multipack::bytes_to_bits(&public_input));
+
+        Ok(Self { proof, inputs })
+    }
+
+    /// Verify this item on its own, using the prepared verifying key `pvk`.
+    pub fn verify_single(&self, pvk: &PreparedVerifyingKey<Bls12>) -> Result<(), Error> {
+        verify_proof(pvk, &self.proof, &self.inputs).map_err(Error::from)
+    }
+}
+
+/// Returns the verification items for the Sprout Groth16 proofs in
+/// `transaction`, which was mined in a block at `height` on `network`.
+///
+/// JoinSplits only use Groth16 proofs in V4 transactions, which are valid
+/// from Sapling activation. Earlier JoinSplits use BCTV14 proofs, so this
+/// function returns no items for them.
+pub fn sprout_joinsplit_items(
+    network: Network,
+    height: BlockHeight,
+    transaction: &Transaction,
+) -> Result<Vec<Item>, Error> {
+    if !ConsensusRule::SproutGroth16Proofs.is_active(network, height) {
+        return Ok(Vec::new());
+    }
+
+    match transaction.groth16_joinsplit_data() {
+        Some(joinsplit_data) => joinsplit_data
+            .joinsplits()
+            .map(|joinsplit| Item::from_sprout_joinsplit(joinsplit, &joinsplit_data.pub_key))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The error type for Groth16 verification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// At least one proof in the batch was invalid.
+    InvalidProof,
+    /// A proof or its inputs were malformed.
+    Malformed,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::InvalidProof => f.write_str("invalid Groth16 proof"),
+            Error::Malformed => f.write_str("malformed Groth16 proof or inputs"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<SynthesisError> for Error {
+    fn from(_: SynthesisError) -> Self {
+        Error::Malformed
+    }
+}
+
+impl From<VerificationError> for Error {
+    fn from(error: VerificationError) -> Self {
+        match error {
+            VerificationError::InvalidProof => Error::InvalidProof,
+            VerificationError::InvalidVerifyingKey => Error::Malformed,
+        }
+    }
+}
+
+/// A Groth16 verifier for a single circuit, which verifies batches, and
+/// falls back to verifying each item on its own if a batch fails.
+///
+/// The fallback means that one invalid proof only fails its own request,
+/// rather than every request in the same batch.
+#[derive(Clone)]
+pub struct FallbackVerifier {
+    /// The batch verifier, which is also used to flush batches.
+    batch: Batch<Verifier, Item>,
+    /// The single item verifier, used when a batch fails.
+    single: SingleVerifier,
+}
+
+impl FallbackVerifier {
+    /// Create a new fallback verifier for the circuit with the prepared
+    /// verifying key `pvk`.
+    ///
+    /// Use a `ParamsLoader` to get the prepared verifying key for each
+    /// circuit.
+    pub fn new(
+        pvk: Arc<PreparedVerifyingKey<Bls12>>,
+        max_items: usize,
+        max_latency: Duration,
+    ) -> Self {
+        Self {
+            batch: Batch::new(Verifier::new(pvk.clone()), max_items, max_latency),
+            single: SingleVerifier { pvk },
+        }
+    }
+
+    /// Returns a verification service that uses this verifier's batches,
+    /// with a single item fallback.
+    pub fn service(&self) -> Fallback<Batch<Verifier, Item>, SingleVerifier> {
+        Fallback::new(self.batch.clone(), self.single.clone())
+    }
+
+    /// Asks the batch worker to flush the current batch.
+    ///
+    /// See `Batch::flush` for details.
+    pub fn flush(&self) {
+        self.batch.flush();
+    }
+}
+
+/// Groth16 proof verifier service, for a single circuit, which verifies each
+/// item on its own.
+#[derive(Clone)]
+pub struct SingleVerifier {
+    /// The prepared verifying key for the circuit this verifier checks.
+    pvk: Arc<PreparedVerifyingKey<Bls12>>,
+}
+
+impl Service<Item> for SingleVerifier {
+    type Response = ();
+    type Error = Error;
+    type Future = Ready<Result<(), Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, item: Item) -> Self::Future {
+        ready(item.verify_single(&self.pvk))
+    }
+}
+
+/// Groth16 proof batch verifier service, for a single circuit.
+///
+/// Use a `ParamsLoader` to get the prepared verifying key for each circuit.
+pub struct Verifier {
+    /// The prepared verifying key for the circuit this verifier checks.
+    pvk: Arc<PreparedVerifyingKey<Bls12>>,
+    /// The queued items in the current batch.
+    batch: Vec<Item>,
+    // This uses a "broadcast" channel, which is an mpmc channel. See the
+    // RedJubjub verifier for details.
+    tx: Sender<Result<(), Error>>,
+}
+
+impl Verifier {
+    /// Create a new Groth16 verifier, using the prepared verifying key `pvk`.
+    pub fn new(pvk: Arc<PreparedVerifyingKey<Bls12>>) -> Self {
+        // XXX(hdevalence) what's a reasonable choice here?
+        let (tx, _) = channel(10);
+        Self {
+            pvk,
+            batch: Vec::new(),
+            tx,
+        }
+    }
+
+    /// Verify all the queued items, and send the result to any pending
+    /// futures.
+    fn flush(&mut self) {
+        let batch = mem::take(&mut self.batch);
+        let pvk = &self.pvk;
+        let result = batch.iter().try_for_each(|item| item.verify_single(pvk));
+        let _ = self.tx.send(result);
+    }
+}
+
+impl Service<BatchControl<Item>> for Verifier {
+    type Response = ();
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: BatchControl<Item>) -> Self::Future {
+        match req {
+            BatchControl::Item(item) => {
+                tracing::trace!("got item");
+                self.batch.push(item);
+                let mut rx = self.tx.subscribe();
+                Box::pin(async move {
+                    match rx.recv().await {
+                        Ok(result) => result,
+                        Err(RecvError::Lagged(_)) => {
+                            tracing::warn!("missed channel updates for the correct proof batch!");
+                            Err(Error::InvalidProof)
+                        }
+                        Err(RecvError::Closed) => panic!("verifier was dropped without flushing"),
+                    }
+                })
+            }
+
+            BatchControl::Flush => {
+                tracing::trace!("got flush command");
+                self.flush();
+                Box::pin(async { Ok(()) })
+            }
+        }
+    }
+}
+
+impl Drop for Verifier {
+    fn drop(&mut self) {
+        // We need to flush the current batch in case there are still any pending futures.
+        self.flush();
+    }
+}
@@ -0,0 +1,262 @@
+//! Loading and caching of the Sapling and Sprout Groth16 parameters.
+//!
+//! Zebra does not rely on zcashd's `fetch-params.sh` script. Instead, the
+//! parameter files are downloaded on first use, checked against their known
+//! BLAKE2b-512 hashes, and cached in the Zebra cache directory.
+
+use std::{
+    fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bellman::groth16::{prepare_verifying_key, PreparedVerifyingKey, VerifyingKey};
+use bls12_381::Bls12;
+
+/// The inner error type for parameter loading.
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The base URL for the Zcash parameter downloads.
+const DOWNLOAD_URL: &str = "https://download.z.cash/downloads";
+
+/// The subdirectory of the Zebra cache directory that contains the parameters.
+const PARAMS_DIR: &str = "params";
+
+/// A Groth16 parameter file, with its expected hash.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ParamFile {
+    /// The name of the file, both on disk and on the download server.
+    pub(crate) name: &'static str,
+    /// The hex-encoded BLAKE2b-512 hash of the file contents.
+    pub(crate) hash: &'static str,
+}
+
+/// The Sapling Spend circuit parameters.
+pub(crate) const SAPLING_SPEND: ParamFile = ParamFile {
+    name: "sapling-spend.params",
+    hash: "8270785a1a0d0bc77196f000ee6d221c9c9894f55307bd9357c3f0105d31ca63991ab91324160d8f53e2bbd3c2633a6eb8bdf5205d822e7f3f73edac51b2b70c",
+};
+
+/// The Sapling Output circuit parameters.
+pub(crate) const SAPLING_OUTPUT: ParamFile = ParamFile {
+    name: "sapling-output.params",
+    hash: "657e3d38dbb5cb5e7dd2970e8b03d69b4787dd907285b5a7f0790dcc8072f60bf593b32cc2d1c030e00ff5ae64bf84c5c3beb84ddc841d48264b4a171744d028",
+};
+
+/// The Sprout JoinSplit circuit parameters, for Groth16 proofs.
+///
+/// After Sapling activation, Sprout JoinSplits use Groth16 proofs, rather
+/// than BCTV14 proofs.
+pub(crate) const SPROUT_GROTH16: ParamFile = ParamFile {
+    name: "sprout-groth16.params",
+    hash: "e9b238411bd6c0ec4791e9d04245ec350c9c5744f5610dfcce4365d5ca49dfefd5054e371842b3f88fa1b9d7e8e075249b3ebabd167fa8b0f3161292d36c180a",
+};
+
+/// Loads the Groth16 parameters from a parameter directory, the first time
+/// they are needed.
+///
+/// Clones of a loader share the loaded parameters, so the parameter files
+/// are only read once.
+#[derive(Clone)]
+pub struct ParamsLoader {
+    /// The directory that contains the parameter files, or `None` if there
+    /// is no cache directory.
+    params_dir: Option<PathBuf>,
+    /// The loaded parameters, if they have already been loaded.
+    params: Arc<Mutex<Option<Arc<Groth16Params>>>>,
+}
+
+impl ParamsLoader {
+    /// Returns a loader for the parameters in the `params` subdirectory of
+    /// `cache_dir`.
+    ///
+    /// If `cache_dir` is `None`, loading the parameters fails.
+    pub fn new(cache_dir: Option<&Path>) -> Self {
+        Self {
+            params_dir: cache_dir.map(|cache_dir| cache_dir.join(PARAMS_DIR)),
+            params: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the parameters, loading them if needed.
+    ///
+    /// Loading reads and hashes large files, and may download them, so this
+    /// function must not be called from an async task.
+    pub fn load_blocking(&self) -> Result<Arc<Groth16Params>, Error> {
+        // Holding the lock while loading stops concurrent callers from
+        // downloading the same files.
+        let mut params = self.params.lock().expect("no panics while loading params");
+
+        if let Some(params) = params.as_ref() {
+            return Ok(params.clone());
+        }
+
+        let params_dir = self
+            .params_dir
+            .as_ref()
+            .ok_or("a cache directory is required to store the Groth16 parameters")?;
+        let loaded = Arc::new(Groth16Params::load(params_dir)?);
+        *params = Some(loaded.clone());
+
+        Ok(loaded)
+    }
+
+    /// Returns the parameters, loading them on a blocking thread if needed.
+    pub async fn load(&self) -> Result<Arc<Groth16Params>, Error> {
+        let loader = self.clone();
+        tokio::task::spawn_blocking(move || loader.load_blocking()).await?
+    }
+}
+
+/// Groth16 Zero-Knowledge Proof prepared verifying keys, for all the circuits
+/// Zebra verifies.
+///
+/// Zebra only verifies proofs, so it doesn't keep the proving parameters in
+/// memory.
+pub struct Groth16Params {
+    /// The Sapling circuit verifying keys.
+    pub sapling: SaplingParams,
+    /// The Sprout circuit verifying key.
+    pub sprout: SproutParams,
+}
+
+impl Groth16Params {
+    /// Load the verifying keys from `params_dir`, downloading any missing
+    /// parameter files.
+    pub fn load(params_dir: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            sapling: SaplingParams::load(params_dir)?,
+            sprout: SproutParams::load(params_dir)?,
+        })
+    }
+}
+
+/// The Groth16 prepared verifying keys for the Sapling circuits.
+pub struct SaplingParams {
+    /// The prepared verifying key for the Spend circuit.
+    pub spend_prepared_verifying_key: Arc<PreparedVerifyingKey<Bls12>>,
+    /// The prepared verifying key for the Output circuit.
+    pub output_prepared_verifying_key: Arc<PreparedVerifyingKey<Bls12>>,
+}
+
+impl SaplingParams {
+    /// Load the Sapling verifying keys from `params_dir`, downloading any
+    /// missing files.
+    pub fn load(params_dir: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            spend_prepared_verifying_key: read_prepared_verifying_key(&fetch(
+                params_dir,
+                SAPLING_SPEND,
+            )?)?,
+            output_prepared_verifying_key: read_prepared_verifying_key(&fetch(
+                params_dir,
+                SAPLING_OUTPUT,
+            )?)?,
+        })
+    }
+}
+
+/// The Groth16 prepared verifying key for the Sprout JoinSplit circuit.
+pub struct SproutParams {
+    /// The prepared verifying key for the JoinSplit circuit.
+    pub joinsplit_prepared_verifying_key: Arc<PreparedVerifyingKey<Bls12>>,
+}
+
+impl SproutParams {
+    /// Load the Sprout verifying key from `params_dir`, downloading the
+    /// parameter file if it is missing.
+    pub fn load(params_dir: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            joinsplit_prepared_verifying_key: read_prepared_verifying_key(&fetch(
+                params_dir,
+                SPROUT_GROTH16,
+            )?)?,
+        })
+    }
+}
+
+/// Returns the path to `file` in `params_dir`, downloading it if it is
+/// missing.
+///
+/// Cached files are checked against their expected hash. If a cached file is
+/// corrupt, it is deleted and downloaded again.
+pub(crate) fn fetch(params_dir: &Path, file: ParamFile) -> Result<PathBuf, Error> {
+    let path = params_dir.join(file.name);
+
+    if path.exists() {
+        match verify_hash(&path, file) {
+            Ok(()) => return Ok(path),
+            Err(error) => {
+                tracing::warn!(?path, ?error, "removing corrupt cached parameter file");
+                fs::remove_file(&path)?;
+            }
+        }
+    }
+
+    download(&path, file)?;
+    verify_hash(&path, file)?;
+
+    Ok(path)
+}
+
+/// Download `file` to `path`.
+///
+/// The file is written to a temporary path, then renamed, so interrupted
+/// downloads don't leave partial files in the cache.
+fn download(path: &Path, file: ParamFile) -> Result<(), Error> {
+    let url = format!("{}/{}", DOWNLOAD_URL, file.name);
+    tracing::info!(
+        ?url,
+        ?path,
+        "downloading Groth16 parameters, this may take a while"
+    );
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let response = minreq::get(&url).send()?;
+    if response.status_code != 200 {
+        Err(format!(
+            "downloading {} failed with HTTP status {}",
+            url, response.status_code
+        ))?;
+    }
+
+    let partial_path = path.with_extension("part");
+    fs::write(&partial_path, response.as_bytes())?;
+    fs::rename(&partial_path, path)?;
+
+    Ok(())
+}
+
+/// Check that the file at `path` matches the expected hash for `file`.
+pub(crate) fn verify_hash(path: &Path, file: ParamFile) -> Result<(), Error> {
+    let mut state = blake2b_simd::Params::new().hash_length(64).to_state();
+    io::copy(&mut BufReader::new(fs::File::open(path)?), &mut state)?;
+    let hash = state.finalize();
+
+    if hash.to_hex().as_str() != file.hash {
+        Err(format!(
+            "{} has hash {}, expected {}",
+            file.name,
+            hash.to_hex(),
+            file.hash
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Read the verifying key from the parameter file at `path`, and prepare it
+/// for verification.
+///
+/// The verifying key is at the start of the parameter file, so the rest of
+/// the file is not parsed.
+fn read_prepared_verifying_key(path: &Path) -> Result<Arc<PreparedVerifyingKey<Bls12>>, Error> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    let vk = VerifyingKey::<Bls12>::read(reader)?;
+
+    Ok(Arc::new(prepare_verifying_key(&vk)))
+}
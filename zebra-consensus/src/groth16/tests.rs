@@ -0,0 +1,80 @@
+//! Tests for Groth16 parameter loading and proof items
+
+use super::params::*;
+use super::*;
+
+use std::fs;
+
+use color_eyre::eyre::Result;
+use tempdir::TempDir;
+
+use zebra_chain::{block::Block, serialization::ZcashDeserialize, Network::*};
+
+#[test]
+fn corrupt_params_file_is_rejected() -> Result<()> {
+    zebra_test::init();
+
+    let dir = TempDir::new("zebra_groth16_params")?;
+    let path = dir.path().join(SAPLING_OUTPUT.name);
+    fs::write(&path, b"not the sapling output parameters")?;
+
+    assert!(verify_hash(&path, SAPLING_OUTPUT).is_err());
+    assert!(verify_hash(&path, SAPLING_SPEND).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn missing_params_file_is_rejected() -> Result<()> {
+    zebra_test::init();
+
+    let dir = TempDir::new("zebra_groth16_params")?;
+    let path = dir.path().join(SAPLING_SPEND.name);
+
+    assert!(verify_hash(&path, SAPLING_SPEND).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn sprout_joinsplit_items_before_sapling() -> Result<()> {
+    zebra_test::init();
+
+    let block = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..])?;
+    let height = block
+        .coinbase_height()
+        .expect("block has a coinbase height");
+
+    for transaction in block.transactions.iter() {
+        assert!(sprout_joinsplit_items(Mainnet, height, transaction)?.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sprout_joinsplit_items_after_sapling() -> Result<()> {
+    zebra_test::init();
+
+    let block = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_434873_BYTES[..])?;
+    let height = block
+        .coinbase_height()
+        .expect("block has a coinbase height");
+
+    for transaction in block.transactions.iter() {
+        let expected_items = match transaction.as_ref() {
+            Transaction::V4 {
+                joinsplit_data: Some(joinsplit_data),
+                ..
+            } => joinsplit_data.joinsplits().count(),
+            _ => 0,
+        };
+
+        assert_eq!(
+            sprout_joinsplit_items(Mainnet, height, transaction)?.len(),
+            expected_items
+        );
+    }
+
+    Ok(())
+}
@@ -16,10 +16,14 @@
 #![allow(clippy::try_err)]
 
 pub mod block;
+pub mod block_template;
 pub mod chain;
 pub mod checkpoint;
+pub mod groth16;
 pub mod mempool;
 pub mod parameters;
 pub mod redjubjub;
 mod script;
 mod transaction;
+
+pub use transaction::{TransactionError, UTXO_LOOKUP_TIMEOUT};
@@ -10,20 +10,388 @@
 //! The mempool is provided via a `tower::Service`, to support backpressure and batch
 //! verification.
 
+use std::{
+    collections::HashMap,
+    error,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tower::{Service, ServiceExt};
+
+use zebra_chain::{
+    block::BlockHeaderHash,
+    transaction::{OutPoint, TransactionHash, TransparentOutput},
+    types::{BlockHeight, HeightDiff},
+    Network,
+};
+
+use crate::{
+    block_template::MempoolTransaction,
+    transaction::{self, best_chain_transaction, is_expiring_soon, TransactionVerifier},
+};
+
+pub use zebra_node_services::mempool::{
+    MempoolChange, MempoolError, RemovalReason, Request, Response,
+};
+
+pub mod policy;
+
+#[cfg(test)]
+mod tests;
+
+/// Mempool policy configuration.
+///
+/// The defaults match zcashd's standard transaction policy.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Config {
+    /// The minimum fee rate for relayed transactions, in zatoshis per 1000
+    /// bytes.
+    ///
+    /// This rate is also used to calculate the dust threshold for outputs.
+    pub min_relay_fee_per_kb: u64,
+
+    /// The maximum serialized size of a relayed transaction, in bytes.
+    pub max_standard_tx_size: usize,
+
+    /// The maximum size of each transparent input script, in bytes.
+    pub max_script_sig_size: usize,
+
+    /// The maximum size of a data (`OP_RETURN`) output script, in bytes.
+    pub max_data_carrier_size: usize,
+
+    /// The highest transaction version that is relayed.
+    pub max_standard_version: u32,
+
+    /// The maximum number of ZIP-317 unpaid logical actions in a relayed
+    /// transaction.
+    pub tx_unpaid_action_limit: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_relay_fee_per_kb: 100,
+            max_standard_tx_size: 100_000,
+            max_script_sig_size: 1_650,
+            max_data_carrier_size: 223,
+            max_standard_version: 4,
+            tx_unpaid_action_limit: 50,
+        }
+    }
+}
+
+/// The number of blocks before its expiry height that a transaction stops
+/// being accepted into the mempool.
+///
+/// This matches `TX_EXPIRING_SOON_THRESHOLD` in zcashd, so transactions
+/// have time to be mined before they expire.
+pub const TX_EXPIRING_SOON_THRESHOLD: HeightDiff = 3;
+
+/// The number of blocks before a coinbase output can be spent.
+pub const COINBASE_MATURITY: HeightDiff = 100;
+
+/// The number of mempool changes that are buffered for each subscriber.
+///
+/// Subscribers that fall further behind miss some changes.
+pub const MEMPOOL_CHANGE_BUFFER: usize = 1_000;
+
+/// The error type for the mempool services.
+type Error = Box<dyn error::Error + Send + Sync + 'static>;
+
 /// Mempool state.
 ///
 /// New transactions are verified, checked against the chain state, then added to the
 /// mempool.
 ///
-/// `ZebraMempoolState` is not yet implemented.
-#[derive(Default)]
-struct ZebraMempoolState {}
+/// Clones share the same transactions.
+#[derive(Clone, Debug)]
+pub struct ZebraMempoolState {
+    inner: Arc<Mutex<MempoolInner>>,
+    /// Sends each change to the mempool transactions to subscribers.
+    changes: broadcast::Sender<MempoolChange>,
+}
+
+impl Default for ZebraMempoolState {
+    fn default() -> Self {
+        let (changes, _) = broadcast::channel(MEMPOOL_CHANGE_BUFFER);
+        ZebraMempoolState {
+            inner: Default::default(),
+            changes,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MempoolInner {
+    /// The verified transactions, by hash.
+    transactions: HashMap<TransactionHash, MempoolTransaction>,
+    /// The hash of the mempool transaction that spends each outpoint.
+    spent: HashMap<OutPoint, TransactionHash>,
+    /// The best chain tip that the transactions were last checked against.
+    tip: Option<BlockHeaderHash>,
+}
+
+impl ZebraMempoolState {
+    /// Returns true if the transaction with `hash` is in the mempool.
+    pub fn contains(&self, hash: &TransactionHash) -> bool {
+        self.lock().transactions.contains_key(hash)
+    }
+
+    /// Returns the transactions in the mempool, and their fees.
+    pub fn transactions(&self) -> Vec<MempoolTransaction> {
+        self.lock().transactions.values().cloned().collect()
+    }
+
+    /// Returns a receiver for the changes to the mempool transactions, after
+    /// this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<MempoolChange> {
+        self.changes.subscribe()
+    }
+
+    /// Returns the output spent by `outpoint`, if it was created by a
+    /// mempool transaction.
+    pub(crate) fn output(&self, outpoint: &OutPoint) -> Option<TransparentOutput> {
+        self.lock()
+            .transactions
+            .get(&outpoint.hash)
+            .and_then(|tx| tx.transaction.outputs().nth(outpoint.index as usize))
+            .cloned()
+    }
+
+    /// Adds `transaction` to the mempool, if none of its inputs are already
+    /// spent by other mempool transactions.
+    fn insert(&self, transaction: MempoolTransaction) -> Result<TransactionHash, MempoolError> {
+        let mut inner = self.lock();
+        let hash = TransactionHash::from(&*transaction.transaction);
+        if inner.transactions.contains_key(&hash) {
+            return Err(MempoolError::AlreadyInMempool);
+        }
+
+        if let Some(outpoint) = transaction
+            .transaction
+            .spent_outpoints()
+            .find(|outpoint| inner.spent.contains_key(outpoint))
+        {
+            return Err(MempoolError::Conflict(*outpoint));
+        }
+
+        let added = transaction.transaction.clone();
+        for outpoint in added.spent_outpoints() {
+            inner.spent.insert(*outpoint, hash);
+        }
+        inner.transactions.insert(hash, transaction);
+        drop(inner);
+
+        // Sending fails if there are no subscribers
+        let _ = self.changes.send(MempoolChange::Added(added));
+        Ok(hash)
+    }
+
+    /// Records that the best chain tip is `tip`, and returns the hashes of
+    /// the mempool transactions if it has changed since the last call.
+    fn update_tip(&self, tip: BlockHeaderHash) -> Option<Vec<TransactionHash>> {
+        let mut inner = self.lock();
+        if inner.tip == Some(tip) {
+            return None;
+        }
+
+        inner.tip = Some(tip);
+        Some(inner.transactions.keys().cloned().collect())
+    }
+
+    /// Removes the transaction with `hash` from the mempool, because of
+    /// `reason`.
+    fn remove(&self, hash: &TransactionHash, reason: RemovalReason) -> Option<MempoolTransaction> {
+        let mut inner = self.lock();
+        let removed = inner.transactions.remove(hash);
+        if let Some(removed) = removed.as_ref() {
+            for outpoint in removed.transaction.spent_outpoints() {
+                inner.spent.remove(outpoint);
+            }
+        }
+        drop(inner);
+
+        if removed.is_some() {
+            let _ = self.changes.send(MempoolChange::Removed {
+                hash: *hash,
+                reason,
+            });
+        }
+        removed
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MempoolInner> {
+        self.inner.lock().expect("mutex should be unpoisoned")
+    }
+}
 
 /// Mempool transaction verification.
 ///
 /// New transactions are verified, checked against the chain state, then added to the
-/// mempool.
+/// mempool. The verifier responds to `Queue` requests with the hash of the
+/// added transaction, or a [`MempoolError`] if the transaction was rejected.
+///
+/// Transactions can spend outputs from the best chain, or from other mempool
+/// transactions. Zebra doesn't track spent outputs in the chain state yet.
 ///
-/// `MempoolTransactionVerifier` is not yet implemented.
-#[derive(Default)]
-struct MempoolTransactionVerifier {}
+/// Zebra doesn't verify transaction scripts, signatures, or shielded proofs
+/// yet, so transactions that have any are rejected with
+/// [`MempoolError::Unverifiable`], after the other checks. Otherwise, Zebra
+/// would accept and relay invalid transactions.
+pub struct MempoolTransactionVerifier<S> {
+    /// The underlying `ZebraState`, possibly wrapped in other services.
+    state_service: S,
+
+    /// The mempool that verified transactions are added to.
+    mempool: ZebraMempoolState,
+
+    /// The verifier for mempool transactions, which uses the standard
+    /// transaction policy.
+    transaction_verifier: TransactionVerifier<S>,
+}
+
+/// Return a mempool transaction verifier for `network`, which checks
+/// transactions against `state_service`, and adds them to `mempool`.
+pub fn init<S>(
+    network: Network,
+    config: Config,
+    state_service: S,
+    mempool: ZebraMempoolState,
+) -> MempoolTransactionVerifier<S>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    MempoolTransactionVerifier {
+        transaction_verifier: TransactionVerifier::new(network, state_service.clone())
+            .with_mempool(config, mempool.clone()),
+        state_service,
+        mempool,
+    }
+}
+
+impl<S> Service<Request> for MempoolTransactionVerifier<S>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // We expect state queries to be fast, so we don't need to call
+        // `state_service.poll_ready()` here.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let Request::Queue(transaction) = request;
+        let state = self.state_service.clone();
+        let mempool = self.mempool.clone();
+        let transaction_verifier = self.transaction_verifier.clone();
+
+        async move {
+            let (tip_hash, tip_height) = tip(state.clone()).await?;
+            let next_height = tip_height
+                .next()
+                .expect("valid chain tips are below the maximum height");
+            if let Some(hashes) = mempool.update_tip(tip_hash) {
+                remove_stale(state.clone(), &mempool, hashes, next_height).await?;
+            }
+
+            let fee = match transaction_verifier
+                .oneshot(transaction::Request::Mempool {
+                    transaction: transaction.clone(),
+                    height: next_height,
+                })
+                .await?
+            {
+                transaction::Response::Mempool { fee, .. } => fee,
+                _ => unreachable!("Mempool request can only result in Response::Mempool"),
+            };
+
+            let hash = mempool.insert(MempoolTransaction { transaction, fee })?;
+            tracing::debug!(?hash, %fee, "added transaction to mempool");
+            metrics::gauge!(
+                "mempool.transactions",
+                mempool.lock().transactions.len() as i64
+            );
+
+            Ok(Response::Queued(hash))
+        }
+        .boxed()
+    }
+}
+
+/// Returns the hash and height of the best chain tip.
+async fn tip<S>(state: S) -> Result<(BlockHeaderHash, BlockHeight), Error>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error> + Clone,
+{
+    let hash = match state.clone().oneshot(zebra_state::Request::GetTip).await? {
+        zebra_state::Response::Tip { hash } => hash,
+        _ => unreachable!("GetTip request can only result in Response::Tip"),
+    };
+    let height = match state
+        .oneshot(zebra_state::Request::GetBlock { hash })
+        .await?
+    {
+        zebra_state::Response::Block { block } => block
+            .coinbase_height()
+            .expect("blocks in the state have a coinbase height"),
+        _ => unreachable!("GetBlock request can only result in Response::Block"),
+    };
+
+    Ok((hash, height))
+}
+
+/// Removes the transactions in `hashes` that have been mined, or that expire
+/// too soon to be mined in the block at `next_height`.
+async fn remove_stale<S>(
+    state: S,
+    mempool: &ZebraMempoolState,
+    hashes: Vec<TransactionHash>,
+    next_height: BlockHeight,
+) -> Result<(), Error>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error> + Clone,
+{
+    for hash in hashes {
+        let expired = mempool
+            .lock()
+            .transactions
+            .get(&hash)
+            .map(|tx| is_expiring_soon(&tx.transaction, next_height))
+            .unwrap_or(false);
+
+        let reason = if expired {
+            Some(RemovalReason::Expired)
+        } else if best_chain_transaction(state.clone(), hash).await?.is_some() {
+            Some(RemovalReason::Mined)
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            mempool.remove(&hash, reason);
+            tracing::debug!(?hash, expired, "removed stale transaction from mempool");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Standard transaction policy for the mempool.
+//!
+//! These checks are not consensus rules: a block can contain non-standard
+//! transactions. But zcashd nodes refuse to relay non-standard transactions,
+//! so Zebra applies the same policy before accepting a transaction into its
+//! mempool.
+//!
+//! The checks and defaults match `IsStandardTx` and `AcceptToMemoryPool` in
+//! zcashd.
+
+use zebra_chain::{
+    serialization::ZcashSerialize,
+    transaction::{Transaction, TransparentInput, TransparentOutput},
+    types::{
+        amount::{Amount, NonNegative},
+        script::ScriptKind,
+    },
+};
+
+pub use zebra_node_services::mempool::PolicyError;
+
+use super::Config;
+
+/// The serialized size of a standard P2PKH input, which is used to estimate
+/// the cost of spending an output in the dust calculation.
+const SPEND_INPUT_SIZE: u64 = 148;
+
+/// Returns the serialized size of `item`.
+fn serialized_size<T: ZcashSerialize>(item: &T) -> usize {
+    item.zcash_serialize_to_vec()
+        .expect("serializing into a vec is infallible")
+        .len()
+}
+
+/// Returns the fee for `size` bytes at `fee_per_kb` zatoshis per 1000 bytes.
+///
+/// Like zcashd, any non-empty transaction pays at least `fee_per_kb`
+/// zatoshis, if the fee rate is non-zero.
+pub fn fee_for_size(fee_per_kb: u64, size: usize) -> u64 {
+    let fee = fee_per_kb.saturating_mul(size as u64) / 1000;
+    if fee == 0 && size > 0 {
+        fee_per_kb
+    } else {
+        fee
+    }
+}
+
+/// Returns the minimum value for `output`, using the relay fee in `config`.
+///
+/// An output is dust if spending it would cost more than a third of its
+/// value in relay fees. Data outputs are unspendable, so they are never dust.
+pub fn dust_threshold(config: &Config, output: &TransparentOutput) -> u64 {
+    if output.pk_script.is_null_data() {
+        return 0;
+    }
+
+    let size = serialized_size(output) as u64 + SPEND_INPUT_SIZE;
+    3 * fee_for_size(config.min_relay_fee_per_kb, size as usize)
+}
+
+/// Check that `transaction` is standard, using the limits in `config`.
+///
+/// Coinbase transactions are never relayed, so callers should reject them
+/// before calling this function.
+pub fn check_standard(config: &Config, transaction: &Transaction) -> Result<(), PolicyError> {
+    let version = transaction.version();
+    if version < 1 || version > config.max_standard_version {
+        return Err(PolicyError::Version(version));
+    }
+
+    let size = serialized_size(transaction);
+    if size > config.max_standard_tx_size {
+        return Err(PolicyError::TxSize(size));
+    }
+
+    for (index, input) in transaction.inputs().enumerate() {
+        if let TransparentInput::PrevOut { script, .. } = input {
+            if script.0.len() > config.max_script_sig_size {
+                return Err(PolicyError::ScriptSigSize {
+                    index,
+                    size: script.0.len(),
+                });
+            }
+            if !script.is_push_only() {
+                return Err(PolicyError::ScriptSigNotPushOnly { index });
+            }
+        }
+    }
+
+    let mut data_outputs = 0;
+    for (index, output) in transaction.outputs().enumerate() {
+        match output.pk_script.kind() {
+            ScriptKind::NonStandard => return Err(PolicyError::NonStandardOutput { index }),
+            ScriptKind::NullData => {
+                let size = output.pk_script.0.len();
+                if size > config.max_data_carrier_size {
+                    return Err(PolicyError::DataCarrierSize { index, size });
+                }
+                data_outputs += 1;
+            }
+            _ => {
+                if u64::from(output.value) < dust_threshold(config, output) {
+                    return Err(PolicyError::Dust { index });
+                }
+            }
+        }
+    }
+
+    if data_outputs > 1 {
+        return Err(PolicyError::MultipleDataOutputs);
+    }
+
+    Ok(())
+}
+
+/// Check that `fee` is at least the minimum relay fee for `transaction`, and
+/// that it pays for enough of the ZIP-317 logical actions in `transaction`.
+///
+/// The fee depends on the values of the outputs spent by `transaction`, so
+/// the caller must look them up in the chain state.
+pub fn check_fee(
+    config: &Config,
+    transaction: &Transaction,
+    fee: Amount<NonNegative>,
+) -> Result<(), PolicyError> {
+    let required = fee_for_size(config.min_relay_fee_per_kb, serialized_size(transaction));
+    if u64::from(fee) < required {
+        return Err(PolicyError::InsufficientFee {
+            fee: u64::from(fee),
+            required,
+        });
+    }
+
+    let unpaid = transaction.unpaid_actions(fee);
+    if unpaid > config.tx_unpaid_action_limit {
+        return Err(PolicyError::UnpaidActions {
+            unpaid,
+            limit: config.tx_unpaid_action_limit,
+        });
+    }
+
+    Ok(())
+}
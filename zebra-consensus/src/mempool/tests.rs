@@ -0,0 +1,398 @@
+//! Tests for the mempool policy and transaction verifier.
+
+use std::{convert::TryInto, sync::Arc};
+
+use color_eyre::eyre::{eyre, Report};
+use tower::{util::ServiceExt, Service};
+
+use zebra_chain::{
+    block::Block,
+    serialization::{ZcashDeserialize, ZcashSerialize},
+    transaction::{OutPoint, Transaction, TransactionHash, TransparentInput, TransparentOutput},
+    types::{amount::Amount, BlockHeight, LockTime, Script},
+    Network,
+};
+
+use super::{
+    policy::{check_fee, check_standard, dust_threshold, fee_for_size, PolicyError},
+    Config, MempoolChange, MempoolError, MempoolTransaction, RemovalReason, Request, Response,
+    ZebraMempoolState,
+};
+
+/// Returns a P2PKH output script.
+fn p2pkh_script() -> Script {
+    let mut bytes = vec![0x76, 0xa9, 20];
+    bytes.extend_from_slice(&[7; 20]);
+    bytes.extend_from_slice(&[0x88, 0xac]);
+    Script(bytes)
+}
+
+/// Returns a V1 transaction with one input and `outputs`.
+fn transaction(script_sig: Script, outputs: Vec<(u64, Script)>) -> Transaction {
+    Transaction::V1 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint: OutPoint {
+                hash: TransactionHash([0; 32]),
+                index: 0,
+            },
+            script: script_sig,
+            sequence: u32::MAX,
+        }],
+        outputs: outputs
+            .into_iter()
+            .map(|(value, pk_script)| TransparentOutput {
+                value: value.try_into().expect("test values are valid amounts"),
+                pk_script,
+            })
+            .collect(),
+        lock_time: LockTime::Height(BlockHeight(0)),
+    }
+}
+
+#[test]
+fn fee_rates() {
+    assert_eq!(fee_for_size(100, 0), 0);
+    assert_eq!(fee_for_size(100, 1), 100);
+    assert_eq!(fee_for_size(100, 250), 25);
+    assert_eq!(fee_for_size(100, 10_000), 1_000);
+    assert_eq!(fee_for_size(0, 10_000), 0);
+}
+
+#[test]
+fn dust() {
+    zebra_test::init();
+
+    let config = Config::default();
+    let output = TransparentOutput {
+        value: 0u64.try_into().unwrap(),
+        pk_script: p2pkh_script(),
+    };
+
+    // A P2PKH output is 34 bytes, and spending it takes 148 bytes, so the
+    // threshold is 3 * (100 * 182 / 1000) zatoshis
+    assert_eq!(dust_threshold(&config, &output), 54);
+
+    let threshold = dust_threshold(&config, &output);
+    let tx = transaction(Script(vec![]), vec![(threshold - 1, p2pkh_script())]);
+    assert_eq!(
+        check_standard(&config, &tx),
+        Err(PolicyError::Dust { index: 0 })
+    );
+
+    let tx = transaction(Script(vec![]), vec![(threshold, p2pkh_script())]);
+    assert_eq!(check_standard(&config, &tx), Ok(()));
+}
+
+#[test]
+fn scripts() {
+    zebra_test::init();
+
+    let config = Config::default();
+
+    // Input scripts must be push-only
+    let tx = transaction(Script(vec![0x76]), vec![(1_000, p2pkh_script())]);
+    assert_eq!(
+        check_standard(&config, &tx),
+        Err(PolicyError::ScriptSigNotPushOnly { index: 0 })
+    );
+
+    // Output scripts must be standard
+    let tx = transaction(Script(vec![]), vec![(1_000, Script(vec![0xac]))]);
+    assert_eq!(
+        check_standard(&config, &tx),
+        Err(PolicyError::NonStandardOutput { index: 0 })
+    );
+
+    // Only one small data output is allowed
+    let data = Script(vec![0x6a, 2, 1, 2]);
+    let tx = transaction(Script(vec![]), vec![(0, data.clone())]);
+    assert_eq!(check_standard(&config, &tx), Ok(()));
+
+    let tx = transaction(Script(vec![]), vec![(0, data.clone()), (0, data)]);
+    assert_eq!(
+        check_standard(&config, &tx),
+        Err(PolicyError::MultipleDataOutputs)
+    );
+
+    let mut big_data = vec![0x6a, 0x4c, 250];
+    big_data.extend_from_slice(&[0; 250]);
+    let tx = transaction(Script(vec![]), vec![(0, Script(big_data))]);
+    assert_eq!(
+        check_standard(&config, &tx),
+        Err(PolicyError::DataCarrierSize {
+            index: 0,
+            size: 253
+        })
+    );
+}
+
+#[test]
+fn config_limits() {
+    zebra_test::init();
+
+    let tx = transaction(Script(vec![]), vec![(1_000, p2pkh_script())]);
+
+    let config = Config {
+        max_standard_version: 0,
+        ..Config::default()
+    };
+    assert_eq!(check_standard(&config, &tx), Err(PolicyError::Version(1)));
+
+    let config = Config {
+        max_standard_tx_size: 10,
+        ..Config::default()
+    };
+    assert!(matches!(
+        check_standard(&config, &tx),
+        Err(PolicyError::TxSize(_))
+    ));
+}
+
+#[test]
+fn relay_fee() {
+    zebra_test::init();
+
+    let config = Config::default();
+    let tx = transaction(Script(vec![]), vec![(1_000, p2pkh_script())]);
+    let size = tx
+        .zcash_serialize_to_vec()
+        .expect("serializing into a vec is infallible")
+        .len();
+    let required = fee_for_size(config.min_relay_fee_per_kb, size);
+
+    assert_eq!(
+        check_fee(&config, &tx, (required - 1).try_into().unwrap()),
+        Err(PolicyError::InsufficientFee {
+            fee: required - 1,
+            required
+        })
+    );
+    assert_eq!(
+        check_fee(&config, &tx, required.try_into().unwrap()),
+        Ok(())
+    );
+}
+
+#[test]
+fn unpaid_actions() {
+    zebra_test::init();
+
+    let config = Config {
+        tx_unpaid_action_limit: 1,
+        ..Config::default()
+    };
+    let tx = transaction(Script(vec![]), vec![(1_000, p2pkh_script())]);
+
+    // The transaction has one logical action, but pays for the two grace
+    // actions
+    assert_eq!(
+        check_fee(&config, &tx, 100u64.try_into().unwrap()),
+        Err(PolicyError::UnpaidActions {
+            unpaid: 2,
+            limit: 1
+        })
+    );
+    assert_eq!(
+        check_fee(&config, &tx, 5_000u64.try_into().unwrap()),
+        Ok(())
+    );
+    assert_eq!(check_fee(&config, &tx, tx.conventional_fee()), Ok(()));
+}
+
+/// Adds the mainnet genesis block and block 1 to `state`.
+async fn add_blocks<S>(state: &mut S) -> Result<(), Report>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = super::Error>,
+{
+    for bytes in &[
+        &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+    ] {
+        let block = Arc::new(Block::zcash_deserialize(*bytes)?);
+        state
+            .ready_and()
+            .await
+            .map_err(|e| eyre!(e))?
+            .call(zebra_state::Request::AddBlock {
+                block,
+                deferred_pool_contribution: Amount::zero(),
+            })
+            .await
+            .map_err(|e| eyre!(e))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the mempool error that `transaction` is rejected with.
+async fn rejection<S>(verifier: &mut S, transaction: Transaction) -> Result<MempoolError, Report>
+where
+    S: Service<Request, Response = Response, Error = super::Error>,
+{
+    let error = verifier
+        .ready_and()
+        .await
+        .map_err(|e| eyre!(e))?
+        .call(Request::Queue(Arc::new(transaction)))
+        .await
+        .expect_err("transaction should be rejected");
+
+    Ok(*error.downcast::<MempoolError>().map_err(|e| eyre!(e))?)
+}
+
+#[tokio::test]
+async fn verifier_rejections() -> Result<(), Report> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let mut verifier = super::init(
+        Network::Mainnet,
+        Config::default(),
+        state,
+        ZebraMempoolState::default(),
+    );
+
+    let block_1 = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?;
+    let coinbase = block_1.transactions[0].clone();
+    assert_eq!(
+        rejection(&mut verifier, (*coinbase).clone()).await?,
+        MempoolError::Coinbase
+    );
+
+    let tx = transaction(Script(vec![]), vec![(1_000, p2pkh_script())]);
+    let unknown = OutPoint {
+        hash: TransactionHash([0; 32]),
+        index: 0,
+    };
+    assert_eq!(
+        rejection(&mut verifier, tx).await?,
+        MempoolError::MissingInput(unknown)
+    );
+
+    // Block 1 is the tip, so its coinbase can't be spent in block 2
+    let immature = OutPoint {
+        hash: TransactionHash::from(&*coinbase),
+        index: 0,
+    };
+    let tx = Transaction::V1 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint: immature,
+            script: Script(vec![]),
+            sequence: u32::MAX,
+        }],
+        outputs: vec![TransparentOutput {
+            value: 1_000u64.try_into().unwrap(),
+            pk_script: p2pkh_script(),
+        }],
+        lock_time: LockTime::Height(BlockHeight(0)),
+    };
+    assert_eq!(
+        rejection(&mut verifier, tx).await?,
+        MempoolError::ImmatureCoinbase(immature)
+    );
+
+    let tx = Transaction::V4 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint: unknown,
+            script: Script(vec![]),
+            sequence: u32::MAX,
+        }],
+        outputs: Vec::new(),
+        lock_time: LockTime::Height(BlockHeight(0)),
+        expiry_height: BlockHeight(4),
+        value_balance: 0i64.try_into().unwrap(),
+        shielded_data: None,
+        joinsplit_data: None,
+    };
+    assert_eq!(
+        rejection(&mut verifier, tx).await?,
+        MempoolError::Expired {
+            expiry_height: BlockHeight(4),
+            next_height: BlockHeight(2),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn mempool_conflicts() {
+    zebra_test::init();
+
+    let mempool = ZebraMempoolState::default();
+    let tx = Arc::new(transaction(Script(vec![]), vec![(1_000, p2pkh_script())]));
+    let hash = TransactionHash::from(&*tx);
+    let entry = MempoolTransaction {
+        transaction: tx.clone(),
+        fee: 100u64.try_into().unwrap(),
+    };
+
+    assert_eq!(mempool.insert(entry.clone()), Ok(hash));
+    assert!(mempool.contains(&hash));
+    assert_eq!(mempool.transactions(), vec![entry.clone()]);
+    assert_eq!(
+        mempool.insert(entry.clone()),
+        Err(MempoolError::AlreadyInMempool)
+    );
+
+    // Mempool outputs can be spent by other mempool transactions
+    let child = OutPoint { hash, index: 0 };
+    assert_eq!(mempool.output(&child), tx.outputs().next().cloned());
+
+    // But each output can only be spent once
+    let double_spend = transaction(Script(vec![0x00]), vec![(2_000, p2pkh_script())]);
+    let spent = OutPoint {
+        hash: TransactionHash([0; 32]),
+        index: 0,
+    };
+    assert_eq!(
+        mempool.insert(MempoolTransaction {
+            transaction: Arc::new(double_spend.clone()),
+            ..entry.clone()
+        }),
+        Err(MempoolError::Conflict(spent))
+    );
+
+    // Removed transactions don't spend their inputs any more
+    assert!(mempool.remove(&hash, RemovalReason::Mined).is_some());
+    let double_spend = Arc::new(double_spend);
+    assert_eq!(
+        mempool.insert(MempoolTransaction {
+            transaction: double_spend.clone(),
+            ..entry
+        }),
+        Ok(TransactionHash::from(&*double_spend))
+    );
+}
+
+#[test]
+fn mempool_changes() {
+    zebra_test::init();
+
+    let mempool = ZebraMempoolState::default();
+    let mut changes = mempool.subscribe();
+
+    let tx = Arc::new(transaction(Script(vec![]), vec![(1_000, p2pkh_script())]));
+    let hash = TransactionHash::from(&*tx);
+    let entry = MempoolTransaction {
+        transaction: tx.clone(),
+        fee: 100u64.try_into().unwrap(),
+    };
+
+    assert_eq!(mempool.insert(entry), Ok(hash));
+    assert_eq!(changes.try_recv(), Ok(MempoolChange::Added(tx)));
+
+    assert!(mempool.remove(&hash, RemovalReason::Mined).is_some());
+    assert_eq!(
+        changes.try_recv(),
+        Ok(MempoolChange::Removed {
+            hash,
+            reason: RemovalReason::Mined
+        })
+    );
+
+    // Missing transactions aren't reported
+    assert!(mempool.remove(&hash, RemovalReason::Expired).is_none());
+    assert!(changes.try_recv().is_err());
+}
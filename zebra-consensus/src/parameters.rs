@@ -7,13 +7,24 @@
 //! block header during the Heartwood upgrade.)
 //!
 //! Typically, consensus parameters are accessed via a function that takes a
-//! `Network` and `BlockHeight`.
+//! `Network` and `BlockHeight`. Consensus rules that change in network
+//! upgrades are listed in the `rules` module.
 
+pub mod block_version;
+pub mod commitment;
 pub mod genesis;
 pub mod network_upgrade;
+pub mod note_plaintext;
+pub mod rules;
+pub mod subsidy;
 
+pub use block_version::*;
+pub use commitment::*;
 pub use genesis::*;
 pub use network_upgrade::*;
+pub use note_plaintext::*;
+pub use rules::*;
+pub use subsidy::*;
 
 #[cfg(test)]
 mod tests;
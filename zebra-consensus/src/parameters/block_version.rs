@@ -0,0 +1,72 @@
+//! Block header version consensus parameters.
+//!
+//! The Zcash specification says that "the current and only defined block
+//! version number for Zcash is 4", but zcashd only rejects versions less than
+//! 4. Some mining software sets other versions, and those blocks are in the
+//! chain, so rejecting them would fork Zebra off the network.
+//!
+//! Miners can also use higher versions to signal support for future rules.
+//! Zebra accepts those blocks, but counts them, so operators can see when
+//! blocks with unknown versions start appearing.
+
+use std::fmt;
+
+/// The minimum block version, when interpreted as an `i32`.
+pub const MIN_BLOCK_VERSION: u32 = 4;
+
+/// The block version that Zebra understands, and uses in block templates.
+pub const CURRENT_BLOCK_VERSION: u32 = 4;
+
+/// The kind of a valid block version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BlockVersion {
+    /// The current block version.
+    Current,
+    /// A version greater than the current version, which might signal future
+    /// consensus rules.
+    ///
+    /// Blocks with future versions are valid.
+    Future,
+}
+
+/// An invalid block version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BlockVersionError {
+    /// The version is negative, when interpreted as an `i32`.
+    Negative(u32),
+    /// The version is less than [`MIN_BLOCK_VERSION`].
+    TooLow(u32),
+}
+
+impl fmt::Display for BlockVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockVersionError::Negative(version) => {
+                write!(f, "block version {:#010x} is negative", version)
+            }
+            BlockVersionError::TooLow(version) => write!(
+                f,
+                "block version {} is less than the minimum version {}",
+                version, MIN_BLOCK_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlockVersionError {}
+
+/// Check that `version` is a valid block header version, and return its kind.
+///
+/// zcashd treats block versions as signed integers, so versions with the high
+/// bit set are negative, and invalid.
+pub fn check_block_version(version: u32) -> Result<BlockVersion, BlockVersionError> {
+    if (version as i32) < 0 {
+        Err(BlockVersionError::Negative(version))
+    } else if version < MIN_BLOCK_VERSION {
+        Err(BlockVersionError::TooLow(version))
+    } else if version > CURRENT_BLOCK_VERSION {
+        Ok(BlockVersion::Future)
+    } else {
+        Ok(BlockVersion::Current)
+    }
+}
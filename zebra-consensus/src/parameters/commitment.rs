@@ -0,0 +1,51 @@
+//! Block header commitment consensus parameters.
+//!
+//! The meaning of the block header commitment field depends on the network
+//! upgrade, so Zebra has to know the block height to interpret it.
+
+use zebra_chain::{
+    block::{
+        ChainHistoryBlockTxAuthCommitmentHash, ChainHistoryMmrRootHash, Commitment,
+        CommitmentError, CHAIN_HISTORY_ACTIVATION_RESERVED,
+    },
+    note_commitment_tree::SaplingNoteTreeRootHash,
+    types::BlockHeight,
+    Network,
+};
+
+use super::{ConsensusRule, NetworkUpgrade};
+
+/// Returns the interpretation of `bytes`, the commitment field of the block
+/// header at `height` on `network`.
+///
+/// Returns an error if the Heartwood activation block has non-zero commitment
+/// bytes. The other commitments can only be checked against the chain state.
+//
+// TODO: check the chain history tree roots, once the state keeps a history
+//       tree, and the final Sapling roots, once it keeps a note commitment
+//       tree.
+pub fn block_commitment(
+    network: Network,
+    height: BlockHeight,
+    bytes: [u8; 32],
+) -> Result<Commitment, CommitmentError> {
+    use Commitment::*;
+
+    if ConsensusRule::BlockCommitments.is_active(network, height) {
+        Ok(ChainHistoryBlockTxAuthCommitment(
+            ChainHistoryBlockTxAuthCommitmentHash(bytes),
+        ))
+    } else if ConsensusRule::ChainHistoryRootCommitment.is_active(network, height) {
+        if Some(height) != NetworkUpgrade::Heartwood.activation_height(network) {
+            Ok(ChainHistoryRoot(ChainHistoryMmrRootHash(bytes)))
+        } else if bytes == CHAIN_HISTORY_ACTIVATION_RESERVED {
+            Ok(ChainHistoryActivationReserved)
+        } else {
+            Err(CommitmentError::InvalidChainHistoryActivationReserved { actual: bytes })
+        }
+    } else if ConsensusRule::FinalSaplingRootCommitment.is_active(network, height) {
+        Ok(FinalSaplingRoot(SaplingNoteTreeRootHash(bytes)))
+    } else {
+        Ok(PreSaplingReserved(bytes))
+    }
+}
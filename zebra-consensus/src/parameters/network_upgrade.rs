@@ -5,6 +5,13 @@ use NetworkUpgrade::*;
 use std::collections::{BTreeMap, HashMap};
 use std::ops::Bound::*;
 
+use chrono::Duration;
+
+use super::ConsensusRule;
+
+pub use zebra_chain::parameters::ConsensusBranchId;
+
+use zebra_chain::parameters::testnet_parameters;
 use zebra_chain::types::BlockHeight;
 use zebra_chain::{Network, Network::*};
 
@@ -29,6 +36,15 @@ pub enum NetworkUpgrade {
     Heartwood,
     /// The Zcash protocol after the Canopy upgrade.
     Canopy,
+    /// The Zcash protocol after the NU5 upgrade.
+    ///
+    /// NU5 has no default Testnet activation height, because Canopy doesn't
+    /// have one yet. It can be activated on configured test networks.
+    Nu5,
+    /// The Zcash protocol after the NU6 upgrade.
+    ///
+    /// Like NU5, NU6 has no default Testnet activation height.
+    Nu6,
 }
 
 /// Mainnet network upgrade activation heights.
@@ -42,6 +58,8 @@ pub(crate) const MAINNET_ACTIVATION_HEIGHTS: &[(BlockHeight, NetworkUpgrade)] =
     (BlockHeight(653_600), Blossom),
     (BlockHeight(903_000), Heartwood),
     (BlockHeight(1_046_400), Canopy),
+    (BlockHeight(1_687_104), Nu5),
+    (BlockHeight(2_726_400), Nu6),
 ];
 
 /// Testnet network upgrade activation heights.
@@ -58,11 +76,6 @@ pub(crate) const TESTNET_ACTIVATION_HEIGHTS: &[(BlockHeight, NetworkUpgrade)] =
     // See ZIP 251 for updates.
 ];
 
-/// The Consensus Branch Id, used to bind transactions and blocks to a
-/// particular network upgrade.
-#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
-pub struct ConsensusBranchId(u32);
-
 /// Network Upgrade Consensus Branch Ids.
 ///
 /// Branch ids are the same for mainnet and testnet. If there is a testnet
@@ -75,15 +88,23 @@ pub struct ConsensusBranchId(u32);
 /// do the uniqueness check in the unit tests.
 pub(crate) const CONSENSUS_BRANCH_IDS: &[(NetworkUpgrade, ConsensusBranchId)] = &[
     // TODO(teor): byte order?
-    (Overwinter, ConsensusBranchId(0x5ba81b19)),
-    (Sapling, ConsensusBranchId(0x76b809bb)),
-    (Blossom, ConsensusBranchId(0x2bb40e60)),
-    (Heartwood, ConsensusBranchId(0xf5b9230b)),
+    (Overwinter, ConsensusBranchId::new(0x5ba81b19)),
+    (Sapling, ConsensusBranchId::new(0x76b809bb)),
+    (Blossom, ConsensusBranchId::new(0x2bb40e60)),
+    (Heartwood, ConsensusBranchId::new(0xf5b9230b)),
     // As of 21 July 2020. Could change before mainnet activation.
     // See ZIP 251 for updates.
-    (Canopy, ConsensusBranchId(0xe9ff75a6)),
+    (Canopy, ConsensusBranchId::new(0xe9ff75a6)),
+    (Nu5, ConsensusBranchId::new(0xc2d6d0b4)),
+    (Nu6, ConsensusBranchId::new(0xc8e71055)),
 ];
 
+/// The target block spacing before Blossom.
+const PRE_BLOSSOM_POW_TARGET_SPACING: i64 = 150;
+
+/// The target block spacing after Blossom activation.
+const POST_BLOSSOM_POW_TARGET_SPACING: i64 = 75;
+
 impl NetworkUpgrade {
     /// Returns a BTreeMap of activation heights and network upgrades for
     /// `network`.
@@ -92,7 +113,25 @@ impl NetworkUpgrade {
     /// network upgrade does not appear in the list.
     ///
     /// This is actually a bijective map.
+    ///
+    /// Uses the configured Testnet activation heights, if there are any.
     pub(crate) fn activation_list(network: Network) -> BTreeMap<BlockHeight, NetworkUpgrade> {
+        if let (Testnet, Some(heights)) = (
+            network,
+            testnet_parameters().and_then(|parameters| parameters.activation_heights.clone()),
+        ) {
+            let upgrades = [Overwinter, Sapling, Blossom, Heartwood, Canopy, Nu5, Nu6];
+            return std::iter::once((BlockHeight(0), BeforeOverwinter))
+                .chain(
+                    heights
+                        .in_order()
+                        .iter()
+                        .zip(upgrades.iter())
+                        .filter_map(|(height, nu)| height.map(|height| (height, *nu))),
+                )
+                .collect();
+        }
+
         match network {
             Mainnet => MAINNET_ACTIVATION_HEIGHTS,
             Testnet => TESTNET_ACTIVATION_HEIGHTS,
@@ -133,6 +172,25 @@ impl NetworkUpgrade {
             .next()
     }
 
+    /// Returns the target block spacing for this network upgrade.
+    ///
+    /// Blossom halved the target spacing, and later upgrades kept the
+    /// post-Blossom spacing.
+    pub fn target_spacing(&self) -> Duration {
+        let spacing_seconds = if ConsensusRule::PostBlossomTargetSpacing.is_active_in(*self) {
+            POST_BLOSSOM_POW_TARGET_SPACING
+        } else {
+            PRE_BLOSSOM_POW_TARGET_SPACING
+        };
+
+        Duration::seconds(spacing_seconds)
+    }
+
+    /// Returns the target block spacing for `network` and `height`.
+    pub fn target_spacing_for_height(network: Network, height: BlockHeight) -> Duration {
+        NetworkUpgrade::current(network, height).target_spacing()
+    }
+
     /// Returns a BTreeMap of NetworkUpgrades and their ConsensusBranchIds.
     ///
     /// Branch ids are the same for mainnet and testnet.
@@ -153,11 +211,9 @@ impl NetworkUpgrade {
     }
 }
 
-impl ConsensusBranchId {
-    /// Returns the current consensus branch id for `network` and `height`.
-    ///
-    /// Returns None if the network has no branch id at this height.
-    pub fn current(network: Network, height: BlockHeight) -> Option<ConsensusBranchId> {
-        NetworkUpgrade::current(network, height).branch_id()
-    }
+/// Returns the current consensus branch id for `network` and `height`.
+///
+/// Returns None if the network has no branch id at this height.
+pub fn current_branch_id(network: Network, height: BlockHeight) -> Option<ConsensusBranchId> {
+    NetworkUpgrade::current(network, height).branch_id()
 }
@@ -0,0 +1,55 @@
+//! Sapling note plaintext version consensus parameters, from [ZIP-212].
+//!
+//! [ZIP-212]: https://zips.z.cash/zip-0212
+
+use zebra_chain::{
+    notes::sapling::{NOTE_PLAINTEXT_V1, NOTE_PLAINTEXT_V2},
+    types::{BlockHeight, HeightDiff},
+    Network,
+};
+
+use super::{ConsensusRule, NetworkUpgrade};
+
+/// The number of blocks after Canopy activation where received notes can
+/// use either note plaintext version.
+///
+/// Matches `ZIP212_GRACE_PERIOD` in zcashd. It is 28 days of post-Blossom
+/// blocks.
+pub const ZIP212_GRACE_PERIOD: HeightDiff = 32_256;
+
+/// Returns the note plaintext lead byte that coinbase outputs must use in
+/// the block at `height` on `network`.
+///
+/// Miners can choose the version of their coinbase outputs, so coinbase
+/// outputs switch versions at Canopy activation, without a grace period.
+pub fn coinbase_note_plaintext_version(network: Network, height: BlockHeight) -> u8 {
+    if ConsensusRule::NotePlaintextV2.is_active(network, height) {
+        NOTE_PLAINTEXT_V2
+    } else {
+        NOTE_PLAINTEXT_V1
+    }
+}
+
+/// Returns true if `lead_byte` is a valid note plaintext version for a note
+/// received in the block at `height` on `network`.
+///
+/// Senders may not know about Canopy activation straight away, so V1 notes
+/// are still valid for [`ZIP212_GRACE_PERIOD`] blocks after activation.
+/// Coinbase outputs must use [`coinbase_note_plaintext_version`] instead.
+pub fn is_valid_note_plaintext_version(
+    network: Network,
+    height: BlockHeight,
+    lead_byte: u8,
+) -> bool {
+    let activation_height = NetworkUpgrade::Canopy.activation_height(network);
+
+    match lead_byte {
+        // If the end of the grace period is past the maximum height, V1 notes
+        // are always valid
+        NOTE_PLAINTEXT_V1 => activation_height.map_or(true, |activation| {
+            (activation + ZIP212_GRACE_PERIOD).map_or(true, |end| height < end)
+        }),
+        NOTE_PLAINTEXT_V2 => activation_height.map_or(false, |activation| height >= activation),
+        _ => false,
+    }
+}
@@ -0,0 +1,109 @@
+//! The network upgrades where each consensus rule applies.
+//!
+//! Consensus rules that change in network upgrades are listed in
+//! [`CONSENSUS_RULES`], and verifiers check them using
+//! [`ConsensusRule::is_active`], rather than comparing heights with upgrade
+//! activation heights.
+//!
+//! To add a network upgrade, add it to the upgrades of each rule that still
+//! applies after the upgrade, then add any new rules.
+
+use ConsensusRule::*;
+
+use zebra_chain::{types::BlockHeight, Network};
+
+use super::NetworkUpgrade::{self, *};
+
+/// A consensus rule that only applies in some network upgrades.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ConsensusRule {
+    /// Part of the block subsidy is paid to the founders' reward address.
+    ///
+    /// The founders' reward also ends at the first halving.
+    FoundersReward,
+    /// Part of the block subsidy is paid to the ZIP-207 funding streams.
+    ///
+    /// The ZIP-214 funding streams end at the second halving. NU6 replaces
+    /// them with the ZIP-1015 funding stream.
+    FundingStreams,
+    /// Part of the block subsidy is added to the [ZIP-1015] deferred
+    /// development fund pool (the "lockbox"), rather than being paid to an
+    /// output.
+    ///
+    /// [ZIP-1015]: https://zips.z.cash/zip-1015
+    DeferredPool,
+    /// Blocks are mined using the shorter post-Blossom target spacing.
+    PostBlossomTargetSpacing,
+    /// Sprout JoinSplits in V4 transactions use Groth16 proofs, rather than
+    /// BCTV14 proofs.
+    SproutGroth16Proofs,
+    /// The block header commitment is the final Sapling note commitment tree
+    /// root.
+    FinalSaplingRootCommitment,
+    /// The block header commitment is the chain history tree root, except in
+    /// the Heartwood activation block.
+    ChainHistoryRootCommitment,
+    /// The block header commitment combines the chain history tree root and
+    /// the authorizing data root of the block's transactions.
+    ///
+    /// NU5 introduced this commitment.
+    BlockCommitments,
+    /// The Sapling outputs of coinbase transactions must be recoverable with
+    /// the all-zero outgoing viewing key, from [ZIP-213].
+    ///
+    /// [ZIP-213]: https://zips.z.cash/zip-0213
+    CoinbaseOutputRecovery,
+    /// Coinbase outputs use the V2 note plaintext, which derives `esk` and
+    /// `rcm` from `rseed`, from [ZIP-212].
+    ///
+    /// [ZIP-212]: https://zips.z.cash/zip-0212
+    NotePlaintextV2,
+}
+
+/// The network upgrades where each consensus rule applies.
+///
+/// This is actually a map, but it is const, so we use a vector, and do the
+/// uniqueness check in the unit tests.
+pub(crate) const CONSENSUS_RULES: &[(ConsensusRule, &[NetworkUpgrade])] = &[
+    (
+        FoundersReward,
+        &[BeforeOverwinter, Overwinter, Sapling, Blossom, Heartwood],
+    ),
+    (FundingStreams, &[Canopy, Nu5, Nu6]),
+    (DeferredPool, &[Nu6]),
+    (
+        PostBlossomTargetSpacing,
+        &[Blossom, Heartwood, Canopy, Nu5, Nu6],
+    ),
+    (
+        SproutGroth16Proofs,
+        &[Sapling, Blossom, Heartwood, Canopy, Nu5, Nu6],
+    ),
+    (FinalSaplingRootCommitment, &[Sapling, Blossom]),
+    (ChainHistoryRootCommitment, &[Heartwood, Canopy]),
+    (BlockCommitments, &[Nu5, Nu6]),
+    (CoinbaseOutputRecovery, &[Heartwood, Canopy, Nu5, Nu6]),
+    (NotePlaintextV2, &[Canopy, Nu5, Nu6]),
+];
+
+impl ConsensusRule {
+    /// Returns the network upgrades where this rule applies.
+    pub fn upgrades(&self) -> &'static [NetworkUpgrade] {
+        CONSENSUS_RULES
+            .iter()
+            .find(|(rule, _)| rule == self)
+            .map(|(_, upgrades)| *upgrades)
+            .expect("every consensus rule is in the table")
+    }
+
+    /// Returns true if this rule applies in `upgrade`.
+    pub fn is_active_in(&self, upgrade: NetworkUpgrade) -> bool {
+        self.upgrades().contains(&upgrade)
+    }
+
+    /// Returns true if this rule applies to the block at `height` on
+    /// `network`.
+    pub fn is_active(&self, network: Network, height: BlockHeight) -> bool {
+        self.is_active_in(NetworkUpgrade::current(network, height))
+    }
+}
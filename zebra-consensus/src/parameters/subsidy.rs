@@ -0,0 +1,433 @@
+//! Block subsidy, founders' reward, and funding stream parameters for Zcash.
+//!
+//! See [protocol specification §7.8][7.8], [ZIP-207] and [ZIP-214] for
+//! funding streams, and [ZIP-1015] for the deferred development fund pool.
+//!
+//! [7.8]: https://zips.z.cash/protocol/protocol.pdf#subsidies
+//! [ZIP-207]: https://zips.z.cash/zip-0207
+//! [ZIP-214]: https://zips.z.cash/zip-0214
+//! [ZIP-1015]: https://zips.z.cash/zip-1015
+
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fmt,
+    ops::Range,
+    str::FromStr,
+};
+
+use zebra_chain::{
+    addresses::{sapling::SaplingShieldedAddress, transparent::TransparentAddress},
+    serialization::SerializationError,
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight, HeightDiff, Script,
+    },
+    Network,
+};
+
+use super::{ConsensusRule, NetworkUpgrade::*};
+
+mod addresses;
+
+/// The maximum block subsidy, in zatoshis, before any halvings.
+pub const MAX_BLOCK_SUBSIDY: u64 = 1_250_000_000;
+
+/// The number of blocks in the slow start period, where the subsidy
+/// increases linearly.
+pub const SLOW_START_INTERVAL: BlockHeight = BlockHeight(20_000);
+
+/// The shift applied to halving heights, because of the slow start.
+pub const SLOW_START_SHIFT: BlockHeight = BlockHeight(10_000);
+
+/// The number of blocks between halvings, before Blossom.
+pub const PRE_BLOSSOM_HALVING_INTERVAL: HeightDiff = 840_000;
+
+/// The number of blocks between halvings, after Blossom.
+///
+/// Blossom halved the target block spacing, so it doubled the halving
+/// interval.
+pub const POST_BLOSSOM_HALVING_INTERVAL: HeightDiff = 1_680_000;
+
+/// The ratio of pre-Blossom and post-Blossom target block spacings.
+const BLOSSOM_POW_TARGET_SPACING_RATIO: HeightDiff = 2;
+
+/// The divisor for the founders' reward fraction of the block subsidy.
+pub const FOUNDERS_FRACTION_DIVISOR: u64 = 5;
+
+/// The denominator of the funding stream fractions of the block subsidy.
+pub const FUNDING_STREAM_RECEIVER_DENOMINATOR: u64 = 100;
+
+/// The numerator of the deferred pool fraction of the block subsidy, over
+/// `FUNDING_STREAM_RECEIVER_DENOMINATOR`.
+pub const DEFERRED_POOL_NUMERATOR: u64 = 12;
+
+/// The number of blocks from NU6 activation that add to the deferred pool,
+/// and pay the post-NU6 funding stream.
+///
+/// On Mainnet, NU6 activates at the second halving, so this matches the
+/// ZIP-1015 heights.
+pub const DEFERRED_POOL_DURATION: HeightDiff = POST_BLOSSOM_HALVING_INTERVAL / 4;
+
+/// The number of blocks that each funding stream address is used for.
+///
+/// There are 48 address change intervals in each post-Blossom halving
+/// interval.
+pub const FUNDING_STREAM_ADDRESS_CHANGE_INTERVAL: HeightDiff = POST_BLOSSOM_HALVING_INTERVAL / 48;
+
+/// A recipient of a [ZIP-214] funding stream.
+///
+/// [ZIP-214]: https://zips.z.cash/zip-0214
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum FundingStreamReceiver {
+    /// The Electric Coin Company.
+    Ecc,
+    /// The Zcash Foundation.
+    ZcashFoundation,
+    /// The Major Grants fund, administered by the Zcash Foundation.
+    ///
+    /// After NU6, this stream pays Zcash Community Grants, as specified in
+    /// [ZIP-1015].
+    ///
+    /// [ZIP-1015]: https://zips.z.cash/zip-1015
+    MajorGrants,
+}
+
+impl FundingStreamReceiver {
+    /// The funding stream receivers, in the order used by `zcashd`.
+    pub const ALL: [FundingStreamReceiver; 3] = [
+        FundingStreamReceiver::Ecc,
+        FundingStreamReceiver::ZcashFoundation,
+        FundingStreamReceiver::MajorGrants,
+    ];
+
+    /// Returns the numerator of this receiver's fraction of the block
+    /// subsidy, over `FUNDING_STREAM_RECEIVER_DENOMINATOR`.
+    pub fn numerator(&self) -> u64 {
+        match self {
+            FundingStreamReceiver::Ecc => 7,
+            FundingStreamReceiver::ZcashFoundation => 5,
+            FundingStreamReceiver::MajorGrants => 8,
+        }
+    }
+
+    /// Returns the address that receives this funding stream in the block at
+    /// `height` on `network`.
+    ///
+    /// Returns `None` if `height` is outside the funding stream heights, or
+    /// if `network` is a Testnet with custom activation heights, and the
+    /// address list doesn't cover `height`.
+    pub fn address_for(
+        &self,
+        height: BlockHeight,
+        network: Network,
+    ) -> Option<FundingStreamAddress> {
+        if is_post_nu6_funding_period(network, height) {
+            let address = match (self, network) {
+                (FundingStreamReceiver::MajorGrants, Network::Mainnet) => {
+                    addresses::ZCASH_COMMUNITY_GRANTS_ADDRESS_MAINNET
+                }
+                (FundingStreamReceiver::MajorGrants, Network::Testnet) => {
+                    addresses::ZCASH_COMMUNITY_GRANTS_ADDRESS_TESTNET
+                }
+                _ => return None,
+            };
+
+            return Some(
+                address
+                    .parse()
+                    .expect("funding stream addresses are valid addresses"),
+            );
+        }
+
+        let index = funding_stream_address_index(network, height)?;
+
+        let address = match (self, network) {
+            (FundingStreamReceiver::Ecc, Network::Mainnet) => {
+                addresses::ECC_ADDRESSES_MAINNET.get(index)?
+            }
+            (FundingStreamReceiver::Ecc, Network::Testnet) => {
+                addresses::ECC_ADDRESSES_TESTNET.get(index)?
+            }
+            (FundingStreamReceiver::ZcashFoundation, Network::Mainnet) => {
+                addresses::ZCASH_FOUNDATION_ADDRESS_MAINNET
+            }
+            (FundingStreamReceiver::ZcashFoundation, Network::Testnet) => {
+                addresses::ZCASH_FOUNDATION_ADDRESS_TESTNET
+            }
+            (FundingStreamReceiver::MajorGrants, Network::Mainnet) => {
+                addresses::MAJOR_GRANTS_ADDRESS_MAINNET
+            }
+            (FundingStreamReceiver::MajorGrants, Network::Testnet) => {
+                addresses::MAJOR_GRANTS_ADDRESS_TESTNET
+            }
+        };
+
+        Some(
+            address
+                .parse()
+                .expect("funding stream addresses are valid addresses"),
+        )
+    }
+}
+
+/// The address that receives a funding stream.
+///
+/// Funding streams can be paid to transparent P2SH addresses, or to Sapling
+/// addresses. Sapling funding stream outputs must be recoverable with the
+/// all-zero outgoing viewing key, like other shielded coinbase outputs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FundingStreamAddress {
+    /// A transparent address, paid by a transparent output.
+    Transparent(TransparentAddress),
+    /// A Sapling address, paid by a Sapling output.
+    Sapling(SaplingShieldedAddress),
+}
+
+impl FundingStreamAddress {
+    /// Returns the network for this address.
+    pub fn network(&self) -> Network {
+        match self {
+            FundingStreamAddress::Transparent(address) => address.network(),
+            FundingStreamAddress::Sapling(address) => address.network(),
+        }
+    }
+
+    /// Returns the output script that pays this address, or `None` if it is
+    /// a Sapling address.
+    pub fn output_script(&self) -> Option<Script> {
+        match self {
+            FundingStreamAddress::Transparent(address) => Some(address.output_script()),
+            FundingStreamAddress::Sapling(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for FundingStreamAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FundingStreamAddress::Transparent(address) => address.fmt(f),
+            FundingStreamAddress::Sapling(address) => address.fmt(f),
+        }
+    }
+}
+
+impl FromStr for FundingStreamAddress {
+    type Err = SerializationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Sapling addresses are bech32, so they never decode as base58check
+        match s.parse() {
+            Ok(address) => Ok(FundingStreamAddress::Transparent(address)),
+            Err(_) => Ok(FundingStreamAddress::Sapling(s.parse()?)),
+        }
+    }
+}
+
+/// Returns the heights where the funding stream addresses are specified on
+/// `network`.
+///
+/// Funding streams are only paid after Canopy activation, so on networks
+/// without Canopy, these heights have no funding streams.
+pub fn funding_stream_heights(network: Network) -> Range<BlockHeight> {
+    match network {
+        Network::Mainnet => BlockHeight(1_046_400)..BlockHeight(2_726_400),
+        Network::Testnet => BlockHeight(1_028_500)..BlockHeight(2_796_000),
+    }
+}
+
+/// Returns the index of the funding stream address for the block at `height`
+/// on `network`.
+///
+/// Returns `None` if `height` is outside the funding stream heights.
+pub fn funding_stream_address_index(network: Network, height: BlockHeight) -> Option<usize> {
+    let heights = funding_stream_heights(network);
+    if !heights.contains(&height) {
+        return None;
+    }
+
+    // Address periods are aligned, so that each halving starts a new
+    // address period
+    let address_period = |height: BlockHeight| {
+        (height - height_for_first_halving(network) + POST_BLOSSOM_HALVING_INTERVAL)
+            / FUNDING_STREAM_ADDRESS_CHANGE_INTERVAL
+    };
+
+    Some((address_period(height) - address_period(heights.start)) as usize)
+}
+
+/// Returns the height of the first halving on `network`.
+pub fn height_for_first_halving(network: Network) -> BlockHeight {
+    let pre_blossom_halving = (SLOW_START_SHIFT + PRE_BLOSSOM_HALVING_INTERVAL)
+        .expect("the pre-Blossom halving height is valid");
+
+    match Blossom.activation_height(network) {
+        // Count the remaining pre-Blossom blocks as two post-Blossom blocks
+        Some(blossom_height) if blossom_height < pre_blossom_halving => (blossom_height
+            + (pre_blossom_halving - blossom_height) * BLOSSOM_POW_TARGET_SPACING_RATIO)
+            .expect("the first halving height is valid"),
+        _ => pre_blossom_halving,
+    }
+}
+
+/// Returns the number of halvings before `height` on `network`.
+///
+/// Heights in the slow start period are treated as if they were before the
+/// first halving.
+pub fn halving(network: Network, height: BlockHeight) -> u32 {
+    if height < SLOW_START_SHIFT {
+        return 0;
+    }
+
+    let halvings = match Blossom.activation_height(network) {
+        Some(blossom_height) if height >= blossom_height => {
+            // Count pre-Blossom blocks as two post-Blossom blocks
+            let scaled_pre_blossom =
+                (blossom_height - SLOW_START_SHIFT) * BLOSSOM_POW_TARGET_SPACING_RATIO;
+            let post_blossom = height - blossom_height;
+            (scaled_pre_blossom + post_blossom) / POST_BLOSSOM_HALVING_INTERVAL
+        }
+        _ => (height - SLOW_START_SHIFT) / PRE_BLOSSOM_HALVING_INTERVAL,
+    };
+
+    // Test networks can activate Blossom during the slow start
+    u32::try_from(halvings.max(0)).expect("the number of halvings fits in a u32")
+}
+
+/// Returns the total block subsidy for `height` on `network`.
+///
+/// This is the subsidy before the founders' reward and funding streams are
+/// paid, and does not include transaction fees.
+pub fn block_subsidy(network: Network, height: BlockHeight) -> Amount<NonNegative> {
+    let slow_start_rate = MAX_BLOCK_SUBSIDY / SLOW_START_INTERVAL.0 as u64;
+    // The subsidy is scaled down, because blocks are mined more often
+    let is_post_blossom = ConsensusRule::PostBlossomTargetSpacing.is_active(network, height);
+
+    let subsidy = if height.0 < SLOW_START_INTERVAL.0 / 2 {
+        slow_start_rate * height.0 as u64
+    } else if height < SLOW_START_INTERVAL {
+        slow_start_rate * (height.0 as u64 + 1)
+    } else {
+        let halved_subsidy = MAX_BLOCK_SUBSIDY
+            .checked_shr(halving(network, height))
+            .unwrap_or(0);
+        if is_post_blossom {
+            halved_subsidy / BLOSSOM_POW_TARGET_SPACING_RATIO as u64
+        } else {
+            halved_subsidy
+        }
+    };
+
+    subsidy
+        .try_into()
+        .expect("subsidies are less than the maximum amount")
+}
+
+/// Returns the founders' reward for `height` on `network`.
+///
+/// The founders' reward is paid until the first halving, or until Canopy
+/// activates, whichever is earlier.
+pub fn founders_reward(network: Network, height: BlockHeight) -> Amount<NonNegative> {
+    if halving(network, height) < 1 && ConsensusRule::FoundersReward.is_active(network, height) {
+        (u64::from(block_subsidy(network, height)) / FOUNDERS_FRACTION_DIVISOR)
+            .try_into()
+            .expect("the founders' reward is less than the subsidy")
+    } else {
+        0u64.try_into().expect("zero is a valid amount")
+    }
+}
+
+/// Returns the funding stream values for `height` on `network`.
+///
+/// The [ZIP-214] funding streams are paid from Canopy activation until the
+/// second halving. After NU6, the Zcash Community Grants stream is paid for
+/// `DEFERRED_POOL_DURATION` blocks, as specified in [ZIP-1015].
+/// Returns an empty map if there are no funding streams at `height`.
+///
+/// [ZIP-214]: https://zips.z.cash/zip-0214
+/// [ZIP-1015]: https://zips.z.cash/zip-1015
+pub fn funding_stream_values(
+    network: Network,
+    height: BlockHeight,
+) -> HashMap<FundingStreamReceiver, Amount<NonNegative>> {
+    let receivers: &[FundingStreamReceiver] =
+        if !ConsensusRule::FundingStreams.is_active(network, height) {
+            &[]
+        } else if ConsensusRule::DeferredPool.is_active(network, height) {
+            if is_post_nu6_funding_period(network, height) {
+                &[FundingStreamReceiver::MajorGrants]
+            } else {
+                &[]
+            }
+        } else if halving(network, height) < 2 {
+            &FundingStreamReceiver::ALL
+        } else {
+            &[]
+        };
+
+    let subsidy = u64::from(block_subsidy(network, height));
+    receivers
+        .iter()
+        .map(|receiver| {
+            let value = subsidy * receiver.numerator() / FUNDING_STREAM_RECEIVER_DENOMINATOR;
+            let value = value
+                .try_into()
+                .expect("funding stream values are less than the subsidy");
+            (*receiver, value)
+        })
+        .collect()
+}
+
+/// Returns the value added to the deferred pool by the block at `height` on
+/// `network`.
+///
+/// The deferred pool value isn't paid to any coinbase output.
+pub fn deferred_pool_contribution(network: Network, height: BlockHeight) -> Amount<NonNegative> {
+    if is_post_nu6_funding_period(network, height) {
+        deferred_fraction(network, height)
+    } else {
+        0
+    }
+    .try_into()
+    .expect("the deferred pool contribution is less than the subsidy")
+}
+
+/// Returns true if `height` is in the [ZIP-1015] development fund period on
+/// `network`: the `DEFERRED_POOL_DURATION` blocks starting at NU6 activation.
+///
+/// [ZIP-1015]: https://zips.z.cash/zip-1015
+fn is_post_nu6_funding_period(network: Network, height: BlockHeight) -> bool {
+    match Nu6.activation_height(network) {
+        Some(nu6_height) => {
+            ConsensusRule::DeferredPool.is_active(network, height)
+                && height - nu6_height < DEFERRED_POOL_DURATION
+        }
+        None => false,
+    }
+}
+
+/// Returns the deferred pool fraction of the block subsidy at `height` on
+/// `network`, in zatoshis.
+fn deferred_fraction(network: Network, height: BlockHeight) -> u64 {
+    u64::from(block_subsidy(network, height)) * DEFERRED_POOL_NUMERATOR
+        / FUNDING_STREAM_RECEIVER_DENOMINATOR
+}
+
+/// Returns the miner's share of the block subsidy for `height` on `network`.
+///
+/// This is the block subsidy, minus the founders' reward, funding streams,
+/// and deferred pool contribution.
+/// It does not include transaction fees.
+pub fn miner_subsidy(network: Network, height: BlockHeight) -> Amount<NonNegative> {
+    let founders_reward = u64::from(founders_reward(network, height));
+    let funding_streams: u64 = funding_stream_values(network, height)
+        .values()
+        .map(|value| u64::from(*value))
+        .sum();
+
+    let deferred = u64::from(deferred_pool_contribution(network, height));
+
+    (u64::from(block_subsidy(network, height)) - founders_reward - funding_streams - deferred)
+        .try_into()
+        .expect("the miner subsidy is less than the block subsidy")
+}
@@ -0,0 +1,145 @@
+//! The funding stream recipient addresses from [ZIP-214].
+//!
+//! Each list has one address for each address change interval of the
+//! funding stream, in order.
+//!
+//! [ZIP-214]: https://zips.z.cash/zip-0214
+
+/// The Electric Coin Company funding stream addresses on Mainnet.
+pub(super) const ECC_ADDRESSES_MAINNET: &[&str] = &[
+    "t3LmX1cxWPPPqL4TZHx42HU3U5ghbFjRiif",
+    "t3Toxk1vJQ6UjWQ42tUJz2rV2feUWkpbTDs",
+    "t3ZBdBe4iokmsjdhMuwkxEdqMCFN16YxKe6",
+    "t3ZuaJziLM8xZ32rjDUzVjVtyYdDSz8GLWB",
+    "t3bAtYWa4bi8VrtvqySxnbr5uqcG9czQGTZ",
+    "t3dktADfb5Rmxncpe1HS5BRS5Gcj7MZWYBi",
+    "t3hgskquvKKoCtvxw86yN7q8bzwRxNgUZmc",
+    "t3R1VrLzwcxAZzkX4mX3KGbWpNsgtYtMntj",
+    "t3ff6fhemqPMVujD3AQurxRxTdvS1pPSaa2",
+    "t3cEUQFG3KYnFG6qYhPxSNgGi3HDjUPwC3J",
+    "t3WR9F5U4QvUFqqx9zFmwT6xFqduqRRXnaa",
+    "t3PYc1LWngrdUrJJbHkYPCKvJuvJjcm85Ch",
+    "t3bgkjiUeatWNkhxY3cWyLbTxKksAfk561R",
+    "t3Z5rrR8zahxUpZ8itmCKhMSfxiKjUp5Dk5",
+    "t3PU1j7YW3fJ67jUbkGhSRto8qK2qXCUiW3",
+    "t3S3yaT7EwNLaFZCamfsxxKwamQW2aRGEkh",
+    "t3eutXKJ9tEaPSxZpmowhzKhPfJvmtwTEZK",
+    "t3gbTb7brxLdVVghSPSd3ycGxzHbUpukeDm",
+    "t3UCKW2LrHFqPMQFEbZn6FpjqnhAAbfpMYR",
+    "t3NyHsrnYbqaySoQqEQRyTWkjvM2PLkU7Uu",
+    "t3QEFL6acxuZwiXtW3YvV6njDVGjJ1qeaRo",
+    "t3PdBRr2S1XTDzrV8bnZkXF3SJcrzHWe1wj",
+    "t3ZWyRPpWRo23pKxTLtWsnfEKeq9T4XPxKM",
+    "t3he6QytKCTydhpztykFsSsb9PmBT5JBZLi",
+    "t3VWxWDsLb2TURNEP6tA1ZSeQzUmPKFNxRY",
+    "t3NmWLvZkbciNAipauzsFRMxoZGqmtJksbz",
+    "t3cKr4YxVPvPBG1mCvzaoTTdBNokohsRJ8n",
+    "t3T3smGZn6BoSFXWWXa1RaoQdcyaFjMfuYK",
+    "t3gkDUe9Gm4GGpjMk86TiJZqhztBVMiUSSA",
+    "t3eretuBeBXFHe5jAqeSpUS1cpxVh51fAeb",
+    "t3dN8g9zi2UGJdixGe9txeSxeofLS9t3yFQ",
+    "t3S799pq9sYBFwccRecoTJ3SvQXRHPrHqvx",
+    "t3fhYnv1S5dXwau7GED3c1XErzt4n4vDxmf",
+    "t3cmE3vsBc5xfDJKXXZdpydCPSdZqt6AcNi",
+    "t3h5fPdjJVHaH4HwynYDM5BB3J7uQaoUwKi",
+    "t3Ma35c68BgRX8sdLDJ6WR1PCrKiWHG4Da9",
+    "t3LokMKPL1J8rkJZvVpfuH7dLu6oUWqZKQK",
+    "t3WFFGbEbhJWnASZxVLw2iTJBZfJGGX73mM",
+    "t3L8GLEsUn4QHNaRYcX3EGyXmQ8kjpT1zTa",
+    "t3PgfByBhaBSkH8uq4nYJ9ZBX4NhGCJBVYm",
+    "t3WecsqKDhWXD4JAgBVcnaCC2itzyNZhJrv",
+    "t3ZG9cSfopnsMQupKW5v9sTotjcP5P6RTbn",
+    "t3hC1Ywb5zDwUYYV8LwhvF5rZ6m49jxXSG5",
+    "t3VgMqDL15ZcyQDeqBsBW3W6rzfftrWP2yB",
+    "t3LC94Y6BwLoDtBoK2NuewaEbnko1zvR9rm",
+    "t3cWCUZJR3GtALaTcatrrpNJ3MGbMFVLRwQ",
+    "t3YYF4rPLVxDcF9hHFsXyc5Yq1TFfbojCY6",
+    "t3XHAGxRP2FNfhAjxGjxbrQPYtQQjc3RCQD",
+];
+
+/// The Zcash Foundation funding stream address on Mainnet.
+///
+/// The same address is used for every address change interval.
+pub(super) const ZCASH_FOUNDATION_ADDRESS_MAINNET: &str = "t3dvVE3SQEi7kqNzwrfNePxZ1d4hUyztBA1";
+
+/// The Major Grants funding stream address on Mainnet.
+///
+/// The same address is used for every address change interval.
+pub(super) const MAJOR_GRANTS_ADDRESS_MAINNET: &str = "t3XyYW8yBFRuMnfvm5KLGFbEVz25kckZXym";
+
+/// The post-NU6 Zcash Community Grants funding stream address on Mainnet.
+///
+/// The same address is used for every address change interval.
+pub(super) const ZCASH_COMMUNITY_GRANTS_ADDRESS_MAINNET: &str =
+    "t3cFfPt1Bcvgez9ZbMBFWeZsskxTkPzGCow";
+
+/// The Electric Coin Company funding stream addresses on Testnet.
+pub(super) const ECC_ADDRESSES_TESTNET: &[&str] = &[
+    "t26ovBdKAJLtrvBsE2QGF4nqBkEuptuPFZz",
+    "t26ovBdKAJLtrvBsE2QGF4nqBkEuptuPFZz",
+    "t26ovBdKAJLtrvBsE2QGF4nqBkEuptuPFZz",
+    "t26ovBdKAJLtrvBsE2QGF4nqBkEuptuPFZz",
+    "t2NNHrgPpE388atmWSF4DxAb3xAoW5Yp45M",
+    "t2VMN28itPyMeMHBEd9Z1hm6YLkQcGA1Wwe",
+    "t2CHa1TtdfUV8UYhNm7oxbzRyfr8616BYh2",
+    "t2F77xtr28U96Z2bC53ZEdTnQSUAyDuoa67",
+    "t2ARrzhbgcpoVBDPivUuj6PzXzDkTBPqfcT",
+    "t278aQ8XbvFR15mecRguiJDQQVRNnkU8kJw",
+    "t2Dp1BGnZsrTXZoEWLyjHmg3EPvmwBnPDGB",
+    "t2KzeqXgf4ju33hiSqCuKDb8iHjPCjMq9iL",
+    "t2Nyxqv1BiWY1eUSiuxVw36oveawYuo18tr",
+    "t2DKFk5JRsVoiuinK8Ti6eM4Yp7v8BbfTyH",
+    "t2CUaBca4k1x36SC4q8Nc8eBoqkMpF3CaLg",
+    "t296SiKL7L5wvFmEdMxVLz1oYgd6fTfcbZj",
+    "t29fBCFbhgsjL3XYEZ1yk1TUh7eTusB6dPg",
+    "t2FGofLJXa419A76Gpf5ncxQB4gQXiQMXjK",
+    "t2ExfrnRVnRiXDvxerQ8nZbcUQvNvAJA6Qu",
+    "t28JUffLp47eKPRHKvwSPzX27i9ow8LSXHx",
+    "t2JXWPtrtyL861rFWMZVtm3yfgxAf4H7uPA",
+    "t2QdgbJoWfYHgyvEDEZBjHmgkr9yNJff3Hi",
+    "t2QW43nkco8r32ZGRN6iw6eSzyDjkMwCV3n",
+    "t2DgYDXMJTYLwNcxighQ9RCgPxMVATRcUdC",
+    "t2Bop7dg33HGZx3wunnQzi2R2ntfpjuti3M",
+    "t2HVeEwovcLq9RstAbYkqngXNEsCe2vjJh9",
+    "t2HxbP5keQSx7p592zWQ5bJ5GrMmGDsV2Xa",
+    "t2TJzUg2matao3mztBRJoWnJY6ekUau6tPD",
+    "t29pMzxmo6wod25YhswcjKv3AFRNiBZHuhj",
+    "t2QBQMRiJKYjshJpE6RhbF7GLo51yE6d4wZ",
+    "t2F5RqnqguzZeiLtYHFx4yYfy6pDnut7tw5",
+    "t2CHvyZANE7XCtg8AhZnrcHCC7Ys1jJhK13",
+    "t2BRzpMdrGWZJ2upsaNQv6fSbkbTy7EitLo",
+    "t2BFixHGQMAWDY67LyTN514xRAB94iEjXp3",
+    "t2Uvz1iVPzBEWfQBH1p7NZJsFhD74tKaG8V",
+    "t2CmFDj5q6rJSRZeHf1SdrowinyMNcj438n",
+    "t2ErNvWEReTfPDBaNizjMPVssz66aVZh1hZ",
+    "t2GeJQ8wBUiHKDVzVM5ZtKfY5reCg7CnASs",
+    "t2L2eFtkKv1G6j55kLytKXTGuir4raAy3yr",
+    "t2EK2b87dpPazb7VvmEGc8iR6SJ289RywGL",
+    "t2DJ7RKeZJxdA4nZn8hRGXE8NUyTzjujph9",
+    "t2K1pXo4eByuWpKLkssyMLe8QKUbxnfFC3H",
+    "t2TB4mbSpuAcCWkH94Leb27FnRxo16AEHDg",
+    "t2Phx4gVL4YRnNsH3jM1M7jE4Fo329E66Na",
+    "t2VQZGmeNomN8c3USefeLL9nmU6M8x8CVzC",
+    "t2RicCvTVTY5y9JkreSRv3Xs8q2K67YxHLi",
+    "t2JrSLxTGc8wtPDe9hwbaeUjCrCfc4iZnDD",
+    "t2Uh9Au1PDDSw117sAbGivKREkmMxVC5tZo",
+    "t2FDwoJKLeEBMTy3oP7RLQ1Fihhvz49a3Bv",
+    "t2FY18mrgtb7QLeHA8ShnxLXuW8cNQ2n1v8",
+    "t2L15TkDYum7dnQRBqfvWdRe8Yw3jVy9z7g",
+];
+
+/// The Zcash Foundation funding stream address on Testnet.
+///
+/// The same address is used for every address change interval.
+pub(super) const ZCASH_FOUNDATION_ADDRESS_TESTNET: &str = "t27eWDgjFYJGVXmzrXeVjnb5J3uXDM9xH9v";
+
+/// The Major Grants funding stream address on Testnet.
+///
+/// The same address is used for every address change interval.
+pub(super) const MAJOR_GRANTS_ADDRESS_TESTNET: &str = "t2Gvxv2uNM7hbbACjNox4H6DjByoKZ2Fa3P";
+
+/// The post-NU6 Zcash Community Grants funding stream address on Testnet.
+///
+/// The same address is used for every address change interval.
+pub(super) const ZCASH_COMMUNITY_GRANTS_ADDRESS_TESTNET: &str =
+    "t2HifwjUj9uyxr9bknR8LFuQbc98c3vkXtu";
@@ -94,7 +94,7 @@ fn activation_consistent(network: Network) {
         // Network upgrades don't repeat
         assert_ne!(NetworkUpgrade::next(network, height), Some(network_upgrade));
         assert_ne!(
-            NetworkUpgrade::next(network, BlockHeight(height.0 + 1)),
+            NetworkUpgrade::next(network, (height + 1).unwrap()),
             Some(network_upgrade)
         );
         assert_ne!(
@@ -133,7 +133,7 @@ fn branch_id_extremes(network: Network) {
         NetworkUpgrade::branch_id_list().get(&BeforeOverwinter),
         None
     );
-    assert_eq!(ConsensusBranchId::current(network, BlockHeight(0)), None);
+    assert_eq!(current_branch_id(network, BlockHeight(0)), None);
     assert_eq!(
         NetworkUpgrade::branch_id_list().get(&Overwinter).cloned(),
         Overwinter.branch_id()
@@ -145,7 +145,7 @@ fn branch_id_extremes(network: Network) {
         NetworkUpgrade::branch_id_list().get(&NetworkUpgrade::current(network, BlockHeight::MAX)),
         None
     );
-    assert_ne!(ConsensusBranchId::current(network, BlockHeight::MAX), None);
+    assert_ne!(current_branch_id(network, BlockHeight::MAX), None);
 }
 
 #[test]
@@ -169,9 +169,450 @@ fn branch_id_consistent(network: Network) {
         // Skip network upgrades that don't have activation heights yet
         if let Some(height) = height {
             assert_eq!(
-                ConsensusBranchId::current(network, height),
+                current_branch_id(network, height),
                 network_upgrade.branch_id()
             );
         }
     }
 }
+
+#[test]
+fn target_spacing_mainnet() {
+    target_spacing(Mainnet)
+}
+
+#[test]
+fn target_spacing_testnet() {
+    target_spacing(Testnet)
+}
+
+/// Check that the target spacing halves at Blossom activation.
+fn target_spacing(network: Network) {
+    let blossom_height = Blossom
+        .activation_height(network)
+        .expect("Blossom activation height is specified");
+
+    assert_eq!(
+        NetworkUpgrade::target_spacing_for_height(network, BlockHeight(0)),
+        chrono::Duration::seconds(150)
+    );
+    assert_eq!(
+        NetworkUpgrade::target_spacing_for_height(network, (blossom_height - 1).unwrap()),
+        chrono::Duration::seconds(150)
+    );
+    assert_eq!(
+        NetworkUpgrade::target_spacing_for_height(network, blossom_height),
+        chrono::Duration::seconds(75)
+    );
+    assert_eq!(
+        NetworkUpgrade::target_spacing_for_height(network, BlockHeight(u32::MAX)),
+        chrono::Duration::seconds(75)
+    );
+}
+
+#[test]
+fn halving_heights() {
+    // The first halving is at the same height on both networks, because it is
+    // after Blossom activation
+    assert_eq!(halving(Mainnet, BlockHeight(0)), 0);
+    assert_eq!(halving(Mainnet, BlockHeight(1_046_399)), 0);
+    assert_eq!(halving(Mainnet, BlockHeight(1_046_400)), 1);
+    assert_eq!(halving(Mainnet, BlockHeight(2_726_399)), 1);
+    assert_eq!(halving(Mainnet, BlockHeight(2_726_400)), 2);
+
+    assert_eq!(halving(Testnet, BlockHeight(1_115_999)), 0);
+    assert_eq!(halving(Testnet, BlockHeight(1_116_000)), 1);
+    assert_eq!(halving(Testnet, BlockHeight(2_796_000)), 2);
+}
+
+#[test]
+fn block_subsidy_mainnet() {
+    let subsidy = |height| u64::from(block_subsidy(Mainnet, BlockHeight(height)));
+
+    // Slow start
+    assert_eq!(subsidy(0), 0);
+    assert_eq!(subsidy(1), 62_500);
+    assert_eq!(subsidy(9_999), 624_937_500);
+    assert_eq!(subsidy(10_000), 625_062_500);
+    assert_eq!(subsidy(19_999), 1_250_000_000);
+
+    assert_eq!(subsidy(20_000), 1_250_000_000);
+    assert_eq!(subsidy(653_599), 1_250_000_000);
+    // Blossom
+    assert_eq!(subsidy(653_600), 625_000_000);
+    // First halving
+    assert_eq!(subsidy(1_046_400), 312_500_000);
+    assert_eq!(subsidy(2_726_400), 156_250_000);
+
+    // Eventually, the subsidy is zero
+    assert_eq!(subsidy(u32::MAX), 0);
+}
+
+#[test]
+fn subsidy_recipients_mainnet() {
+    let height = BlockHeight(419_200);
+    assert_eq!(u64::from(founders_reward(Mainnet, height)), 250_000_000);
+    assert!(funding_stream_values(Mainnet, height).is_empty());
+    assert_eq!(u64::from(miner_subsidy(Mainnet, height)), 1_000_000_000);
+
+    // Canopy replaces the founders' reward with funding streams
+    let height = BlockHeight(1_046_400);
+    assert_eq!(u64::from(founders_reward(Mainnet, height)), 0);
+    let funding_streams = funding_stream_values(Mainnet, height);
+    assert_eq!(
+        u64::from(funding_streams[&FundingStreamReceiver::Ecc]),
+        21_875_000
+    );
+    assert_eq!(
+        u64::from(funding_streams[&FundingStreamReceiver::ZcashFoundation]),
+        15_625_000
+    );
+    assert_eq!(
+        u64::from(funding_streams[&FundingStreamReceiver::MajorGrants]),
+        25_000_000
+    );
+    assert_eq!(u64::from(miner_subsidy(Mainnet, height)), 250_000_000);
+
+    // NU6 replaces the ZIP-214 funding streams with the ZIP-1015 Zcash
+    // Community Grants stream and deferred pool, at the second halving
+    let height = BlockHeight(2_726_400);
+    let funding_streams = funding_stream_values(Mainnet, height);
+    assert_eq!(funding_streams.len(), 1);
+    assert_eq!(
+        u64::from(funding_streams[&FundingStreamReceiver::MajorGrants]),
+        12_500_000
+    );
+    assert_eq!(
+        u64::from(deferred_pool_contribution(Mainnet, height)),
+        18_750_000
+    );
+    assert_eq!(u64::from(miner_subsidy(Mainnet, height)), 125_000_000);
+
+    // The ZIP-1015 streams last for 420,000 blocks
+    let height = BlockHeight(3_146_399);
+    assert_eq!(funding_stream_values(Mainnet, height).len(), 1);
+    let height = BlockHeight(3_146_400);
+    assert!(funding_stream_values(Mainnet, height).is_empty());
+    assert_eq!(u64::from(deferred_pool_contribution(Mainnet, height)), 0);
+    assert_eq!(
+        miner_subsidy(Mainnet, height),
+        block_subsidy(Mainnet, height)
+    );
+}
+
+#[test]
+fn post_nu6_funding_stream_addresses_mainnet() {
+    for &height in &[2_726_400, 3_146_399] {
+        let height = BlockHeight(height);
+        assert_eq!(
+            FundingStreamReceiver::MajorGrants
+                .address_for(height, Mainnet)
+                .map(|address| address.to_string()),
+            Some("t3cFfPt1Bcvgez9ZbMBFWeZsskxTkPzGCow".to_string())
+        );
+        assert_eq!(
+            FundingStreamReceiver::Ecc.address_for(height, Mainnet),
+            None
+        );
+        assert_eq!(
+            FundingStreamReceiver::ZcashFoundation.address_for(height, Mainnet),
+            None
+        );
+    }
+
+    assert_eq!(
+        FundingStreamReceiver::MajorGrants.address_for(BlockHeight(3_146_400), Mainnet),
+        None
+    );
+}
+
+#[test]
+fn first_halving() {
+    assert_eq!(height_for_first_halving(Mainnet), BlockHeight(1_046_400));
+    assert_eq!(height_for_first_halving(Testnet), BlockHeight(1_116_000));
+    assert_eq!(halving(Mainnet, BlockHeight(1_046_399)), 0);
+    assert_eq!(halving(Mainnet, BlockHeight(1_046_400)), 1);
+}
+
+#[test]
+fn funding_stream_addresses_mainnet() {
+    funding_stream_addresses(Mainnet, 48)
+}
+
+#[test]
+fn funding_stream_addresses_testnet() {
+    funding_stream_addresses(Testnet, 51)
+}
+
+/// Check that there is a funding stream address for `network` at every
+/// funding stream height, and that there are `ecc_addresses` address
+/// periods.
+fn funding_stream_addresses(network: Network, ecc_addresses: usize) {
+    let heights = funding_stream_heights(network);
+    let before = (heights.start - 1).unwrap();
+    let last = (heights.end - 1).unwrap();
+
+    assert_eq!(funding_stream_address_index(network, before), None);
+    assert_eq!(
+        funding_stream_address_index(network, heights.start),
+        Some(0)
+    );
+    assert_eq!(
+        funding_stream_address_index(network, last),
+        Some(ecc_addresses - 1)
+    );
+    assert_eq!(funding_stream_address_index(network, heights.end), None);
+
+    for receiver in FundingStreamReceiver::ALL.iter() {
+        for height in &[heights.start, last] {
+            let address = receiver
+                .address_for(*height, network)
+                .expect("funding streams have an address at every height");
+            assert_eq!(address.network(), network);
+        }
+        // The post-NU6 funding stream has different addresses
+        assert_ne!(
+            receiver.address_for(heights.end, network),
+            receiver.address_for(last, network)
+        );
+    }
+}
+
+#[test]
+fn funding_stream_address_changes_mainnet() {
+    let ecc = FundingStreamReceiver::Ecc;
+    let first = ecc.address_for(BlockHeight(1_046_400), Mainnet);
+    assert_eq!(
+        first.map(|address| address.to_string()),
+        Some("t3LmX1cxWPPPqL4TZHx42HU3U5ghbFjRiif".to_owned())
+    );
+
+    // The address changes at multiples of the change interval, counting
+    // from the first halving
+    let change = (BlockHeight(1_046_400) + FUNDING_STREAM_ADDRESS_CHANGE_INTERVAL).unwrap();
+    assert_eq!(ecc.address_for((change - 1).unwrap(), Mainnet), first);
+    assert_ne!(ecc.address_for(change, Mainnet), first);
+
+    assert_eq!(
+        ecc.address_for(BlockHeight(2_726_399), Mainnet)
+            .map(|address| address.to_string()),
+        Some("t3XHAGxRP2FNfhAjxGjxbrQPYtQQjc3RCQD".to_owned())
+    );
+}
+
+/// Check that each consensus rule is listed once, and applies in a
+/// contiguous range of network upgrades.
+#[test]
+fn consensus_rules_contiguous() {
+    let rules: HashSet<&ConsensusRule> = CONSENSUS_RULES.iter().map(|(rule, _)| rule).collect();
+    assert_eq!(CONSENSUS_RULES.len(), rules.len());
+
+    let upgrades: Vec<NetworkUpgrade> = NetworkUpgrade::activation_list(Mainnet)
+        .values()
+        .cloned()
+        .collect();
+    for (rule, _) in CONSENSUS_RULES {
+        let active: Vec<bool> = upgrades
+            .iter()
+            .map(|upgrade| rule.is_active_in(*upgrade))
+            .collect();
+        let changes = active.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        assert!(
+            changes <= 2 && !(changes == 2 && active[0]),
+            "{:?} must apply in contiguous upgrades",
+            rule
+        );
+    }
+}
+
+#[test]
+fn consensus_rule_activation_mainnet() {
+    consensus_rule_activation(Mainnet)
+}
+
+#[test]
+fn consensus_rule_activation_testnet() {
+    consensus_rule_activation(Testnet)
+}
+
+/// Check that consensus rules activate at network upgrade heights on
+/// `network`.
+fn consensus_rule_activation(network: Network) {
+    let sapling_height = Sapling
+        .activation_height(network)
+        .expect("Sapling activation height is specified");
+    assert!(!ConsensusRule::SproutGroth16Proofs.is_active(network, (sapling_height - 1).unwrap()));
+    assert!(ConsensusRule::SproutGroth16Proofs.is_active(network, sapling_height));
+
+    // The founders' reward is replaced by funding streams
+    for upgrade in NetworkUpgrade::activation_list(network).values() {
+        assert_ne!(
+            ConsensusRule::FoundersReward.is_active_in(*upgrade),
+            ConsensusRule::FundingStreams.is_active_in(*upgrade),
+            "{:?}",
+            upgrade
+        );
+    }
+}
+
+#[test]
+fn deferred_pool() {
+    // NU6 doesn't have a default Testnet activation height
+    assert_eq!(
+        u64::from(deferred_pool_contribution(Testnet, BlockHeight::MAX)),
+        0
+    );
+
+    // The ZIP-1015 deferred pool gets 12% of the subsidy for 420,000 blocks
+    let contribution = |height| u64::from(deferred_pool_contribution(Mainnet, BlockHeight(height)));
+    assert_eq!(contribution(2_726_399), 0);
+    assert_eq!(contribution(2_726_400), 18_750_000);
+    assert_eq!(contribution(3_146_399), 18_750_000);
+    assert_eq!(contribution(3_146_400), 0);
+}
+
+/// Check the block version rules for the minimum, current, and future
+/// versions, and versions with the high bit set.
+#[test]
+fn block_version() {
+    assert_eq!(check_block_version(0), Err(BlockVersionError::TooLow(0)));
+    assert_eq!(
+        check_block_version(MIN_BLOCK_VERSION - 1),
+        Err(BlockVersionError::TooLow(MIN_BLOCK_VERSION - 1))
+    );
+    assert_eq!(
+        check_block_version(CURRENT_BLOCK_VERSION),
+        Ok(BlockVersion::Current)
+    );
+    assert_eq!(
+        check_block_version(CURRENT_BLOCK_VERSION + 1),
+        Ok(BlockVersion::Future)
+    );
+
+    // Some mainnet blocks have the bit-reversal of version 4
+    assert_eq!(check_block_version(536_870_912), Ok(BlockVersion::Future));
+
+    assert_eq!(
+        check_block_version(1 << 31),
+        Err(BlockVersionError::Negative(1 << 31))
+    );
+    assert_eq!(
+        check_block_version(u32::MAX),
+        Err(BlockVersionError::Negative(u32::MAX))
+    );
+}
+
+#[test]
+fn block_commitment_mainnet() {
+    block_commitment_kinds(Mainnet)
+}
+
+#[test]
+fn block_commitment_testnet() {
+    block_commitment_kinds(Testnet)
+}
+
+/// Check that the block header commitment is interpreted using the rules of
+/// each network upgrade on `network`.
+fn block_commitment_kinds(network: Network) {
+    use zebra_chain::block::{Commitment::*, CommitmentError};
+
+    let bytes = [1; 32];
+    let sapling_height = Sapling
+        .activation_height(network)
+        .expect("Sapling activation height is specified");
+    let heartwood_height = Heartwood
+        .activation_height(network)
+        .expect("Heartwood activation height is specified");
+
+    assert!(matches!(
+        block_commitment(network, (sapling_height - 1).unwrap(), bytes),
+        Ok(PreSaplingReserved(_))
+    ));
+    assert!(matches!(
+        block_commitment(network, sapling_height, bytes),
+        Ok(FinalSaplingRoot(_))
+    ));
+    assert!(matches!(
+        block_commitment(network, (heartwood_height - 1).unwrap(), bytes),
+        Ok(FinalSaplingRoot(_))
+    ));
+
+    // The Heartwood activation block has a reserved commitment
+    assert_eq!(
+        block_commitment(network, heartwood_height, [0; 32]),
+        Ok(ChainHistoryActivationReserved)
+    );
+    assert_eq!(
+        block_commitment(network, heartwood_height, bytes),
+        Err(CommitmentError::InvalidChainHistoryActivationReserved { actual: bytes })
+    );
+
+    assert!(matches!(
+        block_commitment(network, (heartwood_height + 1).unwrap(), bytes),
+        Ok(ChainHistoryRoot(_))
+    ));
+
+    for (height, upgrade) in NetworkUpgrade::activation_list(network) {
+        if ConsensusRule::BlockCommitments.is_active_in(upgrade) {
+            assert!(matches!(
+                block_commitment(network, height, bytes),
+                Ok(ChainHistoryBlockTxAuthCommitment(_))
+            ));
+        }
+    }
+
+    // NU5 replaces the chain history root with the block commitments hash
+    if let Some(nu5_height) = Nu5.activation_height(network) {
+        assert!(matches!(
+            block_commitment(network, (nu5_height - 1).unwrap(), bytes),
+            Ok(ChainHistoryRoot(_))
+        ));
+        assert!(matches!(
+            block_commitment(network, nu5_height, bytes),
+            Ok(ChainHistoryBlockTxAuthCommitment(_))
+        ));
+    }
+
+    // Commitments round-trip to the same bytes
+    for height in &[sapling_height, heartwood_height, BlockHeight::MAX] {
+        if let Ok(commitment) = block_commitment(network, *height, bytes) {
+            assert_eq!(commitment.to_bytes(), bytes);
+        }
+    }
+}
+
+#[test]
+fn note_plaintext_versions_mainnet() {
+    use zebra_chain::notes::sapling::{NOTE_PLAINTEXT_V1, NOTE_PLAINTEXT_V2};
+
+    let canopy = Canopy
+        .activation_height(Mainnet)
+        .expect("Canopy activation height is specified");
+    let grace_period_end = (canopy + ZIP212_GRACE_PERIOD).unwrap();
+    let before = (canopy - 1).unwrap();
+    let last_grace = (grace_period_end - 1).unwrap();
+
+    // Coinbase outputs switch versions at activation
+    assert_eq!(
+        coinbase_note_plaintext_version(Mainnet, before),
+        NOTE_PLAINTEXT_V1
+    );
+    assert_eq!(
+        coinbase_note_plaintext_version(Mainnet, canopy),
+        NOTE_PLAINTEXT_V2
+    );
+
+    // Other notes have a grace period
+    let valid = |height, lead_byte| is_valid_note_plaintext_version(Mainnet, height, lead_byte);
+    assert!(valid(before, NOTE_PLAINTEXT_V1));
+    assert!(!valid(before, NOTE_PLAINTEXT_V2));
+    assert!(valid(canopy, NOTE_PLAINTEXT_V1));
+    assert!(valid(canopy, NOTE_PLAINTEXT_V2));
+    assert!(valid(last_grace, NOTE_PLAINTEXT_V1));
+    assert!(!valid(grace_period_end, NOTE_PLAINTEXT_V1));
+    assert!(valid(grace_period_end, NOTE_PLAINTEXT_V2));
+
+    // Unknown versions are never valid
+    assert!(!valid(canopy, 0x03));
+}
@@ -9,14 +9,652 @@
 //! Verification is provided via a `tower::Service`, to support backpressure and batch
 //! verification.
 //!
+//! Transactions in blocks and mempool transactions are verified using
+//! different rules, so each [`Request`] says which context the transaction is
+//! verified in.
+//!
 //! This is an internal module. Use `verify::BlockVerifier` for blocks and their
 //! transactions, or `mempool::MempoolTransactionVerifier` for mempool transactions.
 
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error, fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use futures_util::FutureExt;
+use tower::{Service, ServiceExt};
+
+use zebra_chain::{
+    keys::sapling::OutgoingViewingKey,
+    parameters::ConsensusBranchId,
+    transaction::{OutPoint, Transaction, TransactionHash, TransparentOutput},
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight, HeightDiff, LockTime,
+    },
+    Network,
+};
+
+use crate::{
+    mempool::{
+        policy, Config, MempoolError, ZebraMempoolState, COINBASE_MATURITY,
+        TX_EXPIRING_SOON_THRESHOLD,
+    },
+    parameters::{self, ConsensusRule, FundingStreamAddress, FundingStreamReceiver},
+};
+
+/// The error type for the transaction verifier.
+type Error = Box<dyn error::Error + Send + Sync + 'static>;
+
+/// The longest time that a block transaction waits for each of its inputs.
+///
+/// Inputs can spend outputs from blocks that are still being verified, so
+/// lookups wait for those blocks to be committed. But blocks that spend
+/// missing outputs must eventually fail, rather than waiting forever.
+pub const UTXO_LOOKUP_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+
+/// The reason a transaction in a block is invalid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TransactionError {
+    /// An input spends an output that is not in the block, and was not added
+    /// to the state within [`UTXO_LOOKUP_TIMEOUT`].
+    MissingInput(OutPoint),
+    /// An input spends a coinbase output that has not matured.
+    ImmatureCoinbase(OutPoint),
+    /// The transaction spends less than it creates.
+    InputsBelowOutputs {
+        /// The value of the transparent inputs and shielded value transfers
+        /// into the transparent pool, in zatoshis.
+        inputs: i64,
+        /// The value of the transparent outputs and shielded value transfers
+        /// out of the transparent pool, in zatoshis.
+        outputs: i64,
+    },
+    /// The consensus branch ID in the transaction is not the branch ID of the
+    /// network upgrade at the block height.
+    WrongConsensusBranchId {
+        /// The branch ID in the transaction.
+        found: ConsensusBranchId,
+        /// The branch ID at the block height, if there is one.
+        expected: Option<ConsensusBranchId>,
+    },
+    /// A Sapling output in a coinbase transaction can't be recovered with
+    /// the all-zero outgoing viewing key.
+    UnrecoverableCoinbaseOutput,
+    /// A Sapling output in a coinbase transaction uses the wrong note
+    /// plaintext version for the block height.
+    WrongNotePlaintextVersion {
+        /// The note plaintext lead byte in the output.
+        found: u8,
+        /// The note plaintext lead byte for coinbase outputs at the block
+        /// height.
+        expected: u8,
+    },
+    /// A coinbase transaction doesn't pay a funding stream to its address.
+    MissingFundingStreamOutput(FundingStreamReceiver),
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransactionError::MissingInput(outpoint) => write!(
+                f,
+                "missing input {}:{}: output was not added to the state in time",
+                outpoint.hash, outpoint.index
+            ),
+            TransactionError::ImmatureCoinbase(outpoint) => write!(
+                f,
+                "input {}:{} spends an immature coinbase output",
+                outpoint.hash, outpoint.index
+            ),
+            TransactionError::InputsBelowOutputs { inputs, outputs } => write!(
+                f,
+                "input value {} is less than output value {}",
+                inputs, outputs
+            ),
+            TransactionError::WrongConsensusBranchId { found, expected } => match expected {
+                Some(expected) => write!(
+                    f,
+                    "transaction consensus branch ID {} does not match the block's branch ID {}",
+                    found, expected
+                ),
+                None => write!(
+                    f,
+                    "transaction has consensus branch ID {}, but the block's network upgrade has no branch ID",
+                    found
+                ),
+            },
+            TransactionError::UnrecoverableCoinbaseOutput => f.write_str(
+                "coinbase output can't be recovered with the all-zero outgoing viewing key",
+            ),
+            TransactionError::WrongNotePlaintextVersion { found, expected } => write!(
+                f,
+                "coinbase output has note plaintext version {:#04x}, but it must be {:#04x}",
+                found, expected
+            ),
+            TransactionError::MissingFundingStreamOutput(receiver) => write!(
+                f,
+                "coinbase transaction doesn't pay the {:?} funding stream",
+                receiver
+            ),
+        }
+    }
+}
+
+impl error::Error for TransactionError {}
+
+/// A transaction verification request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Request {
+    /// Verify a transaction in a block.
+    ///
+    /// Coinbase transactions are allowed, and the transaction must be valid
+    /// at the height and time of the block.
+    ///
+    /// Inputs are looked up in `known_utxos`, then in the state. State
+    /// lookups wait for the output's block to be committed, for up to
+    /// [`UTXO_LOOKUP_TIMEOUT`].
+    Block {
+        /// The transaction to verify.
+        transaction: Arc<Transaction>,
+        /// The outputs created by the block that contains the transaction.
+        known_utxos: Arc<HashMap<OutPoint, zebra_state::Utxo>>,
+        /// The height of the block that contains the transaction.
+        height: BlockHeight,
+        /// The time in the header of the block that contains the transaction.
+        time: DateTime<Utc>,
+    },
+    /// Verify an unmined transaction for the mempool.
+    ///
+    /// Coinbase transactions are rejected, the transaction must not expire
+    /// soon after `height`, and it must meet the standard transaction policy,
+    /// including the minimum fee.
+    ///
+    /// Mempool transactions fail with a [`MempoolError`].
+    Mempool {
+        /// The transaction to verify.
+        transaction: Arc<Transaction>,
+        /// The height of the next block.
+        height: BlockHeight,
+    },
+}
+
+/// A transaction verification response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Response {
+    /// The response to a `Block` request.
+    Block {
+        /// The hash of the verified transaction.
+        hash: TransactionHash,
+    },
+    /// The response to a `Mempool` request.
+    Mempool {
+        /// The hash of the verified transaction.
+        hash: TransactionHash,
+        /// The fee paid by the transaction.
+        fee: Amount<NonNegative>,
+    },
+}
+
 /// Internal transaction verification service.
 ///
 /// After verification, the transaction future completes. State changes are handled by
 /// `BlockVerifier` or `MempoolTransactionVerifier`.
 ///
-/// `TransactionVerifier` is not yet implemented.
-#[derive(Default)]
-pub(crate) struct TransactionVerifier {}
+/// Transactions can spend outputs from the best chain, and mempool
+/// transactions can also spend outputs from other mempool transactions.
+/// Scripts and shielded proofs are not verified yet.
+///
+/// Transactions are bound to the network upgrade at their block height, by
+/// the consensus branch ID for `network` at that height.
+#[derive(Clone)]
+pub(crate) struct TransactionVerifier<S> {
+    /// The network this verifier checks transactions for.
+    network: Network,
+
+    /// The underlying `ZebraState`, possibly wrapped in other services.
+    state_service: S,
+
+    /// The standard transaction policy, and the mempool that mempool
+    /// transactions can spend outputs from.
+    ///
+    /// If this is `None`, `Mempool` requests fail.
+    mempool: Option<(Config, ZebraMempoolState)>,
+}
+
+impl<S> TransactionVerifier<S>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    /// Returns a transaction verifier for `network`, which checks
+    /// transactions against `state_service`.
+    pub(crate) fn new(network: Network, state_service: S) -> Self {
+        TransactionVerifier {
+            network,
+            state_service,
+            mempool: None,
+        }
+    }
+
+    /// Returns this verifier, with mempool transactions checked against the
+    /// policy in `config`, and allowed to spend outputs from `mempool`.
+    pub(crate) fn with_mempool(self, config: Config, mempool: ZebraMempoolState) -> Self {
+        TransactionVerifier {
+            mempool: Some((config, mempool)),
+            ..self
+        }
+    }
+}
+
+impl<S> Service<Request> for TransactionVerifier<S>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // We expect state queries to be fast, so we don't need to call
+        // `state_service.poll_ready()` here.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match request {
+            Request::Block {
+                transaction,
+                known_utxos,
+                height,
+                time,
+            } => {
+                let network = self.network;
+                let state = self.state_service.clone();
+
+                async move {
+                    let hash = TransactionHash::from(&*transaction);
+
+                    check_consensus_branch_id(&transaction, network, height).map_err(
+                        |(found, expected)| TransactionError::WrongConsensusBranchId {
+                            found,
+                            expected,
+                        },
+                    )?;
+                    if is_expired(&transaction, height) {
+                        Err("transaction is expired at the block height")?;
+                    }
+                    if !is_final(&transaction, height, time) {
+                        Err("transaction lock time is after the block height or time")?;
+                    }
+                    if transaction.is_coinbase() {
+                        check_coinbase_outputs(&transaction, network, height)?;
+                        check_funding_streams(&transaction, network, height)?;
+                        return Ok(Response::Block { hash });
+                    }
+
+                    let mut inputs = i64::from(transaction.shielded_value_in()?);
+                    for outpoint in transaction.spent_outpoints() {
+                        let utxo = match known_utxos.get(outpoint) {
+                            Some(utxo) => utxo.clone(),
+                            None => await_utxo(state.clone(), *outpoint).await?,
+                        };
+                        if utxo.from_coinbase && height - utxo.height < COINBASE_MATURITY {
+                            Err(TransactionError::ImmatureCoinbase(*outpoint))?;
+                        }
+                        inputs += i64::from(utxo.output.value);
+                    }
+                    let outputs = i64::from(transaction.transparent_value_out()?)
+                        + i64::from(transaction.shielded_value_out()?);
+
+                    if inputs < outputs {
+                        Err(TransactionError::InputsBelowOutputs { inputs, outputs })?;
+                    }
+
+                    Ok(Response::Block { hash })
+                }
+                .boxed()
+            }
+            Request::Mempool {
+                transaction,
+                height,
+            } => {
+                let network = self.network;
+                let state = self.state_service.clone();
+                let (config, mempool) = match self.mempool.clone() {
+                    Some(mempool) => mempool,
+                    None => {
+                        return async { Err("mempool transactions can't be verified".into()) }
+                            .boxed()
+                    }
+                };
+
+                async move {
+                    let hash = TransactionHash::from(&*transaction);
+
+                    if transaction.is_coinbase() {
+                        Err(MempoolError::Coinbase)?;
+                    }
+                    if mempool.contains(&hash) {
+                        Err(MempoolError::AlreadyInMempool)?;
+                    }
+                    if best_chain_transaction(state.clone(), hash).await?.is_some() {
+                        Err(MempoolError::AlreadyInChain)?;
+                    }
+                    check_consensus_branch_id(&transaction, network, height).map_err(
+                        |(found, expected)| MempoolError::WrongConsensusBranchId {
+                            found,
+                            expected,
+                        },
+                    )?;
+
+                    policy::check_standard(&config, &transaction).map_err(MempoolError::from)?;
+                    if is_expiring_soon(&transaction, height) {
+                        Err(MempoolError::Expired {
+                            expiry_height: transaction
+                                .expiry_height()
+                                .expect("expired transactions have an expiry height"),
+                            next_height: height,
+                        })?;
+                    }
+                    // zcashd uses the median time of recent blocks, which the
+                    // state doesn't provide yet
+                    if !is_final(&transaction, height, Utc::now()) {
+                        Err(MempoolError::NonFinal)?;
+                    }
+
+                    let mut inputs = i64::from(transaction.shielded_value_in()?);
+                    for outpoint in transaction.spent_outpoints() {
+                        let output = match mempool.output(outpoint) {
+                            Some(output) => output,
+                            None => best_chain_output(state.clone(), outpoint).await?,
+                        };
+                        inputs += i64::from(output.value);
+                    }
+                    let outputs = i64::from(transaction.transparent_value_out()?)
+                        + i64::from(transaction.shielded_value_out()?);
+
+                    let fee = Amount::try_from(inputs - outputs)
+                        .map_err(|_| MempoolError::InputsBelowOutputs { inputs, outputs })?;
+                    policy::check_fee(&config, &transaction, fee).map_err(MempoolError::from)?;
+
+                    // Don't accept transactions that could be invalid, because
+                    // the mempool relays them to peers, and miners include them
+                    // in blocks
+                    if has_unverified_authorization(&transaction) {
+                        Err(MempoolError::Unverifiable)?;
+                    }
+
+                    Ok(Response::Mempool { hash, fee })
+                }
+                .boxed()
+            }
+        }
+    }
+}
+
+/// Returns true if `transaction` has transparent scripts, JoinSplits, or
+/// Sapling shielded data, which Zebra can't verify yet.
+fn has_unverified_authorization(transaction: &Transaction) -> bool {
+    transaction.inputs().next().is_some()
+        || transaction.joinsplit_count() > 0
+        || transaction.shielded_data().is_some()
+}
+
+/// Checks that the consensus branch ID in `transaction`, if it has one, is
+/// the branch ID of the network upgrade at `height` on `network`.
+///
+/// Returns the transaction's branch ID and the expected branch ID if they
+/// don't match. Transactions without a branch ID are only bound to the
+/// upgrade by their signature hashes, so they always pass this check.
+pub(crate) fn check_consensus_branch_id(
+    transaction: &Transaction,
+    network: Network,
+    height: BlockHeight,
+) -> Result<(), (ConsensusBranchId, Option<ConsensusBranchId>)> {
+    check_branch_id(transaction.consensus_branch_id(), network, height)
+}
+
+/// Checks that `found`, a transaction's consensus branch ID, is the branch ID
+/// of the network upgrade at `height` on `network`.
+///
+/// Zebra doesn't parse V5 transactions yet, so `found` is always `None` for
+/// real transactions.
+fn check_branch_id(
+    found: Option<ConsensusBranchId>,
+    network: Network,
+    height: BlockHeight,
+) -> Result<(), (ConsensusBranchId, Option<ConsensusBranchId>)> {
+    let found = match found {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    let expected = parameters::current_branch_id(network, height);
+    if expected == Some(found) {
+        Ok(())
+    } else {
+        Err((found, expected))
+    }
+}
+
+/// Checks that the Sapling outputs of the coinbase `transaction`, in the
+/// block at `height` on `network`, can be recovered with the all-zero
+/// outgoing viewing key, and use the coinbase note plaintext version.
+///
+/// Coinbase transactions can't have Sapling outputs before Heartwood, so
+/// there is nothing to check before then.
+fn check_coinbase_outputs(
+    transaction: &Transaction,
+    network: Network,
+    height: BlockHeight,
+) -> Result<(), TransactionError> {
+    if !ConsensusRule::CoinbaseOutputRecovery.is_active(network, height) {
+        return Ok(());
+    }
+
+    let ovk = OutgoingViewingKey::from([0; 32]);
+    let expected = parameters::coinbase_note_plaintext_version(network, height);
+    for output in transaction.sapling_outputs() {
+        let note = output
+            .recover_note(&ovk)
+            .ok_or(TransactionError::UnrecoverableCoinbaseOutput)?;
+        if note.lead_byte != expected {
+            return Err(TransactionError::WrongNotePlaintextVersion {
+                found: note.lead_byte,
+                expected,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the coinbase `transaction`, in the block at `height` on
+/// `network`, pays each funding stream to its address.
+///
+/// Transparent addresses must be paid by a transparent output with the
+/// address's script. Sapling addresses must be paid by a Sapling output that
+/// can be recovered with the all-zero outgoing viewing key. Recovery checks
+/// the output's note commitment, so the note must have the address's
+/// diversifier and transmission key, and the funding stream value.
+///
+/// If there is no address for a funding stream at `height`, because
+/// `network` is a test network with custom activation heights, that stream
+/// isn't checked.
+fn check_funding_streams(
+    transaction: &Transaction,
+    network: Network,
+    height: BlockHeight,
+) -> Result<(), TransactionError> {
+    let funding_streams = parameters::funding_stream_values(network, height);
+    if funding_streams.is_empty() {
+        return Ok(());
+    }
+
+    let ovk = OutgoingViewingKey::from([0; 32]);
+    let notes: Vec<_> = transaction
+        .sapling_outputs()
+        .filter_map(|output| output.recover_note(&ovk))
+        .collect();
+
+    for receiver in FundingStreamReceiver::ALL.iter() {
+        let (value, address) = match (
+            funding_streams.get(receiver),
+            receiver.address_for(height, network),
+        ) {
+            (Some(value), Some(address)) => (*value, address),
+            _ => continue,
+        };
+
+        let is_paid = match address {
+            FundingStreamAddress::Transparent(address) => {
+                let pk_script = address.output_script();
+                transaction
+                    .outputs()
+                    .any(|output| output.value == value && output.pk_script == pk_script)
+            }
+            FundingStreamAddress::Sapling(address) => notes.iter().any(|note| {
+                note.diversifier == address.diversifier()
+                    && note.transmission_key == address.transmission_key()
+                    && note.value == u64::from(value)
+            }),
+        };
+        if !is_paid {
+            return Err(TransactionError::MissingFundingStreamOutput(*receiver));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if `transaction` can't be mined in the block at `height`,
+/// because it has expired.
+///
+/// An expiry height of zero means that the transaction never expires, and
+/// coinbase transactions don't expire.
+pub(crate) fn is_expired(transaction: &Transaction, height: BlockHeight) -> bool {
+    match transaction.expiry_height() {
+        Some(expiry_height) => {
+            expiry_height.0 != 0 && !transaction.is_coinbase() && height > expiry_height
+        }
+        None => false,
+    }
+}
+
+/// Returns true if `transaction` expires within
+/// [`TX_EXPIRING_SOON_THRESHOLD`] blocks of `next_height`.
+///
+/// Transactions that are expiring soon are not accepted into the mempool,
+/// so they have time to be mined.
+pub(crate) fn is_expiring_soon(transaction: &Transaction, next_height: BlockHeight) -> bool {
+    // Heights after the maximum height are treated as the maximum height
+    let expiring_height = (next_height + TX_EXPIRING_SOON_THRESHOLD).unwrap_or(BlockHeight::MAX);
+    is_expired(transaction, expiring_height)
+}
+
+/// Returns true if the lock time of `transaction` allows it to be mined in
+/// a block at `height` and `time`.
+///
+/// Like zcashd, transactions with a lock time of zero, or with final
+/// sequence numbers in all their inputs, are always final.
+fn is_final(transaction: &Transaction, height: BlockHeight, time: DateTime<Utc>) -> bool {
+    let unlocked = match transaction.lock_time() {
+        LockTime::Height(BlockHeight(0)) => true,
+        LockTime::Height(lock_height) => lock_height < height,
+        LockTime::Time(lock_time) => lock_time < time,
+    };
+
+    unlocked
+        || transaction
+            .inputs()
+            .all(|input| input.sequence() == u32::MAX)
+}
+
+/// Returns the transaction with `hash`, and its depth, if it is in the best
+/// chain.
+pub(crate) async fn best_chain_transaction<S>(
+    state: S,
+    hash: TransactionHash,
+) -> Result<Option<(Arc<Transaction>, u32)>, Error>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error> + Clone,
+{
+    // The state returns an error for unknown transactions
+    let (transaction, block_hash) = match state
+        .clone()
+        .oneshot(zebra_state::Request::GetTransaction { hash })
+        .await
+    {
+        Ok(zebra_state::Response::Transaction {
+            transaction,
+            block_hash,
+        }) => (transaction, block_hash),
+        Ok(_) => unreachable!("GetTransaction request can only result in Response::Transaction"),
+        Err(_) => return Ok(None),
+    };
+
+    match state
+        .oneshot(zebra_state::Request::GetDepth { hash: block_hash })
+        .await?
+    {
+        zebra_state::Response::Depth(depth) => Ok(depth.map(|depth| (transaction, depth))),
+        _ => unreachable!("GetDepth request can only result in Response::Depth"),
+    }
+}
+
+/// Returns the output spent by `outpoint`, waiting up to
+/// [`UTXO_LOOKUP_TIMEOUT`] for it to be added to the state.
+async fn await_utxo<S>(state: S, outpoint: OutPoint) -> Result<zebra_state::Utxo, Error>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error>,
+{
+    let lookup = state.oneshot(zebra_state::Request::AwaitUtxo { outpoint });
+    match tokio::time::timeout(UTXO_LOOKUP_TIMEOUT, lookup).await {
+        Ok(Ok(zebra_state::Response::Utxo(utxo))) => Ok(utxo),
+        Ok(Ok(_)) => unreachable!("AwaitUtxo request can only result in Response::Utxo"),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(TransactionError::MissingInput(outpoint).into()),
+    }
+}
+
+/// Returns the best chain output spent by `outpoint`, checking that it can
+/// be spent in the next block.
+async fn best_chain_output<S>(state: S, outpoint: &OutPoint) -> Result<TransparentOutput, Error>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = Error> + Clone,
+{
+    let (transaction, depth) = best_chain_transaction(state, outpoint.hash)
+        .await?
+        .ok_or(MempoolError::MissingInput(*outpoint))?;
+    let output = transaction
+        .outputs()
+        .nth(outpoint.index as usize)
+        .cloned()
+        .ok_or(MempoolError::MissingInput(*outpoint))?;
+
+    // The next block is `depth + 1` blocks above the output
+    if transaction.is_coinbase() && HeightDiff::from(depth) + 1 < COINBASE_MATURITY {
+        Err(MempoolError::ImmatureCoinbase(*outpoint))?;
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,360 @@
+//! Tests for the transaction verifier.
+
+use std::{convert::TryInto, sync::Arc};
+
+use chrono::{TimeZone, Utc};
+use color_eyre::eyre::{eyre, Report};
+use tower::ServiceExt;
+
+use zebra_chain::{
+    block::Block,
+    serialization::ZcashDeserialize,
+    transaction::{
+        CoinbaseData, OutPoint, Transaction, TransactionHash, TransparentInput, TransparentOutput,
+    },
+    types::{amount::Amount, BlockHeight, LockTime, Script},
+    Network,
+};
+
+use super::*;
+use crate::parameters::NetworkUpgrade;
+
+/// Returns a V4 transaction with one input that has `sequence`, and
+/// `lock_time` and `expiry_height`.
+fn transaction(sequence: u32, lock_time: LockTime, expiry_height: BlockHeight) -> Transaction {
+    Transaction::V4 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint: OutPoint {
+                hash: TransactionHash([0; 32]),
+                index: 0,
+            },
+            script: Script(vec![]),
+            sequence,
+        }],
+        outputs: Vec::new(),
+        lock_time,
+        expiry_height,
+        value_balance: 0i64.try_into().unwrap(),
+        shielded_data: None,
+        joinsplit_data: None,
+    }
+}
+
+/// Returns a V4 transaction that spends `outpoint`, and has no outputs.
+fn spend(outpoint: OutPoint) -> Arc<Transaction> {
+    let mut tx = transaction(u32::MAX, LockTime::Height(BlockHeight(0)), BlockHeight(0));
+    if let Transaction::V4 { inputs, .. } = &mut tx {
+        inputs[0] = TransparentInput::PrevOut {
+            outpoint,
+            script: Script(vec![]),
+            sequence: u32::MAX,
+        };
+    }
+    Arc::new(tx)
+}
+
+#[test]
+fn expiry() {
+    let never = transaction(u32::MAX, LockTime::Height(BlockHeight(0)), BlockHeight(0));
+    assert!(!is_expired(&never, BlockHeight(1_000_000)));
+
+    let tx = transaction(u32::MAX, LockTime::Height(BlockHeight(0)), BlockHeight(10));
+    assert!(!is_expired(&tx, BlockHeight(10)));
+    assert!(is_expired(&tx, BlockHeight(11)));
+
+    // Mempool transactions need time to be mined
+    assert!(!is_expiring_soon(&tx, BlockHeight(7)));
+    assert!(is_expiring_soon(&tx, BlockHeight(8)));
+}
+
+#[test]
+fn lock_time() {
+    let time = Utc.timestamp(1_600_000_000, 0);
+
+    let tx = transaction(0, LockTime::Height(BlockHeight(10)), BlockHeight(0));
+    assert!(!is_final(&tx, BlockHeight(10), time));
+    assert!(is_final(&tx, BlockHeight(11), time));
+
+    let tx = transaction(0, LockTime::Time(time), BlockHeight(0));
+    assert!(!is_final(&tx, BlockHeight(10), time));
+    assert!(is_final(
+        &tx,
+        BlockHeight(10),
+        Utc.timestamp(1_600_000_001, 0)
+    ));
+
+    // Final sequence numbers disable the lock time
+    let tx = transaction(u32::MAX, LockTime::Height(BlockHeight(10)), BlockHeight(0));
+    assert!(is_final(&tx, BlockHeight(10), time));
+}
+
+#[test]
+fn consensus_branch_id() {
+    zebra_test::init();
+
+    // Transactions before V5 only commit to the branch ID in their sighashes,
+    // so they pass the check before and after each upgrade
+    let tx = transaction(u32::MAX, LockTime::Height(BlockHeight(0)), BlockHeight(0));
+    assert_eq!(tx.consensus_branch_id(), None);
+    for &network in &[Network::Mainnet, Network::Testnet] {
+        for &height in &[BlockHeight(0), BlockHeight(419_200), BlockHeight::MAX] {
+            assert_eq!(check_consensus_branch_id(&tx, network, height), Ok(()));
+        }
+    }
+
+    // V5 transactions must have the branch ID of the current upgrade
+    let sapling = NetworkUpgrade::Sapling.branch_id();
+    let blossom = NetworkUpgrade::Blossom.branch_id();
+    assert_eq!(
+        check_branch_id(sapling, Network::Mainnet, BlockHeight(419_200)),
+        Ok(())
+    );
+
+    let error = check_branch_id(sapling, Network::Mainnet, BlockHeight(653_600)).unwrap_err();
+    assert_eq!(error, (sapling.unwrap(), blossom));
+    let error = TransactionError::WrongConsensusBranchId {
+        found: error.0,
+        expected: error.1,
+    };
+    assert!(error.to_string().contains("does not match"));
+
+    // Upgrades before Overwinter don't have a branch ID
+    let error = check_branch_id(sapling, Network::Mainnet, BlockHeight(0)).unwrap_err();
+    assert_eq!(error, (sapling.unwrap(), None));
+}
+
+#[test]
+fn coinbase_outputs_without_sapling() {
+    zebra_test::init();
+
+    // Coinbase outputs are only checked if they are shielded, so
+    // transparent-only transactions pass before and after Heartwood and
+    // Canopy
+    let tx = transaction(u32::MAX, LockTime::Height(BlockHeight(0)), BlockHeight(0));
+    for &height in &[BlockHeight(0), BlockHeight(903_000), BlockHeight(1_046_400)] {
+        assert_eq!(
+            check_coinbase_outputs(&tx, Network::Mainnet, height),
+            Ok(())
+        );
+    }
+}
+
+#[test]
+fn coinbase_outputs_with_sapling() {
+    zebra_test::init();
+
+    let block = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_949496_BYTES[..])
+        .expect("test block is valid");
+    let height = BlockHeight(949_496);
+    let coinbase = &block.transactions[0];
+    assert_eq!(coinbase.sapling_outputs().count(), 1);
+    assert_eq!(
+        check_coinbase_outputs(coinbase, Network::Mainnet, height),
+        Ok(())
+    );
+
+    // Canopy coinbase outputs must use ZIP-212 note plaintexts
+    assert_eq!(
+        check_coinbase_outputs(coinbase, Network::Mainnet, BlockHeight(1_046_400)),
+        Err(TransactionError::WrongNotePlaintextVersion {
+            found: 0x01,
+            expected: 0x02,
+        })
+    );
+}
+
+#[test]
+fn funding_streams() {
+    zebra_test::init();
+
+    let network = Network::Mainnet;
+    let height = BlockHeight(1_046_400);
+    let coinbase = |outputs| Transaction::V4 {
+        inputs: vec![TransparentInput::Coinbase {
+            height,
+            data: CoinbaseData::new(Vec::new()).unwrap(),
+            sequence: u32::MAX,
+        }],
+        outputs,
+        lock_time: LockTime::Height(BlockHeight(0)),
+        expiry_height: BlockHeight(0),
+        value_balance: 0i64.try_into().unwrap(),
+        shielded_data: None,
+        joinsplit_data: None,
+    };
+
+    let funding_streams = parameters::funding_stream_values(network, height);
+    let mut outputs: Vec<TransparentOutput> = FundingStreamReceiver::ALL
+        .iter()
+        .map(|receiver| TransparentOutput {
+            value: funding_streams[receiver],
+            pk_script: receiver
+                .address_for(height, network)
+                .and_then(|address| address.output_script())
+                .expect("Canopy funding stream addresses are transparent"),
+        })
+        .collect();
+    assert_eq!(
+        check_funding_streams(&coinbase(outputs.clone()), network, height),
+        Ok(())
+    );
+
+    // Funding streams start at Canopy
+    assert_eq!(
+        check_funding_streams(&coinbase(Vec::new()), network, BlockHeight(1_046_399)),
+        Ok(())
+    );
+
+    // Each funding stream must be paid its exact value
+    let mut underpaid = outputs.clone();
+    underpaid[0].value = (u64::from(underpaid[0].value) - 1).try_into().unwrap();
+    assert_eq!(
+        check_funding_streams(&coinbase(underpaid), network, height),
+        Err(TransactionError::MissingFundingStreamOutput(
+            FundingStreamReceiver::Ecc
+        ))
+    );
+
+    outputs.pop();
+    assert_eq!(
+        check_funding_streams(&coinbase(outputs), network, height),
+        Err(TransactionError::MissingFundingStreamOutput(
+            FundingStreamReceiver::MajorGrants
+        ))
+    );
+}
+
+#[tokio::test]
+async fn block_and_mempool_contexts() -> Result<(), Report> {
+    zebra_test::init();
+
+    let verifier = TransactionVerifier::new(Network::Mainnet, zebra_state::in_memory::init());
+    let block = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?;
+    let coinbase = block.transactions[0].clone();
+
+    // Coinbase transactions are valid in blocks
+    let response = verifier
+        .clone()
+        .oneshot(Request::Block {
+            transaction: coinbase.clone(),
+            known_utxos: Arc::new(HashMap::new()),
+            height: BlockHeight(1),
+            time: block.header.time,
+        })
+        .await
+        .map_err(|e| eyre!(e))?;
+    assert_eq!(
+        response,
+        Response::Block {
+            hash: TransactionHash::from(&*coinbase)
+        }
+    );
+
+    // Block transactions must be valid at the block height
+    let tx = Arc::new(transaction(
+        u32::MAX,
+        LockTime::Height(BlockHeight(0)),
+        BlockHeight(1),
+    ));
+    let request = Request::Block {
+        transaction: tx,
+        known_utxos: Arc::new(HashMap::new()),
+        height: BlockHeight(2),
+        time: block.header.time,
+    };
+    assert!(verifier.clone().oneshot(request).await.is_err());
+
+    // Mempool requests need a mempool
+    let request = Request::Mempool {
+        transaction: coinbase.clone(),
+        height: BlockHeight(2),
+    };
+    assert!(verifier.clone().oneshot(request.clone()).await.is_err());
+
+    // Coinbase transactions are rejected from the mempool
+    let error = verifier
+        .with_mempool(Config::default(), ZebraMempoolState::default())
+        .oneshot(request)
+        .await
+        .expect_err("coinbase transactions should be rejected");
+    assert_eq!(
+        *error.downcast::<MempoolError>().map_err(|e| eyre!(e))?,
+        MempoolError::Coinbase
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn block_inputs() -> Result<(), Report> {
+    zebra_test::init();
+
+    let state = zebra_state::in_memory::init();
+    let verifier = TransactionVerifier::new(Network::Mainnet, state.clone());
+    let block = Arc::new(Block::zcash_deserialize(
+        &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+    )?);
+    let outpoint = OutPoint {
+        hash: TransactionHash::from(&*block.transactions[0]),
+        index: 0,
+    };
+
+    // Outputs from the same block are immature coinbase outputs
+    let error = verifier
+        .clone()
+        .oneshot(Request::Block {
+            transaction: spend(outpoint),
+            known_utxos: Arc::new(zebra_state::Utxo::from_block(&block)),
+            height: BlockHeight(1),
+            time: block.header.time,
+        })
+        .await
+        .expect_err("immature coinbase spends should be rejected");
+    assert_eq!(
+        *error.downcast::<TransactionError>().map_err(|e| eyre!(e))?,
+        TransactionError::ImmatureCoinbase(outpoint)
+    );
+
+    // Lookups wait for the output's block to be added to the state
+    let request = verifier.oneshot(Request::Block {
+        transaction: spend(outpoint),
+        known_utxos: Arc::new(HashMap::new()),
+        height: BlockHeight(101),
+        time: block.header.time,
+    });
+    let mut verified = tokio::spawn(request);
+    assert!(
+        tokio::time::timeout(Duration::from_millis(10), &mut verified)
+            .await
+            .is_err(),
+        "missing inputs should wait for the state"
+    );
+
+    let genesis = Arc::new(Block::zcash_deserialize(
+        &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+    )?);
+    state
+        .clone()
+        .oneshot(zebra_state::Request::AddBlock {
+            block: genesis,
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await
+        .map_err(|e| eyre!(e))?;
+    state
+        .oneshot(zebra_state::Request::AddBlock {
+            block,
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await
+        .map_err(|e| eyre!(e))?;
+    let response = verified.await?.map_err(|e| eyre!(e))?;
+    assert_eq!(
+        response,
+        Response::Block {
+            hash: TransactionHash::from(&*spend(outpoint))
+        }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,322 @@
+//! Derive macros for Zcash consensus-critical serialization.
+//!
+//! `#[derive(ZcashSerialize, ZcashDeserialize)]` implements the
+//! `zebra_chain::serialization` traits for a struct, by serializing each of
+//! its fields in declaration order. The derives are re-exported by
+//! `zebra_chain::serialization`, so other crates don't need to depend on this
+//! crate.
+//!
+//! Each field uses its own `ZcashSerialize` and `ZcashDeserialize` impls,
+//! except:
+//! - `[u8; N]` arrays, which are written as raw bytes;
+//! - fields marked `#[zcash(bytes)]`, which must be a `Vec<u8>`, and are
+//!   written as a `CompactSize` length, followed by the bytes;
+//! - fields marked `#[zcash(optional)]`, which must be an `Option<T>`, and
+//!   are written as a one-byte presence flag, followed by the value if it is
+//!   present. This is the `Optional` encoding used by `zcashd`.
+//!
+//! Length-prefixed lists of other types can use `Vec<T>`, which has its own
+//! impls.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use zebra_chain::{
+//!     block::BlockHeaderHash,
+//!     serialization::{ZcashDeserialize, ZcashSerialize},
+//! };
+//!
+//! #[derive(ZcashSerialize, ZcashDeserialize)]
+//! struct Example {
+//!     hash: [u8; 32],
+//!     index: u32,
+//!     #[zcash(bytes)]
+//!     script: Vec<u8>,
+//!     #[zcash(optional)]
+//!     block: Option<BlockHeaderHash>,
+//! }
+//! ```
+//!
+//! Structs with custom encodings, validation, or version-dependent fields
+//! still need hand-written impls.
+
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Expr, Field, Fields, GenericParam, Generics,
+    Index, Meta, NestedMeta, Type,
+};
+
+/// Derives `ZcashSerialize` for a struct.
+///
+/// See the crate documentation for the encoding of each field.
+#[proc_macro_derive(ZcashSerialize, attributes(zcash))]
+pub fn derive_zcash_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_serialize(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Derives `ZcashDeserialize` for a struct.
+///
+/// See the crate documentation for the encoding of each field.
+#[proc_macro_derive(ZcashDeserialize, attributes(zcash))]
+pub fn derive_zcash_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_deserialize(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// How a field is serialized.
+enum Encoding {
+    /// Using the field type's own impls.
+    Default,
+    /// A `[u8; N]` array with length `N`, as raw bytes.
+    ByteArray(Expr),
+    /// A `Vec<u8>`, with a `CompactSize` length prefix.
+    Bytes,
+    /// An `Option<T>`, with a one-byte presence flag.
+    Optional,
+}
+
+/// Returns the encoding of `field`, based on its type and attributes.
+fn field_encoding(field: &Field) -> syn::Result<Encoding> {
+    let mut encoding = None;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("zcash"))
+    {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected #[zcash(bytes)] or #[zcash(optional)]",
+                ))
+            }
+        };
+
+        for nested in list.nested {
+            let new_encoding = match &nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("bytes") => Encoding::Bytes,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("optional") => {
+                    Encoding::Optional
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unknown zcash attribute, expected `bytes` or `optional`",
+                    ))
+                }
+            };
+            if encoding.replace(new_encoding).is_some() {
+                return Err(syn::Error::new_spanned(
+                    nested,
+                    "fields can only have one zcash attribute",
+                ));
+            }
+        }
+    }
+
+    if let Some(encoding) = encoding {
+        return Ok(encoding);
+    }
+
+    // Const generics aren't stable, so arrays don't have trait impls
+    match &field.ty {
+        Type::Array(array) => match &*array.elem {
+            Type::Path(elem) if elem.path.is_ident("u8") => {
+                Ok(Encoding::ByteArray(array.len.clone()))
+            }
+            _ => Err(syn::Error::new_spanned(
+                &field.ty,
+                "only [u8; N] arrays can be serialized",
+            )),
+        },
+        _ => Ok(Encoding::Default),
+    }
+}
+
+/// Returns the fields of the struct in `input`, or an error if it is not a
+/// struct.
+fn struct_fields(input: &DeriveInput) -> syn::Result<&Fields> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            "Zcash serialization can only be derived for structs",
+        )),
+    }
+}
+
+/// Adds `bound` to every type parameter in `generics`.
+fn add_trait_bounds(mut generics: Generics, bound: syn::TypeParamBound) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(type_param) = param {
+            type_param.bounds.push(bound.clone());
+        }
+    }
+    generics
+}
+
+fn expand_serialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = struct_fields(&input)?;
+
+    let writes = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = match &field.ident {
+                Some(ident) => quote!(#ident),
+                None => {
+                    let index = Index::from(i);
+                    quote!(#index)
+                }
+            };
+
+            Ok(match field_encoding(field)? {
+                Encoding::Default => quote! {
+                    ::zebra_chain::serialization::ZcashSerialize::zcash_serialize(
+                        &self.#member,
+                        &mut writer,
+                    )?;
+                },
+                Encoding::ByteArray(_) => quote! {
+                    ::std::io::Write::write_all(&mut writer, &self.#member[..])?;
+                },
+                Encoding::Bytes => quote! {
+                    ::zebra_chain::serialization::WriteZcashExt::write_bytes(
+                        &mut writer,
+                        &self.#member[..],
+                    )?;
+                },
+                Encoding::Optional => quote! {
+                    match &self.#member {
+                        ::std::option::Option::None => {
+                            ::std::io::Write::write_all(&mut writer, &[0])?;
+                        }
+                        ::std::option::Option::Some(value) => {
+                            ::std::io::Write::write_all(&mut writer, &[1])?;
+                            ::zebra_chain::serialization::ZcashSerialize::zcash_serialize(
+                                value,
+                                &mut writer,
+                            )?;
+                        }
+                    }
+                },
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let generics = add_trait_bounds(
+        input.generics.clone(),
+        parse_quote!(::zebra_chain::serialization::ZcashSerialize),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::zebra_chain::serialization::ZcashSerialize
+            for #name #ty_generics #where_clause
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn zcash_serialize<W: ::std::io::Write>(
+                &self,
+                mut writer: W,
+            ) -> ::std::result::Result<(), ::std::io::Error> {
+                #(#writes)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+fn expand_deserialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = struct_fields(&input)?;
+
+    let reads = fields
+        .iter()
+        .map(|field| {
+            let read = match field_encoding(field)? {
+                Encoding::Default => quote! {
+                    ::zebra_chain::serialization::ZcashDeserialize::zcash_deserialize(
+                        &mut reader,
+                    )?
+                },
+                Encoding::ByteArray(len) => quote! {
+                    {
+                        let mut bytes = [0u8; #len];
+                        ::std::io::Read::read_exact(&mut reader, &mut bytes[..])?;
+                        bytes
+                    }
+                },
+                Encoding::Bytes => quote! {
+                    ::zebra_chain::serialization::ReadZcashExt::read_bytes(&mut reader)?
+                },
+                Encoding::Optional => quote! {
+                    {
+                        let mut flag = [0u8; 1];
+                        ::std::io::Read::read_exact(&mut reader, &mut flag)?;
+                        match flag[0] {
+                            0 => ::std::option::Option::None,
+                            1 => ::std::option::Option::Some(
+                                ::zebra_chain::serialization::ZcashDeserialize::zcash_deserialize(
+                                    &mut reader,
+                                )?,
+                            ),
+                            _ => {
+                                return ::std::result::Result::Err(
+                                    ::zebra_chain::serialization::SerializationError::Parse(
+                                        "invalid optional field flag",
+                                    ),
+                                )
+                            }
+                        }
+                    }
+                },
+            };
+
+            Ok(match &field.ident {
+                Some(ident) => quote!(#ident: #read),
+                None => read,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // Struct expression fields are evaluated in order, so the fields are
+    // read in declaration order
+    let body = match fields {
+        Fields::Named(_) => quote!(Self { #(#reads),* }),
+        Fields::Unnamed(_) => quote!(Self( #(#reads),* )),
+        Fields::Unit => quote!(Self),
+    };
+
+    let name = &input.ident;
+    let generics = add_trait_bounds(
+        input.generics.clone(),
+        parse_quote!(::zebra_chain::serialization::ZcashDeserialize),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::zebra_chain::serialization::ZcashDeserialize
+            for #name #ty_generics #where_clause
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn zcash_deserialize<R: ::std::io::Read>(
+                mut reader: R,
+            ) -> ::std::result::Result<Self, ::zebra_chain::serialization::SerializationError> {
+                ::std::result::Result::Ok(#body)
+            }
+        }
+    })
+}
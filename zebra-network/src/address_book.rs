@@ -8,19 +8,30 @@ use std::{
 };
 
 use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
 use tracing::Span;
 
+use zebra_chain::types::BlockHeight;
+
 use crate::{
     constants,
-    types::{MetaAddr, PeerServices},
+    types::{MetaAddr, PeerInfo, PeerServices},
 };
 
 /// A database of peers, their advertised services, and information on when they
 /// were last seen.
 #[derive(Debug)]
 pub struct AddressBook {
-    by_addr: HashMap<SocketAddr, (DateTime<Utc>, PeerServices)>,
+    by_addr: HashMap<SocketAddr, MetaAddr>,
     by_time: BTreeSet<MetaAddr>,
+    /// Diagnostic information about our current peer connections.
+    connections: HashMap<SocketAddr, PeerInfo>,
+    /// The time of the latest failed request to each peer, including peers we
+    /// have since disconnected from.
+    ///
+    /// Entries are removed when we open a new connection to the peer, and
+    /// expire after [`constants::FAILED_PEER_EXCLUSION_DURATION`].
+    failed: HashMap<SocketAddr, DateTime<Utc>>,
     span: Span,
 }
 
@@ -31,6 +42,8 @@ impl AddressBook {
         AddressBook {
             by_addr: HashMap::default(),
             by_time: BTreeSet::default(),
+            connections: HashMap::default(),
+            failed: HashMap::default(),
             span,
         }
     }
@@ -39,10 +52,10 @@ impl AddressBook {
     /// quadratic in the address book size.
     #[cfg(test)]
     fn assert_consistency(&self) {
-        for (a, (t, s)) in self.by_addr.iter() {
+        for (a, m) in self.by_addr.iter() {
             for meta in self.by_time.iter().filter(|meta| meta.addr == *a) {
-                if meta.last_seen != *t || meta.services != *s {
-                    panic!("meta {:?} is not {:?}, {:?}, {:?}", meta, a, t, s);
+                if meta != m {
+                    panic!("meta {:?} is not {:?}", meta, m);
                 }
             }
         }
@@ -57,17 +70,15 @@ impl AddressBook {
     /// Returns the entry corresponding to `addr`, or `None` if it does not exist.
     pub fn get_by_addr(&self, addr: SocketAddr) -> Option<MetaAddr> {
         let _guard = self.span.enter();
-        let (last_seen, services) = self.by_addr.get(&addr).cloned()?;
-        Some(MetaAddr {
-            addr,
-            last_seen,
-            services,
-        })
+        self.by_addr.get(&addr).cloned()
     }
 
     /// Add `new` to the address book, updating the previous entry if `new` is
     /// more recent or discarding `new` if it is stale.
-    pub fn update(&mut self, new: MetaAddr) {
+    ///
+    /// If `new` has no handshake latency, the previous entry's latency is
+    /// kept.
+    pub fn update(&mut self, mut new: MetaAddr) {
         let _guard = self.span.enter();
         trace!(
             ?new,
@@ -84,15 +95,78 @@ impl AddressBook {
                 self.by_time
                     .take(&prev)
                     .expect("cannot have by_addr entry without by_time entry");
+                new.handshake_latency = new.handshake_latency.or(prev.handshake_latency);
             }
         }
         self.by_time.insert(new);
-        self.by_addr.insert(new.addr, (new.last_seen, new.services));
+        self.by_addr.insert(new.addr, new);
 
         #[cfg(test)]
         self.assert_consistency();
     }
 
+    /// Return an iterator over the start heights advertised by our current
+    /// peer connections.
+    ///
+    /// Start heights are only sent during the handshake, so they can be
+    /// lower than the peer's current tip.
+    pub fn live_start_heights<'a>(&'a self) -> impl Iterator<Item = BlockHeight> + 'a {
+        self.connections.values().map(|info| info.start_height)
+    }
+
+    /// Record a new connection to the peer described by `info`.
+    ///
+    /// Failed requests on earlier connections to the peer are forgotten.
+    pub fn connection_opened(&mut self, info: PeerInfo) {
+        let _guard = self.span.enter();
+        self.failed.remove(&info.addr);
+        self.connections.insert(info.addr, info);
+    }
+
+    /// Remove the connection to the peer at `addr`.
+    pub fn connection_closed(&mut self, addr: &SocketAddr) {
+        let _guard = self.span.enter();
+        self.connections.remove(addr);
+    }
+
+    /// Update the connection information for the peer at `addr`, if we are
+    /// connected to it.
+    pub fn update_connection<F>(&mut self, addr: &SocketAddr, update: F)
+    where
+        F: FnOnce(&mut PeerInfo),
+    {
+        let _guard = self.span.enter();
+        if let Some(info) = self.connections.get_mut(addr) {
+            let failures = info.failures;
+            update(info);
+            if info.failures > failures {
+                // Drop expired failures, so the map doesn't grow without bound
+                let cutoff = AddressBook::failed_cutoff_time();
+                self.failed.retain(|_, failed_at| *failed_at > cutoff);
+                self.failed.insert(*addr, Utc::now());
+            }
+        }
+    }
+
+    /// Return an iterator over diagnostic information for our current peer
+    /// connections, ordered by address.
+    pub fn connected_peers(&self) -> impl Iterator<Item = PeerInfo> {
+        let _guard = self.span.enter();
+        let mut peers: Vec<PeerInfo> = self
+            .connections
+            .values()
+            .cloned()
+            .map(|mut info| {
+                if let Some(meta) = self.by_addr.get(&info.addr) {
+                    info.last_seen = info.last_seen.max(meta.last_seen);
+                }
+                info
+            })
+            .collect();
+        peers.sort_by_key(|info| info.addr);
+        peers.into_iter()
+    }
+
     /// Compute a cutoff time that can determine whether an entry
     /// in an address book being updated with peer message timestamps
     /// represents a known-disconnected peer or a potentially-connected peer.
@@ -108,13 +182,20 @@ impl AddressBook {
         Utc::now() - CD::from_std(constants::LIVE_PEER_DURATION).unwrap()
     }
 
+    /// Compute a cutoff time for failed requests. Failures before this time
+    /// have expired.
+    fn failed_cutoff_time() -> DateTime<Utc> {
+        use chrono::Duration as CD;
+        Utc::now() - CD::from_std(constants::FAILED_PEER_EXCLUSION_DURATION).unwrap()
+    }
+
     /// Returns true if the given [`SocketAddr`] could potentially be connected
     /// to a node feeding timestamps into this address book.
     pub fn is_potentially_connected(&self, addr: &SocketAddr) -> bool {
         let _guard = self.span.enter();
         match self.by_addr.get(addr) {
             None => false,
-            Some((ref last_seen, _)) => last_seen > &AddressBook::cutoff_time(),
+            Some(meta) => meta.last_seen > AddressBook::cutoff_time(),
         }
     }
 
@@ -125,19 +206,50 @@ impl AddressBook {
         self.by_time.iter().rev().cloned()
     }
 
+    /// Returns a sample of peers to send to a remote peer, in response to a
+    /// `getaddr` request.
+    ///
+    /// Private addresses, and peers with recent failed requests, are excluded,
+    /// even if we have since disconnected from them. The remaining peers are
+    /// sanitized, shuffled, and truncated to
+    /// [`constants::MAX_ADDRS_IN_GETADDR_RESPONSE`].
+    pub fn sanitized(&self) -> Vec<MetaAddr> {
+        let _guard = self.span.enter();
+        let mut peers = self
+            .peers()
+            .filter(|meta| !meta.is_private())
+            .filter(|meta| !self.has_failed_requests(&meta.addr))
+            .map(MetaAddr::sanitize)
+            .collect::<Vec<_>>();
+
+        // The peers are ordered by recency, which reveals when we last heard
+        // from them
+        peers.shuffle(&mut rand::thread_rng());
+        peers.truncate(constants::MAX_ADDRS_IN_GETADDR_RESPONSE);
+        peers
+    }
+
+    /// Returns true if some of our recent requests to the peer at `addr` have
+    /// failed, on its current connection or an earlier one.
+    fn has_failed_requests(&self, addr: &SocketAddr) -> bool {
+        self.failed.get(addr).map_or(false, |failed_at| {
+            *failed_at > AddressBook::failed_cutoff_time()
+        })
+    }
+
     /// Return an iterator over peers known to be disconnected, ordered from most
     /// recently seen to least recently seen.
     pub fn disconnected_peers<'a>(&'a self) -> impl Iterator<Item = MetaAddr> + 'a {
         let _guard = self.span.enter();
         use std::net::{IpAddr, Ipv4Addr};
         use std::ops::Bound::{Excluded, Unbounded};
-        let cutoff_meta = MetaAddr {
-            last_seen: AddressBook::cutoff_time(),
-            // The ordering on MetaAddrs is newest-first, then arbitrary,
-            // so any fields will do here.
-            addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
-            services: PeerServices::default(),
-        };
+        // The ordering on MetaAddrs is newest-first, then arbitrary,
+        // so any other fields will do here.
+        let cutoff_meta = MetaAddr::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
+            PeerServices::default(),
+            AddressBook::cutoff_time(),
+        );
 
         self.by_time
             .range((Excluded(cutoff_meta), Unbounded))
@@ -163,6 +275,18 @@ impl AddressBook {
         }
     }
 
+    /// Removes the entry for `addr` from the address book, returning it if it
+    /// existed.
+    pub fn remove(&mut self, addr: &SocketAddr) -> Option<MetaAddr> {
+        let _guard = self.span.enter();
+        self.failed.remove(addr);
+        let meta = self.by_addr.remove(addr)?;
+        self.by_time
+            .take(&meta)
+            .expect("cannot have by_addr entry without by_time entry");
+        Some(meta)
+    }
+
     /// Returns the number of entries in this address book.
     pub fn len(&self) -> usize {
         self.by_time.len()
@@ -202,3 +326,126 @@ impl<'a> Iterator for Drain<'a> {
         Some(next_item)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    use crate::types::{ConnectionDirection, Version};
+
+    fn peer_info(addr: SocketAddr) -> PeerInfo {
+        PeerInfo {
+            addr,
+            direction: ConnectionDirection::Outbound,
+            version: Version(170_011),
+            user_agent: "/MagicBean:3.1.0/".to_string(),
+            services: PeerServices::NODE_NETWORK,
+            start_height: BlockHeight(1_000),
+            connected_at: Utc.timestamp(1_573_680_222, 0),
+            last_seen: Utc.timestamp(1_573_680_222, 0),
+            failures: 0,
+            awaiting_response: false,
+        }
+    }
+
+    #[test]
+    fn connected_peers() {
+        zebra_test::init();
+
+        let mut book = AddressBook::new(Span::none());
+        let addr: SocketAddr = "127.0.0.1:8233".parse().unwrap();
+        let other: SocketAddr = "127.0.0.2:8233".parse().unwrap();
+
+        book.connection_opened(peer_info(addr));
+        book.connection_opened(peer_info(other));
+        book.update_connection(&addr, |info| info.failures += 1);
+
+        // Messages from the peer update its last seen time
+        let last_seen = Utc.timestamp(1_573_680_300, 0);
+        book.update(MetaAddr::new(addr, PeerServices::NODE_NETWORK, last_seen));
+
+        let peers = book.connected_peers().collect::<Vec<_>>();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].addr, addr);
+        assert_eq!(peers[0].failures, 1);
+        assert_eq!(peers[0].last_seen, last_seen);
+        assert_eq!(peers[1].addr, other);
+        assert_eq!(peers[1].failures, 0);
+
+        let mut other_info = peer_info(other);
+        other_info.start_height = BlockHeight(2_000);
+        book.connection_opened(other_info.clone());
+        let mut heights = book.live_start_heights().collect::<Vec<_>>();
+        heights.sort();
+        assert_eq!(heights, vec![BlockHeight(1_000), BlockHeight(2_000)]);
+
+        // Disconnected peers don't contribute start heights
+        book.connection_closed(&addr);
+        let peers = book.connected_peers().collect::<Vec<_>>();
+        assert_eq!(peers, vec![other_info]);
+        assert_eq!(
+            book.live_start_heights().collect::<Vec<_>>(),
+            vec![BlockHeight(2_000)]
+        );
+    }
+
+    #[test]
+    fn sanitized_peers() {
+        zebra_test::init();
+
+        let mut book = AddressBook::new(Span::none());
+        let public: SocketAddr = "1.1.1.1:8233".parse().unwrap();
+        let failed: SocketAddr = "1.1.1.2:8233".parse().unwrap();
+        let private: SocketAddr = "192.168.1.1:8233".parse().unwrap();
+
+        for addr in &[public, failed, private] {
+            book.update(MetaAddr::new(
+                *addr,
+                PeerServices::NODE_NETWORK,
+                Utc.timestamp(1_573_680_222, 0),
+            ));
+        }
+        book.connection_opened(peer_info(failed));
+        book.update_connection(&failed, |info| info.failures += 1);
+
+        let peers = book.sanitized();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].addr, public);
+
+        // Failed peers stay excluded after we disconnect from them
+        book.connection_closed(&failed);
+        let peers = book.sanitized();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].addr, public);
+        assert_eq!(
+            peers[0].last_seen.timestamp() % constants::TIMESTAMP_TRUNCATION_SECONDS,
+            0
+        );
+
+        // Failed peers are included again after a new connection
+        book.connection_opened(peer_info(failed));
+        assert_eq!(book.sanitized().len(), 2);
+
+        // Or after their failures expire
+        book.update_connection(&failed, |info| info.failures += 1);
+        assert_eq!(book.sanitized().len(), 1);
+        let expired = AddressBook::failed_cutoff_time() - chrono::Duration::seconds(1);
+        book.failed.insert(failed, expired);
+        assert_eq!(book.sanitized().len(), 2);
+
+        // Responses are truncated
+        for i in 0..(2 * constants::MAX_ADDRS_IN_GETADDR_RESPONSE) {
+            book.update(MetaAddr::new(
+                SocketAddr::new([1, 1, 2, i as u8].into(), 8233),
+                PeerServices::NODE_NETWORK,
+                Utc.timestamp(1_573_680_222, 0),
+            ));
+        }
+        assert_eq!(
+            book.sanitized().len(),
+            constants::MAX_ADDRS_IN_GETADDR_RESPONSE
+        );
+    }
+}
@@ -1,11 +1,8 @@
-use std::{
-    collections::HashSet,
-    net::{SocketAddr, ToSocketAddrs},
-    string::String,
-    time::Duration,
-};
+use std::{collections::HashSet, net::SocketAddr, path::PathBuf, string::String, time::Duration};
 
-use zebra_chain::Network;
+use zebra_chain::{parameters::TestnetParameters, Network};
+
+use crate::{resolver, PeerTrust};
 
 /// Configuration for networking code.
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -28,12 +25,68 @@ pub struct Config {
     /// testnet.
     pub initial_testnet_peers: HashSet<String>,
 
+    /// Additional DNS seeders or initial peers, which are used on the
+    /// configured network, along with the default initial peers.
+    ///
+    /// Entries without a port use the default port for the network.
+    pub additional_seeders: HashSet<String>,
+
+    /// Peers that this node always stays connected to, on the configured
+    /// network.
+    ///
+    /// Manual peers are reconnected if their connections close, and they are
+    /// never disconnected by the peer set. Use them for your own
+    /// infrastructure nodes. Entries without a port use the default port for
+    /// the network.
+    pub manual_peers: HashSet<String>,
+
     /// The outgoing request buffer size for the peer set.
     pub peerset_request_buffer_size: usize,
 
     /// The initial target size for the peer set.
     pub peerset_initial_target_size: usize,
 
+    /// The maximum number of new connections in each
+    /// `connection_rate_window`.
+    ///
+    /// Inbound and outbound connections are limited separately.
+    pub connection_rate_limit: usize,
+
+    /// The maximum number of new connections to addresses with the same IP
+    /// prefix in each `connection_rate_window`.
+    ///
+    /// IPv4 prefixes are 16 bits, and IPv6 prefixes are 32 bits.
+    pub connection_rate_limit_per_prefix: usize,
+
+    /// The maximum number of outbound connections to peers with the same IP
+    /// prefix.
+    ///
+    /// This stops an attacker that controls a few IP prefixes from filling
+    /// all our outbound connections. IPv4 prefixes are 16 bits, and IPv6
+    /// prefixes are 32 bits.
+    pub outbound_peers_per_prefix: usize,
+
+    /// A file for caching peer addresses between runs.
+    ///
+    /// Cached peers are used as connection candidates on startup, and the
+    /// cache is updated each time the crawler runs. The file can also be a
+    /// `zcashd` `peers.dat` file, which is replaced by a Zebra cache on the
+    /// first update.
+    ///
+    /// If unset, peer addresses are not cached. But `zebrad` caches peers in
+    /// a network-specific subdirectory of the state cache directory, unless
+    /// this file is set.
+    pub peer_cache_file: Option<PathBuf>,
+
+    /// The trust levels of peer IP addresses, like
+    /// `"203.0.113.7" = "trusted"`.
+    ///
+    /// Trusted peers skip connection rate limits and the outbound prefix
+    /// limit, and they are preferred for block downloads. Use this for your own nodes, like the `zcashd` nodes
+    /// next to a mining pool's Zebra node. Unlisted peers have the `default`
+    /// trust level.
+    pub peer_trust: PeerTrust,
+
     // Note: due to the way this is rendered by the toml
     // serializer, the Duration fields should come last.
     /// The default RTT estimate for peer responses, used in load-balancing.
@@ -47,22 +100,56 @@ pub struct Config {
 
     /// How frequently we attempt to connect to a new peer.
     pub new_peer_interval: Duration,
+
+    /// The time window for connection rate limits.
+    pub connection_rate_window: Duration,
+
+    /// The timeout for each DNS seeder lookup.
+    ///
+    /// Seeders are resolved in parallel, so this is also the maximum time
+    /// spent resolving initial peers.
+    pub dns_timeout: Duration,
+
+    /// Parameters for a configured test network, which override the default
+    /// Testnet magic, genesis hash, and network upgrade activation heights.
+    ///
+    /// Can only be set when `network` is `Testnet`.
+    pub testnet_parameters: Option<TestnetParameters>,
 }
 
 impl Config {
-    fn parse_peers<S: ToSocketAddrs>(peers: HashSet<S>) -> HashSet<SocketAddr> {
-        peers
-            .iter()
-            .flat_map(|s| s.to_socket_addrs())
-            .flatten()
-            .collect()
+    /// Get the initial seed peers based on the configured network.
+    ///
+    /// Seeder hostnames are resolved asynchronously and in parallel. Seeders
+    /// that fail to resolve within `dns_timeout` are skipped.
+    pub async fn initial_peers(&self) -> HashSet<SocketAddr> {
+        let mut peers = match self.network {
+            Network::Mainnet => self.initial_mainnet_peers.clone(),
+            Network::Testnet => self.initial_testnet_peers.clone(),
+        };
+        peers.extend(self.additional_seeders.iter().cloned());
+
+        resolver::resolve_peers(peers, self.default_port(), self.dns_timeout).await
     }
 
-    /// Get the initial seed peers based on the configured network.
-    pub fn initial_peers(&self) -> HashSet<SocketAddr> {
+    /// Get the manual peers for the configured network.
+    ///
+    /// Hostnames are resolved asynchronously and in parallel. Peers that fail
+    /// to resolve within `dns_timeout` are skipped.
+    pub async fn manual_peers(&self) -> HashSet<SocketAddr> {
+        resolver::resolve_peers(
+            self.manual_peers.clone(),
+            self.default_port(),
+            self.dns_timeout,
+        )
+        .await
+    }
+
+    /// Returns the default peer port for the configured network.
+    pub fn default_port(&self) -> u16 {
         match self.network {
-            Network::Mainnet => Config::parse_peers(self.initial_mainnet_peers.clone()),
-            Network::Testnet => Config::parse_peers(self.initial_testnet_peers.clone()),
+            Network::Mainnet => 8233,
+            Network::Testnet => 18233,
         }
     }
 }
@@ -96,12 +183,22 @@ impl Default for Config {
             network: Network::Mainnet,
             initial_mainnet_peers: mainnet_peers,
             initial_testnet_peers: testnet_peers,
+            additional_seeders: HashSet::new(),
+            manual_peers: HashSet::new(),
             ewma_default_rtt: Duration::from_secs(1),
             ewma_decay_time: Duration::from_secs(60),
             peerset_request_buffer_size: 10,
             handshake_timeout: Duration::from_secs(4),
             new_peer_interval: Duration::from_secs(60),
             peerset_initial_target_size: 50,
+            connection_rate_limit: 100,
+            connection_rate_limit_per_prefix: 4,
+            outbound_peers_per_prefix: 2,
+            peer_cache_file: None,
+            peer_trust: PeerTrust::default(),
+            connection_rate_window: Duration::from_secs(60),
+            dns_timeout: Duration::from_secs(5),
+            testnet_parameters: None,
         }
     }
 }
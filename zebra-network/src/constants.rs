@@ -27,12 +27,38 @@ pub const LIVE_PEER_DURATION: Duration = Duration::from_secs(60 + 10 + 10 + 10);
 /// connected peer.
 pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
 
+/// How long peers with failed requests are excluded from our `getaddr`
+/// responses, unless we connect to them again.
+pub const FAILED_PEER_EXCLUSION_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How often we try to reconnect to manual peers that aren't connected.
+///
+/// Matches the added node retry interval in `zcashd`.
+pub const MANUAL_PEER_RECONNECT_INTERVAL: Duration = Duration::from_secs(120);
+
 /// Truncate timestamps in outbound address messages to this time interval.
 ///
 /// This is intended to prevent a peer from learning exactly when we received
 /// messages from each of our peers.
 pub const TIMESTAMP_TRUNCATION_SECONDS: i64 = 30 * 60;
 
+/// The maximum number of addresses in a response to a `getaddr` request.
+///
+/// Sending a small sample of our address book means that remote peers can't
+/// trivially learn our entire peer set.
+pub const MAX_ADDRS_IN_GETADDR_RESPONSE: usize = 50;
+
+/// The maximum number of addresses in an `addr` message.
+///
+/// Matches `MAX_ADDR_TO_SEND` in `zcashd`.
+pub const MAX_ADDRS_IN_MESSAGE: u64 = 1000;
+
+/// The maximum number of inventory items in an `inv`, `getdata`, or
+/// `notfound` message.
+///
+/// Matches `MAX_INV_SZ` in `zcashd`.
+pub const MAX_INV_IN_MESSAGE: u64 = 50_000;
+
 /// The User-Agent string provided by the node.
 pub const USER_AGENT: &str = "🦓 Zebra 3.0.0-alpha.0 🦓";
 
@@ -42,6 +68,12 @@ pub const USER_AGENT: &str = "🦓 Zebra 3.0.0-alpha.0 🦓";
 /// based on where we are in the network upgrade cycle.
 pub const CURRENT_VERSION: Version = Version(170_011);
 
+/// The first protocol version which supports [ZIP-239] `MSG_WTX` inventory
+/// items.
+///
+/// [ZIP-239]: https://zips.z.cash/zip-0239
+pub const WTX_RELAY_VERSION: Version = Version(170_014);
+
 /// The most recent bilateral consensus upgrade implemented by this crate.
 ///
 /// Used to select the minimum supported version for peer connections.
@@ -50,6 +82,10 @@ pub const CURRENT_VERSION: Version = Version(170_011);
 //       See the detailed comment in handshake.rs, where this constant is used.
 pub const MIN_NETWORK_UPGRADE: NetworkUpgrade = Heartwood;
 
+/// The BIP-158 basic filter type, which is the only compact filter type
+/// Zebra serves.
+pub const BASIC_FILTER_TYPE: u8 = 0;
+
 /// Magic numbers used to identify different Zcash networks.
 pub mod magics {
     use super::*;
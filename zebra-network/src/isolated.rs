@@ -0,0 +1,135 @@
+//! Code for creating isolated connections to specific peers.
+
+use std::{
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::{channel::mpsc, prelude::*};
+use tokio::net::TcpStream;
+use tower::{
+    util::{BoxService, Oneshot},
+    Service,
+};
+use tracing::Level;
+
+use crate::{
+    peer, types::ConnectionDirection, AddressBook, BoxedStdError, Config, Request, Response,
+};
+
+/// Use the provided TCP connection to create a Zcash connection completely
+/// isolated from all other node state.
+///
+/// The connection pool returned by `init` should be used for all requests that
+/// don't require isolated state or use of an existing TCP connection. However,
+/// this low-level API is useful for custom network crawlers or Tor connections.
+///
+/// In addition to being completely isolated from all other node state, this
+/// method also aims to be minimally distinguishable from other clients:
+///
+/// - it does not listen for inbound connections, or advertise a listener address;
+/// - it advertises a block height of zero, and asks the peer not to relay
+///   transactions;
+/// - it ignores all inbound requests from the peer.
+///
+/// Note that this method does not implement any timeout behavior, so callers may
+/// want to layer it with a timeout as appropriate for their application.
+///
+/// # Inputs
+///
+/// - `conn`: an existing TCP connection to use. Passing an existing TCP
+///   connection allows this method to be used with clearnet or Tor transports.
+///
+/// - `user_agent`: a valid BIP14 user-agent, e.g., the empty string.
+pub fn connect_isolated(
+    conn: TcpStream,
+    user_agent: String,
+) -> impl Future<Output = Result<BoxService<Request, Response, BoxedStdError>, BoxedStdError>> {
+    let config = Config {
+        user_agent,
+        ..Config::default()
+    };
+    let inbound_service =
+        tower::service_fn(|_req| async move { Ok::<Response, BoxedStdError>(Response::Nil) });
+    // Nothing reads the timestamps or the address book, so they can't leak
+    // into the rest of the node.
+    let (timestamp_collector, _) = mpsc::channel(1);
+    let address_book = Arc::new(Mutex::new(AddressBook::new(span!(
+        Level::TRACE,
+        "isolated peer"
+    ))));
+    let handshake =
+        peer::Handshake::new(config, inbound_service, timestamp_collector, address_book);
+
+    // We can't get the remote addr from conn, because it might be a TCP
+    // connection through a SOCKS proxy, not directly to the remote. zcashd
+    // doesn't check the address we send, and Zebra only uses it for logging.
+    let remote_addr: SocketAddr = "0.0.0.0:8233".parse().unwrap();
+
+    Oneshot::new(
+        handshake,
+        (conn, remote_addr, ConnectionDirection::Outbound),
+    )
+    .map_ok(|client| BoxService::new(Wrapper(client)))
+}
+
+/// Converts the errors from a `peer::Client` into boxed errors.
+struct Wrapper(peer::Client);
+
+impl Service<Request> for Wrapper {
+    type Response = Response;
+    type Error = BoxedStdError;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        self.0.call(req).map_err(Into::into).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn connect_isolated_sends_minimal_version() {
+        zebra_test::init();
+
+        use crate::protocol::external::{Codec, Message};
+        use tokio_util::codec::Framed;
+
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listen_addr = listener.local_addr().unwrap();
+
+        let conn = TcpStream::connect(listen_addr).await.unwrap();
+        tokio::spawn(connect_isolated(conn, "".to_string()));
+
+        let (inbound_conn, _) = listener.accept().await.unwrap();
+        let mut inbound_stream = Framed::new(inbound_conn, Codec::builder().finish());
+
+        match inbound_stream.next().await {
+            Some(Ok(Message::Version {
+                user_agent,
+                start_height,
+                relay,
+                address_from,
+                ..
+            })) => {
+                assert_eq!(user_agent, "");
+                assert_eq!(start_height.0, 0);
+                assert!(!relay);
+                assert!(address_from.1.ip().is_unspecified());
+            }
+            msg => panic!("expected a version message, got {:?}", msg),
+        }
+    }
+}
@@ -57,23 +57,37 @@ pub type BoxedStdError = Box<dyn std::error::Error + Send + Sync + 'static>;
 mod address_book;
 mod config;
 mod constants;
+mod isolated;
 mod meta_addr;
 mod peer;
+mod peer_info;
 mod peer_set;
 mod policies;
 mod protocol;
+mod resolver;
 mod timestamp_collector;
+mod trust;
+
+pub mod peer_cache;
 
 pub use crate::{
     address_book::AddressBook,
     config::Config,
-    peer_set::init,
+    constants::CURRENT_VERSION,
+    isolated::connect_isolated,
+    peer::{Fault, HandshakeError, PeerError, SharedPeerError},
+    peer_set::{init, PeerControl},
     policies::{RetryErrors, RetryLimit},
     protocol::external::codec::Builder,
     protocol::internal::{Request, Response},
+    trust::{PeerTrust, TrustLevel},
 };
 
 /// Types used in the definition of [`Request`] and [`Response`] messages.
 pub mod types {
-    pub use crate::{meta_addr::MetaAddr, protocol::types::PeerServices};
+    pub use crate::{
+        meta_addr::MetaAddr,
+        peer_info::{ConnectionDirection, PeerInfo},
+        protocol::types::{PeerServices, Version},
+    };
 }
@@ -3,20 +3,25 @@
 use std::{
     cmp::{Ord, Ordering},
     io::{Read, Write},
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
 };
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use chrono::{DateTime, TimeZone, Utc};
 
 use zebra_chain::serialization::{
-    ReadZcashExt, SerializationError, WriteZcashExt, ZcashDeserialize, ZcashSerialize,
+    ReadZcashExt, SerializationError, TrustedPreallocate, WriteZcashExt, ZcashDeserialize,
+    ZcashSerialize,
 };
 
 use crate::protocol::types::PeerServices;
 
 /// An address with metadata on its advertised services and last-seen time.
 ///
+/// Zebra also tracks some local metadata about each peer, which is used to
+/// rank connection candidates. Local metadata is never sent to remote peers.
+///
 /// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#Network_address)
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct MetaAddr {
@@ -26,16 +31,62 @@ pub struct MetaAddr {
     pub services: PeerServices,
     /// When the peer was last seen.
     pub last_seen: DateTime<Utc>,
+    /// How long our last successful handshake with the peer took, if we have
+    /// connected to it.
+    pub handshake_latency: Option<Duration>,
+    /// The number of consecutive failed connection attempts to the peer.
+    pub failure_streak: u32,
 }
 
 impl MetaAddr {
+    /// Returns a new `MetaAddr` for `addr`, with no local metadata.
+    pub fn new(addr: SocketAddr, services: PeerServices, last_seen: DateTime<Utc>) -> MetaAddr {
+        MetaAddr {
+            addr,
+            services,
+            last_seen,
+            handshake_latency: None,
+            failure_streak: 0,
+        }
+    }
+
     /// Sanitize this `MetaAddr` before sending it to a remote peer.
+    ///
+    /// Last seen times in the future are replaced with the current time, then
+    /// all times are truncated, so remote peers can't learn exactly when we
+    /// received messages from each of our peers.
     pub fn sanitize(mut self) -> MetaAddr {
         let interval = crate::constants::TIMESTAMP_TRUNCATION_SECONDS;
-        let ts = self.last_seen.timestamp();
+        let ts = self.last_seen.min(Utc::now()).timestamp();
         self.last_seen = Utc.timestamp(ts - ts.rem_euclid(interval), 0);
         self
     }
+
+    /// Returns true if this address is private, local, or otherwise not
+    /// reachable from the public internet.
+    ///
+    /// Private addresses should not be sent to remote peers.
+    pub fn is_private(&self) -> bool {
+        match self.addr.ip() {
+            IpAddr::V4(ip) => {
+                ip.is_private()
+                    || ip.is_loopback()
+                    || ip.is_link_local()
+                    || ip.is_unspecified()
+                    || ip.is_broadcast()
+                    || ip.is_documentation()
+            }
+            IpAddr::V6(ip) => {
+                let first_segment = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local addresses, fc00::/7
+                    || first_segment & 0xfe00 == 0xfc00
+                    // Link-local addresses, fe80::/10
+                    || first_segment & 0xffc0 == 0xfe80
+            }
+        }
+    }
 }
 
 impl Ord for MetaAddr {
@@ -56,6 +107,8 @@ impl Ord for MetaAddr {
             }
             .then(self.addr.port().cmp(&other.addr.port()))
             .then(self.services.bits().cmp(&other.services.bits()))
+            .then(self.handshake_latency.cmp(&other.handshake_latency))
+            .then(self.failure_streak.cmp(&other.failure_streak))
         })
     }
 }
@@ -77,12 +130,19 @@ impl ZcashSerialize for MetaAddr {
 
 impl ZcashDeserialize for MetaAddr {
     fn zcash_deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
-        Ok(MetaAddr {
-            last_seen: Utc.timestamp(reader.read_u32::<LittleEndian>()? as i64, 0),
-            // Discard unknown service bits.
-            services: PeerServices::from_bits_truncate(reader.read_u64::<LittleEndian>()?),
-            addr: reader.read_socket_addr()?,
-        })
+        let last_seen = Utc.timestamp(reader.read_u32::<LittleEndian>()? as i64, 0);
+        // Discard unknown service bits.
+        let services = PeerServices::from_bits_truncate(reader.read_u64::<LittleEndian>()?);
+        let addr = reader.read_socket_addr()?;
+        Ok(MetaAddr::new(addr, services, last_seen))
+    }
+}
+
+impl TrustedPreallocate for MetaAddr {
+    fn max_allocation() -> u64 {
+        // This limit is lower than the number of 30-byte addresses that fit
+        // in a message.
+        crate::constants::MAX_ADDRS_IN_MESSAGE
     }
 }
 
@@ -92,11 +152,11 @@ mod tests {
     // XXX remove this test and replace it with a proptest instance.
     #[test]
     fn sanitize_truncates_timestamps() {
-        let entry = MetaAddr {
-            services: PeerServices::default(),
-            addr: "127.0.0.1:8233".parse().unwrap(),
-            last_seen: Utc.timestamp(1_573_680_222, 0),
-        }
+        let entry = MetaAddr::new(
+            "127.0.0.1:8233".parse().unwrap(),
+            PeerServices::default(),
+            Utc.timestamp(1_573_680_222, 0),
+        )
         .sanitize();
         // We want the sanitized timestamp to be a multiple of the truncation interval.
         assert_eq!(
@@ -104,4 +164,39 @@ mod tests {
             0
         );
     }
+
+    #[test]
+    fn sanitize_clamps_future_timestamps() {
+        let entry = MetaAddr::new(
+            "1.1.1.1:8233".parse().unwrap(),
+            PeerServices::default(),
+            Utc::now() + chrono::Duration::days(1),
+        )
+        .sanitize();
+
+        assert!(entry.last_seen <= Utc::now());
+    }
+
+    #[test]
+    fn private_addresses() {
+        for (addr, is_private) in &[
+            ("1.1.1.1:8233", false),
+            ("127.0.0.1:8233", true),
+            ("10.0.0.1:8233", true),
+            ("192.168.1.1:8233", true),
+            ("169.254.0.1:8233", true),
+            ("0.0.0.0:8233", true),
+            ("[2001:4860::8888]:8233", false),
+            ("[::1]:8233", true),
+            ("[fd00::1]:8233", true),
+            ("[fe80::1]:8233", true),
+        ] {
+            let entry = MetaAddr::new(
+                addr.parse().unwrap(),
+                PeerServices::default(),
+                Utc.timestamp(1_573_680_222, 0),
+            );
+            assert_eq!(entry.is_private(), *is_private, "{}", addr);
+        }
+    }
 }
@@ -17,5 +17,5 @@ use error::ErrorSlot;
 pub use client::Client;
 pub use connection::Connection;
 pub use connector::Connector;
-pub use error::{HandshakeError, PeerError, SharedPeerError};
+pub use error::{Fault, HandshakeError, PeerError, SharedPeerError};
 pub use handshake::Handshake;
@@ -15,7 +15,8 @@
 // disturb this code. This code is best shunned and left encapsulated.
 
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 
 use futures::{
     channel::{mpsc, oneshot},
@@ -38,7 +39,8 @@ use crate::{
         external::{types::Nonce, InventoryHash, Message},
         internal::{Request, Response},
     },
-    BoxedStdError,
+    types::PeerInfo,
+    AddressBook, BoxedStdError,
 };
 
 use super::{ClientRequest, ErrorSlot, PeerError, SharedPeerError};
@@ -53,6 +55,11 @@ pub(super) enum Handler {
         blocks: Vec<Arc<Block>>,
     },
     FindBlocks,
+    CompactFilters {
+        stop_hash: BlockHeaderHash,
+        filters: Vec<(BlockHeaderHash, Vec<u8>)>,
+    },
+    CompactFilterHeaders,
 }
 
 impl Handler {
@@ -107,6 +114,38 @@ impl Handler {
                     })
                     .collect(),
             ))),
+            (
+                CompactFilters {
+                    stop_hash,
+                    mut filters,
+                },
+                Message::CFilter {
+                    filter_type: constants::BASIC_FILTER_TYPE,
+                    block_hash,
+                    filter,
+                },
+            ) => {
+                let finished = block_hash == stop_hash;
+                filters.push((block_hash, filter));
+                if finished {
+                    Finished(Ok(Response::CompactFilters(filters)))
+                } else {
+                    CompactFilters { stop_hash, filters }
+                }
+            }
+            (
+                CompactFilterHeaders,
+                Message::CFHeaders {
+                    filter_type: constants::BASIC_FILTER_TYPE,
+                    stop_hash,
+                    previous_filter_header,
+                    filter_hashes,
+                },
+            ) => Finished(Ok(Response::CompactFilterHeaders {
+                stop_hash,
+                previous_header: previous_filter_header,
+                filter_hashes,
+            })),
             // By default, messages are not responses.
             (state, msg) => {
                 trace!(?msg, "did not interpret message as response");
@@ -145,6 +184,11 @@ pub struct Connection<S, Tx> {
     pub(super) error_slot: ErrorSlot,
     //pub(super) peer_rx: Rx,
     pub(super) peer_tx: Tx,
+    /// The address of the remote peer.
+    pub(super) addr: SocketAddr,
+    /// The address book, which holds diagnostic information about this
+    /// connection.
+    pub(super) address_book: Arc<Mutex<AddressBook>>,
 }
 
 impl<S, Tx> Connection<S, Tx>
@@ -191,6 +235,7 @@ where
                         }
                         Either::Right((None, _)) => {
                             trace!("client_rx closed, ending connection");
+                            self.connection_closed();
                             return;
                         }
                         Either::Right((Some(req), _)) => {
@@ -245,6 +290,14 @@ where
                                         ..
                                     } => {
                                         let _ = tx.send(response);
+                                        // self.state is moved out, so we
+                                        // can't call methods on self here.
+                                        self.address_book
+                                            .lock()
+                                            .expect("mutex should be unpoisoned")
+                                            .update_connection(&self.addr, |info| {
+                                                info.awaiting_response = false
+                                            });
                                         State::AwaitingRequest
                                     }
                                     pending @ State::AwaitingResponse { .. } => pending,
@@ -267,6 +320,13 @@ where
                                 // Other request timeouts fail the request.
                                 State::AwaitingResponse { tx, .. } => {
                                     let _ = tx.send(Err(e.into()));
+                                    self.address_book
+                                        .lock()
+                                        .expect("mutex should be unpoisoned")
+                                        .update_connection(&self.addr, |info| {
+                                            info.failures += 1;
+                                            info.awaiting_response = false;
+                                        });
                                     State::AwaitingRequest
                                 }
                                 _ => unreachable!(),
@@ -291,13 +351,35 @@ where
                             // Continue until we've errored all queued reqs
                             continue;
                         }
-                        None => return,
+                        None => {
+                            self.connection_closed();
+                            return;
+                        }
                     }
                 }
             }
         }
     }
 
+    /// Update the diagnostic information for this connection.
+    fn update_peer_info<F>(&self, update: F)
+    where
+        F: FnOnce(&mut PeerInfo),
+    {
+        self.address_book
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .update_connection(&self.addr, update);
+    }
+
+    /// Remove the diagnostic information for this connection.
+    fn connection_closed(&self) {
+        self.address_book
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .connection_closed(&self.addr);
+    }
+
     /// Marks the peer as having failed with error `e`.
     fn fail_with(&mut self, e: PeerError) {
         debug!(%e, "failing peer service with error");
@@ -339,6 +421,29 @@ where
         use State::*;
         let ClientRequest { request, tx, span } = req;
 
+        // Peer info requests are answered by the peer set, so an individual
+        // connection has no peers to report.
+        if let Request::PeerInfo = request {
+            let _ = tx.send(Ok(Response::PeerInfo(Vec::new())));
+            return;
+        }
+
+        // Peers don't respond to transactions, so there is nothing to wait
+        // for after sending them.
+        if let Request::PushTransaction(transaction) = request {
+            match self.peer_tx.send(Message::Tx(transaction)).await {
+                Ok(()) => {
+                    let _ = tx.send(Ok(Response::Nil));
+                }
+                Err(e) => {
+                    self.fail_with(e.into());
+                    let e = self.error_slot.try_get_error().unwrap();
+                    let _ = tx.send(Err(e));
+                }
+            }
+            return;
+        }
+
         // XXX(hdevalence) this is truly horrible, but let's fix it later
 
         // Inner match returns Result with the new state or an error.
@@ -394,10 +499,60 @@ where
                     tx,
                     span,
                 }),
+            (
+                AwaitingRequest,
+                CompactFilters {
+                    start_height,
+                    stop_hash,
+                },
+            ) => self
+                .peer_tx
+                .send(Message::GetCFilters {
+                    filter_type: constants::BASIC_FILTER_TYPE,
+                    start_height,
+                    stop_hash,
+                })
+                .await
+                .map_err(|e| e.into())
+                .map(|()| AwaitingResponse {
+                    handler: Handler::CompactFilters {
+                        stop_hash,
+                        filters: Vec::new(),
+                    },
+                    tx,
+                    span,
+                }),
+            (
+                AwaitingRequest,
+                CompactFilterHeaders {
+                    start_height,
+                    stop_hash,
+                },
+            ) => self
+                .peer_tx
+                .send(Message::GetCFHeaders {
+                    filter_type: constants::BASIC_FILTER_TYPE,
+                    start_height,
+                    stop_hash,
+                })
+                .await
+                .map_err(|e| e.into())
+                .map(|()| AwaitingResponse {
+                    handler: Handler::CompactFilterHeaders,
+                    tx,
+                    span,
+                }),
+            (AwaitingRequest, Request::PeerInfo) => {
+                unreachable!("peer info requests are answered above")
+            }
+            (AwaitingRequest, PushTransaction(_)) => {
+                unreachable!("pushed transactions are sent above")
+            }
         } {
             Ok(new_state) => {
                 self.state = new_state;
                 self.request_timer = Some(delay_for(constants::REQUEST_TIMEOUT));
+                self.update_peer_info(|info| info.awaiting_response = true);
             }
             Err(e) => self.fail_with(e),
         }
@@ -429,7 +584,7 @@ where
             _ => {}
         }
 
-        // Per BIP-011, since we don't advertise NODE_BLOOM, we MUST
+        // Per BIP-111, since we don't advertise NODE_BLOOM, we MUST
         // disconnect from this peer immediately.
         match msg {
             Message::FilterLoad { .. }
@@ -441,6 +596,25 @@ where
             _ => {}
         }
 
+        // These messages are informational, or replies to requests we never
+        // make, so we log and ignore them, rather than closing the connection.
+        match msg {
+            Message::Reject {
+                message,
+                ccode,
+                reason,
+                data,
+            } => {
+                debug!(%message, ?ccode, %reason, ?data, "peer rejected a message");
+                return;
+            }
+            Message::MerkleBlock { .. } => {
+                debug!("ignoring unsolicited merkleblock message");
+                return;
+            }
+            _ => {}
+        }
+
         // Interpret `msg` as a request from the remote peer to our node,
         // and try to construct an appropriate request object.
         let req = match msg {
@@ -449,6 +623,27 @@ where
                 None
             }
             Message::GetAddr => Some(Request::Peers),
+            Message::GetCFilters {
+                filter_type: constants::BASIC_FILTER_TYPE,
+                start_height,
+                stop_hash,
+            } => Some(Request::CompactFilters {
+                start_height,
+                stop_hash,
+            }),
+            Message::GetCFHeaders {
+                filter_type: constants::BASIC_FILTER_TYPE,
+                start_height,
+                stop_hash,
+            } => Some(Request::CompactFilterHeaders {
+                start_height,
+                stop_hash,
+            }),
+            Message::GetCFilters { filter_type, .. }
+            | Message::GetCFHeaders { filter_type, .. } => {
+                debug!(filter_type, "ignoring request for unsupported filter type");
+                None
+            }
             _ => {
                 debug!("unhandled message type");
                 None
@@ -511,6 +706,39 @@ where
                     self.fail_with(e.into())
                 }
             }
+            Response::CompactFilters(filters) => {
+                // Generate one cfilter message per block.
+                for (block_hash, filter) in filters.into_iter() {
+                    let msg = Message::CFilter {
+                        filter_type: constants::BASIC_FILTER_TYPE,
+                        block_hash,
+                        filter,
+                    };
+                    if let Err(e) = self.peer_tx.send(msg).await {
+                        self.fail_with(e.into());
+                        return;
+                    }
+                }
+            }
+            Response::CompactFilterHeaders {
+                stop_hash,
+                previous_header,
+                filter_hashes,
+            } => {
+                let msg = Message::CFHeaders {
+                    filter_type: constants::BASIC_FILTER_TYPE,
+                    stop_hash,
+                    previous_filter_header: previous_header,
+                    filter_hashes,
+                };
+                if let Err(e) = self.peer_tx.send(msg).await {
+                    self.fail_with(e.into())
+                }
+            }
+            Response::PeerInfo(_) => {
+                // Our peer information is only for local diagnostics.
+                debug!("ignoring peer info response to inbound request");
+            }
         }
     }
 }
@@ -9,7 +9,7 @@ use futures::prelude::*;
 use tokio::net::TcpStream;
 use tower::{discover::Change, Service, ServiceExt};
 
-use crate::{BoxedStdError, Request, Response};
+use crate::{types::ConnectionDirection, BoxedStdError, Request, Response};
 
 use super::{Client, Handshake};
 
@@ -18,19 +18,36 @@ use super::{Client, Handshake};
 /// [`tower::Service`] lets us apply unified timeout policies, etc.
 pub struct Connector<S> {
     handshaker: Handshake<S>,
+    direction: ConnectionDirection,
 }
 
 impl<S: Clone> Clone for Connector<S> {
     fn clone(&self) -> Self {
         Connector {
             handshaker: self.handshaker.clone(),
+            direction: self.direction,
         }
     }
 }
 
 impl<S> Connector<S> {
+    /// Returns a connector for outbound peer connections.
     pub fn new(handshaker: Handshake<S>) -> Self {
-        Connector { handshaker }
+        Connector {
+            handshaker,
+            direction: ConnectionDirection::Outbound,
+        }
+    }
+
+    /// Returns a connector for feeler connections, which check that a peer
+    /// is reachable.
+    ///
+    /// Callers should drop the returned client, which closes the connection.
+    pub fn feeler(handshaker: Handshake<S>) -> Self {
+        Connector {
+            handshaker,
+            direction: ConnectionDirection::Feeler,
+        }
     }
 }
 
@@ -50,10 +67,11 @@ where
 
     fn call(&mut self, addr: SocketAddr) -> Self::Future {
         let mut hs = self.handshaker.clone();
+        let direction = self.direction;
         async move {
             let stream = TcpStream::connect(addr).await?;
             hs.ready_and().await?;
-            let client = hs.call((stream, addr)).await?;
+            let client = hs.call((stream, addr, direction)).await?;
             Ok(Change::Insert(addr, client))
         }
         .boxed()
@@ -1,21 +1,94 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    error::Error as StdError,
+    fmt,
+    sync::{Arc, Mutex},
+};
 
 use thiserror::Error;
 
 use tracing_error::TracedError;
 use zebra_chain::serialization::SerializationError;
 
+/// Who caused a network error.
+///
+/// The peer set and crawler use faults to decide how to react to errors:
+/// peers that misbehave can be avoided, but transient errors and our own
+/// errors shouldn't count against a peer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// The error was caused by this node, for example, by overloaded internal
+    /// services, or a connection to ourselves.
+    Ours,
+    /// The error was caused by the remote peer violating the protocol.
+    Peer,
+    /// The error was caused by network conditions, and might not happen if
+    /// we try again.
+    Transient,
+}
+
+impl Fault {
+    /// Returns the fault of the first network error in `error`'s chain of
+    /// sources, or `None` if the chain doesn't contain any network errors.
+    ///
+    /// IO errors and timeouts are transient.
+    pub fn of(error: &(dyn StdError + 'static)) -> Option<Fault> {
+        let mut next = Some(error);
+        while let Some(error) = next {
+            if let Some(e) = error.downcast_ref::<SharedPeerError>() {
+                return Some(e.fault());
+            } else if let Some(e) = error.downcast_ref::<PeerError>() {
+                return Some(e.fault());
+            } else if let Some(e) = error.downcast_ref::<HandshakeError>() {
+                return Some(e.fault());
+            } else if error.is::<std::io::Error>() || error.is::<tower::timeout::error::Elapsed>() {
+                return Some(Fault::Transient);
+            }
+            next = error.source();
+        }
+        None
+    }
+}
+
 /// A wrapper around `Arc<PeerError>` that implements `Error`.
-#[derive(Error, Debug, Clone)]
-#[error(transparent)]
-pub struct SharedPeerError(Arc<TracedError<PeerError>>);
+///
+/// The [`Fault`] of the error is kept outside the `Arc`, so it can be checked
+/// without unwrapping the traced error.
+#[derive(Debug, Clone)]
+pub struct SharedPeerError {
+    error: Arc<TracedError<PeerError>>,
+    fault: Fault,
+}
+
+impl SharedPeerError {
+    /// Returns who caused this error.
+    pub fn fault(&self) -> Fault {
+        self.fault
+    }
+}
+
+impl fmt::Display for SharedPeerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl StdError for SharedPeerError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.error.source()
+    }
+}
 
 impl<E> From<E> for SharedPeerError
 where
     PeerError: From<E>,
 {
     fn from(source: E) -> Self {
-        Self(Arc::new(TracedError::from(PeerError::from(source))))
+        let error = PeerError::from(source);
+        let fault = error.fault();
+        Self {
+            error: Arc::new(TracedError::from(error)),
+            fault,
+        }
     }
 }
 
@@ -57,6 +130,23 @@ pub enum PeerError {
     WrongBlock,
 }
 
+impl PeerError {
+    /// Returns who caused this error.
+    pub fn fault(&self) -> Fault {
+        match self {
+            PeerError::ConnectionClosed | PeerError::ClientRequestTimeout => Fault::Transient,
+            PeerError::Overloaded => Fault::Ours,
+            // Rejects can be caused by our own requests, or by races with
+            // the peer's mempool, so they don't count against the peer.
+            PeerError::Rejected => Fault::Transient,
+            PeerError::Serialization(_)
+            | PeerError::DuplicateHandshake
+            | PeerError::UnsupportedMessage
+            | PeerError::WrongBlock => Fault::Peer,
+        }
+    }
+}
+
 #[derive(Default, Clone)]
 pub(super) struct ErrorSlot(pub(super) Arc<Mutex<Option<SharedPeerError>>>);
 
@@ -92,3 +182,46 @@ pub enum HandshakeError {
     #[error("Peer offered obsolete version: {0:?}")]
     ObsoleteVersion(crate::protocol::external::types::Version),
 }
+
+impl HandshakeError {
+    /// Returns who caused this error.
+    pub fn fault(&self) -> Fault {
+        match self {
+            HandshakeError::ConnectionClosed | HandshakeError::Io(_) => Fault::Transient,
+            // Nonce reuse usually means we connected to ourselves
+            HandshakeError::NonceReuse => Fault::Ours,
+            HandshakeError::UnexpectedMessage(_)
+            | HandshakeError::Serialization(_)
+            | HandshakeError::ObsoleteVersion(_) => Fault::Peer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::BoxedStdError;
+
+    #[test]
+    fn faults_of_boxed_errors() {
+        let shared: BoxedStdError = SharedPeerError::from(PeerError::WrongBlock).into();
+        assert_eq!(Fault::of(&*shared), Some(Fault::Peer));
+
+        let handshake: BoxedStdError = HandshakeError::NonceReuse.into();
+        assert_eq!(Fault::of(&*handshake), Some(Fault::Ours));
+
+        let io: BoxedStdError = std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into();
+        assert_eq!(Fault::of(&*io), Some(Fault::Transient));
+
+        let other: BoxedStdError = "some other error".into();
+        assert_eq!(Fault::of(&*other), None);
+    }
+
+    #[test]
+    fn shared_errors_keep_their_fault() {
+        let error = SharedPeerError::from(PeerError::Overloaded);
+        assert_eq!(error.clone().fault(), Fault::Ours);
+        assert_eq!(error.to_string(), PeerError::Overloaded.to_string());
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     pin::Pin,
     sync::{Arc, Mutex},
     task::{Context, Poll},
+    time::Instant,
 };
 
 use chrono::Utc;
@@ -26,8 +27,8 @@ use crate::{
         external::{types::*, Codec, Message},
         internal::{Request, Response},
     },
-    types::MetaAddr,
-    BoxedStdError, Config,
+    types::{ConnectionDirection, MetaAddr, PeerInfo},
+    AddressBook, BoxedStdError, Config,
 };
 
 use super::{Client, Connection, ErrorSlot, HandshakeError};
@@ -38,6 +39,7 @@ pub struct Handshake<S> {
     config: Config,
     internal_service: S,
     timestamp_collector: mpsc::Sender<MetaAddr>,
+    address_book: Arc<Mutex<AddressBook>>,
     nonces: Arc<Mutex<HashSet<Nonce>>>,
 }
 
@@ -47,6 +49,7 @@ impl<S: Clone> Clone for Handshake<S> {
             config: self.config.clone(),
             internal_service: self.internal_service.clone(),
             timestamp_collector: self.timestamp_collector.clone(),
+            address_book: self.address_book.clone(),
             nonces: self.nonces.clone(),
         }
     }
@@ -62,6 +65,7 @@ where
         config: Config,
         internal_service: S,
         timestamp_collector: mpsc::Sender<MetaAddr>,
+        address_book: Arc<Mutex<AddressBook>>,
     ) -> Self {
         // XXX this function has too many parameters, but it's not clear how to
         // do a nice builder as all fields are mandatory. Could have Builder1,
@@ -72,12 +76,13 @@ where
             config,
             internal_service,
             timestamp_collector,
+            address_book,
             nonces: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 }
 
-impl<S> Service<(TcpStream, SocketAddr)> for Handshake<S>
+impl<S> Service<(TcpStream, SocketAddr, ConnectionDirection)> for Handshake<S>
 where
     S: Service<Request, Response = Response, Error = BoxedStdError> + Clone + Send + 'static,
     S::Future: Send,
@@ -91,8 +96,8 @@ where
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: (TcpStream, SocketAddr)) -> Self::Future {
-        let (tcp_stream, addr) = req;
+    fn call(&mut self, req: (TcpStream, SocketAddr, ConnectionDirection)) -> Self::Future {
+        let (tcp_stream, addr, direction) = req;
 
         let connector_span = span!(Level::INFO, "connector", addr = ?addr);
         // set parent: None for the peer connection span, as it should exist
@@ -104,6 +109,7 @@ where
         let nonces = self.nonces.clone();
         let internal_service = self.internal_service.clone();
         let timestamp_collector = self.timestamp_collector.clone();
+        let address_book = self.address_book.clone();
         let user_agent = self.config.user_agent.clone();
         let network = self.config.network;
 
@@ -137,6 +143,9 @@ where
                 relay: false,
             };
 
+            // Measure the handshake latency from our version message to the
+            // remote verack, which includes the peer's processing time.
+            let handshake_start = Instant::now();
             debug!(?version, "sending initial version message");
             stream.send(version).await?;
 
@@ -147,14 +156,22 @@ where
 
             // Check that we got a Version and destructure its fields into the local scope.
             debug!(?remote_msg, "got message from remote peer");
-            let (remote_nonce, remote_services, remote_version) = if let Message::Version {
+            let (
+                remote_nonce,
+                remote_services,
+                remote_version,
+                remote_user_agent,
+                remote_start_height,
+            ) = if let Message::Version {
                 nonce,
                 services,
                 version,
+                user_agent,
+                start_height,
                 ..
             } = remote_msg
             {
-                (nonce, services, version)
+                (nonce, services, version, user_agent, start_height)
             } else {
                 return Err(HandshakeError::UnexpectedMessage(Box::new(remote_msg)));
             };
@@ -182,6 +199,8 @@ where
             } else {
                 return Err(HandshakeError::UnexpectedMessage(Box::new(remote_msg)));
             }
+            let handshake_latency = handshake_start.elapsed();
+            debug!(?handshake_latency, "finished version handshake");
 
             // XXX in zcashd remote peer can only send one version message and
             // we would disconnect here if it received a second one. Is it even possible
@@ -220,6 +239,37 @@ where
             let bare_codec = stream.codec_mut();
             bare_codec.reconfigure_version(negotiated_version);
 
+            // Peers only send their height during the handshake, so we record
+            // it for estimating the network chain tip, along with the other
+            // handshake fields for peer diagnostics.
+            {
+                let mut address_book = address_book.lock().expect("mutex should be unpoisoned");
+
+                let now = Utc::now();
+                address_book.connection_opened(PeerInfo {
+                    addr,
+                    direction,
+                    version: negotiated_version,
+                    user_agent: remote_user_agent,
+                    services: remote_services,
+                    start_height: remote_start_height,
+                    connected_at: now,
+                    last_seen: now,
+                    failures: 0,
+                    awaiting_response: false,
+                });
+            }
+
+            // Record the latency, so the crawler can rank this peer the next
+            // time it needs a candidate.
+            let _ = timestamp_collector
+                .clone()
+                .send(MetaAddr {
+                    handshake_latency: Some(handshake_latency),
+                    ..MetaAddr::new(addr, remote_services, Utc::now())
+                })
+                .await;
+
             debug!("constructing client, spawning server");
 
             // These channels should not be cloned more than they are
@@ -263,11 +313,7 @@ where
                             );
                             use futures::sink::SinkExt;
                             let _ = timestamp_collector
-                                .send(MetaAddr {
-                                    addr,
-                                    services: remote_services,
-                                    last_seen: Utc::now(),
-                                })
+                                .send(MetaAddr::new(addr, remote_services, Utc::now()))
                                 .await;
                         }
                         msg
@@ -283,6 +329,8 @@ where
                 error_slot: slot,
                 peer_tx,
                 request_timer: None,
+                addr,
+                address_book,
             };
 
             tokio::spawn(
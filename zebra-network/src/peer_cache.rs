@@ -0,0 +1,332 @@
+//! Reading and writing peer address caches.
+//!
+//! Zebra stores its peer cache in a stable text format, so it can be shared
+//! with other tools:
+//!
+//! ```text
+//! # zebra-peers v1
+//! # <address> <services> <last seen, in seconds since the Unix epoch>
+//! 203.0.113.6:8233 1 1573680222
+//! [2001:db8::1]:8233 1 1573680100
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored, except for the
+//! version header, which must be the first line.
+//!
+//! Zebra can also read the `peers.dat` files written by `zcashd`.
+
+use std::{
+    fs,
+    io::{self, BufRead, Cursor, Read, Write},
+    net::SocketAddr,
+    path::Path,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::{TimeZone, Utc};
+use thiserror::Error;
+
+use zebra_chain::{
+    serialization::{ReadZcashExt, SerializationError, ZcashDeserialize},
+    types::Sha256dChecksum,
+    Network,
+};
+
+use crate::{
+    protocol::external::types::Magic,
+    types::{MetaAddr, PeerServices},
+};
+
+/// The first line of a Zebra peer cache file.
+pub const HEADER: &str = "# zebra-peers v1";
+
+/// The size of the trailing checksum in a `zcashd` `peers.dat` file.
+const PEERS_DAT_CHECKSUM_LEN: usize = 32;
+
+/// The serialized size of each address entry in a `zcashd` `peers.dat` file.
+const PEERS_DAT_ENTRY_LEN: usize = 62;
+
+/// An error reading a peer cache.
+#[derive(Error, Debug)]
+pub enum PeerCacheError {
+    /// An underlying IO error.
+    #[error("io error")]
+    Io(#[from] io::Error),
+    /// A `peers.dat` entry was malformed.
+    #[error("serialization error")]
+    Serialization(#[from] SerializationError),
+    /// The file is not a Zebra peer cache or a `zcashd` `peers.dat` file for
+    /// the configured network.
+    #[error("unknown peer cache format, or a peers.dat file for a different network")]
+    UnknownFormat,
+    /// A line in a Zebra peer cache was malformed.
+    #[error("line {line}: {reason}")]
+    Parse {
+        /// The line number, starting at 1.
+        line: usize,
+        /// The reason the line is invalid.
+        reason: &'static str,
+    },
+    /// The checksum in a `peers.dat` file did not match its contents.
+    #[error("peers.dat checksum does not match its contents")]
+    BadChecksum,
+}
+
+/// Write `peers` to `writer` in the Zebra peer cache format.
+pub fn write<W, I>(mut writer: W, peers: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = MetaAddr>,
+{
+    writeln!(writer, "{}", HEADER)?;
+    writeln!(
+        writer,
+        "# <address> <services> <last seen, in seconds since the Unix epoch>"
+    )?;
+    for peer in peers {
+        writeln!(
+            writer,
+            "{} {} {}",
+            peer.addr,
+            peer.services.bits(),
+            peer.last_seen.timestamp()
+        )?;
+    }
+    Ok(())
+}
+
+/// Read peers from `reader` in the Zebra peer cache format.
+pub fn read<R: BufRead>(reader: R) -> Result<Vec<MetaAddr>, PeerCacheError> {
+    let mut lines = reader.lines();
+    match lines.next().transpose()? {
+        Some(header) if header.trim_end() == HEADER => {}
+        _ => return Err(PeerCacheError::UnknownFormat),
+    }
+
+    let mut peers = Vec::new();
+    // The header is line 1
+    for (line_number, line) in (2..).zip(lines) {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parse_error = |reason| PeerCacheError::Parse {
+            line: line_number,
+            reason,
+        };
+        let mut fields = line.split_whitespace();
+        let addr: SocketAddr = fields
+            .next()
+            .and_then(|addr| addr.parse().ok())
+            .ok_or_else(|| parse_error("invalid address"))?;
+        let services = fields
+            .next()
+            .and_then(|services| services.parse().ok())
+            // Discard unknown service bits, like we do for addr messages
+            .map(PeerServices::from_bits_truncate)
+            .ok_or_else(|| parse_error("invalid services"))?;
+        let last_seen = fields
+            .next()
+            .and_then(|last_seen| last_seen.parse().ok())
+            .map(|last_seen| Utc.timestamp(last_seen, 0))
+            .ok_or_else(|| parse_error("invalid last seen time"))?;
+        if fields.next().is_some() {
+            return Err(parse_error("too many fields"));
+        }
+
+        peers.push(MetaAddr::new(addr, services, last_seen));
+    }
+
+    Ok(peers)
+}
+
+/// Read peers from the contents of a `zcashd` `peers.dat` file for `network`.
+///
+/// Only the address entries are read. `zcashd`'s bucket assignments and
+/// connection statistics are ignored.
+pub fn read_zcashd_peers_dat(
+    bytes: &[u8],
+    network: Network,
+) -> Result<Vec<MetaAddr>, PeerCacheError> {
+    if bytes.len() < PEERS_DAT_CHECKSUM_LEN || !bytes.starts_with(&Magic::from(network).0) {
+        return Err(PeerCacheError::UnknownFormat);
+    }
+
+    // The checksum is a full SHA-256d hash, but we only have a helper for its
+    // first four bytes, which is enough to detect corruption.
+    let (data, checksum) = bytes.split_at(bytes.len() - PEERS_DAT_CHECKSUM_LEN);
+    if Sha256dChecksum::from(data).0[..] != checksum[..4] {
+        return Err(PeerCacheError::BadChecksum);
+    }
+
+    let mut reader = Cursor::new(&data[4..]);
+    let _version = reader.read_u8()?;
+    let key_len = reader.read_u8()?;
+    if key_len != 32 {
+        return Err(SerializationError::Parse("unexpected peers.dat key size").into());
+    }
+    let _key = reader.read_32_bytes()?;
+    let new_count = reader.read_i32::<LittleEndian>()?;
+    let tried_count = reader.read_i32::<LittleEndian>()?;
+    let _bucket_count = reader.read_i32::<LittleEndian>()?;
+
+    if new_count < 0 || tried_count < 0 {
+        return Err(SerializationError::Parse("negative peers.dat entry count").into());
+    }
+    let count = new_count as usize + tried_count as usize;
+    let remaining = data.len().saturating_sub(4 + reader.position() as usize);
+    if count > remaining / PEERS_DAT_ENTRY_LEN {
+        return Err(SerializationError::Parse("peers.dat entry count is too large").into());
+    }
+
+    let mut peers = Vec::with_capacity(count);
+    for _ in 0..count {
+        // Each entry is a disk-serialized CAddress, followed by the address
+        // it was learned from and some connection statistics.
+        let _serialization_version = reader.read_i32::<LittleEndian>()?;
+        peers.push(MetaAddr::zcash_deserialize(&mut reader)?);
+        let _source = reader.read_ip_addr()?;
+        let _last_success = reader.read_i64::<LittleEndian>()?;
+        let _attempts = reader.read_i32::<LittleEndian>()?;
+    }
+
+    Ok(peers)
+}
+
+/// Read the peers in the file at `path`, which can be a Zebra peer cache, or
+/// a `zcashd` `peers.dat` file for `network`.
+pub fn load(path: &Path, network: Network) -> Result<Vec<MetaAddr>, PeerCacheError> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)?.read_to_end(&mut bytes)?;
+
+    if bytes.starts_with(HEADER.as_bytes()) {
+        read(&bytes[..])
+    } else {
+        read_zcashd_peers_dat(&bytes, network)
+    }
+}
+
+/// Write `peers` to a Zebra peer cache file at `path`.
+///
+/// The peers are written to a temporary file, which then replaces `path`, so
+/// readers never see a partially written cache.
+pub fn save<I>(path: &Path, peers: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = MetaAddr>,
+{
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut file = io::BufWriter::new(fs::File::create(&temp_path)?);
+        write(&mut file, peers)?;
+        file.flush()?;
+    }
+    fs::rename(temp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_chain::serialization::ZcashSerialize;
+
+    fn peers() -> Vec<MetaAddr> {
+        vec![
+            MetaAddr::new(
+                "203.0.113.6:8233".parse().unwrap(),
+                PeerServices::NODE_NETWORK,
+                Utc.timestamp(1_573_680_222, 0),
+            ),
+            MetaAddr::new(
+                "[2001:db8::1]:18233".parse().unwrap(),
+                PeerServices::empty(),
+                Utc.timestamp(1_573_680_100, 0),
+            ),
+        ]
+    }
+
+    #[test]
+    fn roundtrip() {
+        zebra_test::init();
+
+        let mut bytes = Vec::new();
+        write(&mut bytes, peers()).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("203.0.113.6:8233 1 1573680222"));
+
+        assert_eq!(read(&bytes[..]).unwrap(), peers());
+    }
+
+    #[test]
+    fn parse_errors() {
+        zebra_test::init();
+
+        assert!(matches!(
+            read(&b"203.0.113.6:8233 1 1573680222\n"[..]),
+            Err(PeerCacheError::UnknownFormat)
+        ));
+
+        let bytes = format!("{}\n\n# comment\n203.0.113.6:8233 1\n", HEADER);
+        assert!(matches!(
+            read(bytes.as_bytes()),
+            Err(PeerCacheError::Parse { line: 4, .. })
+        ));
+    }
+
+    /// Returns a `peers.dat` file for `network` containing `peers`.
+    fn peers_dat(network: Network, peers: &[MetaAddr]) -> Vec<u8> {
+        use byteorder::WriteBytesExt;
+
+        let mut bytes = Magic::from(network).0.to_vec();
+        bytes.push(1);
+        bytes.push(32);
+        bytes.extend_from_slice(&[7; 32]);
+        bytes.write_i32::<LittleEndian>(peers.len() as i32).unwrap();
+        bytes.write_i32::<LittleEndian>(0).unwrap();
+        // zcashd flags the bucket count with bit 30
+        bytes.write_i32::<LittleEndian>(1024 ^ (1 << 30)).unwrap();
+        for peer in peers {
+            bytes.write_i32::<LittleEndian>(170_011).unwrap();
+            peer.zcash_serialize(&mut bytes).unwrap();
+            bytes.extend_from_slice(&[0; 16]);
+            bytes.write_i64::<LittleEndian>(0).unwrap();
+            bytes.write_i32::<LittleEndian>(0).unwrap();
+        }
+        // An empty new bucket
+        bytes.write_i32::<LittleEndian>(0).unwrap();
+
+        let mut checksum = Sha256dChecksum::from(&bytes[..]).0.to_vec();
+        checksum.extend_from_slice(&[0; 28]);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    #[test]
+    fn zcashd_peers_dat() {
+        zebra_test::init();
+
+        let bytes = peers_dat(Network::Mainnet, &peers());
+        assert_eq!(
+            read_zcashd_peers_dat(&bytes, Network::Mainnet).unwrap(),
+            peers()
+        );
+
+        assert!(matches!(
+            read_zcashd_peers_dat(&bytes, Network::Testnet),
+            Err(PeerCacheError::UnknownFormat)
+        ));
+
+        let mut corrupt = bytes;
+        corrupt[10] ^= 0xff;
+        assert!(matches!(
+            read_zcashd_peers_dat(&corrupt, Network::Mainnet),
+            Err(PeerCacheError::BadChecksum)
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+//! Diagnostic information about connected peers.
+
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Utc};
+
+use zebra_chain::types::BlockHeight;
+
+use crate::protocol::types::{PeerServices, Version};
+
+/// Which side of a peer connection opened it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConnectionDirection {
+    /// The remote peer connected to our listener.
+    Inbound,
+    /// We connected to the remote peer.
+    Outbound,
+    /// We connected to the remote peer to check that its address is
+    /// reachable, and that it supports our protocol version.
+    ///
+    /// Feeler connections are closed after the handshake.
+    Feeler,
+}
+
+/// Diagnostic information about a connected peer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PeerInfo {
+    /// The peer's address.
+    pub addr: SocketAddr,
+    /// Which side opened the connection.
+    pub direction: ConnectionDirection,
+    /// The protocol version negotiated during the handshake.
+    pub version: Version,
+    /// The user agent sent by the peer during the handshake.
+    pub user_agent: String,
+    /// The services advertised by the peer during the handshake.
+    pub services: PeerServices,
+    /// The block height advertised by the peer during the handshake.
+    pub start_height: BlockHeight,
+    /// When the handshake finished.
+    pub connected_at: DateTime<Utc>,
+    /// When we last received a message from the peer.
+    pub last_seen: DateTime<Utc>,
+    /// The number of our requests to the peer that have failed without
+    /// closing the connection, for example by timing out.
+    pub failures: u32,
+    /// Whether the peer is currently handling one of our requests.
+    ///
+    /// Each connection handles one request at a time, so this is the current
+    /// load on the connection.
+    pub awaiting_response: bool,
+}
@@ -1,9 +1,16 @@
 mod candidate_set;
+mod control;
 mod initialize;
+mod ip_prefix;
+mod rate_limit;
 mod set;
 mod unready_service;
 
-use candidate_set::CandidateSet;
+use candidate_set::{default_score, CandidateSet};
+use control::{is_connected, PeerCommand};
+use ip_prefix::IpPrefix;
+use rate_limit::RateLimiter;
 use set::PeerSet;
 
+pub use control::PeerControl;
 pub use initialize::init;
@@ -1,17 +1,28 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use chrono::Utc;
 use futures::stream::{FuturesUnordered, StreamExt};
 use tower::{Service, ServiceExt};
 use tracing::Level;
 
-use crate::{types::MetaAddr, AddressBook, BoxedStdError, Request, Response};
+use super::IpPrefix;
+use crate::{
+    trust::PeerTrust,
+    types::{ConnectionDirection, MetaAddr, PeerServices},
+    AddressBook, BoxedStdError, Request, Response,
+};
 
 /// The `CandidateSet` maintains a pool of candidate peers.
 ///
 /// It divides the set of all possible candidate peers into three disjoint subsets:
 ///
-/// 1. Disconnected peers, which we previously connected to but are not currently connected to;
+/// 1. Disconnected peers, which we previously connected to but are not currently connected to,
+///    including gossiped peers that passed a feeler connection;
 /// 2. Gossiped peers, which we learned about from other peers but have never connected to;
 /// 3. Failed peers, to whom we attempted to connect but were unable to.
 ///
@@ -74,12 +85,63 @@ use crate::{types::MetaAddr, AddressBook, BoxedStdError, Request, Response};
 ///    │to Discover │
 ///    └────────────┘
 /// ```
+///
+/// To make eclipse attacks harder, candidates are only selected if we have
+/// fewer than `outbound_prefix_limit` outbound connections to their IP prefix.
+/// Candidates with full prefixes stay in their subset, so they can be selected
+/// after some of those connections close. Trusted peers are chosen by the node
+/// operator, so they are exempt from the limit, and their connections don't
+/// count towards it.
+///
+/// Within each subset, candidates are ranked by a [`ScoreFn`], which prefers
+/// peers that serve blocks, respond quickly, and haven't failed recently.
+/// Candidates with equal scores are taken in the subset's recency order.
 pub(super) struct CandidateSet<S> {
     pub(super) disconnected: AddressBook,
     pub(super) gossiped: AddressBook,
     pub(super) failed: AddressBook,
     pub(super) peer_set: Arc<Mutex<AddressBook>>,
     pub(super) peer_service: S,
+    /// The maximum number of outbound connections to each IP prefix.
+    outbound_prefix_limit: usize,
+    /// The trust levels of peer addresses, used to exempt trusted peers from
+    /// the outbound prefix limit.
+    peer_trust: PeerTrust,
+    /// The candidates that are currently being dialed.
+    pending: HashSet<SocketAddr>,
+    /// The function used to rank candidates.
+    score: ScoreFn,
+}
+
+/// A function that scores a candidate peer. Higher scores are dialed first.
+pub(super) type ScoreFn = fn(&MetaAddr) -> i64;
+
+/// The assumed handshake latency of peers we haven't connected to.
+const UNKNOWN_LATENCY: Duration = Duration::from_millis(500);
+
+/// The maximum handshake latency that affects a candidate's score.
+const MAX_SCORED_LATENCY: Duration = Duration::from_secs(5);
+
+/// The maximum failure streak that affects a candidate's score.
+const MAX_SCORED_FAILURES: u32 = 10;
+
+/// The default candidate score.
+///
+/// Peers that don't advertise `NODE_NETWORK` can't serve blocks, so they
+/// are ranked below every peer that does. Then each failed connection attempt
+/// costs as much as a second of handshake latency.
+pub(super) fn default_score(meta: &MetaAddr) -> i64 {
+    let latency = meta
+        .handshake_latency
+        .unwrap_or(UNKNOWN_LATENCY)
+        .min(MAX_SCORED_LATENCY);
+    let failures = meta.failure_streak.min(MAX_SCORED_FAILURES);
+
+    let mut score = -(latency.as_millis() as i64) - 1_000 * i64::from(failures);
+    if !meta.services.contains(PeerServices::NODE_NETWORK) {
+        score -= 1_000_000;
+    }
+    score
 }
 
 impl<S> CandidateSet<S>
@@ -87,13 +149,23 @@ where
     S: Service<Request, Response = Response, Error = BoxedStdError>,
     S::Future: Send + 'static,
 {
-    pub fn new(peer_set: Arc<Mutex<AddressBook>>, peer_service: S) -> CandidateSet<S> {
+    pub fn new(
+        peer_set: Arc<Mutex<AddressBook>>,
+        peer_service: S,
+        outbound_prefix_limit: usize,
+        peer_trust: PeerTrust,
+        score: ScoreFn,
+    ) -> CandidateSet<S> {
         CandidateSet {
             disconnected: AddressBook::new(span!(Level::TRACE, "disconnected peers")),
             gossiped: AddressBook::new(span!(Level::TRACE, "gossiped peers")),
             failed: AddressBook::new(span!(Level::TRACE, "failed peers")),
             peer_set,
             peer_service,
+            outbound_prefix_limit,
+            peer_trust,
+            pending: HashSet::new(),
+            score,
         }
     }
 
@@ -146,20 +218,294 @@ where
         Ok(())
     }
 
+    /// Add `addrs` from a peer cache to the gossiped peers.
+    pub fn add_cached(&mut self, addrs: impl IntoIterator<Item = MetaAddr>) {
+        let peer_set = &self.peer_set;
+        self.gossiped.extend(
+            addrs
+                .into_iter()
+                .filter(|meta| !peer_set.lock().unwrap().contains_addr(&meta.addr)),
+        );
+    }
+
+    /// Returns the next candidate to dial, and marks it as pending.
+    ///
+    /// Callers must call `report_failed` or `report_finished` when they are
+    /// finished with the candidate.
     pub fn next(&mut self) -> Option<MetaAddr> {
         metrics::gauge!("candidate_set.disconnected", self.disconnected.len() as i64);
         metrics::gauge!("candidate_set.gossiped", self.gossiped.len() as i64);
         metrics::gauge!("candidate_set.failed", self.failed.len() as i64);
         let guard = self.peer_set.lock().unwrap();
-        self.disconnected
-            .drain_oldest()
-            .chain(self.gossiped.drain_newest())
-            .chain(self.failed.drain_oldest())
-            .find(|meta| !guard.is_potentially_connected(&meta.addr))
+
+        // Count our untrusted outbound connections, including the ones we're
+        // dialing
+        let peer_trust = &self.peer_trust;
+        let mut outbound = HashMap::<IpPrefix, usize>::new();
+        let connected = guard
+            .connected_peers()
+            .filter(|info| info.direction == ConnectionDirection::Outbound)
+            .map(|info| info.addr);
+        for addr in connected
+            .chain(self.pending.iter().cloned())
+            .filter(|addr| !peer_trust.is_trusted(addr))
+        {
+            *outbound.entry(IpPrefix::from(addr.ip())).or_default() += 1;
+        }
+        let limit = self.outbound_prefix_limit;
+        let has_capacity = |meta: &MetaAddr| {
+            peer_trust.is_trusted(&meta.addr)
+                || outbound
+                    .get(&IpPrefix::from(meta.addr.ip()))
+                    .map(|count| *count < limit)
+                    .unwrap_or(true)
+        };
+        let pending = &self.pending;
+        let is_connected = |meta: &MetaAddr| {
+            guard.is_potentially_connected(&meta.addr) || pending.contains(&meta.addr)
+        };
+
+        let score = self.score;
+        let (disconnected, gossiped, failed) =
+            (&mut self.disconnected, &mut self.gossiped, &mut self.failed);
+        let next = take_candidate(disconnected, false, &is_connected, &has_capacity, score)
+            .or_else(|| take_candidate(gossiped, true, &is_connected, &has_capacity, score))
+            .or_else(|| take_candidate(failed, false, &is_connected, &has_capacity, score))?;
+        self.pending.insert(next.addr);
+
+        Some(next)
+    }
+
+    /// Returns the next gossiped candidate for a feeler connection, and marks
+    /// it as pending.
+    ///
+    /// Feeler connections check that gossiped peers are reachable, so we
+    /// can try them before other gossiped peers. They are closed after the
+    /// handshake, so they don't count towards the outbound prefix limit.
+    pub fn next_feeler(&mut self) -> Option<MetaAddr> {
+        let guard = self.peer_set.lock().unwrap();
+        let pending = &self.pending;
+        let is_connected = |meta: &MetaAddr| {
+            guard.is_potentially_connected(&meta.addr) || pending.contains(&meta.addr)
+        };
+
+        let next = take_candidate(&mut self.gossiped, true, is_connected, |_| true, self.score)?;
+        self.pending.insert(next.addr);
+
+        Some(next)
+    }
+
+    /// Moves a candidate that passed a feeler connection to the disconnected
+    /// peers, which are tried before gossiped peers.
+    pub fn report_feeler_succeeded(&mut self, mut addr: MetaAddr) {
+        self.pending.remove(&addr.addr);
+        addr.last_seen = Utc::now();
+        self.disconnected.update(addr);
     }
 
     pub fn report_failed(&mut self, mut addr: MetaAddr) {
+        self.pending.remove(&addr.addr);
         addr.last_seen = Utc::now();
+        addr.failure_streak = addr.failure_streak.saturating_add(1);
         self.failed.update(addr);
     }
+
+    /// Marks the candidate at `addr` as no longer pending, because it has
+    /// connected, or we decided not to dial it.
+    pub fn report_finished(&mut self, addr: &SocketAddr) {
+        self.pending.remove(addr);
+    }
+}
+
+/// Removes and returns the highest scoring candidate in `book` that isn't
+/// connected or pending, and has an IP prefix with outbound capacity.
+///
+/// Candidates with equal scores are taken newest first if `newest_first` is
+/// true, and oldest first otherwise. Connected candidates are discarded, and
+/// candidates without capacity are kept in `book`.
+fn take_candidate(
+    book: &mut AddressBook,
+    newest_first: bool,
+    is_connected: impl Fn(&MetaAddr) -> bool,
+    has_capacity: impl Fn(&MetaAddr) -> bool,
+    score: ScoreFn,
+) -> Option<MetaAddr> {
+    // `MetaAddr`s sort newest-first
+    let mut candidates = book.peers().collect::<Vec<_>>();
+    candidates.sort();
+    if !newest_first {
+        candidates.reverse();
+    }
+
+    let mut best: Option<(i64, MetaAddr)> = None;
+    for meta in candidates {
+        if is_connected(&meta) {
+            book.remove(&meta.addr);
+        } else if has_capacity(&meta) {
+            let meta_score = score(&meta);
+            match best {
+                Some((best_score, _)) if best_score >= meta_score => {}
+                _ => best = Some((meta_score, meta)),
+            }
+        }
+    }
+
+    let (_, candidate) = best?;
+    book.remove(&candidate.addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::IpAddr;
+
+    use chrono::TimeZone;
+    use tracing::Span;
+
+    use crate::{trust::TrustLevel, types::PeerServices};
+
+    fn meta(addr: &str, last_seen: i64) -> MetaAddr {
+        MetaAddr::new(
+            addr.parse().unwrap(),
+            PeerServices::NODE_NETWORK,
+            Utc.timestamp(last_seen, 0),
+        )
+    }
+
+    #[test]
+    fn take_candidate_skips_full_prefixes() {
+        zebra_test::init();
+
+        let mut book = AddressBook::new(Span::none());
+        book.extend(vec![
+            meta("1.1.1.1:8233", 3),
+            meta("1.2.1.1:8233", 2),
+            meta("1.3.1.1:8233", 1),
+        ]);
+
+        let is_connected = |meta: &MetaAddr| meta.addr.ip() == IpAddr::from([1, 3, 1, 1]);
+        let has_capacity = |meta: &MetaAddr| IpPrefix::from(meta.addr.ip()) != IpPrefix::V4([1, 1]);
+
+        // The newest candidate has a full prefix, so it is kept
+        let candidate = take_candidate(&mut book, true, &is_connected, &has_capacity, |_| 0);
+        assert_eq!(candidate, Some(meta("1.2.1.1:8233", 2)));
+        assert!(book.contains_addr(&"1.1.1.1:8233".parse().unwrap()));
+
+        // Connected candidates are discarded
+        let candidate = take_candidate(&mut book, false, &is_connected, &has_capacity, |_| 0);
+        assert_eq!(candidate, None);
+        assert!(!book.contains_addr(&"1.3.1.1:8233".parse().unwrap()));
+        assert_eq!(book.len(), 1);
+    }
+
+    #[test]
+    fn feelers_take_newest_gossiped_peers() {
+        zebra_test::init();
+
+        let peer_set = Arc::new(Mutex::new(AddressBook::new(Span::none())));
+        let peer_service =
+            tower::service_fn(|_: Request| async { Ok::<_, BoxedStdError>(Response::Nil) });
+        let mut candidates = CandidateSet::new(
+            peer_set,
+            peer_service,
+            2,
+            PeerTrust::default(),
+            default_score,
+        );
+        candidates.add_cached(vec![meta("1.1.1.1:8233", 1), meta("1.2.1.1:8233", 2)]);
+
+        let feeler = candidates.next_feeler().expect("there are gossiped peers");
+        assert_eq!(feeler, meta("1.2.1.1:8233", 2));
+
+        // Successful feelers are tried before gossiped peers
+        candidates.report_feeler_succeeded(feeler);
+        assert_eq!(candidates.next().map(|meta| meta.addr), Some(feeler.addr));
+        assert_eq!(candidates.next(), Some(meta("1.1.1.1:8233", 1)));
+        assert_eq!(candidates.next_feeler(), None);
+    }
+
+    #[test]
+    fn trusted_peers_skip_prefix_limit() {
+        zebra_test::init();
+
+        let peer_set = Arc::new(Mutex::new(AddressBook::new(Span::none())));
+        let peer_service =
+            tower::service_fn(|_: Request| async { Ok::<_, BoxedStdError>(Response::Nil) });
+        let peer_trust = PeerTrust(
+            vec![("1.1.1.1".parse().unwrap(), TrustLevel::Trusted)]
+                .into_iter()
+                .collect(),
+        );
+        let mut candidates =
+            CandidateSet::new(peer_set, peer_service, 1, peer_trust, default_score);
+        candidates.add_cached(vec![
+            meta("1.1.1.1:8233", 3),
+            meta("1.1.2.2:8233", 2),
+            meta("1.1.3.3:8233", 1),
+        ]);
+
+        // The pending trusted peer doesn't count towards the limit
+        assert_eq!(candidates.next(), Some(meta("1.1.1.1:8233", 3)));
+        assert_eq!(candidates.next(), Some(meta("1.1.2.2:8233", 2)));
+        assert_eq!(candidates.next(), None);
+
+        // Trusted peers can be dialed when their prefix is full
+        candidates.report_finished(&"1.1.1.1:8233".parse().unwrap());
+        candidates.add_cached(vec![meta("1.1.1.1:8233", 4)]);
+        assert_eq!(candidates.next(), Some(meta("1.1.1.1:8233", 4)));
+    }
+
+    #[test]
+    fn take_candidate_ranks_by_score() {
+        zebra_test::init();
+
+        let mut book = AddressBook::new(Span::none());
+        let slow = MetaAddr {
+            handshake_latency: Some(Duration::from_secs(2)),
+            ..meta("1.1.1.1:8233", 4)
+        };
+        let fast = MetaAddr {
+            handshake_latency: Some(Duration::from_millis(50)),
+            ..meta("1.2.1.1:8233", 3)
+        };
+        let failing = MetaAddr {
+            handshake_latency: Some(Duration::from_millis(50)),
+            failure_streak: 3,
+            ..meta("1.3.1.1:8233", 2)
+        };
+        let no_blocks = MetaAddr {
+            services: PeerServices::empty(),
+            ..meta("1.4.1.1:8233", 1)
+        };
+        book.extend(vec![slow, fast, failing, no_blocks]);
+
+        let mut order = Vec::new();
+        while let Some(candidate) =
+            take_candidate(&mut book, true, |_| false, |_| true, default_score)
+        {
+            order.push(candidate);
+        }
+        assert_eq!(order, vec![fast, slow, failing, no_blocks]);
+    }
+
+    #[test]
+    fn failures_lower_scores() {
+        let candidate = meta("1.1.1.1:8233", 1);
+        let failed = MetaAddr {
+            failure_streak: 1,
+            ..candidate
+        };
+        assert!(default_score(&failed) < default_score(&candidate));
+
+        // Streaks are capped, so peers can recover after long outages
+        let streak = |failure_streak| MetaAddr {
+            failure_streak,
+            ..candidate
+        };
+        assert_eq!(
+            default_score(&streak(MAX_SCORED_FAILURES)),
+            default_score(&streak(u32::MAX))
+        );
+    }
 }
@@ -0,0 +1,199 @@
+//! Operator control of individual peer connections.
+//!
+//! Manual peers are peers that the node operator wants us to stay connected
+//! to, like their own infrastructure nodes. They are reconnected when their
+//! connections close, and they can't be disconnected until they are removed.
+
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures::{channel::mpsc, sink::SinkExt};
+
+use crate::{AddressBook, BoxedStdError};
+
+/// A command from the node operator to the peer set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum PeerCommand {
+    /// Try to connect to the peer once.
+    Connect(SocketAddr),
+    /// Close the connection to the peer.
+    Disconnect(SocketAddr),
+}
+
+/// A handle for connecting to and disconnecting from individual peers.
+///
+/// Commands are applied by a background task, so connections are opened
+/// and closed after the methods return.
+#[derive(Clone, Debug)]
+pub struct PeerControl {
+    /// The sender for the background task's commands.
+    commands: mpsc::Sender<PeerCommand>,
+    /// The address book, which holds our current peer connections.
+    address_book: Arc<Mutex<AddressBook>>,
+    /// The manual peers, which the background task keeps connected.
+    manual_peers: Arc<Mutex<HashSet<SocketAddr>>>,
+}
+
+impl PeerControl {
+    /// Returns a handle that sends commands for the peers in `address_book`
+    /// over `commands`, and updates the `manual_peers`.
+    pub(super) fn new(
+        commands: mpsc::Sender<PeerCommand>,
+        address_book: Arc<Mutex<AddressBook>>,
+        manual_peers: Arc<Mutex<HashSet<SocketAddr>>>,
+    ) -> PeerControl {
+        PeerControl {
+            commands,
+            address_book,
+            manual_peers,
+        }
+    }
+
+    /// Adds `addr` to the manual peers, and starts connecting to it.
+    ///
+    /// Returns an error if the peer has already been added. Manual peers are
+    /// not saved, so peers that should be kept after a restart must also be
+    /// added to the `manual_peers` config.
+    pub async fn add(&self, addr: SocketAddr) -> Result<(), BoxedStdError> {
+        if !self
+            .manual_peers
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .insert(addr)
+        {
+            Err("peer has already been added")?;
+        }
+
+        // Connect now, rather than waiting for the next reconnection attempt
+        if !self.is_connected(&addr) {
+            self.commands
+                .clone()
+                .send(PeerCommand::Connect(addr))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Removes `addr` from the manual peers.
+    ///
+    /// Returns an error if the peer has not been added. The current
+    /// connection to the peer stays open, but it is not reconnected after it
+    /// closes.
+    pub fn remove(&self, addr: SocketAddr) -> Result<(), BoxedStdError> {
+        if !self
+            .manual_peers
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .remove(&addr)
+        {
+            Err("peer has not been added")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the manual peers, ordered by address.
+    pub fn manual_peers(&self) -> Vec<SocketAddr> {
+        let mut peers: Vec<_> = self
+            .manual_peers
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .iter()
+            .cloned()
+            .collect();
+        peers.sort();
+        peers
+    }
+
+    /// Starts a single connection attempt to the peer at `addr`.
+    ///
+    /// Returns an error if we are already connected to the peer. Failed
+    /// connection attempts are logged, but not retried.
+    pub async fn connect(&self, addr: SocketAddr) -> Result<(), BoxedStdError> {
+        if self.is_connected(&addr) {
+            Err("peer is already connected")?;
+        }
+
+        self.commands
+            .clone()
+            .send(PeerCommand::Connect(addr))
+            .await?;
+        Ok(())
+    }
+
+    /// Closes the connection to the peer at `addr`.
+    ///
+    /// Returns an error if we are not connected to the peer, or if it is a
+    /// manual peer. The peer can be reconnected later, if it is chosen by the
+    /// crawler, or it connects to us.
+    pub async fn disconnect(&self, addr: SocketAddr) -> Result<(), BoxedStdError> {
+        if !self.is_connected(&addr) {
+            Err("peer is not connected")?;
+        }
+        if self
+            .manual_peers
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .contains(&addr)
+        {
+            Err("peer was added manually, remove it before disconnecting")?;
+        }
+
+        self.commands
+            .clone()
+            .send(PeerCommand::Disconnect(addr))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns true if we have an open connection to the peer at `addr`.
+    fn is_connected(&self, addr: &SocketAddr) -> bool {
+        is_connected(&self.address_book, addr)
+    }
+}
+
+/// Returns true if `address_book` has an open connection to the peer at
+/// `addr`.
+pub(super) fn is_connected(address_book: &Mutex<AddressBook>, addr: &SocketAddr) -> bool {
+    address_book
+        .lock()
+        .expect("mutex should be unpoisoned")
+        .connected_peers()
+        .any(|peer| peer.addr == *addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::StreamExt;
+    use tracing::Span;
+
+    #[tokio::test]
+    async fn manual_peers() -> Result<(), BoxedStdError> {
+        zebra_test::init();
+
+        let (commands, mut command_rx) = mpsc::channel(10);
+        let control = PeerControl::new(
+            commands,
+            Arc::new(Mutex::new(AddressBook::new(Span::none()))),
+            Arc::new(Mutex::new(HashSet::new())),
+        );
+        let addr: SocketAddr = "127.0.0.1:8233".parse()?;
+
+        // Added peers are connected immediately
+        control.add(addr).await?;
+        assert_eq!(command_rx.next().await, Some(PeerCommand::Connect(addr)));
+        assert_eq!(control.manual_peers(), vec![addr]);
+        assert!(control.add(addr).await.is_err());
+
+        control.remove(addr)?;
+        assert!(control.manual_peers().is_empty());
+        assert!(control.remove(addr).is_err());
+
+        Ok(())
+    }
+}
@@ -4,7 +4,9 @@
 // which is (c) 2019 Tower Contributors (MIT licensed).
 
 use std::{
+    collections::HashSet,
     net::SocketAddr,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 
@@ -24,16 +26,23 @@ use tower::{
 use tower_load::{peak_ewma::PeakEwmaDiscover, NoInstrument};
 
 use crate::{
-    peer, timestamp_collector::TimestampCollector, AddressBook, BoxedStdError, Config, Request,
-    Response,
+    constants, peer, peer_cache,
+    timestamp_collector::TimestampCollector,
+    types::{ConnectionDirection, MetaAddr},
+    AddressBook, BoxedStdError, Config, Fault, Request, Response,
 };
 
-use super::CandidateSet;
 use super::PeerSet;
+use super::RateLimiter;
+use super::{default_score, CandidateSet};
+use super::{is_connected, PeerCommand, PeerControl};
 
 type PeerChange = Result<Change<SocketAddr, peer::Client>, BoxedStdError>;
 
 /// Initialize a peer set with the given `config`, forwarding peer requests to the `inbound_service`.
+///
+/// Returns the peer set, its address book, and a handle for connecting to and
+/// disconnecting from individual peers.
 pub async fn init<S>(
     config: Config,
     inbound_service: S,
@@ -47,6 +56,7 @@ pub async fn init<S>(
         + Clone
         + 'static,
     Arc<Mutex<AddressBook>>,
+    PeerControl,
 )
 where
     S: Service<Request, Response = Response, Error = BoxedStdError> + Clone + Send + 'static,
@@ -58,13 +68,19 @@ where
     // handshakes. These use the same handshake service internally to detect
     // self-connection attempts. Both are decorated with a tower TimeoutLayer to
     // enforce timeouts as specified in the Config.
-    let (listener, connector) = {
+    let (listener, connector, feeler_connector) = {
         use tower::timeout::TimeoutLayer;
         let hs_timeout = TimeoutLayer::new(config.handshake_timeout);
-        let hs = peer::Handshake::new(config.clone(), inbound_service, timestamp_collector);
+        let hs = peer::Handshake::new(
+            config.clone(),
+            inbound_service,
+            timestamp_collector,
+            address_book.clone(),
+        );
         (
             hs_timeout.layer(hs.clone()),
-            hs_timeout.layer(peer::Connector::new(hs)),
+            hs_timeout.layer(peer::Connector::new(hs.clone())),
+            hs_timeout.layer(peer::Connector::feeler(hs)),
         )
     };
 
@@ -88,6 +104,8 @@ where
         ),
         demand_tx.clone(),
         handle_rx,
+        address_book.clone(),
+        config.peer_trust.clone(),
     );
     let peer_set = Buffer::new(peer_set, config.peerset_request_buffer_size);
 
@@ -95,16 +113,53 @@ where
 
     // 1. Initial peers, specified in the config.
     let add_guard = tokio::spawn(add_initial_peers(
-        config.initial_peers(),
+        config.clone(),
         connector.clone(),
         peerset_tx.clone(),
     ));
 
     // 2. Incoming peer connections, via a listener.
-    let listen_guard = tokio::spawn(listen(config.listen_addr, listener, peerset_tx.clone()));
+    let listen_guard = tokio::spawn(listen(
+        config.listen_addr,
+        listener,
+        RateLimiter::new(&config),
+        peerset_tx.clone(),
+    ));
 
-    // 3. Outgoing peers we connect to in response to load.
-    let mut candidates = CandidateSet::new(address_book.clone(), peer_set.clone());
+    // 3. Peers that the node operator connects to or disconnects from, and
+    //    manual peers, which we always reconnect to.
+    let (command_tx, command_rx) = mpsc::channel::<PeerCommand>(100);
+    let manual_peers = Arc::new(Mutex::new(HashSet::new()));
+    let peer_control = PeerControl::new(command_tx, address_book.clone(), manual_peers.clone());
+    let control_guard = tokio::spawn(apply_peer_commands(
+        config.clone(),
+        command_rx,
+        manual_peers,
+        address_book.clone(),
+        connector.clone(),
+        peerset_tx.clone(),
+    ));
+
+    // 4. Outgoing peers we connect to in response to load.
+    let mut candidates = CandidateSet::new(
+        address_book.clone(),
+        peer_set.clone(),
+        config.outbound_peers_per_prefix,
+        config.peer_trust.clone(),
+        default_score,
+    );
+
+    if let Some(path) = &config.peer_cache_file {
+        match peer_cache::load(path, config.network) {
+            Ok(cached) => {
+                info!(count = cached.len(), ?path, "loaded cached peers");
+                candidates.add_cached(cached);
+            }
+            // The cache is created by the first crawl
+            Err(peer_cache::PeerCacheError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(?path, error = %e, "could not load cached peers"),
+        }
+    }
 
     // We need to await candidates.update() here, because zcashd only sends one
     // `addr` message per connection, and if we only have one initial peer we
@@ -120,25 +175,28 @@ where
 
     let crawl_guard = tokio::spawn(crawl_and_dial(
         config.new_peer_interval,
+        config.peer_cache_file.clone(),
+        RateLimiter::new(&config),
         demand_tx,
         demand_rx,
         candidates,
         connector,
+        feeler_connector,
         peerset_tx,
     ));
 
     handle_tx
-        .send(vec![add_guard, listen_guard, crawl_guard])
+        .send(vec![add_guard, listen_guard, control_guard, crawl_guard])
         .unwrap();
 
-    (peer_set, address_book)
+    (peer_set, address_book, peer_control)
 }
 
-/// Use the provided `handshaker` to connect to `initial_peers`, then send
-/// the results over `tx`.
-#[instrument(skip(initial_peers, connector, tx))]
+/// Resolve the initial peers in `config`, use the provided `handshaker` to
+/// connect to them, then send the results over `tx`.
+#[instrument(skip(config, connector, tx))]
 async fn add_initial_peers<S>(
-    initial_peers: std::collections::HashSet<SocketAddr>,
+    config: Config,
     connector: S,
     mut tx: mpsc::Sender<PeerChange>,
 ) -> Result<(), BoxedStdError>
@@ -147,6 +205,7 @@ where
         + Clone,
     S::Future: Send + 'static,
 {
+    let initial_peers = config.initial_peers().await;
     info!(?initial_peers, "Connecting to initial peer set");
     use tower::util::CallAllUnordered;
     let addr_stream = futures::stream::iter(initial_peers.into_iter());
@@ -159,25 +218,140 @@ where
     Ok(())
 }
 
+/// Apply the node operator's `commands`, and keep the `manual_peers`
+/// connected, using `connector` to connect to peers. Then send the new peers
+/// and disconnected peers over `tx`.
+///
+/// The manual peers in `config` are added when the task starts. Manual peers
+/// that aren't in the `address_book`'s connections are redialed every
+/// [`constants::MANUAL_PEER_RECONNECT_INTERVAL`].
+#[instrument(skip(config, commands, manual_peers, address_book, connector, tx))]
+async fn apply_peer_commands<C>(
+    config: Config,
+    mut commands: mpsc::Receiver<PeerCommand>,
+    manual_peers: Arc<Mutex<HashSet<SocketAddr>>>,
+    address_book: Arc<Mutex<AddressBook>>,
+    mut connector: C,
+    mut tx: mpsc::Sender<PeerChange>,
+) -> Result<(), BoxedStdError>
+where
+    C: Service<SocketAddr, Response = Change<SocketAddr, peer::Client>, Error = BoxedStdError>
+        + Clone
+        + Send
+        + 'static,
+    C::Future: Send + 'static,
+{
+    use futures::{
+        future::{
+            select,
+            Either::{Left, Right},
+        },
+        TryFutureExt,
+    };
+
+    let initial_manual_peers = config.manual_peers().await;
+    if !initial_manual_peers.is_empty() {
+        info!(?initial_manual_peers, "adding manual peers");
+        manual_peers
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .extend(initial_manual_peers);
+    }
+
+    let mut handshakes = FuturesUnordered::new();
+    // Keep an unresolved future in the pool, so the stream never terminates.
+    handshakes.push(future::pending().boxed());
+    // Peers with in-flight handshakes, which we don't redial
+    let mut pending = HashSet::new();
+
+    // The first tick completes immediately, so we connect to the initial
+    // manual peers.
+    let mut reconnect_timer = tokio::time::interval(constants::MANUAL_PEER_RECONNECT_INTERVAL);
+
+    loop {
+        // Collect the peers to dial, so we don't hold the locks across awaits
+        let dial: Vec<SocketAddr> = match select(
+            select(commands.next(), reconnect_timer.next()),
+            handshakes.next(),
+        )
+        .await
+        {
+            Left((Left((Some(PeerCommand::Connect(addr)), _)), _)) => {
+                info!(?addr, "connecting to peer at operator request");
+                vec![addr]
+            }
+            Left((Left((Some(PeerCommand::Disconnect(addr)), _)), _)) => {
+                info!(?addr, "disconnecting from peer at operator request");
+                // Dropping the client closes the connection
+                tx.send(Ok(Change::Remove(addr))).await?;
+                continue;
+            }
+            Left((Right((Some(_timer), _)), _)) => manual_peers
+                .lock()
+                .expect("mutex should be unpoisoned")
+                .iter()
+                .filter(|addr| !is_connected(&address_book, addr))
+                .cloned()
+                .collect(),
+            Right((Some(Ok(change)), _)) => {
+                if let Change::Insert(ref addr, _) = change {
+                    pending.remove(addr);
+                }
+                tx.send(Ok(change)).await?;
+                continue;
+            }
+            Right((Some(Err((addr, error))), _)) => {
+                info!(?addr, %error, "failed to connect to peer");
+                pending.remove(&addr);
+                continue;
+            }
+            // If we don't match one of these patterns, shutdown.
+            _ => break,
+        };
+
+        for addr in dial {
+            if !pending.insert(addr) {
+                continue;
+            }
+
+            debug!(?addr, "dialing operator peer");
+            connector.ready_and().await?;
+            handshakes.push(connector.call(addr).map_err(move |e| (addr, e)).boxed());
+        }
+    }
+
+    Ok(())
+}
+
 /// Bind to `addr`, listen for peers using `handshaker`, then send the
 /// results over `tx`.
-#[instrument(skip(tx, handshaker))]
+#[instrument(skip(tx, handshaker, rate_limiter))]
 async fn listen<S>(
     addr: SocketAddr,
     mut handshaker: S,
+    mut rate_limiter: RateLimiter,
     tx: mpsc::Sender<PeerChange>,
 ) -> Result<(), BoxedStdError>
 where
-    S: Service<(TcpStream, SocketAddr), Response = peer::Client, Error = BoxedStdError> + Clone,
+    S: Service<
+            (TcpStream, SocketAddr, ConnectionDirection),
+            Response = peer::Client,
+            Error = BoxedStdError,
+        > + Clone,
     S::Future: Send + 'static,
 {
     let mut listener = TcpListener::bind(addr).await?;
     loop {
         if let Ok((tcp_stream, addr)) = listener.accept().await {
+            if !rate_limiter.check(&addr) {
+                // Dropping the stream closes the connection
+                debug!(?addr, "rate limited incoming connection, closing it");
+                continue;
+            }
             debug!(?addr, "got incoming connection");
             handshaker.ready_and().await?;
             // Construct a handshake future but do not drive it yet....
-            let handshake = handshaker.call((tcp_stream, addr));
+            let handshake = handshaker.call((tcp_stream, addr, ConnectionDirection::Inbound));
             // ... instead, spawn a new task to handle this connection
             let mut tx2 = tx.clone();
             tokio::spawn(async move {
@@ -189,22 +363,41 @@ where
     }
 }
 
+/// The result of a successful outbound connection attempt.
+enum Dialed {
+    /// A new peer for the peer set.
+    Peer(Change<SocketAddr, peer::Client>),
+    /// A feeler connection to this candidate succeeded, and was closed.
+    Feeler(MetaAddr),
+}
+
 /// Given a channel that signals a need for new peers, try to connect to a peer
 /// and send the resulting `peer::Client` through a channel.
+///
+/// Each time the crawler runs, it also makes a feeler connection to a
+/// gossiped peer, so that reachable peers are tried before other gossiped
+/// peers.
 #[instrument(skip(
     new_peer_interval,
+    peer_cache_file,
+    rate_limiter,
     demand_tx,
     demand_rx,
     candidates,
     connector,
+    feeler_connector,
     success_tx
 ))]
+#[allow(clippy::too_many_arguments)]
 async fn crawl_and_dial<C, S>(
     new_peer_interval: std::time::Duration,
+    peer_cache_file: Option<PathBuf>,
+    mut rate_limiter: RateLimiter,
     mut demand_tx: mpsc::Sender<()>,
     mut demand_rx: mpsc::Receiver<()>,
     mut candidates: CandidateSet<S>,
     mut connector: C,
+    mut feeler_connector: C,
     mut success_tx: mpsc::Sender<PeerChange>,
 ) -> Result<(), BoxedStdError>
 where
@@ -247,12 +440,20 @@ where
                     continue;
                 }
                 if let Some(candidate) = candidates.next() {
+                    if !rate_limiter.check(&candidate.addr) {
+                        // The crawl timer will send more demand later, so
+                        // we don't need to retry immediately.
+                        debug!(?candidate.addr, "rate limited outbound connection, skipping candidate");
+                        candidates.report_finished(&candidate.addr);
+                        continue;
+                    }
                     debug!(?candidate.addr, "attempting outbound connection in response to demand");
                     connector.ready_and().await?;
                     handshakes.push(
                         connector
                             .call(candidate.addr)
-                            .map_err(move |_| candidate)
+                            .map_ok(Dialed::Peer)
+                            .map_err(move |e| (candidate, e))
                             .boxed(),
                     );
                 } else {
@@ -266,19 +467,59 @@ where
             Left((Right((Some(_timer), _)), _)) => {
                 debug!("crawling for more peers");
                 candidates.update().await?;
+                if let Some(path) = &peer_cache_file {
+                    let peers: Vec<_> = candidates
+                        .peer_set
+                        .lock()
+                        .expect("mutex must be unpoisoned")
+                        .peers()
+                        .collect();
+                    if let Err(e) = peer_cache::save(path, peers) {
+                        warn!(?path, error = %e, "could not save cached peers");
+                    }
+                }
+                if let Some(candidate) = candidates.next_feeler() {
+                    if rate_limiter.check(&candidate.addr) {
+                        debug!(?candidate.addr, "attempting feeler connection");
+                        feeler_connector.ready_and().await?;
+                        handshakes.push(
+                            feeler_connector
+                                .call(candidate.addr)
+                                // Dropping the client closes the connection
+                                .map_ok(move |_client| Dialed::Feeler(candidate))
+                                .map_err(move |e| (candidate, e))
+                                .boxed(),
+                        );
+                    } else {
+                        debug!(?candidate.addr, "rate limited feeler connection, skipping candidate");
+                        candidates.report_finished(&candidate.addr);
+                    }
+                }
                 // Try to connect to a new peer.
                 let _ = demand_tx.try_send(());
             }
-            Right((Some(Ok(change)), _)) => {
+            Right((Some(Ok(Dialed::Peer(change))), _)) => {
                 // in fact all changes are Insert so this branch is always taken
                 if let Change::Insert(ref addr, _) = change {
                     debug!(candidate.addr = ?addr, "successfully dialed new peer");
+                    candidates.report_finished(addr);
                 }
                 success_tx.send(Ok(change)).await?;
             }
-            Right((Some(Err(candidate)), _)) => {
-                debug!(?candidate.addr, "failed to connect to peer");
-                candidates.report_failed(candidate);
+            Right((Some(Ok(Dialed::Feeler(candidate))), _)) => {
+                debug!(?candidate.addr, "feeler connection succeeded");
+                candidates.report_feeler_succeeded(candidate);
+            }
+            Right((Some(Err((candidate, error))), _)) => {
+                let fault = Fault::of(&*error);
+                debug!(?candidate.addr, %error, ?fault, "failed to connect to peer");
+                // Our own errors, like connections to ourselves, shouldn't
+                // count against the candidate.
+                if fault == Some(Fault::Ours) {
+                    candidates.report_finished(&candidate.addr);
+                } else {
+                    candidates.report_failed(candidate);
+                }
                 // The demand signal that was taken out of the queue
                 // to attempt to connect to the failed candidate never
                 // turned into a connection, so add it back:
@@ -0,0 +1,33 @@
+//! IP address prefixes, used to group peer addresses.
+
+use std::net::IpAddr;
+
+/// An IP address prefix, used to group addresses that are likely to be
+/// controlled by the same operator.
+///
+/// Like zcashd's network groups, this uses the first 16 bits of IPv4
+/// addresses, and the first 32 bits of IPv6 addresses.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub(super) enum IpPrefix {
+    V4([u8; 2]),
+    V6([u8; 4]),
+}
+
+impl From<IpAddr> for IpPrefix {
+    fn from(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => {
+                let octets = ip.octets();
+                IpPrefix::V4([octets[0], octets[1]])
+            }
+            // IPv4-mapped addresses are grouped with their IPv4 address
+            IpAddr::V6(ip) => match ip.to_ipv4() {
+                Some(ip) => IpAddr::V4(ip).into(),
+                None => {
+                    let octets = ip.octets();
+                    IpPrefix::V6([octets[0], octets[1], octets[2], octets[3]])
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Rate limits for opening new peer connections.
+
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::IpPrefix;
+use crate::{Config, PeerTrust};
+
+/// Limits the rate of new peer connections, in total and for each IP prefix.
+///
+/// This stops the crawler from repeatedly dialing the same unreachable
+/// peers, and stops inbound connection floods from exhausting our file
+/// descriptors. Trusted peers are not limited, and don't count towards the
+/// limits.
+#[derive(Debug)]
+pub(super) struct RateLimiter {
+    window: Duration,
+    limit: usize,
+    prefix_limit: usize,
+    peer_trust: PeerTrust,
+    /// The times and prefixes of the connections allowed in the current
+    /// window, oldest first.
+    recent: VecDeque<(Instant, IpPrefix)>,
+}
+
+impl RateLimiter {
+    /// Construct a rate limiter using the limits in `config`.
+    pub fn new(config: &Config) -> Self {
+        RateLimiter {
+            window: config.connection_rate_window,
+            limit: config.connection_rate_limit,
+            prefix_limit: config.connection_rate_limit_per_prefix,
+            peer_trust: config.peer_trust.clone(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Returns true if a new connection to `addr` is allowed now, and records
+    /// the connection.
+    pub fn check(&mut self, addr: &SocketAddr) -> bool {
+        self.check_at(addr, Instant::now())
+    }
+
+    fn check_at(&mut self, addr: &SocketAddr, now: Instant) -> bool {
+        if self.peer_trust.is_trusted(addr) {
+            return true;
+        }
+
+        while let Some((time, _)) = self.recent.front() {
+            if now.saturating_duration_since(*time) >= self.window {
+                self.recent.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let prefix = IpPrefix::from(addr.ip());
+        let prefix_count = self.recent.iter().filter(|(_, p)| *p == prefix).count();
+        if self.recent.len() >= self.limit || prefix_count >= self.prefix_limit {
+            metrics::counter!("pool.rate_limited_connections", 1);
+            return false;
+        }
+
+        self.recent.push_back((now, prefix));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::TrustLevel;
+
+    fn limiter(limit: usize, prefix_limit: usize) -> RateLimiter {
+        RateLimiter::new(&Config {
+            connection_rate_limit: limit,
+            connection_rate_limit_per_prefix: prefix_limit,
+            connection_rate_window: Duration::from_secs(60),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn limits_each_prefix() {
+        zebra_test::init();
+
+        let mut limiter = limiter(10, 2);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(&"1.2.3.4:8233".parse().unwrap(), now));
+        assert!(limiter.check_at(&"1.2.5.6:8233".parse().unwrap(), now));
+        // Same /16 prefix, including as an IPv4-mapped address
+        assert!(!limiter.check_at(&"1.2.7.8:8233".parse().unwrap(), now));
+        assert!(!limiter.check_at(&"[::ffff:1.2.7.8]:8233".parse().unwrap(), now));
+        // Different prefixes
+        assert!(limiter.check_at(&"1.3.3.4:8233".parse().unwrap(), now));
+        assert!(limiter.check_at(&"[2001:db8::1]:8233".parse().unwrap(), now));
+        assert!(limiter.check_at(&"[2001:db8:1::1]:8233".parse().unwrap(), now));
+        assert!(!limiter.check_at(&"[2001:db8:2::1]:8233".parse().unwrap(), now));
+
+        // The window has passed
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.check_at(&"1.2.7.8:8233".parse().unwrap(), later));
+    }
+
+    #[test]
+    fn limits_total() {
+        zebra_test::init();
+
+        let mut limiter = limiter(2, 2);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(&"1.1.1.1:8233".parse().unwrap(), now));
+        assert!(limiter.check_at(&"2.2.2.2:8233".parse().unwrap(), now));
+        assert!(!limiter.check_at(&"3.3.3.3:8233".parse().unwrap(), now));
+
+        let later = now + Duration::from_secs(30);
+        assert!(!limiter.check_at(&"3.3.3.3:8233".parse().unwrap(), later));
+        let later = now + Duration::from_secs(61);
+        assert!(limiter.check_at(&"3.3.3.3:8233".parse().unwrap(), later));
+    }
+
+    #[test]
+    fn trusted_peers_are_not_limited() {
+        zebra_test::init();
+
+        let mut limiter = RateLimiter::new(&Config {
+            connection_rate_limit: 1,
+            connection_rate_limit_per_prefix: 1,
+            connection_rate_window: Duration::from_secs(60),
+            peer_trust: PeerTrust(
+                vec![("1.1.1.1".parse().unwrap(), TrustLevel::Trusted)]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Config::default()
+        });
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at(&"1.1.1.1:8233".parse().unwrap(), now));
+        }
+        // Trusted connections don't use up the limit
+        assert!(limiter.check_at(&"2.2.2.2:8233".parse().unwrap(), now));
+        assert!(!limiter.check_at(&"3.3.3.3:8233".parse().unwrap(), now));
+    }
+}
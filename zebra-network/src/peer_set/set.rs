@@ -4,7 +4,9 @@ use std::{
     fmt::Debug,
     future::Future,
     marker::PhantomData,
+    net::SocketAddr,
     pin::Pin,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 
@@ -24,7 +26,7 @@ use tower_load::Load;
 
 use crate::{
     protocol::internal::{Request, Response},
-    BoxedStdError,
+    AddressBook, BoxedStdError, Fault, PeerTrust,
 };
 
 use super::unready_service::{Error as UnreadyError, UnreadyService};
@@ -88,12 +90,17 @@ where
     /// These guards are checked for errors as part of `poll_ready` which lets
     /// the `PeerSet` propagate errors from background tasks back to the user
     guards: futures::stream::FuturesUnordered<JoinHandle<Result<(), BoxedStdError>>>,
+    /// The address book, which holds diagnostic information about our peer
+    /// connections.
+    address_book: Arc<Mutex<AddressBook>>,
+    /// The trust levels of peer addresses, used to prefer trusted peers for
+    /// block downloads.
+    peer_trust: PeerTrust,
 }
 
 impl<D> PeerSet<D>
 where
-    D: Discover + Unpin,
-    D::Key: Clone + Debug,
+    D: Discover<Key = SocketAddr> + Unpin,
     D::Service: Service<Request, Response = Response> + Load,
     D::Error: Into<BoxedStdError>,
     <D::Service as Service<Request>>::Error: Into<BoxedStdError> + 'static,
@@ -105,6 +112,8 @@ where
         discover: D,
         demand_signal: mpsc::Sender<()>,
         handle_rx: tokio::sync::oneshot::Receiver<Vec<JoinHandle<Result<(), BoxedStdError>>>>,
+        address_book: Arc<Mutex<AddressBook>>,
+        peer_trust: PeerTrust,
     ) -> Self {
         Self {
             discover,
@@ -115,6 +124,8 @@ where
             demand_signal,
             guards: futures::stream::FuturesUnordered::new(),
             handle_rx,
+            address_book,
+            peer_trust,
         }
     }
 
@@ -213,7 +224,8 @@ where
                 }
                 Poll::Ready(Some(Err((key, UnreadyError::Inner(e))))) => {
                     let error = e.into();
-                    debug!(%error, "service failed while unready, dropped");
+                    let fault = record_dropped_peer(&error);
+                    debug!(%error, ?fault, "service failed while unready, dropped");
                     let _cancel = self.cancel_handles.remove(&key);
                     assert!(_cancel.is_some(), "missing cancel handle");
                 }
@@ -245,6 +257,25 @@ where
         }
     }
 
+    /// Returns the index of the least loaded ready trusted peer, if there is
+    /// one.
+    fn select_trusted_ready_index(&self) -> Option<usize> {
+        let mut best: Option<(usize, <D::Service as Load>::Metric)> = None;
+        for (index, (key, svc)) in self.ready_services.iter().enumerate() {
+            if !self.peer_trust.is_trusted(key) {
+                continue;
+            }
+
+            let load = svc.load();
+            match best {
+                Some((_, ref best_load)) if *best_load <= load => {}
+                _ => best = Some((index, load)),
+            }
+        }
+
+        best.map(|(index, _)| index)
+    }
+
     /// Accesses a ready endpoint by index and returns its current load.
     fn ready_index_load(&self, index: usize) -> <D::Service as Load>::Metric {
         let (_, svc) = self.ready_services.get_index(index).expect("invalid index");
@@ -252,10 +283,23 @@ where
     }
 }
 
+/// Classifies the `error` that caused a peer to be dropped, and records it in
+/// the peer set metrics.
+fn record_dropped_peer(error: &BoxedStdError) -> Option<Fault> {
+    let fault = Fault::of(&**error);
+    let label = match fault {
+        Some(Fault::Ours) => "ours",
+        Some(Fault::Peer) => "peer",
+        Some(Fault::Transient) => "transient",
+        None => "unknown",
+    };
+    metrics::counter!("pool.dropped_peers", 1, "fault" => label);
+    fault
+}
+
 impl<D> Service<Request> for PeerSet<D>
 where
-    D: Discover + Unpin,
-    D::Key: Clone + Debug + ToString,
+    D: Discover<Key = SocketAddr> + Unpin,
     D::Service: Service<Request, Response = Response> + Load,
     D::Error: Into<BoxedStdError>,
     <D::Service as Service<Request>>::Error: Into<BoxedStdError> + 'static,
@@ -305,7 +349,8 @@ where
                     }
                     Poll::Ready(Err(e)) => {
                         let error = e.into();
-                        trace!(%error, "preselected service failed, dropping it");
+                        let fault = record_dropped_peer(&error);
+                        trace!(%error, ?fault, "preselected service failed, dropping it");
                         self.ready_services
                             .swap_remove_index(index)
                             .expect("preselected index must be valid");
@@ -325,6 +370,55 @@ where
     }
 
     fn call(&mut self, req: Request) -> Self::Future {
+        // Peer info is answered from the address book, so it doesn't use the
+        // preselected peer.
+        if let Request::PeerInfo = req {
+            let peers = self
+                .address_book
+                .lock()
+                .expect("mutex should be unpoisoned")
+                .connected_peers()
+                .collect();
+            return future::ready(Ok(Response::PeerInfo(peers))).boxed();
+        }
+
+        // Transactions are gossiped to every ready peer, so they don't use
+        // the preselected peer either.
+        if let Request::PushTransaction(transaction) = req {
+            self.next_idx = None;
+
+            let ready_services: Vec<_> = self.ready_services.drain(..).collect();
+            let mut sends = Vec::with_capacity(ready_services.len());
+            for (key, mut svc) in ready_services {
+                sends.push(
+                    svc.call(Request::PushTransaction(transaction.clone()))
+                        .map_err(Into::<BoxedStdError>::into),
+                );
+                self.push_unready(key, svc);
+            }
+
+            return async move {
+                let mut error: Option<BoxedStdError> = None;
+                for result in future::join_all(sends).await {
+                    match result {
+                        Ok(_) => return Ok(Response::Nil),
+                        Err(e) => error = Some(e),
+                    }
+                }
+                Err(error.expect("the peer set is ready, so it has at least one peer"))
+            }
+            .boxed();
+        }
+
+        // Trusted peers are preferred for block downloads, because they are
+        // usually the operator's own nodes
+        if let Request::BlocksByHash(_) = req {
+            if let Some(index) = self.select_trusted_ready_index() {
+                trace!(index, "selected trusted service for block download");
+                self.next_idx = Some(index);
+            }
+        }
+
         let index = self
             .next_idx
             .take()
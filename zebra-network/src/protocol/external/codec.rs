@@ -9,9 +9,10 @@ use chrono::{TimeZone, Utc};
 use tokio_util::codec::{Decoder, Encoder};
 
 use zebra_chain::{
-    block::{Block, BlockHeaderHash},
+    block::{Block, BlockHeader, BlockHeaderHash},
     serialization::{
-        ReadZcashExt, SerializationError as Error, WriteZcashExt, ZcashDeserialize, ZcashSerialize,
+        CompactSize, ReadZcashExt, SerializationError as Error, WriteZcashExt, ZcashDeserialize,
+        ZcashSerialize, MAX_PROTOCOL_MESSAGE_LEN,
     },
     transaction::Transaction,
     types::{BlockHeight, Sha256dChecksum},
@@ -21,6 +22,7 @@ use zebra_chain::{
 use crate::constants;
 
 use super::{
+    inv::InventoryHash,
     message::{Message, RejectReason},
     types::*,
 };
@@ -28,9 +30,6 @@ use super::{
 /// The length of a Bitcoin message header.
 const HEADER_LEN: usize = 24usize;
 
-/// Maximum size of a protocol message body.
-const MAX_PROTOCOL_MESSAGE_LEN: usize = 2 * 1024 * 1024;
-
 /// A codec which produces Bitcoin messages from byte streams and vice versa.
 pub struct Codec {
     builder: Builder,
@@ -99,11 +98,17 @@ impl Encoder for Codec {
 
     fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
         use Error::Parse;
+        use Message::*;
+
         // XXX(HACK): this is inefficient and does an extra allocation.
         // instead, we should have a size estimator for the message, reserve
         // that much space, write the header (with zeroed checksum), then the body,
         // then write the computed checksum in-place.  for now, just do an extra alloc.
 
+        if let Inv(hashes) | GetData(hashes) | NotFound(hashes) = &item {
+            self.check_inventory_version(hashes)?;
+        }
+
         let mut body = Vec::new();
         self.write_body(&item, &mut body)?;
 
@@ -111,7 +116,6 @@ impl Encoder for Codec {
             return Err(Parse("body length exceeded maximum size"));
         }
 
-        use Message::*;
         // Note: because all match arms must have
         // the same type, and the array length is
         // part of the type, having at least one
@@ -137,6 +141,11 @@ impl Encoder for Codec {
             FilterLoad { .. } => b"filterload\0\0",
             FilterAdd { .. } => b"filteradd\0\0\0",
             FilterClear { .. } => b"filterclear\0",
+            MerkleBlock { .. } => b"merkleblock\0",
+            GetCFilters { .. } => b"getcfilters\0",
+            CFilter { .. } => b"cfilter\0\0\0\0\0",
+            GetCFHeaders { .. } => b"getcfheaders",
+            CFHeaders { .. } => b"cfheaders\0\0\0",
         };
         trace!(?item, len = body.len());
 
@@ -158,6 +167,23 @@ impl Encoder for Codec {
 }
 
 impl Codec {
+    /// Check that `hashes` can be sent to a peer using the negotiated
+    /// version.
+    ///
+    /// `MSG_WTX` items must only be sent to peers that support ZIP-239,
+    /// because older peers can't parse their 64-byte hashes.
+    fn check_inventory_version(&self, hashes: &[InventoryHash]) -> Result<(), Error> {
+        let has_wtx = hashes
+            .iter()
+            .any(|hash| matches!(hash, InventoryHash::Wtx(_)));
+        if has_wtx && self.builder.version < constants::WTX_RELAY_VERSION {
+            return Err(Error::Parse(
+                "MSG_WTX inventory is not supported by the negotiated version",
+            ));
+        }
+        Ok(())
+    }
+
     /// Write the body of the message into the given writer. This allows writing
     /// the message body prior to writing the header, so that the header can
     /// contain a checksum of the message body.
@@ -186,17 +212,17 @@ impl Codec {
                 writer.write_u64::<LittleEndian>(from_services.bits())?;
                 writer.write_socket_addr(*from_addr)?;
 
-                writer.write_u64::<LittleEndian>(nonce.0)?;
+                nonce.zcash_serialize(&mut writer)?;
                 writer.write_string(&user_agent)?;
                 writer.write_u32::<LittleEndian>(start_height.0)?;
                 writer.write_u8(*relay as u8)?;
             }
             Message::Verack => { /* Empty payload -- no-op */ }
             Message::Ping(nonce) => {
-                writer.write_u64::<LittleEndian>(nonce.0)?;
+                nonce.zcash_serialize(&mut writer)?;
             }
             Message::Pong(nonce) => {
-                writer.write_u64::<LittleEndian>(nonce.0)?;
+                nonce.zcash_serialize(&mut writer)?;
             }
             Message::Reject {
                 message,
@@ -207,7 +233,9 @@ impl Codec {
                 writer.write_string(&message)?;
                 writer.write_u8(*ccode as u8)?;
                 writer.write_string(&reason)?;
-                writer.write_all(&data.unwrap())?;
+                if let Some(data) = data {
+                    writer.write_all(data)?;
+                }
             }
             Message::Addr(addrs) => addrs.zcash_serialize(&mut writer)?,
             Message::GetAddr => { /* Empty payload -- no-op */ }
@@ -242,13 +270,66 @@ impl Codec {
             } => {
                 writer.write_all(&filter.0)?;
                 writer.write_u32::<LittleEndian>(*hash_functions_count)?;
-                writer.write_u32::<LittleEndian>(tweak.0)?;
+                tweak.zcash_serialize(&mut writer)?;
                 writer.write_u8(*flags)?;
             }
             Message::FilterAdd { data } => {
                 writer.write_all(data)?;
             }
             Message::FilterClear => { /* Empty payload -- no-op */ }
+            Message::MerkleBlock {
+                header,
+                total_transactions,
+                hashes,
+                flags,
+            } => {
+                header.zcash_serialize(&mut writer)?;
+                writer.write_u32::<LittleEndian>(*total_transactions)?;
+                CompactSize::from_len(hashes.len())?.zcash_serialize(&mut writer)?;
+                for hash in hashes {
+                    writer.write_all(hash)?;
+                }
+                CompactSize::from_len(flags.len())?.zcash_serialize(&mut writer)?;
+                writer.write_all(flags)?;
+            }
+            Message::GetCFilters {
+                filter_type,
+                start_height,
+                stop_hash,
+            }
+            | Message::GetCFHeaders {
+                filter_type,
+                start_height,
+                stop_hash,
+            } => {
+                writer.write_u8(*filter_type)?;
+                writer.write_u32::<LittleEndian>(start_height.0)?;
+                stop_hash.zcash_serialize(&mut writer)?;
+            }
+            Message::CFilter {
+                filter_type,
+                block_hash,
+                filter,
+            } => {
+                writer.write_u8(*filter_type)?;
+                block_hash.zcash_serialize(&mut writer)?;
+                CompactSize::from_len(filter.len())?.zcash_serialize(&mut writer)?;
+                writer.write_all(filter)?;
+            }
+            Message::CFHeaders {
+                filter_type,
+                stop_hash,
+                previous_filter_header,
+                filter_hashes,
+            } => {
+                writer.write_u8(*filter_type)?;
+                stop_hash.zcash_serialize(&mut writer)?;
+                writer.write_all(previous_filter_header)?;
+                CompactSize::from_len(filter_hashes.len())?.zcash_serialize(&mut writer)?;
+                for hash in filter_hashes {
+                    writer.write_all(hash)?;
+                }
+            }
         }
         Ok(())
     }
@@ -379,6 +460,11 @@ impl Decoder for Codec {
                     b"filterload\0\0" => self.read_filterload(body_reader, body_len),
                     b"filteradd\0\0\0" => self.read_filteradd(body_reader),
                     b"filterclear\0" => self.read_filterclear(body_reader),
+                    b"merkleblock\0" => self.read_merkleblock(body_reader),
+                    b"getcfilters\0" => self.read_getcfilters(body_reader),
+                    b"cfilter\0\0\0\0\0" => self.read_cfilter(body_reader),
+                    b"getcfheaders" => self.read_getcfheaders(body_reader),
+                    b"cfheaders\0\0\0" => self.read_cfheaders(body_reader),
                     _ => return Err(Parse("unknown command")),
                 }
                 // We need Ok(Some(msg)) to signal that we're done decoding.
@@ -407,7 +493,7 @@ impl Codec {
                 PeerServices::from_bits_truncate(reader.read_u64::<LittleEndian>()?),
                 reader.read_socket_addr()?,
             ),
-            nonce: Nonce(reader.read_u64::<LittleEndian>()?),
+            nonce: Nonce::zcash_deserialize(&mut reader)?,
             user_agent: reader.read_string()?,
             start_height: BlockHeight(reader.read_u32::<LittleEndian>()?),
             relay: match reader.read_u8()? {
@@ -423,11 +509,11 @@ impl Codec {
     }
 
     fn read_ping<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
-        Ok(Message::Ping(Nonce(reader.read_u64::<LittleEndian>()?)))
+        Ok(Message::Ping(Nonce::zcash_deserialize(&mut reader)?))
     }
 
     fn read_pong<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
-        Ok(Message::Pong(Nonce(reader.read_u64::<LittleEndian>()?)))
+        Ok(Message::Pong(Nonce::zcash_deserialize(&mut reader)?))
     }
 
     fn read_reject<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
@@ -446,7 +532,22 @@ impl Codec {
                 _ => return Err(Error::Parse("invalid RejectReason value in ccode field")),
             },
             reason: reader.read_string()?,
-            data: Some(reader.read_32_bytes()?),
+            // Only rejects for some message types have extra data, and the
+            // message doesn't say whether it's present, so we check the
+            // remaining length instead.
+            data: {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                match data.len() {
+                    0 => None,
+                    32 => {
+                        let mut hash = [0; 32];
+                        hash.copy_from_slice(&data);
+                        Some(hash)
+                    }
+                    _ => return Err(Error::Parse("invalid extra data length in reject")),
+                }
+            },
         })
     }
 
@@ -531,16 +632,22 @@ impl Codec {
         Ok(Message::FilterLoad {
             filter: Filter(filter_bytes),
             hash_functions_count: reader.read_u32::<LittleEndian>()?,
-            tweak: Tweak(reader.read_u32::<LittleEndian>()?),
+            tweak: Tweak::zcash_deserialize(&mut reader)?,
             flags: reader.read_u8()?,
         })
     }
 
     fn read_filteradd<R: Read>(&self, reader: R) -> Result<Message, Error> {
-        let mut bytes = Vec::new();
+        const MAX_FILTERADD_LENGTH: u64 = 520;
 
-        // Maximum size of data is 520 bytes.
-        reader.take(520).read_exact(&mut bytes)?;
+        let mut bytes = Vec::new();
+        // Read one extra byte, so we can detect oversized data.
+        reader
+            .take(MAX_FILTERADD_LENGTH + 1)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > MAX_FILTERADD_LENGTH {
+            return Err(Error::Parse("filteradd data exceeded maximum size"));
+        }
 
         Ok(Message::FilterAdd { data: bytes })
     }
@@ -548,6 +655,95 @@ impl Codec {
     fn read_filterclear<R: Read>(&self, mut _reader: R) -> Result<Message, Error> {
         Ok(Message::FilterClear)
     }
+
+    fn read_merkleblock<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
+        let header = BlockHeader::zcash_deserialize(&mut reader)?;
+        let total_transactions = reader.read_u32::<LittleEndian>()?;
+
+        // Don't preallocate using the untrusted counts, the reads will fail
+        // at the end of the message body.
+        let hash_count: u64 = CompactSize::zcash_deserialize(&mut reader)?.into();
+        if hash_count > (MAX_PROTOCOL_MESSAGE_LEN / 32) as u64 {
+            return Err(Error::Parse("merkleblock has too many hashes"));
+        }
+        let mut hashes = Vec::new();
+        for _ in 0..hash_count {
+            hashes.push(reader.read_32_bytes()?);
+        }
+
+        let flag_count: u64 = CompactSize::zcash_deserialize(&mut reader)?.into();
+        let mut flags = Vec::new();
+        (&mut reader).take(flag_count).read_to_end(&mut flags)?;
+        if flags.len() as u64 != flag_count {
+            return Err(Error::Parse("merkleblock flags were truncated"));
+        }
+
+        Ok(Message::MerkleBlock {
+            header,
+            total_transactions,
+            hashes,
+            flags,
+        })
+    }
+
+    fn read_getcfilters<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
+        Ok(Message::GetCFilters {
+            filter_type: reader.read_u8()?,
+            start_height: BlockHeight(reader.read_u32::<LittleEndian>()?),
+            stop_hash: BlockHeaderHash::zcash_deserialize(&mut reader)?,
+        })
+    }
+
+    fn read_cfilter<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
+        let filter_type = reader.read_u8()?;
+        let block_hash = BlockHeaderHash::zcash_deserialize(&mut reader)?;
+
+        let filter_len: u64 = CompactSize::zcash_deserialize(&mut reader)?.into();
+        let mut filter = Vec::new();
+        (&mut reader).take(filter_len).read_to_end(&mut filter)?;
+        if filter.len() as u64 != filter_len {
+            return Err(Error::Parse("cfilter filter was truncated"));
+        }
+
+        Ok(Message::CFilter {
+            filter_type,
+            block_hash,
+            filter,
+        })
+    }
+
+    fn read_getcfheaders<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
+        Ok(Message::GetCFHeaders {
+            filter_type: reader.read_u8()?,
+            start_height: BlockHeight(reader.read_u32::<LittleEndian>()?),
+            stop_hash: BlockHeaderHash::zcash_deserialize(&mut reader)?,
+        })
+    }
+
+    fn read_cfheaders<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
+        /// The maximum number of filter hashes in a `cfheaders` message.
+        const MAX_CFHEADERS_HASHES: u64 = 2000;
+
+        let filter_type = reader.read_u8()?;
+        let stop_hash = BlockHeaderHash::zcash_deserialize(&mut reader)?;
+        let previous_filter_header = reader.read_32_bytes()?;
+
+        let hash_count: u64 = CompactSize::zcash_deserialize(&mut reader)?.into();
+        if hash_count > MAX_CFHEADERS_HASHES {
+            return Err(Error::Parse("cfheaders contained too many filter hashes"));
+        }
+        let mut filter_hashes = Vec::with_capacity(hash_count as usize);
+        for _ in 0..hash_count {
+            filter_hashes.push(reader.read_32_bytes()?);
+        }
+
+        Ok(Message::CFHeaders {
+            filter_type,
+            stop_hash,
+            previous_filter_header,
+            filter_hashes,
+        })
+    }
 }
 
 // XXX replace these interior unit tests with exterior integration tests + proptest
@@ -672,6 +868,185 @@ mod tests {
         });
     }
 
+    /// Serialize and deserialize `msg` using the default codec.
+    fn round_trip(rt: &mut Runtime, msg: &Message) -> Result<Message, Error> {
+        use tokio_util::codec::{FramedRead, FramedWrite};
+        let bytes = rt.block_on(async {
+            let mut bytes = Vec::new();
+            {
+                let mut fw = FramedWrite::new(&mut bytes, Codec::builder().finish());
+                fw.send(msg.clone())
+                    .await
+                    .expect("message should be serialized");
+            }
+            bytes
+        });
+
+        rt.block_on(async {
+            let mut fr = FramedRead::new(Cursor::new(&bytes), Codec::builder().finish());
+            fr.next().await.expect("a next message should be available")
+        })
+    }
+
+    #[test]
+    fn reject_message_round_trip() {
+        zebra_test::init();
+        let mut rt = Runtime::new().unwrap();
+
+        // Rejects for version messages have no extra data
+        let v = Message::Reject {
+            message: "version".to_owned(),
+            ccode: RejectReason::Obsolete,
+            reason: "Version must be 170011 or greater".to_owned(),
+            data: None,
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+
+        // Rejects for transactions have the transaction hash
+        let v = Message::Reject {
+            message: "tx".to_owned(),
+            ccode: RejectReason::InsufficientFee,
+            reason: "insufficient fee".to_owned(),
+            data: Some([7; 32]),
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+    }
+
+    #[test]
+    fn filteradd_message_round_trip() {
+        zebra_test::init();
+        let mut rt = Runtime::new().unwrap();
+
+        let v = Message::FilterAdd { data: vec![1; 520] };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+
+        let v = Message::FilterAdd { data: vec![1; 521] };
+        round_trip(&mut rt, &v).expect_err("oversized filteradd should not deserialize");
+    }
+
+    #[test]
+    fn oversized_inventory_lists_are_rejected() {
+        use zebra_chain::transaction::TransactionHash;
+        zebra_test::init();
+        let mut rt = Runtime::new().unwrap();
+
+        let max = crate::constants::MAX_INV_IN_MESSAGE as usize;
+        let hashes = vec![InventoryHash::Tx(TransactionHash([1; 32])); max + 1];
+
+        let v = Message::Inv(hashes[..max].to_vec());
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+
+        let v = Message::Inv(hashes.clone());
+        round_trip(&mut rt, &v).expect_err("oversized inv should not deserialize");
+
+        let v = Message::GetData(hashes);
+        round_trip(&mut rt, &v).expect_err("oversized getdata should not deserialize");
+    }
+
+    #[test]
+    fn merkleblock_message_round_trip() {
+        use zebra_chain::serialization::ZcashDeserializeInto;
+        zebra_test::init();
+        let mut rt = Runtime::new().unwrap();
+
+        let header: BlockHeader = zebra_test::vectors::HEADER_MAINNET_415000_BYTES
+            .zcash_deserialize_into()
+            .unwrap();
+        let v = Message::MerkleBlock {
+            header,
+            total_transactions: 3,
+            hashes: vec![[1; 32], [2; 32]],
+            flags: vec![0b1101],
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+    }
+
+    #[test]
+    fn compact_filter_messages_round_trip() {
+        zebra_test::init();
+        let mut rt = Runtime::new().unwrap();
+
+        let v = Message::GetCFilters {
+            filter_type: 0,
+            start_height: BlockHeight(1),
+            stop_hash: BlockHeaderHash([1; 32]),
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+
+        let v = Message::CFilter {
+            filter_type: 0,
+            block_hash: BlockHeaderHash([1; 32]),
+            filter: vec![2, 3, 4],
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+
+        let v = Message::GetCFHeaders {
+            filter_type: 0,
+            start_height: BlockHeight(1),
+            stop_hash: BlockHeaderHash([1; 32]),
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+
+        let v = Message::CFHeaders {
+            filter_type: 0,
+            stop_hash: BlockHeaderHash([1; 32]),
+            previous_filter_header: [2; 32],
+            filter_hashes: vec![[3; 32], [4; 32]],
+        };
+        assert_eq!(round_trip(&mut rt, &v).unwrap(), v);
+    }
+
+    #[test]
+    fn wtx_inventory_version_gating() {
+        use tokio_util::codec::{FramedRead, FramedWrite};
+        use zebra_chain::transaction::{AuthDigest, TransactionHash, WtxId};
+        zebra_test::init();
+        let mut rt = Runtime::new().unwrap();
+
+        let v = Message::Inv(vec![
+            InventoryHash::Tx(TransactionHash([1; 32])),
+            InventoryHash::Wtx(WtxId {
+                id: TransactionHash([2; 32]),
+                auth_digest: AuthDigest([3; 32]),
+            }),
+        ]);
+
+        // Peers without ZIP-239 support can't parse MSG_WTX
+        rt.block_on(async {
+            let mut bytes = Vec::new();
+            let mut fw = FramedWrite::new(&mut bytes, Codec::builder().finish());
+            fw.send(v.clone())
+                .await
+                .expect_err("MSG_WTX should not be sent to old peers");
+        });
+
+        let codec = || {
+            Codec::builder()
+                .for_version(crate::constants::WTX_RELAY_VERSION)
+                .finish()
+        };
+        let v_bytes = rt.block_on(async {
+            let mut bytes = Vec::new();
+            {
+                let mut fw = FramedWrite::new(&mut bytes, codec());
+                fw.send(v.clone())
+                    .await
+                    .expect("message should be serialized");
+            }
+            bytes
+        });
+
+        let v_parsed = rt.block_on(async {
+            let mut fr = FramedRead::new(Cursor::new(&v_bytes), codec());
+            fr.next()
+                .await
+                .expect("a next message should be available")
+                .expect("that message should deserialize")
+        });
+
+        assert_eq!(v, v_parsed);
+    }
+
     #[test]
     fn decode_state_debug() {
         assert_eq!(format!("{:?}", DecodeState::Head), "DecodeState::Head");
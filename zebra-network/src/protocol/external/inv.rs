@@ -10,9 +10,9 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use zebra_chain::block::BlockHeaderHash;
 use zebra_chain::serialization::{
-    ReadZcashExt, SerializationError, ZcashDeserialize, ZcashSerialize,
+    ReadZcashExt, SerializationError, TrustedPreallocate, ZcashDeserialize, ZcashSerialize,
 };
-use zebra_chain::transaction::TransactionHash;
+use zebra_chain::transaction::{AuthDigest, TransactionHash, WtxId};
 
 /// An inventory hash which refers to some advertised or requested data.
 ///
@@ -38,6 +38,15 @@ pub enum InventoryHash {
     /// rather than a block message; this only works if a bloom filter has been
     /// set.
     FilteredBlock(BlockHeaderHash),
+    /// A wide transaction ID, which commits to a transaction and its
+    /// authorizing data.
+    ///
+    /// Defined in [ZIP-239] for v5 transactions. Unlike other inventory
+    /// items, the hash is 64 bytes long, so it must only be sent to peers
+    /// that support ZIP-239.
+    ///
+    /// [ZIP-239]: https://zips.z.cash/zip-0239
+    Wtx(WtxId),
 }
 
 impl From<TransactionHash> for InventoryHash {
@@ -54,6 +63,12 @@ impl From<BlockHeaderHash> for InventoryHash {
     }
 }
 
+impl From<WtxId> for InventoryHash {
+    fn from(wtx_id: WtxId) -> InventoryHash {
+        InventoryHash::Wtx(wtx_id)
+    }
+}
+
 impl ZcashSerialize for InventoryHash {
     fn zcash_serialize<W: Write>(&self, mut writer: W) -> Result<(), std::io::Error> {
         let (code, bytes) = match *self {
@@ -61,9 +76,13 @@ impl ZcashSerialize for InventoryHash {
             InventoryHash::Tx(hash) => (1, hash.0),
             InventoryHash::Block(hash) => (2, hash.0),
             InventoryHash::FilteredBlock(hash) => (3, hash.0),
+            InventoryHash::Wtx(wtx_id) => (5, wtx_id.id.0),
         };
         writer.write_u32::<LittleEndian>(code)?;
         writer.write_all(&bytes)?;
+        if let InventoryHash::Wtx(wtx_id) = self {
+            writer.write_all(&wtx_id.auth_digest.0)?;
+        }
         Ok(())
     }
 }
@@ -77,7 +96,19 @@ impl ZcashDeserialize for InventoryHash {
             1 => Ok(InventoryHash::Tx(TransactionHash(bytes))),
             2 => Ok(InventoryHash::Block(BlockHeaderHash(bytes))),
             3 => Ok(InventoryHash::FilteredBlock(BlockHeaderHash(bytes))),
+            5 => Ok(InventoryHash::Wtx(WtxId {
+                id: TransactionHash(bytes),
+                auth_digest: AuthDigest(reader.read_32_bytes()?),
+            })),
             _ => Err(SerializationError::Parse("invalid inventory code")),
         }
     }
 }
+
+impl TrustedPreallocate for InventoryHash {
+    fn max_allocation() -> u64 {
+        // This limit is lower than the number of 36-byte inventory hashes
+        // that fit in a message.
+        crate::constants::MAX_INV_IN_MESSAGE
+    }
+}
@@ -110,12 +110,12 @@ pub enum Message {
         reason: String,
 
         /// Optional extra data provided for some errors.
+        ///
+        /// Rejects for `tx` and `block` messages contain the hash of the
+        /// rejected object. Other rejects have no extra data.
         // Currently, all errors which provide this field fill it with
         // the TXID or block header hash of the object being rejected,
         // so the field is 32 bytes.
-        //
-        // Q: can we tell Rust that this field is optional? Or just
-        // default its value to an empty array, I guess.
         data: Option<[u8; 32]>,
     },
 
@@ -292,6 +292,110 @@ pub enum Message {
     /// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#filterload.2C_filteradd.2C_filterclear.2C_merkleblock)
     /// [BIP37]: https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki
     FilterClear,
+
+    /// A `merkleblock` message.
+    ///
+    /// This was defined in [BIP37], which is included in Zcash. Zebra does
+    /// not send `filterload` messages, so any `merkleblock` it receives is
+    /// unsolicited.
+    ///
+    /// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#filterload.2C_filteradd.2C_filterclear.2C_merkleblock)
+    /// [BIP37]: https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki
+    MerkleBlock {
+        /// The header of the filtered block.
+        header: BlockHeader,
+
+        /// The number of transactions in the block, including unmatched
+        /// transactions.
+        total_transactions: u32,
+
+        /// Transaction hashes and intermediate merkle tree hashes, in
+        /// depth-first order.
+        hashes: Vec<[u8; 32]>,
+
+        /// Flag bits, packed per 8 in a byte, least significant bit first,
+        /// which describe how to traverse the partial merkle tree.
+        flags: Vec<u8>,
+    },
+
+    /// A `getcfilters` message.
+    ///
+    /// Requests compact filters for a range of blocks, ending at `stop_hash`.
+    ///
+    /// This was defined in [BIP157].
+    ///
+    /// [BIP157]: https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki
+    GetCFilters {
+        /// The type of filter being requested.
+        filter_type: u8,
+
+        /// The height of the first block in the requested range.
+        start_height: BlockHeight,
+
+        /// The hash of the last block in the requested range.
+        stop_hash: BlockHeaderHash,
+    },
+
+    /// A `cfilter` message.
+    ///
+    /// Contains the compact filter for a single block, in response to a
+    /// `getcfilters` message.
+    ///
+    /// This was defined in [BIP157].
+    ///
+    /// [BIP157]: https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki
+    CFilter {
+        /// The type of the filter.
+        filter_type: u8,
+
+        /// The hash of the block the filter was computed from.
+        block_hash: BlockHeaderHash,
+
+        /// The serialized compact filter.
+        filter: Vec<u8>,
+    },
+
+    /// A `getcfheaders` message.
+    ///
+    /// Requests compact filter hashes for a range of blocks, ending at
+    /// `stop_hash`.
+    ///
+    /// This was defined in [BIP157].
+    ///
+    /// [BIP157]: https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki
+    GetCFHeaders {
+        /// The type of filter being requested.
+        filter_type: u8,
+
+        /// The height of the first block in the requested range.
+        start_height: BlockHeight,
+
+        /// The hash of the last block in the requested range.
+        stop_hash: BlockHeaderHash,
+    },
+
+    /// A `cfheaders` message.
+    ///
+    /// Contains compact filter hashes for a range of blocks, in response to a
+    /// `getcfheaders` message. Clients chain the hashes onto
+    /// `previous_filter_header` to derive and check the filter headers.
+    ///
+    /// This was defined in [BIP157].
+    ///
+    /// [BIP157]: https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki
+    CFHeaders {
+        /// The type of the filters.
+        filter_type: u8,
+
+        /// The hash of the last block in the range.
+        stop_hash: BlockHeaderHash,
+
+        /// The filter header of the block before the range.
+        previous_filter_header: [u8; 32],
+
+        /// The filter hashes of the blocks in the range, in height order.
+        filter_hashes: Vec<[u8; 32]>,
+    },
 }
 
 impl<E> From<E> for Message
@@ -4,6 +4,8 @@ use crate::constants::magics;
 
 use std::fmt;
 
+use zebra_chain::parameters::testnet_parameters;
+use zebra_chain::serialization::{ZcashDeserialize, ZcashSerialize};
 use zebra_chain::types::BlockHeight;
 use zebra_chain::Network::{self, *};
 use zebra_consensus::parameters::NetworkUpgrade::{self, *};
@@ -24,7 +26,16 @@ impl fmt::Debug for Magic {
 
 impl From<Network> for Magic {
     /// Get the magic value associated to this `Network`.
+    ///
+    /// Uses the configured Testnet magic, if there is one.
     fn from(network: Network) -> Self {
+        if let (Testnet, Some(magic)) = (
+            network,
+            testnet_parameters().and_then(|parameters| parameters.magic),
+        ) {
+            return Magic(magic);
+        }
+
         match network {
             Network::Mainnet => magics::MAINNET,
             Network::Testnet => magics::TESTNET,
@@ -53,6 +64,10 @@ impl Version {
             (Mainnet, Heartwood) => 170_011,
             (Testnet, Canopy) => 170_012,
             (Mainnet, Canopy) => 170_013,
+            (Testnet, Nu5) => 170_050,
+            (Mainnet, Nu5) => 170_100,
+            (Testnet, Nu6) => 170_110,
+            (Mainnet, Nu6) => 170_120,
         })
     }
 
@@ -80,7 +95,7 @@ bitflags! {
 }
 
 /// A nonce used in the networking layer to identify messages.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, ZcashSerialize, ZcashDeserialize)]
 pub struct Nonce(pub u64);
 
 impl Default for Nonce {
@@ -91,7 +106,7 @@ impl Default for Nonce {
 }
 
 /// A random value to add to the seed value in a hash function.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, ZcashSerialize, ZcashDeserialize)]
 pub struct Tweak(pub u32);
 
 impl Default for Tweak {
@@ -174,7 +189,7 @@ mod test {
     /// are consistent for `network`.
     fn version_consistent(network: Network) {
         let highest_network_upgrade = NetworkUpgrade::current(network, BlockHeight::MAX);
-        assert!(highest_network_upgrade == Canopy || highest_network_upgrade == Heartwood,
+        assert!(highest_network_upgrade == Nu6 || highest_network_upgrade == Canopy || highest_network_upgrade == Heartwood,
                 "expected coverage of all network upgrades: add the new network upgrade to the list in this test");
 
         for &network_upgrade in &[
@@ -184,6 +199,8 @@ mod test {
             Blossom,
             Heartwood,
             Canopy,
+            Nu5,
+            Nu6,
         ] {
             let height = network_upgrade.activation_height(network);
             if let Some(height) = height {
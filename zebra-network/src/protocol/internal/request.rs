@@ -1,6 +1,6 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc};
 
-use zebra_chain::block::BlockHeaderHash;
+use zebra_chain::{block::BlockHeaderHash, transaction::Transaction, types::BlockHeight};
 
 use super::super::types::Nonce;
 
@@ -69,4 +69,54 @@ pub enum Request {
         /// Optionally, the last header to request.
         stop: Option<BlockHeaderHash>,
     },
+
+    /// Request diagnostic information about our current peer connections.
+    ///
+    /// This request is answered by the peer set itself, rather than being
+    /// sent to a peer. Like other requests, it waits for the peer set to be
+    /// ready, so callers that need peer information when no peers are ready
+    /// should use [`AddressBook::connected_peers`](crate::AddressBook::connected_peers).
+    ///
+    /// # Returns
+    ///
+    /// Returns [`Response::PeerInfo`](super::Response::PeerInfo).
+    PeerInfo,
+
+    /// Push a transaction to remote peers, so they can add it to their
+    /// mempools.
+    ///
+    /// The peer set sends this request to every ready peer, rather than
+    /// a single peer. Peers don't respond to transactions, so the request
+    /// completes once the transaction has been sent to at least one peer.
+    ///
+    /// # Returns
+    ///
+    /// Returns [`Response::Nil`](super::Response::Nil).
+    PushTransaction(Arc<Transaction>),
+
+    /// Request BIP-158 basic compact filters for a range of blocks.
+    ///
+    /// # Returns
+    ///
+    /// Returns [`Response::CompactFilters`](super::Response::CompactFilters).
+    CompactFilters {
+        /// The height of the first block in the range.
+        start_height: BlockHeight,
+        /// The hash of the last block in the range.
+        stop_hash: BlockHeaderHash,
+    },
+
+    /// Request BIP-157 basic compact filter hashes for a range of blocks,
+    /// along with the filter header before the range.
+    ///
+    /// # Returns
+    ///
+    /// Returns
+    /// [`Response::CompactFilterHeaders`](super::Response::CompactFilterHeaders).
+    CompactFilterHeaders {
+        /// The height of the first block in the range.
+        start_height: BlockHeight,
+        /// The hash of the last block in the range.
+        stop_hash: BlockHeaderHash,
+    },
 }
@@ -1,7 +1,7 @@
 // XXX clean module layout of zebra_chain
 use zebra_chain::block::{Block, BlockHeaderHash};
 
-use crate::meta_addr::MetaAddr;
+use crate::{meta_addr::MetaAddr, peer_info::PeerInfo};
 use std::sync::Arc;
 
 /// A response to a network request, represented in internal format.
@@ -18,4 +18,21 @@ pub enum Response {
 
     /// A list of block hashes.
     BlockHeaderHashes(Vec<BlockHeaderHash>),
+
+    /// Diagnostic information about our current peer connections.
+    PeerInfo(Vec<PeerInfo>),
+
+    /// BIP-158 basic compact filters, with the hash of the block each filter
+    /// was computed from.
+    CompactFilters(Vec<(BlockHeaderHash, Vec<u8>)>),
+
+    /// BIP-157 basic compact filter hashes for a range of blocks.
+    CompactFilterHeaders {
+        /// The hash of the last block in the range.
+        stop_hash: BlockHeaderHash,
+        /// The filter header of the block before the range.
+        previous_header: [u8; 32],
+        /// The filter hashes of the blocks in the range, in height order.
+        filter_hashes: Vec<[u8; 32]>,
+    },
 }
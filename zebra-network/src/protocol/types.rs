@@ -2,3 +2,5 @@
 pub use super::external::types::Nonce;
 // The services flag is used in `MetaAddr`s.
 pub use super::external::types::PeerServices;
+// The negotiated version is included in `PeerInfo`s.
+pub use super::external::types::Version;
@@ -0,0 +1,151 @@
+//! Asynchronous DNS resolution for initial peers and DNS seeders.
+//!
+//! Seeders are resolved in parallel, and each lookup has a timeout, so a
+//! seeder with broken DNS doesn't stop Zebra from connecting to the others.
+
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use futures::future;
+use trust_dns_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
+
+/// Resolves each of `peers` to a set of socket addresses, using a separate
+/// `timeout` for each lookup.
+///
+/// Peers can be IP addresses or hostnames, with an optional port. Peers
+/// without a port use `default_port`. Peers that fail to resolve are logged
+/// and skipped.
+pub(crate) async fn resolve_peers(
+    peers: HashSet<String>,
+    default_port: u16,
+    timeout: Duration,
+) -> HashSet<SocketAddr> {
+    let resolver = match new_resolver(timeout).await {
+        Some(resolver) => resolver,
+        None => return HashSet::new(),
+    };
+
+    let lookups = peers.into_iter().map(|peer| {
+        let resolver = &resolver;
+        async move {
+            let (host, port) = split_host_port(&peer, default_port);
+            if let Ok(ip) = host.parse::<IpAddr>() {
+                return vec![SocketAddr::new(ip, port)];
+            }
+
+            match tokio::time::timeout(timeout, resolver.lookup_ip(host)).await {
+                Ok(Ok(ips)) => {
+                    let addrs = ips
+                        .iter()
+                        .map(|ip| SocketAddr::new(ip, port))
+                        .collect::<Vec<_>>();
+                    debug!(?peer, count = addrs.len(), "resolved initial peer");
+                    addrs
+                }
+                Ok(Err(e)) => {
+                    warn!(?peer, error = %e, "could not resolve initial peer");
+                    Vec::new()
+                }
+                Err(_) => {
+                    warn!(?peer, ?timeout, "timed out resolving initial peer");
+                    Vec::new()
+                }
+            }
+        }
+    });
+
+    future::join_all(lookups)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Returns a resolver using the system DNS configuration, or the resolver
+/// defaults if the system configuration can't be read.
+async fn new_resolver(timeout: Duration) -> Option<TokioAsyncResolver> {
+    match TokioAsyncResolver::tokio_from_system_conf().await {
+        Ok(resolver) => return Some(resolver),
+        Err(e) => warn!(error = %e, "could not read system DNS configuration, using defaults"),
+    }
+
+    let mut opts = ResolverOpts::default();
+    opts.timeout = timeout;
+    match TokioAsyncResolver::tokio(ResolverConfig::default(), opts).await {
+        Ok(resolver) => Some(resolver),
+        Err(e) => {
+            error!(error = %e, "could not create DNS resolver");
+            None
+        }
+    }
+}
+
+/// Splits `peer` into a host and a port, using `default_port` if `peer` has
+/// no port.
+///
+/// IPv6 addresses with ports must be in brackets, like `[::1]:8233`.
+fn split_host_port(peer: &str, default_port: u16) -> (&str, u16) {
+    if let Ok(addr) = peer.parse::<SocketAddr>() {
+        let host = peer.rsplitn(2, ':').nth(1).unwrap_or(peer);
+        return (unbracket(host), addr.port());
+    }
+    if peer.parse::<IpAddr>().is_ok() || peer.starts_with('[') {
+        return (unbracket(peer), default_port);
+    }
+
+    let mut parts = peer.rsplitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(port), Some(host)) => match port.parse() {
+            Ok(port) => (host, port),
+            Err(_) => (peer, default_port),
+        },
+        _ => (peer, default_port),
+    }
+}
+
+/// Removes the brackets around an IPv6 address.
+fn unbracket(host: &str) -> &str {
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_host_port_defaults() {
+        for (peer, host, port) in &[
+            ("dnsseed.z.cash:8233", "dnsseed.z.cash", 8233),
+            ("dnsseed.z.cash", "dnsseed.z.cash", 18233),
+            ("127.0.0.1:8233", "127.0.0.1", 8233),
+            ("127.0.0.1", "127.0.0.1", 18233),
+            ("[::1]:8233", "::1", 8233),
+            ("::1", "::1", 18233),
+        ] {
+            assert_eq!(split_host_port(peer, 18233), (*host, *port), "{}", peer);
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_ip_addresses_without_dns() {
+        zebra_test::init();
+
+        let peers = ["127.0.0.1:8233", "[::1]", "127.0.0.2"]
+            .iter()
+            .map(|&s| String::from(s))
+            .collect();
+        let addrs = resolve_peers(peers, 18233, Duration::from_secs(1)).await;
+
+        let expected = ["127.0.0.1:8233", "[::1]:18233", "127.0.0.2:18233"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect::<HashSet<SocketAddr>>();
+        assert_eq!(addrs, expected);
+    }
+}
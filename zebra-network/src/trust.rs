@@ -0,0 +1,84 @@
+//! Per-address trust levels for peers, set by the node operator.
+//!
+//! Trusted peers are usually the operator's own nodes, like a mining pool's
+//! `zcashd` nodes. They skip connection rate limits and the outbound prefix
+//! limit, and they are preferred for block downloads.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+/// How much we trust a peer.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustLevel {
+    /// A peer that is treated like any other peer.
+    #[default]
+    Default,
+    /// A peer that is operated by us, or someone we trust.
+    ///
+    /// Trusted peers skip connection rate limits and the outbound prefix
+    /// limit, and they are preferred for block downloads. Zebra doesn't ban
+    /// peers, so trusted peers are never banned.
+    Trusted,
+}
+
+/// The trust levels of peer IP addresses.
+///
+/// Peers that aren't listed have the default trust level.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PeerTrust(pub HashMap<IpAddr, TrustLevel>);
+
+impl PeerTrust {
+    /// Returns the trust level of the peer at `addr`.
+    ///
+    /// IPv4-mapped IPv6 addresses have the trust level of their IPv4 address.
+    pub fn level(&self, addr: &SocketAddr) -> TrustLevel {
+        let ip = match addr.ip() {
+            IpAddr::V6(ip) if ip.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => {
+                IpAddr::V4(ip.to_ipv4().expect("mapped addresses have an IPv4 address"))
+            }
+            ip => ip,
+        };
+
+        self.0.get(&ip).cloned().unwrap_or_default()
+    }
+
+    /// Returns true if the peer at `addr` is trusted.
+    pub fn is_trusted(&self, addr: &SocketAddr) -> bool {
+        self.level(addr) == TrustLevel::Trusted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trust_levels() {
+        zebra_test::init();
+
+        let trust = PeerTrust(
+            vec![
+                ("1.2.3.4".parse().unwrap(), TrustLevel::Trusted),
+                ("2001:db8::1".parse().unwrap(), TrustLevel::Trusted),
+                ("5.6.7.8".parse().unwrap(), TrustLevel::Default),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        // Trust is per IP address, so it applies to every port
+        assert!(trust.is_trusted(&"1.2.3.4:8233".parse().unwrap()));
+        assert!(trust.is_trusted(&"1.2.3.4:1234".parse().unwrap()));
+        assert!(trust.is_trusted(&"[::ffff:1.2.3.4]:8233".parse().unwrap()));
+        assert!(trust.is_trusted(&"[2001:db8::1]:8233".parse().unwrap()));
+
+        assert!(!trust.is_trusted(&"5.6.7.8:8233".parse().unwrap()));
+        assert!(!trust.is_trusted(&"1.2.3.5:8233".parse().unwrap()));
+        // IPv4-compatible addresses aren't mapped
+        assert!(!trust.is_trusted(&"[::1.2.3.4]:8233".parse().unwrap()));
+    }
+}
@@ -0,0 +1,26 @@
+//! The request, response, and error types of Zebra's shared services.
+//!
+//! Crates that call a service can depend on these types, rather than on the
+//! crate that implements the service. So projects that embed parts of Zebra
+//! can provide their own implementations.
+//!
+//! ## Organizational Structure
+//!
+//! * [`mempool`] is the interface of the mempool transaction verifier, which
+//!   is implemented by `zebra-consensus`
+//!
+//! The state request and response types are still defined in `zebra-state`,
+//! because state responses contain compact block filters, which are
+//! implemented by `zebra-state`.
+
+#![doc(html_favicon_url = "https://www.zfnd.org/images/zebra-favicon-128.png")]
+#![doc(html_logo_url = "https://www.zfnd.org/images/zebra-icon.png")]
+#![doc(html_root_url = "https://doc.zebra.zfnd.org/zebra_node_services")]
+#![deny(missing_docs)]
+
+use std::error;
+
+pub mod mempool;
+
+/// The error type of Zebra's services.
+pub type BoxError = Box<dyn error::Error + Send + Sync + 'static>;
@@ -0,0 +1,265 @@
+//! The mempool service interface.
+//!
+//! The mempool verifies transactions against the best chain and the standard
+//! transaction policy, then stores them until they are mined.
+
+use std::{fmt, sync::Arc};
+
+use zebra_chain::{
+    parameters::ConsensusBranchId,
+    transaction::{OutPoint, Transaction, TransactionHash},
+    types::BlockHeight,
+};
+
+/// A mempool request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// Verify a transaction, and add it to the mempool.
+    ///
+    /// Rejected transactions fail with a [`MempoolError`].
+    Queue(Arc<Transaction>),
+}
+
+/// A mempool response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// The response to a `Queue` request, with the hash of the transaction
+    /// that was added to the mempool.
+    Queued(TransactionHash),
+}
+
+/// A change to the transactions in the mempool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MempoolChange {
+    /// A verified transaction was added to the mempool.
+    Added(Arc<Transaction>),
+    /// A transaction was removed from the mempool.
+    Removed {
+        /// The hash of the removed transaction.
+        hash: TransactionHash,
+        /// Why the transaction was removed.
+        reason: RemovalReason,
+    },
+}
+
+/// The reason a transaction was removed from the mempool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The transaction was mined in a best chain block.
+    Mined,
+    /// The transaction expires before it can be mined.
+    Expired,
+}
+
+/// The reason a transaction was not added to the mempool.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MempoolError {
+    /// Coinbase transactions can only be included in blocks.
+    Coinbase,
+    /// The transaction is already in the mempool.
+    AlreadyInMempool,
+    /// The transaction is already in the best chain.
+    AlreadyInChain,
+    /// The transaction does not meet the standard transaction policy.
+    Policy(PolicyError),
+    /// The transaction expires before it can be mined.
+    Expired {
+        /// The expiry height of the transaction.
+        expiry_height: BlockHeight,
+        /// The height of the next block.
+        next_height: BlockHeight,
+    },
+    /// The lock time of the transaction is after the next block height or
+    /// the current time.
+    NonFinal,
+    /// The consensus branch ID in the transaction is not the branch ID of the
+    /// network upgrade at the next block height.
+    WrongConsensusBranchId {
+        /// The branch ID in the transaction.
+        found: ConsensusBranchId,
+        /// The branch ID at the next block height, if there is one.
+        expected: Option<ConsensusBranchId>,
+    },
+    /// An input spends an output that is not in the best chain or the
+    /// mempool.
+    MissingInput(OutPoint),
+    /// An input spends a coinbase output that has not matured.
+    ImmatureCoinbase(OutPoint),
+    /// An input spends an output that is already spent by another mempool
+    /// transaction.
+    Conflict(OutPoint),
+    /// The transaction spends less than it creates.
+    InputsBelowOutputs {
+        /// The value of the transparent inputs and shielded value transfers
+        /// into the transparent pool, in zatoshis.
+        inputs: i64,
+        /// The value of the transparent outputs and shielded value transfers
+        /// out of the transparent pool, in zatoshis.
+        outputs: i64,
+    },
+    /// The transaction has scripts, signatures, or proofs, which Zebra can't
+    /// verify yet.
+    Unverifiable,
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MempoolError::Coinbase => f.write_str("coinbase transactions can't be relayed"),
+            MempoolError::AlreadyInMempool => f.write_str("transaction already in mempool"),
+            MempoolError::AlreadyInChain => f.write_str("transaction already in block chain"),
+            MempoolError::Policy(e) => write!(f, "transaction is not standard: {}", e),
+            MempoolError::Expired {
+                expiry_height,
+                next_height,
+            } => write!(
+                f,
+                "transaction expiry height {} is too close to the next block height {}",
+                expiry_height.0, next_height.0
+            ),
+            MempoolError::NonFinal => f.write_str("transaction lock time is not final"),
+            MempoolError::WrongConsensusBranchId { found, expected } => match expected {
+                Some(expected) => write!(
+                    f,
+                    "transaction consensus branch ID {} does not match the current branch ID {}",
+                    found, expected
+                ),
+                None => write!(
+                    f,
+                    "transaction has consensus branch ID {}, but the current network upgrade has no branch ID",
+                    found
+                ),
+            },
+            MempoolError::MissingInput(outpoint) => {
+                write!(f, "missing input {}:{}", outpoint.hash, outpoint.index)
+            }
+            MempoolError::ImmatureCoinbase(outpoint) => write!(
+                f,
+                "input {}:{} spends an immature coinbase output",
+                outpoint.hash, outpoint.index
+            ),
+            MempoolError::Conflict(outpoint) => write!(
+                f,
+                "input {}:{} is already spent by a mempool transaction",
+                outpoint.hash, outpoint.index
+            ),
+            MempoolError::InputsBelowOutputs { inputs, outputs } => write!(
+                f,
+                "input value {} is less than output value {}",
+                inputs, outputs
+            ),
+            MempoolError::Unverifiable => f.write_str(
+                "transaction scripts, signatures, and proofs can't be verified yet",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+impl From<PolicyError> for MempoolError {
+    fn from(e: PolicyError) -> Self {
+        MempoolError::Policy(e)
+    }
+}
+
+/// The reason a transaction was rejected by the mempool policy.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PolicyError {
+    /// The transaction version is not relayed.
+    Version(u32),
+    /// The serialized transaction is larger than the maximum standard size.
+    TxSize(usize),
+    /// An input script is larger than the maximum standard size.
+    ScriptSigSize {
+        /// The index of the input.
+        index: usize,
+        /// The size of the input script.
+        size: usize,
+    },
+    /// An input script contains opcodes that are not data pushes.
+    ScriptSigNotPushOnly {
+        /// The index of the input.
+        index: usize,
+    },
+    /// An output script does not match a standard template.
+    NonStandardOutput {
+        /// The index of the output.
+        index: usize,
+    },
+    /// A data output script is larger than the maximum data carrier size.
+    DataCarrierSize {
+        /// The index of the output.
+        index: usize,
+        /// The size of the output script.
+        size: usize,
+    },
+    /// The transaction has more than one data output.
+    MultipleDataOutputs,
+    /// An output value is below the dust threshold for its script.
+    Dust {
+        /// The index of the output.
+        index: usize,
+    },
+    /// The transaction fee is below the minimum relay fee for its size.
+    InsufficientFee {
+        /// The fee paid by the transaction, in zatoshis.
+        fee: u64,
+        /// The minimum fee for the transaction, in zatoshis.
+        required: u64,
+    },
+    /// The transaction fee leaves too many ZIP-317 logical actions unpaid.
+    UnpaidActions {
+        /// The number of unpaid actions.
+        unpaid: u64,
+        /// The maximum number of unpaid actions.
+        limit: u64,
+    },
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyError::Version(version) => {
+                write!(f, "transaction version {} is not standard", version)
+            }
+            PolicyError::TxSize(size) => {
+                write!(
+                    f,
+                    "transaction size {} is larger than the standard size",
+                    size
+                )
+            }
+            PolicyError::ScriptSigSize { index, size } => write!(
+                f,
+                "input {} script size {} is larger than the standard size",
+                index, size
+            ),
+            PolicyError::ScriptSigNotPushOnly { index } => {
+                write!(f, "input {} script is not push-only", index)
+            }
+            PolicyError::NonStandardOutput { index } => {
+                write!(f, "output {} script is not standard", index)
+            }
+            PolicyError::DataCarrierSize { index, size } => write!(
+                f,
+                "output {} data size {} is larger than the data carrier size",
+                index, size
+            ),
+            PolicyError::MultipleDataOutputs => f.write_str("more than one data output"),
+            PolicyError::Dust { index } => write!(f, "output {} value is dust", index),
+            PolicyError::InsufficientFee { fee, required } => write!(
+                f,
+                "fee {} is less than the minimum relay fee {}",
+                fee, required
+            ),
+            PolicyError::UnpaidActions { unpaid, limit } => write!(
+                f,
+                "{} unpaid actions is more than the limit {}",
+                unpaid, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
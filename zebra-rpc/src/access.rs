@@ -0,0 +1,205 @@
+//! Access control for the RPC endpoint: allowed client addresses, HTTP basic
+//! authentication, and enabled methods.
+//!
+//! Authentication uses the same cookie file format as `zcashd`, so existing
+//! clients can find the generated credentials.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    net::IpAddr,
+    path::Path,
+};
+
+use rand::Rng;
+
+use crate::Config;
+
+/// The username in generated cookie files.
+const COOKIE_USER: &str = "__cookie__";
+
+/// The name of the generated cookie file.
+const COOKIE_FILE_NAME: &str = ".cookie";
+
+/// The prefix of HTTP basic `Authorization` headers.
+const BASIC: &[u8] = b"Basic ";
+
+/// The access rules for the RPC endpoint.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Access {
+    /// The remote addresses that can use the endpoint, as well as loopback
+    /// addresses.
+    allow_ips: Vec<IpAddr>,
+    /// The expected `user:password` credentials, if requests are
+    /// authenticated.
+    credentials: Option<String>,
+    /// The methods that can be called, or `None` if all methods can be
+    /// called.
+    enabled_methods: Option<HashSet<String>>,
+}
+
+impl Access {
+    /// Returns the access rules in `config`.
+    ///
+    /// If `config` has a cookie directory and no password, writes a cookie
+    /// file with a random password.
+    pub(crate) fn new(config: &Config) -> io::Result<Access> {
+        let credentials = match (&config.user, &config.password, &config.cookie_dir) {
+            (Some(user), Some(password), _) => Some(format!("{}:{}", user, password)),
+            (_, _, Some(cookie_dir)) => Some(write_cookie(cookie_dir)?),
+            _ => None,
+        };
+
+        Ok(Access {
+            allow_ips: config.allow_ips.clone(),
+            credentials,
+            enabled_methods: config
+                .enabled_methods
+                .as_ref()
+                .map(|methods| methods.iter().cloned().collect()),
+        })
+    }
+
+    /// Returns true if requests are authenticated.
+    pub(crate) fn is_authenticated(&self) -> bool {
+        self.credentials.is_some()
+    }
+
+    /// Returns true if the client at `addr` can use the endpoint.
+    pub(crate) fn allows_addr(&self, addr: IpAddr) -> bool {
+        addr.is_loopback() || self.allow_ips.contains(&addr)
+    }
+
+    /// Returns true if `authorization`, the value of the HTTP `Authorization`
+    /// header, has the expected credentials.
+    pub(crate) fn is_authorized(&self, authorization: Option<&[u8]>) -> bool {
+        let expected = match &self.credentials {
+            Some(expected) => expected,
+            None => return true,
+        };
+
+        authorization
+            .filter(|header| header.starts_with(BASIC))
+            .and_then(|header| base64::decode(&header[BASIC.len()..]).ok())
+            .map(|credentials| timing_resistant_eq(&credentials, expected.as_bytes()))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if `method` can be called.
+    pub(crate) fn allows_method(&self, method: &str) -> bool {
+        match &self.enabled_methods {
+            Some(methods) => methods.contains(method),
+            None => true,
+        }
+    }
+}
+
+/// Writes a cookie file with a random password to `cookie_dir`, and returns
+/// the `user:password` credentials.
+///
+/// On Unix, only the file's owner can read the cookie.
+fn write_cookie(cookie_dir: &Path) -> io::Result<String> {
+    let password: [u8; 32] = rand::thread_rng().gen();
+    let credentials = format!("{}:{}", COOKIE_USER, hex::encode(password));
+
+    fs::create_dir_all(cookie_dir)?;
+
+    // The mode only applies to new files, so replace any old cookie file
+    let path = cookie_dir.join(COOKIE_FILE_NAME);
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(credentials.as_bytes())?;
+
+    Ok(credentials)
+}
+
+/// Returns true if `a` and `b` are equal, taking the same amount of time for
+/// all inputs of the same length.
+fn timing_resistant_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn basic_auth() {
+        let config = Config {
+            user: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+            ..Config::default()
+        };
+        let access = Access::new(&config).unwrap();
+
+        let header = format!("Basic {}", base64::encode("user:pass"));
+        assert!(access.is_authorized(Some(header.as_bytes())));
+
+        let header = format!("Basic {}", base64::encode("user:wrong"));
+        assert!(!access.is_authorized(Some(header.as_bytes())));
+        assert!(!access.is_authorized(Some(b"Basic !")));
+        assert!(!access.is_authorized(None));
+
+        assert!(Access::default().is_authorized(None));
+    }
+
+    #[test]
+    fn cookie_file() {
+        let dir = TempDir::new("zebra_rpc_cookie").unwrap();
+        let config = Config {
+            cookie_dir: Some(dir.path().to_owned()),
+            ..Config::default()
+        };
+        let access = Access::new(&config).unwrap();
+
+        let cookie = fs::read_to_string(dir.path().join(COOKIE_FILE_NAME)).unwrap();
+        assert!(cookie.starts_with("__cookie__:"));
+
+        let header = format!("Basic {}", base64::encode(&cookie));
+        assert!(access.is_authorized(Some(header.as_bytes())));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = fs::metadata(dir.path().join(COOKIE_FILE_NAME)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+
+        // Restarting replaces the cookie
+        let restarted = Access::new(&config).unwrap();
+        assert!(!restarted.is_authorized(Some(header.as_bytes())));
+    }
+
+    #[test]
+    fn addresses_and_methods() {
+        let config = Config {
+            allow_ips: vec!["192.0.2.1".parse().unwrap()],
+            enabled_methods: Some(vec!["getblockcount".to_owned()]),
+            ..Config::default()
+        };
+        let access = Access::new(&config).unwrap();
+
+        assert!(access.allows_addr("127.0.0.1".parse().unwrap()));
+        assert!(access.allows_addr("::1".parse().unwrap()));
+        assert!(access.allows_addr("192.0.2.1".parse().unwrap()));
+        assert!(!access.allows_addr("192.0.2.2".parse().unwrap()));
+
+        assert!(access.allows_method("getblockcount"));
+        assert!(!access.allows_method("sendrawtransaction"));
+        assert!(Access::default().allows_method("sendrawtransaction"));
+    }
+}
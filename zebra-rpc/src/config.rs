@@ -0,0 +1,77 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration for the RPC endpoint.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Config {
+    /// The address used for the JSON-RPC endpoint.
+    ///
+    /// If this is `None`, the RPC endpoint is disabled.
+    pub listen_addr: Option<SocketAddr>,
+
+    /// The remote IP addresses that can use the RPC endpoint.
+    ///
+    /// Loopback addresses can always use the endpoint. Requests from other
+    /// addresses are rejected, even if `listen_addr` accepts connections
+    /// from them.
+    pub allow_ips: Vec<IpAddr>,
+
+    /// The username for HTTP basic authentication.
+    ///
+    /// If `user` and `password` are both set, requests must use them.
+    pub user: Option<String>,
+
+    /// The password for HTTP basic authentication.
+    pub password: Option<String>,
+
+    /// The directory for the `zcashd`-style `.cookie` file.
+    ///
+    /// If `user` and `password` are not set, and this directory is set,
+    /// a random password is generated when the endpoint starts. Clients
+    /// authenticate by reading the username and password from the cookie
+    /// file.
+    ///
+    /// If no credentials are configured, requests are not authenticated.
+    /// Admin methods, like `stop` and `addnode`, can only be called if
+    /// requests are authenticated.
+    pub cookie_dir: Option<PathBuf>,
+
+    /// The names of the RPC methods that can be called.
+    ///
+    /// If this is `None`, all methods can be called.
+    pub enabled_methods: Option<Vec<String>>,
+
+    /// The maximum number of method calls that run in parallel.
+    ///
+    /// Each call runs in its own task, so slow calls don't delay calls from
+    /// other clients. Long polls, which wait for chain tip changes, don't
+    /// count towards this limit.
+    pub parallel_requests: usize,
+
+    /// The maximum number of long polls that wait in parallel.
+    ///
+    /// Long polls that would go over this limit are rejected, rather than
+    /// queued, because waiting long polls can't free up their permits until
+    /// the chain tip changes.
+    pub parallel_long_polls: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            allow_ips: Vec::new(),
+            user: None,
+            password: None,
+            cookie_dir: None,
+            enabled_methods: None,
+            parallel_requests: 8,
+            parallel_long_polls: 32,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A JSON-RPC error, with a `zcashd`-compatible error code.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Error {
+    /// The error code.
+    pub code: i64,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl Error {
+    /// The request body is not valid JSON.
+    pub const PARSE_ERROR: i64 = -32700;
+    /// The request is not a valid JSON-RPC request.
+    pub const INVALID_REQUEST: i64 = -32600;
+    /// The requested method does not exist.
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    /// A general error, which doesn't have a more specific code.
+    pub const MISC_ERROR: i64 = -1;
+    /// A parameter has the wrong JSON type.
+    pub const TYPE_ERROR: i64 = -3;
+    /// An address, key, block, or transaction is invalid or unknown.
+    pub const INVALID_ADDRESS_OR_KEY: i64 = -5;
+    /// A parameter has an invalid value.
+    pub const INVALID_PARAMETER: i64 = -8;
+    /// A raw transaction or block could not be deserialized.
+    pub const DESERIALIZATION_ERROR: i64 = -22;
+    /// A submitted transaction spends outputs that can't be found.
+    pub const VERIFY_ERROR: i64 = -25;
+    /// A submitted transaction was rejected by the consensus rules or the
+    /// mempool policy.
+    pub const VERIFY_REJECTED: i64 = -26;
+    /// A submitted transaction is already in the best chain.
+    pub const VERIFY_ALREADY_IN_CHAIN: i64 = -27;
+
+    /// Returns a new error with `code` and `message`.
+    pub fn new(code: i64, message: impl Into<String>) -> Error {
+        Error {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Returns an error for an unknown `method`.
+    pub fn method_not_found(method: &str) -> Error {
+        Error::new(
+            Error::METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )
+    }
+
+    /// Returns an error for a parameter with an invalid value.
+    pub fn invalid_parameter(message: impl Into<String>) -> Error {
+        Error::new(Error::INVALID_PARAMETER, message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,11 +1,25 @@
+//! A zcashd-compatible JSON-RPC interface for Zebra.
+//!
+//! ## Organizational Structure
+//!
+//! * [`server`] accepts JSON-RPC requests over HTTP, checks that the client
+//!   can use the endpoint and the method, and sends them to [`Rpc`]
+//! * [`Rpc`] dispatches each request to its method
+//! * method results and errors use the same JSON format as `zcashd`
+//! * [`openapi`] documents the methods and their result types
+
 #![doc(html_favicon_url = "https://www.zfnd.org/images/zebra-favicon-128.png")]
 #![doc(html_logo_url = "https://www.zfnd.org/images/zebra-icon.png")]
 #![doc(html_root_url = "https://doc.zebra.zfnd.org/zebra_rpc")]
+#![warn(missing_docs)]
+
+mod access;
+mod config;
+mod error;
+pub mod methods;
+pub mod openapi;
+pub mod server;
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
+pub use config::Config;
+pub use error::Error;
+pub use methods::Rpc;
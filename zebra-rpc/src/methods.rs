@@ -0,0 +1,282 @@
+//! The RPC methods supported by Zebra, and their result types.
+//!
+//! Method names, parameters, and results follow `zcashd`, so existing
+//! wallets and explorers can use Zebra without changes.
+
+use std::{
+    error,
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tower::{buffer::Buffer, util::BoxService, Service, ServiceExt};
+
+use zebra_chain::{block::Block, Network};
+use zebra_network as zn;
+use zebra_node_services::mempool as mp;
+use zebra_state as zs;
+
+use crate::Error;
+
+mod address;
+mod admin;
+mod chain;
+mod long_poll;
+mod mining;
+mod network;
+mod script;
+mod shielded;
+#[cfg(test)]
+mod tests;
+mod transaction;
+
+pub(crate) use admin::is_admin;
+use admin::Admin;
+pub(crate) use long_poll::is_long_poll;
+use network::NetworkDiagnostics;
+
+pub use address::{AddressType, ValidateAddress, ZValidateAddress};
+pub use admin::NodeControl;
+pub use chain::{
+    BestBlockHeightAndHash, BlockHeaderObject, BlockObject, BlockTransaction, BlockchainInfo,
+    ValuePoolObject,
+};
+pub use mining::MiningInfo;
+pub use network::{LocalAddressObject, NetworkInfo, PeerInfoObject};
+pub use shielded::{AnchorList, AnchorObject, TreeObject};
+pub use transaction::{
+    InputObject, JoinSplitObject, OutputObject, RawTransactionObject, ScriptObject,
+    ScriptPubKeyObject, ShieldedOutputObject, SpendObject, TransactionObject,
+};
+
+type BoxError = Box<dyn error::Error + Send + Sync + 'static>;
+
+/// A service that verifies transactions, and adds them to the mempool.
+///
+/// Rejected transactions fail with a
+/// [`MempoolError`](zebra_node_services::mempool::MempoolError).
+pub type Mempool = Buffer<BoxService<mp::Request, mp::Response, BoxError>, mp::Request>;
+
+/// A service that sends requests to remote peers.
+pub type Peers = Buffer<BoxService<zn::Request, zn::Response, BoxError>, zn::Request>;
+
+/// Handles RPC method calls, using the blocks in `state`.
+#[derive(Clone)]
+pub struct Rpc<ZS> {
+    /// The network that addresses and blocks must belong to.
+    network: Network,
+    /// The state service, which answers chain queries.
+    state: ZS,
+    /// The mempool and peer set, which accept submitted transactions.
+    ///
+    /// If this is `None`, transactions can't be submitted.
+    mempool: Option<(Mempool, Peers)>,
+    /// The network configuration and connected peers.
+    ///
+    /// If this is `None`, network information methods fail.
+    network_diagnostics: Option<NetworkDiagnostics>,
+    /// The node and peer set controls.
+    ///
+    /// If this is `None`, admin methods fail.
+    admin: Option<Admin>,
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns a new RPC handler for `network`, which answers chain queries
+    /// using `state`.
+    pub fn new(network: Network, state: ZS) -> Rpc<ZS> {
+        Rpc {
+            network,
+            state,
+            mempool: None,
+            network_diagnostics: None,
+            admin: None,
+        }
+    }
+
+    /// Returns this RPC handler, with transactions submitted to `mempool`,
+    /// and sent to `peers` once they are accepted.
+    pub fn with_mempool(self, mempool: Mempool, peers: Peers) -> Rpc<ZS> {
+        Rpc {
+            mempool: Some((mempool, peers)),
+            ..self
+        }
+    }
+
+    /// Returns this RPC handler, with network information from
+    /// `network_config` and `mempool_config`, and peer information from
+    /// `address_book`.
+    pub fn with_network_diagnostics(
+        self,
+        network_config: &zn::Config,
+        mempool_config: &zebra_consensus::mempool::Config,
+        address_book: Arc<Mutex<zn::AddressBook>>,
+    ) -> Rpc<ZS> {
+        Rpc {
+            network_diagnostics: Some(NetworkDiagnostics::new(
+                network_config,
+                mempool_config,
+                address_book,
+            )),
+            ..self
+        }
+    }
+
+    /// Returns this RPC handler, with admin methods that control `node` and
+    /// the peer connections in `peers`.
+    pub fn with_admin(self, node: Arc<dyn NodeControl>, peers: zn::PeerControl) -> Rpc<ZS> {
+        Rpc {
+            admin: Some(Admin::new(node, peers)),
+            ..self
+        }
+    }
+
+    /// Calls the RPC `method` with `params`, and returns its JSON result.
+    pub async fn call(&self, method: &str, params: Vec<Value>) -> Result<Value, Error> {
+        match method {
+            "addnode" => {
+                let node: String = param(&params, 0)?;
+                let command: String = param(&params, 1)?;
+                result(self.add_node(&node, &command).await?)
+            }
+            "clearbanned" => result(self.clear_banned()?),
+            "disconnectnode" => {
+                let node: String = param(&params, 0)?;
+                result(self.disconnect_node(&node).await?)
+            }
+            "getbestblockhash" => result(self.get_best_block_hash().await?),
+            "getbestblockheightandhash" => result(self.get_best_block_height_and_hash().await?),
+            "getblock" => {
+                let hash_or_height: String = param(&params, 0)?;
+                let verbosity: u8 = optional_param(&params, 1, 1)?;
+                self.get_block(&hash_or_height, verbosity).await
+            }
+            "getblockchaininfo" => result(self.get_blockchain_info().await?),
+            "getblockcount" => result(self.get_block_count().await?),
+            "getblockhash" => {
+                let height: u32 = param(&params, 0)?;
+                result(self.get_block_hash(height).await?)
+            }
+            "getblockheader" => {
+                let hash: String = param(&params, 0)?;
+                let verbose: bool = optional_param(&params, 1, true)?;
+                self.get_block_header(&hash, verbose).await
+            }
+            "getdifficulty" => result(self.get_difficulty().await?),
+            "getmininginfo" => result(self.get_mining_info().await?),
+            "getnetworkhashps" | "getnetworksolps" => {
+                let blocks: i32 = optional_param(&params, 0, mining::DEFAULT_SOLPS_BLOCKS)?;
+                let height: i32 = optional_param(&params, 1, -1)?;
+                result(self.get_network_sol_ps(blocks, height).await?)
+            }
+            "getnetworkinfo" => result(self.get_network_info()?),
+            "getpeerinfo" => result(self.get_peer_info()?),
+            "getrawtransaction" => {
+                let txid: String = param(&params, 0)?;
+                let verbose = match optional_param(&params, 1, Value::Bool(false))? {
+                    Value::Bool(verbose) => verbose,
+                    Value::Number(verbose) => verbose.as_u64() != Some(0),
+                    _ => return Err(Error::new(Error::TYPE_ERROR, "verbose must be a number")),
+                };
+                self.get_raw_transaction(&txid, verbose).await
+            }
+            "sendrawtransaction" => {
+                let hex: String = param(&params, 0)?;
+                result(self.send_raw_transaction(&hex).await?)
+            }
+            "setloglevel" => {
+                let filter: String = param(&params, 0)?;
+                result(self.set_log_level(&filter)?)
+            }
+            "stop" => result(self.stop()?),
+            "validateaddress" => {
+                let address: String = param(&params, 0)?;
+                result(address::validate_address(self.network, &address))
+            }
+            "waitfornewblock" => {
+                let timeout_ms: u64 = optional_param(&params, 0, 0)?;
+                result(self.wait_for_new_block(timeout_ms).await?)
+            }
+            "z_listanchors" => {
+                let count: u32 = optional_param(&params, 0, shielded::DEFAULT_ANCHOR_COUNT)?;
+                result(self.z_list_anchors(count).await?)
+            }
+            "z_validateaddress" => {
+                let address: String = param(&params, 0)?;
+                result(address::z_validate_address(self.network, &address))
+            }
+            _ => Err(Error::method_not_found(method)),
+        }
+    }
+
+    /// Sends `request` to the state service, and returns its response.
+    async fn state_request(&self, request: zs::Request) -> Result<zs::Response, Error> {
+        self.state
+            .clone()
+            .oneshot(request)
+            .await
+            .map_err(|e| Error::new(Error::MISC_ERROR, e.to_string()))
+    }
+
+    /// Returns the tip block of the best chain.
+    async fn tip(&self) -> Result<Arc<Block>, Error> {
+        let hash = match self.state_request(zs::Request::GetTip).await? {
+            zs::Response::Tip { hash } => hash,
+            _ => unreachable!("GetTip request can only result in Response::Tip"),
+        };
+
+        match self.state_request(zs::Request::GetBlock { hash }).await? {
+            zs::Response::Block { block } => Ok(block),
+            _ => unreachable!("GetBlock request can only result in Response::Block"),
+        }
+    }
+}
+
+/// Returns the parameter at `index` in `params`, deserialized as a `T`.
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, Error> {
+    let value = params
+        .get(index)
+        .ok_or_else(|| Error::invalid_parameter(format!("missing parameter {}", index)))?;
+
+    serde_json::from_value(value.clone()).map_err(|e| {
+        Error::new(
+            Error::TYPE_ERROR,
+            format!("invalid type for parameter {}: {}", index, e),
+        )
+    })
+}
+
+/// Returns the parameter at `index` in `params`, or `default` if it is
+/// missing or null.
+fn optional_param<T: DeserializeOwned>(
+    params: &[Value],
+    index: usize,
+    default: T,
+) -> Result<T, Error> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(default),
+        Some(_) => param(params, index),
+    }
+}
+
+/// Returns `value` as a JSON method result.
+fn result<T: Serialize>(value: T) -> Result<Value, Error> {
+    serde_json::to_value(value).map_err(|e| Error::new(Error::MISC_ERROR, e.to_string()))
+}
+
+/// Returns `bytes` as hex, in reverse order.
+///
+/// `zcashd` displays hashes, roots, and 32-byte keys in reverse byte order.
+fn reversed_hex(mut bytes: [u8; 32]) -> String {
+    bytes.reverse();
+    hex::encode(bytes)
+}
@@ -0,0 +1,162 @@
+//! The `validateaddress` and `z_validateaddress` methods.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use zebra_chain::{
+    addresses::{
+        sapling::SaplingShieldedAddress, sprout::SproutShieldedAddress,
+        transparent::TransparentAddress, unified::UnifiedAddress,
+    },
+    Network,
+};
+
+use super::reversed_hex;
+
+/// The type of a valid address, as reported by `z_validateaddress`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressType {
+    /// A transparent pay-to-public-key-hash address.
+    P2pkh,
+    /// A transparent pay-to-script-hash address.
+    P2sh,
+    /// A Sprout shielded address.
+    Sprout,
+    /// A Sapling shielded address.
+    Sapling,
+    /// A unified address.
+    Unified,
+}
+
+/// The result of `validateaddress`.
+///
+/// Only `isvalid` is present for invalid addresses.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ValidateAddress {
+    /// Is the address a valid transparent address for this network?
+    pub isvalid: bool,
+    /// The validated address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// The hex-encoded output script that pays to the address.
+    #[serde(
+        rename = "scriptPubKey",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub script_pub_key: Option<String>,
+    /// Is the address a pay-to-script-hash address?
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isscript: Option<bool>,
+}
+
+/// The result of `z_validateaddress`.
+///
+/// Only `isvalid` is present for invalid addresses. The key fields are only
+/// present for shielded addresses of the matching type.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ZValidateAddress {
+    /// Is the address a valid transparent, Sprout, Sapling, or unified
+    /// address for this network?
+    pub isvalid: bool,
+    /// The validated address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// The address type.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub address_type: Option<AddressType>,
+    /// The hex-encoded Sapling diversifier.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diversifier: Option<String>,
+    /// The hex-encoded Sapling diversified transmission key, in `zcashd`
+    /// display order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diversifiedtransmissionkey: Option<String>,
+    /// The hex-encoded Sprout paying key, in `zcashd` display order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payingkey: Option<String>,
+    /// The hex-encoded Sprout transmission key, in `zcashd` display order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transmissionkey: Option<String>,
+}
+
+/// Returns the `validateaddress` result for `address` on `network`.
+pub(super) fn validate_address(network: Network, address: &str) -> ValidateAddress {
+    let parsed = match address.parse::<TransparentAddress>() {
+        Ok(parsed) if parsed.network() == network => parsed,
+        _ => return ValidateAddress::default(),
+    };
+
+    ValidateAddress {
+        isvalid: true,
+        address: Some(address.to_owned()),
+        script_pub_key: Some(hex::encode(&parsed.output_script().0)),
+        isscript: Some(matches!(parsed, TransparentAddress::PayToScriptHash { .. })),
+    }
+}
+
+/// Returns the `z_validateaddress` result for `address` on `network`.
+pub(super) fn z_validate_address(network: Network, address: &str) -> ZValidateAddress {
+    let valid = ZValidateAddress {
+        isvalid: true,
+        address: Some(address.to_owned()),
+        ..ZValidateAddress::default()
+    };
+
+    if let Ok(parsed) = address.parse::<TransparentAddress>() {
+        if parsed.network() != network {
+            return ZValidateAddress::default();
+        }
+
+        let address_type = match parsed {
+            TransparentAddress::PayToScriptHash { .. } => AddressType::P2sh,
+            TransparentAddress::PayToPublicKeyHash { .. } => AddressType::P2pkh,
+        };
+        return ZValidateAddress {
+            address_type: Some(address_type),
+            ..valid
+        };
+    }
+
+    if let Ok(parsed) = address.parse::<SaplingShieldedAddress>() {
+        if parsed.network() != network {
+            return ZValidateAddress::default();
+        }
+
+        return ZValidateAddress {
+            address_type: Some(AddressType::Sapling),
+            diversifier: Some(hex::encode(parsed.diversifier().0)),
+            diversifiedtransmissionkey: Some(reversed_hex(<[u8; 32]>::from(
+                parsed.transmission_key(),
+            ))),
+            ..valid
+        };
+    }
+
+    if let Ok(parsed) = address.parse::<SproutShieldedAddress>() {
+        if parsed.network() != network {
+            return ZValidateAddress::default();
+        }
+
+        return ZValidateAddress {
+            address_type: Some(AddressType::Sprout),
+            payingkey: Some(reversed_hex(parsed.paying_key().0)),
+            transmissionkey: Some(reversed_hex(*parsed.transmission_key().as_bytes())),
+            ..valid
+        };
+    }
+
+    if let Ok(parsed) = address.parse::<UnifiedAddress>() {
+        if parsed.network() != network {
+            return ZValidateAddress::default();
+        }
+
+        return ZValidateAddress {
+            address_type: Some(AddressType::Unified),
+            ..valid
+        };
+    }
+
+    ZValidateAddress::default()
+}
@@ -0,0 +1,157 @@
+//! Node administration methods: `stop`, `setloglevel`, `clearbanned`,
+//! `addnode`, and `disconnectnode`.
+//!
+//! Admin methods change the running node, so the server only allows them
+//! when requests are authenticated.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use tower::Service;
+
+use zebra_chain::Network;
+use zebra_network as zn;
+use zebra_state as zs;
+
+use super::{BoxError, Rpc};
+use crate::Error;
+
+/// The methods that change the running node.
+const ADMIN_METHODS: &[&str] = &[
+    "addnode",
+    "clearbanned",
+    "disconnectnode",
+    "setloglevel",
+    "stop",
+];
+
+/// Returns true if `method` is an admin method.
+pub(crate) fn is_admin(method: &str) -> bool {
+    ADMIN_METHODS.contains(&method)
+}
+
+/// Node administration actions that are implemented by the node, rather than
+/// by the library crates.
+pub trait NodeControl: Send + Sync + 'static {
+    /// Starts a graceful shutdown of the node.
+    fn stop(&self);
+
+    /// Replaces the node's log filter with `filter`, which uses the
+    /// `tracing` filter syntax, like `info,zebra_network=debug`.
+    ///
+    /// Returns an error if the filter is invalid.
+    fn set_log_filter(&self, filter: &str) -> Result<(), BoxError>;
+}
+
+/// The node and peer set controls used by the admin methods.
+#[derive(Clone)]
+pub(super) struct Admin {
+    /// The node, which can be stopped, and has a log filter.
+    node: Arc<dyn NodeControl>,
+    /// The peer set, which can connect to and disconnect from peers.
+    peers: zn::PeerControl,
+}
+
+impl Admin {
+    /// Returns the admin controls for `node` and `peers`.
+    pub(super) fn new(node: Arc<dyn NodeControl>, peers: zn::PeerControl) -> Admin {
+        Admin { node, peers }
+    }
+}
+
+/// Parses `node`, a peer address with an optional port.
+///
+/// Addresses without a port use the default port for `network`.
+fn parse_node(network: Network, node: &str) -> Result<SocketAddr, Error> {
+    let default_port = match network {
+        Network::Mainnet => 8233,
+        Network::Testnet => 18233,
+    };
+
+    node.parse()
+        .or_else(|_| {
+            node.parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, default_port))
+        })
+        .map_err(|_| Error::invalid_parameter(format!("Invalid node address: {}", node)))
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns the admin controls, or an error if they are not available.
+    fn admin(&self) -> Result<&Admin, Error> {
+        self.admin
+            .as_ref()
+            .ok_or_else(|| Error::new(Error::MISC_ERROR, "Admin methods are not available"))
+    }
+
+    /// Starts a graceful shutdown of the node.
+    pub(super) fn stop(&self) -> Result<String, Error> {
+        self.admin()?.node.stop();
+
+        Ok("Zebra server stopping".to_string())
+    }
+
+    /// Replaces the node's log filter with `filter`.
+    pub(super) fn set_log_level(&self, filter: &str) -> Result<(), Error> {
+        self.admin()?
+            .node
+            .set_log_filter(filter)
+            .map_err(|e| Error::invalid_parameter(e.to_string()))
+    }
+
+    /// Clears the list of banned peers.
+    ///
+    /// Zebra doesn't ban peers, so there is nothing to clear. This method is
+    /// provided for compatibility with `zcashd` management scripts.
+    pub(super) fn clear_banned(&self) -> Result<(), Error> {
+        self.admin()?;
+
+        Ok(())
+    }
+
+    /// Changes the manual peers, or connects to the peer at `node` once.
+    ///
+    /// The `add` command adds `node` to the manual peers, which are always
+    /// reconnected, and `remove` removes it. The `onetry` command makes a
+    /// single connection attempt. Added peers are not saved, so they should
+    /// also be added to the `manual_peers` config.
+    pub(super) async fn add_node(&self, node: &str, command: &str) -> Result<(), Error> {
+        let admin = self.admin()?;
+        let addr = parse_node(self.network, node)?;
+
+        let result = match command {
+            "add" => admin.peers.add(addr).await,
+            "remove" => admin.peers.remove(addr),
+            "onetry" => admin.peers.connect(addr).await,
+            _ => {
+                return Err(Error::invalid_parameter(
+                    "command must be 'add', 'remove', or 'onetry'",
+                ))
+            }
+        };
+
+        result.map_err(|e| Error::new(Error::MISC_ERROR, e.to_string()))
+    }
+
+    /// Closes the connection to the peer at `node`.
+    pub(super) async fn disconnect_node(&self, node: &str) -> Result<(), Error> {
+        let admin = self.admin()?;
+        let addr = parse_node(self.network, node)?;
+
+        admin
+            .peers
+            .disconnect(addr)
+            .await
+            .map_err(|e| Error::new(Error::MISC_ERROR, e.to_string()))
+    }
+}
@@ -0,0 +1,422 @@
+//! Chain query methods: `getblockcount`, `getbestblockhash`,
+//! `getbestblockheightandhash`, `getblockchaininfo`, `getblockhash`,
+//! `getblockheader`, and `getblock`.
+
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower::Service;
+
+use zebra_chain::{
+    block::{Block, BlockHeaderHash, Commitment},
+    serialization::ZcashSerialize,
+    transaction::TransactionHash,
+    types::{amount::Amount, BlockHeight},
+    Network,
+};
+use zebra_state as zs;
+
+use super::{
+    result, reversed_hex,
+    transaction::{transaction_object, TransactionObject},
+    BoxError, Rpc,
+};
+use crate::Error;
+
+/// The result of `getbestblockheightandhash`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct BestBlockHeightAndHash {
+    /// The height of the best chain tip.
+    pub height: u32,
+    /// The hash of the best chain tip.
+    pub hash: String,
+}
+
+/// The result of `getblockchaininfo`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct BlockchainInfo {
+    /// The network name, `main` or `test`.
+    pub chain: String,
+    /// The height of the best chain tip.
+    pub blocks: u32,
+    /// The hash of the best chain tip.
+    pub bestblockhash: String,
+    /// The difficulty of the best chain tip, as a multiple of the minimum
+    /// difficulty.
+    pub difficulty: f64,
+    /// The total value in each of the chain's value pools, after the best
+    /// chain tip.
+    #[serde(rename = "valuePools")]
+    pub value_pools: Vec<ValuePoolObject>,
+}
+
+/// A chain value pool in the result of `getblockchaininfo`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ValuePoolObject {
+    /// The pool name: `transparent`, `sprout`, `sapling`, `orchard`, or
+    /// `lockbox`.
+    pub id: String,
+    /// Is the pool value tracked by this node?
+    pub monitored: bool,
+    /// The pool value, in ZEC.
+    #[serde(rename = "chainValue")]
+    pub chain_value: f64,
+    /// The pool value, in zatoshis.
+    #[serde(rename = "chainValueZat")]
+    pub chain_value_zat: i64,
+}
+
+impl ValuePoolObject {
+    /// Returns the pool object for the pool `id`, with `value`.
+    fn new<C: Copy>(id: &str, value: Amount<C>) -> ValuePoolObject {
+        ValuePoolObject {
+            id: id.to_string(),
+            monitored: true,
+            chain_value: value.zec(),
+            chain_value_zat: value.into(),
+        }
+    }
+}
+
+/// The verbose result of `getblockheader`.
+///
+/// Hashes and roots are hex-encoded in `zcashd` display order.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct BlockHeaderObject {
+    /// The block hash.
+    pub hash: String,
+    /// The number of blocks in the best chain from this block to the tip,
+    /// inclusive, or -1 if the block is not in the best chain.
+    pub confirmations: i64,
+    /// The block height.
+    pub height: u32,
+    /// The block version.
+    pub version: u32,
+    /// The transaction merkle root.
+    pub merkleroot: String,
+    /// The final Sapling note commitment tree root.
+    ///
+    /// Only Sapling and Blossom blocks have the root in their header, and
+    /// Zebra doesn't track the tree yet, so other blocks omit this field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finalsaplingroot: Option<String>,
+    /// The block time, in seconds since the Unix epoch.
+    pub time: i64,
+    /// The block nonce.
+    pub nonce: String,
+    /// The hex-encoded Equihash solution.
+    pub solution: String,
+    /// The difficulty threshold, in compact form.
+    pub bits: String,
+    /// The difficulty, as a multiple of the minimum difficulty.
+    pub difficulty: f64,
+    /// The hash of the previous block, if this is not the genesis block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previousblockhash: Option<String>,
+    /// The hash of the next block in the best chain, if there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nextblockhash: Option<String>,
+}
+
+/// The verbose result of `getblock`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct BlockObject {
+    /// The block header fields.
+    #[serde(flatten)]
+    pub header: BlockHeaderObject,
+    /// The serialized size of the block, in bytes.
+    pub size: usize,
+    /// The transactions in the block.
+    pub tx: Vec<BlockTransaction>,
+}
+
+/// A transaction in the verbose result of `getblock`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum BlockTransaction {
+    /// The transaction hash, for verbosity 1.
+    Hash(String),
+    /// The decoded transaction, for verbosity 2.
+    Object(Box<TransactionObject>),
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns the height of the best chain tip.
+    pub(super) async fn get_block_count(&self) -> Result<u32, Error> {
+        Ok(coinbase_height(&*self.tip().await?).0)
+    }
+
+    /// Returns the hash of the best chain tip.
+    pub(super) async fn get_best_block_hash(&self) -> Result<String, Error> {
+        Ok(self.tip().await?.hash().to_string())
+    }
+
+    /// Returns the height and hash of the best chain tip.
+    pub(super) async fn get_best_block_height_and_hash(
+        &self,
+    ) -> Result<BestBlockHeightAndHash, Error> {
+        let tip = self.tip().await?;
+
+        Ok(BestBlockHeightAndHash {
+            height: coinbase_height(&tip).0,
+            hash: tip.hash().to_string(),
+        })
+    }
+
+    /// Returns the state of the best chain, including its value pools.
+    pub(super) async fn get_blockchain_info(&self) -> Result<BlockchainInfo, Error> {
+        let tip = self.tip().await?;
+        let height = coinbase_height(&tip);
+
+        let pools = match self
+            .state_request(zs::Request::GetValuePools { height })
+            .await?
+        {
+            zs::Response::ValuePools(pools) => pools,
+            _ => unreachable!("GetValuePools request can only result in Response::ValuePools"),
+        };
+
+        Ok(BlockchainInfo {
+            chain: chain_name(self.network).to_string(),
+            blocks: height.0,
+            bestblockhash: tip.hash().to_string(),
+            difficulty: difficulty(self.network, tip.header.bits),
+            value_pools: vec![
+                ValuePoolObject::new("transparent", pools.transparent),
+                ValuePoolObject::new("sprout", pools.sprout),
+                ValuePoolObject::new("sapling", pools.sapling),
+                ValuePoolObject::new("orchard", pools.orchard),
+                ValuePoolObject::new("lockbox", pools.deferred),
+            ],
+        })
+    }
+
+    /// Returns the hash of the block at `height` in the best chain.
+    pub(super) async fn get_block_hash(&self, height: u32) -> Result<String, Error> {
+        self.best_chain_block(BlockHeight(height))
+            .await?
+            .map(|block| block.hash().to_string())
+            .ok_or_else(|| Error::invalid_parameter("Block height out of range"))
+    }
+
+    /// Returns the header of the block with `hash`, as a JSON object if
+    /// `verbose` is true, or as hex otherwise.
+    pub(super) async fn get_block_header(&self, hash: &str, verbose: bool) -> Result<Value, Error> {
+        let hash: BlockHeaderHash = hash
+            .parse()
+            .map_err(|_| Error::invalid_parameter("hash must be a hexadecimal string"))?;
+        let block = self.block_by_hash(hash).await?;
+
+        if verbose {
+            result(self.block_header_object(&block).await?)
+        } else {
+            result(hex::encode(serialize(&block.header)))
+        }
+    }
+
+    /// Returns the block with `hash_or_height`, as hex if `verbosity` is 0, as
+    /// a JSON object with transaction IDs if `verbosity` is 1, or as a JSON
+    /// object with decoded transactions if `verbosity` is 2.
+    ///
+    /// Heights are looked up in the best chain.
+    pub(super) async fn get_block(
+        &self,
+        hash_or_height: &str,
+        verbosity: u8,
+    ) -> Result<Value, Error> {
+        let block = if hash_or_height.len() < 2 * 32 {
+            let height: BlockHeight = hash_or_height
+                .parse()
+                .map_err(|_| Error::invalid_parameter("Block height out of range"))?;
+            self.best_chain_block(height)
+                .await?
+                .ok_or_else(|| Error::invalid_parameter("Block height out of range"))?
+        } else {
+            let hash: BlockHeaderHash = hash_or_height
+                .parse()
+                .map_err(|_| Error::invalid_parameter("hash must be a hexadecimal string"))?;
+            self.block_by_hash(hash).await?
+        };
+
+        let bytes = serialize(&*block);
+        let tx = match verbosity {
+            0 => return result(hex::encode(bytes)),
+            1 => block
+                .transactions
+                .iter()
+                .map(|transaction| {
+                    BlockTransaction::Hash(TransactionHash::from(&**transaction).to_string())
+                })
+                .collect(),
+            2 => block
+                .transactions
+                .iter()
+                .map(|transaction| {
+                    BlockTransaction::Object(Box::new(transaction_object(
+                        self.network,
+                        transaction,
+                    )))
+                })
+                .collect(),
+            _ => return Err(Error::invalid_parameter("Invalid verbosity value")),
+        };
+
+        result(BlockObject {
+            header: self.block_header_object(&block).await?,
+            size: bytes.len(),
+            tx,
+        })
+    }
+
+    /// Returns the block with `hash`.
+    pub(super) async fn block_by_hash(&self, hash: BlockHeaderHash) -> Result<Arc<Block>, Error> {
+        match self.state_request(zs::Request::GetBlock { hash }).await {
+            Ok(zs::Response::Block { block }) => Ok(block),
+            Ok(_) => unreachable!("GetBlock request can only result in Response::Block"),
+            Err(_) => Err(Error::new(Error::INVALID_ADDRESS_OR_KEY, "Block not found")),
+        }
+    }
+
+    /// Returns the verbose JSON form of the header of `block`.
+    async fn block_header_object(&self, block: &Block) -> Result<BlockHeaderObject, Error> {
+        let hash = block.hash();
+        let header = &block.header;
+        let height = coinbase_height(block);
+        let tip_height = coinbase_height(&*self.tip().await?);
+
+        let is_best_chain = self
+            .best_chain_block(height)
+            .await?
+            .map(|best| best.hash() == hash)
+            .unwrap_or(false);
+        let (confirmations, nextblockhash) = if is_best_chain {
+            let next = match height.next() {
+                Some(next_height) => self.best_chain_block(next_height).await?,
+                None => None,
+            };
+            (
+                tip_height - height + 1,
+                next.map(|next| next.hash().to_string()),
+            )
+        } else {
+            (-1, None)
+        };
+
+        Ok(BlockHeaderObject {
+            hash: hash.to_string(),
+            confirmations,
+            height: height.0,
+            version: header.version,
+            merkleroot: reversed_hex(header.merkle_root_hash.0),
+            finalsaplingroot: final_sapling_root(self.network, block),
+            time: header.time.timestamp(),
+            nonce: reversed_hex(header.nonce),
+            solution: hex::encode(&header.solution.0[..]),
+            bits: format!("{:08x}", header.bits),
+            difficulty: difficulty(self.network, header.bits),
+            previousblockhash: if height.0 > 0 {
+                Some(header.previous_block_hash.to_string())
+            } else {
+                None
+            },
+            nextblockhash,
+        })
+    }
+
+    /// Returns the block at `height` in the best chain, or `None` if the
+    /// best chain is shorter than `height`.
+    pub(super) async fn best_chain_block(
+        &self,
+        height: BlockHeight,
+    ) -> Result<Option<Arc<Block>>, Error> {
+        if height > coinbase_height(&*self.tip().await?) {
+            return Ok(None);
+        }
+
+        match self
+            .state_request(zs::Request::GetBlockByHeight { height })
+            .await?
+        {
+            zs::Response::Block { block } => Ok(Some(block)),
+            _ => unreachable!("GetBlockByHeight request can only result in Response::Block"),
+        }
+    }
+}
+
+/// Returns `value`, serialized in the Zcash format.
+pub(super) fn serialize<T: ZcashSerialize>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value
+        .zcash_serialize(&mut bytes)
+        .expect("serialization into a vec can't fail");
+    bytes
+}
+
+/// Returns the height of `block`, which must be in the state.
+pub(super) fn coinbase_height(block: &Block) -> BlockHeight {
+    block
+        .coinbase_height()
+        .expect("blocks in the state have a coinbase height")
+}
+
+/// Returns the final Sapling root committed to by the header of `block`, as
+/// hex, or `None` if the header at its height doesn't commit to the root.
+///
+/// Headers only commit to the final Sapling root from Sapling activation
+/// until Heartwood activation. Zebra doesn't keep a note commitment tree yet,
+/// so it can't calculate the roots of other blocks.
+pub(super) fn final_sapling_root(network: Network, block: &Block) -> Option<String> {
+    match zebra_consensus::parameters::block_commitment(
+        network,
+        coinbase_height(block),
+        block.header.commitment_bytes,
+    ) {
+        Ok(Commitment::FinalSaplingRoot(root)) => Some(reversed_hex(root.0)),
+        _ => None,
+    }
+}
+
+/// Returns the `zcashd` name of `network`: `main` or `test`.
+pub(super) fn chain_name(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => "main",
+        Network::Testnet => "test",
+    }
+}
+
+/// Returns the difficulty of the compact threshold `bits` on `network`, as a
+/// multiple of the minimum difficulty.
+///
+/// Uses the same floating-point calculation as `zcashd`.
+pub(super) fn difficulty(network: Network, bits: u32) -> f64 {
+    let pow_limit_bits: u32 = match network {
+        Network::Mainnet => 0x1f07_ffff,
+        Network::Testnet => 0x2007_ffff,
+    };
+
+    let mut shift = (bits >> 24) & 0xff;
+    let pow_limit_shift = (pow_limit_bits >> 24) & 0xff;
+    let mut difficulty =
+        f64::from(pow_limit_bits & 0x00ff_ffff) / f64::from((bits & 0x00ff_ffff).max(1));
+
+    while shift < pow_limit_shift {
+        difficulty *= 256.0;
+        shift += 1;
+    }
+    while shift > pow_limit_shift {
+        difficulty /= 256.0;
+        shift -= 1;
+    }
+
+    difficulty
+}
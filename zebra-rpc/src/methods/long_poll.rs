@@ -0,0 +1,81 @@
+//! Long polling for best chain tip changes, and the `waitfornewblock`
+//! method.
+//!
+//! Long polls can wait for a long time, so they have their own limit, rather
+//! than using up the server's parallel request limit.
+
+use std::time::Duration;
+
+use tower::Service;
+
+use zebra_chain::block::BlockHeaderHash;
+use zebra_state as zs;
+
+use super::{chain::coinbase_height, BestBlockHeightAndHash, BoxError, Rpc};
+use crate::Error;
+
+/// The methods that wait for chain tip changes.
+const LONG_POLL_METHODS: &[&str] = &["waitfornewblock"];
+
+/// Returns true if `method` is a long poll.
+pub(crate) fn is_long_poll(method: &str) -> bool {
+    LONG_POLL_METHODS.contains(&method)
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Waits for the best chain tip to change, for up to `timeout_ms`
+    /// milliseconds, then returns the height and hash of the current tip.
+    ///
+    /// A timeout of zero waits until the tip changes.
+    pub(super) async fn wait_for_new_block(
+        &self,
+        timeout_ms: u64,
+    ) -> Result<BestBlockHeightAndHash, Error> {
+        let timeout = match timeout_ms {
+            0 => None,
+            timeout_ms => Some(Duration::from_millis(timeout_ms)),
+        };
+
+        let tip = self.tip().await?.hash();
+        let hash = self.wait_for_tip_change(tip, timeout).await?;
+        let block = self.block_by_hash(hash).await?;
+
+        Ok(BestBlockHeightAndHash {
+            height: coinbase_height(&block).0,
+            hash: hash.to_string(),
+        })
+    }
+
+    /// Waits until the best chain tip is not `tip`, or until `timeout`
+    /// elapses, then returns the hash of the current tip.
+    ///
+    /// If `timeout` is `None`, waits until the tip changes.
+    pub(super) async fn wait_for_tip_change(
+        &self,
+        tip: BlockHeaderHash,
+        timeout: Option<Duration>,
+    ) -> Result<BlockHeaderHash, Error> {
+        let changed = self.state_request(zs::Request::AwaitTipChange { tip });
+        let response = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, changed).await {
+                Ok(response) => response?,
+                // The tip didn't change
+                Err(_) => return Ok(tip),
+            },
+            None => changed.await?,
+        };
+
+        match response {
+            zs::Response::Tip { hash } => Ok(hash),
+            _ => unreachable!("AwaitTipChange request can only result in Response::Tip"),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Mining information methods: `getdifficulty`, `getnetworksolps`,
+//! `getnetworkhashps`, and `getmininginfo`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::Service;
+
+use zebra_chain::{types::BlockHeight, work::CompactDifficulty, Network};
+use zebra_state as zs;
+
+use super::{
+    chain::{chain_name, coinbase_height, difficulty},
+    BoxError, Rpc,
+};
+use crate::Error;
+
+/// The default number of blocks used to estimate the network solution rate.
+pub(super) const DEFAULT_SOLPS_BLOCKS: i32 = 120;
+
+/// The maximum number of blocks used to estimate the network solution rate.
+const MAX_SOLPS_BLOCKS: i32 = 1000;
+
+/// The number of blocks used to estimate the network solution rate, when
+/// the requested number of blocks is not positive.
+///
+/// This is the difficulty averaging window, `nPowAveragingWindow` in
+/// `zcashd`.
+const POW_AVERAGING_WINDOW: u32 = 17;
+
+/// The result of `getmininginfo`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct MiningInfo {
+    /// The height of the best chain tip.
+    pub blocks: u32,
+    /// The difficulty of the best chain tip, as a multiple of the minimum
+    /// difficulty.
+    pub difficulty: f64,
+    /// The estimated network solution rate over the default number of
+    /// blocks, in solutions per second.
+    pub networksolps: u64,
+    /// The same as `networksolps`. Kept for compatibility with older
+    /// clients.
+    pub networkhashps: u64,
+    /// The network name, `main` or `test`.
+    pub chain: String,
+    /// Is this node on the test network?
+    pub testnet: bool,
+    /// Mining warnings. Zebra doesn't have mining warnings, so this is
+    /// always empty.
+    pub errors: String,
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns the difficulty of the best chain tip, as a multiple of the
+    /// minimum difficulty.
+    pub(super) async fn get_difficulty(&self) -> Result<f64, Error> {
+        Ok(difficulty(self.network, self.tip().await?.header.bits))
+    }
+
+    /// Returns the estimated network solution rate, in solutions per second.
+    ///
+    /// The estimate is the work done in the `blocks` blocks before the best
+    /// chain block at `height`, divided by the time between the earliest and
+    /// latest of those blocks. If `blocks` is not positive, uses the
+    /// difficulty averaging window. If `height` is negative or not below the
+    /// tip, uses the tip.
+    ///
+    /// Uses the same calculation as `zcashd`, so the estimate is zero when
+    /// there is only the genesis block, or when all the block times are
+    /// equal. Unlike `zcashd`, `blocks` is limited to `MAX_SOLPS_BLOCKS`,
+    /// because each block header is a separate state request.
+    pub(super) async fn get_network_sol_ps(&self, blocks: i32, height: i32) -> Result<u64, Error> {
+        if blocks > MAX_SOLPS_BLOCKS {
+            return Err(Error::invalid_parameter(format!(
+                "blocks must be at most {}",
+                MAX_SOLPS_BLOCKS
+            )));
+        }
+
+        let tip_height = coinbase_height(&*self.tip().await?);
+        let end = if height >= 0 && (height as u32) < tip_height.0 {
+            height as u32
+        } else {
+            tip_height.0
+        };
+        let blocks = if blocks > 0 {
+            blocks as u32
+        } else {
+            POW_AVERAGING_WINDOW
+        };
+        let start = end - blocks.min(end);
+
+        let mut min_time = i64::MAX;
+        let mut max_time = i64::MIN;
+        let mut work: u128 = 0;
+        for height in start..=end {
+            let header = match self
+                .state_request(zs::Request::GetBlockHeaderByHeight {
+                    height: BlockHeight(height),
+                })
+                .await?
+            {
+                zs::Response::BlockHeader { header } => header,
+                _ => unreachable!(
+                    "GetBlockHeaderByHeight request can only result in Response::BlockHeader"
+                ),
+            };
+
+            let time = header.time.timestamp();
+            min_time = min_time.min(time);
+            max_time = max_time.max(time);
+
+            // The work of the first block was done before the window starts
+            if height > start {
+                work += CompactDifficulty(header.bits)
+                    .to_work()
+                    .ok_or_else(|| {
+                        Error::new(Error::MISC_ERROR, "Invalid difficulty threshold in chain")
+                    })?
+                    .as_u128();
+            }
+        }
+
+        if min_time == max_time {
+            return Ok(0);
+        }
+
+        Ok((work as f64 / (max_time - min_time) as f64) as u64)
+    }
+
+    /// Returns the height, difficulty, and estimated solution rate of the
+    /// best chain.
+    pub(super) async fn get_mining_info(&self) -> Result<MiningInfo, Error> {
+        let tip = self.tip().await?;
+        let networksolps = self.get_network_sol_ps(DEFAULT_SOLPS_BLOCKS, -1).await?;
+
+        Ok(MiningInfo {
+            blocks: coinbase_height(&tip).0,
+            difficulty: difficulty(self.network, tip.header.bits),
+            networksolps,
+            networkhashps: networksolps,
+            chain: chain_name(self.network).to_string(),
+            testnet: self.network == Network::Testnet,
+            errors: String::new(),
+        })
+    }
+}
@@ -0,0 +1,231 @@
+//! Network information methods: `getnetworkinfo` and `getpeerinfo`.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::Service;
+
+use zebra_chain::types::amount::COIN;
+use zebra_consensus::mempool as mempool_config;
+use zebra_network::{
+    types::{ConnectionDirection, PeerInfo},
+    AddressBook,
+};
+use zebra_state as zs;
+
+use super::{BoxError, Rpc};
+use crate::Error;
+
+/// The network configuration and connected peers, used by the network
+/// information methods.
+#[derive(Clone)]
+pub(super) struct NetworkDiagnostics {
+    /// The user agent that we advertise to peers.
+    user_agent: String,
+    /// The address that we listen for peer connections on.
+    listen_addr: SocketAddr,
+    /// The minimum fee rate for relayed transactions, in zatoshis per 1000
+    /// bytes.
+    min_relay_fee_per_kb: u64,
+    /// The address book, which holds diagnostic information about our peer
+    /// connections.
+    address_book: Arc<Mutex<AddressBook>>,
+}
+
+impl NetworkDiagnostics {
+    /// Returns the network diagnostics for a node with `network_config` and
+    /// `mempool_config`, which is connected to the peers in `address_book`.
+    pub(super) fn new(
+        network_config: &zebra_network::Config,
+        mempool_config: &mempool_config::Config,
+        address_book: Arc<Mutex<AddressBook>>,
+    ) -> NetworkDiagnostics {
+        NetworkDiagnostics {
+            user_agent: network_config.user_agent.clone(),
+            listen_addr: network_config.listen_addr,
+            min_relay_fee_per_kb: mempool_config.min_relay_fee_per_kb,
+            address_book,
+        }
+    }
+
+    /// Returns the diagnostic information for our current peer connections.
+    fn connected_peers(&self) -> Vec<PeerInfo> {
+        self.address_book
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .connected_peers()
+            .collect()
+    }
+}
+
+/// The result of `getnetworkinfo`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct NetworkInfo {
+    /// The Zebra version, in the `zcashd` client version format.
+    pub version: u64,
+    /// The user agent that this node advertises to peers.
+    pub subversion: String,
+    /// The network protocol version.
+    pub protocolversion: u32,
+    /// The services that this node offers to peers, as hex.
+    pub localservices: String,
+    /// The time offset to peers, in seconds. Zebra doesn't adjust its clock
+    /// using peer times, so this is always zero.
+    pub timeoffset: i64,
+    /// The number of connected peers.
+    pub connections: usize,
+    /// The minimum fee rate for relayed transactions, in ZEC per 1000 bytes.
+    pub relayfee: f64,
+    /// The addresses that this node listens for peer connections on.
+    pub localaddresses: Vec<LocalAddressObject>,
+    /// Network warnings. Zebra doesn't have network warnings, so this is
+    /// always empty.
+    pub warnings: String,
+}
+
+/// A local address in the result of `getnetworkinfo`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct LocalAddressObject {
+    /// The IP address.
+    pub address: String,
+    /// The port.
+    pub port: u16,
+    /// How likely peers are to reach this address. Zebra only knows its
+    /// configured address, so this is always 1.
+    pub score: u32,
+}
+
+/// A connected peer in the result of `getpeerinfo`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct PeerInfoObject {
+    /// The index of the peer in this result.
+    pub id: usize,
+    /// The peer's IP address and port.
+    pub addr: String,
+    /// The services advertised by the peer, as hex.
+    pub services: String,
+    /// When we last received a message from the peer, in seconds since the
+    /// Unix epoch.
+    pub lastrecv: i64,
+    /// When the connection was opened, in seconds since the Unix epoch.
+    pub conntime: i64,
+    /// The protocol version negotiated with the peer.
+    pub version: u32,
+    /// The user agent sent by the peer.
+    pub subver: String,
+    /// Did the peer connect to us?
+    pub inbound: bool,
+    /// The block height advertised by the peer when it connected.
+    pub startingheight: u32,
+    /// Is the connection a short-lived feeler connection?
+    pub feeler: bool,
+    /// The number of our requests to the peer that have failed without
+    /// closing the connection.
+    pub failures: u32,
+    /// Is the peer currently handling one of our requests?
+    pub inflight: bool,
+}
+
+impl From<(usize, PeerInfo)> for PeerInfoObject {
+    fn from((id, info): (usize, PeerInfo)) -> Self {
+        PeerInfoObject {
+            id,
+            addr: info.addr.to_string(),
+            services: format!("{:016x}", info.services.bits()),
+            lastrecv: info.last_seen.timestamp(),
+            conntime: info.connected_at.timestamp(),
+            version: info.version.0,
+            subver: info.user_agent,
+            inbound: info.direction == ConnectionDirection::Inbound,
+            startingheight: info.start_height.0,
+            feeler: info.direction == ConnectionDirection::Feeler,
+            failures: info.failures,
+            inflight: info.awaiting_response,
+        }
+    }
+}
+
+/// Returns Zebra's version in the `zcashd` client version format,
+/// `1_000_000 * major + 10_000 * minor + 100 * patch + build`.
+///
+/// `zcashd` uses build numbers below 50 for pre-releases, and 50 for
+/// releases.
+fn client_version() -> u64 {
+    let part = |part: &str| -> u64 { part.parse().expect("cargo version parts are numbers") };
+    let build = if env!("CARGO_PKG_VERSION_PRE").is_empty() {
+        50
+    } else {
+        0
+    };
+
+    1_000_000 * part(env!("CARGO_PKG_VERSION_MAJOR"))
+        + 10_000 * part(env!("CARGO_PKG_VERSION_MINOR"))
+        + 100 * part(env!("CARGO_PKG_VERSION_PATCH"))
+        + build
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns the network diagnostics, or an error if they are not
+    /// available.
+    fn network_diagnostics(&self) -> Result<&NetworkDiagnostics, Error> {
+        self.network_diagnostics
+            .as_ref()
+            .ok_or_else(|| Error::new(Error::MISC_ERROR, "Network information is not available"))
+    }
+
+    /// Returns this node's version, network configuration, and the number of
+    /// connected peers.
+    pub(super) fn get_network_info(&self) -> Result<NetworkInfo, Error> {
+        let diagnostics = self.network_diagnostics()?;
+
+        // Peers can't connect to an unspecified address
+        let localaddresses = if diagnostics.listen_addr.ip().is_unspecified() {
+            Vec::new()
+        } else {
+            vec![LocalAddressObject {
+                address: diagnostics.listen_addr.ip().to_string(),
+                port: diagnostics.listen_addr.port(),
+                score: 1,
+            }]
+        };
+
+        Ok(NetworkInfo {
+            version: client_version(),
+            subversion: diagnostics.user_agent.clone(),
+            protocolversion: zebra_network::CURRENT_VERSION.0,
+            localservices: format!(
+                "{:016x}",
+                zebra_network::types::PeerServices::NODE_NETWORK.bits()
+            ),
+            timeoffset: 0,
+            connections: diagnostics.connected_peers().len(),
+            relayfee: diagnostics.min_relay_fee_per_kb as f64 / COIN as f64,
+            localaddresses,
+            warnings: String::new(),
+        })
+    }
+
+    /// Returns diagnostic information for each connected peer, ordered by
+    /// address.
+    pub(super) fn get_peer_info(&self) -> Result<Vec<PeerInfoObject>, Error> {
+        Ok(self
+            .network_diagnostics()?
+            .connected_peers()
+            .into_iter()
+            .enumerate()
+            .map(PeerInfoObject::from)
+            .collect())
+    }
+}
@@ -0,0 +1,282 @@
+//! Transparent script decoding, in the `zcashd` format.
+
+use zebra_chain::types::{script::ScriptKind, Script};
+
+/// The largest push opcode that pushes its length directly.
+const OP_PUSHBYTES_75: u8 = 0x4b;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+
+/// Returns the human-readable form of `script`.
+///
+/// Small pushes are shown as numbers, and other pushes as hex. If
+/// `decode_sighash` is true, the hash type of signature pushes is shown by
+/// name, like `zcashd` does for input scripts.
+pub(super) fn asm(script: &Script, decode_sighash: bool) -> String {
+    let mut bytes = &script.0[..];
+    let mut words = Vec::new();
+
+    while let Some((&opcode, rest)) = bytes.split_first() {
+        bytes = rest;
+
+        let push_len = match opcode {
+            0x01..=OP_PUSHBYTES_75 => Some(usize::from(opcode)),
+            OP_PUSHDATA1 => take_len(&mut bytes, 1),
+            OP_PUSHDATA2 => take_len(&mut bytes, 2),
+            OP_PUSHDATA4 => take_len(&mut bytes, 4),
+            _ => {
+                words.push(opcode_name(opcode));
+                continue;
+            }
+        };
+
+        let data = match push_len {
+            Some(len) if len <= bytes.len() => {
+                let (data, rest) = bytes.split_at(len);
+                bytes = rest;
+                data
+            }
+            _ => {
+                words.push("[error]".to_owned());
+                break;
+            }
+        };
+
+        words.push(if data.len() <= 4 {
+            script_num(data).to_string()
+        } else if decode_sighash {
+            signature_asm(data)
+        } else {
+            hex::encode(data)
+        });
+    }
+
+    words.join(" ")
+}
+
+/// Returns the `zcashd` name of the standard template `kind`.
+pub(super) fn kind_name(kind: &ScriptKind) -> &'static str {
+    match kind {
+        ScriptKind::PayToPublicKey { .. } => "pubkey",
+        ScriptKind::PayToPublicKeyHash { .. } => "pubkeyhash",
+        ScriptKind::PayToScriptHash { .. } => "scripthash",
+        ScriptKind::NullData => "nulldata",
+        ScriptKind::NonStandard => "nonstandard",
+    }
+}
+
+/// Reads a little-endian push length of `size` bytes from the start of
+/// `bytes`, or returns `None` if `bytes` is too short.
+fn take_len(bytes: &mut &[u8], size: usize) -> Option<usize> {
+    if bytes.len() < size {
+        return None;
+    }
+
+    let (len, rest) = bytes.split_at(size);
+    *bytes = rest;
+
+    Some(
+        len.iter()
+            .rev()
+            .fold(0, |acc, &byte| (acc << 8) | usize::from(byte)),
+    )
+}
+
+/// Returns `data` as a minimally-encoded script number.
+///
+/// Script numbers are little-endian, with a sign bit in the last byte.
+fn script_num(data: &[u8]) -> i64 {
+    let mut value = data
+        .iter()
+        .rev()
+        .fold(0i64, |acc, &byte| (acc << 8) | i64::from(byte));
+
+    match data.last() {
+        Some(last) if last & 0x80 != 0 => {
+            value &= !(0x80 << (8 * (data.len() - 1)));
+            -value
+        }
+        _ => value,
+    }
+}
+
+/// Returns `data` as hex, with the hash type shown by name if `data` looks
+/// like a DER signature.
+fn signature_asm(data: &[u8]) -> String {
+    let (hash_type, signature) = data.split_last().expect("signatures are not empty");
+    let looks_like_der = signature.len() >= 8
+        && signature[0] == 0x30
+        && usize::from(signature[1]) == signature.len() - 2;
+
+    let hash_type = match hash_type {
+        0x01 => "ALL",
+        0x02 => "NONE",
+        0x03 => "SINGLE",
+        0x81 => "ALL|ANYONECANPAY",
+        0x82 => "NONE|ANYONECANPAY",
+        0x83 => "SINGLE|ANYONECANPAY",
+        _ => return hex::encode(data),
+    };
+
+    if looks_like_der {
+        format!("{}[{}]", hex::encode(signature), hash_type)
+    } else {
+        hex::encode(data)
+    }
+}
+
+/// Returns the name of a non-push `opcode`.
+fn opcode_name(opcode: u8) -> String {
+    let name = match opcode {
+        0x00 => return "0".to_owned(),
+        0x4f => return "-1".to_owned(),
+        0x51..=0x60 => return (opcode - 0x50).to_string(),
+        0x50 => "OP_RESERVED",
+        0x61 => "OP_NOP",
+        0x62 => "OP_VER",
+        0x63 => "OP_IF",
+        0x64 => "OP_NOTIF",
+        0x65 => "OP_VERIF",
+        0x66 => "OP_VERNOTIF",
+        0x67 => "OP_ELSE",
+        0x68 => "OP_ENDIF",
+        0x69 => "OP_VERIFY",
+        0x6a => "OP_RETURN",
+        0x6b => "OP_TOALTSTACK",
+        0x6c => "OP_FROMALTSTACK",
+        0x6d => "OP_2DROP",
+        0x6e => "OP_2DUP",
+        0x6f => "OP_3DUP",
+        0x70 => "OP_2OVER",
+        0x71 => "OP_2ROT",
+        0x72 => "OP_2SWAP",
+        0x73 => "OP_IFDUP",
+        0x74 => "OP_DEPTH",
+        0x75 => "OP_DROP",
+        0x76 => "OP_DUP",
+        0x77 => "OP_NIP",
+        0x78 => "OP_OVER",
+        0x79 => "OP_PICK",
+        0x7a => "OP_ROLL",
+        0x7b => "OP_ROT",
+        0x7c => "OP_SWAP",
+        0x7d => "OP_TUCK",
+        0x7e => "OP_CAT",
+        0x7f => "OP_SUBSTR",
+        0x80 => "OP_LEFT",
+        0x81 => "OP_RIGHT",
+        0x82 => "OP_SIZE",
+        0x83 => "OP_INVERT",
+        0x84 => "OP_AND",
+        0x85 => "OP_OR",
+        0x86 => "OP_XOR",
+        0x87 => "OP_EQUAL",
+        0x88 => "OP_EQUALVERIFY",
+        0x89 => "OP_RESERVED1",
+        0x8a => "OP_RESERVED2",
+        0x8b => "OP_1ADD",
+        0x8c => "OP_1SUB",
+        0x8d => "OP_2MUL",
+        0x8e => "OP_2DIV",
+        0x8f => "OP_NEGATE",
+        0x90 => "OP_ABS",
+        0x91 => "OP_NOT",
+        0x92 => "OP_0NOTEQUAL",
+        0x93 => "OP_ADD",
+        0x94 => "OP_SUB",
+        0x95 => "OP_MUL",
+        0x96 => "OP_DIV",
+        0x97 => "OP_MOD",
+        0x98 => "OP_LSHIFT",
+        0x99 => "OP_RSHIFT",
+        0x9a => "OP_BOOLAND",
+        0x9b => "OP_BOOLOR",
+        0x9c => "OP_NUMEQUAL",
+        0x9d => "OP_NUMEQUALVERIFY",
+        0x9e => "OP_NUMNOTEQUAL",
+        0x9f => "OP_LESSTHAN",
+        0xa0 => "OP_GREATERTHAN",
+        0xa1 => "OP_LESSTHANOREQUAL",
+        0xa2 => "OP_GREATERTHANOREQUAL",
+        0xa3 => "OP_MIN",
+        0xa4 => "OP_MAX",
+        0xa5 => "OP_WITHIN",
+        0xa6 => "OP_RIPEMD160",
+        0xa7 => "OP_SHA1",
+        0xa8 => "OP_SHA256",
+        0xa9 => "OP_HASH160",
+        0xaa => "OP_HASH256",
+        0xab => "OP_CODESEPARATOR",
+        0xac => "OP_CHECKSIG",
+        0xad => "OP_CHECKSIGVERIFY",
+        0xae => "OP_CHECKMULTISIG",
+        0xaf => "OP_CHECKMULTISIGVERIFY",
+        0xb0 => "OP_NOP1",
+        0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+        0xb2 => "OP_NOP3",
+        0xb3 => "OP_NOP4",
+        0xb4 => "OP_NOP5",
+        0xb5 => "OP_NOP6",
+        0xb6 => "OP_NOP7",
+        0xb7 => "OP_NOP8",
+        0xb8 => "OP_NOP9",
+        0xb9 => "OP_NOP10",
+        _ => "OP_UNKNOWN",
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_scripts() {
+        let p2sh = Script(hex::decode("a9147d46a730d31f97b1930d3368a967c309bd4d136a87").unwrap());
+        assert_eq!(kind_name(&p2sh.kind()), "scripthash");
+        assert_eq!(
+            asm(&p2sh, false),
+            "OP_HASH160 7d46a730d31f97b1930d3368a967c309bd4d136a OP_EQUAL"
+        );
+
+        let mut p2pk = vec![33];
+        p2pk.extend_from_slice(&[0x02; 33]);
+        p2pk.push(0xac);
+        let p2pk = Script(p2pk);
+        assert_eq!(kind_name(&p2pk.kind()), "pubkey");
+        assert!(asm(&p2pk, false).ends_with(" OP_CHECKSIG"));
+
+        let null_data = Script(vec![0x6a, 0x02, 0xff, 0x00]);
+        assert_eq!(kind_name(&null_data.kind()), "nulldata");
+        assert_eq!(asm(&null_data, false), "OP_RETURN 255");
+
+        let truncated = Script(vec![0x76, 0x05, 0x00]);
+        assert_eq!(kind_name(&truncated.kind()), "nonstandard");
+        assert_eq!(asm(&truncated, false), "OP_DUP [error]");
+    }
+
+    #[test]
+    fn script_numbers() {
+        assert_eq!(script_num(&[]), 0);
+        assert_eq!(script_num(&[0x51]), 0x51);
+        assert_eq!(script_num(&[0x81]), -1);
+        assert_eq!(script_num(&[0xff, 0x00]), 255);
+        assert_eq!(script_num(&[0x00, 0x80]), 0);
+        assert_eq!(script_num(&[0x01, 0x00, 0x00, 0x80]), -1);
+    }
+
+    #[test]
+    fn input_signatures() {
+        let mut signature = vec![0x30, 6, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01];
+        signature.push(0x01);
+
+        let mut script = vec![signature.len() as u8];
+        script.extend_from_slice(&signature);
+        let script = Script(script);
+
+        assert_eq!(asm(&script, true), "3006020101020101[ALL]");
+        assert_eq!(asm(&script, false), "300602010102010101");
+    }
+}
@@ -0,0 +1,154 @@
+//! Shielded pool methods: `z_listanchors`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower::Service;
+
+use zebra_chain::types::BlockHeight;
+use zebra_state as zs;
+
+use super::{
+    chain::{coinbase_height, final_sapling_root},
+    BoxError, Rpc,
+};
+use crate::Error;
+
+/// The default number of recent anchors returned by `z_listanchors`.
+pub(super) const DEFAULT_ANCHOR_COUNT: u32 = 10;
+
+/// The maximum number of recent anchors returned by `z_listanchors`.
+const MAX_ANCHOR_COUNT: u32 = 100;
+
+/// The result of `z_listanchors`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct AnchorList {
+    /// The height of the best chain tip.
+    pub height: u32,
+    /// The hash of the best chain tip.
+    pub hash: String,
+    /// The Sprout note commitment tree, after the tip.
+    pub sprout: TreeObject,
+    /// The Sapling note commitment tree, after the tip.
+    pub sapling: TreeObject,
+    /// The Orchard note commitment tree, after the tip.
+    ///
+    /// Zebra doesn't support Orchard transactions yet, so this tree is always
+    /// empty.
+    pub orchard: TreeObject,
+    /// The anchors of the most recent best chain blocks, starting at the tip.
+    pub anchors: Vec<AnchorObject>,
+}
+
+/// A note commitment tree in the result of `z_listanchors`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct TreeObject {
+    /// The number of note commitments in the tree.
+    pub size: u64,
+    /// The final root of the tree.
+    ///
+    /// Only Sapling and Blossom block headers commit to the Sapling root, and
+    /// Zebra doesn't track the trees yet, so other roots are omitted.
+    #[serde(rename = "finalRoot", default, skip_serializing_if = "Option::is_none")]
+    pub final_root: Option<String>,
+}
+
+/// A recent block's anchor in the result of `z_listanchors`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct AnchorObject {
+    /// The height of the block.
+    pub height: u32,
+    /// The hash of the block.
+    pub hash: String,
+    /// The final Sapling root after the block, which later Sapling spends can
+    /// use as their anchor.
+    ///
+    /// Omitted if the block header doesn't commit to the root, which includes
+    /// every block after Heartwood activation.
+    #[serde(
+        rename = "saplingRoot",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sapling_root: Option<String>,
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns the note commitment tree sizes and final roots after the best
+    /// chain tip, and the anchors of the `count` most recent best chain
+    /// blocks.
+    ///
+    /// Zebra doesn't track the note commitment trees yet, so the roots are
+    /// read from block headers. Only the headers from Sapling activation until
+    /// Heartwood activation commit to the final Sapling root, so the Sapling
+    /// roots of other blocks, and all the Sprout and Orchard roots, are
+    /// omitted.
+    pub(super) async fn z_list_anchors(&self, count: u32) -> Result<AnchorList, Error> {
+        if count == 0 || count > MAX_ANCHOR_COUNT {
+            return Err(Error::invalid_parameter(format!(
+                "count must be between 1 and {}",
+                MAX_ANCHOR_COUNT
+            )));
+        }
+
+        let tip = self.tip().await?;
+        let height = coinbase_height(&tip);
+        let sizes = match self
+            .state_request(zs::Request::GetTreeSizes { height })
+            .await?
+        {
+            zs::Response::TreeSizes(sizes) => sizes,
+            _ => unreachable!("GetTreeSizes request can only result in Response::TreeSizes"),
+        };
+
+        let mut anchors = Vec::new();
+        for anchor_height in (height.0.saturating_sub(count - 1)..=height.0).rev() {
+            let block = if anchor_height == height.0 {
+                tip.clone()
+            } else {
+                match self
+                    .state_request(zs::Request::GetBlockByHeight {
+                        height: BlockHeight(anchor_height),
+                    })
+                    .await?
+                {
+                    zs::Response::Block { block } => block,
+                    _ => {
+                        unreachable!("GetBlockByHeight request can only result in Response::Block")
+                    }
+                }
+            };
+
+            anchors.push(AnchorObject {
+                height: anchor_height,
+                hash: block.hash().to_string(),
+                sapling_root: final_sapling_root(self.network, &block),
+            });
+        }
+
+        Ok(AnchorList {
+            height: height.0,
+            hash: tip.hash().to_string(),
+            sprout: TreeObject {
+                size: sizes.sprout,
+                final_root: None,
+            },
+            sapling: TreeObject {
+                size: sizes.sapling,
+                final_root: final_sapling_root(self.network, &tip),
+            },
+            orchard: TreeObject {
+                size: sizes.orchard,
+                final_root: None,
+            },
+            anchors,
+        })
+    }
+}
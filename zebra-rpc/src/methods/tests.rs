@@ -0,0 +1,665 @@
+//! Tests for RPC methods.
+
+use std::{convert::TryInto, time::Duration};
+
+use serde_json::json;
+
+use zebra_chain::{
+    serialization::{ZcashDeserialize, ZcashSerialize},
+    transaction::{OutPoint, Transaction, TransactionHash, TransparentInput, TransparentOutput},
+    types::{amount::Amount, BlockHeight, LockTime, Script},
+};
+use zebra_test::vectors::{
+    BLOCK_MAINNET_1_BYTES, BLOCK_MAINNET_2_BYTES, BLOCK_MAINNET_GENESIS_BYTES,
+};
+
+use super::*;
+
+/// A mainnet P2PKH address.
+const P2PKH: &str = "t1bmMa1wJDFdbc2TiURQP5BbBz6jHjUBuHq";
+
+/// A mainnet P2SH address.
+const P2SH: &str = "t3Vz22vK5z2LcKEdg16Yv4FFneEL1zg9ojd";
+
+/// A mainnet Sapling address.
+const SAPLING: &str =
+    "zs1qqqqqqqqqqqqqqqqqrjq05nyfku05msvu49mawhg6kr0wwljahypwyk2h88z6975u563j8nfaxd";
+
+/// A mainnet unified address, with P2PKH, Sapling, and Orchard receivers.
+const UNIFIED: &str = "u1pg2aaph7jp8rpf6yhsza25722sg5fcn3vaca6ze27hqjw7jvvhhuxkpcg0ge9xh6drsgdkda8qjq5chpehkcpxf87rnjryjqwymdheptpvnljqqrjqzjwkc2ma6hcq666kgwfytxwac8eyex6ndgr6ezte66706e3vaqrd25dzvzkc69kw0jgywtd0cmq52q5lkw6uh7hyvzjse8ksx";
+
+/// The mainnet genesis block hash.
+const GENESIS_HASH: &str = "00040fe8ec8471911baa1db1266ea15dd06b4a8a5c453883c000b031973dce08";
+
+/// A mainnet Sprout address.
+const SPROUT: &str = "zcU1Cd6zYyZCd2VJF8yKgmzjxdiiU1rgTTjEwoN1CGUWCziPkUTXUjXmX7TMqdMNsTfuiGN1jQoVN4kGxUR4sAPN4XZ7pxb";
+
+#[tokio::test]
+async fn validateaddress() -> Result<(), Error> {
+    zebra_test::init();
+
+    let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+
+    let result = rpc.call("validateaddress", vec![json!(P2SH)]).await?;
+    assert_eq!(
+        result,
+        json!({
+            "isvalid": true,
+            "address": P2SH,
+            "scriptPubKey": "a9147d46a730d31f97b1930d3368a967c309bd4d136a87",
+            "isscript": true,
+        })
+    );
+
+    let result = rpc.call("validateaddress", vec![json!(P2PKH)]).await?;
+    assert_eq!(result["isvalid"], json!(true));
+    assert_eq!(result["isscript"], json!(false));
+
+    // Shielded addresses are only valid for z_validateaddress
+    let result = rpc.call("validateaddress", vec![json!(SAPLING)]).await?;
+    assert_eq!(result, json!({ "isvalid": false }));
+
+    // Addresses for other networks are invalid
+    let rpc = Rpc::new(Network::Testnet, zebra_state::in_memory::init());
+    let result = rpc.call("validateaddress", vec![json!(P2PKH)]).await?;
+    assert_eq!(result, json!({ "isvalid": false }));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn z_validateaddress() -> Result<(), Error> {
+    zebra_test::init();
+
+    let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+
+    let result = rpc.call("z_validateaddress", vec![json!(P2PKH)]).await?;
+    assert_eq!(
+        result,
+        json!({ "isvalid": true, "address": P2PKH, "type": "p2pkh" })
+    );
+
+    let result = rpc.call("z_validateaddress", vec![json!(SAPLING)]).await?;
+    assert_eq!(result["isvalid"], json!(true));
+    assert_eq!(result["type"], json!("sapling"));
+    assert_eq!(result["diversifier"], json!("0000000000000000000000"));
+    assert!(result.get("payingkey").is_none());
+
+    let result = rpc.call("z_validateaddress", vec![json!(SPROUT)]).await?;
+    assert_eq!(result["isvalid"], json!(true));
+    assert_eq!(result["type"], json!("sprout"));
+    assert!(result.get("diversifier").is_none());
+
+    let result = rpc.call("z_validateaddress", vec![json!(UNIFIED)]).await?;
+    assert_eq!(
+        result,
+        json!({ "isvalid": true, "address": UNIFIED, "type": "unified" })
+    );
+
+    for invalid in &[
+        "",
+        "not an address",
+        &SAPLING[..SAPLING.len() - 1],
+        &UNIFIED[..UNIFIED.len() - 1],
+    ] {
+        let result = rpc.call("z_validateaddress", vec![json!(invalid)]).await?;
+        assert_eq!(result, json!({ "isvalid": false }));
+    }
+
+    let rpc = Rpc::new(Network::Testnet, zebra_state::in_memory::init());
+    for other_network in &[SAPLING, UNIFIED] {
+        let result = rpc
+            .call("z_validateaddress", vec![json!(other_network)])
+            .await?;
+        assert_eq!(result, json!({ "isvalid": false }));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_errors() {
+    zebra_test::init();
+
+    let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+
+    let error = rpc.call("nonexistent", vec![]).await.unwrap_err();
+    assert_eq!(error.code, Error::METHOD_NOT_FOUND);
+
+    let error = rpc.call("validateaddress", vec![]).await.unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    let error = rpc
+        .call("validateaddress", vec![json!(1)])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::TYPE_ERROR);
+}
+
+/// Adds the mainnet genesis block and block 1 to `state`.
+async fn add_blocks<ZS>(state: &mut ZS) -> Result<(), BoxError>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>,
+{
+    for bytes in &[&BLOCK_MAINNET_GENESIS_BYTES[..], &BLOCK_MAINNET_1_BYTES[..]] {
+        let block = Arc::new(Block::zcash_deserialize(*bytes)?);
+        state
+            .ready_and()
+            .await?
+            .call(zs::Request::AddBlock {
+                block,
+                deferred_pool_contribution: Amount::zero(),
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chain_queries() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+
+    assert_eq!(rpc.call("getblockcount", vec![]).await?, json!(1));
+    assert_eq!(
+        rpc.call("getbestblockhash", vec![]).await?,
+        json!(block_1.hash().to_string())
+    );
+    assert_eq!(
+        rpc.call("getbestblockheightandhash", vec![]).await?,
+        json!({ "height": 1, "hash": block_1.hash().to_string() })
+    );
+    assert_eq!(
+        rpc.call("getblockhash", vec![json!(0)]).await?,
+        json!(GENESIS_HASH)
+    );
+
+    let error = rpc.call("getblockhash", vec![json!(2)]).await.unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn getblockchaininfo() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+
+    let info: BlockchainInfo =
+        serde_json::from_value(rpc.call("getblockchaininfo", vec![]).await?)?;
+    assert_eq!(info.chain, "main");
+    assert_eq!(info.blocks, 1);
+    assert_eq!(info.bestblockhash, block_1.hash().to_string());
+
+    let ids: Vec<&str> = info
+        .value_pools
+        .iter()
+        .map(|pool| pool.id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        vec!["transparent", "sprout", "sapling", "orchard", "lockbox"]
+    );
+
+    // The first two blocks only have coinbase outputs
+    let transparent_value: i64 = [&BLOCK_MAINNET_GENESIS_BYTES[..], &BLOCK_MAINNET_1_BYTES[..]]
+        .iter()
+        .map(|bytes| Block::zcash_deserialize(*bytes).unwrap())
+        .flat_map(|block| block.transactions)
+        .flat_map(|transaction| {
+            transaction
+                .outputs()
+                .map(|output| i64::from(output.value))
+                .collect::<Vec<_>>()
+        })
+        .sum();
+    assert_eq!(info.value_pools[0].chain_value_zat, transparent_value);
+    for pool in &info.value_pools[1..] {
+        assert_eq!(pool.chain_value_zat, 0, "{}", pool.id);
+        assert!(pool.monitored);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn mining_info() -> Result<(), BoxError> {
+    use zebra_chain::work::CompactDifficulty;
+
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+    let genesis = Block::zcash_deserialize(&BLOCK_MAINNET_GENESIS_BYTES[..])?;
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+
+    assert_eq!(rpc.call("getdifficulty", vec![]).await?, json!(1.0));
+
+    // The only work in the window is block 1
+    let work = CompactDifficulty(block_1.header.bits)
+        .to_work()
+        .unwrap()
+        .as_u128();
+    let seconds = block_1.header.time.timestamp() - genesis.header.time.timestamp();
+    let solps = (work as f64 / seconds as f64) as u64;
+    assert_eq!(rpc.call("getnetworksolps", vec![]).await?, json!(solps));
+    assert_eq!(
+        rpc.call("getnetworkhashps", vec![json!(0), json!(-1)])
+            .await?,
+        json!(solps)
+    );
+
+    // The genesis block has no previous blocks
+    assert_eq!(
+        rpc.call("getnetworksolps", vec![json!(120), json!(0)])
+            .await?,
+        json!(0)
+    );
+
+    let error = rpc
+        .call("getnetworksolps", vec![json!(1001)])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    let info: MiningInfo = serde_json::from_value(rpc.call("getmininginfo", vec![]).await?)?;
+    assert_eq!(info.blocks, 1);
+    assert!((info.difficulty - 1.0).abs() < f64::EPSILON);
+    assert_eq!(info.networksolps, solps);
+    assert_eq!(info.networkhashps, solps);
+    assert_eq!(info.chain, "main");
+    assert!(!info.testnet);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn z_listanchors() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+
+    // These blocks are before Sapling, and only have coinbase transactions
+    let list: AnchorList = serde_json::from_value(rpc.call("z_listanchors", vec![]).await?)?;
+    assert_eq!(list.height, 1);
+    assert_eq!(list.hash, block_1.hash().to_string());
+    for tree in &[&list.sprout, &list.sapling, &list.orchard] {
+        assert_eq!(tree.size, 0);
+        assert_eq!(tree.final_root, None);
+    }
+    assert_eq!(
+        list.anchors,
+        vec![
+            AnchorObject {
+                height: 1,
+                hash: block_1.hash().to_string(),
+                sapling_root: None,
+            },
+            AnchorObject {
+                height: 0,
+                hash: GENESIS_HASH.to_string(),
+                sapling_root: None,
+            },
+        ]
+    );
+
+    let list: AnchorList =
+        serde_json::from_value(rpc.call("z_listanchors", vec![json!(1)]).await?)?;
+    assert_eq!(list.anchors.len(), 1);
+
+    let error = rpc.call("z_listanchors", vec![json!(0)]).await.unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn getblockheader() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+
+    // Serialized headers are followed by the transaction count
+    let result = rpc
+        .call("getblockheader", vec![json!(GENESIS_HASH), json!(false)])
+        .await?;
+    assert_eq!(
+        result,
+        json!(hex::encode(&BLOCK_MAINNET_GENESIS_BYTES[..1487]))
+    );
+
+    let result = rpc
+        .call("getblockheader", vec![json!(GENESIS_HASH)])
+        .await?;
+    let header: BlockHeaderObject = serde_json::from_value(result)?;
+    assert_eq!(header.hash, GENESIS_HASH);
+    assert_eq!(header.confirmations, 2);
+    assert_eq!(header.height, 0);
+    assert_eq!(header.version, 4);
+    assert_eq!(header.bits, "1f07ffff");
+    assert!((header.difficulty - 1.0).abs() < f64::EPSILON);
+    assert_eq!(header.previousblockhash, None);
+    assert_eq!(
+        header.nextblockhash,
+        rpc.call("getblockhash", vec![json!(1)])
+            .await?
+            .as_str()
+            .map(str::to_owned)
+    );
+
+    let error = rpc
+        .call("getblockheader", vec![json!("00".repeat(32))])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::INVALID_ADDRESS_OR_KEY);
+
+    let error = rpc
+        .call("getblockheader", vec![json!("not a hash")])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn getblock() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+
+    let result = rpc.call("getblock", vec![json!("0"), json!(0)]).await?;
+    assert_eq!(result, json!(hex::encode(&BLOCK_MAINNET_GENESIS_BYTES[..])));
+
+    let result = rpc.call("getblock", vec![json!(GENESIS_HASH)]).await?;
+    let block: BlockObject = serde_json::from_value(result)?;
+    assert_eq!(block.header.hash, GENESIS_HASH);
+    assert_eq!(block.size, BLOCK_MAINNET_GENESIS_BYTES.len());
+    assert_eq!(block.tx.len(), 1);
+    assert!(matches!(block.tx[0], BlockTransaction::Hash(_)));
+
+    let result = rpc.call("getblock", vec![json!("1"), json!(2)]).await?;
+    assert_eq!(result["height"], json!(1));
+    assert_eq!(result["previousblockhash"], json!(GENESIS_HASH));
+
+    let coinbase = &result["tx"][0];
+    assert_eq!(coinbase["version"], json!(1));
+    assert_eq!(coinbase["overwintered"], json!(false));
+    assert_eq!(
+        coinbase["vin"],
+        json!([{ "coinbase": "5100", "sequence": u32::MAX }])
+    );
+    assert_eq!(coinbase["vout"][0]["valueZat"], json!(50_000));
+    assert_eq!(coinbase["vout"][0]["value"], json!(0.0005));
+    assert_eq!(coinbase["vout"][1]["n"], json!(1));
+    assert_eq!(
+        coinbase["vout"][1]["scriptPubKey"]["hex"],
+        json!("a9147d46a730d31f97b1930d3368a967c309bd4d136a87")
+    );
+    assert_eq!(coinbase["vjoinsplit"], json!([]));
+    assert!(coinbase.get("valueBalance").is_none());
+
+    let error = rpc.call("getblock", vec![json!("2")]).await.unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    let error = rpc
+        .call("getblock", vec![json!("0"), json!(3)])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::INVALID_PARAMETER);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn getrawtransaction() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state);
+
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+    let coinbase = &block_1.transactions[0];
+    let txid = TransactionHash::from(coinbase.as_ref()).to_string();
+
+    let mut coinbase_bytes = Vec::new();
+    coinbase.zcash_serialize(&mut coinbase_bytes)?;
+
+    let result = rpc.call("getrawtransaction", vec![json!(txid)]).await?;
+    assert_eq!(result, json!(hex::encode(&coinbase_bytes)));
+
+    let result = rpc
+        .call("getrawtransaction", vec![json!(txid), json!(1)])
+        .await?;
+    let transaction: RawTransactionObject = serde_json::from_value(result)?;
+    assert_eq!(transaction.hex, hex::encode(&coinbase_bytes));
+    assert_eq!(transaction.transaction.txid, txid);
+    assert_eq!(transaction.blockhash, Some(block_1.hash().to_string()));
+    assert_eq!(transaction.height, Some(1));
+    assert_eq!(transaction.confirmations, Some(1));
+    assert_eq!(transaction.time, Some(block_1.header.time.timestamp()));
+
+    let script_pub_key = &transaction.transaction.vout[1].script_pub_key;
+    assert_eq!(script_pub_key.script_type, "scripthash");
+    assert_eq!(script_pub_key.req_sigs, Some(1));
+    assert_eq!(script_pub_key.addresses, Some(vec![P2SH.to_owned()]));
+    assert_eq!(
+        script_pub_key.asm,
+        "OP_HASH160 7d46a730d31f97b1930d3368a967c309bd4d136a OP_EQUAL"
+    );
+
+    let script_pub_key = &transaction.transaction.vout[0].script_pub_key;
+    assert_eq!(script_pub_key.script_type, "pubkey");
+    assert_eq!(script_pub_key.req_sigs, Some(1));
+    assert!(script_pub_key.addresses.as_ref().unwrap()[0].starts_with("t1"));
+
+    let error = rpc
+        .call("getrawtransaction", vec![json!("00".repeat(32))])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::INVALID_ADDRESS_OR_KEY);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sendrawtransaction() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state.clone());
+
+    let error = rpc
+        .call("sendrawtransaction", vec![json!("00")])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::MISC_ERROR);
+
+    let mempool = zebra_consensus::mempool::init(
+        Network::Mainnet,
+        Default::default(),
+        state,
+        zebra_consensus::mempool::ZebraMempoolState::default(),
+    );
+    let peers = tower::service_fn(|_: zn::Request| async { Ok(zn::Response::Nil) });
+    let rpc = rpc.with_mempool(
+        Buffer::new(BoxService::new(mempool), 1),
+        Buffer::new(BoxService::new(peers), 1),
+    );
+
+    let error = rpc
+        .call("sendrawtransaction", vec![json!("00")])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::DESERIALIZATION_ERROR);
+
+    // Coinbase transactions can't be relayed
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+    let mut coinbase_bytes = Vec::new();
+    block_1.transactions[0].zcash_serialize(&mut coinbase_bytes)?;
+    let error = rpc
+        .call(
+            "sendrawtransaction",
+            vec![json!(hex::encode(coinbase_bytes))],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::VERIFY_REJECTED);
+
+    // Missing inputs have their own error code
+    let mut pk_script = vec![0x76, 0xa9, 20];
+    pk_script.extend_from_slice(&[7; 20]);
+    pk_script.extend_from_slice(&[0x88, 0xac]);
+    let transaction = Transaction::V1 {
+        inputs: vec![TransparentInput::PrevOut {
+            outpoint: OutPoint {
+                hash: TransactionHash([1; 32]),
+                index: 0,
+            },
+            script: Script(Vec::new()),
+            sequence: u32::MAX,
+        }],
+        outputs: vec![TransparentOutput {
+            value: 1_000u64.try_into()?,
+            pk_script: Script(pk_script),
+        }],
+        lock_time: LockTime::Height(BlockHeight(0)),
+    };
+    let mut bytes = Vec::new();
+    transaction.zcash_serialize(&mut bytes)?;
+    let error = rpc
+        .call("sendrawtransaction", vec![json!(hex::encode(bytes))])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code, Error::VERIFY_ERROR);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn waitfornewblock() -> Result<(), BoxError> {
+    zebra_test::init();
+
+    let mut state = zebra_state::in_memory::init();
+    add_blocks(&mut state).await?;
+    let rpc = Rpc::new(Network::Mainnet, state.clone());
+    let block_1 = Block::zcash_deserialize(&BLOCK_MAINNET_1_BYTES[..])?;
+    let block_2 = Arc::new(Block::zcash_deserialize(&BLOCK_MAINNET_2_BYTES[..])?);
+
+    // Timeouts return the current tip
+    assert_eq!(
+        rpc.call("waitfornewblock", vec![json!(10)]).await?,
+        json!({ "height": 1, "hash": block_1.hash().to_string() })
+    );
+
+    let mut wait = tokio::spawn(async move { rpc.call("waitfornewblock", vec![]).await });
+    assert!(
+        tokio::time::timeout(Duration::from_millis(10), &mut wait)
+            .await
+            .is_err(),
+        "waitfornewblock should wait for the tip to change"
+    );
+    state
+        .ready_and()
+        .await?
+        .call(zs::Request::AddBlock {
+            block: block_2.clone(),
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await?;
+
+    assert_eq!(
+        wait.await??,
+        json!({ "height": 2, "hash": block_2.hash().to_string() })
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn network_info() -> Result<(), Error> {
+    use chrono::{TimeZone, Utc};
+    use zebra_network::types::{ConnectionDirection, PeerInfo, PeerServices, Version};
+
+    zebra_test::init();
+
+    let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+    let error = rpc.call("getpeerinfo", vec![]).await.unwrap_err();
+    assert_eq!(error.code, Error::MISC_ERROR);
+
+    let network_config = zn::Config {
+        listen_addr: "192.0.2.1:8233".parse().unwrap(),
+        ..zn::Config::default()
+    };
+    let address_book = Arc::new(Mutex::new(zn::AddressBook::new(tracing::Span::none())));
+    let rpc = rpc.with_network_diagnostics(
+        &network_config,
+        &zebra_consensus::mempool::Config::default(),
+        address_book.clone(),
+    );
+
+    let result = rpc.call("getnetworkinfo", vec![]).await?;
+    assert_eq!(result["subversion"], json!(network_config.user_agent));
+    assert_eq!(result["connections"], json!(0));
+    assert_eq!(result["relayfee"], json!(0.000001));
+    assert_eq!(
+        result["localaddresses"],
+        json!([{ "address": "192.0.2.1", "port": 8233, "score": 1 }])
+    );
+    assert_eq!(rpc.call("getpeerinfo", vec![]).await?, json!([]));
+
+    let connected_at = Utc.timestamp(1_600_000_000, 0);
+    address_book.lock().unwrap().connection_opened(PeerInfo {
+        addr: "192.0.2.2:8233".parse().unwrap(),
+        direction: ConnectionDirection::Inbound,
+        version: Version(170_011),
+        user_agent: "/MagicBean:4.0.0/".to_string(),
+        services: PeerServices::NODE_NETWORK,
+        start_height: BlockHeight(1_000_000),
+        connected_at,
+        last_seen: connected_at,
+        failures: 0,
+        awaiting_response: true,
+    });
+
+    let result = rpc.call("getnetworkinfo", vec![]).await?;
+    assert_eq!(result["connections"], json!(1));
+    assert_eq!(
+        rpc.call("getpeerinfo", vec![]).await?,
+        json!([{
+            "id": 0,
+            "addr": "192.0.2.2:8233",
+            "services": "0000000000000001",
+            "lastrecv": 1_600_000_000,
+            "conntime": 1_600_000_000,
+            "version": 170_011,
+            "subver": "/MagicBean:4.0.0/",
+            "inbound": true,
+            "startingheight": 1_000_000,
+            "feeler": false,
+            "failures": 0,
+            "inflight": true,
+        }])
+    );
+
+    Ok(())
+}
@@ -0,0 +1,511 @@
+//! Decoded transactions, in the `zcashd` JSON format, and the
+//! `getrawtransaction` and `sendrawtransaction` methods.
+//!
+//! Shielded descriptions only include their commitments, nullifiers, and
+//! keys. Proofs, signatures, and note ciphertexts are omitted.
+
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower::{Service, ServiceExt};
+use tracing::{debug, warn};
+
+use zebra_chain::{
+    proofs::ZkSnarkProof,
+    serialization::ZcashDeserialize,
+    transaction::{JoinSplit, Output, Spend, Transaction, TransactionHash, TransparentInput},
+    types::{LockTime, Script},
+    Network,
+};
+use zebra_network as zn;
+use zebra_node_services::mempool::{self as mp, MempoolError};
+use zebra_state as zs;
+
+use super::{
+    chain::{coinbase_height, serialize},
+    result, reversed_hex, script, BoxError, Rpc,
+};
+use crate::Error;
+
+/// A decoded transaction, as returned by `getblock` with verbosity 2.
+///
+/// `getrawtransaction` returns a [`RawTransactionObject`], which adds chain
+/// context to these fields.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct TransactionObject {
+    /// The transaction hash.
+    pub txid: String,
+    /// The transaction version.
+    pub version: u32,
+    /// Is this an Overwinter or later transaction?
+    pub overwintered: bool,
+    /// The hex-encoded version group ID, for Overwinter and later
+    /// transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versiongroupid: Option<String>,
+    /// The lock time, as a block height or a Unix timestamp.
+    pub locktime: u32,
+    /// The expiry height, for Overwinter and later transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiryheight: Option<u32>,
+    /// The transparent inputs.
+    pub vin: Vec<InputObject>,
+    /// The transparent outputs.
+    pub vout: Vec<OutputObject>,
+    /// The Sprout JoinSplit descriptions.
+    pub vjoinsplit: Vec<JoinSplitObject>,
+    /// The net value of Sapling spends minus outputs, in ZEC, for Sapling
+    /// transactions.
+    #[serde(
+        rename = "valueBalance",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value_balance: Option<f64>,
+    /// The net value of Sapling spends minus outputs, in zatoshis, for
+    /// Sapling transactions.
+    #[serde(
+        rename = "valueBalanceZat",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value_balance_zat: Option<i64>,
+    /// The Sapling spend descriptions, for Sapling transactions.
+    #[serde(
+        rename = "vShieldedSpend",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub shielded_spends: Option<Vec<SpendObject>>,
+    /// The Sapling output descriptions, for Sapling transactions.
+    #[serde(
+        rename = "vShieldedOutput",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub shielded_outputs: Option<Vec<ShieldedOutputObject>>,
+}
+
+/// A decoded transparent input.
+///
+/// Coinbase inputs only have `coinbase` and `sequence` fields.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct InputObject {
+    /// The hex-encoded coinbase script, for coinbase inputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<String>,
+    /// The hash of the transaction that contains the spent output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub txid: Option<String>,
+    /// The index of the spent output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    /// The script that authorizes the spend.
+    #[serde(rename = "scriptSig", default, skip_serializing_if = "Option::is_none")]
+    pub script_sig: Option<ScriptObject>,
+    /// The input sequence number.
+    pub sequence: u32,
+}
+
+/// A decoded transparent output.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct OutputObject {
+    /// The output value, in ZEC.
+    pub value: f64,
+    /// The output value, in zatoshis.
+    #[serde(rename = "valueZat")]
+    pub value_zat: i64,
+    /// The index of the output in the transaction.
+    pub n: u32,
+    /// The script that locks the output.
+    #[serde(rename = "scriptPubKey")]
+    pub script_pub_key: ScriptPubKeyObject,
+}
+
+/// A decoded input script.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ScriptObject {
+    /// The script opcodes and pushes, with signature hash types shown by name.
+    pub asm: String,
+    /// The hex-encoded script.
+    pub hex: String,
+}
+
+/// A decoded output script.
+///
+/// `reqSigs` and `addresses` are only present for scripts that pay to a
+/// transparent address.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ScriptPubKeyObject {
+    /// The script opcodes and pushes.
+    pub asm: String,
+    /// The hex-encoded script.
+    pub hex: String,
+    /// The number of signatures required to spend the output.
+    #[serde(rename = "reqSigs", default, skip_serializing_if = "Option::is_none")]
+    pub req_sigs: Option<u32>,
+    /// The script type, like `pubkeyhash` or `nonstandard`.
+    #[serde(rename = "type")]
+    pub script_type: String,
+    /// The addresses that the output pays to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<Vec<String>>,
+}
+
+/// A decoded transaction with its location in the chain, as returned by
+/// `getrawtransaction` with `verbose` set.
+///
+/// The chain fields are missing if the transaction is not in a block.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct RawTransactionObject {
+    /// The hex-encoded transaction.
+    pub hex: String,
+    /// The decoded transaction fields.
+    #[serde(flatten)]
+    pub transaction: TransactionObject,
+    /// The hash of the block that contains the transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blockhash: Option<String>,
+    /// The height of the block that contains the transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// The number of blocks in the best chain from the transaction's block
+    /// to the tip, inclusive, or 0 if the block is not in the best chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmations: Option<i64>,
+    /// The block time, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<i64>,
+    /// The block time, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocktime: Option<i64>,
+}
+
+/// A decoded Sprout JoinSplit description.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct JoinSplitObject {
+    /// The value removed from the transparent value pool, in ZEC.
+    pub vpub_old: f64,
+    /// The value removed from the transparent value pool, in zatoshis.
+    #[serde(rename = "vpub_oldZat")]
+    pub vpub_old_zat: i64,
+    /// The value added to the transparent value pool, in ZEC.
+    pub vpub_new: f64,
+    /// The value added to the transparent value pool, in zatoshis.
+    #[serde(rename = "vpub_newZat")]
+    pub vpub_new_zat: i64,
+    /// The Sprout note commitment tree anchor.
+    pub anchor: String,
+    /// The nullifiers of the input notes.
+    pub nullifiers: Vec<String>,
+    /// The note commitments of the output notes.
+    pub commitments: Vec<String>,
+    /// The ephemeral X25519 public key.
+    #[serde(rename = "onetimePubKey")]
+    pub onetime_pub_key: String,
+    /// The random seed.
+    #[serde(rename = "randomSeed")]
+    pub random_seed: String,
+}
+
+/// A decoded Sapling spend description.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct SpendObject {
+    /// The value commitment of the input note.
+    pub cv: String,
+    /// The Sapling note commitment tree anchor.
+    pub anchor: String,
+    /// The nullifier of the input note.
+    pub nullifier: String,
+    /// The randomized spend authorization key.
+    pub rk: String,
+}
+
+/// A decoded Sapling output description.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ShieldedOutputObject {
+    /// The value commitment of the output note.
+    pub cv: String,
+    /// The note commitment of the output note.
+    pub cmu: String,
+    /// The ephemeral Jubjub public key.
+    #[serde(rename = "ephemeralKey")]
+    pub ephemeral_key: String,
+}
+
+impl<ZS> Rpc<ZS>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    /// Returns the transaction with `txid`, as a JSON object if `verbose` is
+    /// true, or as hex otherwise.
+    ///
+    /// Only transactions in blocks can be found.
+    pub(super) async fn get_raw_transaction(
+        &self,
+        txid: &str,
+        verbose: bool,
+    ) -> Result<Value, Error> {
+        let hash: TransactionHash = txid
+            .parse()
+            .map_err(|_| Error::invalid_parameter("txid must be a hexadecimal string"))?;
+
+        let (transaction, block_hash) = match self
+            .state_request(zs::Request::GetTransaction { hash })
+            .await
+        {
+            Ok(zs::Response::Transaction {
+                transaction,
+                block_hash,
+            }) => (transaction, block_hash),
+            Ok(_) => {
+                unreachable!("GetTransaction request can only result in Response::Transaction")
+            }
+            Err(_) => {
+                return Err(Error::new(
+                    Error::INVALID_ADDRESS_OR_KEY,
+                    "No information available about transaction",
+                ))
+            }
+        };
+
+        let hex = hex::encode(serialize(&*transaction));
+        if !verbose {
+            return result(hex);
+        }
+
+        let block = self.block_by_hash(block_hash).await?;
+        let height = coinbase_height(&block);
+        let confirmations = match self.best_chain_block(height).await? {
+            Some(best) if best.hash() == block_hash => {
+                coinbase_height(&*self.tip().await?) - height + 1
+            }
+            _ => 0,
+        };
+        let time = block.header.time.timestamp();
+
+        result(RawTransactionObject {
+            hex,
+            transaction: transaction_object(self.network, &transaction),
+            blockhash: Some(block_hash.to_string()),
+            height: Some(height.0),
+            confirmations: Some(confirmations),
+            time: Some(time),
+            blocktime: Some(time),
+        })
+    }
+
+    /// Verifies the hex-encoded transaction `hex`, adds it to the mempool,
+    /// and sends it to peers. Returns the transaction ID.
+    ///
+    /// Transactions are only sent to peers after the mempool accepts them.
+    /// The mempool rejects transactions with scripts, signatures, or proofs,
+    /// because Zebra can't verify them yet.
+    ///
+    /// Transactions that are already in the mempool are sent to peers again.
+    /// Peers don't acknowledge transactions, so they are sent in the
+    /// background, and failures are only logged.
+    pub(super) async fn send_raw_transaction(&self, hex: &str) -> Result<String, Error> {
+        let (mempool, peers) = self
+            .mempool
+            .clone()
+            .ok_or_else(|| Error::new(Error::MISC_ERROR, "Mempool is not available"))?;
+
+        let transaction = hex::decode(hex)
+            .ok()
+            .and_then(|bytes| Transaction::zcash_deserialize(&bytes[..]).ok())
+            .map(Arc::new)
+            .ok_or_else(|| Error::new(Error::DESERIALIZATION_ERROR, "TX decode failed"))?;
+        let hash = TransactionHash::from(&*transaction);
+
+        if let Err(e) = boxed_oneshot(mempool, mp::Request::Queue(transaction.clone())).await {
+            match e.downcast::<MempoolError>() {
+                Ok(e) if *e == MempoolError::AlreadyInMempool => {}
+                Ok(e) => return Err(mempool_error(*e)),
+                Err(e) => return Err(Error::new(Error::MISC_ERROR, e.to_string())),
+            }
+        }
+
+        let push = boxed_oneshot(peers, zn::Request::PushTransaction(transaction));
+        tokio::spawn(async move {
+            match push.await {
+                Ok(_) => debug!(?hash, "sent transaction to peers"),
+                Err(e) => warn!(?hash, %e, "could not send transaction to peers"),
+            }
+        });
+
+        Ok(hash.to_string())
+    }
+}
+
+/// Sends `request` to `service`, and returns the boxed response future.
+///
+/// Awaiting an unboxed `Buffer` response future confuses the compiler's
+/// `Send` check for the RPC futures, so we erase its type.
+fn boxed_oneshot<S, R>(
+    service: S,
+    request: R,
+) -> Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>
+where
+    S: Service<R, Error = BoxError> + Send + 'static,
+    S::Future: Send,
+    R: Send + 'static,
+{
+    Box::pin(service.oneshot(request))
+}
+
+/// Returns the `zcashd` error code and message for a transaction that was
+/// rejected by the mempool.
+fn mempool_error(error: MempoolError) -> Error {
+    let code = match error {
+        MempoolError::MissingInput(_) => Error::VERIFY_ERROR,
+        MempoolError::AlreadyInChain => Error::VERIFY_ALREADY_IN_CHAIN,
+        _ => Error::VERIFY_REJECTED,
+    };
+
+    Error::new(code, error.to_string())
+}
+
+/// Returns the decoded JSON form of `transaction`, with addresses for
+/// `network`.
+pub(super) fn transaction_object(network: Network, transaction: &Transaction) -> TransactionObject {
+    let locktime = match transaction.lock_time() {
+        LockTime::Height(height) => height.0,
+        LockTime::Time(time) => time.timestamp() as u32,
+    };
+
+    let mut object = TransactionObject {
+        txid: TransactionHash::from(transaction).to_string(),
+        version: transaction.version(),
+        overwintered: transaction.version_group_id().is_some(),
+        versiongroupid: transaction
+            .version_group_id()
+            .map(|id| format!("{:08x}", id)),
+        locktime,
+        expiryheight: transaction.expiry_height().map(|height| height.0),
+        vin: transaction.inputs().map(input_object).collect(),
+        vout: transaction
+            .outputs()
+            .enumerate()
+            .map(|(n, output)| OutputObject {
+                value: output.value.zec(),
+                value_zat: output.value.into(),
+                n: n as u32,
+                script_pub_key: script_pub_key_object(network, &output.pk_script),
+            })
+            .collect(),
+        vjoinsplit: transaction
+            .bctv14_joinsplits()
+            .map(joinsplit_object)
+            .chain(transaction.groth16_joinsplits().map(joinsplit_object))
+            .collect(),
+        value_balance: None,
+        value_balance_zat: None,
+        shielded_spends: None,
+        shielded_outputs: None,
+    };
+
+    if let Some(value_balance) = transaction.sapling_value_balance() {
+        object.value_balance = Some(value_balance.zec());
+        object.value_balance_zat = Some(value_balance.into());
+        object.shielded_spends = Some(transaction.sapling_spends().map(spend_object).collect());
+        object.shielded_outputs = Some(
+            transaction
+                .sapling_outputs()
+                .map(shielded_output_object)
+                .collect(),
+        );
+    }
+
+    object
+}
+
+/// Returns the decoded JSON form of `input`.
+fn input_object(input: &TransparentInput) -> InputObject {
+    match input {
+        TransparentInput::Coinbase { sequence, .. } => InputObject {
+            coinbase: Some(hex::encode(&input.script_sig().0)),
+            txid: None,
+            vout: None,
+            script_sig: None,
+            sequence: *sequence,
+        },
+        TransparentInput::PrevOut {
+            outpoint,
+            script,
+            sequence,
+        } => InputObject {
+            coinbase: None,
+            txid: Some(outpoint.hash.to_string()),
+            vout: Some(outpoint.index),
+            script_sig: Some(ScriptObject {
+                asm: script::asm(script, true),
+                hex: hex::encode(&script.0),
+            }),
+            sequence: *sequence,
+        },
+    }
+}
+
+/// Returns the decoded JSON form of the output script `script`, with
+/// addresses for `network`.
+fn script_pub_key_object(network: Network, script: &Script) -> ScriptPubKeyObject {
+    let address = script.address(network);
+
+    ScriptPubKeyObject {
+        asm: script::asm(script, false),
+        hex: hex::encode(&script.0),
+        req_sigs: address.map(|_| 1),
+        script_type: script::kind_name(&script.kind()).to_owned(),
+        addresses: address.map(|address| vec![address.to_string()]),
+    }
+}
+
+/// Returns the decoded JSON form of `joinsplit`.
+fn joinsplit_object<P: ZkSnarkProof>(joinsplit: &JoinSplit<P>) -> JoinSplitObject {
+    JoinSplitObject {
+        vpub_old: joinsplit.vpub_old.zec(),
+        vpub_old_zat: joinsplit.vpub_old.into(),
+        vpub_new: joinsplit.vpub_new.zec(),
+        vpub_new_zat: joinsplit.vpub_new.into(),
+        anchor: reversed_hex(joinsplit.anchor.0),
+        nullifiers: joinsplit
+            .nullifiers
+            .iter()
+            .map(|nullifier| reversed_hex(nullifier.clone().into()))
+            .collect(),
+        commitments: joinsplit
+            .commitments
+            .iter()
+            .map(|commitment| reversed_hex(commitment.0))
+            .collect(),
+        onetime_pub_key: reversed_hex(*joinsplit.ephemeral_key.as_bytes()),
+        random_seed: reversed_hex(joinsplit.random_seed),
+    }
+}
+
+/// Returns the decoded JSON form of `spend`.
+fn spend_object(spend: &Spend) -> SpendObject {
+    SpendObject {
+        cv: reversed_hex(spend.cv),
+        anchor: reversed_hex(spend.anchor.0),
+        nullifier: reversed_hex(spend.nullifier.clone().into()),
+        rk: reversed_hex(spend.rk.into()),
+    }
+}
+
+/// Returns the decoded JSON form of `output`.
+fn shielded_output_object(output: &Output) -> ShieldedOutputObject {
+    ShieldedOutputObject {
+        cv: reversed_hex(output.cv),
+        cmu: reversed_hex(output.cmu),
+        ephemeral_key: reversed_hex(output.ephemeral_key.to_bytes()),
+    }
+}
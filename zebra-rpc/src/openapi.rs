@@ -0,0 +1,500 @@
+//! An OpenAPI document for the RPC methods.
+//!
+//! Each method is documented as a `POST` to its own path, so typed clients
+//! can be generated using standard OpenAPI tools. The server ignores request
+//! paths, so these requests also work when they are sent to `/`, like
+//! `zcashd` clients do.
+//!
+//! Admin methods are marked in their descriptions. The server only allows
+//! them if requests are authenticated.
+//!
+//! Parameter and result schemas are derived from the method result types, so
+//! they stay in sync with the JSON that the methods return.
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{Schema, SchemaObject, SubschemaValidation},
+};
+use serde_json::{json, Map, Value};
+
+use crate::{methods::*, Error};
+
+/// The OpenAPI version of the document.
+const OPENAPI_VERSION: &str = "3.1.0";
+
+/// The path of shared schemas in the document.
+const SCHEMAS_PATH: &str = "#/components/schemas/";
+
+/// A documented RPC method.
+struct Method {
+    /// The method name.
+    name: &'static str,
+    /// A description of the method.
+    description: &'static str,
+    /// The positional parameters.
+    params: Vec<Param>,
+    /// The schema of the method result.
+    result: Schema,
+}
+
+/// A documented positional parameter.
+struct Param {
+    /// Can the parameter be omitted?
+    optional: bool,
+    /// The schema of the parameter, including its name and description.
+    schema: Schema,
+}
+
+impl Param {
+    /// Returns a required parameter with `schema`.
+    fn required(name: &str, description: &str, schema: Schema) -> Param {
+        Param {
+            optional: false,
+            schema: describe(schema, name, description),
+        }
+    }
+
+    /// Returns a parameter with `schema`, which can be omitted or null.
+    fn optional(name: &str, description: &str, schema: Schema) -> Param {
+        Param {
+            optional: true,
+            schema: describe(schema, name, description),
+        }
+    }
+}
+
+/// Returns the OpenAPI document for the RPC methods.
+pub fn document() -> Value {
+    let mut gen = SchemaSettings::draft2019_09()
+        .with(|settings| {
+            settings.definitions_path = SCHEMAS_PATH.to_owned();
+            settings.meta_schema = None;
+        })
+        .into_generator();
+
+    let error = gen.subschema_for::<Error>();
+    let paths: Map<String, Value> = methods(&mut gen)
+        .into_iter()
+        .map(|method| (format!("/{}", method.name), path_item(&method, &error)))
+        .collect();
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "Zebra RPC",
+            "description": "The zcashd-compatible JSON-RPC methods supported by Zebra.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": "http://127.0.0.1:8232" }],
+        "paths": paths,
+        "components": { "schemas": gen.definitions() },
+    })
+}
+
+/// Returns the documentation for every RPC method.
+fn methods(gen: &mut SchemaGenerator) -> Vec<Method> {
+    let block_hash = || string("A block hash, as hex");
+    let node = || string("A peer IP address, with an optional port");
+
+    vec![
+        Method {
+            name: "addnode",
+            description: "Admin: adds or removes a manual peer, which is always reconnected, or \
+                          connects to a peer once.",
+            params: vec![
+                Param::required("node", "The peer address", node()),
+                Param::required(
+                    "command",
+                    "add, remove, or onetry",
+                    gen.subschema_for::<String>(),
+                ),
+            ],
+            result: null(),
+        },
+        Method {
+            name: "clearbanned",
+            description: "Admin: clears the banned peers. Zebra doesn't ban peers, so this does \
+                          nothing.",
+            params: vec![],
+            result: null(),
+        },
+        Method {
+            name: "disconnectnode",
+            description: "Admin: closes the connection to a peer.",
+            params: vec![Param::required("node", "The peer address", node())],
+            result: null(),
+        },
+        Method {
+            name: "getbestblockhash",
+            description: "Returns the hash of the best chain tip.",
+            params: vec![],
+            result: gen.subschema_for::<String>(),
+        },
+        Method {
+            name: "getbestblockheightandhash",
+            description: "Returns the height and hash of the best chain tip.",
+            params: vec![],
+            result: gen.subschema_for::<BestBlockHeightAndHash>(),
+        },
+        Method {
+            name: "getblock",
+            description: "Returns a block, by hash or best chain height.",
+            params: vec![
+                Param::required(
+                    "hash_or_height",
+                    "The block hash, or its height in the best chain",
+                    gen.subschema_for::<String>(),
+                ),
+                Param::optional(
+                    "verbosity",
+                    "0 for hex, 1 for an object with transaction IDs, or 2 for an object with \
+                     decoded transactions (default 1)",
+                    gen.subschema_for::<u8>(),
+                ),
+            ],
+            result: one_of(vec![
+                string("The serialized block, as hex"),
+                gen.subschema_for::<BlockObject>(),
+            ]),
+        },
+        Method {
+            name: "getblockchaininfo",
+            description: "Returns the state of the best chain, including its value pools.",
+            params: vec![],
+            result: gen.subschema_for::<BlockchainInfo>(),
+        },
+        Method {
+            name: "getblockcount",
+            description: "Returns the height of the best chain tip.",
+            params: vec![],
+            result: gen.subschema_for::<u32>(),
+        },
+        Method {
+            name: "getblockhash",
+            description: "Returns the hash of the block at a height in the best chain.",
+            params: vec![Param::required(
+                "height",
+                "The block height",
+                gen.subschema_for::<u32>(),
+            )],
+            result: block_hash(),
+        },
+        Method {
+            name: "getblockheader",
+            description: "Returns a block header, by hash.",
+            params: vec![
+                Param::required("hash", "The block hash", block_hash()),
+                Param::optional(
+                    "verbose",
+                    "true for an object, or false for hex (default true)",
+                    gen.subschema_for::<bool>(),
+                ),
+            ],
+            result: one_of(vec![
+                string("The serialized header, as hex"),
+                gen.subschema_for::<BlockHeaderObject>(),
+            ]),
+        },
+        Method {
+            name: "getdifficulty",
+            description: "Returns the difficulty of the best chain tip.",
+            params: vec![],
+            result: gen.subschema_for::<f64>(),
+        },
+        Method {
+            name: "getmininginfo",
+            description: "Returns the height, difficulty, and estimated solution rate of the \
+                          best chain.",
+            params: vec![],
+            result: gen.subschema_for::<MiningInfo>(),
+        },
+        Method {
+            name: "getnetworkhashps",
+            description: "The same as getnetworksolps. Kept for compatibility with older clients.",
+            params: solps_params(gen),
+            result: gen.subschema_for::<u64>(),
+        },
+        Method {
+            name: "getnetworkinfo",
+            description: "Returns the node version, network configuration, and connection count.",
+            params: vec![],
+            result: gen.subschema_for::<NetworkInfo>(),
+        },
+        Method {
+            name: "getnetworksolps",
+            description: "Returns the estimated network solution rate, in solutions per second.",
+            params: solps_params(gen),
+            result: gen.subschema_for::<u64>(),
+        },
+        Method {
+            name: "getpeerinfo",
+            description: "Returns diagnostic information about each connected peer.",
+            params: vec![],
+            result: gen.subschema_for::<Vec<PeerInfoObject>>(),
+        },
+        Method {
+            name: "getrawtransaction",
+            description: "Returns a transaction in a block, by hash.",
+            params: vec![
+                Param::required(
+                    "txid",
+                    "The transaction hash",
+                    string("A transaction hash, as hex"),
+                ),
+                Param::optional(
+                    "verbose",
+                    "Non-zero or true for an object, or zero or false for hex (default false)",
+                    one_of(vec![
+                        gen.subschema_for::<bool>(),
+                        gen.subschema_for::<u64>(),
+                    ]),
+                ),
+            ],
+            result: one_of(vec![
+                string("The serialized transaction, as hex"),
+                gen.subschema_for::<RawTransactionObject>(),
+            ]),
+        },
+        Method {
+            name: "sendrawtransaction",
+            description: "Submits a transaction to the mempool, and sends it to peers.",
+            params: vec![Param::required(
+                "hexstring",
+                "The serialized transaction, as hex",
+                gen.subschema_for::<String>(),
+            )],
+            result: string("The transaction hash, as hex"),
+        },
+        Method {
+            name: "setloglevel",
+            description: "Admin: replaces the log filter.",
+            params: vec![Param::required(
+                "filter",
+                "The new filter, like info,zebra_network=debug",
+                gen.subschema_for::<String>(),
+            )],
+            result: null(),
+        },
+        Method {
+            name: "stop",
+            description: "Admin: starts a graceful shutdown of the node.",
+            params: vec![],
+            result: string("A shutdown message"),
+        },
+        Method {
+            name: "validateaddress",
+            description: "Returns information about a transparent address.",
+            params: vec![Param::required(
+                "address",
+                "The address",
+                gen.subschema_for::<String>(),
+            )],
+            result: gen.subschema_for::<ValidateAddress>(),
+        },
+        Method {
+            name: "waitfornewblock",
+            description: "Waits for the best chain tip to change, then returns the new tip.",
+            params: vec![Param::optional(
+                "timeout",
+                "The longest time to wait, in milliseconds, or 0 to wait forever (default 0)",
+                gen.subschema_for::<u64>(),
+            )],
+            result: gen.subschema_for::<BestBlockHeightAndHash>(),
+        },
+        Method {
+            name: "z_listanchors",
+            description: "Returns the note commitment tree sizes and final roots after the best \
+                          chain tip, and the anchors of recent best chain blocks. Zebra doesn't \
+                          track the note commitment trees yet, so it only returns the Sapling \
+                          roots committed to by block headers from Sapling activation until \
+                          Heartwood activation. Other roots are omitted.",
+            params: vec![Param::optional(
+                "count",
+                "The number of recent blocks, from 1 to 100 (default 10)",
+                gen.subschema_for::<u32>(),
+            )],
+            result: gen.subschema_for::<AnchorList>(),
+        },
+        Method {
+            name: "z_validateaddress",
+            description: "Returns information about a transparent or shielded address.",
+            params: vec![Param::required(
+                "address",
+                "The address",
+                gen.subschema_for::<String>(),
+            )],
+            result: gen.subschema_for::<ZValidateAddress>(),
+        },
+    ]
+}
+
+/// Returns the parameters of `getnetworksolps` and `getnetworkhashps`.
+fn solps_params(gen: &mut SchemaGenerator) -> Vec<Param> {
+    vec![
+        Param::optional(
+            "blocks",
+            "The number of blocks to average over, up to 1000, or 0 or less for the difficulty \
+             averaging window (default 120)",
+            gen.subschema_for::<i32>(),
+        ),
+        Param::optional(
+            "height",
+            "The best chain height to estimate at, or -1 for the tip (default -1)",
+            gen.subschema_for::<i32>(),
+        ),
+    ]
+}
+
+/// Returns the OpenAPI path item for `method`, which fails with `error`.
+fn path_item(method: &Method, error: &Schema) -> Value {
+    let required_params = method.params.iter().filter(|param| !param.optional).count();
+    let params: Vec<&Schema> = method.params.iter().map(|param| &param.schema).collect();
+
+    json!({
+        "post": {
+            "operationId": method.name,
+            "summary": method.description,
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "application/json": {
+                        "schema": {
+                            "type": "object",
+                            "required": ["method"],
+                            "properties": {
+                                "jsonrpc": { "type": "string", "const": "1.0" },
+                                "method": { "type": "string", "const": method.name },
+                                "params": {
+                                    "type": "array",
+                                    "prefixItems": params,
+                                    "items": false,
+                                    "minItems": required_params,
+                                },
+                                "id": {},
+                            },
+                        },
+                    },
+                },
+            },
+            "responses": {
+                "200": {
+                    "description": "The method result",
+                    "content": {
+                        "application/json": {
+                            "schema": response(&method.result, &Schema::Bool(false)),
+                        },
+                    },
+                },
+                "default": {
+                    "description": "The method error",
+                    "content": {
+                        "application/json": {
+                            "schema": response(&Schema::Bool(false), error),
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+/// Returns the schema of a JSON-RPC response with `result` and `error`.
+///
+/// The unused field is always null.
+fn response(result: &Schema, error: &Schema) -> Value {
+    json!({
+        "type": "object",
+        "required": ["result", "error", "id"],
+        "properties": {
+            "result": one_of(vec![result.clone(), null()]),
+            "error": one_of(vec![error.clone(), null()]),
+            "id": {},
+        },
+    })
+}
+
+/// Returns a string schema with `description`.
+fn string(description: &str) -> Schema {
+    let mut schema = SchemaGenerator::default()
+        .subschema_for::<String>()
+        .into_object();
+    schema.metadata().description = Some(description.to_owned());
+    schema.into()
+}
+
+/// Returns the schema of the null value.
+fn null() -> Schema {
+    SchemaGenerator::default().subschema_for::<()>()
+}
+
+/// Returns a schema that matches exactly one of `schemas`.
+fn one_of(schemas: Vec<Schema>) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            one_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Returns `schema`, with `title` and `description`.
+fn describe(schema: Schema, title: &str, description: &str) -> Schema {
+    let mut schema = schema.into_object();
+    let metadata = schema.metadata();
+    metadata.title = Some(title.to_owned());
+    metadata.description = Some(description.to_owned());
+    schema.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn documents_all_methods() {
+        zebra_test::init();
+
+        let document = document();
+        let paths = document["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 24);
+
+        let rpc = Rpc::new(
+            zebra_chain::Network::Mainnet,
+            zebra_state::in_memory::init(),
+        );
+        for path in paths.keys() {
+            let method = path.trim_start_matches('/');
+            if let Err(error) = rpc.call(method, vec![]).await {
+                assert_ne!(error.code, Error::METHOD_NOT_FOUND, "{}", method);
+            }
+        }
+    }
+
+    #[test]
+    fn result_schemas() {
+        let document = document();
+        let schemas = &document["components"]["schemas"];
+
+        for name in &[
+            "AnchorList",
+            "BlockObject",
+            "BlockchainInfo",
+            "MiningInfo",
+            "NetworkInfo",
+            "PeerInfoObject",
+            "RawTransactionObject",
+            "ZValidateAddress",
+            "Error",
+        ] {
+            assert!(schemas[name].is_object(), "missing schema: {}", name);
+        }
+
+        let getblock = &document["paths"]["/getblock"]["post"];
+        let params = &getblock["requestBody"]["content"]["application/json"]["schema"]
+            ["properties"]["params"];
+        assert_eq!(params["minItems"], json!(1));
+        assert_eq!(params["prefixItems"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,417 @@
+//! A JSON-RPC server over HTTP.
+//!
+//! Requests and responses use the `zcashd` JSON-RPC 1.0 format: each request
+//! is a JSON object with a `method`, positional `params`, and an `id`, and
+//! each response has a `result`, an `error`, and the request `id`.
+
+use std::{error, net::IpAddr, sync::Arc};
+
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use tower::Service;
+use tracing::{error, info, warn};
+
+use zebra_state as zs;
+
+use crate::{
+    access::Access,
+    methods::{is_admin, is_long_poll},
+    Config, Error, Rpc,
+};
+
+type BoxError = Box<dyn error::Error + Send + Sync + 'static>;
+
+/// A JSON-RPC request.
+#[derive(Clone, Debug, Deserialize)]
+struct RpcRequest {
+    /// The method name.
+    method: String,
+    /// The positional method parameters.
+    #[serde(default)]
+    params: Vec<Value>,
+    /// The request id, which is copied to the response.
+    #[serde(default)]
+    id: Value,
+}
+
+/// A JSON-RPC response.
+///
+/// Exactly one of `result` and `error` is not null.
+#[derive(Clone, Debug, Serialize)]
+struct RpcResponse {
+    /// The method result.
+    result: Value,
+    /// The method error.
+    error: Option<Error>,
+    /// The request id.
+    id: Value,
+}
+
+/// The limits on the number of method calls that run in parallel.
+struct Limits {
+    /// The permits for method calls, except long polls.
+    calls: Arc<Semaphore>,
+    /// The permits for long polls.
+    long_polls: Arc<Semaphore>,
+}
+
+impl Limits {
+    /// Returns the parallel call limits in `config`.
+    fn new(config: &Config) -> Limits {
+        Limits {
+            calls: Arc::new(Semaphore::new(config.parallel_requests.max(1))),
+            long_polls: Arc::new(Semaphore::new(config.parallel_long_polls.max(1))),
+        }
+    }
+}
+
+/// Spawn the RPC server, if it is enabled in `config`.
+///
+/// Method calls are handled by `rpc`. Clients and methods are restricted by
+/// the access rules in `config`, and calls run in parallel up to the limits
+/// in `config`.
+pub fn spawn<ZS>(config: Config, rpc: Rpc<ZS>)
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    let addr = match config.listen_addr {
+        Some(addr) => addr,
+        None => return,
+    };
+
+    info!(?addr, "Initializing RPC endpoint");
+
+    let access = match Access::new(&config) {
+        Ok(access) => Arc::new(access),
+        Err(e) => {
+            error!("Could not write RPC cookie file");
+            error!("Error: {}", e);
+            return;
+        }
+    };
+    if !access.is_authenticated() && !addr.ip().is_loopback() {
+        warn!(
+            ?addr,
+            "RPC endpoint is not loopback-only, but requests are not authenticated"
+        );
+    }
+
+    let limit = Arc::new(Limits::new(&config));
+
+    let service = make_service_fn(move |conn: &AddrStream| {
+        let remote_ip = conn.remote_addr().ip();
+        let rpc = rpc.clone();
+        let access = access.clone();
+        let limit = limit.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                request_handler(req, remote_ip, rpc.clone(), access.clone(), limit.clone())
+            }))
+        }
+    });
+
+    tokio::spawn(async move {
+        let server = match Server::try_bind(&addr) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not open RPC endpoint listener");
+                error!("Error: {}", e);
+                return;
+            }
+        }
+        .serve(service);
+
+        if let Err(e) = server.await {
+            error!("Server error: {}", e);
+        }
+    });
+}
+
+async fn request_handler<ZS>(
+    req: Request<Body>,
+    remote_ip: IpAddr,
+    rpc: Rpc<ZS>,
+    access: Arc<Access>,
+    limit: Arc<Limits>,
+) -> Result<Response<Body>, hyper::Error>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    if req.method() != Method::POST {
+        return Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(Body::empty())
+            .expect("response with known status code cannot fail"));
+    }
+
+    if !access.allows_addr(remote_ip) {
+        warn!(
+            ?remote_ip,
+            "RPC request from an address that is not allowed"
+        );
+        return Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Body::empty())
+            .expect("response with known status code cannot fail"));
+    }
+
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| value.as_bytes());
+    if !access.is_authorized(authorization) {
+        warn!(?remote_ip, "RPC request with incorrect credentials");
+        return Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(header::WWW_AUTHENTICATE, r#"Basic realm="jsonrpc""#)
+            .body(Body::empty())
+            .expect("response with known status code cannot fail"));
+    }
+
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    let (code, response) = handle_body(&body, &rpc, &access, &limit).await;
+
+    Ok(Response::builder()
+        .status(code)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::to_vec(&response).expect("responses serialize to JSON"),
+        ))
+        .expect("response with known status code cannot fail"))
+}
+
+/// Returns the HTTP status code and JSON-RPC response for a request `body`.
+///
+/// Only the methods allowed by `access` are called, and admin methods are only
+/// called if requests are authenticated. Each call holds a permit from
+/// `limit` while it runs. Long polls are rejected if all their permits are in
+/// use. Uses the same status codes as `zcashd` for errors.
+async fn handle_body<ZS>(
+    body: &[u8],
+    rpc: &Rpc<ZS>,
+    access: &Access,
+    limit: &Limits,
+) -> (StatusCode, RpcResponse)
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = BoxError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    ZS::Future: Send,
+{
+    let request: RpcRequest = match serde_json::from_slice::<Value>(body) {
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = Error::new(Error::INVALID_REQUEST, e.to_string());
+                return (StatusCode::BAD_REQUEST, error_response(error, Value::Null));
+            }
+        },
+        Err(e) => {
+            let error = Error::new(Error::PARSE_ERROR, e.to_string());
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_response(error, Value::Null),
+            );
+        }
+    };
+
+    if !access.allows_method(&request.method) {
+        let error = Error::new(
+            Error::METHOD_NOT_FOUND,
+            format!("Method not allowed: {}", request.method),
+        );
+        return (StatusCode::FORBIDDEN, error_response(error, request.id));
+    }
+
+    // Admin methods change the node, so they need credentials, even on
+    // loopback-only endpoints
+    if is_admin(&request.method) && !access.is_authenticated() {
+        let error = Error::new(
+            Error::METHOD_NOT_FOUND,
+            format!("Method requires RPC authentication: {}", request.method),
+        );
+        return (StatusCode::FORBIDDEN, error_response(error, request.id));
+    }
+
+    let permit = if is_long_poll(&request.method) {
+        match limit.long_polls.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let error = Error::new(
+                    Error::MISC_ERROR,
+                    format!("Too many long polls: {}", request.method),
+                );
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    error_response(error, request.id),
+                );
+            }
+        }
+    } else {
+        limit.calls.clone().acquire_owned().await
+    };
+
+    let call = {
+        let rpc = rpc.clone();
+        let method = request.method;
+        let params = request.params;
+        // The permit moves into the task, so it is held until the call
+        // finishes, even if the client disconnects first
+        tokio::spawn(async move {
+            let _permit = permit;
+            rpc.call(&method, params).await
+        })
+    };
+    let result = match call.await {
+        Ok(result) => result,
+        Err(e) => Err(Error::new(Error::MISC_ERROR, e.to_string())),
+    };
+
+    match result {
+        Ok(result) => (
+            StatusCode::OK,
+            RpcResponse {
+                result,
+                error: None,
+                id: request.id,
+            },
+        ),
+        Err(error) => {
+            let code = match error.code {
+                Error::METHOD_NOT_FOUND => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (code, error_response(error, request.id))
+        }
+    }
+}
+
+/// Returns a response for `error`, for the request with `id`.
+fn error_response(error: Error, id: Value) -> RpcResponse {
+    RpcResponse {
+        result: Value::Null,
+        error: Some(error),
+        id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use zebra_chain::Network;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn request_format() {
+        zebra_test::init();
+
+        let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+        let access = Access::default();
+        let limit = Limits::new(&Config::default());
+
+        let body =
+            br#"{"jsonrpc": "1.0", "id": "test", "method": "validateaddress", "params": [""]}"#;
+        let (code, response) = handle_body(body, &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            json!({ "result": { "isvalid": false }, "error": null, "id": "test" })
+        );
+
+        let body = br#"{"id": 1, "method": "nonexistent"}"#;
+        let (code, response) = handle_body(body, &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::NOT_FOUND);
+        assert_eq!(response.error.unwrap().code, Error::METHOD_NOT_FOUND);
+        assert_eq!(response.id, json!(1));
+
+        let (code, response) = handle_body(b"{", &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.error.unwrap().code, Error::PARSE_ERROR);
+
+        let (code, response) = handle_body(br#"{"params": []}"#, &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert_eq!(response.error.unwrap().code, Error::INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn enabled_methods() {
+        zebra_test::init();
+
+        let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+        let config = Config {
+            enabled_methods: Some(vec!["validateaddress".to_owned()]),
+            ..Config::default()
+        };
+        let access = Access::new(&config).unwrap();
+        let limit = Limits::new(&Config::default());
+
+        let body = br#"{"id": 1, "method": "validateaddress", "params": [""]}"#;
+        let (code, _) = handle_body(body, &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::OK);
+
+        let body = br#"{"id": 1, "method": "z_validateaddress", "params": [""]}"#;
+        let (code, response) = handle_body(body, &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::FORBIDDEN);
+        assert_eq!(response.error.unwrap().code, Error::METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn long_poll_limit() {
+        zebra_test::init();
+
+        let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+        let config = Config {
+            parallel_long_polls: 1,
+            ..Config::default()
+        };
+        let limit = Limits::new(&config);
+        let _waiting = limit.long_polls.try_acquire().unwrap();
+
+        let body = br#"{"id": 1, "method": "waitfornewblock", "params": [1]}"#;
+        let (code, response) = handle_body(body, &rpc, &Access::default(), &limit).await;
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.error.unwrap().code, Error::MISC_ERROR);
+    }
+
+    #[tokio::test]
+    async fn admin_methods_need_authentication() {
+        zebra_test::init();
+
+        let rpc = Rpc::new(Network::Mainnet, zebra_state::in_memory::init());
+        let limit = Limits::new(&Config::default());
+        let body = br#"{"id": 1, "method": "clearbanned"}"#;
+
+        let (code, response) = handle_body(body, &rpc, &Access::default(), &limit).await;
+        assert_eq!(code, StatusCode::FORBIDDEN);
+        assert_eq!(response.error.unwrap().code, Error::METHOD_NOT_FOUND);
+
+        // The method is allowed, but this handler doesn't have admin controls
+        let config = Config {
+            user: Some("user".to_owned()),
+            password: Some("pass".to_owned()),
+            ..Config::default()
+        };
+        let access = Access::new(&config).unwrap();
+        let (code, response) = handle_body(body, &rpc, &access, &limit).await;
+        assert_eq!(code, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.error.unwrap().code, Error::MISC_ERROR);
+    }
+}
@@ -0,0 +1,109 @@
+//! Benchmarks for reading blocks and outputs from the on-disk state.
+//!
+//! Compares a point lookup for each block with the height-ordered iterators
+//! used by bulk exports.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use tempdir::TempDir;
+use tower::ServiceExt;
+
+use zebra_chain::{
+    block::Block,
+    serialization::ZcashDeserialize,
+    types::{amount::Amount, BlockHeight},
+    Network,
+};
+use zebra_state::{on_disk, Config, Request};
+
+/// Returns a state containing the mainnet test blocks, and a reader for it.
+///
+/// The `TempDir` must be kept until the benchmarks finish.
+fn reader_with_test_blocks() -> (TempDir, on_disk::StateReader) {
+    let blocks: Vec<Arc<Block>> = [
+        &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_2_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_3_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_4_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_5_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_6_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_7_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_8_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_9_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_10_BYTES[..],
+    ]
+    .iter()
+    .map(|bytes| Block::zcash_deserialize(*bytes).unwrap().into())
+    .collect();
+
+    let storage_guard = TempDir::new("").unwrap();
+    let config = Config {
+        cache_dir: Some(storage_guard.path().to_owned()),
+        compact_filters: false,
+        min_free_disk_space_mb: 0,
+    };
+
+    // Dropping the runtime closes the database, so the reader can open it
+    {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let service = on_disk::init(config.clone(), Network::Mainnet);
+            for block in blocks {
+                service
+                    .clone()
+                    .oneshot(Request::AddBlock {
+                        block,
+                        deferred_pool_contribution: Amount::zero(),
+                    })
+                    .await
+                    .unwrap();
+            }
+        });
+    }
+
+    let reader = on_disk::StateReader::open(&config, Network::Mainnet).unwrap();
+    (storage_guard, reader)
+}
+
+fn bench_reads(c: &mut Criterion) {
+    let (_storage_guard, reader) = reader_with_test_blocks();
+    let tip_height = reader
+        .blocks(BlockHeight(0))
+        .unwrap()
+        .last()
+        .unwrap()
+        .unwrap()
+        .0;
+
+    let mut group = c.benchmark_group("state reads");
+
+    group.bench_function("block point lookups", |b| {
+        b.iter(|| {
+            for height in 0..=tip_height.0 {
+                reader.block(BlockHeight(height)).unwrap().unwrap();
+            }
+        })
+    });
+
+    group.bench_function("block iterator", |b| {
+        b.iter(|| {
+            for entry in reader.blocks(BlockHeight(0)).unwrap() {
+                entry.unwrap();
+            }
+        })
+    });
+
+    group.bench_function("utxo iterator", |b| {
+        b.iter(|| {
+            for entry in reader.utxos().unwrap() {
+                entry.unwrap();
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_reads);
+criterion_main!(benches);
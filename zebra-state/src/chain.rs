@@ -0,0 +1,208 @@
+//! Chains of non-finalized blocks, ordered by their cumulative work.
+//!
+//! This module only defines the `Chain` type. The state services don't use it
+//! yet: they don't store side-chains, and their commit and tip logic only
+//! knows about the single chain on disk or in memory. When the state stores
+//! side-chains, it will keep each non-finalized fork in a `Chain`, and use the
+//! greatest `Chain` as its best chain.
+
+use std::{cmp::Ordering, collections::BTreeMap, sync::Arc};
+
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    types::BlockHeight,
+    work::{PartialCumulativeWork, Work},
+};
+
+use crate::Error;
+
+/// A chain of non-finalized blocks.
+///
+/// Not used by the state services yet, see the module docs.
+///
+/// Chains are ordered by their partial cumulative work. Height is not used,
+/// because a shorter chain can have more work than a longer chain.
+#[derive(Clone, Debug, Default)]
+pub struct Chain {
+    blocks: BTreeMap<BlockHeight, Arc<Block>>,
+    partial_cumulative_work: PartialCumulativeWork,
+}
+
+impl Chain {
+    /// Returns a new, empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Push `block` onto the tip of this chain.
+    ///
+    /// Returns an error if `block` is not a child of the current tip, or if
+    /// its difficulty threshold is invalid.
+    pub fn push(&mut self, block: Arc<Block>) -> Result<(), Error> {
+        let height = block
+            .coinbase_height()
+            .ok_or("block has no coinbase height")?;
+
+        if let Some((tip_height, tip)) = self.blocks.iter().next_back() {
+            if block.header.previous_block_hash != tip.hash() || tip_height.next() != Some(height) {
+                Err("block is not a child of the chain tip")?;
+            }
+        }
+
+        self.partial_cumulative_work += block_work(&block)?;
+        self.blocks.insert(height, block);
+
+        Ok(())
+    }
+
+    /// Remove the tip block from this chain, and return it.
+    pub fn pop_tip(&mut self) -> Option<Arc<Block>> {
+        let tip_height = *self.blocks.keys().next_back()?;
+        let block = self.blocks.remove(&tip_height)?;
+
+        self.partial_cumulative_work -=
+            block_work(&block).expect("blocks in the chain have valid work");
+
+        Some(block)
+    }
+
+    /// Returns the hash of the tip block, or `None` if the chain is empty.
+    pub fn tip_hash(&self) -> Option<BlockHeaderHash> {
+        self.blocks.values().next_back().map(|block| block.hash())
+    }
+
+    /// Returns the sum of the work of the blocks in this chain.
+    pub fn partial_cumulative_work(&self) -> PartialCumulativeWork {
+        self.partial_cumulative_work
+    }
+}
+
+/// Returns the work of `block`, or an error if its difficulty threshold is
+/// invalid.
+fn block_work(block: &Block) -> Result<Work, Error> {
+    block
+        .header
+        .difficulty_threshold()
+        .to_work()
+        .ok_or_else(|| "block has an invalid difficulty threshold".into())
+}
+
+impl PartialEq for Chain {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Chain {}
+
+impl PartialOrd for Chain {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Chain {
+    /// Chains with more work are greater.
+    ///
+    /// If two chains have the same work, the chain with the greater tip hash
+    /// bytes is greater. This tie-breaker only needs to be deterministic
+    /// within the local node, so it doesn't need to match zcashd, which
+    /// prefers the chain it received first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cumulative_work
+            .cmp(&other.partial_cumulative_work)
+            .then_with(|| {
+                let self_hash = self.tip_hash().map(|hash| hash.0);
+                let other_hash = other.tip_hash().map(|hash| hash.0);
+                self_hash.cmp(&other_hash)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_chain::serialization::ZcashDeserialize;
+
+    /// Returns mainnet block 1, with its difficulty threshold set to `bits`.
+    fn block_1_with_bits(bits: u32) -> Arc<Block> {
+        let mut block =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..]).unwrap();
+        block.header.bits = bits;
+        block.into()
+    }
+
+    #[test]
+    fn push_and_pop() -> Result<(), Error> {
+        zebra_test::init();
+
+        let genesis: Arc<Block> =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?.into();
+        let block1: Arc<Block> =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?.into();
+
+        let mut chain = Chain::new();
+        chain.push(genesis.clone())?;
+        chain.push(block1.clone())?;
+        assert_eq!(chain.tip_hash(), Some(block1.hash()));
+
+        let expected =
+            PartialCumulativeWork::default() + block_work(&genesis)? + block_work(&block1)?;
+        assert_eq!(chain.partial_cumulative_work(), expected);
+
+        // Blocks must extend the tip
+        assert!(chain.push(genesis.clone()).is_err());
+
+        assert_eq!(chain.pop_tip(), Some(block1));
+        assert_eq!(
+            chain.partial_cumulative_work(),
+            PartialCumulativeWork::from(block_work(&genesis)?)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn best_chain_uses_work() -> Result<(), Error> {
+        zebra_test::init();
+
+        // A single block with a lower threshold has more work than two
+        // blocks with higher thresholds
+        let genesis =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?;
+        assert_eq!(genesis.header.bits, 0x1f07_ffff);
+
+        let mut easy = Chain::new();
+        easy.push(genesis.into())?;
+        easy.push(block_1_with_bits(0x1f07_ffff))?;
+        let mut hard = Chain::new();
+        hard.push(block_1_with_bits(0x1d00_ffff))?;
+
+        assert!(hard > easy);
+        assert!(hard.partial_cumulative_work() > easy.partial_cumulative_work());
+
+        // Chains with equal work are ordered by their tip hashes
+        let mut tied = Chain::new();
+        tied.push(block_1_with_bits(0x1f07_ffff))?;
+        let mut other_tied = Chain::new();
+        let mut block = (*block_1_with_bits(0x1f07_ffff)).clone();
+        block.header.nonce[0] ^= 1;
+        other_tied.push(block.into())?;
+
+        assert_eq!(
+            tied.partial_cumulative_work(),
+            other_tied.partial_cumulative_work()
+        );
+        assert_ne!(tied, other_tied);
+        assert_eq!(
+            tied.cmp(&other_tied),
+            tied.tip_hash()
+                .unwrap()
+                .0
+                .cmp(&other_tied.tip_hash().unwrap().0)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+//! BIP-158 compact block filters.
+//!
+//! A basic filter contains the transparent output scripts created by a block,
+//! and the scripts of the outputs spent by the block. Light clients download
+//! filters instead of blocks, and only download the blocks whose filters
+//! match their own scripts.
+//!
+//! Zcash shielded data is not included in the filters.
+//!
+//! [BIP-158]: https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki
+
+use std::{collections::BTreeSet, fmt, hash::Hasher, io::Cursor};
+
+use sha2::{Digest, Sha256};
+use siphasher::sip::SipHasher24;
+
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    serialization::{CompactSize, SerializationError, ZcashDeserialize, ZcashSerialize},
+};
+
+/// The filter type for basic filters.
+pub const BASIC_FILTER_TYPE: u8 = 0;
+
+/// The maximum number of filters in a `getcfilters` response, from BIP-157.
+pub const MAX_GETCFILTERS_SIZE: u32 = 1000;
+
+/// The maximum number of filter hashes in a `getcfheaders` response, from
+/// BIP-157.
+pub const MAX_GETCFHEADERS_SIZE: u32 = 2000;
+
+/// The Golomb-Rice coding parameter for basic filters.
+const P: u8 = 19;
+
+/// The inverse false positive rate for basic filters.
+const M: u64 = 784_931;
+
+/// The `OP_RETURN` opcode, which makes an output unspendable.
+const OP_RETURN: u8 = 0x6a;
+
+/// A serialized BIP-158 basic block filter.
+#[derive(Clone, Eq, PartialEq)]
+pub struct BlockFilter(pub Vec<u8>);
+
+/// The double SHA-256 hash of a serialized block filter.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct FilterHash(pub [u8; 32]);
+
+/// A commitment to a block filter and all the filters before it.
+///
+/// The genesis block's previous filter header is all zeroes.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct FilterHeader(pub [u8; 32]);
+
+impl fmt::Debug for BlockFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("BlockFilter")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+impl fmt::Debug for FilterHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("FilterHash")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+impl fmt::Debug for FilterHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("FilterHeader")
+            .field(&hex::encode(&self.0))
+            .finish()
+    }
+}
+
+/// Returns true if `script` is included in filters when it is created by a
+/// block.
+///
+/// Unspendable data outputs are skipped, because clients can't receive funds
+/// in them.
+pub fn is_filtered_output(script: &[u8]) -> bool {
+    !script.is_empty() && script[0] != OP_RETURN
+}
+
+impl BlockFilter {
+    /// Compute the basic filter for `block`, which spends outputs with
+    /// `spent_scripts`.
+    ///
+    /// The caller must look up the spent scripts in the chain state.
+    pub fn basic<I>(block: &Block, spent_scripts: I) -> BlockFilter
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        let elements: BTreeSet<Vec<u8>> = block
+            .transactions
+            .iter()
+            .flat_map(|transaction| transaction.outputs())
+            .map(|output| output.pk_script.0.clone())
+            .filter(|script| is_filtered_output(script))
+            .chain(
+                spent_scripts
+                    .into_iter()
+                    .filter(|script| !script.is_empty()),
+            )
+            .collect();
+
+        let bytes = CompactSize::from_len(elements.len())
+            .and_then(|count| count.zcash_serialize_to_vec())
+            .expect("blocks have fewer filter elements than the maximum compactsize");
+
+        let mut writer = BitWriter::new(bytes);
+        let mut last = 0;
+        for value in hashed_set(&block.hash(), elements.iter()) {
+            golomb_rice_encode(&mut writer, value - last);
+            last = value;
+        }
+
+        BlockFilter(writer.finish())
+    }
+
+    /// Returns true if the filter for the block with `block_hash` matches any
+    /// of `scripts`.
+    ///
+    /// Filters have false positives, but no false negatives.
+    pub fn matches_any<'a, I>(
+        &self,
+        block_hash: &BlockHeaderHash,
+        scripts: I,
+    ) -> Result<bool, SerializationError>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut reader = Cursor::new(&self.0[..]);
+        let count: u64 = CompactSize::zcash_deserialize(&mut reader)?.into();
+        let f = count.checked_mul(M).ok_or(SerializationError::Parse(
+            "compact filter element count is too large",
+        ))?;
+
+        let mut queries: Vec<u64> = scripts
+            .into_iter()
+            .map(|script| hash_to_range(block_hash, script, f))
+            .collect();
+        queries.sort_unstable();
+
+        let mut reader = BitReader::new(&self.0[reader.position() as usize..]);
+        let mut queries = queries.into_iter().peekable();
+        let mut value = 0u64;
+        for _ in 0..count {
+            value = value
+                .checked_add(golomb_rice_decode(&mut reader)?)
+                .ok_or(SerializationError::Parse("compact filter value overflowed"))?;
+            while let Some(&query) = queries.peek() {
+                if query == value {
+                    return Ok(true);
+                } else if query < value {
+                    queries.next();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Returns the hash of this filter.
+    pub fn hash(&self) -> FilterHash {
+        FilterHash(sha256d(&[&self.0]))
+    }
+
+    /// Returns the header for this filter, which commits to `previous`.
+    pub fn header(&self, previous: FilterHeader) -> FilterHeader {
+        self.hash().header(previous)
+    }
+}
+
+impl FilterHash {
+    /// Returns the header for the filter with this hash, which commits to
+    /// `previous`.
+    pub fn header(&self, previous: FilterHeader) -> FilterHeader {
+        FilterHeader(sha256d(&[&self.0, &previous.0]))
+    }
+}
+
+/// Returns the double SHA-256 hash of the concatenated `parts`.
+fn sha256d(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    for part in parts {
+        hasher.input(part);
+    }
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&Sha256::digest(&hasher.result()));
+    hash
+}
+
+/// Hash `elements` into the range `0..(elements.len() * M)`, using a key
+/// derived from `block_hash`, and return the sorted results.
+fn hashed_set<'a, I>(block_hash: &BlockHeaderHash, elements: I) -> Vec<u64>
+where
+    I: ExactSizeIterator<Item = &'a Vec<u8>>,
+{
+    let f = elements.len() as u64 * M;
+    let mut values: Vec<u64> = elements
+        .map(|element| hash_to_range(block_hash, element, f))
+        .collect();
+    values.sort_unstable();
+    values
+}
+
+/// Hash `element` into the range `0..f`, using a SipHash key derived from
+/// `block_hash`.
+fn hash_to_range(block_hash: &BlockHeaderHash, element: &[u8], f: u64) -> u64 {
+    let mut k0 = [0; 8];
+    let mut k1 = [0; 8];
+    k0.copy_from_slice(&block_hash.0[0..8]);
+    k1.copy_from_slice(&block_hash.0[8..16]);
+
+    let mut hasher = SipHasher24::new_with_keys(u64::from_le_bytes(k0), u64::from_le_bytes(k1));
+    hasher.write(element);
+
+    ((u128::from(hasher.finish()) * u128::from(f)) >> 64) as u64
+}
+
+/// Write `value` to `writer` using Golomb-Rice coding with parameter `P`.
+fn golomb_rice_encode(writer: &mut BitWriter, value: u64) {
+    for _ in 0..(value >> P) {
+        writer.write_bit(true);
+    }
+    writer.write_bit(false);
+    writer.write_bits(value, P);
+}
+
+/// Read a Golomb-Rice coded value with parameter `P` from `reader`.
+fn golomb_rice_decode(reader: &mut BitReader) -> Result<u64, SerializationError> {
+    let mut quotient = 0u64;
+    while reader.read_bit()? {
+        quotient += 1;
+    }
+    let remainder = reader.read_bits(P)?;
+
+    quotient
+        .checked_shl(P.into())
+        .filter(|shifted| shifted >> P == quotient)
+        .map(|shifted| shifted | remainder)
+        .ok_or(SerializationError::Parse("compact filter value overflowed"))
+}
+
+/// Writes bits to a byte vector, most significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// The number of bits used in the last byte, or 0 if it is full.
+    used: u8,
+}
+
+impl BitWriter {
+    fn new(bytes: Vec<u8>) -> Self {
+        BitWriter { bytes, used: 0 }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().expect("a byte was pushed above") |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+
+    /// Write the low `count` bits of `value`.
+    fn write_bits(&mut self, value: u64, count: u8) {
+        for i in (0..count).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    /// Returns the written bytes, padding the last byte with zero bits.
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads bits from a byte slice, most significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// The index of the next bit.
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Result<bool, SerializationError> {
+        let byte = self
+            .bytes
+            .get(self.position / 8)
+            .ok_or(SerializationError::Parse("compact filter was truncated"))?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn read_bits(&mut self, count: u8) -> Result<u64, SerializationError> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_chain::serialization::ZcashDeserialize;
+
+    #[test]
+    fn golomb_rice_round_trip() {
+        let values = [0, 1, (1 << P) - 1, 1 << P, 3 << P, 12_345_678];
+
+        let mut writer = BitWriter::new(Vec::new());
+        for &value in values.iter() {
+            golomb_rice_encode(&mut writer, value);
+        }
+        let bytes = writer.finish();
+
+        let mut reader = BitReader::new(&bytes);
+        for &value in values.iter() {
+            assert_eq!(golomb_rice_decode(&mut reader).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn basic_filter_matches_outputs() {
+        zebra_test::init();
+
+        let block =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..]).unwrap();
+        let hash = block.hash();
+        let coinbase_script = block.transactions[0]
+            .outputs()
+            .next()
+            .unwrap()
+            .pk_script
+            .0
+            .clone();
+        let spent_script = vec![0x51; 25];
+
+        let filter = BlockFilter::basic(&block, vec![spent_script.clone()]);
+
+        assert!(filter
+            .matches_any(&hash, vec![&coinbase_script[..]])
+            .unwrap());
+        assert!(filter.matches_any(&hash, vec![&spent_script[..]]).unwrap());
+        assert!(!filter.matches_any(&hash, vec![&[0x52; 25][..]]).unwrap());
+        assert!(!filter.matches_any(&hash, Vec::<&[u8]>::new()).unwrap());
+    }
+
+    #[test]
+    fn empty_filter() {
+        zebra_test::init();
+
+        let mut block =
+            Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..]).unwrap();
+        block.transactions.clear();
+
+        let filter = BlockFilter::basic(&block, Vec::new());
+        assert_eq!(filter.0, vec![0]);
+        assert!(!filter
+            .matches_any(&block.hash(), vec![&[0x51][..]])
+            .unwrap());
+    }
+
+    #[test]
+    fn filter_headers_chain() {
+        let filter = BlockFilter(vec![0]);
+        let genesis_header = filter.header(FilterHeader([0; 32]));
+
+        assert_eq!(genesis_header, filter.hash().header(FilterHeader([0; 32])));
+        assert_ne!(filter.header(genesis_header), genesis_header);
+    }
+}
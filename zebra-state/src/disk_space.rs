@@ -0,0 +1,256 @@
+//! Disk space checks for the on-disk state.
+//!
+//! Running out of disk space part way through a database write can leave the
+//! state in a bad state, or crash the node. So the state checks the free
+//! space on its disk before committing blocks, and pauses block commits
+//! while the free space is below the configured minimum. Commits resume once
+//! space is freed.
+//!
+//! The state also warns early: at startup if the disk doesn't have room for
+//! the rest of the chain, and during the sync if the free space is expected
+//! to reach the minimum within a day, at the recent growth rate.
+
+use std::{
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use zebra_chain::Network;
+
+/// The expected size of a fully synced mainnet state, in bytes.
+///
+/// This is a rough estimate, which should be updated as the chain grows.
+const MAINNET_EXPECTED_SIZE: u64 = 40 * GIB;
+
+/// The expected size of a fully synced testnet state, in bytes.
+const TESTNET_EXPECTED_SIZE: u64 = 10 * GIB;
+
+/// One gibibyte.
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// One mebibyte.
+const MIB: u64 = 1024 * 1024;
+
+/// The minimum time between disk space checks, while commits are running.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The time between disk space checks, while commits are paused.
+const PAUSED_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The minimum time between growth rate samples.
+const GROWTH_SAMPLE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Warn if the free space will reach the minimum within this time.
+const LOW_SPACE_WARNING_TIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Checks the free disk space for an on-disk state.
+#[derive(Debug)]
+pub(crate) struct DiskSpace {
+    /// The path of the state database.
+    path: PathBuf,
+    /// The minimum free space for block commits, in bytes.
+    min_free: u64,
+    /// The expected size of a fully synced state, in bytes.
+    expected_size: u64,
+    /// The results of recent checks.
+    checks: Mutex<Checks>,
+}
+
+/// The results of recent disk space checks.
+#[derive(Debug, Default)]
+struct Checks {
+    /// The time of the last check, and whether there was enough space.
+    last_check: Option<(Instant, bool)>,
+    /// The free space at the start of the current growth rate sample.
+    growth_sample: Option<(Instant, u64)>,
+}
+
+impl DiskSpace {
+    /// Returns a disk space checker for the state at `path`, which pauses
+    /// commits when there are less than `min_free_mb` mebibytes free.
+    pub(crate) fn new(path: PathBuf, network: Network, min_free_mb: u64) -> Self {
+        let expected_size = match network {
+            Network::Mainnet => MAINNET_EXPECTED_SIZE,
+            Network::Testnet => TESTNET_EXPECTED_SIZE,
+        };
+
+        DiskSpace {
+            path,
+            min_free: min_free_mb.saturating_mul(MIB),
+            expected_size,
+            checks: Mutex::new(Checks::default()),
+        }
+    }
+
+    /// Warns if the disk does not have room for the rest of the chain, given
+    /// the current `state_size` in bytes.
+    pub(crate) fn preflight(&self, state_size: u64) {
+        let available = match fs2::available_space(&self.path) {
+            Ok(available) => available,
+            Err(e) => {
+                tracing::warn!(path = ?self.path, ?e, "could not check free disk space");
+                return;
+            }
+        };
+
+        let needed = remaining_size(self.expected_size, state_size) + self.min_free;
+        if available < self.min_free {
+            tracing::error!(
+                path = ?self.path,
+                available_mb = available / MIB,
+                min_free_mb = self.min_free / MIB,
+                "not enough free disk space for the state: block commits will pause until space is freed"
+            );
+        } else if available < needed {
+            tracing::warn!(
+                path = ?self.path,
+                available_mb = available / MIB,
+                needed_mb = needed / MIB,
+                "the disk may run out of space before the state is synced"
+            );
+        } else {
+            tracing::info!(
+                path = ?self.path,
+                available_mb = available / MIB,
+                needed_mb = needed / MIB,
+                "checked free disk space for the state"
+            );
+        }
+    }
+
+    /// Waits until there is enough free space to commit a block.
+    ///
+    /// Returns immediately if the space was checked recently, and there was
+    /// enough space.
+    pub(crate) async fn wait_for_space(&self) {
+        let mut paused = false;
+        while !self.check() {
+            if !paused {
+                tracing::error!(
+                    path = ?self.path,
+                    min_free_mb = self.min_free / MIB,
+                    "low disk space: pausing block commits until space is freed"
+                );
+                paused = true;
+            }
+            tokio::time::delay_for(PAUSED_CHECK_INTERVAL).await;
+        }
+
+        if paused {
+            tracing::info!(path = ?self.path, "disk space is available, resuming block commits");
+        }
+    }
+
+    /// Returns true if there is enough free space to commit a block.
+    ///
+    /// Also updates the growth rate, and warns if the disk will be full soon.
+    fn check(&self) -> bool {
+        let now = Instant::now();
+        let mut checks = self.checks.lock().unwrap();
+
+        if let Some((last_time, true)) = checks.last_check {
+            if now.duration_since(last_time) < CHECK_INTERVAL {
+                return true;
+            }
+        }
+
+        let available = match fs2::available_space(&self.path) {
+            Ok(available) => available,
+            Err(e) => {
+                // Don't stop the node because the check failed
+                tracing::warn!(path = ?self.path, ?e, "could not check free disk space");
+                checks.last_check = Some((now, true));
+                return true;
+            }
+        };
+
+        let has_space = available >= self.min_free;
+        checks.last_check = Some((now, has_space));
+
+        match checks.growth_sample {
+            Some((start, start_available))
+                if now.duration_since(start) >= GROWTH_SAMPLE_INTERVAL =>
+            {
+                let time_left = time_until_full(
+                    start_available,
+                    available,
+                    now.duration_since(start),
+                    self.min_free,
+                );
+                if let Some(time_left) = time_left {
+                    if has_space && time_left < LOW_SPACE_WARNING_TIME {
+                        tracing::warn!(
+                            path = ?self.path,
+                            available_mb = available / MIB,
+                            ?time_left,
+                            "disk space is running low: block commits will pause when it runs out"
+                        );
+                    }
+                }
+                checks.growth_sample = Some((now, available));
+            }
+            Some(_) => {}
+            None => checks.growth_sample = Some((now, available)),
+        }
+
+        has_space
+    }
+}
+
+/// Returns the expected size of the rest of the chain, given the current
+/// `state_size`.
+fn remaining_size(expected_size: u64, state_size: u64) -> u64 {
+    expected_size.saturating_sub(state_size)
+}
+
+/// Returns the time until the free space reaches `min_free`, if free space
+/// went from `start_available` to `available` over `elapsed`.
+///
+/// Returns `None` if the free space is not shrinking.
+fn time_until_full(
+    start_available: u64,
+    available: u64,
+    elapsed: Duration,
+    min_free: u64,
+) -> Option<Duration> {
+    let used = start_available.checked_sub(available)?;
+    if used == 0 || elapsed == Duration::from_secs(0) {
+        return None;
+    }
+
+    let bytes_per_sec = used as f64 / elapsed.as_secs_f64();
+    let left = available.saturating_sub(min_free) as f64;
+    Some(Duration::from_secs_f64(left / bytes_per_sec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_chain_size() {
+        assert_eq!(remaining_size(10 * GIB, 4 * GIB), 6 * GIB);
+        // The chain can be larger than our estimate
+        assert_eq!(remaining_size(10 * GIB, 12 * GIB), 0);
+    }
+
+    #[test]
+    fn growth_rate_estimate() {
+        let hour = Duration::from_secs(60 * 60);
+
+        // 1 GiB per hour, with 10 GiB left above the 1 GiB minimum
+        let time_left = time_until_full(12 * GIB, 11 * GIB, hour, GIB).unwrap();
+        assert_eq!(time_left.as_secs_f64().round(), (hour * 10).as_secs_f64());
+
+        // Free space is growing, or not changing
+        assert_eq!(time_until_full(11 * GIB, 12 * GIB, hour, GIB), None);
+        assert_eq!(time_until_full(11 * GIB, 11 * GIB, hour, GIB), None);
+
+        // Already below the minimum
+        assert_eq!(
+            time_until_full(GIB, GIB / 2, hour, GIB),
+            Some(Duration::from_secs(0))
+        );
+    }
+}
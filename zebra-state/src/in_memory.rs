@@ -3,27 +3,111 @@
 //! This service is provided as an independent implementation of the
 //! zebra-state service to use in verifying the correctness of `on_disk`'s
 //! `Service` implementation.
-use super::{Request, Response};
+use super::{
+    tip_watch::TipWatch,
+    utxo::{PendingUtxos, Utxo},
+    KnownBlock, Request, Response, TreeSizes, ValuePools,
+};
 use futures::prelude::*;
 use std::{
+    collections::BTreeMap,
     error,
     future::Future,
     pin::Pin,
     task::{Context, Poll},
 };
 use tower::{buffer::Buffer, Service};
-use zebra_chain::block::BlockHeaderHash;
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    transaction::OutPoint,
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight,
+    },
+};
 
 mod block_index;
 
 #[derive(Default)]
 struct InMemoryState {
     index: block_index::BlockIndex,
+    tip_watch: TipWatch,
+    pending_utxos: PendingUtxos,
+    /// The chain value pools after each block in the index.
+    value_pools: BTreeMap<BlockHeight, ValuePools>,
+    /// The note commitment tree sizes after each block in the index.
+    tree_sizes: BTreeMap<BlockHeight, TreeSizes>,
 }
 
 impl InMemoryState {
-    fn contains(&mut self, _hash: BlockHeaderHash) -> Result<Option<u32>, Error> {
-        todo!()
+    /// Returns the output at `outpoint`, if it is in the state.
+    fn utxo(&mut self, outpoint: OutPoint) -> Option<Utxo> {
+        let (transaction, block_hash) = self.index.get_transaction(outpoint.hash)?;
+        let output = transaction.outputs().nth(outpoint.index as usize)?.clone();
+        let height = self.index.get(block_hash)?.coinbase_height()?;
+
+        Some(Utxo {
+            output,
+            height,
+            from_coinbase: transaction.is_coinbase(),
+        })
+    }
+
+    /// Returns the chain value pools after the block at `height`.
+    fn value_pools(&self, height: BlockHeight) -> Result<ValuePools, Error> {
+        self.value_pools
+            .get(&height)
+            .copied()
+            .ok_or_else(|| "value pools could not be found".into())
+    }
+
+    /// Returns the note commitment tree sizes after the block at `height`.
+    fn tree_sizes(&self, height: BlockHeight) -> Result<TreeSizes, Error> {
+        self.tree_sizes
+            .get(&height)
+            .copied()
+            .ok_or_else(|| "tree sizes could not be found".into())
+    }
+
+    /// Returns the chain value pools after adding `block`, or an error if
+    /// `block` would make the Sprout value pool negative.
+    ///
+    /// Returns an error if the value pools before `block` are missing.
+    fn check_turnstile(
+        &mut self,
+        block: &Block,
+        deferred_pool_contribution: Amount<NonNegative>,
+    ) -> Result<ValuePools, Error> {
+        let height = block
+            .coinbase_height()
+            .ok_or("block has no coinbase height")?;
+        let previous_pools = match height.previous() {
+            None => ValuePools::default(),
+            Some(previous_height) => self.value_pools(previous_height)?,
+        };
+
+        // Later transactions in the block can spend its own outputs
+        let block_outputs = Utxo::from_block(block);
+        let pools = previous_pools.add_block(block, deferred_pool_contribution, |outpoint| {
+            block_outputs
+                .get(outpoint)
+                .cloned()
+                .or_else(|| self.utxo(*outpoint))
+                .map(|utxo| utxo.output.value)
+        })?;
+        pools.check_turnstile(block.hash(), height)?;
+
+        Ok(pools)
+    }
+
+    fn contains(&mut self, hash: BlockHeaderHash) -> Result<Option<u32>, Error> {
+        Ok(self.index.get(hash).map(|block| {
+            self.index.depth(
+                block
+                    .coinbase_height()
+                    .expect("blocks in the state have a coinbase height"),
+            )
+        }))
     }
 }
 
@@ -40,11 +124,33 @@ impl Service<Request> for InMemoryState {
     fn call(&mut self, req: Request) -> Self::Future {
         tracing::debug!(?req);
         match req {
-            Request::AddBlock { block } => {
+            Request::AddBlock {
+                block,
+                deferred_pool_contribution,
+            } => {
                 let result = self
-                    .index
-                    .insert(block)
-                    .map(|hash| Response::Added { hash });
+                    .check_turnstile(&block, deferred_pool_contribution)
+                    .and_then(|pools| {
+                        let height = block
+                            .coinbase_height()
+                            .expect("blocks with value pools have a coinbase height");
+                        let sizes = match height.previous() {
+                            None => TreeSizes::default(),
+                            Some(previous_height) => self.tree_sizes(previous_height)?,
+                        }
+                        .add_block(&block);
+
+                        let hash = self.index.insert(block.clone())?;
+                        self.value_pools.insert(height, pools);
+                        self.tree_sizes.insert(height, sizes);
+                        Ok(Response::Added { hash })
+                    });
+                if result.is_ok() {
+                    self.pending_utxos.respond_block(&block);
+                }
+                if let Some(tip) = self.index.get_tip() {
+                    self.tip_watch.update(tip.hash());
+                }
 
                 async { result }.boxed()
             }
@@ -57,6 +163,38 @@ impl Service<Request> for InMemoryState {
 
                 async move { result }.boxed()
             }
+            Request::GetBlockByHeight { height } => {
+                let result = self
+                    .index
+                    .get(height)
+                    .map(|block| Response::Block { block })
+                    .ok_or_else(|| "block could not be found".into());
+
+                async move { result }.boxed()
+            }
+            Request::GetBlockHeaderByHeight { height } => {
+                let result = self
+                    .index
+                    .get(height)
+                    .map(|block| Response::BlockHeader {
+                        header: block.header,
+                    })
+                    .ok_or_else(|| "block could not be found".into());
+
+                async move { result }.boxed()
+            }
+            Request::GetTransaction { hash } => {
+                let result = self
+                    .index
+                    .get_transaction(hash)
+                    .map(|(transaction, block_hash)| Response::Transaction {
+                        transaction,
+                        block_hash,
+                    })
+                    .ok_or_else(|| "transaction could not be found".into());
+
+                async move { result }.boxed()
+            }
             Request::GetTip => {
                 let result = self
                     .index
@@ -67,6 +205,32 @@ impl Service<Request> for InMemoryState {
 
                 async move { result }.boxed()
             }
+            Request::AwaitTipChange { tip } => {
+                let changed = self.tip_watch.changed_from(tip);
+
+                async move {
+                    changed
+                        .await
+                        .map(|hash| Response::Tip { hash })
+                        .ok_or_else(|| "zebra-state was dropped".into())
+                }
+                .boxed()
+            }
+            Request::AwaitUtxo { outpoint } => {
+                let added = self.pending_utxos.queue(outpoint);
+                let utxo = self.utxo(outpoint);
+
+                async move {
+                    match utxo {
+                        Some(utxo) => Ok(Response::Utxo(utxo)),
+                        None => added
+                            .await
+                            .map(Response::Utxo)
+                            .ok_or_else(|| "zebra-state was dropped".into()),
+                    }
+                }
+                .boxed()
+            }
             Request::GetDepth { hash } => {
                 let res = self.contains(hash);
 
@@ -77,6 +241,21 @@ impl Service<Request> for InMemoryState {
                 }
                 .boxed()
             }
+            Request::IsFinalized { hash } => {
+                let is_finalized = self
+                    .index
+                    .get(hash)
+                    .map(|block| self.index.depth(block.coinbase_height().unwrap()))
+                    .map(crate::is_finalized_depth)
+                    .unwrap_or(false);
+
+                async move { Ok(Response::IsFinalized(is_finalized)) }.boxed()
+            }
+            Request::KnownBlock { hash } => {
+                let known_block = self.index.get(hash).map(|_| KnownBlock::BestChain);
+
+                async move { Ok(Response::KnownBlock(known_block)) }.boxed()
+            }
             Request::GetBlockLocator { genesis } => {
                 let tip = self.index.get_tip();
                 let tip = match tip {
@@ -106,6 +285,23 @@ impl Service<Request> for InMemoryState {
 
                 async move { Ok(Response::BlockLocator { block_locator }) }.boxed()
             }
+            Request::GetValuePools { height } => {
+                let result = self.value_pools(height).map(Response::ValuePools);
+
+                async move { result }.boxed()
+            }
+            Request::GetTreeSizes { height } => {
+                let result = self.tree_sizes(height).map(Response::TreeSizes);
+
+                async move { result }.boxed()
+            }
+            Request::DatabaseInfo => {
+                async move { Err("the in-memory state does not have a database".into()) }.boxed()
+            }
+            Request::GetCompactFilters { .. } | Request::GetCompactFilterHeaders { .. } => {
+                async move { Err("the in-memory state does not support compact filters".into()) }
+                    .boxed()
+            }
         }
     }
 }
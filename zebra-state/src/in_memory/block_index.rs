@@ -5,12 +5,16 @@ use std::{
 };
 use zebra_chain::{
     block::{Block, BlockHeaderHash},
+    transaction::{Transaction, TransactionHash},
     types::BlockHeight,
 };
+
+use crate::FinalizedFork;
 #[derive(Default)]
 pub(super) struct BlockIndex {
     by_hash: HashMap<BlockHeaderHash, Arc<Block>>,
     by_height: BTreeMap<BlockHeight, Arc<Block>>,
+    by_txid: HashMap<TransactionHash, BlockHeaderHash>,
 }
 
 impl BlockIndex {
@@ -21,13 +25,24 @@ impl BlockIndex {
         let block = block.into();
         let hash = block.as_ref().into();
         let height = block.coinbase_height().unwrap();
+        let depth = self.depth(height);
 
         match self.by_height.entry(height) {
             Entry::Vacant(entry) => {
                 let _ = entry.insert(block.clone());
+                for transaction in &block.transactions {
+                    let _ = self
+                        .by_txid
+                        .insert(TransactionHash::from(transaction.as_ref()), hash);
+                }
                 let _ = self.by_hash.insert(hash, block);
                 Ok(hash)
             }
+            Entry::Occupied(_) if crate::is_finalized_depth(depth) => Err(FinalizedFork {
+                hash,
+                height,
+                depth,
+            })?,
             Entry::Occupied(_) => Err("forks in the chain aren't supported yet")?,
         }
     }
@@ -40,6 +55,31 @@ impl BlockIndex {
         .cloned()
     }
 
+    /// Returns the transaction with `hash`, and the hash of the block that
+    /// contains it.
+    pub(super) fn get_transaction(
+        &self,
+        hash: TransactionHash,
+    ) -> Option<(Arc<Transaction>, BlockHeaderHash)> {
+        let block_hash = *self.by_txid.get(&hash)?;
+        let transaction = self.by_hash[&block_hash]
+            .transactions
+            .iter()
+            .find(|transaction| TransactionHash::from(transaction.as_ref()) == hash)
+            .cloned()?;
+
+        Some((transaction, block_hash))
+    }
+
+    /// Returns the number of blocks above `height` in the chain.
+    pub(super) fn depth(&self, height: BlockHeight) -> u32 {
+        self.by_height
+            .keys()
+            .next_back()
+            .map(|tip_height| crate::block_depth(*tip_height, height))
+            .unwrap_or(0)
+    }
+
     pub(super) fn get_tip(&self) -> Option<Arc<Block>> {
         self.by_height
             .iter()
@@ -8,6 +8,30 @@
 //! * BlockHeight -> Block
 //!
 //! Inserting a block into the service will create a mapping in each tree for that block.
+//!
+//! ## Finality
+//!
+//! Blocks that are at least [`MAX_BLOCK_REORG_HEIGHT`] blocks below the tip of
+//! the best chain are finalized. The state never replaces a finalized block
+//! with a block from a fork: it rejects those blocks with a [`FinalizedFork`]
+//! error. So services that need irreversible blocks, like exchanges crediting
+//! deposits, can rely on [`Request::IsFinalized`].
+//!
+//! ## Value Pools
+//!
+//! The state tracks the total value in each of the chain's [`ValuePools`].
+//! Blocks that would withdraw more value from the Sprout pool than it holds
+//! are rejected with a [`NegativeValuePool`] error.
+//!
+//! It also tracks the number of note commitments in each of the chain's
+//! note commitment trees, as [`TreeSizes`].
+//!
+//! ## Networks
+//!
+//! Each network's state is stored in its own subdirectory of the cache
+//! directory, like `state/mainnet`. The state records its network, and
+//! refuses to open with a [`WrongNetwork`] error if it is used with a
+//! different network.
 
 #![doc(html_favicon_url = "https://www.zfnd.org/images/zebra-favicon-128.png")]
 #![doc(html_logo_url = "https://www.zfnd.org/images/zebra-icon.png")]
@@ -17,23 +41,63 @@
 use color_eyre::eyre::{eyre, Report};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::{error, iter, sync::Arc};
+use std::{convert::TryFrom, error, fmt, iter, sync::Arc};
 use tower::{Service, ServiceExt};
 
 use zebra_chain::{
-    block::{Block, BlockHeaderHash},
-    types::BlockHeight,
+    block::{Block, BlockHeader, BlockHeaderHash},
+    transaction::{OutPoint, Transaction, TransactionHash},
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight, HeightDiff,
+    },
+    Network,
 };
 
+use compact_filter::{BlockFilter, FilterHash, FilterHeader};
+
+pub mod chain;
+pub mod compact_filter;
+mod disk_space;
 pub mod in_memory;
 pub mod on_disk;
+mod tip_watch;
+mod tree_size;
+mod utxo;
+mod value_pool;
+
+pub use tree_size::TreeSizes;
+pub use utxo::Utxo;
+pub use value_pool::{NegativeValuePool, ValuePools};
 
 /// Configuration for networking code.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    /// The root directory for the state storage
+    /// The root directory for the state storage.
+    ///
+    /// Each network's state is stored in a separate subdirectory, like
+    /// `state/mainnet` or `state/testnet`.
     pub cache_dir: Option<PathBuf>,
+
+    /// Whether to compute and store BIP-158 compact block filters.
+    ///
+    /// When this is enabled on an existing state, filters for the blocks
+    /// already in the state are computed on startup.
+    #[serde(default)]
+    pub compact_filters: bool,
+
+    /// The minimum free disk space for the state, in mebibytes.
+    ///
+    /// Block commits pause while the free space on the state's disk is below
+    /// this limit, and resume once space is freed.
+    #[serde(default = "default_min_free_disk_space_mb")]
+    pub min_free_disk_space_mb: u64,
+}
+
+/// Returns the default minimum free disk space, in mebibytes.
+fn default_min_free_disk_space_mb() -> u64 {
+    1024
 }
 
 impl Config {
@@ -44,16 +108,60 @@ impl Config {
     ///
     /// This function should panic if the user of `zebra-state` doesn't configure
     /// a directory to store the state.
-    pub(crate) fn sled_config(&self) -> sled::Config {
-        let path = self
-            .cache_dir
+    pub(crate) fn sled_config(&self, network: Network) -> sled::Config {
+        sled::Config::default().path(self.state_path(network))
+    }
+
+    /// Returns the path of the sled database for `network`.
+    ///
+    /// # Details
+    ///
+    /// This function should panic if the user of `zebra-state` doesn't configure
+    /// a directory to store the state.
+    pub(crate) fn state_path(&self, network: Network) -> PathBuf {
+        self.network_dir("state", network).unwrap_or_else(|| {
+            todo!("create a nice user facing error explaining how to set the cache directory")
+        })
+    }
+
+    /// Returns the subdirectory of the cache directory for `kind` data on
+    /// `network`, like `state/mainnet`.
+    ///
+    /// Other components can use this function to store their data alongside
+    /// the state, separated by network. Returns `None` if the cache directory
+    /// is not set.
+    pub fn network_dir(&self, kind: &str, network: Network) -> Option<PathBuf> {
+        let network = match network {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+        };
+
+        self.cache_dir
             .as_ref()
-            .unwrap_or_else(|| {
-                todo!("create a nice user facing error explaining how to set the cache directory")
-            })
-            .join("state");
+            .map(|cache_dir| cache_dir.join(kind).join(network))
+    }
+
+    /// Returns an error if the cache directory contains a state that was
+    /// created before states were separated by network.
+    ///
+    /// Zebra can't tell which network that state belongs to without opening
+    /// it, so the user has to move it to the right subdirectory.
+    pub(crate) fn check_legacy_state(&self) -> Result<(), Error> {
+        let legacy_path = match &self.cache_dir {
+            Some(cache_dir) => cache_dir.join("state"),
+            None => return Ok(()),
+        };
 
-        sled::Config::default().path(path)
+        if legacy_path.join("db").exists() || legacy_path.join("conf").exists() {
+            Err(format!(
+                "the state at {:?} is not in a network subdirectory: move its contents to {:?} or {:?}",
+                legacy_path,
+                legacy_path.join("mainnet"),
+                legacy_path.join("testnet"),
+            ))?;
+        }
+
+        Ok(())
     }
 }
 
@@ -64,7 +172,11 @@ impl Default for Config {
             .ok()
             .or_else(|| dirs::cache_dir().map(|dir| dir.join("zebra")));
 
-        Self { cache_dir }
+        Self {
+            cache_dir,
+            compact_filters: false,
+            min_free_disk_space_mb: default_min_free_disk_space_mb(),
+        }
     }
 }
 
@@ -76,12 +188,45 @@ pub enum Request {
     AddBlock {
         /// The block to be added to the state
         block: Arc<Block>,
+        /// The part of the block subsidy that the block adds to the deferred
+        /// development fund pool
+        ///
+        /// The state can't calculate block subsidies, so callers calculate
+        /// this value using `zebra_consensus::parameters::deferred_pool_contribution`.
+        deferred_pool_contribution: Amount<NonNegative>,
     },
     /// Get a block from the zebra-state
     GetBlock {
         /// The hash used to identify the block
         hash: BlockHeaderHash,
     },
+    /// Get the block at a height in the current best chain
+    GetBlockByHeight {
+        /// The height of the block
+        height: BlockHeight,
+    },
+    /// Get the header of the block at a height in the current best chain
+    ///
+    /// Only the header is read, so this request is cheaper than
+    /// `GetBlockByHeight`.
+    GetBlockHeaderByHeight {
+        /// The height of the block
+        height: BlockHeight,
+    },
+    /// Get a transaction, and the hash of the block that contains it
+    GetTransaction {
+        /// The hash of the transaction
+        hash: TransactionHash,
+    },
+    /// Wait until the output at `outpoint` is in the state
+    ///
+    /// Returns immediately if the output is already in the state. This
+    /// request never fails if the output is missing, so callers should add
+    /// a timeout.
+    AwaitUtxo {
+        /// The outpoint of the output
+        outpoint: OutPoint,
+    },
     /// Get a block locator list for the current best chain
     GetBlockLocator {
         /// The genesis block of the current best chain
@@ -89,11 +234,64 @@ pub enum Request {
     },
     /// Get the block that is the tip of the current chain
     GetTip,
+    /// Wait until the tip of the current chain is not `tip`
+    ///
+    /// Returns immediately if the tip has already changed.
+    AwaitTipChange {
+        /// The hash of the tip that the caller already knows about
+        tip: BlockHeaderHash,
+    },
     /// Ask the state if the given hash is part of the current best chain
     GetDepth {
         /// The hash to check against the current chain
         hash: BlockHeaderHash,
     },
+    /// Ask the state if the given hash is already known, without loading the
+    /// block
+    KnownBlock {
+        /// The hash to look for
+        hash: BlockHeaderHash,
+    },
+    /// Ask the state if the given hash is finalized in the current best chain
+    ///
+    /// Unknown blocks are not finalized.
+    IsFinalized {
+        /// The hash to check against the current chain
+        hash: BlockHeaderHash,
+    },
+    /// Get the compact filters for a range of blocks in the current best
+    /// chain
+    GetCompactFilters {
+        /// The height of the first block in the range
+        start_height: BlockHeight,
+        /// The hash of the last block in the range
+        stop_hash: BlockHeaderHash,
+    },
+    /// Get the compact filter hashes for a range of blocks in the current best
+    /// chain, and the filter header before the range
+    GetCompactFilterHeaders {
+        /// The height of the first block in the range
+        start_height: BlockHeight,
+        /// The hash of the last block in the range
+        stop_hash: BlockHeaderHash,
+    },
+    /// Get the chain value pools after the block at a height in the current
+    /// best chain
+    GetValuePools {
+        /// The height of the block
+        height: BlockHeight,
+    },
+    /// Get the note commitment tree sizes after the block at a height in the
+    /// current best chain
+    GetTreeSizes {
+        /// The height of the block
+        height: BlockHeight,
+    },
+    /// Get diagnostic information about the state database
+    ///
+    /// This request reads every entry in the database, so it can take a long
+    /// time for a large state. It also updates the database metrics.
+    DatabaseInfo,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -105,17 +303,35 @@ pub enum Response {
         /// The hash of the block that was added
         hash: BlockHeaderHash,
     },
-    /// The response to a `GetBlock` request by hash
+    /// The response to a `GetBlock` request by hash, or a `GetBlockByHeight`
+    /// request
     Block {
         /// The block that was requested
         block: Arc<Block>,
     },
+    /// The response to a `GetBlockHeaderByHeight` request
+    BlockHeader {
+        /// The block header that was requested
+        header: BlockHeader,
+    },
+    /// The response to a `GetTransaction` request
+    Transaction {
+        /// The transaction that was requested
+        transaction: Arc<Transaction>,
+        /// The hash of the block that contains the transaction
+        block_hash: BlockHeaderHash,
+    },
+    /// The response to an `AwaitUtxo` request
+    Utxo(
+        /// The output, and where it was created
+        Utxo,
+    ),
     /// The response to a `GetBlockLocator` request
     BlockLocator {
         /// The set of blocks that make up the block locator
         block_locator: Vec<BlockHeaderHash>,
     },
-    /// The response to a `GetTip` request
+    /// The response to a `GetTip` or `AwaitTipChange` request
     Tip {
         /// The hash of the block at the tip of the current chain
         hash: BlockHeaderHash,
@@ -126,13 +342,137 @@ pub enum Response {
         /// The number of blocks above the given block in the current best chain
         Option<u32>,
     ),
+    /// The response to a `KnownBlock` request
+    KnownBlock(
+        /// Where the block is known, or `None` if it is unknown
+        Option<KnownBlock>,
+    ),
+    /// The response to an `IsFinalized` request
+    IsFinalized(
+        /// Whether the block is at least `MAX_BLOCK_REORG_HEIGHT` blocks below
+        /// the tip of the current best chain
+        bool,
+    ),
+    /// The response to a `GetCompactFilters` request
+    CompactFilters {
+        /// The hash and filter of each block in the range, in height order
+        filters: Vec<(BlockHeaderHash, BlockFilter)>,
+    },
+    /// The response to a `GetCompactFilterHeaders` request
+    CompactFilterHeaders {
+        /// The filter header of the block before the range
+        previous_header: FilterHeader,
+        /// The filter hash of each block in the range, in height order
+        filter_hashes: Vec<FilterHash>,
+    },
+    /// The response to a `GetValuePools` request
+    ValuePools(
+        /// The total value in each pool
+        ValuePools,
+    ),
+    /// The response to a `GetTreeSizes` request
+    TreeSizes(
+        /// The number of note commitments in each tree
+        TreeSizes,
+    ),
+    /// The response to a `DatabaseInfo` request
+    DatabaseInfo(
+        /// The tip, size, and trees of the database
+        on_disk::StateInfo,
+    ),
+}
+
+/// Where a known block is stored or queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KnownBlock {
+    /// The block is in the current best chain in the state.
+    ///
+    /// The state does not store side-chains yet, so this is the only location
+    /// returned by the state service.
+    BestChain,
+    /// The block is queued for verification, but it has not been committed to
+    /// the state.
+    ///
+    /// Returned by services that track in-flight blocks, like the chain
+    /// verifier.
+    Queue,
+}
+
+/// The maximum number of blocks that a chain reorganisation can roll back.
+///
+/// Blocks that are at least this many blocks below the tip of the best chain
+/// are finalized. This matches `MAX_REORG_LENGTH` in zcashd.
+pub const MAX_BLOCK_REORG_HEIGHT: u32 = 99;
+
+/// Returns true if a block that is `depth` blocks below the tip is finalized.
+fn is_finalized_depth(depth: u32) -> bool {
+    depth >= MAX_BLOCK_REORG_HEIGHT
+}
+
+/// Returns the number of blocks above `height` in a chain with a tip at
+/// `tip_height`.
+///
+/// Heights above the tip have a depth of zero.
+fn block_depth(tip_height: BlockHeight, height: BlockHeight) -> u32 {
+    u32::try_from((tip_height - height).max(0)).expect("valid heights fit in a u32")
+}
+
+/// The error returned by the state for blocks that would replace a finalized
+/// block.
+///
+/// Callers can downcast the state error to this type, to distinguish forks
+/// from other errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FinalizedFork {
+    /// The hash of the rejected block.
+    pub hash: BlockHeaderHash,
+    /// The height of the rejected block.
+    pub height: BlockHeight,
+    /// The number of blocks above the finalized block in the current best
+    /// chain.
+    pub depth: u32,
+}
+
+impl fmt::Display for FinalizedFork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "block {:?} at height {:?} would replace a finalized block {} blocks below the tip",
+            self.hash, self.height, self.depth
+        )
+    }
 }
 
+impl error::Error for FinalizedFork {}
+
+/// The error returned when opening a state that was created for a different
+/// network.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrongNetwork {
+    /// The path of the state.
+    pub path: PathBuf,
+    /// The network that the state was opened for.
+    pub expected: Network,
+    /// The network that the state was created for.
+    pub found: Network,
+}
+
+impl fmt::Display for WrongNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the state at {:?} was created for {:?}, but zebrad is configured for {:?}",
+            self.path, self.found, self.expected
+        )
+    }
+}
+
+impl error::Error for WrongNetwork {}
+
 /// Get the heights of the blocks for constructing a block_locator list
 fn block_locator_heights(tip_height: BlockHeight) -> impl Iterator<Item = BlockHeight> {
-    iter::successors(Some(1u32), |h| h.checked_mul(2))
-        .flat_map(move |step| tip_height.0.checked_sub(step))
-        .map(BlockHeight)
+    iter::successors(Some(1), |step: &HeightDiff| step.checked_mul(2))
+        .flat_map(move |step| tip_height - step)
         .chain(iter::once(BlockHeight(0)))
 }
 
@@ -190,7 +530,34 @@ mod tests {
     fn test_no_path() {
         zebra_test::init();
 
-        let bad_config = Config { cache_dir: None };
-        let _unreachable = bad_config.sled_config();
+        let bad_config = Config {
+            cache_dir: None,
+            compact_filters: false,
+            min_free_disk_space_mb: 0,
+        };
+        let _unreachable = bad_config.sled_config(Network::Mainnet);
+    }
+
+    #[test]
+    fn network_dirs() {
+        zebra_test::init();
+
+        let config = Config {
+            cache_dir: Some(PathBuf::from("cache")),
+            compact_filters: false,
+            min_free_disk_space_mb: 0,
+        };
+        assert_eq!(
+            config.state_path(Network::Mainnet),
+            PathBuf::from("cache").join("state").join("mainnet")
+        );
+        assert_eq!(
+            config.state_path(Network::Testnet),
+            PathBuf::from("cache").join("state").join("testnet")
+        );
+        assert_ne!(
+            config.network_dir("peers", Network::Mainnet),
+            config.network_dir("peers", Network::Testnet)
+        );
     }
 }
@@ -1,38 +1,123 @@
 //! The primary implementation of the `zebra_state::Service` built upon sled
-use super::{Request, Response};
-use crate::Config;
+use super::{FinalizedFork, KnownBlock, Request, Response, WrongNetwork};
+use crate::{
+    compact_filter::{self, BlockFilter, FilterHeader},
+    disk_space::DiskSpace,
+    tip_watch::TipWatch,
+    utxo::{PendingUtxos, Utxo},
+    Config, TreeSizes, ValuePools,
+};
 use futures::prelude::*;
+use sled::{
+    transaction::{
+        ConflictableTransactionError, ConflictableTransactionResult, TransactionError,
+        TransactionalTree,
+    },
+    Transactional,
+};
 use std::sync::Arc;
 use std::{
+    collections::HashMap,
+    convert::TryInto,
     error,
     future::Future,
+    path::PathBuf,
     pin::Pin,
     task::{Context, Poll},
 };
-use tower::{buffer::Buffer, Service};
+use tower::{buffer::Buffer, Service, ServiceExt};
 use zebra_chain::serialization::{ZcashDeserialize, ZcashSerialize};
 use zebra_chain::{
-    block::{Block, BlockHeaderHash},
-    types::BlockHeight,
+    block::{Block, BlockHeader, BlockHeaderHash},
+    parameters::genesis_block,
+    transaction::{OutPoint, Transaction, TransactionHash, TransparentInput},
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight,
+    },
+    Network,
 };
 
+/// The number of blocks between updates of the database size metric.
+const SIZE_METRIC_INTERVAL: u32 = 100;
+
 #[derive(Clone)]
 struct SledState {
     storage: sled::Db,
+    /// The path of the sled database.
+    path: PathBuf,
+    /// Whether to compute and store compact block filters.
+    compact_filters: bool,
+    /// The hash of the best chain tip, updated when blocks are committed.
+    tip_watch: TipWatch,
+    /// The `AwaitUtxo` requests that are waiting for blocks to be committed.
+    pending_utxos: PendingUtxos,
+    /// Pauses block commits while disk space is low.
+    disk_space: Arc<DiskSpace>,
 }
 
 impl SledState {
-    pub(crate) fn new(config: &Config) -> Self {
-        let config = config.sled_config();
+    pub(crate) fn new(config: &Config, network: Network) -> Self {
+        let storage = open(config, network).unwrap_or_else(|e| {
+            panic!("could not open the state: {}", e);
+        });
+
+        let disk_space = DiskSpace::new(
+            config.state_path(network),
+            network,
+            config.min_free_disk_space_mb,
+        );
+        disk_space.preflight(storage.size_on_disk().unwrap_or(0));
+
+        let mut state = Self {
+            storage,
+            path: config.state_path(network),
+            compact_filters: config.compact_filters,
+            tip_watch: TipWatch::default(),
+            pending_utxos: PendingUtxos::default(),
+            disk_space: Arc::new(disk_space),
+        };
+
+        state
+            .index_missing_transactions()
+            .expect("indexing transactions for existing blocks succeeds");
+
+        if state.compact_filters {
+            state
+                .index_missing_filters()
+                .expect("computing compact filters for existing blocks succeeds");
+        }
+
+        state
+            .index_missing_value_pools()
+            .expect("computing value pools for existing blocks succeeds");
+
+        state
+            .index_missing_tree_sizes()
+            .expect("computing tree sizes for existing blocks succeeds");
+
+        // Genesis blocks are hard-coded, so we commit them directly, rather
+        // than downloading and verifying them.
+        if state.get_tip().unwrap().is_none() {
+            if let Some(genesis) = genesis_block(network) {
+                tracing::info!(?network, hash = ?genesis.hash(), "committing genesis block to empty state");
+                state
+                    .insert(genesis, Amount::zero())
+                    .expect("committing the genesis block to an empty state succeeds");
+            }
+        }
 
-        Self {
-            storage: config.open().unwrap(),
+        if let Some(tip) = state.get_tip().unwrap() {
+            state.tip_watch.update(tip.hash());
         }
+
+        state
     }
 
     pub(super) fn insert(
         &mut self,
         block: impl Into<Arc<Block>>,
+        deferred_pool_contribution: Amount<NonNegative>,
     ) -> Result<BlockHeaderHash, Error> {
         let block = block.into();
         let hash: BlockHeaderHash = block.as_ref().into();
@@ -44,13 +129,435 @@ impl SledState {
         let mut bytes = Vec::new();
         block.zcash_serialize(&mut bytes)?;
 
-        // TODO(jlusby): make this transactional
-        by_height.insert(&height.0.to_be_bytes(), bytes.as_slice())?;
-        by_hash.insert(&hash.0, bytes)?;
+        // The block is already in the chain, and its indexes are up to date
+        let key = height.0.to_be_bytes();
+        if by_height.get(&key)?.as_deref() == Some(bytes.as_slice())
+            && self.storage.open_tree(b"value_pools")?.contains_key(&key)?
+        {
+            return Ok(hash);
+        }
+
+        // Forks can't replace finalized blocks
+        if by_height.contains_key(&height.0.to_be_bytes())? && !self.contains(&hash)? {
+            let tip_height = self
+                .get_tip()?
+                .and_then(|tip| tip.coinbase_height())
+                .expect("storage must have a tip if it contains a block");
+            let depth = crate::block_depth(tip_height, height);
+            if crate::is_finalized_depth(depth) {
+                Err(FinalizedFork {
+                    hash,
+                    height,
+                    depth,
+                })?;
+            }
+        }
+
+        // Blocks can't withdraw more than the Sprout pool holds
+        let undo = self.value_pool_undo(height)?;
+        let pools =
+            self.calculate_value_pools(&block, height, deferred_pool_contribution, &undo)?;
+        pools.check_turnstile(hash, height)?;
+        let sizes = self.calculate_tree_sizes(&block, height)?;
+
+        let by_txid = self.storage.open_tree(b"by_txid")?;
+        let value_pools = self.storage.open_tree(b"value_pools")?;
+        let value_pool_outputs = self.storage.open_tree(b"value_pool_outputs")?;
+        let tree_sizes = self.storage.open_tree(b"tree_sizes")?;
+        let cfilters = self.storage.open_tree(b"cfilters")?;
+        let cfheaders = self.storage.open_tree(b"cfheaders")?;
+        let cfilter_outputs = self.storage.open_tree(b"cfilter_outputs")?;
+        let compact_filters = self.compact_filters;
+
+        // The block and its indexes are committed in a single transaction, so
+        // if the node stops part way through a commit, the state reopens at
+        // the previous tip. Otherwise, the output indexes could be missing
+        // outputs that are still needed to index the rest of the block.
+        (
+            &by_height,
+            &by_hash,
+            &by_txid,
+            &value_pools,
+            &value_pool_outputs,
+            &tree_sizes,
+            &cfilters,
+            &cfheaders,
+            &cfilter_outputs,
+        )
+            .transaction(
+                |(
+                    by_height,
+                    by_hash,
+                    by_txid,
+                    value_pools,
+                    value_pool_outputs,
+                    tree_sizes,
+                    cfilters,
+                    cfheaders,
+                    cfilter_outputs,
+                )| {
+                    by_height.insert(&height.0.to_be_bytes()[..], bytes.as_slice())?;
+                    by_hash.insert(&hash.0[..], bytes.as_slice())?;
+                    index_transactions(by_txid, &block, hash)?;
+
+                    if compact_filters {
+                        index_filter(cfilters, cfheaders, cfilter_outputs, &block, height)?;
+                    }
+                    undo_value_pools(value_pools, value_pool_outputs, &undo)?;
+                    index_value_pools(value_pools, value_pool_outputs, &block, height, pools)?;
+                    for height in &undo.heights {
+                        tree_sizes.remove(&height.to_be_bytes()[..])?;
+                    }
+                    index_tree_sizes(tree_sizes, height, sizes)?;
+
+                    Ok(())
+                },
+            )
+            .map_err(transaction_error)?;
+
+        // The tip is the highest block
+        let is_tip = match by_height.iter().keys().next_back() {
+            Some(key) => key?.as_ref() == &height.0.to_be_bytes()[..],
+            None => false,
+        };
+        if is_tip {
+            self.tip_watch.update(hash);
+        }
+        self.pending_utxos.respond_block(&block);
+
+        // Measuring the database size lists its files, so it is only done
+        // occasionally
+        if height.0 % SIZE_METRIC_INTERVAL == 0 {
+            metrics::gauge!(
+                "state.sled.size_on_disk",
+                self.storage.size_on_disk()? as i64
+            );
+        }
 
         Ok(hash)
     }
 
+    /// Index the transactions in existing blocks, if the state was created
+    /// before transactions were indexed.
+    fn index_missing_transactions(&self) -> Result<(), Error> {
+        let by_txid = self.storage.open_tree(b"by_txid")?;
+        if !by_txid.is_empty() {
+            return Ok(());
+        }
+
+        let by_height = self.storage.open_tree(b"by_height")?;
+        let mut count = 0;
+        for value in by_height.iter().values() {
+            let block: Block = ZcashDeserialize::zcash_deserialize(value?.as_ref())?;
+            by_txid
+                .transaction(|by_txid| index_transactions(by_txid, &block, block.hash()))
+                .map_err(transaction_error)?;
+            count += 1;
+        }
+
+        if count > 0 {
+            tracing::info!(count, "indexed transactions for existing blocks");
+        }
+
+        Ok(())
+    }
+
+    /// Compute and store the compact filters for any blocks after the last
+    /// indexed block.
+    fn index_missing_filters(&self) -> Result<(), Error> {
+        let filters = self.storage.open_tree(b"cfilters")?;
+        let headers = self.storage.open_tree(b"cfheaders")?;
+        let outputs = self.storage.open_tree(b"cfilter_outputs")?;
+        let start_height = match headers.iter().keys().next_back() {
+            Some(key) => height_key(&key?)? + 1,
+            None => 0,
+        };
+
+        let by_height = self.storage.open_tree(b"by_height")?;
+        let mut count = 0;
+        for entry in by_height.range(start_height.to_be_bytes()..) {
+            let (key, value) = entry?;
+            let block: Block = ZcashDeserialize::zcash_deserialize(value.as_ref())?;
+            let height = BlockHeight(height_key(&key)?);
+            (&filters, &headers, &outputs)
+                .transaction(|(filters, headers, outputs)| {
+                    index_filter(filters, headers, outputs, &block, height)
+                })
+                .map_err(transaction_error)?;
+            count += 1;
+        }
+
+        if count > 0 {
+            tracing::info!(
+                count,
+                start_height,
+                "computed compact filters for existing blocks"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Compute the chain value pools after `block` at `height`, without
+    /// storing them.
+    ///
+    /// Value pools depend on the values of the outputs spent by each block,
+    /// so blocks must be indexed in height order. The unspent output values
+    /// of indexed blocks are stored in the `value_pool_outputs` tree, and
+    /// `undo` is applied before reading them.
+    ///
+    /// Returns an error if the previous value pools are missing, or a spent
+    /// output is missing.
+    fn calculate_value_pools(
+        &self,
+        block: &Block,
+        height: BlockHeight,
+        deferred_pool_contribution: Amount<NonNegative>,
+        undo: &ValuePoolUndo,
+    ) -> Result<ValuePools, Error> {
+        let value_pools = self.storage.open_tree(b"value_pools")?;
+        let outputs = self.storage.open_tree(b"value_pool_outputs")?;
+
+        let previous_pools = match height.previous() {
+            None => ValuePools::default(),
+            Some(previous_height) => {
+                let value = value_pools
+                    .get(previous_height.0.to_be_bytes())?
+                    .ok_or("missing the value pools for the previous block")?;
+                ValuePools::from_bytes(&value)?
+            }
+        };
+
+        // Later transactions in the block can spend its own outputs
+        let block_outputs = Utxo::from_block(block);
+        previous_pools.add_block(block, deferred_pool_contribution, |outpoint| {
+            if let Some(utxo) = block_outputs.get(outpoint) {
+                return Some(utxo.output.value);
+            }
+            let key = outpoint_key(outpoint);
+            let value: [u8; 8] = match undo.outputs.get(&key) {
+                Some(value) => (*value)?,
+                None => outputs.get(key).ok()??.as_ref().try_into().ok()?,
+            };
+            u64::from_le_bytes(value).try_into().ok()
+        })
+    }
+
+    /// Returns the value pool index changes that undo the blocks from
+    /// `height` to the last block with value pools, so a fork can replace the
+    /// block at `height`.
+    ///
+    /// If there are no value pools at `height`, nothing is undone.
+    fn value_pool_undo(&self, height: BlockHeight) -> Result<ValuePoolUndo, Error> {
+        let value_pools = self.storage.open_tree(b"value_pools")?;
+        let by_height = self.storage.open_tree(b"by_height")?;
+
+        // Undo the latest blocks first, so outputs that were created and
+        // spent by the replaced blocks are removed
+        let mut undo = ValuePoolUndo::default();
+        for key in value_pools.range(height.0.to_be_bytes()..).keys().rev() {
+            let key = key?;
+            let value = by_height
+                .get(&key)?
+                .ok_or("value pools refer to a missing block")?;
+            let block: Block = ZcashDeserialize::zcash_deserialize(value.as_ref())?;
+
+            for transaction in block.transactions.iter().rev() {
+                let hash = TransactionHash::from(transaction.as_ref());
+                for index in 0..transaction.outputs().count() {
+                    let outpoint = OutPoint {
+                        hash,
+                        index: index as u32,
+                    };
+                    undo.outputs.insert(outpoint_key(&outpoint), None);
+                }
+
+                for input in transaction.inputs() {
+                    if let TransparentInput::PrevOut { outpoint, .. } = input {
+                        let (spent, _) = self
+                            .get_transaction(outpoint.hash)?
+                            .ok_or("value pools are missing a spent transaction")?;
+                        let output = spent
+                            .outputs()
+                            .nth(outpoint.index as usize)
+                            .ok_or("value pools are missing a spent output")?;
+                        undo.outputs.insert(
+                            outpoint_key(outpoint),
+                            Some(u64::from(output.value).to_le_bytes()),
+                        );
+                    }
+                }
+            }
+
+            undo.heights.push(height_key(&key)?);
+        }
+
+        Ok(undo)
+    }
+
+    /// Compute and store the value pools for any blocks after the last
+    /// indexed block.
+    fn index_missing_value_pools(&self) -> Result<(), Error> {
+        let value_pools = self.storage.open_tree(b"value_pools")?;
+        let outputs = self.storage.open_tree(b"value_pool_outputs")?;
+        let start_height = match value_pools.iter().keys().next_back() {
+            Some(key) => height_key(&key?)? + 1,
+            None => 0,
+        };
+
+        let by_height = self.storage.open_tree(b"by_height")?;
+        let mut previous_hash = match start_height.checked_sub(1) {
+            Some(previous_height) => match by_height.get(previous_height.to_be_bytes())? {
+                Some(value) => Some(Block::zcash_deserialize(value.as_ref())?.hash()),
+                None => None,
+            },
+            None => None,
+        };
+        let mut count = 0;
+        for entry in by_height.range(start_height.to_be_bytes()..) {
+            let (key, value) = entry?;
+            let block: Block = ZcashDeserialize::zcash_deserialize(value.as_ref())?;
+            let height = BlockHeight(height_key(&key)?);
+
+            // A fork replaced the blocks before this block
+            if previous_hash.map(|hash| hash != block.header.previous_block_hash) == Some(true) {
+                tracing::info!(?height, "stopped computing value pools at a replaced block");
+                break;
+            }
+            previous_hash = Some(block.hash());
+
+            // Blocks that are already in the state aren't rejected.
+            //
+            // The state can't calculate block subsidies, so existing blocks
+            // don't add to the deferred pool. Zebra stored value pools
+            // before NU6, and there are no deferred pool contributions
+            // before NU6.
+            let pools = self.calculate_value_pools(
+                &block,
+                height,
+                Amount::zero(),
+                &ValuePoolUndo::default(),
+            )?;
+            (&value_pools, &outputs)
+                .transaction(|(value_pools, outputs)| {
+                    index_value_pools(value_pools, outputs, &block, height, pools)
+                })
+                .map_err(transaction_error)?;
+            count += 1;
+        }
+
+        if count > 0 {
+            tracing::info!(
+                count,
+                start_height,
+                "computed value pools for existing blocks"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Compute the note commitment tree sizes after `block` at `height`,
+    /// without storing them.
+    ///
+    /// Returns an error if the previous tree sizes are missing.
+    fn calculate_tree_sizes(&self, block: &Block, height: BlockHeight) -> Result<TreeSizes, Error> {
+        let tree_sizes = self.storage.open_tree(b"tree_sizes")?;
+
+        let previous_sizes = match height.previous() {
+            None => TreeSizes::default(),
+            Some(previous_height) => {
+                let value = tree_sizes
+                    .get(previous_height.0.to_be_bytes())?
+                    .ok_or("missing the tree sizes for the previous block")?;
+                TreeSizes::from_bytes(&value)?
+            }
+        };
+
+        Ok(previous_sizes.add_block(block))
+    }
+
+    /// Compute and store the note commitment tree sizes for any blocks after
+    /// the last indexed block.
+    fn index_missing_tree_sizes(&self) -> Result<(), Error> {
+        let tree_sizes = self.storage.open_tree(b"tree_sizes")?;
+        let start_height = match tree_sizes.iter().keys().next_back() {
+            Some(key) => height_key(&key?)? + 1,
+            None => 0,
+        };
+
+        let by_height = self.storage.open_tree(b"by_height")?;
+        let mut previous_hash = match start_height.checked_sub(1) {
+            Some(previous_height) => match by_height.get(previous_height.to_be_bytes())? {
+                Some(value) => Some(Block::zcash_deserialize(value.as_ref())?.hash()),
+                None => None,
+            },
+            None => None,
+        };
+        let mut count = 0;
+        for entry in by_height.range(start_height.to_be_bytes()..) {
+            let (key, value) = entry?;
+            let block: Block = ZcashDeserialize::zcash_deserialize(value.as_ref())?;
+            let height = BlockHeight(height_key(&key)?);
+
+            // A fork replaced the blocks before this block
+            if previous_hash.map(|hash| hash != block.header.previous_block_hash) == Some(true) {
+                tracing::info!(?height, "stopped computing tree sizes at a replaced block");
+                break;
+            }
+            previous_hash = Some(block.hash());
+
+            let sizes = self.calculate_tree_sizes(&block, height)?;
+            tree_sizes
+                .transaction(|tree_sizes| index_tree_sizes(tree_sizes, height, sizes))
+                .map_err(transaction_error)?;
+            count += 1;
+        }
+
+        if count > 0 {
+            tracing::info!(
+                count,
+                start_height,
+                "computed tree sizes for existing blocks"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the heights from `start_height` to the height of `stop_hash`,
+    /// checking that the range has at most `max_size` blocks.
+    fn filter_range(
+        &self,
+        start_height: BlockHeight,
+        stop_hash: BlockHeaderHash,
+        max_size: u32,
+    ) -> Result<std::ops::RangeInclusive<u32>, Error> {
+        if !self.compact_filters {
+            Err("compact filters are not enabled")?;
+        }
+
+        // The filter headers index the block hash at each height, so the
+        // blocks don't need to be read
+        let headers = self.storage.open_tree(b"cfheaders")?;
+        let end_height = start_height.0.saturating_add(max_size);
+        for entry in headers.range(start_height.0.to_be_bytes()..end_height.to_be_bytes()) {
+            let (key, value) = entry?;
+            if filter_header_entry(&value)?.0 == stop_hash {
+                return Ok(start_height.0..=height_key(&key)?);
+            }
+        }
+
+        if !self.contains(&stop_hash)? {
+            Err("compact filter stop hash could not be found")?;
+        }
+        Err(
+            "compact filter stop hash is before the start height, or too many compact filters \
+             requested"
+                .into(),
+        )
+    }
+
     pub(super) fn get(&self, query: impl Into<BlockQuery>) -> Result<Option<Arc<Block>>, Error> {
         let query = query.into();
         let value = match query {
@@ -75,6 +582,58 @@ impl SledState {
         }
     }
 
+    /// Returns the transaction with `hash`, and the hash of the block that
+    /// contains it.
+    pub(super) fn get_transaction(
+        &self,
+        hash: TransactionHash,
+    ) -> Result<Option<(Arc<Transaction>, BlockHeaderHash)>, Error> {
+        let by_txid = self.storage.open_tree(b"by_txid")?;
+        let block_hash = match by_txid.get(&hash.0)? {
+            Some(value) => BlockHeaderHash(
+                value
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| "invalid block hash in transaction index")?,
+            ),
+            None => return Ok(None),
+        };
+
+        let block = self
+            .get(block_hash)?
+            .ok_or("transaction index refers to a missing block")?;
+        let transaction = block
+            .transactions
+            .iter()
+            .find(|transaction| TransactionHash::from(transaction.as_ref()) == hash)
+            .cloned()
+            .ok_or("transaction index refers to the wrong block")?;
+
+        Ok(Some((transaction, block_hash)))
+    }
+
+    /// Returns the output at `outpoint`, if it is in the state.
+    fn get_utxo(&self, outpoint: OutPoint) -> Result<Option<Utxo>, Error> {
+        let (transaction, block_hash) = match self.get_transaction(outpoint.hash)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let output = match transaction.outputs().nth(outpoint.index as usize) {
+            Some(output) => output.clone(),
+            None => return Ok(None),
+        };
+        let height = self
+            .get(block_hash)?
+            .and_then(|block| block.coinbase_height())
+            .ok_or("transaction index refers to a missing block")?;
+
+        Ok(Some(Utxo {
+            output,
+            height,
+            from_coinbase: transaction.is_coinbase(),
+        }))
+    }
+
     pub(super) fn get_tip(&self) -> Result<Option<Arc<Block>>, Error> {
         let tree = self.storage.open_tree(b"by_height")?;
         let last_entry = tree.iter().values().next_back();
@@ -97,7 +656,7 @@ impl SledState {
 impl Default for SledState {
     fn default() -> Self {
         let config = crate::Config::default();
-        Self::new(&config)
+        Self::new(&config, Network::default())
     }
 }
 
@@ -113,10 +672,19 @@ impl Service<Request> for SledState {
 
     fn call(&mut self, req: Request) -> Self::Future {
         match req {
-            Request::AddBlock { block } => {
+            Request::AddBlock {
+                block,
+                deferred_pool_contribution,
+            } => {
                 let mut storage = self.clone();
 
-                async move { storage.insert(block).map(|hash| Response::Added { hash }) }.boxed()
+                async move {
+                    storage.disk_space.wait_for_space().await;
+                    storage
+                        .insert(block, deferred_pool_contribution)
+                        .map(|hash| Response::Added { hash })
+                }
+                .boxed()
             }
             Request::GetBlock { hash } => {
                 let storage = self.clone();
@@ -128,6 +696,42 @@ impl Service<Request> for SledState {
                 }
                 .boxed()
             }
+            Request::GetBlockByHeight { height } => {
+                let storage = self.clone();
+                async move {
+                    storage
+                        .get(height)?
+                        .map(|block| Response::Block { block })
+                        .ok_or_else(|| "block could not be found".into())
+                }
+                .boxed()
+            }
+            Request::GetBlockHeaderByHeight { height } => {
+                let storage = self.clone();
+                async move {
+                    let by_height = storage.storage.open_tree(b"by_height")?;
+                    let bytes = by_height
+                        .get(height.0.to_be_bytes())?
+                        .ok_or("block could not be found")?;
+                    // The header is at the start of the serialized block
+                    let header = BlockHeader::zcash_deserialize(bytes.as_ref())?;
+                    Ok(Response::BlockHeader { header })
+                }
+                .boxed()
+            }
+            Request::GetTransaction { hash } => {
+                let storage = self.clone();
+                async move {
+                    storage
+                        .get_transaction(hash)?
+                        .map(|(transaction, block_hash)| Response::Transaction {
+                            transaction,
+                            block_hash,
+                        })
+                        .ok_or_else(|| "transaction could not be found".into())
+                }
+                .boxed()
+            }
             Request::GetTip => {
                 let storage = self.clone();
                 async move {
@@ -139,6 +743,34 @@ impl Service<Request> for SledState {
                 }
                 .boxed()
             }
+            Request::AwaitTipChange { tip } => {
+                let changed = self.tip_watch.changed_from(tip);
+
+                async move {
+                    changed
+                        .await
+                        .map(|hash| Response::Tip { hash })
+                        .ok_or_else(|| "zebra-state was dropped".into())
+                }
+                .boxed()
+            }
+            Request::AwaitUtxo { outpoint } => {
+                let storage = self.clone();
+                // Queue the request before looking up the output, so we don't
+                // miss outputs in blocks that are committed concurrently
+                let added = self.pending_utxos.queue(outpoint);
+
+                async move {
+                    match storage.get_utxo(outpoint)? {
+                        Some(utxo) => Ok(Response::Utxo(utxo)),
+                        None => added
+                            .await
+                            .map(Response::Utxo)
+                            .ok_or_else(|| "zebra-state was dropped".into()),
+                    }
+                }
+                .boxed()
+            }
             Request::GetDepth { hash } => {
                 let storage = self.clone();
 
@@ -154,13 +786,153 @@ impl Service<Request> for SledState {
                         .get_tip()?
                         .expect("storage must have a tip if it contains the previous block");
 
-                    let depth =
-                        tip.coinbase_height().unwrap().0 - block.coinbase_height().unwrap().0;
+                    let depth = crate::block_depth(
+                        tip.coinbase_height().unwrap(),
+                        block.coinbase_height().unwrap(),
+                    );
 
                     Ok(Response::Depth(Some(depth)))
                 }
                 .boxed()
             }
+            Request::IsFinalized { hash } => {
+                let storage = self.clone();
+
+                async move {
+                    let height = match storage.get(hash)? {
+                        Some(block) => block.coinbase_height().unwrap(),
+                        None => return Ok(Response::IsFinalized(false)),
+                    };
+                    let tip = storage
+                        .get_tip()?
+                        .expect("storage must have a tip if it contains a block");
+                    let depth = crate::block_depth(tip.coinbase_height().unwrap(), height);
+
+                    Ok(Response::IsFinalized(crate::is_finalized_depth(depth)))
+                }
+                .boxed()
+            }
+            Request::KnownBlock { hash } => {
+                let storage = self.clone();
+
+                async move {
+                    let known_block = if storage.contains(&hash)? {
+                        Some(KnownBlock::BestChain)
+                    } else {
+                        None
+                    };
+
+                    Ok(Response::KnownBlock(known_block))
+                }
+                .boxed()
+            }
+            Request::GetCompactFilters {
+                start_height,
+                stop_hash,
+            } => {
+                let storage = self.clone();
+
+                async move {
+                    let range = storage.filter_range(
+                        start_height,
+                        stop_hash,
+                        compact_filter::MAX_GETCFILTERS_SIZE,
+                    )?;
+                    let filters = storage.storage.open_tree(b"cfilters")?;
+                    let headers = storage.storage.open_tree(b"cfheaders")?;
+
+                    let filters = range
+                        .map(|height| {
+                            let key = height.to_be_bytes();
+                            let filter = filters.get(key)?.ok_or("missing compact filter")?;
+                            let header = headers.get(key)?.ok_or("missing compact filter")?;
+                            let (hash, _) = filter_header_entry(&header)?;
+                            Ok((hash, BlockFilter(filter.to_vec())))
+                        })
+                        .collect::<Result<_, Error>>()?;
+
+                    Ok(Response::CompactFilters { filters })
+                }
+                .boxed()
+            }
+            Request::GetCompactFilterHeaders {
+                start_height,
+                stop_hash,
+            } => {
+                let storage = self.clone();
+
+                async move {
+                    let range = storage.filter_range(
+                        start_height,
+                        stop_hash,
+                        compact_filter::MAX_GETCFHEADERS_SIZE,
+                    )?;
+                    let filters = storage.storage.open_tree(b"cfilters")?;
+                    let headers = storage.storage.open_tree(b"cfheaders")?;
+
+                    let previous_header = match range.start().checked_sub(1) {
+                        None => FilterHeader([0; 32]),
+                        Some(height) => {
+                            let header = headers
+                                .get(height.to_be_bytes())?
+                                .ok_or("missing compact filter")?;
+                            filter_header_entry(&header)?.1
+                        }
+                    };
+
+                    let filter_hashes = range
+                        .map(|height| {
+                            let filter = filters
+                                .get(height.to_be_bytes())?
+                                .ok_or("missing compact filter")?;
+                            Ok(BlockFilter(filter.to_vec()).hash())
+                        })
+                        .collect::<Result<_, Error>>()?;
+
+                    Ok(Response::CompactFilterHeaders {
+                        previous_header,
+                        filter_hashes,
+                    })
+                }
+                .boxed()
+            }
+            Request::DatabaseInfo => {
+                let storage = self.storage.clone();
+                let path = self.path.clone();
+                async move {
+                    // Reading every entry blocks for a long time
+                    let info =
+                        tokio::task::spawn_blocking(move || storage_info(&storage, path)).await??;
+                    Ok(Response::DatabaseInfo(info))
+                }
+                .boxed()
+            }
+            Request::GetValuePools { height } => {
+                let storage = self.clone();
+
+                async move {
+                    let value_pools = storage.storage.open_tree(b"value_pools")?;
+                    let pools = value_pools
+                        .get(height.0.to_be_bytes())?
+                        .ok_or("value pools could not be found")?;
+
+                    Ok(Response::ValuePools(ValuePools::from_bytes(&pools)?))
+                }
+                .boxed()
+            }
+            Request::GetTreeSizes { height } => {
+                let storage = self.clone();
+
+                async move {
+                    let tree_sizes = storage.storage.open_tree(b"tree_sizes")?;
+                    let sizes = tree_sizes
+                        .get(height.0.to_be_bytes())?
+                        .ok_or("tree sizes could not be found")?;
+
+                    Ok(Response::TreeSizes(TreeSizes::from_bytes(&sizes)?))
+                }
+                .boxed()
+            }
             Request::GetBlockLocator { genesis } => {
                 let storage = self.clone();
 
@@ -198,6 +970,261 @@ impl Service<Request> for SledState {
     }
 }
 
+/// Opens the sled database for `network`, and records its network if it is
+/// new.
+///
+/// Returns an error if the database was created for a different network, or
+/// if the cache directory has a state from before states were separated by
+/// network.
+fn open(config: &Config, network: Network) -> Result<sled::Db, Error> {
+    config.check_legacy_state()?;
+
+    let storage = config.sled_config(network).open()?;
+    check_network(&storage, config.state_path(network), network)?;
+    storage
+        .open_tree(b"metadata")?
+        .insert(b"network", network_key(network))?;
+
+    Ok(storage)
+}
+
+/// Returns an error if `storage` was created for a different network.
+///
+/// States from older versions don't record their network, so their genesis
+/// block is checked instead.
+fn check_network(storage: &sled::Db, path: PathBuf, network: Network) -> Result<(), Error> {
+    let found = match storage.open_tree(b"metadata")?.get(b"network")? {
+        Some(key) if key.as_ref() == network_key(Network::Mainnet) => Some(Network::Mainnet),
+        Some(key) if key.as_ref() == network_key(Network::Testnet) => Some(Network::Testnet),
+        Some(_) => Err("unknown network in state metadata")?,
+        None => match storage.open_tree(b"by_height")?.get(0u32.to_be_bytes())? {
+            Some(bytes) => {
+                let genesis: Block = ZcashDeserialize::zcash_deserialize(bytes.as_ref())?;
+                [Network::Mainnet, Network::Testnet]
+                    .iter()
+                    .copied()
+                    .find(|network| {
+                        genesis_block(*network).map(|block| block.hash()) == Some(genesis.hash())
+                    })
+            }
+            None => None,
+        },
+    };
+
+    if let Some(found) = found {
+        if found != network {
+            Err(WrongNetwork {
+                path,
+                expected: network,
+                found,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the metadata value for `network`.
+fn network_key(network: Network) -> &'static [u8] {
+    match network {
+        Network::Mainnet => b"mainnet",
+        Network::Testnet => b"testnet",
+    }
+}
+
+/// Store the hash of `block` for each of its transactions.
+fn index_transactions(
+    by_txid: &TransactionalTree,
+    block: &Block,
+    hash: BlockHeaderHash,
+) -> ConflictableTransactionResult<(), Error> {
+    for transaction in &block.transactions {
+        let txid = TransactionHash::from(transaction.as_ref());
+        by_txid.insert(&txid.0[..], &hash.0[..])?;
+    }
+
+    Ok(())
+}
+
+/// Compute and store the compact filter for `block` at `height`.
+///
+/// Filters depend on the scripts of the outputs spent by each block, so
+/// blocks must be indexed in height order. The spendable outputs of indexed
+/// blocks are stored in the `cfilter_outputs` tree.
+fn index_filter(
+    filters: &TransactionalTree,
+    headers: &TransactionalTree,
+    outputs: &TransactionalTree,
+    block: &Block,
+    height: BlockHeight,
+) -> ConflictableTransactionResult<(), Error> {
+    let previous_header = match height.previous() {
+        None => FilterHeader([0; 32]),
+        Some(previous_height) => match headers.get(&previous_height.0.to_be_bytes()[..])? {
+            Some(value) => filter_header_entry(&value).map_err(abort)?.1,
+            None => Err(abort(
+                "missing the compact filter header for the previous block".into(),
+            ))?,
+        },
+    };
+
+    let mut spent_scripts = Vec::new();
+    for transaction in &block.transactions {
+        for input in transaction.inputs() {
+            if let TransparentInput::PrevOut { outpoint, .. } = input {
+                if let Some(script) = outputs.remove(&outpoint_key(outpoint)[..])? {
+                    spent_scripts.push(script.to_vec());
+                }
+            }
+        }
+
+        let transaction_hash = TransactionHash::from(transaction.as_ref().clone());
+        for (index, output) in transaction.outputs().enumerate() {
+            if compact_filter::is_filtered_output(&output.pk_script.0) {
+                let outpoint = OutPoint {
+                    hash: transaction_hash,
+                    index: index as u32,
+                };
+                outputs.insert(&outpoint_key(&outpoint)[..], output.pk_script.0.as_slice())?;
+            }
+        }
+    }
+
+    let filter = BlockFilter::basic(block, spent_scripts);
+    let header = filter.header(previous_header);
+
+    let mut header_entry = block.hash().0.to_vec();
+    header_entry.extend_from_slice(&header.0);
+
+    let key = height.0.to_be_bytes();
+    filters.insert(&key[..], filter.0)?;
+    headers.insert(&key[..], header_entry)?;
+
+    Ok(())
+}
+
+/// The value pool index changes that undo the blocks replaced by a fork.
+#[derive(Default)]
+struct ValuePoolUndo {
+    /// The heights of the replaced blocks.
+    heights: Vec<u32>,
+    /// The unspent output values to restore, or `None` for the outputs to
+    /// remove.
+    outputs: HashMap<[u8; 36], Option<[u8; 8]>>,
+}
+
+/// Remove the value pools of the blocks replaced by a fork, and restore the
+/// unspent output values before those blocks.
+fn undo_value_pools(
+    value_pools: &TransactionalTree,
+    outputs: &TransactionalTree,
+    undo: &ValuePoolUndo,
+) -> ConflictableTransactionResult<(), Error> {
+    for height in &undo.heights {
+        value_pools.remove(&height.to_be_bytes()[..])?;
+    }
+
+    for (key, value) in &undo.outputs {
+        match value {
+            Some(value) => outputs.insert(&key[..], &value[..])?,
+            None => outputs.remove(&key[..])?,
+        };
+    }
+
+    Ok(())
+}
+
+/// Store the chain value pools after `block` at `height`, and update the
+/// unspent output values.
+///
+/// `pools` is the result of [`SledState::calculate_value_pools`].
+fn index_value_pools(
+    value_pools: &TransactionalTree,
+    outputs: &TransactionalTree,
+    block: &Block,
+    height: BlockHeight,
+    pools: ValuePools,
+) -> ConflictableTransactionResult<(), Error> {
+    for transaction in &block.transactions {
+        let hash = TransactionHash::from(transaction.as_ref());
+        for (index, output) in transaction.outputs().enumerate() {
+            let outpoint = OutPoint {
+                hash,
+                index: index as u32,
+            };
+            outputs.insert(
+                &outpoint_key(&outpoint)[..],
+                &u64::from(output.value).to_le_bytes()[..],
+            )?;
+        }
+    }
+
+    for transaction in &block.transactions {
+        for input in transaction.inputs() {
+            if let TransparentInput::PrevOut { outpoint, .. } = input {
+                outputs.remove(&outpoint_key(outpoint)[..])?;
+            }
+        }
+    }
+    value_pools.insert(&height.0.to_be_bytes()[..], &pools.to_bytes()[..])?;
+
+    Ok(())
+}
+
+/// Store the note commitment tree `sizes` after the block at `height`.
+///
+/// `sizes` is the result of [`SledState::calculate_tree_sizes`].
+fn index_tree_sizes(
+    tree_sizes: &TransactionalTree,
+    height: BlockHeight,
+    sizes: TreeSizes,
+) -> ConflictableTransactionResult<(), Error> {
+    tree_sizes.insert(&height.0.to_be_bytes()[..], &sizes.to_bytes()[..])?;
+
+    Ok(())
+}
+
+/// Aborts a sled transaction with `error`.
+fn abort(error: Error) -> ConflictableTransactionError<Error> {
+    ConflictableTransactionError::Abort(error)
+}
+
+/// Converts the result of a failed sled transaction into a state error.
+fn transaction_error(error: TransactionError<Error>) -> Error {
+    match error {
+        TransactionError::Abort(error) => error,
+        TransactionError::Storage(error) => error.into(),
+    }
+}
+
+/// Returns the `cfilter_outputs` key for `outpoint`.
+fn outpoint_key(outpoint: &OutPoint) -> [u8; 36] {
+    let mut key = [0; 36];
+    key[..32].copy_from_slice(&outpoint.hash.0);
+    key[32..].copy_from_slice(&outpoint.index.to_be_bytes());
+    key
+}
+
+/// Parses a big-endian height key.
+fn height_key(key: &[u8]) -> Result<u32, Error> {
+    Ok(u32::from_be_bytes(
+        key.try_into().map_err(|_| "invalid height key in state")?,
+    ))
+}
+
+/// Parses a `cfheaders` value into a block hash and filter header.
+fn filter_header_entry(value: &[u8]) -> Result<(BlockHeaderHash, FilterHeader), Error> {
+    if value.len() != 64 {
+        Err("invalid compact filter header in state")?;
+    }
+
+    let mut hash = [0; 32];
+    let mut header = [0; 32];
+    hash.copy_from_slice(&value[..32]);
+    header.copy_from_slice(&value[32..]);
+    Ok((BlockHeaderHash(hash), FilterHeader(header)))
+}
+
 /// An alternate repr for `BlockHeight` that implements `AsRef<[u8]>` for usage
 /// with sled
 struct BytesHeight(u32, [u8; 4]);
@@ -232,9 +1259,389 @@ impl From<BlockHeight> for BlockQuery {
     }
 }
 
+/// Diagnostic information about an on-disk state.
+///
+/// sled does not expose cache or write stall statistics, so they are not
+/// included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateInfo {
+    /// The path of the state database.
+    pub path: PathBuf,
+    /// The height and hash of the tip block, if the state has any blocks.
+    pub tip: Option<(BlockHeight, BlockHeaderHash)>,
+    /// The chain value pools after the tip block, if they have been
+    /// calculated.
+    pub value_pools: Option<ValuePools>,
+    /// Diagnostic information about each tree in the state database.
+    pub trees: Vec<TreeInfo>,
+    /// The total size of the state database on disk, in bytes.
+    pub size_on_disk: u64,
+}
+
+impl StateInfo {
+    /// Returns the number of bytes on disk that are not used by live keys
+    /// and values.
+    ///
+    /// This space is used by old versions of entries, and database
+    /// metadata. sled reclaims old versions in the background, so a large
+    /// value can mean that reclamation is falling behind the writes.
+    pub fn reclaimable_bytes(&self) -> u64 {
+        let live_bytes: u64 = self.trees.iter().map(TreeInfo::live_bytes).sum();
+        self.size_on_disk.saturating_sub(live_bytes)
+    }
+}
+
+/// Diagnostic information about a tree in an on-disk state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeInfo {
+    /// The name of the tree.
+    pub name: String,
+    /// The number of entries in the tree.
+    pub entries: usize,
+    /// The total size of the keys in the tree, in bytes.
+    pub key_bytes: u64,
+    /// The total size of the values in the tree, in bytes.
+    pub value_bytes: u64,
+}
+
+impl TreeInfo {
+    /// Returns the total size of the keys and values in the tree, in bytes.
+    pub fn live_bytes(&self) -> u64 {
+        self.key_bytes + self.value_bytes
+    }
+}
+
+/// Returns diagnostic information about the on-disk state for `config` and
+/// `network`.
+///
+/// Unlike `init`, this function does not modify the state. But sled only
+/// allows one process to open a database, so this function fails if another
+/// process is using the state.
+pub fn state_info(config: &Config, network: Network) -> Result<StateInfo, Error> {
+    config.check_legacy_state()?;
+
+    let path = config.state_path(network);
+    if !path.exists() {
+        Err(format!("there is no state at {:?}", path))?;
+    }
+
+    let storage = config.sled_config(network).open()?;
+    check_network(&storage, path.clone(), network)?;
+
+    storage_info(&storage, path)
+}
+
+/// Returns diagnostic information about `storage`, which is stored at
+/// `path`.
+///
+/// This function reads every entry in the database, so it can take a long
+/// time for a large state.
+fn storage_info(storage: &sled::Db, path: PathBuf) -> Result<StateInfo, Error> {
+    let by_height = storage.open_tree(b"by_height")?;
+    let tip = match by_height.iter().next_back() {
+        Some(entry) => {
+            let (key, value) = entry?;
+            let height = BlockHeight(u32::from_be_bytes(
+                key.as_ref()
+                    .try_into()
+                    .map_err(|_| "invalid height key in state")?,
+            ));
+            let block: Block = ZcashDeserialize::zcash_deserialize(value.as_ref())?;
+            Some((height, block.hash()))
+        }
+        None => None,
+    };
+
+    let value_pools = match tip {
+        Some((height, _)) => storage
+            .open_tree(b"value_pools")?
+            .get(height.0.to_be_bytes())?
+            .map(|value| ValuePools::from_bytes(&value))
+            .transpose()?,
+        None => None,
+    };
+
+    let mut trees = Vec::new();
+    for name in storage.tree_names() {
+        let tree = storage.open_tree(&name)?;
+        let mut info = TreeInfo {
+            name: String::from_utf8_lossy(name.as_ref()).into_owned(),
+            entries: 0,
+            key_bytes: 0,
+            value_bytes: 0,
+        };
+        for entry in tree.iter() {
+            let (key, value) = entry?;
+            info.entries += 1;
+            info.key_bytes += key.len() as u64;
+            info.value_bytes += value.len() as u64;
+        }
+        trees.push(info);
+    }
+
+    let info = StateInfo {
+        path,
+        tip,
+        value_pools,
+        trees,
+        size_on_disk: storage.size_on_disk()?,
+    };
+
+    metrics::gauge!("state.sled.size_on_disk", info.size_on_disk as i64);
+    metrics::gauge!(
+        "state.sled.reclaimable_bytes",
+        info.reclaimable_bytes() as i64
+    );
+    for tree in &info.trees {
+        metrics::gauge!(
+            "state.sled.tree.live_bytes",
+            tree.live_bytes() as i64,
+            "tree" => tree.name.clone()
+        );
+        metrics::gauge!(
+            "state.sled.tree.entries",
+            tree.entries as i64,
+            "tree" => tree.name.clone()
+        );
+    }
+
+    Ok(info)
+}
+
+/// A read-only view of an on-disk state, for bulk exports and re-indexing.
+///
+/// The iterators read the database in height order, and only keep the current
+/// block in memory. So they are much faster than a `GetBlockByHeight` request
+/// for each block, and can read the whole chain.
+///
+/// sled only allows one process to open a database, so a reader can't be
+/// opened while `zebrad start` is using the same state.
+#[derive(Clone, Debug)]
+pub struct StateReader {
+    storage: sled::Db,
+}
+
+impl StateReader {
+    /// Opens the on-disk state for `config` and `network`, for reading.
+    ///
+    /// Unlike `init`, this function does not modify the state.
+    pub fn open(config: &Config, network: Network) -> Result<StateReader, Error> {
+        config.check_legacy_state()?;
+
+        let path = config.state_path(network);
+        if !path.exists() {
+            Err(format!("there is no state at {:?}", path))?;
+        }
+
+        let storage = config.sled_config(network).open()?;
+        check_network(&storage, path, network)?;
+
+        Ok(StateReader { storage })
+    }
+
+    /// Returns the block at `height` in the best chain, if it is in the
+    /// state.
+    pub fn block(&self, height: BlockHeight) -> Result<Option<Arc<Block>>, Error> {
+        let by_height = self.storage.open_tree(b"by_height")?;
+        match by_height.get(height.0.to_be_bytes())? {
+            Some(bytes) => Ok(Some(Arc::new(ZcashDeserialize::zcash_deserialize(
+                bytes.as_ref(),
+            )?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns an iterator over the blocks in the best chain, in height
+    /// order, starting at `start`.
+    pub fn blocks(
+        &self,
+        start: BlockHeight,
+    ) -> Result<impl Iterator<Item = Result<(BlockHeight, Arc<Block>), Error>>, Error> {
+        let by_height = self.storage.open_tree(b"by_height")?;
+
+        Ok(by_height
+            .range(start.0.to_be_bytes()..)
+            .map(|entry| -> Result<_, Error> {
+                let (key, value) = entry?;
+                let height = BlockHeight(height_key(&key)?);
+                let block: Block = ZcashDeserialize::zcash_deserialize(value.as_ref())?;
+                Ok((height, Arc::new(block)))
+            }))
+    }
+
+    /// Checks that the blocks and indexes in the state are consistent, and
+    /// returns the height of the tip.
+    ///
+    /// The best chain must have a block at every height up to the tip, and
+    /// each block must be in the hash and transaction indexes. The value
+    /// pool and compact filter indexes can be behind the tip, because they
+    /// are completed when the state is opened, but they must not be ahead
+    /// of it.
+    pub fn check_consistency(&self) -> Result<Option<BlockHeight>, Error> {
+        let by_hash = self.storage.open_tree(b"by_hash")?;
+        let by_txid = self.storage.open_tree(b"by_txid")?;
+
+        let mut tip = None;
+        for entry in self.blocks(BlockHeight(0))? {
+            let (height, block) = entry?;
+            let expected_height = tip.map(|BlockHeight(tip)| tip + 1).unwrap_or(0);
+            if height.0 != expected_height {
+                Err(format!(
+                    "missing block at height {}, found a block at height {}",
+                    expected_height, height.0
+                ))?;
+            }
+
+            let hash = block.hash();
+            if !by_hash.contains_key(&hash.0)? {
+                Err(format!(
+                    "block at height {} is missing from by_hash",
+                    height.0
+                ))?;
+            }
+            for transaction in &block.transactions {
+                let txid = TransactionHash::from(transaction.as_ref());
+                if by_txid.get(&txid.0)?.as_deref() != Some(&hash.0[..]) {
+                    Err(format!(
+                        "transaction in block at height {} is missing from by_txid",
+                        height.0
+                    ))?;
+                }
+            }
+
+            tip = Some(height);
+        }
+
+        for name in &[
+            &b"value_pools"[..],
+            b"tree_sizes",
+            b"cfheaders",
+            b"cfilters",
+        ] {
+            let tree = self.storage.open_tree(name)?;
+            if let Some(key) = tree.iter().keys().next_back() {
+                let index_height = height_key(&key?)?;
+                if tip.map(|tip| index_height > tip.0).unwrap_or(true) {
+                    Err(format!(
+                        "{} index is ahead of the tip, at height {}",
+                        String::from_utf8_lossy(name),
+                        index_height
+                    ))?;
+                }
+            }
+        }
+
+        Ok(tip)
+    }
+
+    /// Returns an iterator over the unspent transparent outputs in the best
+    /// chain, in the order they were created.
+    ///
+    /// Returns an error if the unspent outputs haven't been indexed up to the
+    /// tip. `zebrad start` indexes the missing blocks when it opens the state.
+    pub fn utxos(&self) -> Result<impl Iterator<Item = Result<(OutPoint, Utxo), Error>>, Error> {
+        let unspent = self.storage.open_tree(b"value_pool_outputs")?;
+
+        // The unspent outputs are updated with the value pools
+        let last_key = |name: &[u8]| -> Result<Option<u32>, Error> {
+            match self.storage.open_tree(name)?.iter().keys().next_back() {
+                Some(key) => Ok(Some(height_key(&key?)?)),
+                None => Ok(None),
+            }
+        };
+        let tip = last_key(b"by_height")?;
+        let indexed = last_key(b"value_pools")?;
+        if indexed != tip {
+            Err(format!(
+                "unspent outputs are only indexed up to height {:?}, but the tip is at height {:?}",
+                indexed, tip
+            ))?;
+        }
+
+        Ok(self
+            .blocks(BlockHeight(0))?
+            .flat_map(move |entry| match block_utxos(&unspent, entry) {
+                Ok(utxos) => utxos.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            }))
+    }
+}
+
+/// Returns the outputs created by the block in `entry` that are in
+/// `unspent`, in the order they were created.
+fn block_utxos(
+    unspent: &sled::Tree,
+    entry: Result<(BlockHeight, Arc<Block>), Error>,
+) -> Result<Vec<(OutPoint, Utxo)>, Error> {
+    let (height, block) = entry?;
+
+    let mut utxos = Vec::new();
+    for transaction in &block.transactions {
+        let hash = TransactionHash::from(transaction.as_ref());
+        let from_coinbase = transaction.is_coinbase();
+        for (index, output) in transaction.outputs().enumerate() {
+            let outpoint = OutPoint {
+                hash,
+                index: index as u32,
+            };
+            if unspent.contains_key(outpoint_key(&outpoint))? {
+                utxos.push((
+                    outpoint,
+                    Utxo {
+                        output: output.clone(),
+                        height,
+                        from_coinbase,
+                    },
+                ));
+            }
+        }
+    }
+
+    Ok(utxos)
+}
+
+/// A state service with separate request queues for block commits and read
+/// queries.
+///
+/// During the initial sync, the verifiers queue up many block commits, so
+/// reads such as tip, depth, and block locator queries use their own queue.
+/// Each queue applies backpressure in the returned futures, rather than in
+/// `poll_ready`, so a full commit queue doesn't block reads.
+#[derive(Clone)]
+struct PrioritizedState {
+    reads: Buffer<SledState, Request>,
+    commits: Buffer<SledState, Request>,
+}
+
+impl Service<Request> for PrioritizedState {
+    type Response = Response;
+    type Error = Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let queue = match req {
+            Request::AddBlock { .. } => self.commits.clone(),
+            _ => self.reads.clone(),
+        };
+
+        queue.oneshot(req).boxed()
+    }
+}
+
 /// Return's a type that implement's the `zebra_state::Service` using `sled`
+///
+/// If the state is empty, and `network` has an embedded genesis block, the
+/// genesis block is committed to the state.
+///
+/// Read queries are not queued behind block commits.
 pub fn init(
     config: Config,
+    network: Network,
 ) -> impl Service<
     Request,
     Response = Response,
@@ -243,7 +1650,12 @@ pub fn init(
 > + Send
        + Clone
        + 'static {
-    Buffer::new(SledState::new(&config), 1)
+    let state = SledState::new(&config, network);
+
+    PrioritizedState {
+        reads: Buffer::new(state.clone(), 1),
+        commits: Buffer::new(state, 1),
+    }
 }
 
 type Error = Box<dyn error::Error + Send + Sync + 'static>;
@@ -0,0 +1,56 @@
+//! Notifications of best chain tip changes.
+
+use std::{future::Future, sync::Arc};
+
+use tokio::sync::watch;
+use zebra_chain::block::BlockHeaderHash;
+
+/// A channel that tracks the hash of the best chain tip.
+///
+/// Clones share the same channel.
+#[derive(Clone, Debug)]
+pub(crate) struct TipWatch {
+    sender: Arc<watch::Sender<Option<BlockHeaderHash>>>,
+    receiver: watch::Receiver<Option<BlockHeaderHash>>,
+}
+
+impl Default for TipWatch {
+    fn default() -> Self {
+        let (sender, receiver) = watch::channel(None);
+        TipWatch {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+}
+
+impl TipWatch {
+    /// Record that the best chain tip is `tip`.
+    pub(crate) fn update(&self, tip: BlockHeaderHash) {
+        if *self.receiver.borrow() != Some(tip) {
+            // We hold a receiver, so the broadcast can't fail
+            let _ = self.sender.broadcast(Some(tip));
+        }
+    }
+
+    /// Returns a future that waits until the best chain tip is not `tip`,
+    /// then returns the new tip.
+    ///
+    /// The future returns `None` if the state is dropped first.
+    pub(crate) fn changed_from(
+        &self,
+        tip: BlockHeaderHash,
+    ) -> impl Future<Output = Option<BlockHeaderHash>> {
+        let mut receiver = self.receiver.clone();
+
+        async move {
+            loop {
+                let current = *receiver.borrow();
+                match current {
+                    Some(current) if current != tip => return Some(current),
+                    _ => receiver.recv().await?,
+                };
+            }
+        }
+    }
+}
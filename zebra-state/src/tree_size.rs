@@ -0,0 +1,112 @@
+//! The number of note commitments in each of the chain's note commitment
+//! trees.
+//!
+//! Each shielded output appends one note commitment to the tree for its
+//! pool, and note commitment trees are append-only. So the tree sizes only
+//! depend on the blocks in the chain, and not on the outputs they spend.
+
+use std::convert::TryInto;
+
+use zebra_chain::block::Block;
+
+use crate::Error;
+
+/// The number of note commitments in each of the chain's note commitment
+/// trees, after a block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreeSizes {
+    /// The number of note commitments in the Sprout tree.
+    pub sprout: u64,
+    /// The number of note commitments in the Sapling tree.
+    pub sapling: u64,
+    /// The number of note commitments in the Orchard tree.
+    ///
+    /// Zebra doesn't support Orchard transactions yet, so this tree is
+    /// always empty.
+    pub orchard: u64,
+}
+
+/// The length of serialized [`TreeSizes`].
+pub(crate) const TREE_SIZES_LEN: usize = 24;
+
+impl TreeSizes {
+    /// Returns the tree sizes after adding `block` to a chain with these
+    /// tree sizes.
+    pub fn add_block(&self, block: &Block) -> TreeSizes {
+        let mut sizes = *self;
+
+        for transaction in &block.transactions {
+            // Each JoinSplit creates two new notes
+            sizes.sprout += 2 * transaction.joinsplit_count() as u64;
+            sizes.sapling += transaction.sapling_outputs().count() as u64;
+        }
+
+        sizes
+    }
+
+    /// Returns these tree sizes as bytes, for storage in the state.
+    pub(crate) fn to_bytes(&self) -> [u8; TREE_SIZES_LEN] {
+        let mut bytes = [0; TREE_SIZES_LEN];
+        for (chunk, size) in bytes
+            .chunks_mut(8)
+            .zip(&[self.sprout, self.sapling, self.orchard])
+        {
+            chunk.copy_from_slice(&size.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parses tree sizes stored in the state.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<TreeSizes, Error> {
+        if bytes.len() != TREE_SIZES_LEN {
+            Err("invalid tree sizes in state")?;
+        }
+
+        let size = |index: usize| -> u64 {
+            let chunk = bytes[index * 8..(index + 1) * 8]
+                .try_into()
+                .expect("chunk is 8 bytes");
+            u64::from_le_bytes(chunk)
+        };
+
+        Ok(TreeSizes {
+            sprout: size(0),
+            sapling: size(1),
+            orchard: size(2),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_chain::serialization::ZcashDeserialize;
+
+    #[test]
+    fn mainnet_coinbase_tree_sizes() -> Result<(), Error> {
+        zebra_test::init();
+
+        let mut sizes = TreeSizes::default();
+        for bytes in &[
+            &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+        ] {
+            let block = Block::zcash_deserialize(*bytes)?;
+            sizes = sizes.add_block(&block);
+        }
+
+        // These blocks only have coinbase transactions
+        assert_eq!(sizes, TreeSizes::default());
+
+        let sizes = TreeSizes {
+            sprout: 1,
+            sapling: u64::MAX,
+            orchard: 0,
+        };
+        assert_eq!(TreeSizes::from_bytes(&sizes.to_bytes())?, sizes);
+        assert!(TreeSizes::from_bytes(&[0; 8]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Transparent outputs, and requests that wait for them to be added to the
+//! state.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use futures::channel::oneshot;
+use zebra_chain::{
+    block::Block,
+    transaction::{OutPoint, TransactionHash, TransparentOutput},
+    types::BlockHeight,
+};
+
+/// A transparent output, and where it was created.
+///
+/// Zebra doesn't track spent outputs yet, so outputs from the state might
+/// already be spent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Utxo {
+    /// The output.
+    pub output: TransparentOutput,
+    /// The height of the block that created the output.
+    pub height: BlockHeight,
+    /// Was the output created by a coinbase transaction?
+    pub from_coinbase: bool,
+}
+
+impl Utxo {
+    /// Returns the outputs created by `block`, by outpoint.
+    ///
+    /// Returns an empty map if `block` has no coinbase height.
+    pub fn from_block(block: &Block) -> HashMap<OutPoint, Utxo> {
+        let height = match block.coinbase_height() {
+            Some(height) => height,
+            None => return HashMap::new(),
+        };
+
+        block
+            .transactions
+            .iter()
+            .flat_map(|transaction| {
+                let hash = TransactionHash::from(transaction.as_ref());
+                let from_coinbase = transaction.is_coinbase();
+                transaction
+                    .outputs()
+                    .enumerate()
+                    .map(move |(index, output)| {
+                        (
+                            OutPoint {
+                                hash,
+                                index: index as u32,
+                            },
+                            Utxo {
+                                output: output.clone(),
+                                height,
+                                from_coinbase,
+                            },
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+/// The `AwaitUtxo` requests that are waiting for outputs.
+///
+/// Clones share the same requests.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PendingUtxos(Arc<Mutex<HashMap<OutPoint, Vec<oneshot::Sender<Utxo>>>>>);
+
+impl PendingUtxos {
+    /// Returns a future that waits until the output at `outpoint` is added to
+    /// the state.
+    ///
+    /// The future returns `None` if the state is dropped first. Callers must
+    /// check that the output is not already in the state after calling this
+    /// method, so outputs that are added concurrently aren't missed.
+    pub(crate) fn queue(&self, outpoint: OutPoint) -> impl Future<Output = Option<Utxo>> {
+        let (sender, receiver) = oneshot::channel();
+
+        let mut pending = self.lock();
+        // Drop requests that have been cancelled or timed out
+        pending.retain(|_, senders| {
+            senders.retain(|sender| !sender.is_canceled());
+            !senders.is_empty()
+        });
+        pending.entry(outpoint).or_default().push(sender);
+
+        async move { receiver.await.ok() }
+    }
+
+    /// Responds to the requests that are waiting for the outputs created by
+    /// `block`.
+    pub(crate) fn respond_block(&self, block: &Block) {
+        let mut pending = self.lock();
+        if pending.is_empty() {
+            return;
+        }
+
+        for (outpoint, utxo) in Utxo::from_block(block) {
+            for sender in pending.remove(&outpoint).unwrap_or_default() {
+                // The request may have timed out
+                let _ = sender.send(utxo.clone());
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<OutPoint, Vec<oneshot::Sender<Utxo>>>> {
+        self.0.lock().expect("mutex should be unpoisoned")
+    }
+}
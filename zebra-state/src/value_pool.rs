@@ -0,0 +1,305 @@
+//! The total value in each of the chain's value pools.
+//!
+//! The transparent and shielded pools change with the transactions in each
+//! block. The deferred development fund pool (the "lockbox") only changes with
+//! the block subsidy, which is a consensus parameter, so callers supply each
+//! block's contribution when they add it to the state.
+
+use std::{convert::TryInto, error, fmt};
+
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    transaction::{OutPoint, TransparentInput},
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight,
+    },
+};
+
+use crate::Error;
+
+/// The total value in each of the chain's value pools, after a block.
+///
+/// Pool values are signed, so that a pool that would go negative can be
+/// detected and rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValuePools {
+    /// The value of the transparent outputs that haven't been spent.
+    pub transparent: Amount,
+    /// The value in the Sprout shielded pool.
+    pub sprout: Amount,
+    /// The value in the Sapling shielded pool.
+    pub sapling: Amount,
+    /// The value in the Orchard shielded pool.
+    ///
+    /// Zebra doesn't support Orchard transactions yet, so this pool is
+    /// always empty.
+    pub orchard: Amount,
+    /// The value in the deferred development fund pool ([ZIP-1015]).
+    ///
+    /// [ZIP-1015]: https://zips.z.cash/zip-1015
+    pub deferred: Amount,
+}
+
+/// The length of a serialized [`ValuePools`].
+pub(crate) const VALUE_POOLS_LEN: usize = 40;
+
+/// The length of a [`ValuePools`] serialized without the deferred pool.
+const LEGACY_VALUE_POOLS_LEN: usize = 32;
+
+impl Default for ValuePools {
+    fn default() -> Self {
+        ValuePools {
+            transparent: Amount::zero(),
+            sprout: Amount::zero(),
+            sapling: Amount::zero(),
+            orchard: Amount::zero(),
+            deferred: Amount::zero(),
+        }
+    }
+}
+
+impl ValuePools {
+    /// Returns the value pools after adding `block` to a chain with these
+    /// value pools.
+    ///
+    /// `deferred_pool_contribution` is the part of the block subsidy that
+    /// `block` adds to the deferred pool. `spent_value` returns the value of
+    /// each transparent output spent by `block`. Outputs created earlier in
+    /// `block` must be available.
+    ///
+    /// Returns an error if a spent output is missing, or if a pool would be
+    /// larger than the maximum amount.
+    pub fn add_block(
+        &self,
+        block: &Block,
+        deferred_pool_contribution: Amount<NonNegative>,
+        mut spent_value: impl FnMut(&OutPoint) -> Option<Amount<NonNegative>>,
+    ) -> Result<ValuePools, Error> {
+        let mut transparent = i64::from(self.transparent);
+        let mut sprout = i64::from(self.sprout);
+        let mut sapling = i64::from(self.sapling);
+
+        for transaction in &block.transactions {
+            transparent += i64::from(transaction.transparent_value_out()?);
+            for input in transaction.inputs() {
+                if let TransparentInput::PrevOut { outpoint, .. } = input {
+                    let value = spent_value(outpoint)
+                        .ok_or("value pools are missing an output spent by the block")?;
+                    transparent -= i64::from(value);
+                }
+            }
+
+            for (vpub_old, vpub_new) in transaction.sprout_value_transfers() {
+                sprout += i64::from(vpub_old) - i64::from(vpub_new);
+            }
+
+            // A positive value balance moves value out of the Sapling pool
+            if let Some(value_balance) = transaction.sapling_value_balance() {
+                sapling -= i64::from(value_balance);
+            }
+        }
+
+        Ok(ValuePools {
+            transparent: transparent.try_into()?,
+            sprout: sprout.try_into()?,
+            sapling: sapling.try_into()?,
+            orchard: self.orchard,
+            deferred: (i64::from(self.deferred) + i64::from(deferred_pool_contribution))
+                .try_into()?,
+        })
+    }
+
+    /// Returns an error if the Sprout pool is negative after the block with
+    /// `hash` at `height`.
+    ///
+    /// The Sprout "turnstile" ([ZIP-209]) stops transactions from withdrawing
+    /// more value from the Sprout pool than was deposited into it, so an
+    /// undetected counterfeiting bug can't inflate the transparent supply.
+    ///
+    /// [ZIP-209]: https://zips.z.cash/zip-0209
+    pub fn check_turnstile(
+        &self,
+        hash: BlockHeaderHash,
+        height: BlockHeight,
+    ) -> Result<(), NegativeValuePool> {
+        if i64::from(self.sprout) < 0 {
+            return Err(NegativeValuePool {
+                hash,
+                height,
+                pool: "sprout",
+                value: self.sprout,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns these value pools as bytes, for storage in the state.
+    pub(crate) fn to_bytes(&self) -> [u8; VALUE_POOLS_LEN] {
+        let mut bytes = [0; VALUE_POOLS_LEN];
+        for (chunk, pool) in bytes.chunks_mut(8).zip(&[
+            self.transparent,
+            self.sprout,
+            self.sapling,
+            self.orchard,
+            self.deferred,
+        ]) {
+            chunk.copy_from_slice(&i64::from(*pool).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parses value pools stored in the state.
+    ///
+    /// Value pools stored before the deferred pool was added don't have a
+    /// deferred pool value, so their deferred pool is empty.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<ValuePools, Error> {
+        if bytes.len() != VALUE_POOLS_LEN && bytes.len() != LEGACY_VALUE_POOLS_LEN {
+            Err("invalid value pools in state")?;
+        }
+
+        let pool = |index: usize| -> Result<Amount, Error> {
+            let mut value = [0; 8];
+            value.copy_from_slice(&bytes[index * 8..(index + 1) * 8]);
+            Ok(i64::from_le_bytes(value).try_into()?)
+        };
+
+        Ok(ValuePools {
+            transparent: pool(0)?,
+            sprout: pool(1)?,
+            sapling: pool(2)?,
+            orchard: pool(3)?,
+            deferred: if bytes.len() == VALUE_POOLS_LEN {
+                pool(4)?
+            } else {
+                Amount::zero()
+            },
+        })
+    }
+}
+
+/// The error returned by the state for blocks that would make a shielded
+/// value pool negative.
+///
+/// Callers can downcast the state error to this type, to distinguish
+/// turnstile violations from other errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NegativeValuePool {
+    /// The hash of the rejected block.
+    pub hash: BlockHeaderHash,
+    /// The height of the rejected block.
+    pub height: BlockHeight,
+    /// The name of the pool.
+    pub pool: &'static str,
+    /// The value the pool would have after the block.
+    pub value: Amount,
+}
+
+impl fmt::Display for NegativeValuePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "block {:?} at height {:?} would make the {} value pool negative: {} ZEC",
+            self.hash, self.height, self.pool, self.value
+        )
+    }
+}
+
+impl error::Error for NegativeValuePool {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{collections::HashMap, sync::Arc};
+
+    use zebra_chain::serialization::ZcashDeserialize;
+
+    use crate::Utxo;
+
+    #[test]
+    fn mainnet_coinbase_values() -> Result<(), Error> {
+        zebra_test::init();
+
+        let mut pools = ValuePools::default();
+        let mut utxos = HashMap::new();
+        let mut output_value = 0;
+        for bytes in &[
+            &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+        ] {
+            let block = Arc::new(Block::zcash_deserialize(*bytes)?);
+            utxos.extend(Utxo::from_block(&block));
+            pools = pools.add_block(&block, Amount::zero(), |outpoint| {
+                utxos.get(outpoint).map(|utxo| utxo.output.value)
+            })?;
+
+            output_value += block
+                .transactions
+                .iter()
+                .map(|transaction| i64::from(transaction.transparent_value_out().unwrap()))
+                .sum::<i64>();
+        }
+
+        // These blocks only have coinbase transactions
+        assert_eq!(i64::from(pools.transparent), output_value);
+        assert_eq!(i64::from(pools.sprout), 0);
+        assert_eq!(i64::from(pools.sapling), 0);
+        assert_eq!(i64::from(pools.deferred), 0);
+        assert_eq!(ValuePools::from_bytes(&pools.to_bytes())?, pools);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deferred_pool_contributions() -> Result<(), Error> {
+        zebra_test::init();
+
+        let mut pools = ValuePools::default();
+        let mut utxos = HashMap::new();
+        for bytes in &[
+            &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+        ] {
+            let block = Arc::new(Block::zcash_deserialize(*bytes)?);
+            utxos.extend(Utxo::from_block(&block));
+            pools = pools.add_block(&block, 18_750_000.try_into()?, |outpoint| {
+                utxos.get(outpoint).map(|utxo| utxo.output.value)
+            })?;
+        }
+
+        assert_eq!(i64::from(pools.deferred), 2 * 18_750_000);
+        assert_eq!(ValuePools::from_bytes(&pools.to_bytes())?, pools);
+
+        // Value pools stored before the deferred pool was added
+        let legacy = &pools.to_bytes()[..LEGACY_VALUE_POOLS_LEN];
+        let legacy_pools = ValuePools::from_bytes(legacy)?;
+        assert_eq!(i64::from(legacy_pools.deferred), 0);
+        assert_eq!(legacy_pools.transparent, pools.transparent);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sprout_turnstile() -> Result<(), Error> {
+        zebra_test::init();
+
+        let hash = BlockHeaderHash([0; 32]);
+        let height = BlockHeight(1);
+
+        let mut pools = ValuePools::default();
+        pools.check_turnstile(hash, height)?;
+
+        // Other pools aren't checked
+        pools.sapling = (-1).try_into()?;
+        pools.check_turnstile(hash, height)?;
+
+        pools.sprout = (-1).try_into()?;
+        let error = pools.check_turnstile(hash, height).unwrap_err();
+        assert_eq!(error.pool, "sprout");
+        assert_eq!(i64::from(error.value), -1);
+
+        Ok(())
+    }
+}
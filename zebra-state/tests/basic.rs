@@ -1,15 +1,20 @@
-use color_eyre::eyre::Report;
+use color_eyre::eyre::{eyre, Report};
 use once_cell::sync::Lazy;
 use std::sync::Arc;
 use tempdir::TempDir;
-use zebra_chain::{block::Block, serialization::ZcashDeserialize};
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    serialization::ZcashDeserialize,
+    types::{amount::Amount, BlockHeight},
+    Network::*,
+};
 use zebra_test::transcript::Transcript;
 
 use zebra_state::*;
 
 static ADD_BLOCK_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
     let block: Arc<_> =
-        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..])
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
             .unwrap()
             .into();
     let hash = block.as_ref().into();
@@ -17,6 +22,7 @@ static ADD_BLOCK_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
         (
             Request::AddBlock {
                 block: block.clone(),
+                deferred_pool_contribution: Amount::zero(),
             },
             Response::Added { hash },
         ),
@@ -35,16 +41,252 @@ static GET_TIP_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
     let hash0 = block0.as_ref().into();
     let hash1 = block1.as_ref().into();
     vec![
-        // Insert higher block first, lower block second
         (
-            Request::AddBlock { block: block1 },
+            Request::AddBlock {
+                block: block0,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash0 },
+        ),
+        (
+            Request::AddBlock {
+                block: block1,
+                deferred_pool_contribution: Amount::zero(),
+            },
             Response::Added { hash: hash1 },
         ),
+        (Request::GetTip, Response::Tip { hash: hash1 }),
+    ]
+});
+
+static GET_BLOCK_BY_HEIGHT_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
+    let block0: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
+            .unwrap()
+            .into();
+    let block1: Arc<_> = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])
+        .unwrap()
+        .into();
+    let hash0 = block0.as_ref().into();
+    let hash1 = block1.as_ref().into();
+    vec![
         (
-            Request::AddBlock { block: block0 },
+            Request::AddBlock {
+                block: block0.clone(),
+                deferred_pool_contribution: Amount::zero(),
+            },
             Response::Added { hash: hash0 },
         ),
-        (Request::GetTip, Response::Tip { hash: hash1 }),
+        (
+            Request::AddBlock {
+                block: block1.clone(),
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash1 },
+        ),
+        (
+            Request::GetBlockHeaderByHeight {
+                height: BlockHeight(1),
+            },
+            Response::BlockHeader {
+                header: block1.header,
+            },
+        ),
+        (
+            Request::GetBlockByHeight {
+                height: BlockHeight(1),
+            },
+            Response::Block { block: block1 },
+        ),
+        (
+            Request::GetBlockByHeight {
+                height: BlockHeight(0),
+            },
+            Response::Block { block: block0 },
+        ),
+    ]
+});
+
+static GET_TRANSACTION_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
+    let block: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
+            .unwrap()
+            .into();
+    let hash = block.as_ref().into();
+    let transaction = block.transactions[0].clone();
+    vec![
+        (
+            Request::AddBlock {
+                block: block.clone(),
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash },
+        ),
+        (
+            Request::GetTransaction {
+                hash: transaction.as_ref().into(),
+            },
+            Response::Transaction {
+                transaction,
+                block_hash: hash,
+            },
+        ),
+    ]
+});
+
+static AWAIT_TIP_CHANGE_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
+    let block: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
+            .unwrap()
+            .into();
+    let hash = block.as_ref().into();
+    vec![
+        (
+            Request::AddBlock {
+                block,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash },
+        ),
+        // The tip has already changed from the caller's tip
+        (
+            Request::AwaitTipChange {
+                tip: BlockHeaderHash([0; 32]),
+            },
+            Response::Tip { hash },
+        ),
+    ]
+});
+
+static KNOWN_BLOCK_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
+    let block0: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
+            .unwrap()
+            .into();
+    let block1: Arc<_> = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])
+        .unwrap()
+        .into();
+    let hash0 = block0.as_ref().into();
+    let hash1 = block1.as_ref().into();
+    vec![
+        (
+            Request::KnownBlock { hash: hash1 },
+            Response::KnownBlock(None),
+        ),
+        (
+            Request::AddBlock {
+                block: block0,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash0 },
+        ),
+        (
+            Request::AddBlock {
+                block: block1,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash1 },
+        ),
+        (
+            Request::KnownBlock { hash: hash1 },
+            Response::KnownBlock(Some(KnownBlock::BestChain)),
+        ),
+    ]
+});
+
+static IS_FINALIZED_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
+    let block0: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
+            .unwrap()
+            .into();
+    let block1: Arc<_> = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])
+        .unwrap()
+        .into();
+    let hash0 = block0.as_ref().into();
+    let hash1 = block1.as_ref().into();
+    vec![
+        (
+            Request::IsFinalized { hash: hash1 },
+            Response::IsFinalized(false),
+        ),
+        (
+            Request::AddBlock {
+                block: block0,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash0 },
+        ),
+        (
+            Request::AddBlock {
+                block: block1,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash1 },
+        ),
+        // Recent blocks can still be replaced by a fork
+        (
+            Request::IsFinalized { hash: hash0 },
+            Response::IsFinalized(false),
+        ),
+        (
+            Request::IsFinalized { hash: hash1 },
+            Response::IsFinalized(false),
+        ),
+    ]
+});
+
+static VALUE_POOLS_TRANSCRIPT: Lazy<Vec<(Request, Response)>> = Lazy::new(|| {
+    let block0: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])
+            .unwrap()
+            .into();
+    let block1: Arc<_> = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])
+        .unwrap()
+        .into();
+    let hash0 = block0.as_ref().into();
+    let hash1 = block1.as_ref().into();
+
+    // These blocks don't spend any outputs
+    let pools0 = ValuePools::default()
+        .add_block(&block0, Amount::zero(), |_| None)
+        .unwrap();
+    let pools1 = pools0.add_block(&block1, Amount::zero(), |_| None).unwrap();
+    assert_ne!(pools0, pools1);
+
+    vec![
+        (
+            Request::AddBlock {
+                block: block0,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash0 },
+        ),
+        (
+            Request::AddBlock {
+                block: block1,
+                deferred_pool_contribution: Amount::zero(),
+            },
+            Response::Added { hash: hash1 },
+        ),
+        (
+            Request::GetValuePools {
+                height: BlockHeight(0),
+            },
+            Response::ValuePools(pools0),
+        ),
+        (
+            Request::GetValuePools {
+                height: BlockHeight(1),
+            },
+            Response::ValuePools(pools1),
+        ),
+        // These blocks don't have any shielded outputs
+        (
+            Request::GetTreeSizes {
+                height: BlockHeight(1),
+            },
+            Response::TreeSizes(TreeSizes::default()),
+        ),
     ]
 });
 
@@ -57,16 +299,30 @@ async fn check_transcripts_test() -> Result<(), Report> {
 async fn check_transcripts() -> Result<(), Report> {
     zebra_test::init();
 
-    for transcript_data in &[&ADD_BLOCK_TRANSCRIPT, &GET_TIP_TRANSCRIPT] {
+    for transcript_data in &[
+        &ADD_BLOCK_TRANSCRIPT,
+        &GET_TIP_TRANSCRIPT,
+        &GET_BLOCK_BY_HEIGHT_TRANSCRIPT,
+        &GET_TRANSACTION_TRANSCRIPT,
+        &AWAIT_TIP_CHANGE_TRANSCRIPT,
+        &KNOWN_BLOCK_TRANSCRIPT,
+        &IS_FINALIZED_TRANSCRIPT,
+        &VALUE_POOLS_TRANSCRIPT,
+    ] {
         let service = in_memory::init();
         let transcript = Transcript::from(transcript_data.iter().cloned());
         /// SPANDOC: check the in memory service against the transcript
         transcript.check(service).await?;
 
         let storage_guard = TempDir::new("")?;
-        let service = on_disk::init(Config {
-            cache_dir: Some(storage_guard.path().to_owned()),
-        });
+        let service = on_disk::init(
+            Config {
+                cache_dir: Some(storage_guard.path().to_owned()),
+                compact_filters: false,
+                min_free_disk_space_mb: 0,
+            },
+            Mainnet,
+        );
         let transcript = Transcript::from(transcript_data.iter().cloned());
         /// SPANDOC: check the on disk service against the transcript
         transcript.check(service).await?;
@@ -76,3 +332,512 @@ async fn check_transcripts() -> Result<(), Report> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn missing_value_pools_are_rejected() -> Result<(), Report> {
+    use tower::ServiceExt;
+
+    zebra_test::init();
+
+    // The state can't check the Sprout turnstile without the value pools
+    // before the block
+    let block: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_415000_BYTES[..])?.into();
+
+    let result = in_memory::init()
+        .oneshot(Request::AddBlock {
+            block: block.clone(),
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await;
+    assert!(
+        result.is_err(),
+        "the in memory state should reject the block"
+    );
+
+    let storage_guard = TempDir::new("")?;
+    let service = on_disk::init(
+        Config {
+            cache_dir: Some(storage_guard.path().to_owned()),
+            compact_filters: false,
+            min_free_disk_space_mb: 0,
+        },
+        Mainnet,
+    );
+    let result = service
+        .clone()
+        .oneshot(Request::AddBlock {
+            block,
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await;
+    assert!(result.is_err(), "the on disk state should reject the block");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn empty_state_has_genesis_test() -> Result<(), Report> {
+    empty_state_has_genesis().await
+}
+
+#[spandoc::spandoc]
+async fn empty_state_has_genesis() -> Result<(), Report> {
+    zebra_test::init();
+
+    let genesis: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?.into();
+    let hash = genesis.as_ref().into();
+
+    let transcript = Transcript::from(
+        vec![
+            (Request::GetTip, Response::Tip { hash }),
+            (
+                Request::GetBlock { hash },
+                Response::Block { block: genesis },
+            ),
+        ]
+        .into_iter(),
+    );
+
+    let storage_guard = TempDir::new("")?;
+    let service = on_disk::init(
+        Config {
+            cache_dir: Some(storage_guard.path().to_owned()),
+            compact_filters: false,
+            min_free_disk_space_mb: 0,
+        },
+        Mainnet,
+    );
+    /// SPANDOC: check that the empty on disk service has the genesis block
+    transcript.check(service).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compact_filters_test() -> Result<(), Report> {
+    compact_filters().await
+}
+
+#[spandoc::spandoc]
+async fn compact_filters() -> Result<(), Report> {
+    use tower::ServiceExt;
+    use zebra_state::compact_filter::{BlockFilter, FilterHeader};
+
+    zebra_test::init();
+
+    let block0 = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?;
+    let block1: Arc<_> =
+        Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..])?.into();
+    let hash0 = block0.hash();
+    let hash1 = block1.hash();
+
+    // Both blocks only spend coinbase inputs
+    let filter0 = BlockFilter::basic(&block0, Vec::new());
+    let filter1 = BlockFilter::basic(&block1, Vec::new());
+
+    let transcript = Transcript::from(
+        vec![
+            (
+                Request::AddBlock {
+                    block: block1,
+                    deferred_pool_contribution: Amount::zero(),
+                },
+                Response::Added { hash: hash1 },
+            ),
+            (
+                Request::GetCompactFilters {
+                    start_height: BlockHeight(0),
+                    stop_hash: hash1,
+                },
+                Response::CompactFilters {
+                    filters: vec![(hash0, filter0.clone()), (hash1, filter1.clone())],
+                },
+            ),
+            (
+                Request::GetCompactFilterHeaders {
+                    start_height: BlockHeight(1),
+                    stop_hash: hash1,
+                },
+                Response::CompactFilterHeaders {
+                    previous_header: filter0.header(FilterHeader([0; 32])),
+                    filter_hashes: vec![filter1.hash()],
+                },
+            ),
+        ]
+        .into_iter(),
+    );
+
+    let storage_guard = TempDir::new("")?;
+    let service = on_disk::init(
+        Config {
+            cache_dir: Some(storage_guard.path().to_owned()),
+            compact_filters: true,
+            min_free_disk_space_mb: 0,
+        },
+        Mainnet,
+    );
+    /// SPANDOC: check that the on disk service serves compact filters
+    transcript.check(service.clone()).await?;
+
+    // The stop hash must be at or after the start height
+    let result = service
+        .oneshot(Request::GetCompactFilters {
+            start_height: BlockHeight(2),
+            stop_hash: hash1,
+        })
+        .await;
+    assert!(result.is_err(), "the stop hash is before the start height");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn database_info() -> Result<(), Report> {
+    use tower::ServiceExt;
+
+    zebra_test::init();
+
+    let genesis = Block::zcash_deserialize(&zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..])?;
+
+    let storage_guard = TempDir::new("")?;
+    let service = on_disk::init(
+        Config {
+            cache_dir: Some(storage_guard.path().to_owned()),
+            compact_filters: false,
+            min_free_disk_space_mb: 0,
+        },
+        Mainnet,
+    );
+
+    let info = match service
+        .oneshot(Request::DatabaseInfo)
+        .await
+        .map_err(|e| eyre!(e))?
+    {
+        Response::DatabaseInfo(info) => info,
+        _ => unreachable!("DatabaseInfo request can only result in Response::DatabaseInfo"),
+    };
+
+    assert_eq!(info.tip, Some((BlockHeight(0), genesis.hash())));
+    assert_eq!(
+        info.value_pools,
+        Some(
+            ValuePools::default()
+                .add_block(&genesis, Amount::zero(), |_| None)
+                .unwrap()
+        )
+    );
+    let by_height = info
+        .trees
+        .iter()
+        .find(|tree| tree.name == "by_height")
+        .expect("the state has a by_height tree");
+    assert_eq!(by_height.entries, 1);
+    assert!(info.reclaimable_bytes() <= info.size_on_disk);
+
+    Ok(())
+}
+
+/// Returns the mainnet test blocks from genesis to block 10.
+fn mainnet_blocks() -> Result<Vec<Arc<Block>>, Report> {
+    let blocks = [
+        &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_2_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_3_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_4_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_5_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_6_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_7_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_8_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_9_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_10_BYTES[..],
+    ];
+
+    blocks
+        .iter()
+        .map(|bytes| Ok(Block::zcash_deserialize(*bytes)?.into()))
+        .collect()
+}
+
+#[test]
+fn state_reader_iterators() -> Result<(), Report> {
+    use tower::ServiceExt;
+
+    zebra_test::init();
+
+    let blocks = mainnet_blocks()?;
+    let storage_guard = TempDir::new("")?;
+    let config = Config {
+        cache_dir: Some(storage_guard.path().to_owned()),
+        compact_filters: false,
+        min_free_disk_space_mb: 0,
+    };
+
+    // Dropping the runtime closes the database, so the reader can open it
+    {
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(async {
+            let service = on_disk::init(config.clone(), Mainnet);
+            for block in blocks.iter().skip(1).cloned() {
+                service
+                    .clone()
+                    .oneshot(Request::AddBlock {
+                        block,
+                        deferred_pool_contribution: Amount::zero(),
+                    })
+                    .await
+                    .map_err(|e| eyre!(e))?;
+            }
+            Ok::<_, Report>(())
+        })?;
+    }
+
+    let reader = on_disk::StateReader::open(&config, Mainnet).map_err(|e| eyre!(e))?;
+    assert_eq!(
+        reader.check_consistency().map_err(|e| eyre!(e))?,
+        Some(BlockHeight(blocks.len() as u32 - 1))
+    );
+
+    let read = reader
+        .blocks(BlockHeight(0))
+        .map_err(|e| eyre!(e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| eyre!(e))?;
+    assert_eq!(read.len(), blocks.len());
+    for (height, ((read_height, read_block), block)) in read.iter().zip(&blocks).enumerate() {
+        assert_eq!(read_height.0 as usize, height);
+        assert_eq!(read_block.hash(), block.hash());
+    }
+
+    let from_5 = reader.blocks(BlockHeight(5)).map_err(|e| eyre!(e))?.count();
+    assert_eq!(from_5, blocks.len() - 5);
+    assert_eq!(
+        reader.block(BlockHeight(3)).map_err(|e| eyre!(e))?,
+        Some(blocks[3].clone())
+    );
+
+    // These blocks don't spend any outputs
+    let utxos = reader
+        .utxos()
+        .map_err(|e| eyre!(e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| eyre!(e))?;
+    let outputs: usize = blocks
+        .iter()
+        .flat_map(|block| block.transactions.iter())
+        .map(|transaction| transaction.outputs().count())
+        .sum();
+    assert_eq!(utxos.len(), outputs);
+    assert!(utxos
+        .windows(2)
+        .all(|pair| pair[0].1.height <= pair[1].1.height));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fork_replaces_value_pools() -> Result<(), Report> {
+    use tower::ServiceExt;
+
+    zebra_test::init();
+
+    let blocks = mainnet_blocks()?;
+    let storage_guard = TempDir::new("")?;
+    let service = on_disk::init(
+        Config {
+            cache_dir: Some(storage_guard.path().to_owned()),
+            compact_filters: false,
+            min_free_disk_space_mb: 0,
+        },
+        Mainnet,
+    );
+    for block in blocks.iter().skip(1).take(3).cloned() {
+        service
+            .clone()
+            .oneshot(Request::AddBlock {
+                block,
+                deferred_pool_contribution: Amount::zero(),
+            })
+            .await
+            .map_err(|e| eyre!(e))?;
+    }
+
+    let value_pools = |height| {
+        service.clone().oneshot(Request::GetValuePools {
+            height: BlockHeight(height),
+        })
+    };
+    let pools3 = value_pools(3).await.map_err(|e| eyre!(e))?;
+
+    // A block with the same transactions, but a different header
+    let mut fork = blocks[2].as_ref().clone();
+    fork.header.nonce[0] ^= 1;
+    service
+        .clone()
+        .oneshot(Request::AddBlock {
+            block: Arc::new(fork),
+            deferred_pool_contribution: Amount::zero(),
+        })
+        .await
+        .map_err(|e| eyre!(e))?;
+    assert!(
+        value_pools(3).await.is_err(),
+        "the fork should replace the value pools after it"
+    );
+    let tree_sizes = service
+        .clone()
+        .oneshot(Request::GetTreeSizes {
+            height: BlockHeight(3),
+        })
+        .await;
+    assert!(
+        tree_sizes.is_err(),
+        "the fork should replace the tree sizes after it"
+    );
+
+    // Switching back to the original chain recalculates the value pools
+    for block in blocks.iter().skip(2).take(2).cloned() {
+        service
+            .clone()
+            .oneshot(Request::AddBlock {
+                block,
+                deferred_pool_contribution: Amount::zero(),
+            })
+            .await
+            .map_err(|e| eyre!(e))?;
+    }
+    assert_eq!(value_pools(3).await.map_err(|e| eyre!(e))?, pools3);
+
+    Ok(())
+}
+
+#[test]
+fn state_reader_utxos_need_index() -> Result<(), Report> {
+    use tower::ServiceExt;
+
+    zebra_test::init();
+
+    let blocks = mainnet_blocks()?;
+    let storage_guard = TempDir::new("")?;
+    let config = Config {
+        cache_dir: Some(storage_guard.path().to_owned()),
+        compact_filters: false,
+        min_free_disk_space_mb: 0,
+    };
+
+    // A fork at height 2 leaves the original block 3 above the indexed blocks
+    {
+        let mut fork = blocks[2].as_ref().clone();
+        fork.header.nonce[0] ^= 1;
+        let added = blocks
+            .iter()
+            .skip(1)
+            .take(3)
+            .cloned()
+            .chain(std::iter::once(Arc::new(fork)));
+
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(async {
+            let service = on_disk::init(config.clone(), Mainnet);
+            for block in added {
+                service
+                    .clone()
+                    .oneshot(Request::AddBlock {
+                        block,
+                        deferred_pool_contribution: Amount::zero(),
+                    })
+                    .await
+                    .map_err(|e| eyre!(e))?;
+            }
+            Ok::<_, Report>(())
+        })?;
+    }
+
+    let reader = on_disk::StateReader::open(&config, Mainnet).map_err(|e| eyre!(e))?;
+    assert!(
+        reader.utxos().is_err(),
+        "utxos should fail when the unspent outputs don't reach the tip"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn state_info_missing_state() -> Result<(), Report> {
+    zebra_test::init();
+
+    let storage_guard = TempDir::new("")?;
+    let config = Config {
+        cache_dir: Some(storage_guard.path().to_owned()),
+        compact_filters: false,
+        min_free_disk_space_mb: 0,
+    };
+
+    assert!(
+        on_disk::state_info(&config, Mainnet).is_err(),
+        "state_info should fail when there is no state in the cache directory"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn state_wrong_network() -> Result<(), Report> {
+    zebra_test::init();
+
+    let storage_guard = TempDir::new("")?;
+    let config = Config {
+        cache_dir: Some(storage_guard.path().to_owned()),
+        compact_filters: false,
+        min_free_disk_space_mb: 0,
+    };
+
+    // A mainnet state in the testnet directory
+    let path = storage_guard.path().join("state").join("testnet");
+    {
+        let storage = sled::Config::default().path(&path).open()?;
+        storage
+            .open_tree(b"metadata")?
+            .insert(b"network", &b"mainnet"[..])?;
+        storage.flush()?;
+    }
+
+    let error = on_disk::state_info(&config, Testnet).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<WrongNetwork>(),
+        Some(&WrongNetwork {
+            path,
+            expected: Testnet,
+            found: Mainnet,
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn state_legacy_layout() -> Result<(), Report> {
+    zebra_test::init();
+
+    let storage_guard = TempDir::new("")?;
+    let config = Config {
+        cache_dir: Some(storage_guard.path().to_owned()),
+        compact_filters: false,
+        min_free_disk_space_mb: 0,
+    };
+
+    // A state from before states were separated by network
+    let legacy_path = storage_guard.path().join("state");
+    std::fs::create_dir_all(&legacy_path)?;
+    std::fs::write(legacy_path.join("db"), b"")?;
+
+    assert!(
+        on_disk::state_info(&config, Mainnet).is_err(),
+        "state_info should fail when the state is not in a network subdirectory"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Crash consistency tests for the on-disk state.
+//!
+//! These tests run a child process that commits blocks to the state, kill it
+//! at a random point, and check that the state reopens at a consistent tip.
+//!
+//! The child is this test binary, running the same test with
+//! `ZEBRA_STATE_CRASH_TEST_DIR` set.
+
+use color_eyre::eyre::{eyre, Report};
+use rand::Rng;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use tempdir::TempDir;
+use tower::ServiceExt;
+use zebra_chain::{
+    block::Block, serialization::ZcashDeserialize, types::amount::Amount, Network::*,
+};
+
+use zebra_state::*;
+
+/// The environment variable that makes the test run as the child process,
+/// which writes states to the directory in the variable.
+const CHILD_DIR_VAR: &str = "ZEBRA_STATE_CRASH_TEST_DIR";
+
+/// The number of times the child process is killed.
+const KILL_ROUNDS: usize = 10;
+
+/// The maximum time the child process runs before it is killed.
+const MAX_KILL_DELAY: Duration = Duration::from_millis(1500);
+
+fn mainnet_blocks() -> Result<Vec<Arc<Block>>, Report> {
+    let blocks = [
+        &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_2_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_3_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_4_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_5_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_6_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_7_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_8_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_9_BYTES[..],
+        &zebra_test::vectors::BLOCK_MAINNET_10_BYTES[..],
+    ];
+
+    blocks
+        .iter()
+        .map(|bytes| Ok(Block::zcash_deserialize(*bytes)?.into()))
+        .collect()
+}
+
+/// Returns the config for a state in `cache_dir`.
+///
+/// Compact filters are enabled, so the test covers all the block indexes.
+fn crash_config(cache_dir: PathBuf) -> Config {
+    Config {
+        cache_dir: Some(cache_dir),
+        compact_filters: true,
+        min_free_disk_space_mb: 0,
+    }
+}
+
+#[test]
+fn killed_writer_leaves_consistent_state() -> Result<(), Report> {
+    if let Some(dir) = env::var_os(CHILD_DIR_VAR) {
+        return write_states(dir.into());
+    }
+
+    zebra_test::init();
+
+    let blocks = mainnet_blocks()?;
+    let storage_guard = TempDir::new("")?;
+    let mut rng = rand::thread_rng();
+
+    for round in 0..KILL_ROUNDS {
+        let dir = storage_guard.path().join(round.to_string());
+        fs::create_dir_all(&dir)?;
+
+        let mut child = Command::new(env::current_exe()?)
+            .args(&[
+                "--exact",
+                "killed_writer_leaves_consistent_state",
+                "--test-threads=1",
+            ])
+            .env(CHILD_DIR_VAR, &dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let delay = rng.gen_range(0, MAX_KILL_DELAY.as_millis() as u64);
+        thread::sleep(Duration::from_millis(delay));
+        child.kill()?;
+        child.wait()?;
+
+        for state_dir in fs::read_dir(&dir)? {
+            check_state(&state_dir?.path(), &blocks)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Commit the test blocks to new states in `dir`, until the process is
+/// killed.
+fn write_states(dir: PathBuf) -> Result<(), Report> {
+    let blocks = mainnet_blocks()?;
+    let mut runtime = tokio::runtime::Runtime::new()?;
+
+    for state in 0.. {
+        let config = crash_config(dir.join(state.to_string()));
+        runtime.block_on(async {
+            let service = on_disk::init(config, Mainnet);
+            for block in blocks.iter().skip(1).cloned() {
+                service
+                    .clone()
+                    .oneshot(Request::AddBlock {
+                        block,
+                        deferred_pool_contribution: Amount::zero(),
+                    })
+                    .await
+                    .map_err(|e| eyre!(e))?;
+            }
+            Ok::<_, Report>(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Check that the state in `cache_dir` is consistent, then reopen it and
+/// commit the rest of `blocks`.
+fn check_state(cache_dir: &Path, blocks: &[Arc<Block>]) -> Result<(), Report> {
+    let config = crash_config(cache_dir.to_owned());
+    let state_path = config
+        .network_dir("state", Mainnet)
+        .expect("the crash test sets a cache directory");
+    if !state_path.exists() {
+        // The child was killed before it created the state
+        return Ok(());
+    }
+
+    let tip = {
+        let reader = on_disk::StateReader::open(&config, Mainnet).map_err(|e| eyre!(e))?;
+        reader
+            .check_consistency()
+            .map_err(|e| eyre!("inconsistent state in {:?}: {}", cache_dir, e))?
+    };
+    let next_height = tip.map(|tip| tip.0 as usize + 1).unwrap_or(0);
+
+    // Dropping the runtime closes the database, so the reader can open it
+    {
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(async {
+            let service = on_disk::init(config.clone(), Mainnet);
+            for block in blocks.iter().skip(next_height.max(1)).cloned() {
+                service
+                    .clone()
+                    .oneshot(Request::AddBlock {
+                        block,
+                        deferred_pool_contribution: Amount::zero(),
+                    })
+                    .await
+                    .map_err(|e| eyre!(e))?;
+            }
+
+            let tip = blocks.last().expect("test blocks are not empty");
+            let response = service
+                .clone()
+                .oneshot(Request::GetTip)
+                .await
+                .map_err(|e| eyre!(e))?;
+            assert_eq!(response, Response::Tip { hash: tip.hash() });
+
+            // The value pools are calculated from the stored outputs, so they
+            // fail if the outputs are inconsistent with the blocks
+            let response = service
+                .clone()
+                .oneshot(Request::GetValuePools {
+                    height: tip.coinbase_height().expect("test blocks have a height"),
+                })
+                .await
+                .map_err(|e| eyre!(e))?;
+            assert!(matches!(response, Response::ValuePools(_)));
+
+            Ok::<_, Report>(())
+        })?;
+    }
+
+    let reader = on_disk::StateReader::open(&config, Mainnet).map_err(|e| eyre!(e))?;
+    assert_eq!(
+        reader.check_consistency().map_err(|e| eyre!(e))?,
+        blocks.last().and_then(|block| block.coinbase_height())
+    );
+
+    Ok(())
+}
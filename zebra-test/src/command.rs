@@ -0,0 +1,171 @@
+//! Launching test subprocesses, and checking their output.
+
+use color_eyre::eyre::{eyre, Report};
+use std::{
+    path::Path,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Returns a command that runs `command_path` in `dir`, capturing its output.
+pub fn test_cmd(command_path: impl AsRef<Path>, dir: impl AsRef<Path>) -> Command {
+    let mut cmd = Command::new(command_path.as_ref());
+    cmd.current_dir(dir.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    cmd
+}
+
+/// Extension methods for running test commands.
+pub trait CommandExt {
+    /// Run the command to completion, and return its output.
+    fn output_checked(&mut self) -> Result<TestOutput, Report>;
+
+    /// Spawn the command, and return a handle to the running child.
+    fn spawn_checked(&mut self) -> Result<TestChild, Report>;
+}
+
+impl CommandExt for Command {
+    fn output_checked(&mut self) -> Result<TestOutput, Report> {
+        let output = self
+            .output()
+            .map_err(|e| eyre!("failed to run {:?}: {}", self, e))?;
+
+        Ok(TestOutput {
+            cmd: format!("{:?}", self),
+            output,
+        })
+    }
+
+    fn spawn_checked(&mut self) -> Result<TestChild, Report> {
+        let child = self
+            .spawn()
+            .map_err(|e| eyre!("failed to spawn {:?}: {}", self, e))?;
+
+        Ok(TestChild {
+            cmd: format!("{:?}", self),
+            child: Some(child),
+        })
+    }
+}
+
+/// A running test subprocess.
+///
+/// The child is killed when it is dropped.
+#[derive(Debug)]
+pub struct TestChild {
+    cmd: String,
+    /// The child process, which is taken when we wait for its output.
+    child: Option<Child>,
+}
+
+impl TestChild {
+    /// Returns the exit status, if the child has exited.
+    pub fn try_status(&mut self) -> Result<Option<ExitStatus>, Report> {
+        Ok(self.child_mut().try_wait()?)
+    }
+
+    /// Wait up to `timeout` for the child to exit by itself.
+    ///
+    /// Returns an error if the child is still running after `timeout`.
+    pub fn wait_with_timeout(mut self, timeout: Duration) -> Result<TestOutput, Report> {
+        let start = Instant::now();
+        while self.try_status()?.is_none() {
+            if start.elapsed() > timeout {
+                return Err(eyre!("{} did not exit within {:?}", self.cmd, timeout));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        self.wait_with_output()
+    }
+
+    /// Kill the child, and return its output.
+    pub fn kill(mut self) -> Result<TestOutput, Report> {
+        // The child may have already exited
+        let _ = self.child_mut().kill();
+        self.wait_with_output()
+    }
+
+    fn wait_with_output(mut self) -> Result<TestOutput, Report> {
+        let child = self.child.take().expect("child is only taken once");
+        let output = child.wait_with_output()?;
+        Ok(TestOutput {
+            cmd: self.cmd.clone(),
+            output,
+        })
+    }
+
+    fn child_mut(&mut self) -> &mut Child {
+        self.child.as_mut().expect("child is only taken on exit")
+    }
+}
+
+impl Drop for TestChild {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// The output of a finished test subprocess.
+#[derive(Debug)]
+pub struct TestOutput {
+    cmd: String,
+    /// The exit status and captured output.
+    pub output: Output,
+}
+
+impl TestOutput {
+    /// Returns the captured standard output, lossily converted to a string.
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output.stdout).into_owned()
+    }
+
+    /// Returns the captured standard error, lossily converted to a string.
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.output.stderr).into_owned()
+    }
+
+    /// Returns an error if the command failed.
+    pub fn assert_success(&self) -> Result<&Self, Report> {
+        if !self.output.status.success() {
+            return Err(self.error(format!("failed with {}", self.output.status)));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns an error if standard output does not contain `needle`.
+    pub fn stdout_contains(&self, needle: &str) -> Result<&Self, Report> {
+        if !self.stdout().contains(needle) {
+            return Err(self.error(format!("stdout does not contain {:?}", needle)));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns an error if standard error does not contain `needle`.
+    pub fn stderr_contains(&self, needle: &str) -> Result<&Self, Report> {
+        if !self.stderr().contains(needle) {
+            return Err(self.error(format!("stderr does not contain {:?}", needle)));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns an error with `msg`, and the command and its output.
+    fn error(&self, msg: String) -> Report {
+        eyre!(
+            "{} {}\n\nstdout:\n{}\n\nstderr:\n{}",
+            self.cmd,
+            msg,
+            self.stdout(),
+            self.stderr()
+        )
+    }
+}
@@ -64,5 +64,8 @@ pub fn init() {
     })
 }
 
+pub mod command;
+pub mod network;
 pub mod transcript;
 pub mod vectors;
+pub mod zip0243;
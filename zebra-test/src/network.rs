@@ -0,0 +1,259 @@
+//! A fake Zcash network peer, which responds to messages using a script.
+//!
+//! The fake peer speaks the Bitcoin-derived Zcash wire protocol, but it
+//! doesn't parse message payloads. Instead, each incoming command is answered
+//! with the raw messages in the peer's [`Script`]. This lets tests check how
+//! `zebrad` behaves on a network, without connecting to real peers.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+
+/// The network magic for Mainnet.
+pub const MAINNET_MAGIC: [u8; 4] = [0x24, 0xe9, 0x27, 0x64];
+
+/// The network magic for Testnet.
+pub const TESTNET_MAGIC: [u8; 4] = [0xfa, 0x1a, 0xf9, 0xbf];
+
+/// The protocol version advertised by the fake peer.
+pub const FAKE_PEER_VERSION: u32 = 170_011;
+
+/// The maximum payload size the fake peer will read.
+const MAX_PAYLOAD_LEN: usize = 2 * 1024 * 1024;
+
+/// A network message, with an unparsed payload.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawMessage {
+    /// The message command, such as `version` or `getblocks`.
+    pub command: String,
+    /// The serialized message body.
+    pub payload: Vec<u8>,
+}
+
+impl RawMessage {
+    /// Create a new message with `command` and `payload`.
+    pub fn new(command: impl Into<String>, payload: Vec<u8>) -> Self {
+        Self {
+            command: command.into(),
+            payload,
+        }
+    }
+
+    /// Create a `version` message advertising `start_height`.
+    pub fn version(start_height: u32) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("the clock is after the epoch")
+            .as_secs() as i64;
+        let user_agent = b"/zebra-test:0.1/";
+
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&FAKE_PEER_VERSION.to_le_bytes());
+        // services: NODE_NETWORK
+        payload.extend_from_slice(&1u64.to_le_bytes());
+        payload.extend_from_slice(&timestamp.to_le_bytes());
+        // address_recv and address_from: no services, unspecified address
+        for _ in 0..2 {
+            payload.extend_from_slice(&0u64.to_le_bytes());
+            payload.extend_from_slice(&[0; 16]);
+            payload.extend_from_slice(&0u16.to_be_bytes());
+        }
+        // A fixed nonce, which is unlikely to match the node's nonce
+        payload.extend_from_slice(&0x7a65_6272_6174_6573u64.to_le_bytes());
+        payload.push(user_agent.len() as u8);
+        payload.extend_from_slice(user_agent);
+        payload.extend_from_slice(&start_height.to_le_bytes());
+        // relay: false
+        payload.push(0);
+
+        Self::new("version", payload)
+    }
+
+    /// Create a `verack` message.
+    pub fn verack() -> Self {
+        Self::new("verack", Vec::new())
+    }
+
+    /// Serialize this message for the network with `magic`.
+    pub fn encode(&self, magic: [u8; 4]) -> Vec<u8> {
+        let mut command = [0u8; 12];
+        command[..self.command.len()].copy_from_slice(self.command.as_bytes());
+
+        let mut bytes = Vec::with_capacity(24 + self.payload.len());
+        bytes.extend_from_slice(&magic);
+        bytes.extend_from_slice(&command);
+        bytes.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&checksum(&self.payload));
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Read a message for the network with `magic` from `reader`.
+    pub fn read<R: Read>(mut reader: R, magic: [u8; 4]) -> io::Result<Self> {
+        let mut header = [0u8; 24];
+        reader.read_exact(&mut header)?;
+
+        if header[0..4] != magic {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "wrong magic"));
+        }
+
+        let command = String::from_utf8_lossy(&header[4..16])
+            .trim_end_matches('\0')
+            .to_owned();
+
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&header[16..20]);
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_PAYLOAD_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "payload is too long",
+            ));
+        }
+
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+
+        if header[20..24] != checksum(&payload) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad checksum"));
+        }
+
+        Ok(Self { command, payload })
+    }
+}
+
+/// Returns the message checksum for `payload`: the first four bytes of its
+/// SHA-256d hash.
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(&Sha256::digest(payload));
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&hash[0..4]);
+    checksum
+}
+
+/// The responses a [`FakePeer`] sends for each incoming command.
+///
+/// `ping` messages are answered with a matching `pong`, unless the script
+/// overrides them. Other unscripted commands are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    responses: HashMap<String, Vec<RawMessage>>,
+}
+
+impl Script {
+    /// Create a script that completes the handshake, advertising
+    /// `start_height`.
+    pub fn handshake(start_height: u32) -> Self {
+        Self::default().respond(
+            "version",
+            vec![RawMessage::version(start_height), RawMessage::verack()],
+        )
+    }
+
+    /// Respond to each `command` with `messages`.
+    pub fn respond(mut self, command: impl Into<String>, messages: Vec<RawMessage>) -> Self {
+        self.responses.insert(command.into(), messages);
+        self
+    }
+}
+
+/// A fake peer, listening on a local port.
+///
+/// The fake peer runs in background threads, until the test process exits.
+#[derive(Clone, Debug)]
+pub struct FakePeer {
+    addr: SocketAddr,
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakePeer {
+    /// Start a fake peer for the network with `magic`, which answers
+    /// connections using `script`.
+    pub fn spawn(magic: [u8; 4], script: Script) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let peer_received = received.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let script = script.clone();
+                let received = peer_received.clone();
+                thread::spawn(move || {
+                    // The connection ends when the node disconnects
+                    let _ = serve(stream, magic, &script, &received);
+                });
+            }
+        });
+
+        Ok(Self { addr, received })
+    }
+
+    /// Returns the address the fake peer is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the commands the fake peer has received, in order.
+    pub fn received_commands(&self) -> Vec<String> {
+        self.received
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .clone()
+    }
+
+    /// Wait until the fake peer receives `command`, or `timeout` elapses.
+    ///
+    /// Returns true if the command was received.
+    pub fn wait_for_command(&self, command: &str, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if self.received_commands().iter().any(|c| c == command) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        false
+    }
+}
+
+/// Answer the messages on `stream` using `script`, recording each command in
+/// `received`.
+fn serve(
+    mut stream: TcpStream,
+    magic: [u8; 4],
+    script: &Script,
+    received: &Mutex<Vec<String>>,
+) -> io::Result<()> {
+    loop {
+        let message = RawMessage::read(&mut stream, magic)?;
+        received
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .push(message.command.clone());
+
+        match script.responses.get(&message.command) {
+            Some(responses) => {
+                for response in responses {
+                    stream.write_all(&response.encode(magic))?;
+                }
+            }
+            None if message.command == "ping" => {
+                let pong = RawMessage::new("pong", message.payload);
+                stream.write_all(&pong.encode(magic))?;
+            }
+            None => {}
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use hex::FromHex;
 
+use std::collections::BTreeMap;
+
 use lazy_static::lazy_static;
 
 // Copied from librustzcash
@@ -265,4 +267,83 @@ lazy_static! {
     pub static ref BLOCK_MAINNET_8_BYTES: Vec<u8> = <Vec<u8>>::from_hex("04000000045ae2a7ae1a5ea25a3d531dde8a42f5bb20f67f49d930acacd98fae05b8040003de449d65aec6f3c2dadf9a53a5e3dff9d972a41ba6f034fcbbe6e86f2e777a0000000000000000000000000000000000000000000000000000000000000000147b1358ffff071fa5556cd0fb280000000000000000000000000000000000000000000000000000fd4005001235028584cb4d1b9f928d34441d86ee727fa0f90f79f4fa8c09d681c0a143283c302af3706adde77e143a694bf163b759f17574989f3b0277b11a7e780e16f8afe5aa632be73ad5fa5fd3fb3602e985fa3add0341fc63b08eb93f9c5e63467f752c49fc25cfea5d0916c6ff4e4c1d427ad634d77836ceaed2f4daeb8126f7775c0ff0495da4a0e414c8e57d49bf74529359554713c2a32a9e35e4097593d6360cf99f991cf17a03e04d7b90422edbfa6e9086d77408d1c592ff22551bee749f10d73fdf048e52c5d75dc926519a9ad8fc0ab48bab37abbd07d3e770b56eff92cd7158f0e3963cc072aa459c6535c9176832a745eddf4c20be8cfb050bb67009c7c17570ab45a227fb8005846dd2014405da319b55c28bdea270b4da58bc613af78a9ebfcb081b54ae9b9f28bdeef513fc9623a2e5b5fb345cdb278b0c3a971c2f5beb74038df6c951738ae29df8d300d3ec7c18501b74ae166505ec46bb9143dd1290cb014a68613103353b8d42d0f671b348b4835dfc88cd01dd60ed698e3445c8a6e1c698bf6691b7a9bb07a41942d1e280e3ac235718732f78c2675df4a5968d1605f2bd7ca54386c67328e7a645d9bd42a657f8d01c06d5bde9cf06bc68af3a91b1e7f3b1fa8a301bc84911ef6937beeb5dc98ed5e21d8a2345ed71965076534d42d6769ce0f1f754781b8f08732f274f679f880202373e9f4bf45afbb786c15031246991d64851f3b65c74451b67dd482dad69274ec07e4721eb005d805d08add53956c3fec6a958235429e874e1ec44735bdd0f50b4a8b0ca3debd5c7a1588866f9b15aabfe3b1a1817047acbccdaf779c142f626d0245625d558dcf54122f58086f1b30fa24ec5a3a5eea48de90f9a7644242955324420c3a9b8c68351c2a28769a07d72c930301bf77020d93eece767a322f45746f72512bf54d100cbbbdea701d03517f6c3264fdf93b1eb5cbb42fb155a66ee1c9133b6ba346bfa0bfbe0cb04f0f8e9c82810757766569176b516439990ed77bea4da1dc2cd43dc06efad2ff52d7fd724e0535ad16142c6b667b01b21c3b961c8c7c74d0b26a9b87defaacefe9d987d34a9f49aa9dea7b1a057aa8f38e4906ebf66ffcac8213fa25a7cc8686acd50d40e5ece1061fdd891265544e9c35776da026f22ff7add026c7d03aec0df83e9028a4f8e43039f4b59ff212b4fd9d4ed8456fa0fe709b9f6ba03c422722b4854a0383a70693b7e7d8960132fa4c78b3bd017ef399274bc48b7b0d85873bf8625fa24367ccaba215e5622d55caf13d982316d481d0627ba5e1929284fdd6b73f6693b608f10e0bb3c190c1a04728a52945ea92f131a3ca3d529718c1dc86513b862437b111585356e64e011df896541a29be4266b9c46bf7e612fb951602707423db862b4e81990d70115483b9504f8bfacb754e0966d914a1ec3b9c2b70bfb2d30de4b4ad5b4e711048ed1bd4d1d817201cf03c4ecb163051054abbf10e06ab84b3888d0ad03922fa5f6865e6070f783bdc54ae868ad32c61f36825b02d0c8f4a7915a43956a6050341fd1656518f9b21b3a2e9e4d8a32643bd581e8076c656a1a30f4dbe6eb0810b90d24c2fc88cb73c8ee45e9d95a521a394efb363c21d0b6f83821d10da81d1f761c9a5dc9de5ad0047a2fd883052a4873e183dd05d595eaaedb96c89e357be5920467da93c2f194d207bce93e7ec1d5c5130abd4513521a464ee1ebbd7c3e70debf75f15f8a9c1f914f377d16b121c25ab3eab07efe1124a915798212c46b51ff73f741e8ef756ca8cd7f816f21f7083f22acaba8fb195bcecf7f12af757a3e6e304c32dc453ae4ce6ed1d60b3517db141355e3303d55992e11d7598f5c3fe8a10f4979b686c1025771c5be1d37a6830101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff025800ffffffff02801a0600000000002321027a46eb513588b01b37ea24303f4b628afd12cc20df789fede0921e43cad3e875aca08601000000000017a9147d46a730d31f97b1930d3368a967c309bd4d136a8700000000").expect("Block bytes are in valid hex representation");
     pub static ref BLOCK_MAINNET_9_BYTES: Vec<u8> = <Vec<u8>>::from_hex("04000000012b0ebc452f335bee12d2dfd9558cd100aa29db6e55498c795c33beda770600dc7cc27c90a1b6e9a6142e3be2536f37fb117fca7db495d16ae4f31b62ab24ff00000000000000000000000000000000000000000000000000000000000000005f7b1358ffff071fa5556ccb4b000000000000000000000000000000000000000000000000000002fd4005006acfefed0dfac1235a490effda752ec897fecb01393c029e23a3269be448c7cfe24436267f25d9011502e69d803a81b2c75112e3882b63f5e9f4b99fa68630085c3163104ec2b822e42d4bf358c7878d3e236b02652b144a9a0b119e5635af07e3a8e65a153d8dd018feeea5bba9ac1defa552193bcbf7d602f1726a880ede7f9bb590ed7d0ce161c27bf314cd4e3f5edbd9171ccc15e0504dc78944753805c3423aa5e5f5b3ee079a165bfdc25edd87b4373ca2d6ae79f758bc7b7030f53e16b71d13caf43436d3703d524f464ebbe2c90f26da9b7586bed1c3244d2471eb24c3bd657f910c34779dfb1e4e09f7bcabb4c165c5b815ce636ff47617cf7c9787480d4f542a61bdb4ebb59dc60e55e73c285725e0b05bd0b3a851934cfd74f4615ed2ec0f5418235df4bf4ee9b2956035622ee80cc6019df057072d57d77df3262f159ae709598868b6e6fedb1f8502030f97cb2b9d5ef9d17ca24c2296b168d7897352d317662acf0015918757e9a99a3ae5891b982d7d9e23246886aaca9660498400e85eff7e4572262ad708ce3879c77e0e608833ea65b66a5a584386f2059f1b9f12678b870f2d40dbfc33d3ee70a41a0373cd9d99dc18573238bd48afbfa02b540ce04f8652b9771a24d63be7ad2bee3cba69f0d09baadbec1de71902d90ad6729dd62529e7f14fa89e5b1323eebec70867ff117e071d9645955e6c9dd47913409db30d6d6c03b69c610bdaef451a4bce3fecd80674dd671e6efbb47952ea2a53d457cc1857f73d7a941963ada0d5fe0e9a0b3a4450e5394de516034066d4b379375db2e8a83b62a8086641d5efd28245001cb8233c71242a93827faca73ab76c30dca58ba9bcc405cb92e51305f5d5bff6c30b1217777ee68fa97c7855dda2c6319ebc5e7f5ab562d27dc9cea9a9695bc9373a0feb855e44e138334e00be9a8da904eb31aef186fd39ee21c6e035378c4e06c8c4dd7993bd2fd76b716738b5984234e53a280304dd40d1bd17aeb4c72a92c1dab4b152f47d793f410b5bd9628c06092ddd46f1a973ef2b8080b1caa38a09d411542a0da4a92b66336c0972d260dba491ab7512009d6281f1c229b237c73db9ff1fbadabd986f2d0b471970ea4dfee8e506e0b7664562dc7a70a6b6571ffd85d1abe2ddf165a3525551ee818d683a306f3001c6dbb71991132d72f7d3d4b7b1d9b620455a75c80337be27fc06086453a4a0566d7ab17e302753e8390dcbfb0fe0ec884578ba13cec0678266c8d13678734d685d8403de5bef7f26e877c97e7d4e99f0bccec704347da2d8d12aa49b2723133fa40e2b97af7d4ff22c2ff709a45ba5e1c7d8a6491a52649dac6cbed2e70c7aca082ed7d9695498b22b99e9ca3cfe8daab5211a4cdd50e2508dc139253294433bf40689df56ab18019744a82099a6bd22f3203757b73266206b364b8d01e478bb81032ffdd361c6cfd375b28e1041d60f990b74dd3f9f4469f7dbfb849a59ff6f72cd00dcdea051521f5d1be05d75760e961530fb13163bda3ef9ad0826c7a0f89346173468d1570b0c4f34ad2c07dab617a2578a3bd2c0a76b1936b0963b1246223ef3359018e21cf2a0ece5517033e5a285bd29121c34d745b5239b99db4e2340b3c70d97b70bf6245252db9372b3026946861b143e3922b1969417dc67a5a7e1cdfc0a06f842b7c10c478b064be56aa3e76a098e853a15940f7b37438e08a6df32b0e456c1ce76b243599aa0302ce9e3b8b49156c1cacc641861d8a4830d48fad44e04827c32d048f834d189a0634583f314c265ed1037385a9f3f17b4c39dae1c43aff1c497a6de619f8def2527aeb47ccf9e42d407258a7477f40fd3dfffddc264f477e9a9f8fc53e1f687943c69a04699b4f8eed10101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff025900ffffffff02d0dd0600000000002321027a46eb513588b01b37ea24303f4b628afd12cc20df789fede0921e43cad3e875ac74b701000000000017a9147d46a730d31f97b1930d3368a967c309bd4d136a8700000000").expect("Block bytes are in valid hex representation");
     pub static ref BLOCK_MAINNET_10_BYTES: Vec<u8> = <Vec<u8>>::from_hex("040000000caa280b9f75c09ec407a2026a4f578a9e1d12b4b560e381474772741f2c06004825e397cf36e2dc4d32c09924231940e14da73bdaf0d37a72d8f18a7002275f0000000000000000000000000000000000000000000000000000000000000000617b1358ffff071fa5556b2500000000000000000000000000000000000000000000000000000000fd400500778fe70985278d941565e13644e59de26059395818bcfdbe4ea2fcadf573b30269e7b576acb4db93af134dc74ff4f013158b6084633ed7084e3e2f19f0a220e589af2457cf4ff103d41114516c25759a8c825d087f853e8686bfa926f724c8b4481fe550b2cdf94a259412c28cb4a099b5d8b513fefaf1ce66607447681994029c3911814b964592b58aa660452dc9dc62474633c514619ee4ed4561a5b273d695a9dc5ffbf3da0352afb9f85815af5998154b39e3a4d96ac5575fcb0fbf0895cc95f256e7f2628b43ebd49d7baa37e9da123bb0cebaca84cd13c5f15c17759af18d973d88e32f593a62a44fac293812a82ea8c885e6f8835f81e603aedbfba0c4198bd5f59aaa515650babda3db61140cc7c29b6d329975ee7a738ba8a3bdd5cea18ffb1235738fe520ada885a4bb648202422e8e56657f40d059d8dbf50bdd66e1adab87ceb8c252fb51db1b26be05ff099ba0978db3c18a20c33a0b8e44c45188a5d2541d047ee195343fe04f77c3b1f7da72c60b1f5dde0924ff3a7b227ac3686d97857ebe9fb627bd350eb410b82a2844667d2b70b0514601c1dd0085553cb232069f550567cc1a72c2c430ff1e94d5add89f74d62d1cbeb581bb1428f94cda53e3ede73cf5d1dbfa5a67179644604f8b3b7ca1b455c59e48c6ca8ba3d5b9921d867da7b2479cb925f9f3fce8f8135165b5df7c7706e4a852fad55d13ab1020de4190e09f023affc9a42fbbfef1e795f743914c44c7a7bf242bd0a21f032d1cab9dea6a97588cda66d405e44b5fe2d680ddfcc83c47dec15f2cf55395018826f0c57ef649455b01480912db9a94b35f51bea3f2007162c83ef43afc5fba0d0c6f9509ecb23d76cb228237dbc82abcf67ee83f11435bfbcae2419bb125a4eabbf9dce2b7af97b7101fe36a5d70503325c3d2921bb911a480877286c9a800c031733b461d75368f833fb6b9241602df969bf930344ba54623745dd451b54efb35f7a18dd19b7b6a282da76988148f735d2a77441541732def1c30d0ce2ed4d4c3479c4963997ff3bc5dad69a1b7274e113700ea354b01ab6751ddced371985cff394c6f1949221dd436d8b34ac2a465b165d0c1310283b39fff1b7e1acc1ce6269b350f961a3275b742dbcd62538f50f3403c5f34f16ee36fa051c4a75aeba2e7dd7dbf0f65074c246cb071d1859fb0d1b8c1f25bea36b3de32ac471c77457a52c105ef88a603e43264cf1c4739fb3a133e7259f9732cafda26a4eea6f5f916770f5b10fe1b59ceffe0d5e775ddfa8352b0fbfab2378bda73f70d547b9dcd0e8009932391140ff28b26d4e676d06c349c93b9fda424cf7924c3603479a1d659833bb54817db3cf6ade12c95bf3d3377367fa9b336dc5a327e539566fca1ebefe7f1dd362e13df7b3dbf618f602203c8176b45a84819d0ba8537b0ae5222d85e1ee76d2048668413f99dc9ddcb829690d0f1b146cc2be8db20293173f5e481a9434233d72d2b4b19bba25321013c58158abc11ea9d79a0157fa5556b2614031b6dc20ed3eb335408d9991952c3805aab3dfac7d61abf322a971b62738ae5967fc79446c2bec7fa3f0f1e150517efdc857fd2e6470daa9284a6a0bc5a705d16277f3bb74cb70b185c59d8cfd43d95a51d310f910bccf60c0048b6d398da33e56011c897a34d05a744157e8b11a501ec010cc9396da6521f6434e3ca9797dbe9e21eb493c05a8cd833741ce5726efb5eee294fbc4b6851e22b7cdc62b177458af9cef6e57a7e808a54c16e11aabc23b997476700e386a6cdbc8dbf310d1fc9cf48fcb24e9d9dd679927056e4d64e24eaa5121d5d242aea07d29ea9c571e813c4aa257837014ad09e0c1e343f6d6b1dcff114df64e45cf6d05bc54c6f9a4d430101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff025a00ffffffff0220a10700000000002321027a46eb513588b01b37ea24303f4b628afd12cc20df789fede0921e43cad3e875ac48e801000000000017a9147d46a730d31f97b1930d3368a967c309bd4d136a8700000000").expect("Block bytes are in valid hex representation");
+
+    // Mainnet block 949496, after Heartwood, with a shielded coinbase output
+    pub static ref BLOCK_MAINNET_949496_BYTES: Vec<u8> = <Vec<u8>>::from_hex("04000000ddb4e9970a6d8e05d2ccc6883a1f26488ee9a2d4344b626eca75020100000000680b9265b33bb6a6ef07a2ee71a85e064a92e2951ae4fbe07434c4889e4f5297058a6b9e510b12d80a9a20a6d83d44c93ca0b4909b3b0197e7540b50544a5d4428aa455f2159021cda28d23d74000000000000000000000000002b0000000000000000000003d656fd400501a34f33094ee01f5ecd8372687525f8dda12cf01f112f13f48ecbac33b599e17d265d073067a2e4a6020c76c7ef379fa2419248919fc66d6d9126120db51719a64dc4ab07a6393625724209d0c97d5da38c97df1af2a1343907dac71d0a83741eeebd363b4d56d3f84b4243236e18e64dbd1685d760ca489e70451ffe2e25f8dfc3b86b1381786ca70039441836e0785790824030f2d3102f60dd9cfef78e0cf25c0f6fbabd2ef00291d43fd298d497228af0f993a45804af6bd53e5f24090da9bbe058b920aa3677cf6dac725bcddfd9bb091624bc43e86df545d0aa3162f6061aaba99f4c2442aecd562cd4618bc3983a852af1b60714f13ea928034ec3673dc6af88ac8f2275a23d79593e86365fa80a10f44b5b4d805b2a6082a733e9c02539a8ee03af04a950cc8aab122994d0f0b25007e32c3d3bee0d61107a6f609dd1afba974a84faa4bcc10658869af7a403431bb97e5fc1512fd8d03eb5e2e12e111c5cd942417a7770b49176939c0e96538f4ecc466da8ffbf900cce64e336f60777c708f6a6c058be81ec5e5f65091f6fd6067cf34867b94ab3ca377b94d12afaaf3a7b05a9f4f09e8c6c3bd8aec1a0b4607a7a49379cd8ca32f1870a1e4f50d6da070462fdd9d4b128ef1c257c0f8d2dee456eefa7ba8087e3104dba0b74eb1dfd9e3eca76ef0258276ff2101404b3abaadd9f9b14d4970691ff2bc1d799e3289551c34cedebaf0d207f48683f1286f51fd26878989055a5d2715675bc2caf9da415a611a45685b1ed7981a51d2243b8468275f7e96e322b656d95d8d77db2d248b365d40c26c1467731170d98ffb7e690a4bbad47f4be65e85e594a194ab5c969e734023be200071572577ba6e1362dfcf09814df1df3865aa5de90e30c699404de49115f066d5f9619297e07b1bbccb8fc85d0f2bbe14d11695a86155f9501e9a0b93394c9bff5e5318e620efc98f2623e0e1d058acf6034653a49f15271e495a0b9b92d96bc59d818ad8b4bd55ff5eb54e5c2714c73a0c28f7256db2627ddb2a61090a43dc28a47bef7f5a14abcef7a3c10284281d22b6ef835c7c714954b6cb53e516cde79a442bb3b92aa9b1913419018b305cd97271e645e419a3b648391561d2fb5a8e943e02467017d03d37002793dc75c902365cae766ff2553e755e9a970d26f76950231ddafcb0bc8b10314f0798227eff08fc2d3a0dc0956e590bb9273abedf820ff159b28a132a23167383f1b73d2479a5011e2a6b68bda4accba3bfef6588f501314db9b9e60b9f266c6b3d67a38cad3a419e5000590c68b570311b361d3226840cb783f45097e97625eed4ec69f998702fb5788f1c7cd025ee59878d5be1167e90b7f476a2a808c22bbe71e1568e6bf6e308a17df2d443fb4877dd0c6460ccf6cf5ce44a4ff79b1036e6e40fd3a8ab5eda3035f01e31138decab69e8606f00b767543394f762602086768f4c50d8a1570c9093ce41eb6ac9c03c9d871f8d31a52a9c0309e81a43fee85ff552cb843b227174156d001fe110097fed30cbdf214aa43eb5dab7c95d904e8c28ab0953d72d525825572cd102f8fc03894436f711a12186efe0ace17796bec9a9155b5de3d56f280fcfb21da2d9fcc1c180556390e8af4fb0059a84be7de1c32c2dbd67e150b69ef44c8afbe5d816f72e3895aeb01489f4dc73b0d534337bd0c5565eb06b7aaf5f4b226617d9a253a186d77c60831c075b373e29ab89a5ecb0a6f5e251922ae718c20eff1cfbb63b3489e639b05d51bdf84740d335d92bc378771d12fc2d63677cf28f986fcea93293f4dabf3e0ce05bcd73737cbfe03aa8f3e17ac001509d3d2e30db7e3f55767263cc56a86701cf97c199307d686efad962570fffaae5a7dc79ac74a5b20fc040400008085202f89010000000000000000000000000000000000000000000000000000000000000000ffffffff0503f87c0e00ffffffff01405973070000000017a914358d5a7f5e2a687eeba360bb473f3c38890674f3870000000000000000b95132e2ffffffff0001f9e9a64065c43fbc60c602dc3b58cdecbbb49ad43a3dd9ae49b48a61c493545e0ba8ec2cb4071d2a8904aa768e5bd9d878be5cf6e172e7c85138731f36f14546dfd77cabcfeece274285111a796b2334fe7a924240327705bb9215c842866303599b366203fa9ca7232f34be1fdc1778b919350976f0b9cb05ed1653b284060cdba7d093da43e19c1e725b0e21de993f3ad8f84194c4e58b128109673fdf9c4d8220f32f22ee17a4616c128b41ff2cb47bcc603b5200d0fa77ce8df9356fa99864ba26f45c6d27f9c4c35cb143293348eb6d9f650be7dc23a78b9682d72cbc0ad42de4f6f81101eb8a8a3eaea1699f7d81c7cee82c76c33f7161a97f6327a8e4befe2cde0ddff7be8373c7609092ad360c0c756863ff4136e0a4d66d0ac511de63e8b9bfd8810a26733df9a34c0e4509b60d1ab3e428dc3a31760a1c316c26610893f92ce6e23c6796aacd4b0c49ab4b151dbec0fcd857a1d0a4d257a4e5f7d9034db2382a51e02df810379e7b9f82d92f34a5dcac5cd1f40f04c9c3923b7fb7d0eb3ae8c1ed6349b772ba79979c9c0a6fb6d88784c6244a50f88ee53974dffae55641021ddfa93a147dbe26c6e3769b5f16a5c3dee1e437aebd0b333640aa9f689a57628d84608461fd749b4f154776d54c0d79585ea34d2423ce3eb110e8c521fb77ec40f1c5ae6dbf6f463ed1cde946c6a8e528731104b895dc65dc0abe0485df3a2136a7c8ac76807f3e4dd4c968bf11d6de2e5d1d0ea40b5f2e8e3b35b5945e28c08ecb68b5146fe7dcd047692e7726a3b0e099925123e5952df5a8a6ba69429f38d7e89a7a95ae0f9525d09d7703963b9b4d2fd9a523bb46cb42779253c86cc1073cf9251ddd97386b871566dff7ec5804c7b8220277341150600ee99e5a04831bec0e1dcc1aca4189226e20292920589a1192e6e10962a27344b15625ff76da58d39536dfef75708aa4ab4a4bb1ceed6c87f297c6891e5ddc3c45379d6f991459b28bee977db2ea9cdf1e386648c339a118f0d891bb7fd5423074cceb2da4b1a248754fa2f304c8c77db4e01956e6742a9115cf83db3f2c5b0d86eb8e3784deb51e888f861249a05554f568f81d314654abf3a649526ac13f21de1a300d55f4699455b9829b81b3cc24a1ceb500a16c1b5912d5defef285604e4f489ba18ca3893525869e4102f0c5ca2855a39ba696ca14864896595e53ba498295f8cdb04235664b1268b19ec98f32f0cef611005aae828ad35c2415ffd6abca16b741a4971f926e839f329e71be02c98c5c2d99bf8f7b2b9718e8f07c6754f1b169057fc69618487ed0b419878d2e5ca5f7f6d94acc00e3ea15bc141b92caade6c8b19c1cf94e5de49f0b301c05a1930bf0d09a2bc1cd643fd01d9e081998ad38eb7ee8a8f5c9fda9254597ec507b4f08480669a380060400008085202f8901fa4fc5d47bb8b72d75a958621c4a84504bbe081ac80176c2b1e89457f9298f09010000006b48304502210089ce9f77b6eab970946fa1924b611393efb6f2b816ad5f94431c54703ac251db02207ec2fe8f67def1e8a3b57bfce71f6e7f09e71cdb9de3038ff8acff17aa45b85e012102169ef254a968648c37c5dcb9c21d2fdf20a562e2868029694740434080c1887affffffff0220920000000000001976a9146bdb1eeafb9123d1f9cc8866bbfc18918aa2266a88acf7e5801f000000001976a91462e92a2bb72dadef2d9f4175081fa6e3ad69e74488ac00000000207d0e0000000000000000000000000400008085202f8901456585068c62053ba45b79595ce9251c8669f3b89acfabeee90de34329e00056010000006b483045022100e310bf1c0f2f38eefafb0ca52aa779ab8a5f2c62400a437f5aa8ce76908e2007022016834e91f89a33e50b689fad50ccfccb25badd006c6d2f8884a7e59f5ded2ebb01210224c5d6e287b4f193fe48e874f0ce3ce7e0659c68effc6773502ee726b68a6a56ffffffff0292060c00000000001976a91477572fcc9f7438cb37052fc4cdfdf1d35e27502788ac29a30d02000000001976a9144554bb3b9c3653c588d9136cdcfe254260be742a88ac00000000207d0e0000000000000000000000000400008085202f89013a36d0f8dcb0bf014fb55620d4c662e98844120ca4febe64b59632aa925b2685010000006a473044022020461e25eaf01b87b5042752e3882d981386c5c59569e2b5f64769a2369a3c9f02205649e78045191a95be83d35bc888d6a0a6c035e4d73f640625b3e5fcdd95158701210224c5d6e287b4f193fe48e874f0ce3ce7e0659c68effc6773502ee726b68a6a56ffffffff02b63c0000000000001976a914d4c85652bd2adacb8e668da6d6b2f2501353e7b888accd450d02000000001976a9144554bb3b9c3653c588d9136cdcfe254260be742a88ac00000000207d0e000000000000000000000000").expect("Block bytes are in valid hex representation");
+    // Testnet block 1101629, after Canopy, with a shielded coinbase output
+    pub static ref BLOCK_TESTNET_1101629_BYTES: Vec<u8> = <Vec<u8>>::from_hex("04000000be68d8a81eba8e8ee38403b2680fb6732d0432d98f5588759ef1fd6bff100000c2251f39388b9627050e47cd85efdd1e4564499f6d56a9ff67a1fea1e135d96c7225c82d08796d6a48b19fa24a9b62641daf2bd4b93abc33e9de4fee89e0565d6e9d725fa1eb001f6b00000000000000400010480000000000000300000000000000000000000000fd40050030dccd2ece043b93e290c40eb4ab845040846518122818c130d2a7355d81b276de93cefde8acf98a3b0a679908787c7211fb5314ea0578560d4279b9e9423677767d2d57f9dd77087511f245214ea9b49cf1f60061d5e02681ed04e685c8bc31fb4d9236a497992a0cbefb1eead727978cbc811ad4193229035775ef6505d12e50394d70ce73d6e0cbb38f3b583ef1c6ac210de6fccd41c68e0ac52fd4120a30d1a207e77b0b7e060a9c2885ca8264b950b624a44315de559515c6146a0b2d6a46aa6129fe5238aeb5486b8bc1665ee7c732b8b4bdf94e3a2985a5c43beba97b9e918a7ac1523e723c2e7caa8545d9ac93f41570313d87bacc72b70a3512b8b99c31d4eaef11e30ad855b5636775f22420804451ec138381b935d505c0c27567559ebbdfe818e669f6e68ba540db4ad74602ccf599e59d54e1052299d6c8e3db25119aeae5ee8f642361ce1cd78da300712c2edb8ab1b4fb35c05602b4bc5d5332984bd8063ebf0b5e0f3d3ab499f9ec075116ab336efcd481121b6aa57ed77963ba06421f30359a1144aa56958055840bcaf6a4dc97c346475530e17b7b82b21c7673038664b6334cf49f017de4feaef3cda5d1ab93ccb045e3ee67ae1cf5cb5240c4fd0b7836614a428e1672144b59e5cfb374e3e82ca3be715069190f4ccfac2434e59c4fa3539552e36286eaeaccc16a4ffa38ad0200d475b851289ee1f181507379663719692b6ce4fe17cea1fe2e107e4ef4e99697cacb0e02abf81cdb9b06145c9ddaccb4c955ac10ec39593205bf67d81fd716f34e37c389ac3ab89fe40acb5d684ea6f6bc451f047a2c72a2561c354a48c47ce7b1b9797c945e47522fdc1f7764cea8ad39a555f5365e80b1b2a9ef7b230bf48957d40c5f2592178178b8d994c31d6739a26c1aee5c3d6d224ba75947f639e4f0d462081b18e5d200964d65b243bcabb72f47424bca57f26f3e7488ce259ebe0104ed4d01806a59d732eb8cb31443bb8b9b14527b2879167086cc84c6600e645693009f5d7a5115726dfea70b29b163ae42bc8d7510bb7f6cbd13c70b999becbcd3c2e2c5db132e807bf81926475414082475ee20f40c3497f986a2c7c678a0a16ec67abbb42c503d4bee14ec5f7f0e150b9a330efe608bfa7e5a5c8f5687a1aabd0bb5ab36941a60e0f5d1b89408c60403eb468c24da5d72e0625365939fb504a80f74bc0563763984d31a0b6cbc0161c57344e881b5db8f5f0aceb625fd9309b5a80466aa88f4509afcb1dafca03182edb3f6e7cb9fbf7ce3e45f7d4f0f502ddfb88a05b86c8a65ca228d4a06440078b30c8a82c3dd3b4825adb1328396fdeb945b04a326d807c1ae2d32e80d1f44c448ab608edb06b5c3627a3bad01ec951c015923fa9a360099585dac0fb3d6c93da9a60b6e99acda0409a4806442d90b4579d402fdd2dd4aee2b3c34ee1561ba0a650b516ee0800308d73704255df131df340f908c8123cc71a09702d3a2c3a8c85e7abc9f0768107726f3d5524a28a748a82e12cf007e44c47aedf9042925dab1812bd8ca64b358cbd207c0fb4defa44c0fc60a643e0f9a26ae69033f76f056314bfc59105807af643b17ce3c0f639a320513bf5a371f701d7d240eef3153d8c66b6e8d67e3d4196c0a957273faffc60e96e491a1496eecc85f799ff1ff63f6b6ded9e0b771ae03c2445cba5732e32979f1e1a4d2c7753ace2b10cd59321fda5371e0d292f95bb0bce1cc038ef8121f94f28ad2eecb6bd7e88912296e50d6e7b6d9da8212c981f2aaa42a6f2c5571b71e43bea6d21fbf4b4b45e6c2cff7e2a6513e86667c227062eda3fb36391d18817ac7b8870415ab27c6b2cb3af4f6ec6cfd95d129bbabe32393b5318f26ac5298e98b7b6eae7bb477010400008085202f89010000000000000000000000000000000000000000000000000000000000000000ffffffff39033dcf1000046e9d725f0de8eb49bd7d8941b6353ae07773f113a0ced89d940a3126f2ec3d3208817eb82f747474746565656573737374742fffffffff0370929b020000000017a91402db6bf7d524268b04edbb986ca4b3ba3528045f8750d6dc010000000017a9140c0bcca02f3cba01a5d7423ac3903d40586399eb8780f0fa020000000017a91471e1df05024288a00802de81e08c437859586c87870000000000000000009b32e2ffffffff00016f741a5c00b141efe6f39df68a8b0c683b293c24aa822a6c64ac1bf36125420722f582f7be2ec9a7ff61c4dd1e31f064f85546aeb01d2f35b3d1b61d277205093199cf83d2adc1a83b1f8b63de3e63d800fa6be4b81502622cacc6e42e9383a89a2e021a6cd3bdf554af6271a2ee2a993f56afe27c2f4cb8f053563fb6e9e4ea829890830851231dce5c66dd2cfc5210686311eea91cc1d74c24ac444633c348bc6e4d5ec5e4e290350b5206e71407fd9719d5c6ae1bc338e6450c43a12e155b8e63c7cdb03db81689bdcb847a84ffdc2eb53738b3e8d2f2f362e9b8cc7acdbc27d15dfc1af294f69822fe6d5de34bcd39fd43b19ee50889cfe0770d7d36da1ef483fdddee5163d89832ebbbbd2be7ba4ec2b016c7539e0eb70362b56b6257ddf750d9b5e092775379a9b59f7aba470bee706c5adb00450d9009ecb93632cf0ce2044fb0d475c09dbc25a57a1f184bfbc5e2036de89147c4b7620012fd9a79bd61b52a58a58c33392b9517f8e158ab010e540e93f414b48c1b3a65f9df5ad696555f9d0e4c132fdcbf83f98da62e618eae85525adaabdf8c4113bb45226fe47964a7102d38f47c5db7535ddb1ab2414572411f46dd42fab8c37cb6497d97be587f2bb7acbd23338110f0db68df8afc11318b14749c25d1885c9233a511e0821cfc06e8ca59046be1eeb781793192c13e8a79ef5b148f4392fa01fb77afeafa9f3394ed9105f1dc3f83d4ba5bfe67f7df002ffd1bc9824e3d099ff87d8a87a9847354b52c10bd662dc4858092e1859548356bd5937e21bcbf3a1cc201c468900f9743e7d0a25863fd6fa4fcd36c6ad69b92478778e44eaf5820698eb991338415aa183d2605e3ab17c8146d5c697f7e1d19a98fb2caf897e7c1759a6b3012730168dde49fdcf8c1e053366db7f5d00c752cd26129084e647074668f2c419ec027a6e1b609709196302de52bb8a61b4adc224fe6517b515e49c656df403ee8ef41b232cdc796d4e6d023ff79cbbb5f2319817f9e89f4b39cdd84ec0ed23574b865401d0b20402b777998a2055f7829f13fc06b0bf1878d27791e223d60e32487565cf9697856c92360e2d8584b57a884f9fa2299684a35316e0cc1f28b70591f2673a742a2a8ce66792d7add4717ac42387901d6a40820a0f07e0c327c77aff1506fc5b3127b387c4b2910c60bdae60394b70d569813e1e1066bde2142a379b52cf1c31bca53d7f9cb0c7c4c912049d617dc0fa41dcb8789b9576a77f5532599ae31fb91e6b4fad2a9380654c0f65e930f2e36277355c7cf4940f955e242a543544cf04e6bbb21b371510fd161dc18d499ee69639f0077ea8fbfe3fa79787317eadcc1524240eb3911ee687c8dc43c4de1d1fb8eccd9df4e7005c82e8b2cc06fff6cac746631e0090fd894447d51c231cdd49cd1ed06").expect("Block bytes are in valid hex representation");
+}
+
+lazy_static! {
+    /// Mainnet blocks, indexed by height.
+    ///
+    /// Heights 0-10 are before Overwinter, so they use BCTV14 JoinSplit
+    /// proofs. Heights 415000 and 434873 are after Sapling activation.
+    ///
+    /// We don't have test vectors for Blossom or later network upgrades yet.
+    pub static ref MAINNET_BLOCKS: BTreeMap<u32, &'static [u8]> = [
+        (0, BLOCK_MAINNET_GENESIS_BYTES.as_slice()),
+        (1, BLOCK_MAINNET_1_BYTES.as_slice()),
+        (2, BLOCK_MAINNET_2_BYTES.as_slice()),
+        (3, BLOCK_MAINNET_3_BYTES.as_slice()),
+        (4, BLOCK_MAINNET_4_BYTES.as_slice()),
+        (5, BLOCK_MAINNET_5_BYTES.as_slice()),
+        (6, BLOCK_MAINNET_6_BYTES.as_slice()),
+        (7, BLOCK_MAINNET_7_BYTES.as_slice()),
+        (8, BLOCK_MAINNET_8_BYTES.as_slice()),
+        (9, BLOCK_MAINNET_9_BYTES.as_slice()),
+        (10, BLOCK_MAINNET_10_BYTES.as_slice()),
+        (415000, BLOCK_MAINNET_415000_BYTES.as_slice()),
+        (434873, BLOCK_MAINNET_434873_BYTES.as_slice()),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+
+    /// The expected hashes of the blocks in `MAINNET_BLOCKS`, indexed by height.
+    ///
+    /// Hashes are hex-encoded in display order, which is the reverse of the
+    /// internal byte order. This is the order used by zcashd and block
+    /// explorers.
+    pub static ref MAINNET_BLOCK_HASHES: BTreeMap<u32, &'static str> = [
+        (0, "00040fe8ec8471911baa1db1266ea15dd06b4a8a5c453883c000b031973dce08"),
+        (1, "0007bc227e1c57a4a70e237cad00e7b7ce565155ab49166bc57397a26d339283"),
+        (2, "0002a26c902619fc964443264feb16f1e3e2d71322fc53dcb81cc5d797e273ed"),
+        (3, "0006f6c4e5bb8e97d65ac9f3fc4bcd8770a6350f9eed0943d84ce9ca65eac67f"),
+        (4, "0002aa8ec32743272e243ce62217b4ac64549a95c88a675661cb9bafc8009813"),
+        (5, "0001c6319dcb2ad2432648547515c95ebab3b080392b9ea0e269f92eeefcb10a"),
+        (6, "0000271abcf616bf342e37ec5eadb957358cafe5e0dcba496634e379c9c692e3"),
+        (7, "0004b805ae8fd9acac30d9497ff620bbf5428ade1d533d5aa25e1aaea7e25a04"),
+        (8, "000677dabe335c798c49556edb29aa00d18c55d9dfd212ee5b332f45bc0e2b01"),
+        (9, "00062c1f7472474781e360b5b4121d9e8a574f6a02a207c49ec0759f0b28aa0c"),
+        (10, "00074c46a4aa8172df8ae2ad1848a2e084e1b6989b7d9e6132adc938bf835b36"),
+        (415000, "0000000001ab37793ce771262b2ffa082519aa3fe891250a1adb43baaf856168"),
+        (434873, "00000000020081c4c720666cce3a674f355c562db7021be19fbaea4d1ffc1e82"),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+
+    /// The expected transaction merkle roots of the blocks in
+    /// `MAINNET_BLOCKS`, indexed by height.
+    ///
+    /// Roots are hex-encoded in display order.
+    pub static ref MAINNET_MERKLE_ROOTS: BTreeMap<u32, &'static str> = [
+        (0, "c4eaa58879081de3c24a7b117ed2b28300e7ec4c4c1dff1d3f1268b7857a4ddb"),
+        (1, "851bf6fbf7a976327817c738c489d7fa657752445430922d94c983c0b9ed4609"),
+        (2, "8974d08d1c5f9c860d8b629d582a56659a4a1dcb2b5f98a25a5afcc2a784b0f4"),
+        (3, "4e3670eae21d0c7225716abacb3a95c203a75d7601594afddb07d175c3babe83"),
+        (4, "781bb9b0168831054c1ed3afd8bf153f3fc848e102cea86977545c86f6e2446e"),
+        (5, "9a4adaf3953818eb1634407032db0e00ef2441c49c1364161411d0743ec1a939"),
+        (6, "1dd2064423542cfee7ca4ec565acb37e1ad217035b6119668352165b20006fe1"),
+        (7, "26b813b0743f015e6b41519d4ca78ee7b5c76cbac5c8b8ac0ec6b9cf88b8328d"),
+        (8, "7a772e6fe8e6bbfc34f0a61ba472d9f9dfe3a5539adfdac2f3c6ae659d44de03"),
+        (9, "ff24ab621bf3e46ad195b47dca7f11fb376f53e23b2e14a6e9b6a1907cc27cdc"),
+        (10, "5f2702708af1d8727ad3f0da3ba74de14019232499c0324ddce236cf97e32548"),
+        (415000, "ce93a30a82e4fb43c7a45afcbd97970473a56872ad6ac3bec995fa4bbc733066"),
+        (434873, "b0e371791b09c276335afa6178d51b943bc5f899bf90c3cb1968426a4ff7f84c"),
+    ]
+    .iter()
+    .cloned()
+    .collect();
 }
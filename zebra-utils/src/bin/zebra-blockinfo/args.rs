@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// The height or hash of the block to fetch using `zcash-cli getblock`
+    #[structopt(required_unless = "file")]
+    pub block: Option<String>,
+
+    /// Read the hex-encoded block from this file, instead of fetching it
+    #[structopt(short, long, parse(from_os_str))]
+    pub file: Option<PathBuf>,
+
+    /// Interpret the block using the Testnet consensus parameters
+    #[structopt(short, long)]
+    pub testnet: bool,
+
+    /// Path to zcash-cli command
+    #[structopt(default_value = "zcash-cli", short, long)]
+    pub cli: String,
+
+    /// Passthrough args for `zcash-cli`
+    #[structopt(last = true)]
+    pub zcli_args: Vec<String>,
+}
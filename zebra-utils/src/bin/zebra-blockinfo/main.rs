@@ -0,0 +1,235 @@
+//! Prints the decoded contents of a Zcash block, and the consensus values
+//! that Zebra calculates for it.
+//!
+//! The block is read from a file containing the hex-encoded block, or
+//! fetched from zcashd using `zcash-cli getblock`.
+//!
+//! For usage please refer to the program help: `zebra-blockinfo --help`
+//!
+//! This is a debugging tool: it is useful for finding the cause of a
+//! consensus mismatch between Zebra and zcashd. It prints the checks that
+//! fail, rather than stopping at the first error.
+
+use color_eyre::eyre::{eyre, Result};
+use std::process::Command;
+use structopt::StructOpt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use zebra_chain::{
+    block::Block,
+    merkle_tree::MerkleTreeRootHash,
+    serialization::ZcashDeserialize,
+    transaction::{Transaction, TransactionHash},
+    types::BlockHeight,
+    Network,
+};
+use zebra_consensus::parameters::{self, FundingStreamReceiver, NetworkUpgrade};
+
+mod args;
+
+/// Fetches the hex-encoded block in `args` using `zcash-cli getblock`.
+fn fetch_block_hex(args: &args::Args) -> Result<String> {
+    let block = args
+        .block
+        .as_ref()
+        .ok_or_else(|| eyre!("a block height or hash is required"))?;
+
+    let mut cmd = Command::new(&args.cli);
+    cmd.args(&args.zcli_args);
+    // Verbosity 0 returns the serialized block
+    cmd.args(&["getblock", block, "0"]);
+
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} getblock failed: {}",
+            args.cli,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+fn main() -> Result<()> {
+    init_tracing();
+
+    color_eyre::install()?;
+
+    let args = args::Args::from_args();
+    let network = if args.testnet {
+        Network::Testnet
+    } else {
+        Network::Mainnet
+    };
+
+    let block_hex = match &args.file {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => fetch_block_hex(&args)?,
+    };
+    let bytes = hex::decode(block_hex.trim())?;
+    let block = Block::zcash_deserialize(&bytes[..])?;
+
+    print_header(&block, bytes.len(), network);
+    match block.coinbase_height() {
+        Some(height) => print_consensus_values(&block, height, network),
+        None => println!("\nno coinbase height: skipping height-dependent consensus values"),
+    }
+    print_transactions(&block);
+
+    Ok(())
+}
+
+/// Prints the header of `block`, which is `size` bytes long, and the header
+/// checks that don't depend on the chain state.
+fn print_header(block: &Block, size: usize, network: Network) {
+    let header = &block.header;
+
+    println!("block {}", block.hash());
+    println!("  size: {} bytes", size);
+    match block.coinbase_height() {
+        Some(height) => println!("  height: {}", height),
+        None => println!("  height: unknown"),
+    }
+
+    println!("\nheader");
+    println!("  version: {}", header.version);
+    println!("  previous block: {}", header.previous_block_hash);
+    println!("  merkle root: {:?}", header.merkle_root_hash);
+    println!(
+        "  commitment bytes: {}",
+        hex::encode(header.commitment_bytes)
+    );
+    println!("  time: {} ({})", header.time, header.time.timestamp());
+    println!(
+        "  bits: {:#010x} ({:?})",
+        header.bits,
+        header.difficulty_threshold()
+    );
+    println!("  nonce: {}", hex::encode(header.nonce));
+
+    println!("\nheader checks");
+    let merkle_root: MerkleTreeRootHash = block
+        .transactions
+        .iter()
+        .map(|tx| TransactionHash::from(&**tx))
+        .collect();
+    if merkle_root == header.merkle_root_hash {
+        println!("  merkle root: ok");
+    } else {
+        println!("  merkle root: MISMATCH, calculated {:?}", merkle_root);
+    }
+    match header.is_difficulty_threshold_met(network) {
+        Ok(()) => println!("  difficulty: ok"),
+        Err(e) => println!("  difficulty: FAILED, {}", e),
+    }
+    match header.is_equihash_solution_valid() {
+        Ok(()) => println!("  equihash solution: ok"),
+        Err(e) => println!("  equihash solution: FAILED, {}", e),
+    }
+}
+
+/// Prints the consensus values for `block`, at `height` on `network`.
+fn print_consensus_values(block: &Block, height: BlockHeight, network: Network) {
+    println!("\nconsensus values ({:?})", network);
+    println!(
+        "  network upgrade: {:?}",
+        NetworkUpgrade::current(network, height)
+    );
+    match parameters::current_branch_id(network, height) {
+        Some(branch_id) => println!("  consensus branch id: {}", branch_id),
+        None => println!("  consensus branch id: none"),
+    }
+    match parameters::block_commitment(network, height, block.header.commitment_bytes) {
+        Ok(commitment) => println!("  block commitment: {:?}", commitment),
+        Err(e) => println!("  block commitment: INVALID, {}", e),
+    }
+
+    println!(
+        "  block subsidy: {}",
+        parameters::block_subsidy(network, height)
+    );
+    println!(
+        "  founders' reward: {}",
+        parameters::founders_reward(network, height)
+    );
+    let funding_streams = parameters::funding_stream_values(network, height);
+    for receiver in FundingStreamReceiver::ALL.iter() {
+        if let Some(value) = funding_streams.get(receiver) {
+            match receiver.address_for(height, network) {
+                Some(address) => {
+                    println!("  {:?} funding stream: {} to {}", receiver, value, address)
+                }
+                None => println!(
+                    "  {:?} funding stream: {} to unknown address",
+                    receiver, value
+                ),
+            }
+        }
+    }
+    println!(
+        "  deferred pool contribution: {}",
+        parameters::deferred_pool_contribution(network, height)
+    );
+    println!(
+        "  miner subsidy: {}",
+        parameters::miner_subsidy(network, height)
+    );
+
+    if let Some(coinbase) = block.transactions.get(0) {
+        match coinbase.transparent_value_out() {
+            Ok(value) => println!("  coinbase transparent value: {}", value),
+            Err(e) => println!("  coinbase transparent value: INVALID, {}", e),
+        }
+    }
+}
+
+/// Prints a summary of each transaction in `block`.
+fn print_transactions(block: &Block) {
+    println!("\ntransactions: {}", block.transactions.len());
+
+    for (index, tx) in block.transactions.iter().enumerate() {
+        println!("\n  [{}] {}", index, TransactionHash::from(&**tx));
+        print_transaction(tx);
+    }
+}
+
+/// Prints a summary of `tx`.
+fn print_transaction(tx: &Transaction) {
+    println!("    version: {}", tx.version());
+    if tx.is_coinbase() {
+        println!("    coinbase: true");
+    }
+    println!("    lock time: {:?}", tx.lock_time());
+    if let Some(expiry_height) = tx.expiry_height() {
+        println!("    expiry height: {}", expiry_height);
+    }
+
+    println!(
+        "    transparent: {} inputs, {} outputs",
+        tx.inputs().count(),
+        tx.outputs().count()
+    );
+    match tx.transparent_value_out() {
+        Ok(value) => println!("    transparent value out: {}", value),
+        Err(e) => println!("    transparent value out: INVALID, {}", e),
+    }
+
+    if tx.joinsplit_count() > 0 {
+        println!("    sprout: {} joinsplits", tx.joinsplit_count());
+    }
+    if let Some(value_balance) = tx.sapling_value_balance() {
+        println!(
+            "    sapling: {} spends, {} outputs, value balance {}",
+            tx.sapling_spends().count(),
+            tx.sapling_outputs().count(),
+            value_balance
+        );
+    }
+}
+
+fn init_tracing() {
+    tracing_subscriber::Registry::default()
+        .with(tracing_error::ErrorLayer::default())
+        .init();
+}
@@ -84,12 +84,7 @@ fn main() -> Result<()> {
         let v: Value = serde_json::from_str(block_raw.trim())?;
 
         // get the values we are interested in
-        let hash: BlockHeaderHash = v["hash"]
-            .as_str()
-            .map(zebra_chain::utils::byte_reverse_hex)
-            .unwrap()
-            .parse()
-            .unwrap();
+        let hash: BlockHeaderHash = v["hash"].as_str().unwrap().parse().unwrap();
         let height = BlockHeight(v["height"].as_u64().unwrap() as u32);
         assert!(height <= BlockHeight::MAX);
         assert_eq!(x, height.0);
@@ -101,7 +96,9 @@ fn main() -> Result<()> {
 
         // compute
         cumulative_bytes += size;
-        height_gap = BlockHeight(height_gap.0 + 1);
+        height_gap = height_gap
+            .next()
+            .expect("checkpoint gaps are less than the maximum height");
 
         // check if checkpoint
         if height == BlockHeight(0)
@@ -109,7 +106,7 @@ fn main() -> Result<()> {
             || height_gap.0 >= zebra_consensus::checkpoint::MAX_CHECKPOINT_HEIGHT_GAP as u32
         {
             // print to output
-            println!("{} {}", height.0, &hex::encode(hash.0),);
+            println!("{} {}", height, hash);
 
             // reset counters
             cumulative_bytes = 0;
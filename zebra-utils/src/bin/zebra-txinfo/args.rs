@@ -0,0 +1,61 @@
+use std::{num::ParseIntError, path::PathBuf};
+
+use structopt::StructOpt;
+
+/// Parses a `u32` in decimal, or in hex with a `0x` prefix.
+fn parse_u32(s: &str) -> Result<u32, ParseIntError> {
+    if s.starts_with("0x") {
+        u32::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse()
+    }
+}
+
+/// Parses a `u32` in hex, with an optional `0x` prefix.
+fn parse_hex_u32(s: &str) -> Result<u32, ParseIntError> {
+    u32::from_str_radix(s.trim_start_matches("0x"), 16)
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// The hex-encoded transaction
+    #[structopt(required_unless = "file")]
+    pub transaction: Option<String>,
+
+    /// Read the hex-encoded transaction from this file
+    #[structopt(short, long, parse(from_os_str))]
+    pub file: Option<PathBuf>,
+
+    /// Compute the signature hash for this transaction
+    #[structopt(long)]
+    pub sighash: bool,
+
+    /// The consensus branch ID for the signature hash, in hex
+    #[structopt(long, parse(try_from_str = parse_hex_u32))]
+    pub branch_id: Option<u32>,
+
+    /// Use the consensus branch ID at this block height
+    #[structopt(long)]
+    pub height: Option<u32>,
+
+    /// Use the Testnet consensus branch IDs
+    #[structopt(short, long)]
+    pub testnet: bool,
+
+    /// The signature hash type, like 1 or 0x81
+    #[structopt(long, default_value = "1", parse(try_from_str = parse_u32))]
+    pub hash_type: u32,
+
+    /// The index of the transparent input to sign. If this is not set, the
+    /// signature hash is for the shielded parts of the transaction
+    #[structopt(long, requires_all = &["script-code", "value"])]
+    pub input: Option<usize>,
+
+    /// The hex-encoded script code of the output spent by the input
+    #[structopt(long)]
+    pub script_code: Option<String>,
+
+    /// The value of the output spent by the input, in zatoshis
+    #[structopt(long)]
+    pub value: Option<u64>,
+}
@@ -0,0 +1,122 @@
+//! Decodes a Zcash transaction, and optionally computes its signature hash.
+//!
+//! For usage please refer to the program help: `zebra-txinfo --help`
+//!
+//! This is a debugging tool for signature mismatches between a wallet and
+//! zcashd. It prints every field of the decoded transaction, and the
+//! signature hash that Zebra computes for an input and hash type.
+//!
+//! Zebra only computes signature hashes for V4 transactions, and doesn't
+//! parse V5 transactions yet.
+
+use std::convert::TryInto;
+
+use color_eyre::eyre::{eyre, Result};
+use structopt::StructOpt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use zebra_chain::{
+    parameters::ConsensusBranchId,
+    serialization::ZcashDeserialize,
+    transaction::{HashType, Transaction, TransactionHash},
+    types::{
+        amount::{Amount, NonNegative},
+        BlockHeight, Script,
+    },
+    Network,
+};
+use zebra_consensus::parameters;
+
+mod args;
+
+fn main() -> Result<()> {
+    init_tracing();
+
+    color_eyre::install()?;
+
+    let args = args::Args::from_args();
+
+    let tx_hex = match (&args.file, &args.transaction) {
+        (Some(path), _) => std::fs::read_to_string(path)?,
+        (None, Some(tx_hex)) => tx_hex.clone(),
+        (None, None) => return Err(eyre!("a transaction or file is required")),
+    };
+    let bytes = hex::decode(tx_hex.trim())?;
+    let tx = Transaction::zcash_deserialize(&bytes[..])?;
+
+    println!("transaction {}", TransactionHash::from(&tx));
+    println!("  size: {} bytes", bytes.len());
+    println!("  version: {}", tx.version());
+    if let Some(version_group_id) = tx.version_group_id() {
+        println!("  version group id: {:#010x}", version_group_id);
+    }
+    println!("\n{:#?}", tx);
+
+    if args.sighash {
+        print_sighash(&tx, &args)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the signature hash of `tx`, for the input and hash type in `args`.
+fn print_sighash(tx: &Transaction, args: &args::Args) -> Result<()> {
+    let network = if args.testnet {
+        Network::Testnet
+    } else {
+        Network::Mainnet
+    };
+
+    let branch_id = match (args.branch_id, args.height) {
+        (Some(branch_id), _) => ConsensusBranchId::new(branch_id),
+        (None, Some(height)) => parameters::current_branch_id(network, BlockHeight(height))
+            .ok_or_else(|| eyre!("there is no consensus branch ID at height {}", height))?,
+        (None, None) => return Err(eyre!("--branch-id or --height is required for --sighash")),
+    };
+    let hash_type = HashType(args.hash_type);
+
+    let script_code = args
+        .script_code
+        .as_ref()
+        .map(|script_code| hex::decode(script_code.trim()).map(Script))
+        .transpose()?;
+    let input: Option<(usize, &Script, Amount<NonNegative>)> =
+        match (args.input, &script_code, args.value) {
+            (Some(index), Some(script_code), Some(value)) => {
+                let input_count = tx.inputs().count();
+                if index >= input_count {
+                    return Err(eyre!(
+                        "input {} is out of range: the transaction has {} transparent inputs",
+                        index,
+                        input_count
+                    ));
+                }
+                Some((index, script_code, value.try_into()?))
+            }
+            _ => None,
+        };
+
+    println!("\nsignature hash");
+    println!("  consensus branch id: {}", branch_id);
+    println!("  hash type: {:#04x}", hash_type.0);
+    match input {
+        Some((index, _, value)) => println!("  input: {} spending {} zatoshis", index, value),
+        None => println!("  input: none (shielded signature hash)"),
+    }
+
+    match tx.sighash(branch_id, hash_type, input) {
+        Some(sighash) => println!("  sighash: {}", hex::encode(sighash)),
+        None => println!(
+            "  sighash: not implemented for V{} transactions",
+            tx.version()
+        ),
+    }
+
+    Ok(())
+}
+
+fn init_tracing() {
+    tracing_subscriber::Registry::default()
+        .with(tracing_error::ErrorLayer::default())
+        .init();
+}
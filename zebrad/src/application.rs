@@ -6,8 +6,9 @@ use abscissa_core::{
     config,
     terminal::component::Terminal,
     trace::Tracing,
-    Application, Component, EntryPoint, FrameworkError, StandardPaths,
+    Application, Component, EntryPoint, FrameworkError, FrameworkErrorKind, StandardPaths,
 };
+use zebra_chain::{parameters::set_testnet_parameters, Network};
 
 /// Application state
 pub static APPLICATION: AppCell<ZebradApp> = AppCell::new();
@@ -126,6 +127,17 @@ impl Application for ZebradApp {
         config: Self::Cfg,
         command: &Self::Cmd,
     ) -> Result<(), FrameworkError> {
+        // Configure the network parameters before any component uses them
+        if let Some(parameters) = config.network.testnet_parameters.clone() {
+            if config.network.network != Network::Testnet {
+                return Err(FrameworkErrorKind::ConfigError
+                    .context("testnet_parameters can only be set on Testnet")
+                    .into());
+            }
+            set_testnet_parameters(parameters)
+                .map_err(|e| FrameworkErrorKind::ConfigError.context(e))?;
+        }
+
         // Configure components
         self.state.components.after_config(&config)?;
         self.config = Some(config);
@@ -1,16 +1,26 @@
 //! Zebrad Subcommands
 
 mod connect;
+mod copy_state;
+mod db_info;
+mod export_peers;
 mod generate;
+mod import_peers;
+mod replay;
 mod revhex;
+mod rpc_schema;
 mod seed;
 mod start;
+mod tip_height;
 mod version;
+mod windows_service;
 
 use self::ZebradCmd::*;
 use self::{
-    connect::ConnectCmd, generate::GenerateCmd, revhex::RevhexCmd, seed::SeedCmd, start::StartCmd,
-    version::VersionCmd,
+    connect::ConnectCmd, copy_state::CopyStateCmd, db_info::DbInfoCmd,
+    export_peers::ExportPeersCmd, generate::GenerateCmd, import_peers::ImportPeersCmd,
+    replay::ReplayCmd, revhex::RevhexCmd, rpc_schema::RpcSchemaCmd, seed::SeedCmd, start::StartCmd,
+    tip_height::TipHeightCmd, version::VersionCmd, windows_service::WindowsServiceCmd,
 };
 
 use crate::config::ZebradConfig;
@@ -34,14 +44,38 @@ pub enum ZebradCmd {
     #[options(help = "testing stub for dumping network messages")]
     Connect(ConnectCmd),
 
+    /// The `copy-state` subcommand
+    #[options(help = "copy the state cache into a new directory, checking each block")]
+    CopyState(CopyStateCmd),
+
+    /// The `db-info` subcommand
+    #[options(help = "print diagnostic information about the state cache")]
+    DbInfo(DbInfoCmd),
+
+    /// The `export-peers` subcommand
+    #[options(help = "export the peer address cache to a file")]
+    ExportPeers(ExportPeersCmd),
+
     /// The `help` subcommand
     #[options(help = "get usage information")]
     Help(Help<Self>),
 
+    /// The `import-peers` subcommand
+    #[options(help = "import a peer cache or zcashd peers.dat file into the peer address cache")]
+    ImportPeers(ImportPeersCmd),
+
+    /// The `replay` subcommand
+    #[options(help = "re-verify a range of blocks from the state cache")]
+    Replay(ReplayCmd),
+
     /// The `revhex` subcommand
     #[options(help = "reverses the endianness of a hex string, like a block or transaction hash")]
     Revhex(RevhexCmd),
 
+    /// The `rpc-schema` subcommand
+    #[options(help = "print an OpenAPI document for the RPC methods")]
+    RpcSchema(RpcSchemaCmd),
+
     /// The `seed` subcommand
     #[options(help = "dns seeder")]
     Seed(SeedCmd),
@@ -50,9 +84,17 @@ pub enum ZebradCmd {
     #[options(help = "start the application")]
     Start(StartCmd),
 
+    /// The `tip-height` subcommand
+    #[options(help = "print the height and hash of the state tip")]
+    TipHeight(TipHeightCmd),
+
     /// The `version` subcommand
     #[options(help = "display version information")]
     Version(VersionCmd),
+
+    /// The `windows-service` subcommand
+    #[options(help = "run the node as a Windows service")]
+    WindowsService(WindowsServiceCmd),
 }
 
 impl ZebradCmd {
@@ -66,8 +108,9 @@ impl ZebradCmd {
     pub(crate) fn uses_stdout(&self) -> bool {
         match self {
             // List all the commands, so new commands have to make a choice here
-            Generate(_) | Help(_) | Revhex(_) | Version(_) => true,
-            Connect(_) | Seed(_) | Start(_) => false,
+            DbInfo(_) | ExportPeers(_) | Generate(_) | Help(_) | ImportPeers(_) | Revhex(_)
+            | RpcSchema(_) | TipHeight(_) | Version(_) => true,
+            Connect(_) | CopyState(_) | Replay(_) | Seed(_) | Start(_) | WindowsService(_) => false,
         }
     }
 
@@ -81,8 +124,9 @@ impl ZebradCmd {
     pub(crate) fn is_server(&self) -> bool {
         match self {
             // List all the commands, so new commands have to make a choice here
-            Connect(_) | Seed(_) | Start(_) => true,
-            Generate(_) | Help(_) | Revhex(_) | Version(_) => false,
+            Connect(_) | CopyState(_) | Replay(_) | Seed(_) | Start(_) | WindowsService(_) => true,
+            DbInfo(_) | ExportPeers(_) | Generate(_) | Help(_) | ImportPeers(_) | Revhex(_)
+            | RpcSchema(_) | TipHeight(_) | Version(_) => false,
         }
     }
 }
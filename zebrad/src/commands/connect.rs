@@ -11,7 +11,7 @@ use futures::{
 use std::collections::BTreeSet;
 use tower::{buffer::Buffer, service_fn, Service, ServiceExt};
 
-use zebra_chain::{block::BlockHeaderHash, types::BlockHeight};
+use zebra_chain::{block::BlockHeaderHash, types::BlockHeight, Network};
 use zebra_consensus::parameters;
 
 /// `connect` subcommand
@@ -73,15 +73,17 @@ impl ConnectCmd {
         config.listen_addr = "0.0.0.0:38233".parse()?;
         // Connect only to the specified peer.
         config.initial_mainnet_peers.insert(self.addr.to_string());
+        let network = config.network;
 
         let state = zebra_state::in_memory::init();
-        let (peer_set, _address_book) = zebra_network::init(config, node).await;
+        let (peer_set, _address_book, _peer_control) = zebra_network::init(config, node).await;
         let retry_peer_set = tower::retry::Retry::new(zebra_network::RetryErrors, peer_set.clone());
 
         let mut downloaded_block_heights = BTreeSet::<BlockHeight>::new();
         downloaded_block_heights.insert(BlockHeight(0));
 
         let mut connect = Connect {
+            network,
             retry_peer_set,
             peer_set,
             state,
@@ -101,6 +103,7 @@ struct Connect<ZN, ZS>
 where
     ZN: Service<zebra_network::Request>,
 {
+    network: Network,
     retry_peer_set: tower::retry::Retry<zebra_network::RetryErrors, ZN>,
     peer_set: ZN,
     state: ZS,
@@ -193,13 +196,19 @@ where
             match self.block_requests.next().await {
                 Some(Ok(zebra_network::Response::Blocks(blocks))) => {
                     for block in blocks {
-                        self.downloaded_block_heights
-                            .insert(block.coinbase_height().unwrap());
+                        let height = block.coinbase_height().unwrap();
+                        self.downloaded_block_heights.insert(height);
                         self.state
                             .ready_and()
                             .await
                             .map_err(|e| eyre!(e))?
-                            .call(zebra_state::Request::AddBlock { block })
+                            .call(zebra_state::Request::AddBlock {
+                                block,
+                                deferred_pool_contribution: parameters::deferred_pool_contribution(
+                                    self.network,
+                                    height,
+                                ),
+                            })
                             .await
                             .map_err(|e| eyre!(e))?;
                     }
@@ -0,0 +1,197 @@
+//! `copy-state` subcommand - copies the state cache into a new directory.
+//!
+//! Every block in the source state is read, checked against some cheap
+//! invariants, and written to the target state using the current disk
+//! format. So this command doubles as a corruption check for the source
+//! state, and a migration path between disk formats.
+//!
+//! The source state is the state cache in the zebrad config. It must not be
+//! used by a running `zebrad start` while it is being copied.
+
+use crate::{components::tokio::TokioComponent, prelude::*};
+
+use abscissa_core::{Command, Options, Runnable};
+use color_eyre::eyre::{eyre, Report};
+use std::{path::PathBuf, sync::Arc};
+use tower::{Service, ServiceExt};
+
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    types::BlockHeight,
+};
+
+/// The number of blocks between progress log messages.
+const PROGRESS_INTERVAL: u32 = 10_000;
+
+/// `copy-state` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct CopyStateCmd {
+    /// The cache directory for the new state.
+    ///
+    /// The directory must not already contain a state.
+    #[options(free, required)]
+    target_dir: PathBuf,
+}
+
+impl CopyStateCmd {
+    async fn copy(&self) -> Result<(), Report> {
+        let network = app_config().network.network;
+        let source_config = app_config().state.clone();
+        let target_config = zebra_state::Config {
+            cache_dir: Some(self.target_dir.clone()),
+            compact_filters: source_config.compact_filters,
+            min_free_disk_space_mb: source_config.min_free_disk_space_mb,
+        };
+
+        // Check the source before opening it, so we don't commit a genesis
+        // block to an empty source state.
+        zebra_state::on_disk::state_info(&source_config, network).map_err(|e| eyre!(e))?;
+        if zebra_state::on_disk::state_info(&target_config, network).is_ok() {
+            return Err(eyre!(
+                "the target directory {:?} already contains a state",
+                self.target_dir
+            ));
+        }
+
+        let source = zebra_state::on_disk::init(source_config, network);
+        let target = zebra_state::on_disk::init(target_config, network);
+
+        let tip = zebra_state::initial_tip(source.clone())
+            .await?
+            .ok_or_else(|| eyre!("the source state contains no blocks"))?;
+        let tip_height = tip
+            .coinbase_height()
+            .ok_or_else(|| eyre!("the source tip block has no coinbase height"))?;
+        info!(?tip_height, tip_hash = ?tip.hash(), "copying state");
+
+        // The state service only looks up blocks by hash, so we walk back
+        // from the tip to find the hashes of all the blocks in the chain.
+        let mut hashes = Vec::with_capacity(tip_height.0 as usize + 1);
+        let mut hash = tip.hash();
+        for height in (0..=tip_height.0).rev() {
+            let block = get_block(source.clone(), hash).await?;
+            hashes.push(hash);
+            if height > 0 {
+                hash = block.header.previous_block_hash;
+            }
+        }
+        hashes.reverse();
+
+        let mut previous_hash = None;
+        for (height, hash) in (0..).map(BlockHeight).zip(hashes) {
+            let block = get_block(source.clone(), hash).await?;
+            check_block(&block, hash, height, previous_hash)?;
+
+            let added = target
+                .clone()
+                .ready_and()
+                .await
+                .map_err(|e| eyre!(e))?
+                .call(zebra_state::Request::AddBlock {
+                    block,
+                    deferred_pool_contribution:
+                        zebra_consensus::parameters::deferred_pool_contribution(network, height),
+                })
+                .await
+                .map_err(|e| eyre!(e))?;
+            match added {
+                zebra_state::Response::Added { hash: added_hash } if added_hash == hash => {}
+                _ => return Err(eyre!("unexpected response when copying block {:?}", hash)),
+            }
+
+            if height.0 % PROGRESS_INTERVAL == 0 {
+                info!(?height, ?tip_height, "copied blocks");
+            }
+            previous_hash = Some(hash);
+        }
+
+        info!(?tip_height, "finished copying state");
+
+        Ok(())
+    }
+}
+
+/// Get the block with `hash` from `state`.
+async fn get_block<S>(state: S, hash: BlockHeaderHash) -> Result<Arc<Block>, Report>
+where
+    S: Service<
+        zebra_state::Request,
+        Response = zebra_state::Response,
+        Error = Box<dyn std::error::Error + Send + Sync + 'static>,
+    >,
+{
+    match state
+        .oneshot(zebra_state::Request::GetBlock { hash })
+        .await
+        .map_err(|e| eyre!(e))?
+    {
+        zebra_state::Response::Block { block } => Ok(block),
+        _ => unreachable!("GetBlock request can only result in Response::Block"),
+    }
+}
+
+/// Check the cheap invariants for a `block` read from the source state.
+///
+/// The block must have the `hash` and `height` it was looked up with, and it
+/// must be a child of `previous_hash`.
+fn check_block(
+    block: &Block,
+    hash: BlockHeaderHash,
+    height: BlockHeight,
+    previous_hash: Option<BlockHeaderHash>,
+) -> Result<(), Report> {
+    if block.hash() != hash {
+        return Err(eyre!(
+            "block {:?} has the wrong hash {:?}",
+            hash,
+            block.hash()
+        ));
+    }
+
+    if block.coinbase_height() != Some(height) {
+        return Err(eyre!(
+            "block {:?} has coinbase height {:?}, expected {:?}",
+            hash,
+            block.coinbase_height(),
+            height
+        ));
+    }
+
+    if let Some(previous_hash) = previous_hash {
+        if block.header.previous_block_hash != previous_hash {
+            return Err(eyre!(
+                "block {:?} has previous block hash {:?}, expected {:?}",
+                hash,
+                block.header.previous_block_hash,
+                previous_hash
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+impl Runnable for CopyStateCmd {
+    /// Copy the state.
+    fn run(&self) {
+        let rt = app_writer()
+            .state_mut()
+            .components
+            .get_downcast_mut::<TokioComponent>()
+            .expect("TokioComponent should be available")
+            .rt
+            .take();
+
+        let result = rt
+            .expect("runtime should not already be taken")
+            .block_on(self.copy());
+
+        match result {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
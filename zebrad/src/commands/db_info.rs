@@ -0,0 +1,53 @@
+//! `db-info` subcommand - prints diagnostic information about the state cache.
+//!
+//! This command reads the state cache directly, so it must not be run while
+//! `zebrad start` is using the same cache directory.
+
+use crate::prelude::*;
+
+use abscissa_core::{Command, Options, Runnable};
+
+/// `db-info` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct DbInfoCmd {}
+
+impl Runnable for DbInfoCmd {
+    /// Print the state path, tip, value pools, and the size of each tree.
+    fn run(&self) {
+        let config = app_config().state.clone();
+        let network = app_config().network.network;
+
+        let info = match zebra_state::on_disk::state_info(&config, network) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        println!("path: {}", info.path.display());
+        match info.tip {
+            Some((height, hash)) => println!("tip: {} {}", height.0, hash),
+            None => println!("tip: none, the state is empty"),
+        }
+        if let Some(pools) = info.value_pools {
+            println!(
+                "value pools: transparent {} ZEC, sprout {} ZEC, sapling {} ZEC, orchard {} ZEC, lockbox {} ZEC",
+                pools.transparent, pools.sprout, pools.sapling, pools.orchard, pools.deferred
+            );
+        }
+        println!("size on disk: {} bytes", info.size_on_disk);
+
+        println!();
+        println!(
+            "{:<24} {:>12} {:>16} {:>16}",
+            "tree", "entries", "key bytes", "value bytes"
+        );
+        for tree in info.trees {
+            println!(
+                "{:<24} {:>12} {:>16} {:>16}",
+                tree.name, tree.entries, tree.key_bytes, tree.value_bytes
+            );
+        }
+    }
+}
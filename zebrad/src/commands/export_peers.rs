@@ -0,0 +1,47 @@
+//! `export-peers` subcommand - exports the peer address cache.
+//!
+//! The exported file uses Zebra's stable peer cache format, which is
+//! documented in `zebra_network::peer_cache`.
+
+use crate::prelude::*;
+
+use abscissa_core::{Command, Options, Runnable};
+use color_eyre::eyre::{eyre, Report};
+use std::path::PathBuf;
+
+/// `export-peers` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct ExportPeersCmd {
+    /// The file to write the peers to.
+    #[options(free, required)]
+    path: PathBuf,
+}
+
+impl ExportPeersCmd {
+    fn export(&self) -> Result<usize, Report> {
+        let cache_file = app_config().peer_cache_file().ok_or_else(|| {
+            eyre!("there is no peer cache file or cache directory in the zebrad config")
+        })?;
+        let config = app_config().network.clone();
+
+        let peers = zebra_network::peer_cache::load(&cache_file, config.network)
+            .map_err(|e| eyre!("could not read {:?}: {}", cache_file, e))?;
+        let count = peers.len();
+        zebra_network::peer_cache::save(&self.path, peers)?;
+
+        Ok(count)
+    }
+}
+
+impl Runnable for ExportPeersCmd {
+    /// Write the cached peers to the target file.
+    fn run(&self) {
+        match self.export() {
+            Ok(count) => println!("exported {} peers to {}", count, self.path.display()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
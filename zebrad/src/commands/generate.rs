@@ -15,10 +15,8 @@ impl Runnable for GenerateCmd {
     /// Start the application.
     fn run(&self) {
         let default_config = ZebradConfig {
-            metrics: Default::default(),
-            network: Default::default(),
-            state: Default::default(),
             tracing: crate::config::TracingSection::populated(),
+            ..ZebradConfig::default()
         };
         let mut output = r"# Default configuration for zebrad.
 #
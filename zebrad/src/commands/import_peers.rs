@@ -0,0 +1,66 @@
+//! `import-peers` subcommand - imports peers into the peer address cache.
+//!
+//! The imported file can be a Zebra peer cache, or a `zcashd` `peers.dat`
+//! file for the configured network. Imported peers are merged with the
+//! existing cache, keeping the most recent last seen time for each address.
+//!
+//! The cache is rewritten by `zebrad start`, so this command should not be
+//! run while a node is using the same cache file.
+
+use crate::prelude::*;
+
+use abscissa_core::{Command, Options, Runnable};
+use color_eyre::eyre::{eyre, Report};
+use std::{io, path::PathBuf};
+
+use zebra_network::{peer_cache, AddressBook};
+
+/// `import-peers` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct ImportPeersCmd {
+    /// The Zebra peer cache or `zcashd` `peers.dat` file to import.
+    #[options(free, required)]
+    path: PathBuf,
+}
+
+impl ImportPeersCmd {
+    fn import(&self) -> Result<(usize, usize), Report> {
+        let cache_file = app_config().peer_cache_file().ok_or_else(|| {
+            eyre!("there is no peer cache file or cache directory in the zebrad config")
+        })?;
+        let config = app_config().network.clone();
+
+        let imported = peer_cache::load(&self.path, config.network)
+            .map_err(|e| eyre!("could not read {:?}: {}", self.path, e))?;
+        let imported_count = imported.len();
+
+        let mut peers = AddressBook::new(tracing::Span::current());
+        match peer_cache::load(&cache_file, config.network) {
+            Ok(existing) => peers.extend(existing),
+            Err(peer_cache::PeerCacheError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(eyre!("could not read {:?}: {}", cache_file, e)),
+        }
+        peers.extend(imported);
+
+        let total = peers.len();
+        peer_cache::save(&cache_file, peers.peers().collect::<Vec<_>>())?;
+
+        Ok((imported_count, total))
+    }
+}
+
+impl Runnable for ImportPeersCmd {
+    /// Merge the peers in the source file into the peer cache.
+    fn run(&self) {
+        match self.import() {
+            Ok((imported, total)) => println!(
+                "imported {} peers, the cache now contains {} peers",
+                imported, total
+            ),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! `replay` subcommand - re-verifies a range of blocks from the state cache.
+//!
+//! The blocks before the range are copied from the state cache into a
+//! temporary state, without verification. Then each block in the range is
+//! read from the state cache, fully verified against the temporary state, and
+//! added to it. Checkpoints are not used, so every block in the range gets
+//! full semantic and contextual verification.
+//!
+//! This command makes it possible to check consensus rule changes against the
+//! chain history, without a network sync.
+//!
+//! The state cache in the zebrad config is only read. It must not be used by
+//! a running `zebrad start` while it is being replayed.
+
+use crate::{components::tokio::TokioComponent, prelude::*};
+
+use abscissa_core::{Command, Options, Runnable};
+use color_eyre::eyre::{eyre, Report};
+use std::{
+    error::Error,
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tower::{Service, ServiceExt};
+
+use zebra_chain::{block::Block, types::BlockHeight, Network};
+
+/// The number of blocks between progress log messages.
+const PROGRESS_INTERVAL: u32 = 1_000;
+
+/// `replay` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct ReplayCmd {
+    /// The height of the first block to verify.
+    #[options(
+        no_short,
+        required,
+        meta = "HEIGHT",
+        help = "The height of the first block to verify"
+    )]
+    from: u32,
+
+    /// The height of the last block to verify.
+    ///
+    /// Defaults to the tip of the state cache.
+    #[options(
+        no_short,
+        meta = "HEIGHT",
+        help = "The height of the last block to verify (default: the state tip)"
+    )]
+    to: Option<u32>,
+
+    /// The cache directory for the temporary state.
+    ///
+    /// The directory must not already exist. It is deleted after the replay
+    /// finishes.
+    #[options(
+        no_short,
+        meta = "DIR",
+        help = "The directory for the temporary state (default: in the system temp directory)"
+    )]
+    temp_dir: Option<PathBuf>,
+}
+
+impl ReplayCmd {
+    async fn replay(&self, temp_dir: PathBuf) -> Result<(), Report> {
+        let network = app_config().network.network;
+        let source_config = app_config().state.clone();
+        let temp_config = zebra_state::Config {
+            cache_dir: Some(temp_dir.clone()),
+            compact_filters: false,
+            min_free_disk_space_mb: source_config.min_free_disk_space_mb,
+        };
+
+        // Check the source before opening it, so we don't commit a genesis
+        // block to an empty source state.
+        zebra_state::on_disk::state_info(&source_config, network).map_err(|e| eyre!(e))?;
+
+        let source = zebra_state::on_disk::init(source_config, network);
+        let tip_height = zebra_state::initial_tip(source.clone())
+            .await?
+            .and_then(|tip| tip.coinbase_height())
+            .ok_or_else(|| eyre!("the state cache contains no blocks"))?;
+
+        let from = BlockHeight(self.from);
+        let to = self.to.map(BlockHeight).unwrap_or(tip_height);
+        if from.0 == 0 || from > to || to > tip_height {
+            return Err(eyre!(
+                "invalid replay range: {:?} to {:?}, expected 1 <= from <= to <= {:?}",
+                from,
+                to,
+                tip_height
+            ));
+        }
+
+        let temp = zebra_state::on_disk::init(temp_config, network);
+        // Load the Groth16 parameters from the source cache directory, so they
+        // aren't downloaded into the temporary state
+        let params_loader =
+            zebra_consensus::groth16::ParamsLoader::new(app_config().state.cache_dir.as_deref());
+        let verifier = zebra_consensus::block::init(network, temp.clone(), params_loader);
+
+        // The on-disk state commits hard-coded genesis blocks when it starts
+        let copy_start = match zebra_state::initial_tip(temp.clone()).await? {
+            Some(_) => 1,
+            None => 0,
+        };
+        info!(?from, "copying the blocks before the replay range");
+        for height in copy_start..from.0 {
+            let block = get_block(source.clone(), BlockHeight(height)).await?;
+            add_block(network, temp.clone(), block).await?;
+
+            if height % (PROGRESS_INTERVAL * 10) == 0 {
+                info!(?height, ?from, "copied blocks");
+            }
+        }
+
+        info!(?from, ?to, "replaying blocks");
+        let start = Instant::now();
+        let mut verify_time = Duration::from_secs(0);
+        for height in from.0..=to.0 {
+            let height = BlockHeight(height);
+            let block = get_block(source.clone(), height).await?;
+            let hash = block.hash();
+
+            let verify_start = Instant::now();
+            verifier.clone().oneshot(block.clone()).await.map_err(|e| {
+                eyre!(
+                    "block {:?} at height {:?} failed verification: {}",
+                    hash,
+                    height,
+                    e
+                )
+            })?;
+            verify_time += verify_start.elapsed();
+
+            add_block(network, temp.clone(), block).await.map_err(|e| {
+                e.wrap_err(format!(
+                    "block {:?} at height {:?} was rejected by the state",
+                    hash, height
+                ))
+            })?;
+
+            if height.0 % PROGRESS_INTERVAL == 0 {
+                info!(?height, ?to, elapsed = ?start.elapsed(), "replayed blocks");
+            }
+        }
+
+        info!(
+            ?from,
+            ?to,
+            elapsed = ?start.elapsed(),
+            ?verify_time,
+            "all blocks in the replay range are valid"
+        );
+
+        Ok(())
+    }
+}
+
+/// The state service error type.
+type StateError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Get the block at `height` from `state`.
+async fn get_block<S>(state: S, height: BlockHeight) -> Result<Arc<Block>, Report>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = StateError>,
+{
+    match state
+        .oneshot(zebra_state::Request::GetBlockByHeight { height })
+        .await
+        .map_err(|e| eyre!(e))?
+    {
+        zebra_state::Response::Block { block } => Ok(block),
+        _ => unreachable!("GetBlockByHeight request can only result in Response::Block"),
+    }
+}
+
+/// Add `block` to `state` for `network`.
+async fn add_block<S>(network: Network, state: S, block: Arc<Block>) -> Result<(), Report>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = StateError>,
+{
+    let hash = block.hash();
+    let height = block
+        .coinbase_height()
+        .ok_or_else(|| eyre!("block {:?} has no coinbase height", hash))?;
+    let deferred_pool_contribution =
+        zebra_consensus::parameters::deferred_pool_contribution(network, height);
+    match state
+        .oneshot(zebra_state::Request::AddBlock {
+            block,
+            deferred_pool_contribution,
+        })
+        .await
+        .map_err(|e| eyre!(e))?
+    {
+        zebra_state::Response::Added { hash: added_hash } if added_hash == hash => Ok(()),
+        _ => Err(eyre!("unexpected response when adding block {:?}", hash)),
+    }
+}
+
+impl Runnable for ReplayCmd {
+    /// Replay the blocks.
+    fn run(&self) {
+        let rt = app_writer()
+            .state_mut()
+            .components
+            .get_downcast_mut::<TokioComponent>()
+            .expect("TokioComponent should be available")
+            .rt
+            .take();
+
+        let temp_dir = self.temp_dir.clone().unwrap_or_else(|| {
+            std::env::temp_dir().join(format!("zebrad-replay-{}", std::process::id()))
+        });
+
+        // Only delete directories that we created
+        let result = if temp_dir.exists() {
+            Err(eyre!(
+                "the temporary state directory {:?} already exists",
+                temp_dir
+            ))
+        } else {
+            let result = rt
+                .expect("runtime should not already be taken")
+                .block_on(self.replay(temp_dir.clone()));
+
+            if let Err(e) = fs::remove_dir_all(&temp_dir) {
+                warn!(?temp_dir, ?e, "could not remove the temporary state");
+            }
+            result
+        };
+
+        match result {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
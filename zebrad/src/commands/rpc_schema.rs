@@ -0,0 +1,32 @@
+//! `rpc-schema` subcommand - prints an OpenAPI document for the RPC methods.
+
+use abscissa_core::{Command, Options, Runnable};
+
+/// `rpc-schema` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct RpcSchemaCmd {
+    /// The file to write the document to.
+    #[options(help = "The file to write the OpenAPI document to (stdout if unspecified)")]
+    output_file: Option<String>,
+}
+
+impl Runnable for RpcSchemaCmd {
+    /// Print the OpenAPI document.
+    fn run(&self) {
+        let document = zebra_rpc::openapi::document();
+        let output = serde_json::to_string_pretty(&document).expect("documents serialize to JSON");
+
+        match self.output_file {
+            Some(ref output_file) => {
+                use std::{fs::File, io::Write};
+                File::create(output_file)
+                    .expect("must be able to open output file")
+                    .write_all(output.as_bytes())
+                    .expect("must be able to write output");
+            }
+            None => {
+                println!("{}", output);
+            }
+        }
+    }
+}
@@ -71,20 +71,9 @@ impl Service<Request> for SeedService {
 
         let response = match req {
             Request::Peers => {
-                // Collect a list of known peers from the address book
-                // and sanitize their timestamps.
-                let mut peers = address_book
-                    .lock()
-                    .unwrap()
-                    .peers()
-                    .map(|addr| addr.sanitize())
-                    .collect::<Vec<_>>();
-                // The peers are still ordered by recency, so shuffle them.
-                use rand::seq::SliceRandom;
-                peers.shuffle(&mut rand::thread_rng());
-                // Finally, truncate the list so that we do not trivially
-                // reveal our entire peer set.
-                peers.truncate(50);
+                // Collect a sanitized sample of known peers from the
+                // address book.
+                let peers = address_book.lock().unwrap().sanitized();
                 debug!(peers.len = peers.len());
                 Ok(Response::Peers(peers))
             }
@@ -135,9 +124,13 @@ impl SeedCmd {
         };
         let buffered_svc = Buffer::new(seed_service, 1);
 
-        let config = app_config().network.clone();
+        let config = zebra_network::Config {
+            peer_cache_file: app_config().peer_cache_file(),
+            ..app_config().network.clone()
+        };
 
-        let (mut peer_set, address_book) = zebra_network::init(config, buffered_svc).await;
+        let (mut peer_set, address_book, _peer_control) =
+            zebra_network::init(config, buffered_svc).await;
 
         let _ = addressbook_tx.send(address_book);
 
@@ -18,22 +18,69 @@
 //!  * Sync Task
 //!    * This task runs in the background and continuously queries the network for
 //!    new blocks to be verified and added to the local state
+//!  * Mempool Service
+//!    * verifies transactions submitted over RPC against the local state,
+//!    and stores them until they are mined
+//!  * RPC Service
+//!    * If enabled, answers zcashd-compatible JSON-RPC requests from wallets
+//!    and explorers
+//!    * If requests are authenticated, lets operators stop the node, change
+//!    the log filter, and connect to or disconnect from peers
+//!    * sends accepted transactions to the mempool, then gossips them to
+//!    peers using the network service
+//!  * Miner Task
+//!    * If the internal miner is enabled, this task mines blocks on the local
+//!    state tip, and submits them to the consensus service
+//!  * Event Socket
+//!    * If enabled, publishes new blocks, reorgs, and mempool changes as JSON
+//!    events to local subscribers
+//!  * ZMQ Publisher
+//!    * If enabled, publishes zcashd-compatible block and transaction
+//!    notifications over ZeroMQ
+//!  * Service Notifications
+//!    * If run by systemd or as a Windows service, reports readiness once the
+//!    state is open and peers are connected, and keeps the systemd watchdog
+//!    alive
 
 use crate::config::ZebradConfig;
-use crate::{components::tokio::TokioComponent, prelude::*};
+use crate::{
+    components::{
+        admin::NodeAdmin, events, health, inflight::InFlightBlocks, miner, service_notify,
+        tokio::TokioComponent, zmq_notify,
+    },
+    prelude::*,
+};
 
 use abscissa_core::{config, Command, FrameworkError, Options, Runnable};
-use color_eyre::eyre::Report;
-use tower::{buffer::Buffer, service_fn};
+use color_eyre::eyre::{eyre, Report};
+use futures::future::{self, Either};
+use std::{error::Error, sync::Arc};
+use tower::{buffer::Buffer, service_fn, util::BoxService, Service, ServiceExt};
+
+use zebra_chain::types::BlockHeight;
+use zebra_network as zn;
+use zebra_state as zs;
 
 mod sync;
 
 /// `start` subcommand
-#[derive(Command, Debug, Options)]
+#[derive(Command, Debug, Default, Options)]
 pub struct StartCmd {
     /// Filter strings
     #[options(free)]
     filters: Vec<String>,
+
+    /// Stop syncing at this height, but keep running
+    #[options(
+        no_short,
+        meta = "HEIGHT",
+        help = "Stop syncing at HEIGHT, but keep running the other services"
+    )]
+    sync_to_height: Option<u32>,
+
+    /// Stop syncing and exit at this height
+    #[options(no_short, meta = "HEIGHT", help = "Stop syncing and exit at HEIGHT")]
+    stop_at_height: Option<u32>,
 }
 
 impl StartCmd {
@@ -41,22 +88,193 @@ impl StartCmd {
         info!(?self, "starting to connect to the network");
 
         let config = app_config();
-        let state = zebra_state::on_disk::init(config.state.clone());
-        let verifier = zebra_consensus::chain::init(config.network.network, state.clone()).await;
+        let state = zebra_state::on_disk::init(config.state.clone(), config.network.network);
+        let params_loader =
+            zebra_consensus::groth16::ParamsLoader::new(config.state.cache_dir.as_deref());
+        let verifier = zebra_consensus::chain::init(
+            config.network.network,
+            state.clone(),
+            params_loader.clone(),
+        )
+        .await;
 
         // The service that our node uses to respond to requests by peers
         let node = Buffer::new(
-            service_fn(|req| async move {
-                info!(?req);
-                Ok::<zebra_network::Response, Report>(zebra_network::Response::Nil)
+            service_fn({
+                let state = state.clone();
+                move |req| respond_to_peer(state.clone(), req)
             }),
             1,
         );
-        let (peer_set, _address_book) = zebra_network::init(config.network.clone(), node).await;
+        let network_config = zn::Config {
+            peer_cache_file: config.peer_cache_file(),
+            ..config.network.clone()
+        };
+        let (peer_set, address_book, peer_control) =
+            zebra_network::init(network_config, node).await;
+
+        service_notify::spawn(address_book.clone());
+
+        let health_status = health::HealthStatus::new();
+        health::spawn(
+            config.health.clone(),
+            health_status.clone(),
+            address_book.clone(),
+        );
+
+        let mempool_state = zebra_consensus::mempool::ZebraMempoolState::default();
+        if config.events.socket_path.is_some() || config.zmq.is_enabled() {
+            let event_source = events::start(state.clone(), &mempool_state);
+            events::spawn(config.events.clone(), event_source.clone());
+            zmq_notify::spawn(config.zmq.clone(), event_source)?;
+        }
+
+        let mempool = zebra_consensus::mempool::init(
+            config.network.network,
+            config.mempool.clone(),
+            state.clone(),
+            mempool_state,
+        );
+        let (node_admin, stop_rx) = NodeAdmin::new();
+        zebra_rpc::server::spawn(
+            config.rpc.clone(),
+            zebra_rpc::Rpc::new(config.network.network, state.clone())
+                .with_mempool(
+                    Buffer::new(BoxService::new(mempool), 1),
+                    Buffer::new(BoxService::new(peer_set.clone()), 1),
+                )
+                .with_network_diagnostics(&config.network, &config.mempool, address_book.clone())
+                .with_admin(Arc::new(node_admin), peer_control),
+        );
+
+        miner::spawn(
+            config.mining.clone(),
+            config.network.network,
+            state.clone(),
+            verifier.clone(),
+        )?;
+
+        if config.sync.warm_up_verifiers {
+            // Downloading the parameters can take a long time, so don't make
+            // the sync wait for it
+            tokio::spawn(async move {
+                if let Err(error) = zebra_consensus::chain::warm_up(params_loader).await {
+                    warn!(?error, "failed to warm up the verifiers");
+                }
+            });
+        }
+
+        let mut syncer = sync::Syncer::new(
+            config.network.network,
+            peer_set,
+            state,
+            verifier,
+            address_book,
+            health_status,
+            config.sync.stall_timeout,
+            InFlightBlocks::new(),
+        )
+        .stop_at_height(config.sync.stop_at_height.map(BlockHeight));
+
+        let run = async {
+            syncer.sync().await?;
 
-        let mut syncer = sync::Syncer::new(config.network.network, peer_set, state, verifier);
+            if config.sync.exit_at_stop_height {
+                info!("sync reached the stop height, exiting");
+                service_notify::stopping();
+                return Ok(());
+            }
+
+            // Keep running the other services
+            info!("sync reached the stop height, waiting for shutdown");
+            future::pending().await
+        };
+
+        // The RPC `stop` method shuts down the node, by returning from this
+        // function
+        let result = match future::select(Box::pin(run), stop_rx).await {
+            Either::Left((result, _)) => result,
+            Either::Right((Ok(()), _)) => {
+                service_notify::stopping();
+                Ok(())
+            }
+            // The RPC endpoint is disabled
+            Either::Right((Err(_), run)) => run.await,
+        };
+        result
+    }
+}
+
+/// Respond to a request from a peer, using the blocks in `state`.
+///
+/// Requests that the state can't answer get an empty response.
+async fn respond_to_peer<S>(mut state: S, req: zn::Request) -> Result<zn::Response, Report>
+where
+    S: Service<zs::Request, Response = zs::Response, Error = Box<dyn Error + Send + Sync>>
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    info!(?req);
+
+    match req {
+        zn::Request::CompactFilters {
+            start_height,
+            stop_hash,
+        } => {
+            let rsp = state
+                .ready_and()
+                .await
+                .map_err(|e| eyre!(e))?
+                .call(zs::Request::GetCompactFilters {
+                    start_height,
+                    stop_hash,
+                })
+                .await
+                .map_err(|e| eyre!(e))?;
 
-        syncer.sync().await
+            match rsp {
+                zs::Response::CompactFilters { filters } => Ok(zn::Response::CompactFilters(
+                    filters
+                        .into_iter()
+                        .map(|(hash, filter)| (hash, filter.0))
+                        .collect(),
+                )),
+                _ => unreachable!(
+                    "GetCompactFilters request can only result in Response::CompactFilters"
+                ),
+            }
+        }
+        zn::Request::CompactFilterHeaders {
+            start_height,
+            stop_hash,
+        } => {
+            let rsp = state
+                .ready_and()
+                .await
+                .map_err(|e| eyre!(e))?
+                .call(zs::Request::GetCompactFilterHeaders {
+                    start_height,
+                    stop_hash,
+                })
+                .await
+                .map_err(|e| eyre!(e))?;
+
+            match rsp {
+                zs::Response::CompactFilterHeaders {
+                    previous_header,
+                    filter_hashes,
+                } => Ok(zn::Response::CompactFilterHeaders {
+                    stop_hash,
+                    previous_header: previous_header.0,
+                    filter_hashes: filter_hashes.into_iter().map(|hash| hash.0).collect(),
+                }),
+                _ => unreachable!(
+                    "GetCompactFilterHeaders request can only result in Response::CompactFilterHeaders"
+                ),
+            }
+        }
+        _ => Ok(zn::Response::Nil),
     }
 }
 
@@ -94,6 +312,15 @@ impl config::Override<ZebradConfig> for StartCmd {
             config.tracing.filter = Some(self.filters.join(","));
         }
 
+        if let Some(height) = self.sync_to_height {
+            config.sync.stop_at_height = Some(height);
+            config.sync.exit_at_stop_height = false;
+        }
+        if let Some(height) = self.stop_at_height {
+            config.sync.stop_at_height = Some(height);
+            config.sync.exit_at_stop_height = true;
+        }
+
         Ok(config)
     }
 }
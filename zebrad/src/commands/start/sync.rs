@@ -1,26 +1,44 @@
-use std::{collections::HashSet, iter, pin::Pin, sync::Arc, time::Duration};
+use std::{
+    collections::HashSet,
+    iter,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
 
 use color_eyre::eyre::{eyre, Report};
 use futures::stream::{FuturesUnordered, StreamExt};
-use tokio::{task::JoinHandle, time::delay_for};
+use tokio::{
+    task::JoinHandle,
+    time::{delay_for, timeout},
+};
 use tower::{retry::Retry, Service, ServiceExt};
 use tracing_futures::{Instrument, Instrumented};
 
 use zebra_chain::{
     block::{Block, BlockHeaderHash},
+    types::{BlockHeight, HeightDiff},
     Network,
 };
-use zebra_consensus::checkpoint;
 use zebra_consensus::parameters;
+use zebra_consensus::{chain::DuplicateBlock, checkpoint};
 use zebra_network::{self as zn, RetryLimit};
 use zebra_state as zs;
 
+use crate::components::{
+    health::HealthStatus, inflight::InFlightBlocks, tip_estimate::estimate_network_tip,
+};
+
 // XXX in the future, we may not be able to access the checkpoint module.
 const FANOUT: usize = checkpoint::MAX_QUEUED_BLOCKS_PER_HEIGHT;
 /// Controls how far ahead of the chain tip the syncer tries to download before
-/// waiting for queued verifications to complete. Set to twice the maximum
-/// checkpoint distance.
-pub const LOOKAHEAD_LIMIT: usize = checkpoint::MAX_CHECKPOINT_HEIGHT_GAP * 2;
+/// waiting for queued verifications to complete. Set to the maximum checkpoint
+/// distance, multiplied by the number of checkpoint ranges that can be
+/// verified in parallel.
+pub const LOOKAHEAD_LIMIT: usize =
+    checkpoint::MAX_CHECKPOINT_HEIGHT_GAP * checkpoint::MAX_PARALLEL_CHECKPOINT_RANGES;
 
 #[derive(Debug)]
 pub struct Syncer<ZN, ZS, ZV>
@@ -41,7 +59,25 @@ where
     prospective_tips: HashSet<BlockHeaderHash>,
     pending_blocks:
         Pin<Box<FuturesUnordered<Instrumented<JoinHandle<Result<BlockHeaderHash, Error>>>>>>,
+    /// The hashes of the blocks that are being downloaded or verified, used
+    /// to avoid duplicate downloads.
+    in_flight: InFlightBlocks,
     genesis_hash: BlockHeaderHash,
+    network: Network,
+    /// Used to get the heights advertised by our peers.
+    address_book: Arc<Mutex<zn::AddressBook>>,
+    /// Used to report sync progress to the health endpoint.
+    health: Arc<HealthStatus>,
+    /// The height of the state tip when the syncer last made progress.
+    last_tip_height: Option<BlockHeight>,
+    /// The time when the syncer last made progress.
+    last_progress: Instant,
+    /// The syncer restarts if it makes no progress for this long, while peers
+    /// report a higher tip.
+    stall_timeout: Duration,
+    /// The syncer stops when the verified tip reaches this height, and
+    /// doesn't commit any blocks above `stop_at_height`.
+    stop_at_height: Option<BlockHeight>,
 }
 
 impl<ZN, ZS, ZV> Syncer<ZN, ZS, ZV>
@@ -58,7 +94,23 @@ where
     ///  - peers: the zebra-network peers to contact for downloads
     ///  - state: the zebra-state that stores the chain
     ///  - verifier: the zebra-consensus verifier that checks the chain
-    pub fn new(chain: Network, peers: ZN, state: ZS, verifier: ZV) -> Self {
+    ///  - address_book: the zebra-network address book, for estimating the
+    ///    network chain tip
+    ///  - health: the status reported by the health endpoint
+    ///  - stall_timeout: the time without progress before the syncer restarts
+    ///  - in_flight: the blocks being downloaded or verified by any part of
+    ///    zebrad
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        chain: Network,
+        peers: ZN,
+        state: ZS,
+        verifier: ZV,
+        address_book: Arc<Mutex<zn::AddressBook>>,
+        health: Arc<HealthStatus>,
+        stall_timeout: Duration,
+        in_flight: InFlightBlocks,
+    ) -> Self {
         let retry_peers = Retry::new(RetryLimit::new(3), peers.clone());
         Self {
             tip_network: peers,
@@ -67,28 +119,57 @@ where
             verifier,
             prospective_tips: HashSet::new(),
             pending_blocks: Box::pin(FuturesUnordered::new()),
+            in_flight,
             genesis_hash: parameters::genesis_hash(chain),
+            network: chain,
+            address_book,
+            health,
+            last_tip_height: None,
+            last_progress: Instant::now(),
+            stall_timeout,
+            stop_at_height: None,
         }
     }
 
+    /// Stop syncing after the block at `height`.
+    ///
+    /// If `height` is `None`, the syncer runs until it fails.
+    pub fn stop_at_height(mut self, height: Option<BlockHeight>) -> Self {
+        self.stop_at_height = height;
+        self
+    }
+
     #[instrument(skip(self))]
     pub async fn sync(&mut self) -> Result<(), Report> {
         // We can't download the genesis block using our normal algorithm,
         // due to protocol limitations
         self.request_genesis().await?;
 
-        loop {
+        'sync: loop {
+            if self.reached_stop_height().await? {
+                return Ok(());
+            }
+
+            if self.is_stalled().await? {
+                self.restart().await?;
+            }
+
             self.obtain_tips().await?;
             metrics::gauge!(
                 "sync.prospective_tips.len",
                 self.prospective_tips.len() as i64
             );
             metrics::gauge!("sync.pending_blocks.len", self.pending_blocks.len() as i64);
+            metrics::gauge!("sync.in_flight_blocks.len", self.in_flight.len() as i64);
 
             // ObtainTips Step 6
             //
             // If there are any prospective tips, call ExtendTips. Continue this step until there are no more prospective tips.
             while !self.prospective_tips.is_empty() {
+                if self.reached_stop_height().await? {
+                    continue 'sync;
+                }
+
                 tracing::debug!("extending prospective tips");
 
                 self.extend_tips().await?;
@@ -105,29 +186,39 @@ where
 
                 // Check whether we need to wait for existing block download tasks to finish
                 while self.pending_blocks.len() > LOOKAHEAD_LIMIT {
-                    match self
-                        .pending_blocks
-                        .next()
-                        .await
+                    let next_block =
+                        match timeout(self.stall_timeout, self.pending_blocks.next()).await {
+                            Ok(next_block) => next_block,
+                            // Downloads or verification can stall, for example
+                            // if a block we need for a checkpoint is missing.
+                            Err(_) => {
+                                if self.is_stalled().await? {
+                                    self.restart().await?;
+                                    continue 'sync;
+                                }
+                                continue;
+                            }
+                        };
+
+                    match next_block
                         .expect("already checked there's at least one pending block task")
                         .expect("block download tasks should not panic")
                     {
                         Ok(hash) => tracing::debug!(?hash, "verified and committed block to state"),
+                        // Another download of the same block won the race.
+                        Err(e) if e.is::<DuplicateBlock>() => {
+                            tracing::debug!(?e, "skipped duplicate block")
+                        }
+                        Err(e) if e.is::<AboveStopHeight>() => {
+                            tracing::debug!(?e, "skipped block above the stop height")
+                        }
                         // This is a non-transient error indicating either that
                         // we've repeatedly missed a block we need or that we've
                         // repeatedly missed a bad block suggested by a peer
                         // feeding us bad hashes.
                         //
-                        // TODO(hdevalence): handle interruptions in the chain
-                        // sync process. this should detect when we've stopped
-                        // making progress (probably using a timeout), then
-                        // continue the loop with a new invocation of
-                        // obtain_tips(), which will restart block downloads.
-                        // this requires correctly constructing a block locator
-                        // (TODO below) and ensuring that the verifier handles
-                        // multiple requests for verification of the same block
-                        // hash by handling both requests or by discarding the
-                        // earlier request in favor of the later one.
+                        // If the missing block stops the sync, the stall
+                        // check restarts block downloads.
                         Err(e) => tracing::error!(?e, "potentially transient error"),
                     };
                 }
@@ -137,6 +228,89 @@ where
         }
     }
 
+    /// Returns true if the verified tip has reached the stop height.
+    ///
+    /// Below the final checkpoint, the sync stops at the highest checkpoint at
+    /// or below the stop height, because the checkpoint verifier only commits
+    /// complete checkpoint ranges.
+    async fn reached_stop_height(&mut self) -> Result<bool, Report> {
+        let stop_height = match self.stop_at_height {
+            Some(stop_height) => checkpoint::reachable_height(self.network, stop_height),
+            None => return Ok(false),
+        };
+
+        let tip_height = zs::initial_tip(self.state.clone())
+            .await?
+            .and_then(|tip| tip.coinbase_height());
+        let reached = tip_height.map_or(false, |tip_height| tip_height >= stop_height);
+        if reached {
+            tracing::info!(
+                ?tip_height,
+                ?stop_height,
+                "sync reached the stop height, stopping block downloads"
+            );
+        }
+
+        Ok(reached)
+    }
+
+    /// Returns true if the state tip has not changed for `stall_timeout`,
+    /// while our peers report a higher tip.
+    ///
+    /// Updates the last progress time if the state tip has changed.
+    async fn is_stalled(&mut self) -> Result<bool, Report> {
+        let tip_height = zs::initial_tip(self.state.clone())
+            .await?
+            .and_then(|tip| tip.coinbase_height());
+
+        if tip_height != self.last_tip_height {
+            self.last_tip_height = tip_height;
+            self.last_progress = Instant::now();
+            return Ok(false);
+        }
+
+        if self.last_progress.elapsed() < self.stall_timeout {
+            return Ok(false);
+        }
+
+        let max_peer_height = self
+            .address_book
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .live_start_heights()
+            .max();
+
+        Ok(match (tip_height, max_peer_height) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(tip_height), Some(peer_height)) => peer_height > tip_height,
+        })
+    }
+
+    /// Restart the sync, by dropping all prospective tips and pending block
+    /// downloads.
+    ///
+    /// The next call to `obtain_tips` starts new block downloads from the
+    /// current state tip.
+    async fn restart(&mut self) -> Result<(), Report> {
+        tracing::warn!(
+            tip_height = ?self.last_tip_height,
+            stall_timeout = ?self.stall_timeout,
+            pending.len = self.pending_blocks.len(),
+            "sync stalled, restarting block downloads"
+        );
+        metrics::counter!("sync.stall_restarts", 1);
+
+        // Dropping the tasks detaches them, so they can still commit blocks.
+        // Their blocks stay in flight until they finish, so new downloads
+        // skip them.
+        self.prospective_tips.clear();
+        self.pending_blocks = Box::pin(FuturesUnordered::new());
+        self.last_progress = Instant::now();
+
+        self.request_genesis().await
+    }
+
     /// Given a block_locator list fan out request for subsequent hashes to
     /// multiple peers
     #[instrument(skip(self))]
@@ -164,6 +338,11 @@ where
 
         tracing::info!(?block_locator, "trying to obtain new chain tips");
 
+        let tip = zs::initial_tip(self.state.clone()).await?;
+        // The largest number of unknown hashes returned by a peer, which is a
+        // lower bound on how far we are behind the network tip.
+        let mut max_unknown_hashes = 0;
+
         // ObtainTips Step 2
         //
         // Make a FindBlocksByHash request to the network F times, where F is a
@@ -225,6 +404,7 @@ where
                     }
 
                     let unknown_hashes = &hashes[first_unknown..];
+                    max_unknown_hashes = max_unknown_hashes.max(unknown_hashes.len());
                     let new_tip = *unknown_hashes
                         .last()
                         .expect("already checked first_unknown < hashes.len()");
@@ -255,6 +435,10 @@ where
             }
         }
 
+        if let Some(tip) = tip {
+            self.update_tip_estimate(&tip, max_unknown_hashes);
+        }
+
         // ObtainTips Step 5
         //
         // Combine all elements of each list into a set, and queue
@@ -265,6 +449,44 @@ where
         Ok(())
     }
 
+    /// Estimate the network chain tip, and report it to the health endpoint.
+    ///
+    /// `unknown_hashes` is the number of hashes after our `tip` that peers
+    /// have told us about.
+    fn update_tip_estimate(&self, tip: &Block, unknown_hashes: usize) {
+        let tip_height = match tip.coinbase_height() {
+            Some(height) => height,
+            None => return,
+        };
+
+        let peer_heights: Vec<BlockHeight> = self
+            .address_book
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .live_start_heights()
+            .collect();
+        let estimated_tip_height = estimate_network_tip(
+            self.network,
+            tip_height,
+            tip.header.time,
+            Utc::now(),
+            peer_heights,
+        )
+        .max((tip_height + unknown_hashes as HeightDiff).unwrap_or(BlockHeight::MAX));
+
+        tracing::info!(
+            ?tip_height,
+            ?estimated_tip_height,
+            "estimated network chain tip"
+        );
+        metrics::gauge!(
+            "sync.estimated_network_tip_height",
+            estimated_tip_height.0 as i64
+        );
+
+        self.health.set_heights(tip_height, estimated_tip_height);
+    }
+
     #[instrument(skip(self))]
     async fn extend_tips(&mut self) -> Result<(), Report> {
         // Extend Tips 1
@@ -359,6 +581,9 @@ where
 
     /// Queue a download for the genesis block, if it isn't currently known to
     /// our node.
+    ///
+    /// The state commits the embedded genesis block for networks that have
+    /// one, so this download is only needed for the other networks.
     async fn request_genesis(&mut self) -> Result<(), Report> {
         // Due to Bitcoin protocol limitations, we can't request the genesis
         // block using our standard tip-following algorithm:
@@ -386,10 +611,45 @@ where
         Ok(())
     }
 
+    /// Returns where the block with `hash` is known in the state, or `None` if
+    /// it is unknown.
+    async fn known_block(
+        &mut self,
+        hash: BlockHeaderHash,
+    ) -> Result<Option<zs::KnownBlock>, Report> {
+        match self
+            .state
+            .ready_and()
+            .await
+            .map_err(|e| eyre!(e))?
+            .call(zebra_state::Request::KnownBlock { hash })
+            .await
+            .map_err(|e| eyre!(e))?
+        {
+            zs::Response::KnownBlock(known_block) => Ok(known_block),
+            _ => unreachable!("KnownBlock request can only result in Response::KnownBlock"),
+        }
+    }
+
     /// Queue downloads for each block that isn't currently known to our node
     async fn request_blocks(&mut self, hashes: Vec<BlockHeaderHash>) -> Result<(), Report> {
         tracing::debug!(hashes.len = hashes.len(), "requesting blocks");
         for hash in hashes.into_iter() {
+            // Skip blocks that are already in flight, or already in the
+            // state, so duplicate hashes from peers don't cause redundant
+            // downloads and verification.
+            let in_flight = match self.in_flight.try_register(hash) {
+                Some(in_flight) => in_flight,
+                None => {
+                    tracing::debug!(?hash, "skipping block that is already in flight");
+                    continue;
+                }
+            };
+            if self.known_block(hash).await?.is_some() {
+                tracing::debug!(?hash, "skipping block that is already in the state");
+                continue;
+            }
+
             // We construct the block download requests sequentially, waiting
             // for the peer set to be ready to process each request. This
             // ensures that we start block downloads in the order we want them
@@ -406,18 +666,38 @@ where
                 .call(zn::Request::BlocksByHash(iter::once(hash).collect()));
             let span = tracing::info_span!("block_fetch_verify", ?hash);
             let mut verifier = self.verifier.clone();
+            let stop_at_height = self.stop_at_height;
             let task = tokio::spawn(async move {
-                let block = match block_req.await {
-                    Ok(zn::Response::Blocks(blocks)) => blocks
-                        .into_iter()
-                        .next()
-                        .expect("successful response has the block in it"),
-                    Ok(_) => unreachable!("wrong response to block request"),
-                    Err(e) => return Err(e),
-                };
-                metrics::counter!("sync.downloaded_blocks", 1);
-
-                verifier.ready_and().await?.call(block).await
+                let result = async move {
+                    let block = match block_req.await {
+                        Ok(zn::Response::Blocks(blocks)) => blocks
+                            .into_iter()
+                            .next()
+                            .expect("successful response has the block in it"),
+                        Ok(_) => unreachable!("wrong response to block request"),
+                        Err(e) => return Err(e),
+                    };
+                    metrics::counter!("sync.downloaded_blocks", 1);
+
+                    // Don't commit blocks above the stop height
+                    if let (Some(stop_height), Some(height)) =
+                        (stop_at_height, block.coinbase_height())
+                    {
+                        if height > stop_height {
+                            return Err(AboveStopHeight {
+                                height,
+                                stop_height,
+                            }
+                            .into());
+                        }
+                    }
+
+                    verifier.ready_and().await?.call(block).await
+                }
+                .await;
+
+                std::mem::drop(in_flight);
+                result
             })
             .instrument(span);
             self.pending_blocks.push(task);
@@ -428,3 +708,23 @@ where
 }
 
 type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The error returned by block download tasks for blocks above the stop
+/// height, which are downloaded but not verified.
+#[derive(Clone, Copy, Debug)]
+struct AboveStopHeight {
+    height: BlockHeight,
+    stop_height: BlockHeight,
+}
+
+impl std::fmt::Display for AboveStopHeight {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "block at height {:?} is above the stop height {:?}",
+            self.height, self.stop_height
+        )
+    }
+}
+
+impl std::error::Error for AboveStopHeight {}
@@ -0,0 +1,31 @@
+//! `tip-height` subcommand - prints the height and hash of the state tip.
+//!
+//! This command reads the state cache directly, so it must not be run while
+//! `zebrad start` is using the same cache directory.
+
+use crate::prelude::*;
+
+use abscissa_core::{Command, Options, Runnable};
+
+/// `tip-height` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct TipHeightCmd {}
+
+impl Runnable for TipHeightCmd {
+    /// Print the height and hash of the finalized tip.
+    fn run(&self) {
+        let config = app_config().state.clone();
+        let network = app_config().network.network;
+
+        match zebra_state::on_disk::state_info(&config, network) {
+            Ok(info) => match info.tip {
+                Some((height, hash)) => println!("{} {:?}", height.0, hash),
+                None => println!("the state is empty"),
+            },
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! `windows-service` subcommand - runs the node as a Windows service.
+//!
+//! Register the service with a command like:
+//!
+//! ```text
+//! sc.exe create zebrad binPath= "C:\path\to\zebrad.exe windows-service"
+//! ```
+//!
+//! The service runs `zebrad start`, and is reported as running once the state
+//! is open and a peer is connected.
+
+use abscissa_core::{Command, Options, Runnable};
+
+/// The name of the Windows service.
+#[cfg(windows)]
+const SERVICE_NAME: &str = "zebrad";
+
+/// `windows-service` subcommand
+#[derive(Command, Debug, Default, Options)]
+pub struct WindowsServiceCmd {}
+
+impl Runnable for WindowsServiceCmd {
+    /// Run the node as a Windows service.
+    #[cfg(windows)]
+    fn run(&self) {
+        // Blocks until the service stops
+        if let Err(e) = service::run() {
+            eprintln!("Error: could not start the Windows service: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    /// Windows services are not supported on this platform.
+    #[cfg(not(windows))]
+    fn run(&self) {
+        eprintln!("Error: windows-service is only supported on Windows, use `zebrad start`");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod service {
+    use std::ffi::OsString;
+
+    use abscissa_core::Runnable;
+    use windows_service::{
+        define_windows_service,
+        service::ServiceControl,
+        service_control_handler::{self, ServiceControlHandlerResult},
+        service_dispatcher,
+    };
+
+    use super::SERVICE_NAME;
+    use crate::{commands::start::StartCmd, components::service_notify};
+
+    // Generates the FFI entry point for the service control manager
+    define_windows_service!(ffi_service_main, service_main);
+
+    /// Connect to the service control manager, and run the service.
+    pub(super) fn run() -> windows_service::Result<()> {
+        service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+    }
+
+    /// Run the node, and handle service control events.
+    fn service_main(_arguments: Vec<OsString>) {
+        let handler = |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                info!(?control, "stopping the Windows service");
+                service_notify::windows::set_stopped();
+                std::process::exit(0);
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+
+        match service_control_handler::register(SERVICE_NAME, handler) {
+            Ok(handle) => service_notify::windows::set_starting(handle),
+            Err(e) => {
+                error!(?e, "could not register the Windows service control handler");
+                return;
+            }
+        }
+
+        StartCmd::default().run();
+        service_notify::windows::set_stopped();
+    }
+}
@@ -1,3 +1,11 @@
+pub mod admin;
+pub mod events;
+pub mod health;
+pub mod inflight;
 pub mod metrics;
+pub mod miner;
+pub mod service_notify;
+pub mod tip_estimate;
 pub mod tokio;
 pub mod tracing;
+pub mod zmq_notify;
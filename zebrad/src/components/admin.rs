@@ -0,0 +1,62 @@
+//! Node controls for the RPC admin methods: graceful shutdown and log filter
+//! changes.
+
+use std::{error::Error, sync::Mutex};
+
+use futures::channel::oneshot;
+use tracing_subscriber::EnvFilter;
+
+use crate::prelude::*;
+
+/// The node controls used by the RPC admin methods.
+#[derive(Debug)]
+pub struct NodeAdmin {
+    /// Sends the shutdown signal, or `None` if the node is already stopping.
+    stop_tx: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl NodeAdmin {
+    /// Returns the node controls, and a receiver that is signalled when the
+    /// RPC `stop` method is called.
+    pub fn new() -> (NodeAdmin, oneshot::Receiver<()>) {
+        let (stop_tx, stop_rx) = oneshot::channel();
+
+        (
+            NodeAdmin {
+                stop_tx: Mutex::new(Some(stop_tx)),
+            },
+            stop_rx,
+        )
+    }
+}
+
+impl zebra_rpc::methods::NodeControl for NodeAdmin {
+    fn stop(&self) {
+        let stop_tx = self
+            .stop_tx
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .take();
+
+        if let Some(stop_tx) = stop_tx {
+            info!("stopping at RPC request");
+            let _ = stop_tx.send(());
+        }
+    }
+
+    fn set_log_filter(&self, filter: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // The tracing component ignores invalid directives, so we check the
+        // filter first
+        filter.parse::<EnvFilter>()?;
+
+        app_writer()
+            .state_mut()
+            .components
+            .get_downcast_mut::<abscissa_core::trace::Tracing>()
+            .expect("Tracing component should be available")
+            .reload_filter(filter);
+        info!(%filter, "replaced the log filter at RPC request");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+//! A stream of chain and mempool events, for indexers and alerting systems.
+//!
+//! Events are published as JSON objects, one per line, to each client of a
+//! Unix domain socket. So clients can subscribe to new blocks, reorgs, and
+//! mempool changes, rather than polling the RPC server.
+//!
+//! Each event has a `type` field:
+//!   - `block_connected`: a block was added to the best chain,
+//!   - `block_disconnected`: a block was removed from the best chain, by a reorg,
+//!   - `reorg`: the best chain switched to a fork, sent before the
+//!     disconnected and connected blocks of the reorg,
+//!   - `mempool_added`: a transaction was added to the mempool,
+//!   - `mempool_removed`: a transaction was mined, or expired,
+//!   - `lagged`: the client fell behind, and missed some events.
+//!
+//! Hashes are hex-encoded, in the byte order used by zcashd and block
+//! explorers.
+
+use std::{error::Error, sync::Arc};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tower::{Service, ServiceExt};
+
+use zebra_chain::{
+    block::{Block, BlockHeaderHash},
+    transaction::{Transaction, TransactionHash},
+    types::BlockHeight,
+};
+use zebra_consensus::mempool::{MempoolChange, RemovalReason, ZebraMempoolState};
+
+use crate::config::EventsSection;
+
+/// The number of events that are buffered for each subscriber.
+///
+/// Subscribers that fall further behind miss some events.
+const EVENT_BUFFER: usize = 10_000;
+
+/// The state service error type.
+type StateError = Box<dyn Error + Send + Sync + 'static>;
+
+/// A chain or mempool event.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A block was added to the best chain.
+    BlockConnected {
+        #[serde(serialize_with = "display")]
+        hash: BlockHeaderHash,
+        height: u32,
+        /// The block, for subscribers that publish block data.
+        #[serde(skip)]
+        block: Arc<Block>,
+    },
+    /// A block was removed from the best chain.
+    BlockDisconnected {
+        #[serde(serialize_with = "display")]
+        hash: BlockHeaderHash,
+        height: u32,
+    },
+    /// The best chain switched to a fork.
+    Reorg {
+        #[serde(serialize_with = "display")]
+        old_tip: BlockHeaderHash,
+        #[serde(serialize_with = "display")]
+        new_tip: BlockHeaderHash,
+        /// The last block that is in both chains.
+        #[serde(serialize_with = "display")]
+        fork_hash: BlockHeaderHash,
+        fork_height: u32,
+    },
+    /// A transaction was added to the mempool.
+    MempoolAdded {
+        #[serde(serialize_with = "display")]
+        txid: TransactionHash,
+        /// The transaction, for subscribers that publish transaction data.
+        #[serde(skip)]
+        transaction: Arc<Transaction>,
+    },
+    /// A transaction was removed from the mempool.
+    MempoolRemoved {
+        #[serde(serialize_with = "display")]
+        txid: TransactionHash,
+        /// `mined` or `expired`.
+        reason: &'static str,
+    },
+    /// A subscriber fell behind, and missed some events.
+    Lagged { missed: u64 },
+}
+
+/// Serializes `value` using its `Display` impl.
+fn display<T: std::fmt::Display, S: serde::Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+impl From<MempoolChange> for Event {
+    fn from(change: MempoolChange) -> Self {
+        match change {
+            MempoolChange::Added(transaction) => Event::MempoolAdded {
+                txid: TransactionHash::from(transaction.as_ref()),
+                transaction,
+            },
+            MempoolChange::Removed { hash, reason } => Event::MempoolRemoved {
+                txid: hash,
+                reason: match reason {
+                    RemovalReason::Mined => "mined",
+                    RemovalReason::Expired => "expired",
+                },
+            },
+        }
+    }
+}
+
+/// The chain and mempool events of a running node.
+///
+/// Clones share the same events.
+#[derive(Clone, Debug)]
+pub struct EventSource {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventSource {
+    /// Returns a receiver for the events after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+/// Start publishing the best chain changes in `state`, and the changes to
+/// `mempool`, as events.
+pub fn start<S>(state: S, mempool: &ZebraMempoolState) -> EventSource
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = StateError>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send,
+{
+    let (sender, _) = broadcast::channel(EVENT_BUFFER);
+
+    let tip_sender = sender.clone();
+    tokio::spawn(async move {
+        if let Err(e) = follow_tip(state, tip_sender).await {
+            error!(?e, "stopped publishing chain events");
+        }
+    });
+
+    let mut changes = mempool.subscribe();
+    let mempool_sender = sender.clone();
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(change) => {
+                    let _ = mempool_sender.send(change.into());
+                }
+                Err(broadcast::RecvError::Lagged(missed)) => {
+                    warn!(missed, "missed some mempool changes");
+                }
+                Err(broadcast::RecvError::Closed) => return,
+            }
+        }
+    });
+
+    EventSource { sender }
+}
+
+/// Send an event for each change to the best chain in `state`.
+///
+/// The state only reports the latest tip, so the blocks between the previous
+/// tip and the new tip are looked up in the state.
+async fn follow_tip<S>(state: S, sender: broadcast::Sender<Event>) -> Result<(), StateError>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = StateError> + Clone,
+{
+    let mut tip = match state.clone().oneshot(zebra_state::Request::GetTip).await? {
+        zebra_state::Response::Tip { hash } => hash,
+        _ => unreachable!("GetTip request can only result in Response::Tip"),
+    };
+
+    loop {
+        let new_tip = match state
+            .clone()
+            .oneshot(zebra_state::Request::AwaitTipChange { tip })
+            .await?
+        {
+            zebra_state::Response::Tip { hash } => hash,
+            _ => unreachable!("AwaitTipChange request can only result in Response::Tip"),
+        };
+
+        for event in tip_change_events(state.clone(), tip, new_tip).await? {
+            // Sending fails if there are no subscribers
+            let _ = sender.send(event);
+        }
+        tip = new_tip;
+    }
+}
+
+/// Returns the events for a best chain tip change from `old_tip` to
+/// `new_tip`.
+///
+/// If `new_tip` is a descendant of `old_tip`, the events are the connected
+/// blocks. Otherwise, they are a reorg event, the disconnected blocks from
+/// `old_tip` back to the fork, then the connected blocks from the fork up to
+/// `new_tip`.
+async fn tip_change_events<S>(
+    state: S,
+    old_tip: BlockHeaderHash,
+    new_tip: BlockHeaderHash,
+) -> Result<Vec<Event>, StateError>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = StateError> + Clone,
+{
+    let mut old_block = get_block(state.clone(), old_tip).await?;
+    let mut new_block = get_block(state.clone(), new_tip).await?;
+    let mut disconnected = Vec::new();
+    let mut connected = Vec::new();
+
+    // Walk back until both branches reach the fork
+    while old_block.hash() != new_block.hash() {
+        let old_height = height(&old_block)?;
+        let new_height = height(&new_block)?;
+
+        if new_height >= old_height {
+            let previous = new_block.header.previous_block_hash;
+            connected.push(new_block);
+            new_block = get_block(state.clone(), previous).await?;
+        }
+        if old_height >= new_height {
+            let previous = old_block.header.previous_block_hash;
+            disconnected.push(old_block);
+            old_block = get_block(state.clone(), previous).await?;
+        }
+    }
+
+    let mut events = Vec::new();
+    if !disconnected.is_empty() {
+        events.push(Event::Reorg {
+            old_tip,
+            new_tip,
+            fork_hash: new_block.hash(),
+            fork_height: height(&new_block)?.0,
+        });
+    }
+    for block in disconnected {
+        events.push(Event::BlockDisconnected {
+            hash: block.hash(),
+            height: height(&block)?.0,
+        });
+    }
+    for block in connected.into_iter().rev() {
+        events.push(Event::BlockConnected {
+            hash: block.hash(),
+            height: height(&block)?.0,
+            block,
+        });
+    }
+
+    Ok(events)
+}
+
+/// Returns the height of `block`.
+fn height(block: &Block) -> Result<BlockHeight, StateError> {
+    Ok(block
+        .coinbase_height()
+        .ok_or("state block has no coinbase height")?)
+}
+
+/// Get the block with `hash` from `state`.
+async fn get_block<S>(state: S, hash: BlockHeaderHash) -> Result<Arc<Block>, StateError>
+where
+    S: Service<zebra_state::Request, Response = zebra_state::Response, Error = StateError>,
+{
+    match state
+        .oneshot(zebra_state::Request::GetBlock { hash })
+        .await?
+    {
+        zebra_state::Response::Block { block } => Ok(block),
+        _ => unreachable!("GetBlock request can only result in Response::Block"),
+    }
+}
+
+/// Spawn the event socket, if it is enabled in `config`.
+///
+/// Each client receives the events from `events`, as JSON lines.
+#[cfg(unix)]
+pub fn spawn(config: EventsSection, events: EventSource) {
+    use std::os::unix::fs::FileTypeExt;
+    use tokio::{io::AsyncWriteExt, net::UnixListener};
+
+    let path = match config.socket_path {
+        Some(path) => path,
+        None => return,
+    };
+
+    // Remove stale sockets from previous runs, but not other files
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if metadata.file_type().is_socket() {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    info!(?path, "Initializing event socket");
+
+    let mut listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Could not open event socket listener");
+            error!("Error: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!(?e, "event socket accept failed");
+                    continue;
+                }
+            };
+
+            let mut receiver = events.subscribe();
+            tokio::spawn(async move {
+                loop {
+                    let event = match receiver.recv().await {
+                        Ok(event) => event,
+                        Err(broadcast::RecvError::Lagged(missed)) => Event::Lagged { missed },
+                        Err(broadcast::RecvError::Closed) => return,
+                    };
+
+                    let mut line =
+                        serde_json::to_vec(&event).expect("events can be serialized as JSON");
+                    line.push(b'\n');
+                    if stream.write_all(&line).await.is_err() {
+                        // The client disconnected
+                        return;
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Spawn the event socket, if it is enabled in `config`.
+///
+/// Unix domain sockets are not supported on this platform.
+#[cfg(not(unix))]
+pub fn spawn(config: EventsSection, _events: EventSource) {
+    if config.socket_path.is_some() {
+        warn!("the event socket is only supported on Unix platforms");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zebra_chain::{serialization::ZcashDeserialize, types::amount::Amount};
+    use zebra_state::in_memory;
+
+    #[tokio::test]
+    async fn connected_blocks() {
+        zebra_test::init();
+
+        let state = in_memory::init();
+        let mut hashes = Vec::new();
+        for bytes in &[
+            &zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..],
+            &zebra_test::vectors::BLOCK_MAINNET_2_BYTES[..],
+        ] {
+            let block = Arc::new(Block::zcash_deserialize(*bytes).unwrap());
+            hashes.push(block.hash());
+            state
+                .clone()
+                .oneshot(zebra_state::Request::AddBlock {
+                    block,
+                    deferred_pool_contribution: Amount::zero(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let events = tip_change_events(state, hashes[0], hashes[2])
+            .await
+            .unwrap();
+        let heights: Vec<u32> = events
+            .iter()
+            .map(|event| match event {
+                Event::BlockConnected { height, .. } => *height,
+                _ => panic!("unexpected event {:?}", event),
+            })
+            .collect();
+        assert_eq!(heights, vec![1, 2]);
+    }
+
+    #[test]
+    fn events_are_json_lines() {
+        zebra_test::init();
+
+        let event = Event::MempoolRemoved {
+            txid: TransactionHash([0; 32]),
+            reason: "mined",
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with(r#"{"type":"mempool_removed","txid":"0000"#));
+        assert!(json.ends_with(r#","reason":"mined"}"#));
+        assert!(!json.contains('\n'));
+    }
+}
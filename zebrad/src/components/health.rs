@@ -0,0 +1,194 @@
+//! An HTTP endpoint for liveness and readiness checks.
+//!
+//! Container orchestrators can use `/healthy` to restart a failed node, and
+//! `/ready` to avoid sending traffic to a node that is still syncing.
+
+use std::{
+    panic,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+use zebra_chain::types::BlockHeight;
+use zebra_network::AddressBook;
+
+use crate::config::HealthSection;
+
+/// The sync and liveness status of a running node, shared between the
+/// syncer and the health endpoint.
+#[derive(Debug, Default)]
+pub struct HealthStatus {
+    /// Set if any thread has panicked.
+    panicked: AtomicBool,
+    /// Set once the syncer has reported a verified tip.
+    has_tip: AtomicBool,
+    /// The height of the verified tip.
+    verified_height: AtomicU32,
+    /// The estimated height of the network tip.
+    estimated_tip_height: AtomicU32,
+}
+
+impl HealthStatus {
+    /// Create a new status, and install a panic hook that marks the node as
+    /// unhealthy.
+    ///
+    /// The previous panic hook is still called after the status is updated.
+    pub fn new() -> Arc<Self> {
+        let status = Arc::new(Self::default());
+
+        let hook_status = status.clone();
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            hook_status.panicked.store(true, Ordering::SeqCst);
+            previous_hook(info);
+        }));
+
+        status
+    }
+
+    /// Record the verified tip height, and the estimated network tip height.
+    pub fn set_heights(&self, verified_height: BlockHeight, estimated_tip_height: BlockHeight) {
+        self.verified_height
+            .store(verified_height.0, Ordering::SeqCst);
+        self.estimated_tip_height
+            .store(estimated_tip_height.0, Ordering::SeqCst);
+        self.has_tip.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the process is alive, and no thread has panicked.
+    pub fn is_healthy(&self) -> bool {
+        !self.panicked.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the verified tip is within `max_blocks_behind` of the
+    /// estimated network tip.
+    pub fn is_synced(&self, max_blocks_behind: u32) -> bool {
+        if !self.has_tip.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        let verified_height = self.verified_height.load(Ordering::SeqCst);
+        let estimated_tip_height = self.estimated_tip_height.load(Ordering::SeqCst);
+
+        estimated_tip_height.saturating_sub(verified_height) <= max_blocks_behind
+    }
+}
+
+/// Spawn the health endpoint, if it is enabled in `config`.
+///
+/// `/ready` uses `address_book` to count the recently live peers.
+pub fn spawn(
+    config: HealthSection,
+    status: Arc<HealthStatus>,
+    address_book: Arc<Mutex<AddressBook>>,
+) {
+    let addr = match config.endpoint_addr {
+        Some(addr) => addr,
+        None => return,
+    };
+
+    info!(?addr, "Initializing health endpoint");
+
+    let service = make_service_fn(move |_| {
+        let config = config.clone();
+        let status = status.clone();
+        let address_book = address_book.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                request_handler(req, config.clone(), status.clone(), address_book.clone())
+            }))
+        }
+    });
+
+    tokio::spawn(async move {
+        let server = match Server::try_bind(&addr) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not open health endpoint listener");
+                error!("Error: {}", e);
+                return;
+            }
+        }
+        .serve(service);
+
+        if let Err(e) = server.await {
+            error!("Server error: {}", e);
+        }
+    });
+}
+
+/// Returns the number of peers in `address_book` that we could currently be
+/// connected to.
+pub fn live_peer_count(address_book: &Mutex<AddressBook>) -> usize {
+    let address_book = address_book.lock().unwrap();
+    address_book
+        .peers()
+        .filter(|peer| address_book.is_potentially_connected(&peer.addr))
+        .count()
+}
+
+async fn request_handler(
+    req: Request<Body>,
+    config: HealthSection,
+    status: Arc<HealthStatus>,
+    address_book: Arc<Mutex<AddressBook>>,
+) -> Result<Response<Body>, hyper::Error> {
+    let (code, body) = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/healthy") => {
+            if status.is_healthy() {
+                (StatusCode::OK, "ok".to_owned())
+            } else {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "a thread has panicked".to_owned(),
+                )
+            }
+        }
+        (&Method::GET, "/ready") => {
+            let peers = live_peer_count(&address_book);
+            if !status.is_synced(config.ready_max_blocks_behind) {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "not synced to the network tip".to_owned(),
+                )
+            } else if peers < config.ready_min_peers {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("only {} live peers, need {}", peers, config.ready_min_peers),
+                )
+            } else {
+                (StatusCode::OK, "ok".to_owned())
+            }
+        }
+        _ => (StatusCode::NOT_FOUND, "".to_owned()),
+    };
+
+    Ok(Response::builder()
+        .status(code)
+        .body(Body::from(body))
+        .expect("response with known status code cannot fail"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_synced() {
+        let status = HealthStatus::default();
+        assert!(!status.is_synced(2), "no tip means not synced");
+
+        status.set_heights(BlockHeight(100), BlockHeight(102));
+        assert!(status.is_synced(2));
+        assert!(!status.is_synced(1));
+
+        // The verified tip can be ahead of a stale estimate
+        status.set_heights(BlockHeight(110), BlockHeight(102));
+        assert!(status.is_synced(0));
+    }
+}
@@ -0,0 +1,92 @@
+//! A registry of blocks that are being downloaded or verified.
+//!
+//! Every path that downloads blocks should share the same registry, so that
+//! each block is only requested and verified once at a time. Zebra doesn't
+//! download gossiped blocks yet, so the syncer is currently the only user.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use zebra_chain::block::BlockHeaderHash;
+
+/// A shared set of the hashes of blocks that are being downloaded or
+/// verified.
+#[derive(Clone, Debug, Default)]
+pub struct InFlightBlocks {
+    hashes: Arc<Mutex<HashSet<BlockHeaderHash>>>,
+}
+
+impl InFlightBlocks {
+    /// Returns a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the block with `hash` as in flight.
+    ///
+    /// Returns a guard that keeps the block registered until it is dropped,
+    /// or `None` if the block is already in flight.
+    pub fn try_register(&self, hash: BlockHeaderHash) -> Option<InFlightGuard> {
+        let newly_registered = self
+            .hashes
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .insert(hash);
+
+        if newly_registered {
+            Some(InFlightGuard {
+                hash,
+                hashes: self.hashes.clone(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of blocks in flight.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.hashes
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .len()
+    }
+}
+
+/// Keeps a block registered as in flight, until the guard is dropped.
+#[derive(Debug)]
+#[must_use = "the block is unregistered when the guard is dropped"]
+pub struct InFlightGuard {
+    hash: BlockHeaderHash,
+    hashes: Arc<Mutex<HashSet<BlockHeaderHash>>>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.hashes
+            .lock()
+            .expect("mutex should be unpoisoned")
+            .remove(&self.hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers_each_block_once() {
+        let in_flight = InFlightBlocks::new();
+        let hash = BlockHeaderHash([1; 32]);
+
+        let guard = in_flight.try_register(hash).expect("new block registers");
+        assert!(in_flight.try_register(hash).is_none());
+        assert_eq!(in_flight.len(), 1);
+
+        drop(guard);
+        assert_eq!(in_flight.len(), 0);
+        assert!(in_flight.try_register(hash).is_some());
+    }
+}
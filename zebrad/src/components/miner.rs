@@ -0,0 +1,221 @@
+//! An internal CPU miner, for tests and private networks.
+//!
+//! The miner builds block templates on the current state tip, searches for
+//! Equihash solutions that meet the tip's difficulty threshold, and submits
+//! mined blocks to the chain verifier.
+//!
+//! The state can't calculate difficulty adjustments or Sapling note
+//! commitment tree roots yet, so mined blocks copy them from the tip. The
+//! mempool doesn't store transactions yet, so mined blocks only contain a
+//! coinbase transaction.
+
+use std::{convert::TryInto, sync::Arc};
+
+use chrono::{Duration, Utc};
+use color_eyre::eyre::{eyre, Report};
+use rand::Rng;
+use tower::{Service, ServiceExt};
+
+use zebra_chain::{
+    addresses::transparent::TransparentAddress,
+    block::{Block, BlockHeaderHash},
+    equihash_solution::EquihashSolution,
+    work::CompactDifficulty,
+    Network,
+};
+use zebra_consensus::{
+    block_template::{BlockTemplate, ChainContext, Recipients},
+    parameters::FundingStreamReceiver,
+};
+use zebra_state as zs;
+
+use crate::config::MiningSection;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The number of nonces the miner tries before it checks for a new tip, and
+/// builds a new template.
+const NONCES_PER_TEMPLATE: usize = 8;
+
+/// Spawn the internal miner, if it is enabled in `config`.
+///
+/// Returns an error if the configured addresses are missing or invalid.
+pub fn spawn<ZS, ZV>(
+    config: MiningSection,
+    network: Network,
+    state: ZS,
+    verifier: ZV,
+) -> Result<(), Report>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = Error> + Send + Clone + 'static,
+    ZS::Future: Send,
+    ZV: Service<Arc<Block>, Response = BlockHeaderHash, Error = Error> + Send + Clone + 'static,
+    ZV::Future: Send,
+{
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let recipients = recipients(&config)?;
+    let coinbase_data = config.coinbase_data.into_bytes();
+
+    tokio::spawn(async move {
+        if let Err(e) = mine(network, recipients, coinbase_data, state, verifier).await {
+            tracing::error!(?e, "internal miner stopped");
+        }
+    });
+
+    Ok(())
+}
+
+/// Returns the coinbase recipients for the addresses in `config`.
+fn recipients(config: &MiningSection) -> Result<Recipients, Report> {
+    let parse = |address: &str| -> Result<_, Report> {
+        let address: TransparentAddress = address
+            .parse()
+            .map_err(|e| eyre!("invalid mining address {:?}: {}", address, e))?;
+        Ok(address.output_script())
+    };
+
+    let miner = config
+        .miner_address
+        .as_deref()
+        .ok_or_else(|| eyre!("the internal miner needs a miner_address"))?;
+
+    Ok(Recipients {
+        miner: parse(miner)?,
+        founders: config.founders_address.as_deref().map(parse).transpose()?,
+        funding_streams: FundingStreamReceiver::ALL
+            .iter()
+            .zip(config.funding_stream_addresses.iter())
+            .map(|(receiver, address)| Ok((*receiver, parse(address)?)))
+            .collect::<Result<_, Report>>()?,
+    })
+}
+
+/// Mine blocks on the state tip, and submit them to `verifier`, forever.
+async fn mine<ZS, ZV>(
+    network: Network,
+    recipients: Recipients,
+    coinbase_data: Vec<u8>,
+    state: ZS,
+    mut verifier: ZV,
+) -> Result<(), Report>
+where
+    ZS: Service<zs::Request, Response = zs::Response, Error = Error> + Send + Clone + 'static,
+    ZS::Future: Send,
+    ZV: Service<Arc<Block>, Response = BlockHeaderHash, Error = Error> + Send + Clone + 'static,
+    ZV::Future: Send,
+{
+    loop {
+        let tip = zs::initial_tip(state.clone())
+            .await?
+            .ok_or_else(|| eyre!("the internal miner needs a genesis block"))?;
+        let tip_height = tip
+            .coinbase_height()
+            .expect("verified blocks have a coinbase height");
+
+        // Block times must be later than the median time of recent blocks, so
+        // make sure the time always increases
+        let time = std::cmp::max(Utc::now(), tip.header.time + Duration::seconds(1));
+        let context = ChainContext {
+            network,
+            height: tip_height
+                .next()
+                .expect("valid chain tips are below the maximum height"),
+            previous_block_hash: tip.hash(),
+            commitment_bytes: tip.header.commitment_bytes,
+            time,
+            bits: tip.header.bits,
+        };
+
+        let template = BlockTemplate::new(context, &recipients, coinbase_data.clone(), Vec::new())?;
+        let block = tokio::task::spawn_blocking(move || solve(&template)).await?;
+
+        if let Some(block) = block {
+            let hash = block.hash();
+            let height = block.coinbase_height();
+            metrics::counter!("miner.mined.block.count", 1);
+
+            let result = verifier
+                .ready_and()
+                .await
+                .map_err(|e| eyre!(e))?
+                .call(Arc::new(block))
+                .await;
+            match result {
+                Ok(hash) => tracing::info!(?hash, ?height, "mined block was verified"),
+                Err(e) => tracing::warn!(?e, ?hash, ?height, "mined block was rejected"),
+            }
+        }
+    }
+}
+
+/// Returns a block for `template` that meets its difficulty threshold, or
+/// `None` if none of the nonces tried have a valid solution.
+///
+/// Solving is slow, so this function should be run on a blocking thread.
+fn solve(template: &BlockTemplate) -> Option<Block> {
+    let threshold = CompactDifficulty(template.context.bits).to_expanded()?;
+
+    let mut nonce = [0; 32];
+    rand::thread_rng().fill(&mut nonce[..]);
+
+    for _ in 0..NONCES_PER_TEMPLATE {
+        let header = template.header(nonce, EquihashSolution([0; 1344]));
+        for solution in header.equihash_solutions() {
+            let block = template.block(nonce, solution);
+            if threshold.is_met_by(&block.hash()) {
+                return Some(block);
+            }
+        }
+
+        increment(&mut nonce);
+    }
+
+    None
+}
+
+/// Increment the low 128 bits of `nonce`, as a little-endian integer.
+fn increment(nonce: &mut [u8; 32]) {
+    let low = u128::from_le_bytes(nonce[..16].try_into().expect("slice is 16 bytes"));
+    nonce[..16].copy_from_slice(&low.wrapping_add(1).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonce_increment() {
+        zebra_test::init();
+
+        let mut nonce = [0xff; 32];
+        nonce[1] = 0;
+        increment(&mut nonce);
+        assert_eq!(&nonce[..2], &[0, 1]);
+        assert_eq!(&nonce[16..], &[0xff; 16]);
+    }
+
+    #[test]
+    fn recipients_from_config() {
+        zebra_test::init();
+
+        let config = MiningSection {
+            enabled: true,
+            miner_address: Some("t1bmMa1wJDFdbc2TiURQP5BbBz6jHjUBuHq".to_owned()),
+            funding_stream_addresses: vec!["t3Vz22vK5z2LcKEdg16Yv4FFneEL1zg9ojd".to_owned()],
+            ..MiningSection::default()
+        };
+        let parsed = recipients(&config).expect("addresses are valid");
+        assert_eq!(parsed.miner.0[0], 0x76);
+        assert!(parsed.founders.is_none());
+        assert_eq!(parsed.funding_streams.len(), 1);
+
+        let config = MiningSection {
+            miner_address: None,
+            ..config
+        };
+        assert!(recipients(&config).is_err());
+    }
+}
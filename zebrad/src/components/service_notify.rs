@@ -0,0 +1,211 @@
+//! Readiness and liveness notifications for service managers.
+//!
+//! On Unix, `zebrad` implements systemd's `sd_notify` protocol. If systemd
+//! starts `zebrad` with `Type=notify`, it sends `READY=1` once the state is
+//! open and a peer is connected. If the unit also has `WatchdogSec=`, it sends
+//! `WATCHDOG=1` from a task on the async runtime. So systemd restarts the node
+//! if the runtime stops scheduling tasks.
+//!
+//! On Windows, `zebrad windows-service` runs the node as a Windows service,
+//! which is reported as running once the node is ready.
+//!
+//! If `zebrad` is not run by a service manager, notifications are ignored.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use zebra_network::AddressBook;
+
+use crate::components::health;
+
+/// The interval between checks for connected peers, before the node is ready.
+const READY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Spawn a task that notifies the service manager when `address_book` has a
+/// live peer, and keeps the systemd watchdog alive.
+///
+/// Call this after the state is open.
+pub fn spawn(address_book: Arc<Mutex<AddressBook>>) {
+    tokio::spawn(async move {
+        while health::live_peer_count(&address_book) == 0 {
+            tokio::time::delay_for(READY_CHECK_INTERVAL).await;
+        }
+
+        info!("state is open and peers are connected, notifying the service manager");
+        ready();
+    });
+
+    #[cfg(unix)]
+    systemd::spawn_watchdog();
+}
+
+/// Tell the service manager that the node is ready.
+fn ready() {
+    #[cfg(unix)]
+    systemd::notify("READY=1\nSTATUS=Syncing with the network");
+
+    #[cfg(windows)]
+    windows::set_running();
+}
+
+/// Tell the service manager that the node is stopping.
+pub fn stopping() {
+    #[cfg(unix)]
+    systemd::notify("STOPPING=1");
+}
+
+#[cfg(unix)]
+mod systemd {
+    use std::{env, os::unix::net::UnixDatagram, time::Duration};
+
+    /// Send `state` to systemd, if it started this process with a notify
+    /// socket.
+    pub(super) fn notify(state: &str) {
+        let path = match env::var_os("NOTIFY_SOCKET") {
+            Some(path) => path,
+            None => return,
+        };
+
+        // The standard library does not support abstract socket addresses
+        if path.to_string_lossy().starts_with('@') {
+            warn!(?path, "abstract NOTIFY_SOCKET addresses are not supported");
+            return;
+        }
+
+        let result =
+            UnixDatagram::unbound().and_then(|socket| socket.send_to(state.as_bytes(), &path));
+        if let Err(e) = result {
+            warn!(?path, ?e, "could not notify systemd");
+        }
+    }
+
+    /// Returns the interval between watchdog notifications, if systemd
+    /// enabled the watchdog for this process.
+    ///
+    /// Notifications are sent at half the watchdog timeout, as recommended
+    /// by `sd_watchdog_enabled(3)`.
+    pub(super) fn watchdog_interval() -> Option<Duration> {
+        if let Some(pid) = env::var_os("WATCHDOG_PID") {
+            if pid.to_string_lossy().parse::<u32>().ok() != Some(std::process::id()) {
+                return None;
+            }
+        }
+
+        let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+        if usec == 0 {
+            return None;
+        }
+
+        Some(Duration::from_micros(usec) / 2)
+    }
+
+    /// Spawn a task that sends watchdog notifications, if systemd enabled the
+    /// watchdog.
+    pub(super) fn spawn_watchdog() {
+        let interval = match watchdog_interval() {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        info!(?interval, "sending systemd watchdog notifications");
+        tokio::spawn(async move {
+            loop {
+                notify("WATCHDOG=1");
+                tokio::time::delay_for(interval).await;
+            }
+        });
+    }
+}
+
+#[cfg(windows)]
+pub(crate) mod windows {
+    use std::{sync::Mutex, time::Duration};
+
+    use windows_service::{
+        service::{
+            ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+        },
+        service_control_handler::ServiceStatusHandle,
+    };
+
+    lazy_static::lazy_static! {
+        /// The status handle of the Windows service, if `zebrad` is running
+        /// as a service.
+        static ref STATUS_HANDLE: Mutex<Option<ServiceStatusHandle>> = Mutex::new(None);
+    }
+
+    /// Report that the service is starting, and store `handle` for later
+    /// status changes.
+    pub(crate) fn set_starting(handle: ServiceStatusHandle) {
+        *STATUS_HANDLE.lock().unwrap() = Some(handle);
+        set_state(ServiceState::StartPending);
+    }
+
+    /// Report that the service is running.
+    pub(super) fn set_running() {
+        set_state(ServiceState::Running);
+    }
+
+    /// Report that the service has stopped.
+    pub(crate) fn set_stopped() {
+        set_state(ServiceState::Stopped);
+    }
+
+    /// Report `state` to the service control manager, if `zebrad` is running
+    /// as a service.
+    fn set_state(state: ServiceState) {
+        let handle = match *STATUS_HANDLE.lock().unwrap() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        let controls_accepted = match state {
+            ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            _ => ServiceControlAccept::empty(),
+        };
+        let status = ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted,
+            exit_code: ServiceExitCode::Win32(0),
+            checkpoint: 0,
+            wait_hint: Duration::from_secs(0),
+            process_id: None,
+        };
+
+        if let Err(e) = handle.set_service_status(status) {
+            warn!(?state, ?e, "could not set the Windows service status");
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::systemd::watchdog_interval;
+
+    use std::{env, time::Duration};
+
+    #[test]
+    fn watchdog_interval_from_env() {
+        zebra_test::init();
+
+        env::remove_var("WATCHDOG_PID");
+        env::remove_var("WATCHDOG_USEC");
+        assert_eq!(watchdog_interval(), None);
+
+        env::set_var("WATCHDOG_USEC", "30000000");
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+
+        // The watchdog is for another process
+        env::set_var("WATCHDOG_PID", (std::process::id() + 1).to_string());
+        assert_eq!(watchdog_interval(), None);
+
+        env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+
+        env::remove_var("WATCHDOG_PID");
+        env::remove_var("WATCHDOG_USEC");
+    }
+}
@@ -0,0 +1,113 @@
+//! Estimating the height of the network chain tip.
+//!
+//! The estimate combines the start heights advertised by our peers, and the
+//! number of blocks we expect the network to have produced since our own tip,
+//! based on the target block spacing. So it is useful before the initial sync
+//! has finished, when we can't ask peers for the tip directly.
+
+use chrono::{DateTime, Utc};
+
+use zebra_chain::{types::BlockHeight, Network};
+use zebra_consensus::parameters::NetworkUpgrade;
+
+/// Returns the estimated network chain tip height, using:
+///  - `tip_height` and `tip_time`: the height and header time of our tip,
+///  - `now`: the current time, and
+///  - `peer_heights`: the start heights advertised by our live peers.
+///
+/// The clock-based estimate uses the target spacing at `tip_height`, so it
+/// underestimates the tip if our tip is before Blossom activation.
+///
+/// We use the median peer height, so a minority of peers can't make us think
+/// we're far behind the network tip. Peers only send their height when they
+/// connect, so we also use the clock-based estimate if it is higher.
+pub fn estimate_network_tip(
+    network: Network,
+    tip_height: BlockHeight,
+    tip_time: DateTime<Utc>,
+    now: DateTime<Utc>,
+    peer_heights: impl IntoIterator<Item = BlockHeight>,
+) -> BlockHeight {
+    let spacing = NetworkUpgrade::target_spacing_for_height(network, tip_height);
+    let elapsed_blocks = (now - tip_time).num_seconds().max(0) / spacing.num_seconds();
+    let clock_estimate = (tip_height + elapsed_blocks).unwrap_or(BlockHeight::MAX);
+
+    let peer_estimate = median(peer_heights.into_iter().map(|height| height.0).collect());
+
+    clock_estimate
+        .max(BlockHeight(peer_estimate.unwrap_or(0)))
+        .max(tip_height)
+}
+
+/// Returns the median of `values`, or `None` if `values` is empty.
+///
+/// If there are an even number of values, returns the lower median.
+fn median(mut values: Vec<u32>) -> Option<u32> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable();
+    Some(values[(values.len() - 1) / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+    use zebra_chain::Network::*;
+
+    #[test]
+    fn median_values() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![5]), Some(5));
+        assert_eq!(median(vec![7, 1]), Some(1));
+        assert_eq!(median(vec![9, 1, 5]), Some(5));
+    }
+
+    #[test]
+    fn estimate_from_clock() {
+        let now = Utc::now();
+        let tip_height = BlockHeight(1_000_000);
+
+        // Post-Blossom blocks are 75 seconds apart
+        let estimate = estimate_network_tip(
+            Mainnet,
+            tip_height,
+            now - Duration::seconds(75 * 10),
+            now,
+            vec![],
+        );
+        assert_eq!(estimate, BlockHeight(1_000_010));
+
+        // A tip in the future doesn't reduce the estimate
+        let estimate =
+            estimate_network_tip(Mainnet, tip_height, now + Duration::hours(1), now, vec![]);
+        assert_eq!(estimate, tip_height);
+    }
+
+    #[test]
+    fn estimate_from_peers() {
+        let now = Utc::now();
+        let tip_height = BlockHeight(1_000);
+
+        // A single outlier can't inflate the estimate
+        let estimate = estimate_network_tip(
+            Mainnet,
+            tip_height,
+            now,
+            now,
+            vec![
+                BlockHeight(2_000),
+                BlockHeight(2_001),
+                BlockHeight(u32::MAX),
+            ],
+        );
+        assert_eq!(estimate, BlockHeight(2_001));
+
+        // Peers below our tip don't reduce the estimate
+        let estimate = estimate_network_tip(Mainnet, tip_height, now, now, vec![BlockHeight(10)]);
+        assert_eq!(estimate, tip_height);
+    }
+}
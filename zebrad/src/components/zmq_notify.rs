@@ -0,0 +1,212 @@
+//! zcashd-compatible ZeroMQ notifications.
+//!
+//! Publishes the same topics and message format as zcashd's
+//! `-zmqpubhashblock`, `-zmqpubhashtx`, `-zmqpubrawblock`, and `-zmqpubrawtx`
+//! options, so existing ZMQ subscribers can follow a Zebra node.
+//!
+//! Each notification is a three-part message:
+//!   - the topic: `hashblock`, `hashtx`, `rawblock`, or `rawtx`,
+//!   - the body: a hash in display byte order, or a serialized block or
+//!     transaction,
+//!   - a 4-byte little-endian sequence number, which increases by one for each
+//!     message on the topic.
+//!
+//! Like zcashd, transaction notifications are published when a transaction
+//! is added to the mempool, and for each transaction in a connected block.
+//!
+//! ZeroMQ support requires the `zmq` feature, which links `libzmq`.
+
+use color_eyre::eyre::Report;
+
+use crate::{components::events::EventSource, config::ZmqSection};
+
+/// Returns the configured topics, and their endpoint addresses.
+#[cfg(any(feature = "zmq", test))]
+fn topics(config: &ZmqSection) -> Vec<(&'static str, String)> {
+    [
+        ("hashblock", &config.pub_hash_block),
+        ("hashtx", &config.pub_hash_tx),
+        ("rawblock", &config.pub_raw_block),
+        ("rawtx", &config.pub_raw_tx),
+    ]
+    .iter()
+    .filter_map(|&(topic, address)| address.clone().map(|address| (topic, address)))
+    .collect()
+}
+
+/// Spawn the ZeroMQ publisher, if any topics are enabled in `config`.
+///
+/// Notifications are published for the chain and mempool changes in
+/// `events`.
+#[cfg(feature = "zmq")]
+pub fn spawn(config: ZmqSection, events: EventSource) -> Result<(), Report> {
+    use std::collections::HashMap;
+
+    use color_eyre::eyre::eyre;
+    use tokio::sync::broadcast;
+
+    if !config.is_enabled() {
+        return Ok(());
+    }
+    let topics = topics(&config);
+
+    // Topics with the same address share a socket, like zcashd
+    let context = zmq::Context::new();
+    let mut sockets: HashMap<String, zmq::Socket> = HashMap::new();
+    for (topic, address) in &topics {
+        if !sockets.contains_key(address) {
+            let socket = context
+                .socket(zmq::PUB)
+                .map_err(|e| eyre!("could not create ZMQ socket: {}", e))?;
+            socket
+                .bind(address)
+                .map_err(|e| eyre!("could not bind ZMQ socket to {}: {}", address, e))?;
+            sockets.insert(address.clone(), socket);
+        }
+        info!(?topic, ?address, "publishing ZMQ notifications");
+    }
+
+    let mut publisher = Publisher {
+        topics: topics
+            .into_iter()
+            .map(|(topic, address)| (topic, address, 0))
+            .collect(),
+        sockets,
+    };
+    let mut receiver = events.subscribe();
+
+    // ZMQ sockets are blocking, so they get their own thread
+    std::thread::spawn(move || {
+        // Keep the context alive as long as the sockets
+        let _context = context;
+        loop {
+            match futures::executor::block_on(receiver.recv()) {
+                Ok(event) => publisher.publish(&event),
+                Err(broadcast::RecvError::Lagged(missed)) => {
+                    warn!(missed, "ZMQ publisher missed some events");
+                }
+                Err(broadcast::RecvError::Closed) => return,
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Spawn the ZeroMQ publisher, if any topics are enabled in `config`.
+///
+/// This build does not have the `zmq` feature, so enabled topics are an
+/// error.
+#[cfg(not(feature = "zmq"))]
+pub fn spawn(config: ZmqSection, _events: EventSource) -> Result<(), Report> {
+    if !config.is_enabled() {
+        Ok(())
+    } else {
+        Err(color_eyre::eyre::eyre!(
+            "ZMQ notifications are enabled in the config, but zebrad was built without the zmq feature"
+        ))
+    }
+}
+
+/// Publishes ZMQ notifications for chain and mempool events.
+#[cfg(feature = "zmq")]
+struct Publisher {
+    /// The enabled topics, their addresses, and their next sequence numbers.
+    topics: Vec<(&'static str, String, u32)>,
+    /// The socket for each address.
+    sockets: std::collections::HashMap<String, zmq::Socket>,
+}
+
+#[cfg(feature = "zmq")]
+impl Publisher {
+    /// Publish the notifications for `event`.
+    fn publish(&mut self, event: &crate::components::events::Event) {
+        use crate::components::events::Event;
+        use zebra_chain::{serialization::ZcashSerialize, transaction::TransactionHash};
+
+        match event {
+            Event::BlockConnected { hash, block, .. } => {
+                self.send("hashblock", || display_order(hash.0));
+                self.send("rawblock", || {
+                    block
+                        .zcash_serialize_to_vec()
+                        .expect("serializing into a vec never fails")
+                });
+                for transaction in &block.transactions {
+                    let txid = TransactionHash::from(transaction.as_ref());
+                    self.send("hashtx", || display_order(txid.0));
+                    self.send("rawtx", || {
+                        transaction
+                            .zcash_serialize_to_vec()
+                            .expect("serializing into a vec never fails")
+                    });
+                }
+            }
+            Event::MempoolAdded { txid, transaction } => {
+                self.send("hashtx", || display_order(txid.0));
+                self.send("rawtx", || {
+                    transaction
+                        .zcash_serialize_to_vec()
+                        .expect("serializing into a vec never fails")
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Send a message on `topic`, if it is enabled.
+    ///
+    /// The body is only created for enabled topics.
+    fn send(&mut self, topic: &'static str, body: impl FnOnce() -> Vec<u8>) {
+        let sockets = &self.sockets;
+        let (address, sequence) = match self.topics.iter_mut().find(|(t, _, _)| *t == topic) {
+            Some((_, address, sequence)) => (address, sequence),
+            None => return,
+        };
+
+        let message = [
+            topic.as_bytes().to_vec(),
+            body(),
+            sequence.to_le_bytes().to_vec(),
+        ];
+        if let Err(e) = sockets[address.as_str()].send_multipart(message.iter(), 0) {
+            warn!(?topic, ?e, "could not publish ZMQ notification");
+        }
+        *sequence = sequence.wrapping_add(1);
+    }
+}
+
+/// Returns `hash` in the byte order used by zcashd's RPCs and ZMQ
+/// notifications.
+#[cfg(feature = "zmq")]
+fn display_order(hash: [u8; 32]) -> Vec<u8> {
+    let mut bytes = hash.to_vec();
+    bytes.reverse();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_by_default() {
+        zebra_test::init();
+
+        assert!(!ZmqSection::default().is_enabled());
+        assert!(topics(&ZmqSection::default()).is_empty());
+    }
+
+    #[test]
+    fn enabled_topics() {
+        zebra_test::init();
+
+        let config = ZmqSection {
+            pub_hash_block: Some("tcp://127.0.0.1:28332".to_string()),
+            pub_raw_tx: Some("tcp://127.0.0.1:28332".to_string()),
+            ..ZmqSection::default()
+        };
+        let topics: Vec<_> = topics(&config).into_iter().map(|(t, _)| t).collect();
+        assert_eq!(topics, vec!["hashblock", "rawtx"]);
+    }
+}
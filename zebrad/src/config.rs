@@ -4,11 +4,13 @@
 //! application's configuration file and/or command-line options
 //! for specifying it.
 
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
+use zebra_consensus::mempool::Config as MempoolSection;
 use zebra_network::Config as NetworkSection;
+use zebra_rpc::Config as RpcSection;
 use zebra_state::Config as StateSection;
 
 /// Configuration for `zebrad`.
@@ -19,17 +21,53 @@ use zebra_state::Config as StateSection;
 #[derive(Clone, Default, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, default)]
 pub struct ZebradConfig {
+    /// Event socket configuration
+    pub events: EventsSection,
+
+    /// Health endpoint configuration
+    pub health: HealthSection,
+
+    /// Mempool policy configuration
+    pub mempool: MempoolSection,
+
     /// Metrics configuration
     pub metrics: MetricsSection,
 
+    /// Internal miner configuration
+    pub mining: MiningSection,
+
     /// Networking configuration
     pub network: NetworkSection,
 
+    /// RPC configuration
+    pub rpc: RpcSection,
+
     /// State configuration
     pub state: StateSection,
 
+    /// Sync configuration
+    pub sync: SyncSection,
+
     /// Tracing configuration
     pub tracing: TracingSection,
+
+    /// ZeroMQ notification configuration
+    pub zmq: ZmqSection,
+}
+
+impl ZebradConfig {
+    /// Returns the peer cache file for the configured network.
+    ///
+    /// If `network.peer_cache_file` is not set, peers are cached in a
+    /// network-specific subdirectory of the state cache directory, like
+    /// `peers/mainnet/peers.txt`.
+    pub fn peer_cache_file(&self) -> Option<PathBuf> {
+        self.network.peer_cache_file.clone().or_else(|| {
+            self.state
+                .network_dir("peers", self.network.network)
+                .map(|dir| dir.join("peers.txt"))
+        })
+    }
 }
 
 /// Tracing configuration section.
@@ -63,6 +101,156 @@ impl Default for MetricsSection {
     }
 }
 
+/// Internal miner configuration section.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct MiningSection {
+    /// Mine blocks on the state tip, using the internal CPU miner.
+    ///
+    /// The internal miner is very slow, so it is only useful on networks with
+    /// a low difficulty, like private test networks.
+    pub enabled: bool,
+
+    /// The transparent address that receives the miner subsidy and
+    /// transaction fees.
+    ///
+    /// Required if the miner is enabled.
+    pub miner_address: Option<String>,
+
+    /// The transparent address that receives the founders' reward.
+    ///
+    /// Required before Canopy activation and the first halving.
+    pub founders_address: Option<String>,
+
+    /// The transparent addresses that receive the funding streams, in order:
+    /// the ECC, the Zcash Foundation, and Major Grants.
+    ///
+    /// By default, the funding streams are paid to the ZIP-214 addresses.
+    /// These addresses override them, for testing.
+    pub funding_stream_addresses: Vec<String>,
+
+    /// Extra data included in the coinbase input of mined blocks.
+    pub coinbase_data: String,
+}
+
+/// Event socket configuration section.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct EventsSection {
+    /// The path of a Unix domain socket that publishes chain and mempool
+    /// events, as JSON lines.
+    ///
+    /// If this is `None`, the event socket is disabled.
+    pub socket_path: Option<PathBuf>,
+}
+
+/// ZeroMQ notification configuration section.
+///
+/// Each field is the endpoint address for a zcashd-compatible notification
+/// topic, like `tcp://127.0.0.1:28332`. Topics can share an address. If a
+/// field is `None`, that topic is disabled.
+///
+/// Enabling any topic requires a `zebrad` built with the `zmq` feature.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ZmqSection {
+    /// The address for block hash notifications, like `-zmqpubhashblock`.
+    pub pub_hash_block: Option<String>,
+
+    /// The address for transaction hash notifications, like `-zmqpubhashtx`.
+    pub pub_hash_tx: Option<String>,
+
+    /// The address for serialized block notifications, like
+    /// `-zmqpubrawblock`.
+    pub pub_raw_block: Option<String>,
+
+    /// The address for serialized transaction notifications, like
+    /// `-zmqpubrawtx`.
+    pub pub_raw_tx: Option<String>,
+}
+
+impl ZmqSection {
+    /// Returns true if any notification topic is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.pub_hash_block.is_some()
+            || self.pub_hash_tx.is_some()
+            || self.pub_raw_block.is_some()
+            || self.pub_raw_tx.is_some()
+    }
+}
+
+/// Health endpoint configuration section.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct HealthSection {
+    /// The address used for the `/healthy` and `/ready` HTTP endpoints.
+    ///
+    /// If this is `None`, the health endpoint is disabled.
+    pub endpoint_addr: Option<SocketAddr>,
+
+    /// The maximum number of blocks the verified tip can be behind the
+    /// estimated network tip, before the node is reported as not ready.
+    pub ready_max_blocks_behind: u32,
+
+    /// The minimum number of recently live peers required for the node to be
+    /// reported as ready.
+    pub ready_min_peers: usize,
+}
+
+impl Default for HealthSection {
+    fn default() -> Self {
+        Self {
+            endpoint_addr: None,
+            ready_max_blocks_behind: 2,
+            ready_min_peers: 1,
+        }
+    }
+}
+
+/// Sync configuration section.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct SyncSection {
+    /// The syncer restarts its block downloads if the verified tip doesn't
+    /// change for this long, while peers report a higher tip.
+    pub stall_timeout: Duration,
+
+    /// Start loading the Groth16 parameters when `zebrad` starts, so that the
+    /// first fully verified blocks aren't delayed.
+    ///
+    /// The parameters are loaded in the background, so the sync doesn't wait
+    /// for them. If they aren't in the cache directory, they are downloaded,
+    /// which can take some time. If this is `false`, the parameters are loaded
+    /// when they are first used.
+    pub warm_up_verifiers: bool,
+
+    /// Stop syncing after the block at this height.
+    ///
+    /// Below the final checkpoint, blocks are verified in checkpoint ranges,
+    /// so the sync stops at the highest checkpoint at or below this height.
+    ///
+    /// Useful for benchmarks, fixed-height state snapshots, and debugging
+    /// consensus issues at a specific height.
+    pub stop_at_height: Option<u32>,
+
+    /// Exit `zebrad` when the sync reaches `stop_at_height`.
+    ///
+    /// If this is `false`, `zebrad` keeps running its other services, like
+    /// the RPC server, after the sync stops.
+    pub exit_at_stop_height: bool,
+}
+
+impl Default for SyncSection {
+    fn default() -> Self {
+        Self {
+            stall_timeout: Duration::from_secs(5 * 60),
+            warm_up_verifiers: true,
+            stop_at_height: None,
+            exit_at_stop_height: false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use color_eyre::eyre::Result;
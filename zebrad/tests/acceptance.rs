@@ -11,7 +11,17 @@
 #![forbid(unsafe_code)]
 
 use abscissa_core::testing::prelude::*;
+use color_eyre::eyre::Result;
 use once_cell::sync::Lazy;
+use std::{fs, path::Path, time::Duration};
+use tempdir::TempDir;
+
+use zebra_chain::Network::Mainnet;
+use zebra_test::{
+    command::{test_cmd, CommandExt},
+    network::{FakePeer, Script, MAINNET_MAGIC},
+};
+use zebrad::config::ZebradConfig;
 
 /// Executes your application binary via `cargo run`.
 pub static RUNNER: Lazy<CmdRunner> = Lazy::new(CmdRunner::default);
@@ -28,3 +38,105 @@ fn version_no_args() {
     cmd.stdout().expect_regex(r"\A\w+ [\d\.\-]+\z");
 }
 */
+
+/// How long to wait for `zebrad` to talk to a fake peer.
+const FAKE_PEER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Returns a temporary directory containing a `zebrad.toml` config file.
+///
+/// The config uses the temporary directory for the state cache, and only
+/// connects to `initial_peers`.
+fn testdir(initial_peers: &[String]) -> Result<TempDir> {
+    let dir = TempDir::new("zebrad_tests")?;
+
+    let mut config = ZebradConfig::default();
+    config.network.network = Mainnet;
+    config.network.listen_addr = "127.0.0.1:0".parse()?;
+    config.network.initial_mainnet_peers = initial_peers.iter().cloned().collect();
+    config.state.cache_dir = Some(dir.path().to_owned());
+    // Don't download the Groth16 parameters in tests
+    config.sync.warm_up_verifiers = false;
+
+    // Convert to a `toml::Value` first, so tables are written after values
+    let config = toml::Value::try_from(config)?;
+    fs::write(dir.path().join("zebrad.toml"), toml::to_string(&config)?)?;
+
+    Ok(dir)
+}
+
+/// Returns a command that runs `zebrad` with `args`, using the config in `dir`.
+fn zebrad_cmd(dir: &Path, args: &[&str]) -> std::process::Command {
+    let mut cmd = test_cmd(env!("CARGO_BIN_EXE_zebrad"), dir);
+    cmd.arg("-c").arg(dir.join("zebrad.toml")).args(args);
+    cmd
+}
+
+#[test]
+fn generate_config() -> Result<()> {
+    zebra_test::init();
+    let dir = testdir(&[])?;
+
+    let output = zebrad_cmd(dir.path(), &["generate"]).output_checked()?;
+    output.assert_success()?;
+    output.stdout_contains("[network]")?;
+    output.stdout_contains("[state]")?;
+
+    Ok(())
+}
+
+#[test]
+fn rpc_schema() -> Result<()> {
+    zebra_test::init();
+    let dir = testdir(&[])?;
+
+    let output = zebrad_cmd(dir.path(), &["rpc-schema"]).output_checked()?;
+    output.assert_success()?;
+    output.stdout_contains(r#""openapi": "3.1.0""#)?;
+    output.stdout_contains(r#""/getblock""#)?;
+
+    Ok(())
+}
+
+#[test]
+fn tip_height_on_empty_cache_fails() -> Result<()> {
+    zebra_test::init();
+    let dir = testdir(&[])?;
+
+    let output = zebrad_cmd(dir.path(), &["tip-height"]).output_checked()?;
+    assert!(
+        !output.output.status.success(),
+        "tip-height should fail when there is no state"
+    );
+    output.stderr_contains("there is no state")?;
+
+    Ok(())
+}
+
+#[test]
+fn start_handshakes_and_syncs_from_fake_peer() -> Result<()> {
+    zebra_test::init();
+
+    let peer = FakePeer::spawn(MAINNET_MAGIC, Script::handshake(0))?;
+    let dir = testdir(&[peer.addr().to_string()])?;
+
+    let child = zebrad_cmd(dir.path(), &["start"]).spawn_checked()?;
+
+    // After the handshake, the syncer asks for blocks after the genesis block
+    let got_getblocks = peer.wait_for_command("getblocks", FAKE_PEER_TIMEOUT);
+    let output = child.kill()?;
+
+    assert!(
+        got_getblocks,
+        "zebrad did not request blocks from the fake peer.\nreceived: {:?}\nstdout:\n{}\nstderr:\n{}",
+        peer.received_commands(),
+        output.stdout(),
+        output.stderr(),
+    );
+    assert_eq!(
+        peer.received_commands().first().map(String::as_str),
+        Some("version"),
+        "zebrad should start the handshake with a version message"
+    );
+
+    Ok(())
+}